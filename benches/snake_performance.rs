@@ -23,10 +23,12 @@ fn benchmark_position_validation(c: &mut Criterion) {
         .flat_map(|x| (0..15).map(move |y| Position::new(x, y)))
         .collect::<Vec<_>>();
 
+    let config = GameConfig::default();
+
     c.bench_function("position_validation", |b| {
         b.iter(|| {
             for pos in &positions {
-                black_box(pos.is_valid());
+                black_box(config.contains(*pos));
             }
         })
     });
@@ -104,15 +106,59 @@ fn benchmark_collision_detection(c: &mut Criterion) {
             .collect::<Vec<_>>();
 
         let game = GameState {
-            snake,
-            direction: Direction::Right,
-            next_direction: Direction::Right,
-            food: Position::new(5, 5),
-            score: 0,
+            run: Run {
+                snake,
+                direction: Direction::Right,
+                next_direction: Direction::Right,
+                food: Food::normal(Position::new(5, 5)),
+                score: 0,
+                game_over: false,
+                speed: Speed::START,
+                clock: TickClock::default(),
+                elapsed: 0.0,
+                weather: Weather::Clear,
+                slippery_cells: Vec::new(),
+                ice: false,
+                grip_cells: Vec::new(),
+                walls: Vec::new(),
+                coop: false,
+                turn_queue: Vec::new(),
+                handicap: Handicap::default(),
+                food_policy: FoodPolicy::default(),
+                food_mix: FoodMix::default(),
+                hazards: Spawner::rain(),
+                spawns: Spawns::default(),
+                grace_left: GracePeriod::Off,
+                turn_cooldown: 0.0,
+                turned_at: None,
+                rng: rand::SeedableRng::seed_from_u64(0),
+                seed: 0,
+                config: GameConfig::default(),
+            },
             high_score: 0,
-            game_over: false,
-            game_speed: 0.2,
-            last_update: 0.0,
+            high_score_checked: true,
+            best_time: 0.0,
+            anim_time: 0.0,
+            screen: Screen::Playing,
+            screen_time: 0.0,
+            menu: MainMenu::default(),
+            settings: Settings::default(),
+            transition: None,
+            reduced_motion: false,
+            theme: Theme::default(),
+            photosensitive: false,
+            food_pulse: 0.0,
+            levels: None,
+            toast: None,
+            packs: Vec::new(),
+            haptics: true,
+            haptic_intensity: DEFAULT_HAPTIC_INTENSITY,
+            notify_best: false,
+            show_hud: true,
+            key_repeat: KeyRepeat::default(),
+            mixer: Mixer::default(),
+            text_cache: TextCache::default(),
+            storage: std::sync::Arc::new(InMemoryStorage::default()),
         };
 
         let test_positions = vec![
@@ -145,15 +191,59 @@ fn benchmark_snake_movement(c: &mut Criterion) {
             .collect::<Vec<_>>();
 
         let game = GameState {
-            snake,
-            direction: Direction::Right,
-            next_direction: Direction::Right,
-            food: Position::new(0, 0), // Place food away from snake
-            score: 0,
+            run: Run {
+                snake,
+                direction: Direction::Right,
+                next_direction: Direction::Right,
+                food: Food::normal(Position::new(0, 0)), // Place food away from snake
+                score: 0,
+                game_over: false,
+                speed: Speed::START,
+                clock: TickClock::default(),
+                elapsed: 0.0,
+                weather: Weather::Clear,
+                slippery_cells: Vec::new(),
+                ice: false,
+                grip_cells: Vec::new(),
+                walls: Vec::new(),
+                coop: false,
+                turn_queue: Vec::new(),
+                handicap: Handicap::default(),
+                food_policy: FoodPolicy::default(),
+                food_mix: FoodMix::default(),
+                hazards: Spawner::rain(),
+                spawns: Spawns::default(),
+                grace_left: GracePeriod::Off,
+                turn_cooldown: 0.0,
+                turned_at: None,
+                rng: rand::SeedableRng::seed_from_u64(0),
+                seed: 0,
+                config: GameConfig::default(),
+            },
             high_score: 0,
-            game_over: false,
-            game_speed: 0.2,
-            last_update: 0.0,
+            high_score_checked: true,
+            best_time: 0.0,
+            anim_time: 0.0,
+            screen: Screen::Playing,
+            screen_time: 0.0,
+            menu: MainMenu::default(),
+            settings: Settings::default(),
+            transition: None,
+            reduced_motion: false,
+            theme: Theme::default(),
+            photosensitive: false,
+            food_pulse: 0.0,
+            levels: None,
+            toast: None,
+            packs: Vec::new(),
+            haptics: true,
+            haptic_intensity: DEFAULT_HAPTIC_INTENSITY,
+            notify_best: false,
+            show_hud: true,
+            key_repeat: KeyRepeat::default(),
+            mixer: Mixer::default(),
+            text_cache: TextCache::default(),
+            storage: std::sync::Arc::new(InMemoryStorage::default()),
         };
 
         group.bench_with_input(
@@ -188,22 +278,66 @@ fn benchmark_snake_growth(c: &mut Criterion) {
             |b, snake| {
                 b.iter(|| {
                     let mut game = GameState {
-                        snake: snake.clone(),
-                        direction: Direction::Right,
-                        next_direction: Direction::Right,
-                        food: Position::new(0, 0), // Place food away from snake
-                        score: 0,
+                        run: Run {
+                            snake: snake.clone(),
+                            direction: Direction::Right,
+                            next_direction: Direction::Right,
+                            food: Food::normal(Position::new(0, 0)), // Place food away from snake
+                            score: 0,
+                            game_over: false,
+                            speed: Speed::START,
+                            clock: TickClock::default(),
+                            elapsed: 0.0,
+                            weather: Weather::Clear,
+                            slippery_cells: Vec::new(),
+                            ice: false,
+                            grip_cells: Vec::new(),
+                            walls: Vec::new(),
+                            coop: false,
+                            turn_queue: Vec::new(),
+                            handicap: Handicap::default(),
+                            food_policy: FoodPolicy::default(),
+                            food_mix: FoodMix::default(),
+                            hazards: Spawner::rain(),
+                            spawns: Spawns::default(),
+                            grace_left: GracePeriod::Off,
+                            turn_cooldown: 0.0,
+                            turned_at: None,
+                            rng: rand::SeedableRng::seed_from_u64(0),
+                            seed: 0,
+                            config: GameConfig::default(),
+                        },
                         high_score: 0,
-                        game_over: false,
-                        game_speed: 0.2,
-                        last_update: 0.0,
+                        high_score_checked: true,
+                        best_time: 0.0,
+                        anim_time: 0.0,
+                        screen: Screen::Playing,
+                        screen_time: 0.0,
+                        menu: MainMenu::default(),
+                        settings: Settings::default(),
+                        transition: None,
+                        reduced_motion: false,
+                        theme: Theme::default(),
+                        photosensitive: false,
+                        food_pulse: 0.0,
+                        levels: None,
+                        toast: None,
+                        packs: Vec::new(),
+                        haptics: true,
+                        haptic_intensity: DEFAULT_HAPTIC_INTENSITY,
+                        notify_best: false,
+                        show_hud: true,
+                        key_repeat: KeyRepeat::default(),
+                        mixer: Mixer::default(),
+                        text_cache: TextCache::default(),
+                        storage: std::sync::Arc::new(InMemoryStorage::default()),
                     };
 
                     // Simulate snake eating food and growing
                     for _ in 0..5 {
                         let head = game.snake[0];
                         let food_pos = head.move_in_direction(game.direction);
-                        game.food = food_pos;
+                        game.food = Food::normal(food_pos);
                         game.move_snake();
                     }
 
@@ -291,15 +425,59 @@ fn benchmark_edge_case_scenarios(c: &mut Criterion) {
                     Position::new(head_pos.x, head_pos.y + 2),
                 ];
                 let mut game = GameState {
-                    snake,
-                    direction,
-                    next_direction: direction,
-                    food: Position::new(5, 5),
-                    score: 0,
+                    run: Run {
+                        snake,
+                        direction,
+                        next_direction: direction,
+                        food: Food::normal(Position::new(5, 5)),
+                        score: 0,
+                        game_over: false,
+                        speed: Speed::START,
+                        clock: TickClock::default(),
+                        elapsed: 0.0,
+                        weather: Weather::Clear,
+                        slippery_cells: Vec::new(),
+                        ice: false,
+                        grip_cells: Vec::new(),
+                        walls: Vec::new(),
+                        coop: false,
+                        turn_queue: Vec::new(),
+                        handicap: Handicap::default(),
+                        food_policy: FoodPolicy::default(),
+                        food_mix: FoodMix::default(),
+                        hazards: Spawner::rain(),
+                        spawns: Spawns::default(),
+                        grace_left: GracePeriod::Off,
+                        turn_cooldown: 0.0,
+                        turned_at: None,
+                        rng: rand::SeedableRng::seed_from_u64(0),
+                        seed: 0,
+                        config: GameConfig::default(),
+                    },
                     high_score: 0,
-                    game_over: false,
-                    game_speed: 0.2,
-                    last_update: 0.0,
+                    high_score_checked: true,
+                    best_time: 0.0,
+                    anim_time: 0.0,
+                    screen: Screen::Playing,
+                    screen_time: 0.0,
+                    menu: MainMenu::default(),
+                    settings: Settings::default(),
+                    transition: None,
+                    reduced_motion: false,
+                    theme: Theme::default(),
+                    photosensitive: false,
+                    food_pulse: 0.0,
+                    levels: None,
+                    toast: None,
+                    packs: Vec::new(),
+                    haptics: true,
+                    haptic_intensity: DEFAULT_HAPTIC_INTENSITY,
+                    notify_best: false,
+                    show_hud: true,
+                    key_repeat: KeyRepeat::default(),
+                    mixer: Mixer::default(),
+                    text_cache: TextCache::default(),
+                    storage: std::sync::Arc::new(InMemoryStorage::default()),
                 };
 
                 game.move_snake();
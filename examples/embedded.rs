@@ -0,0 +1,54 @@
+// A tiny "launcher" that hosts the snake game in one corner of its own window.
+// cargo run --example embedded
+use create_rust_snake_game::{EmbeddedSnake, SnakeGamePlugin};
+use ggez::event::{self, EventHandler};
+use ggez::graphics::{self, Color, Rect, Text};
+use ggez::input::keyboard::{KeyCode, KeyInput};
+use ggez::{Context, ContextBuilder, GameResult};
+
+struct Launcher {
+    snake: EmbeddedSnake,
+}
+
+impl EventHandler for Launcher {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        self.snake.update(ctx)
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas = graphics::Canvas::from_frame(ctx, Color::new(0.1, 0.1, 0.2, 1.0));
+        canvas.draw(
+            &Text::new("Minigames - Tab pauses, Backspace resets"),
+            graphics::DrawParam::default().dest([20.0, 20.0]),
+        );
+        self.snake
+            .draw_in(ctx, &mut canvas, Rect::new(200.0, 80.0, 400.0, 300.0))?;
+        canvas.finish(ctx)
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        key_input: KeyInput,
+        _repeat: bool,
+    ) -> GameResult {
+        match key_input.keycode {
+            Some(KeyCode::Tab) if self.snake.is_paused() => self.snake.resume(),
+            Some(KeyCode::Tab) => self.snake.pause(),
+            Some(KeyCode::Back) => self.snake.reset(),
+            Some(KeyCode::Escape) => ctx.request_quit(),
+            _ => self.snake.key_down(ctx, key_input)?,
+        }
+        Ok(())
+    }
+}
+
+fn main() -> GameResult {
+    let (ctx, event_loop) = ContextBuilder::new("snake_launcher", "ben!")
+        .window_mode(ggez::conf::WindowMode::default().dimensions(800.0, 450.0))
+        .build()?;
+
+    let mut snake = EmbeddedSnake::default();
+    snake.start();
+    event::run(ctx, event_loop, Launcher { snake })
+}
@@ -0,0 +1,13 @@
+use create_rust_snake_game::{run_relay, RELAY_PORT};
+
+// Relay server for online play: `relay [port]`, defaults to RELAY_PORT
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let port = match std::env::args().nth(1) {
+        Some(port) => port.parse()?,
+        None => RELAY_PORT,
+    };
+    let listener = std::net::TcpListener::bind(("0.0.0.0", port))?;
+    println!("Snake relay listening on port {}", port);
+    run_relay(listener)?;
+    Ok(())
+}
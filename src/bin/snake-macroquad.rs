@@ -0,0 +1,239 @@
+// A second, lighter frontend on macroquad - the same core::Run and rules as the ggez
+// game, its own window, input and drawing, and none of ggez. cargo run --features
+// macroquad --bin snake-macroquad, or build it for wasm32-unknown-unknown for the web.
+// macroquad also builds for phones (cargo-quad-apk for Android, xcode for iOS), so this
+// one takes swipes and the on-screen arrows too, and saves the run when it's put away
+use create_rust_snake_game::{Direction, FoodKind, GameConfig, Run, TickClock, TouchControls};
+use macroquad::prelude::*;
+
+// A frame this long means the app was in the background (macroquad doesn't pass on the
+// pause and resume events), so hold the run until the player is back
+const SUSPEND_GAP: f32 = 1.0;
+
+// The ggez game keeps its suspended run for the default board in the same file, so either
+// frontend can pick up where the other left off
+#[cfg(not(target_arch = "wasm32"))]
+const SUSPEND_FILE: &str = "suspended.txt";
+
+// The classic theme's colors
+const SNAKE_HEAD: Color = Color::new(0.0, 1.0, 0.0, 1.0);
+const SNAKE_TAIL: Color = Color::new(0.0, 0.45, 0.2, 1.0);
+
+fn window_conf() -> Conf {
+    let config = GameConfig::default();
+    Conf {
+        window_title: "Super Sick Snake Game".to_string(),
+        window_width: config.screen_width() as i32,
+        window_height: config.screen_height() as i32,
+        window_resizable: false,
+        ..Default::default()
+    }
+}
+
+// A new seed every run, from the clock - there's no std RNG without the ggez game
+fn seed() -> u64 {
+    (macroquad::miniquad::date::now() * 1000.0) as u64
+}
+
+fn segment_color(index: usize, length: usize) -> Color {
+    let t = if length > 1 {
+        index as f32 / (length - 1) as f32
+    } else {
+        0.0
+    };
+    Color::new(
+        SNAKE_HEAD.r + (SNAKE_TAIL.r - SNAKE_HEAD.r) * t,
+        SNAKE_HEAD.g + (SNAKE_TAIL.g - SNAKE_HEAD.g) * t,
+        SNAKE_HEAD.b + (SNAKE_TAIL.b - SNAKE_HEAD.b) * t,
+        1.0,
+    )
+}
+
+fn food_color(kind: FoodKind) -> Color {
+    match kind {
+        FoodKind::Normal => RED,
+        FoodKind::Golden => Color::new(1.0, 0.84, 0.0, 1.0),
+        FoodKind::SlowDown => Color::new(0.35, 0.75, 1.0, 1.0),
+        FoodKind::Shrink => Color::new(0.75, 0.4, 1.0, 1.0),
+        FoodKind::Poison => Color::new(0.45, 0.6, 0.1, 1.0),
+    }
+}
+
+fn draw_cell(config: &GameConfig, x: i32, y: i32, fill: Color) {
+    draw_rectangle(
+        x as f32 * config.cell_size,
+        y as f32 * config.cell_size,
+        config.cell_size - 2.0,
+        config.cell_size - 2.0,
+        fill,
+    );
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn suspend_path() -> Option<std::path::PathBuf> {
+    let dirs = directories::ProjectDirs::from("", "", "snake_game")?;
+    Some(dirs.data_dir().join(SUSPEND_FILE))
+}
+
+// Save the run if it's still going, or clear out an older save if it's over
+#[cfg(not(target_arch = "wasm32"))]
+fn suspend(game: &Run) {
+    let Some(path) = suspend_path() else {
+        return;
+    };
+    if game.game_over {
+        let _ = std::fs::remove_file(path);
+        return;
+    }
+    let saved = std::fs::create_dir_all(path.parent().unwrap_or(&path))
+        .and_then(|_| std::fs::write(&path, game.suspend_text()));
+    if let Err(e) = saved {
+        eprintln!("Failed to save suspended run: {}", e);
+    }
+}
+
+// Carry on from a suspended run if there is one, removing the save either way
+#[cfg(not(target_arch = "wasm32"))]
+fn resume_suspended(game: &mut Run) -> bool {
+    let Some(path) = suspend_path() else {
+        return false;
+    };
+    let Ok(text) = std::fs::read_to_string(&path) else {
+        return false;
+    };
+    let _ = std::fs::remove_file(path);
+    game.resume_text(&text)
+}
+
+// A page has nowhere to keep files, and closing the tab is the end of the run anyway
+#[cfg(target_arch = "wasm32")]
+fn suspend(_game: &Run) {}
+
+#[cfg(target_arch = "wasm32")]
+fn resume_suspended(_game: &mut Run) -> bool {
+    false
+}
+
+#[macroquad::main(window_conf)]
+async fn main() {
+    let config = GameConfig::default();
+    let mut game = Run::new(config, seed());
+    let mut paused = resume_suspended(&mut game);
+    let mut touch = TouchControls::default();
+    let mut clock = TickClock::default();
+    prevent_quit(); // save the run on the way out
+    let keys = [
+        (KeyCode::Up, Direction::Up),
+        (KeyCode::W, Direction::Up),
+        (KeyCode::Down, Direction::Down),
+        (KeyCode::S, Direction::Down),
+        (KeyCode::Left, Direction::Left),
+        (KeyCode::A, Direction::Left),
+        (KeyCode::Right, Direction::Right),
+        (KeyCode::D, Direction::Right),
+    ];
+
+    loop {
+        if is_key_pressed(KeyCode::Escape) || is_quit_requested() {
+            suspend(&game);
+            break;
+        }
+        if get_frame_time() > SUSPEND_GAP && !game.game_over {
+            paused = true;
+        }
+        // the board is drawn at its normal size in the top left, so touches are in board
+        // coordinates already
+        let mut tapped = false;
+        let mut turn = None;
+        for finger in touches() {
+            match finger.phase {
+                TouchPhase::Started if paused => paused = false,
+                TouchPhase::Started => {
+                    turn = turn.or(touch.press(finger.position.x, finger.position.y, &config))
+                }
+                TouchPhase::Ended => {
+                    tapped = true;
+                    turn = turn.or(touch.release(finger.position.x, finger.position.y));
+                }
+                _ => {}
+            }
+        }
+        if paused {
+            if get_last_key_pressed().is_some() {
+                paused = false;
+            }
+        } else if is_key_pressed(KeyCode::R) || (tapped && game.game_over) {
+            game = Run::new(config, seed());
+            clock = TickClock::default();
+        }
+        for (key, direction) in keys {
+            if is_key_pressed(key) {
+                turn = Some(direction);
+            }
+        }
+        if let Some(direction) = turn.filter(|_| !paused && !game.game_over) {
+            game.handle_input(direction);
+        }
+
+        if !game.game_over && !paused {
+            let delta = std::time::Duration::from_secs_f32(get_frame_time());
+            game.elapsed += delta.as_secs_f64();
+            if clock.advance(delta, game.speed) {
+                let score_before = game.score;
+                game.step();
+                // the OS can close a backgrounded app without warning, so keep the save
+                // fresh - food is the only progress worth keeping
+                if game.score > score_before {
+                    suspend(&game);
+                }
+            }
+        }
+
+        clear_background(BLACK);
+        for (index, segment) in game.snake.iter().enumerate() {
+            if game.is_visible(*segment) {
+                let fill = segment_color(index, game.snake.len());
+                draw_cell(&config, segment.x, segment.y, fill);
+            }
+        }
+        let food = game.food.position;
+        if game.is_visible(food) {
+            draw_cell(&config, food.x, food.y, food_color(game.food.kind));
+        }
+        if touch.visible {
+            for (_, button) in TouchControls::buttons(&config) {
+                draw_rectangle(
+                    button.x,
+                    button.y,
+                    button.w,
+                    button.h,
+                    Color::new(1.0, 1.0, 1.0, 0.15),
+                );
+            }
+        }
+        draw_text(&format!("Score: {}", game.score), 10.0, 24.0, 24.0, WHITE);
+        if paused {
+            let message = "PAUSED - tap or press a key to carry on";
+            let size = measure_text(message, None, 28, 1.0);
+            draw_text(
+                message,
+                (screen_width() - size.width) / 2.0,
+                screen_height() / 2.0,
+                28.0,
+                WHITE,
+            );
+        } else if game.game_over {
+            let message = format!("GAME OVER - {} points, R or tap to restart", game.score);
+            let size = measure_text(&message, None, 28, 1.0);
+            draw_text(
+                &message,
+                (screen_width() - size.width) / 2.0,
+                screen_height() / 2.0,
+                28.0,
+                RED,
+            );
+        }
+
+        next_frame().await
+    }
+}
@@ -0,0 +1,116 @@
+// A bare framebuffer frontend on the `pixels` crate - the board is rasterized into a small
+// pixel buffer and scaled up to the window, which suits kiosks and big boards.
+// cargo run --features pixels --bin snake-pixels
+use create_rust_snake_game::{rasterize, Direction, GameState, TickClock};
+use ggez::winit::dpi::LogicalSize;
+use ggez::winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use ggez::winit::event_loop::{ControlFlow, EventLoop};
+use ggez::winit::window::WindowBuilder;
+use pixels::{Pixels, SurfaceTexture};
+use std::time::Instant;
+
+// Pixels per cell in the buffer - the window scales it up
+const CELL_PIXELS: usize = 8;
+const WINDOW_SCALE: u32 = 4;
+
+fn direction_for(key: VirtualKeyCode) -> Option<Direction> {
+    match key {
+        VirtualKeyCode::Up | VirtualKeyCode::W => Some(Direction::Up),
+        VirtualKeyCode::Down | VirtualKeyCode::S => Some(Direction::Down),
+        VirtualKeyCode::Left | VirtualKeyCode::A => Some(Direction::Left),
+        VirtualKeyCode::Right | VirtualKeyCode::D => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut game = GameState::new();
+    let (columns, rows) = game.config.grid();
+    let width = (columns * CELL_PIXELS) as u32;
+    let height = (rows * CELL_PIXELS) as u32;
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Super Sick Snake Game")
+        .with_inner_size(LogicalSize::new(
+            width * WINDOW_SCALE,
+            height * WINDOW_SCALE,
+        ))
+        .build(&event_loop)?;
+    let size = window.inner_size();
+    let mut pixels = Pixels::new(
+        width,
+        height,
+        SurfaceTexture::new(size.width, size.height, &window),
+    )?;
+
+    let mut clock = TickClock::default();
+    let mut last_frame = Instant::now();
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                if let Err(e) = pixels.resize_surface(size.width, size.height) {
+                    eprintln!("Couldn't resize: {}", e);
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => match key {
+                VirtualKeyCode::Escape => *control_flow = ControlFlow::Exit,
+                VirtualKeyCode::R => {
+                    game.restart();
+                    game.skip_transition(); // no transitions in this frontend
+                    clock = TickClock::default();
+                }
+                _ if !game.game_over => {
+                    if let Some(direction) = direction_for(key) {
+                        game.handle_input(direction);
+                    }
+                }
+                _ => {}
+            },
+            Event::MainEventsCleared => {
+                let delta = last_frame.elapsed();
+                last_frame = Instant::now();
+                if !game.game_over {
+                    game.elapsed += delta.as_secs_f64();
+                    if clock.advance(delta, game.speed) {
+                        game.step();
+                        window.set_title(&if game.game_over {
+                            format!("GAME OVER - {} points, R to restart", game.score)
+                        } else {
+                            format!("Super Sick Snake Game - Score: {}", game.score)
+                        });
+                    }
+                }
+                window.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                rasterize(&game, pixels.frame_mut(), CELL_PIXELS);
+                if let Err(e) = pixels.render() {
+                    eprintln!("Couldn't draw: {}", e);
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+            _ => {}
+        }
+    });
+}
@@ -0,0 +1,18 @@
+// The screensaver on its own, for installing as the system screensaver. Understands the
+// arguments screensaver hosts pass: Windows' /s, /c and /p (rename the .exe to .scr),
+// and xscreensaver's -root and -window-id
+use create_rust_snake_game::run_screensaver;
+
+fn main() -> ggez::GameResult {
+    let option = std::env::args().nth(1).unwrap_or_default().to_lowercase();
+    // Windows may glue the window handle on, like "/p:1234"
+    match option.split(':').next().unwrap_or_default() {
+        "/c" => {
+            println!("Super Sick Snake Game has no screensaver settings");
+            Ok(())
+        }
+        // there's no drawing into someone else's window, so no preview
+        "/p" | "-window-id" => Ok(()),
+        _ => run_screensaver(),
+    }
+}
@@ -0,0 +1,168 @@
+// An SDL2 frontend for machines where ggez can't get a wgpu device, like a Raspberry Pi
+// or an old GPU. cargo run --features sdl2 --bin snake-sdl2
+use create_rust_snake_game::{Direction, GameState, TickClock, CELL_SIZE, GRID_HEIGHT, GRID_WIDTH};
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use std::time::Instant;
+
+const SAMPLE_RATE: i32 = 44_100;
+
+// ggez colors are floats, SDL wants bytes
+fn color(color: ggez::graphics::Color) -> Color {
+    let (r, g, b) = color.to_rgb();
+    Color::RGB(r, g, b)
+}
+
+fn cell(x: i32, y: i32) -> Rect {
+    let size = CELL_SIZE as u32 - 2;
+    Rect::new(x * CELL_SIZE as i32, y * CELL_SIZE as i32, size, size)
+}
+
+// A short square wave blip - there's no sound in the game yet, this is just feedback
+fn beep(audio: &Option<AudioQueue<i16>>, frequency: f32, seconds: f32) {
+    let Some(queue) = audio else {
+        return;
+    };
+    let period = SAMPLE_RATE as f32 / frequency;
+    let samples: Vec<i16> = (0..(SAMPLE_RATE as f32 * seconds) as usize)
+        .map(|i| {
+            if (i as f32 % period) < period / 2.0 {
+                3000
+            } else {
+                -3000
+            }
+        })
+        .collect();
+    if let Err(e) = queue.queue_audio(&samples) {
+        eprintln!("Couldn't play sound: {}", e);
+    }
+}
+
+fn direction_for(keycode: Keycode) -> Option<Direction> {
+    match keycode {
+        Keycode::Up | Keycode::W => Some(Direction::Up),
+        Keycode::Down | Keycode::S => Some(Direction::Down),
+        Keycode::Left | Keycode::A => Some(Direction::Left),
+        Keycode::Right | Keycode::D => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+fn main() -> Result<(), String> {
+    let sdl = sdl2::init()?;
+    let window = sdl
+        .video()?
+        .window(
+            "Super Sick Snake Game",
+            (GRID_WIDTH as f32 * CELL_SIZE) as u32,
+            (GRID_HEIGHT as f32 * CELL_SIZE) as u32,
+        )
+        .position_centered()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut canvas = window
+        .into_canvas()
+        .present_vsync()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut events = sdl.event_pump()?;
+
+    // carry on without sound if there's no audio device
+    let spec = AudioSpecDesired {
+        freq: Some(SAMPLE_RATE),
+        channels: Some(1),
+        samples: None,
+    };
+    let audio = match sdl.audio().and_then(|audio| audio.open_queue(None, &spec)) {
+        Ok(queue) => {
+            queue.resume();
+            Some(queue)
+        }
+        Err(e) => {
+            eprintln!("No sound: {}", e);
+            None
+        }
+    };
+
+    let mut game = GameState::new();
+    let mut clock = TickClock::default();
+    let mut last_frame = Instant::now();
+    'running: loop {
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'running,
+                // held keys repeat, but a held R is one restart and a held arrow one turn
+                Event::KeyDown {
+                    keycode: Some(Keycode::R),
+                    repeat: false,
+                    ..
+                } => {
+                    game.restart();
+                    game.skip_transition(); // no transitions in this frontend
+                    clock = TickClock::default();
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } if !game.game_over => {
+                    if let Some(direction) = direction_for(keycode) {
+                        game.handle_input(direction);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let delta = last_frame.elapsed();
+        last_frame = Instant::now();
+        if !game.game_over {
+            game.elapsed += delta.as_secs_f64();
+            if clock.advance(delta, game.speed) {
+                let score_before = game.score;
+                game.step();
+                if game.game_over {
+                    beep(&audio, 110.0, 0.4);
+                } else if game.score > score_before {
+                    beep(&audio, 880.0, 0.08);
+                }
+            }
+        }
+
+        canvas.set_draw_color(color(game.theme.background_at(game.elapsed)));
+        canvas.clear();
+        for (index, segment) in game.snake.iter().enumerate() {
+            if game.is_visible(*segment) {
+                canvas.set_draw_color(color(game.theme.segment_color(index, game.snake.len())));
+                canvas.fill_rect(cell(segment.x, segment.y))?;
+            }
+        }
+        let food = game.food.position;
+        if game.is_visible(food) {
+            canvas.set_draw_color(color(game.food.kind.color(&game.theme)));
+            canvas.fill_rect(cell(food.x, food.y))?;
+        }
+        canvas.present();
+
+        // no text without SDL_ttf, so the score goes in the title bar
+        let title = if game.game_over {
+            format!("GAME OVER - {} points, R to restart", game.score)
+        } else {
+            format!("Super Sick Snake Game - Score: {}", game.score)
+        };
+        if canvas.window().title() != title {
+            canvas
+                .window_mut()
+                .set_title(&title)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+// Headless frontend for wall displays: an LED matrix over serial or a Linux framebuffer,
+// steered by a USB keypad or GPIO buttons.
+//   snake-wall --adalight /dev/ttyUSB0 32x24 [--serpentine] --input /dev/input/event0
+//   snake-wall --fb /dev/fb0 --input /dev/input/event0 [--dirty]
+use create_rust_snake_game::{
+    fit_cell_size, parse_input_event, rasterize, AdalightMatrix, DirtyRaster, DisplayBackend,
+    Framebuffer, GameState, PanelInput, TickClock, INPUT_EVENT_SIZE,
+};
+use std::io::Read;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+const FRAME_TIME: Duration = Duration::from_millis(33);
+
+fn value_after<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|index| args.get(index + 1))
+        .map(String::as_str)
+}
+
+fn open_display(args: &[String]) -> Result<Box<dyn DisplayBackend>, Box<dyn std::error::Error>> {
+    if let Some(port) = value_after(args, "--adalight") {
+        // set the baud rate first, e.g. `stty -F /dev/ttyUSB0 115200 raw`
+        let size = args
+            .iter()
+            .skip_while(|arg| *arg != "--adalight")
+            .nth(2)
+            .ok_or("--adalight needs a port and a size like 32x24")?;
+        let (width, height) = size
+            .split_once('x')
+            .ok_or("matrix size should look like 32x24")?;
+        let output = std::fs::OpenOptions::new().write(true).open(port)?;
+        let mut matrix = AdalightMatrix::new(output, width.parse()?, height.parse()?);
+        matrix.serpentine = args.iter().any(|arg| arg == "--serpentine");
+        return Ok(Box::new(matrix));
+    }
+    let device = value_after(args, "--fb").unwrap_or("/dev/fb0");
+    Ok(Box::new(Framebuffer::open(device)?))
+}
+
+// Each input device gets a thread that turns its key presses into PanelInputs
+fn watch_input(device: &str, inputs: mpsc::Sender<PanelInput>) -> std::io::Result<()> {
+    let mut file = std::fs::File::open(device)?;
+    std::thread::spawn(move || {
+        let mut event = [0; INPUT_EVENT_SIZE];
+        while file.read_exact(&mut event).is_ok() {
+            if let Some(input) = parse_input_event(&event) {
+                if inputs.send(input).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let mut display = open_display(&args)?;
+
+    let (sender, inputs) = mpsc::channel();
+    for (index, arg) in args.iter().enumerate() {
+        if arg == "--input" {
+            let device = args.get(index + 1).ok_or("--input needs a device")?;
+            watch_input(device, sender.clone())?;
+        }
+    }
+
+    let mut game = GameState::new();
+    let cell_size = fit_cell_size(&game.config, display.size());
+    let (columns, rows) = game.config.grid();
+    let (width, height) = (columns * cell_size, rows * cell_size);
+    let mut frame = vec![0; width * height * 4];
+    // only send the cells that changed, for slow displays
+    let mut dirty = args
+        .iter()
+        .any(|arg| arg == "--dirty")
+        .then(|| DirtyRaster::new(&game.config, cell_size));
+    let mut clock = TickClock::default();
+    let mut last_frame = Instant::now();
+    loop {
+        for input in inputs.try_iter() {
+            match input {
+                PanelInput::Steer(direction) if !game.game_over => game.handle_input(direction),
+                PanelInput::Steer(_) => {}
+                PanelInput::Restart => {
+                    game.restart();
+                    game.skip_transition(); // no transitions on a wall
+                    clock = TickClock::default();
+                }
+                PanelInput::Quit => return Ok(()),
+            }
+        }
+
+        let delta = last_frame.elapsed();
+        last_frame = Instant::now();
+        if !game.game_over {
+            game.elapsed += delta.as_secs_f64();
+            if clock.advance(delta, game.speed) {
+                game.step();
+            }
+        }
+
+        if let Some(raster) = &mut dirty {
+            let cells = raster.update(&game);
+            display.show_cells(&raster.frame, (width, height), cell_size, &cells)?;
+        } else {
+            rasterize(&game, &mut frame, cell_size);
+            display.show(&frame, width, height)?;
+        }
+        std::thread::sleep(FRAME_TIME.saturating_sub(last_frame.elapsed()));
+    }
+}
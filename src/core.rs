@@ -0,0 +1,1514 @@
+//! The game without a frontend: a run's snake, food, score, speed and RNG, and what a
+//! tick and a steer do to them, on top of [`crate::rules`]. No ggez, no files and no std -
+//! the ggez game in `game` keeps a [`Run`] and adds the screens, records and sound around
+//! it, and any other frontend can drive one directly.
+
+use crate::rules::{
+    free_cell, move_snake, FoodPolicy, FoodSpawner, GameConfig, Move, RulesRng, Speed, TickClock,
+};
+use crate::rules::{Direction, Position};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+// Optional modifiers that change how a run plays
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Weather {
+    Clear,
+    Rain,                // random cells turn slippery: turning on one applies a tick late
+    Fog { radius: i32 }, // only cells within `radius` of the head are drawn
+}
+
+// Chance per tick that rain makes a new cell slippery, and how many ticks it stays that way
+pub const RAIN_CHANCE: f64 = 0.1;
+pub const SLIPPERY_TICKS: u32 = 15;
+
+// Things that can turn up on the board and get in the snake's way
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hazard {
+    Puddle, // a slippery cell, see Weather::Rain
+}
+
+// Weighted table of things to spawn, rolled every `interval` ticks. Each roll spawns
+// something with probability `chance`, picking from the entries in proportion to their weight
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spawner<T> {
+    pub entries: Vec<(T, u32)>, // item and its weight
+    pub chance: f64,
+    pub interval: u32,
+    pub ticks: u32, // ticks since the last roll
+}
+
+impl<T: Clone> Spawner<T> {
+    pub fn new(chance: f64, interval: u32) -> Self {
+        Self {
+            entries: Vec::new(),
+            chance,
+            interval: interval.max(1),
+            ticks: 0,
+        }
+    }
+
+    pub fn with(mut self, item: T, weight: u32) -> Self {
+        self.entries.push((item, weight));
+        self
+    }
+
+    // Pick an entry by weight, None if the table's empty
+    pub fn pick(&self, rng: &mut impl Rng) -> Option<T> {
+        let total: u32 = self.entries.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = rng.gen_range(0..total);
+        for (item, weight) in &self.entries {
+            if roll < *weight {
+                return Some(item.clone());
+            }
+            roll -= weight;
+        }
+        None
+    }
+
+    // Call once per game tick - returns whatever spawned this tick
+    pub fn tick(&mut self, rng: &mut impl Rng) -> Option<T> {
+        self.ticks += 1;
+        if self.ticks < self.interval {
+            return None;
+        }
+        self.ticks = 0;
+        if rng.gen_bool(self.chance.clamp(0.0, 1.0)) {
+            self.pick(rng)
+        } else {
+            None
+        }
+    }
+}
+
+impl Spawner<Hazard> {
+    // What rain drops on the board
+    pub fn rain() -> Self {
+        Self::new(RAIN_CHANCE, 1).with(Hazard::Puddle, 1)
+    }
+}
+
+// Distance between grip cells in the ice variant
+pub const ICE_GRIP_SPACING: usize = 3;
+
+// Points for eating a piece of food before any handicap
+pub const FOOD_POINTS: u32 = 10;
+
+// Per-player handicap so players of different skill can compete
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Handicap {
+    pub starting_length: Option<usize>, // None keeps the length the run starts with
+    pub speed_offset: f64,              // added to the time between moves, so positive is slower
+    pub score_multiplier: f32,
+}
+
+impl Default for Handicap {
+    fn default() -> Self {
+        Self {
+            starting_length: None,
+            speed_offset: 0.0,
+            score_multiplier: 1.0,
+        }
+    }
+}
+
+impl Handicap {
+    // Points for one piece of food with this handicap
+    pub fn food_points(&self) -> u32 {
+        // adding a half and truncating rounds it - f32::round needs std
+        (FOOD_POINTS as f32 * self.score_multiplier + 0.5) as u32
+    }
+}
+
+// How a run begins: a straight snake of `length` cells with its head on `head`, facing
+// `direction` and trailing away behind it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnakeStart {
+    pub length: usize,
+    pub head: Position,
+    pub direction: Direction,
+}
+
+impl Default for SnakeStart {
+    fn default() -> Self {
+        Self::centered(&GameConfig::default())
+    }
+}
+
+impl SnakeStart {
+    // Three long in the middle of the board, heading right
+    pub fn centered(config: &GameConfig) -> Self {
+        Self {
+            length: 3,
+            head: config.center(),
+            direction: Direction::Right,
+        }
+    }
+
+    // Head first
+    pub fn cells(&self) -> Vec<Position> {
+        let mut cells = vec![self.head];
+        while cells.len() < self.length {
+            let tail = cells[cells.len() - 1];
+            cells.push(tail.move_in_direction(self.direction.opposite()));
+        }
+        cells
+    }
+
+    // The whole snake has to be on the board, with room for its first move
+    pub fn validate(&self, config: &GameConfig) -> Result<(), String> {
+        if self.length == 0 {
+            return Err("length has to be at least 1".to_string());
+        }
+        if let Some(cell) = self
+            .cells()
+            .into_iter()
+            .find(|cell| !config.contains(*cell))
+        {
+            return Err(format!(
+                "a {} long snake at {} runs off the board at {}",
+                self.length,
+                self.head.to_text(),
+                cell.to_text()
+            ));
+        }
+        if !config.contains(config.step(self.head, self.direction)) {
+            return Err(format!(
+                "the snake at {} would crash into the wall on its first move",
+                self.head.to_text()
+            ));
+        }
+        Ok(())
+    }
+}
+
+// A breather at the start of a run, so an awkward spawn or a tiny board can't kill the
+// snake before the player's had a look. The snake holds still (so it can't crash) for
+// a number of ticks or until the first steer - steering always ends it early
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum GracePeriod {
+    #[default]
+    Off,
+    Ticks(u32),
+    UntilInput,
+}
+
+// Where runs can start - one of the candidates is picked at random for each run, so a
+// pack with several doesn't play the same way every time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spawns {
+    pub candidates: Vec<SnakeStart>, // never empty
+    pub grace: GracePeriod,
+}
+
+impl Default for Spawns {
+    fn default() -> Self {
+        SnakeStart::default().into()
+    }
+}
+
+impl From<SnakeStart> for Spawns {
+    fn from(start: SnakeStart) -> Self {
+        Self {
+            candidates: vec![start],
+            grace: GracePeriod::Off,
+        }
+    }
+}
+
+impl Spawns {
+    pub fn pick(&self, rng: &mut impl Rng) -> SnakeStart {
+        self.candidates[rng.gen_range(0..self.candidates.len())]
+    }
+
+    // Same `key = value` lines as a theme file. One start is `length = 5`, `head = 4,7`
+    // and `direction = up`, with anything left out as it is by default. Several are
+    // `spawn = 4,7 up` lines instead of head and direction, all with the same length.
+    // `grace = 3` holds the snake for three ticks, `grace = input` until the first steer.
+    // Whether the starts fit is up to the board they're played on - see `fit`
+    pub fn parse(text: &str) -> Result<Spawns, String> {
+        let mut grace = GracePeriod::Off;
+        let mut single = SnakeStart::default();
+        let mut set_single = false;
+        let mut spawns: Vec<(Position, Direction)> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = match line.find(" # ") {
+                Some(comment) => &line[..comment],
+                None if line.trim_start().starts_with('#') => "",
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fail = |message: String| format!("line {}: {}", number + 1, message);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| fail(format!("expected `key = value`, got `{}`", line)))?;
+            let (key, value) = (key.trim(), value.trim());
+            let cell = |value: &str| {
+                Position::parse(value)
+                    .ok_or_else(|| fail(format!("expected a cell like 4,7, got `{}`", value)))
+            };
+            let direction = |value: &str| {
+                parse_direction_name(value).ok_or_else(|| {
+                    fail(format!("expected up, down, left or right, got `{}`", value))
+                })
+            };
+            match key {
+                "length" => {
+                    single.length = value
+                        .parse()
+                        .map_err(|_| fail(format!("bad length `{}`", value)))?
+                }
+                "head" => {
+                    single.head = cell(value)?;
+                    set_single = true;
+                }
+                "direction" => {
+                    single.direction = direction(value)?;
+                    set_single = true;
+                }
+                "grace" => {
+                    grace = match value {
+                        "input" => GracePeriod::UntilInput,
+                        "0" | "off" => GracePeriod::Off,
+                        ticks => GracePeriod::Ticks(ticks.parse().map_err(|_| {
+                            fail(format!(
+                                "expected a number of ticks or `input`, got `{}`",
+                                value
+                            ))
+                        })?),
+                    }
+                }
+                "spawn" => {
+                    let (at, facing) = value.split_once(' ').ok_or_else(|| {
+                        fail(format!(
+                            "expected a cell and a direction like `4,7 up`, got `{}`",
+                            value
+                        ))
+                    })?;
+                    spawns.push((cell(at)?, direction(facing.trim())?));
+                }
+                _ => return Err(fail(format!("unknown key `{}`", key))),
+            }
+        }
+
+        if single.length == 0 {
+            return Err("length has to be at least 1".to_string());
+        }
+        if spawns.is_empty() {
+            return Ok(Spawns {
+                grace,
+                ..single.into()
+            });
+        }
+        if set_single {
+            return Err("use either head and direction or spawn lines, not both".to_string());
+        }
+        let candidates = spawns
+            .into_iter()
+            .map(|(head, direction)| SnakeStart {
+                head,
+                direction,
+                ..single
+            })
+            .collect();
+        Ok(Spawns { candidates, grace })
+    }
+
+    // Every candidate has to be safe on this board, not just the one that happens to get
+    // picked
+    pub fn fit(&self, config: &GameConfig) -> Result<(), String> {
+        self.candidates
+            .iter()
+            .try_for_each(|start| start.validate(config))
+    }
+}
+
+pub(crate) fn parse_direction_name(name: &str) -> Option<Direction> {
+    match name.to_ascii_lowercase().as_str() {
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+// Most co-op turns that can wait for upcoming ticks
+pub const MAX_QUEUED_TURNS: usize = 2;
+
+// How long the turn assist ignores a second turn for, in seconds - shorter than any tick
+pub const TURN_COOLDOWN: f64 = 0.06;
+
+// Golden food and poison rot away after this many ticks if they're left alone
+pub const FOOD_TIMEOUT_TICKS: u32 = 50;
+// Cells shrink food takes off the tail, never leaving the snake shorter than it starts
+pub const SHRINK_CELLS: usize = 3;
+const MIN_SHRINK_LENGTH: usize = 3;
+// Speed steps slow-down food takes back
+const SLOW_DOWN_STEPS: usize = 4;
+
+// What a piece of food does when it's eaten
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FoodKind {
+    #[default]
+    Normal, // points, and the snake grows and speeds up
+    Golden,   // five times the points, but it doesn't stay long
+    SlowDown, // points, and the speed drops back a few steps
+    Shrink,   // points, and the tail gets shorter instead of longer
+    Poison,   // takes points away, and rots away like golden food
+}
+
+impl FoodKind {
+    pub const ALL: [FoodKind; 5] = [
+        FoodKind::Normal,
+        FoodKind::Golden,
+        FoodKind::SlowDown,
+        FoodKind::Shrink,
+        FoodKind::Poison,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FoodKind::Normal => "normal",
+            FoodKind::Golden => "golden",
+            FoodKind::SlowDown => "slow",
+            FoodKind::Shrink => "shrink",
+            FoodKind::Poison => "poison",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == value)
+    }
+
+    // Ticks it lasts on the board, for the kinds that don't wait forever
+    pub fn lifetime(&self) -> Option<u32> {
+        match self {
+            FoodKind::Golden | FoodKind::Poison => Some(FOOD_TIMEOUT_TICKS),
+            _ => None,
+        }
+    }
+}
+
+// The piece of food on the board
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Food {
+    pub position: Position,
+    pub kind: FoodKind,
+    pub ticks_left: Option<u32>, // until it rots away, for the kinds that do
+}
+
+impl Food {
+    pub fn new(position: Position, kind: FoodKind) -> Self {
+        Self {
+            position,
+            kind,
+            ticks_left: kind.lifetime(),
+        }
+    }
+
+    pub fn normal(position: Position) -> Self {
+        Self::new(position, FoodKind::Normal)
+    }
+}
+
+// How likely each kind of food is, as weights in FoodKind::ALL order. Classic games only
+// ever have normal food
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FoodMix {
+    pub weights: [u32; 5],
+}
+
+impl Default for FoodMix {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+impl FoodMix {
+    pub fn classic() -> Self {
+        Self {
+            weights: [1, 0, 0, 0, 0],
+        }
+    }
+
+    // Mostly normal food with the odd special one
+    pub fn mixed() -> Self {
+        Self {
+            weights: [12, 2, 2, 2, 2],
+        }
+    }
+
+    // Classic games don't roll at all, so they keep the same food for a seed as ever
+    pub fn roll(&self, rng: &mut impl Rng) -> FoodKind {
+        if *self == Self::classic() {
+            return FoodKind::Normal;
+        }
+        let total: u32 = self.weights.iter().sum();
+        let mut pick = rng.gen_range(0..total.max(1));
+        for (kind, weight) in FoodKind::ALL.iter().zip(self.weights) {
+            if pick < weight {
+                return *kind;
+            }
+            pick -= weight;
+        }
+        FoodKind::Normal
+    }
+}
+
+// What changed between two game states, from Run::diff
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateDiff {
+    pub segments_added: Vec<Position>, // in the new snake but not the old one
+    pub segments_removed: Vec<Position>, // in the old snake but not the new one
+    pub food_moved: Option<(Position, Position)>,
+    pub score_delta: i64,
+    pub direction_changed: Option<(Direction, Direction)>,
+    pub game_over_changed: Option<bool>, // the new value, if it changed
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// One line per difference, e.g. for a failed assertion or a replay that doesn't match
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no differences");
+        }
+        let cells = |cells: &[Position]| {
+            cells
+                .iter()
+                .map(|cell| format!("({}, {})", cell.x, cell.y))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let mut lines = Vec::new();
+        if !self.segments_added.is_empty() {
+            lines.push(format!("segments added: {}", cells(&self.segments_added)));
+        }
+        if !self.segments_removed.is_empty() {
+            lines.push(format!(
+                "segments removed: {}",
+                cells(&self.segments_removed)
+            ));
+        }
+        if let Some((from, to)) = self.food_moved {
+            lines.push(format!(
+                "food moved: {} -> {}",
+                cells(&[from]),
+                cells(&[to])
+            ));
+        }
+        if self.score_delta != 0 {
+            lines.push(format!("score: {:+}", self.score_delta));
+        }
+        if let Some((from, to)) = self.direction_changed {
+            lines.push(format!("direction: {:?} -> {:?}", from, to));
+        }
+        if let Some(game_over) = self.game_over_changed {
+            lines.push(format!("game over: {}", game_over));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+// FNV-1a hash - used instead of std's hasher for anything saved or sent over the network,
+// since std's can change between Rust releases
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// The random number generators a game can roll with, picked with --rng. Std is the
+// default and what every seed meant before there was a choice; the others are quicker
+// or carry less state, which counts for more on the web. Pcg and Xoshiro are only in
+// builds with their features
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RngKind {
+    #[default]
+    Std, // ChaCha12, the same rolls everywhere
+    Small,   // rand's SmallRng - quick, but 32 and 64 bit targets roll differently
+    Pcg,     // Pcg32, cheap where 64 bit maths is slow, like wasm
+    Xoshiro, // Xoshiro256++, the same rolls everywhere
+}
+
+impl RngKind {
+    pub const ALL: [RngKind; 4] = [RngKind::Std, RngKind::Small, RngKind::Pcg, RngKind::Xoshiro];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RngKind::Std => "std",
+            RngKind::Small => "small",
+            RngKind::Pcg => "pcg",
+            RngKind::Xoshiro => "xoshiro",
+        }
+    }
+
+    // Whether this build can roll with it
+    pub fn is_available(self) -> bool {
+        match self {
+            RngKind::Std | RngKind::Small => true,
+            RngKind::Pcg => cfg!(feature = "pcg"),
+            RngKind::Xoshiro => cfg!(feature = "xoshiro"),
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let kind = Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == value)
+            .ok_or_else(|| format!("expected std, small, pcg or xoshiro, got `{}`", value))?;
+        if !kind.is_available() {
+            return Err(format!("this build doesn't have the {} feature", value));
+        }
+        Ok(kind)
+    }
+
+    // How replays store it
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+}
+
+// How far resume() will step a generator that can't seek - far more than any run
+// rolls, and still quick to step through
+pub const MAX_RNG_STEPS: u128 = 1 << 24;
+
+#[derive(Debug, Clone)]
+enum RngAlgorithm {
+    Std(Box<ChaCha12Rng>), // much the biggest
+    Small(SmallRng),
+    #[cfg(feature = "pcg")]
+    Pcg(rand_pcg::Pcg32),
+    #[cfg(feature = "xoshiro")]
+    Xoshiro(rand_xoshiro::Xoshiro256PlusPlus),
+}
+
+// The game's RNG, whichever kind it is. Its whole state is the kind, the seed and how
+// far along it is, so a suspended run can carry on with exactly the rolls it would have had
+#[derive(Debug, Clone)]
+pub struct GameRng {
+    seed: u64,
+    steps: u128, // steps taken, for the generators that can't say where they are
+    algorithm: RngAlgorithm,
+}
+
+impl GameRng {
+    // A kind this build doesn't have rolls with Std instead
+    pub fn new(kind: RngKind, seed: u64) -> Self {
+        let algorithm = match kind {
+            RngKind::Small => RngAlgorithm::Small(SmallRng::seed_from_u64(seed)),
+            #[cfg(feature = "pcg")]
+            RngKind::Pcg => RngAlgorithm::Pcg(rand_pcg::Pcg32::seed_from_u64(seed)),
+            #[cfg(feature = "xoshiro")]
+            RngKind::Xoshiro => {
+                RngAlgorithm::Xoshiro(rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64(seed))
+            }
+            _ => RngAlgorithm::Std(Box::new(ChaCha12Rng::seed_from_u64(seed))),
+        };
+        Self {
+            seed,
+            steps: 0,
+            algorithm,
+        }
+    }
+
+    // The RNG `position` rolls on from `seed`, as position() gave it. None for a
+    // position that one of the generators that can't seek would take too long to reach
+    pub fn resume(kind: RngKind, seed: u64, position: u128) -> Option<Self> {
+        let mut rng = Self::new(kind, seed);
+        match &mut rng.algorithm {
+            RngAlgorithm::Std(chacha) => chacha.set_word_pos(position),
+            #[cfg(feature = "pcg")]
+            RngAlgorithm::Pcg(pcg) => {
+                pcg.advance(u64::try_from(position).ok()?);
+                rng.steps = position;
+            }
+            _ if position > MAX_RNG_STEPS => return None,
+            // every step is one u32 for the rest
+            _ => {
+                for _ in 0..position {
+                    rng.next_u32();
+                }
+            }
+        }
+        Some(rng)
+    }
+
+    pub fn kind(&self) -> RngKind {
+        match self.algorithm {
+            RngAlgorithm::Std(_) => RngKind::Std,
+            RngAlgorithm::Small(_) => RngKind::Small,
+            #[cfg(feature = "pcg")]
+            RngAlgorithm::Pcg(_) => RngKind::Pcg,
+            #[cfg(feature = "xoshiro")]
+            RngAlgorithm::Xoshiro(_) => RngKind::Xoshiro,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // How far along from the seed it is - words for ChaCha, steps for the rest
+    pub fn position(&self) -> u128 {
+        match &self.algorithm {
+            RngAlgorithm::Std(chacha) => chacha.get_word_pos(),
+            _ => self.steps,
+        }
+    }
+
+    // Generators with 32 bit outputs take two steps for a u64
+    fn u64_steps(&self) -> u128 {
+        match self.algorithm {
+            RngAlgorithm::Small(_) if cfg!(not(target_pointer_width = "64")) => 2,
+            #[cfg(feature = "pcg")]
+            RngAlgorithm::Pcg(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.steps += 1;
+        match &mut self.algorithm {
+            RngAlgorithm::Std(rng) => rng.next_u32(),
+            RngAlgorithm::Small(rng) => rng.next_u32(),
+            #[cfg(feature = "pcg")]
+            RngAlgorithm::Pcg(rng) => rng.next_u32(),
+            #[cfg(feature = "xoshiro")]
+            RngAlgorithm::Xoshiro(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.steps += self.u64_steps();
+        match &mut self.algorithm {
+            RngAlgorithm::Std(rng) => rng.next_u64(),
+            RngAlgorithm::Small(rng) => rng.next_u64(),
+            #[cfg(feature = "pcg")]
+            RngAlgorithm::Pcg(rng) => rng.next_u64(),
+            #[cfg(feature = "xoshiro")]
+            RngAlgorithm::Xoshiro(rng) => rng.next_u64(),
+        }
+    }
+
+    // Through next_u64 so the steps are counted
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match &mut self.algorithm {
+            RngAlgorithm::Std(rng) => rng.fill_bytes(dest),
+            _ => rand_chacha::rand_core::impls::fill_bytes_via_next(self, dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+// So a GameState can be written out with `rng: SeedableRng::seed_from_u64(0)`
+impl SeedableRng for GameRng {
+    type Seed = [u8; 8];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self::new(RngKind::Std, u64::from_le_bytes(seed))
+    }
+
+    fn seed_from_u64(seed: u64) -> Self {
+        Self::new(RngKind::Std, seed)
+    }
+}
+
+// The rules roll any of rand's generators the way the game always has, so a seed places
+// the same food as it did before the rules were split out
+impl<R: RngCore> RulesRng for R {
+    fn below(&mut self, bound: usize) -> usize {
+        self.gen_range(0..bound.max(1))
+    }
+}
+
+// State sync for netplay and spectators: one frame per tick, usually just a delta (where the
+// head went, whether the tail moved up, any food/score change), with a full keyframe every
+// KEYFRAME_INTERVAL ticks or whenever the receiver loses track
+pub const KEYFRAME_INTERVAL: u64 = 50;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncFrame {
+    Keyframe {
+        tick: u64,
+        snake: Vec<Position>,
+        direction: Direction,
+        next_direction: Direction,
+        food: Position,
+        score: u32,
+        game_over: bool,
+    },
+    Delta {
+        tick: u64,
+        head: Position,
+        tail_removed: bool,
+        food: Option<Position>, // only when it moved
+        score: u32,
+        direction: Direction,
+        next_direction: Direction,
+        game_over: bool,
+        hash: u64, // state_hash after applying, to catch a receiver that's drifted
+    },
+}
+
+pub(crate) fn direction_letter(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "U",
+        Direction::Down => "D",
+        Direction::Left => "L",
+        Direction::Right => "R",
+    }
+}
+
+pub(crate) fn parse_direction_letter(letter: &str) -> Option<Direction> {
+    match letter {
+        "U" => Some(Direction::Up),
+        "D" => Some(Direction::Down),
+        "L" => Some(Direction::Left),
+        "R" => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+impl SyncFrame {
+    // "K <tick> <dir> <next dir> <score> <over> <food> <snake cells...>" or
+    // "D <tick> <head> <tail removed> <food or -> <score> <dir> <next dir> <over> <hash>"
+    pub fn to_line(&self) -> String {
+        match self {
+            SyncFrame::Keyframe {
+                tick,
+                snake,
+                direction,
+                next_direction,
+                food,
+                score,
+                game_over,
+            } => {
+                let snake: Vec<String> = snake.iter().map(Position::to_text).collect();
+                format!(
+                    "K {} {} {} {} {} {} {}",
+                    tick,
+                    direction_letter(*direction),
+                    direction_letter(*next_direction),
+                    score,
+                    u8::from(*game_over),
+                    food.to_text(),
+                    snake.join(" ")
+                )
+            }
+            SyncFrame::Delta {
+                tick,
+                head,
+                tail_removed,
+                food,
+                score,
+                direction,
+                next_direction,
+                game_over,
+                hash,
+            } => format!(
+                "D {} {} {} {} {} {} {} {} {}",
+                tick,
+                head.to_text(),
+                u8::from(*tail_removed),
+                food.map_or("-".to_string(), |food| food.to_text()),
+                score,
+                direction_letter(*direction),
+                direction_letter(*next_direction),
+                u8::from(*game_over),
+                hash
+            ),
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<Self> {
+        let flag = |word: &str| match word {
+            "0" => Some(false),
+            "1" => Some(true),
+            _ => None,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["K", tick, direction, next_direction, score, game_over, food, snake @ ..] => {
+                let snake = snake
+                    .iter()
+                    .map(|cell| Position::parse(cell))
+                    .collect::<Option<Vec<_>>>()?;
+                if snake.is_empty() {
+                    return None;
+                }
+                Some(SyncFrame::Keyframe {
+                    tick: tick.parse().ok()?,
+                    snake,
+                    direction: parse_direction_letter(direction)?,
+                    next_direction: parse_direction_letter(next_direction)?,
+                    food: Position::parse(food)?,
+                    score: score.parse().ok()?,
+                    game_over: flag(game_over)?,
+                })
+            }
+            ["D", tick, head, tail_removed, food, score, direction, next_direction, game_over, hash] => {
+                Some(SyncFrame::Delta {
+                    tick: tick.parse().ok()?,
+                    head: Position::parse(head)?,
+                    tail_removed: flag(tail_removed)?,
+                    food: match *food {
+                        "-" => None,
+                        food => Some(Position::parse(food)?),
+                    },
+                    score: score.parse().ok()?,
+                    direction: parse_direction_letter(direction)?,
+                    next_direction: parse_direction_letter(next_direction)?,
+                    game_over: flag(game_over)?,
+                    hash: hash.parse().ok()?,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+// A rectangle on the board in pixels, for frontends to hit-test and draw however they draw
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Area {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Area {
+    pub fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x <= self.x + self.w && y >= self.y && y <= self.y + self.h
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.w / 2.0, self.y + self.h / 2.0)
+    }
+}
+
+// Least distance a finger has to travel to count as a swipe, in pixels
+pub const SWIPE_THRESHOLD: f32 = 30.0;
+// Width and height of each on-screen arrow button
+pub const TOUCH_BUTTON_SIZE: f32 = 60.0;
+
+// Steering for touch screens: swipe anywhere on the board, or tap the arrow buttons in
+// the bottom right corner. The buttons only show up once the board has been touched
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TouchControls {
+    pub swipe_start: Option<(f32, f32)>,
+    pub visible: bool,
+}
+
+impl TouchControls {
+    // The arrow buttons in board coordinates, laid out like a d-pad
+    pub fn buttons(config: &GameConfig) -> [(Direction, Area); 4] {
+        let size = TOUCH_BUTTON_SIZE;
+        let center_x = config.screen_width() - size * 1.5 - 10.0;
+        let center_y = config.screen_height() - size * 1.5 - 10.0;
+        [
+            (
+                Direction::Up,
+                Area::new(center_x - size / 2.0, center_y - size * 1.5, size, size),
+            ),
+            (
+                Direction::Down,
+                Area::new(center_x - size / 2.0, center_y + size / 2.0, size, size),
+            ),
+            (
+                Direction::Left,
+                Area::new(center_x - size * 1.5, center_y - size / 2.0, size, size),
+            ),
+            (
+                Direction::Right,
+                Area::new(center_x + size / 2.0, center_y - size / 2.0, size, size),
+            ),
+        ]
+    }
+
+    // A finger went down - a button turns straight away, anywhere else might become a swipe
+    pub fn press(&mut self, x: f32, y: f32, config: &GameConfig) -> Option<Direction> {
+        self.visible = true;
+        let button = Self::buttons(config)
+            .into_iter()
+            .find(|(_, area)| area.contains(x, y));
+        match button {
+            Some((direction, _)) => {
+                self.swipe_start = None;
+                Some(direction)
+            }
+            None => {
+                self.swipe_start = Some((x, y));
+                None
+            }
+        }
+    }
+
+    // The finger lifted - a long enough drag turns along whichever axis it moved most
+    pub fn release(&mut self, x: f32, y: f32) -> Option<Direction> {
+        let (start_x, start_y) = self.swipe_start.take()?;
+        let (dx, dy) = (x - start_x, y - start_y);
+        if dx.abs().max(dy.abs()) < SWIPE_THRESHOLD {
+            return None;
+        }
+        Some(match (dx.abs() > dy.abs(), dx > 0.0, dy > 0.0) {
+            (true, true, _) => Direction::Right,
+            (true, false, _) => Direction::Left,
+            (false, _, true) => Direction::Down,
+            (false, _, false) => Direction::Up,
+        })
+    }
+}
+
+// One run of the game: everything the rules decide, and nothing about how it's shown.
+// The time it's given and the steers it gets are all that move it on
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub snake: Vec<Position>,
+    pub direction: Direction,
+    pub next_direction: Direction,
+    pub food: Food,
+    pub score: u32,
+    pub game_over: bool,
+    pub speed: Speed,
+    pub clock: TickClock, // Time towards the next move
+    pub elapsed: f64,     // How long the current run has been going, in seconds
+    pub weather: Weather,
+    pub slippery_cells: Vec<(Position, u32)>, // cell and how many ticks it stays slippery
+    pub ice: bool, // ice variant - the snake can only turn while on a grip cell
+    pub grip_cells: Vec<Position>,
+    pub walls: Vec<Position>, // Cells the snake crashes into, from the level being played
+    pub coop: bool,           // co-op - player one turns left/right, player two turns up/down
+    pub turn_queue: Vec<Direction>, // co-op turns waiting for a tick, oldest first
+    pub handicap: Handicap,
+    pub food_policy: FoodPolicy,
+    pub food_mix: FoodMix,        // Which kinds of food turn up
+    pub hazards: Spawner<Hazard>, // rolled every tick while it's raining
+    pub spawns: Spawns,           // Where and how runs can begin
+    pub grace_left: GracePeriod,  // What's left of this run's grace period
+    pub turn_cooldown: f64, // Seconds a second turn is ignored for before the snake moves, 0 is off
+    pub turned_at: Option<f64>, // When the turn waiting for the next tick was made
+    pub rng: GameRng, // Every roll the game makes - food, spawns, puddles - so a seed replays them
+    pub seed: u64,    // What `rng` was seeded with at the start of this run
+    pub config: GameConfig, // Board and cell size, fixed for the session
+}
+
+impl Run {
+    // A three long snake in the middle of `config`'s board heading right, with every roll
+    // from `seed`
+    pub fn new(config: GameConfig, seed: u64) -> Self {
+        let start = SnakeStart::centered(&config);
+        let snake = start.cells();
+        let mut rng = GameRng::new(RngKind::default(), seed);
+        let food = free_cell(&snake, &config, &mut rng).expect("a new snake leaves room for food");
+        Self {
+            snake,
+            direction: start.direction,
+            next_direction: start.direction,
+            food: Food::normal(food),
+            score: 0,
+            game_over: false,
+            speed: config.speed.start(),
+            clock: TickClock::default(),
+            elapsed: 0.0,
+            weather: Weather::Clear,
+            slippery_cells: Vec::new(),
+            ice: false,
+            grip_cells: Vec::new(),
+            walls: Vec::new(),
+            coop: false,
+            turn_queue: Vec::new(),
+            handicap: Handicap::default(),
+            food_policy: FoodPolicy::default(),
+            food_mix: FoodMix::default(),
+            hazards: Spawner::rain(),
+            spawns: start.into(),
+            grace_left: GracePeriod::Off,
+            turn_cooldown: 0.0,
+            turned_at: None,
+            rng,
+            seed,
+            config,
+        }
+    }
+
+    // Roll from `seed` from now on, starting over with the food on the board
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = GameRng::new(self.rng.kind(), seed);
+        self.spawn_food();
+    }
+
+    // Roll with another kind of RNG, from the same seed
+    pub fn set_rng(&mut self, kind: RngKind) {
+        self.rng = GameRng::new(kind, self.seed);
+        self.spawn_food();
+    }
+
+    // Start runs from one of these spawns instead, moving the snake to one now
+    pub fn set_spawns(&mut self, spawns: Spawns) {
+        let start = spawns.pick(&mut self.rng);
+        self.grace_left = spawns.grace;
+        self.spawns = spawns;
+        self.snake = start.cells();
+        self.direction = start.direction;
+        self.next_direction = start.direction;
+        if self.snake.contains(&self.food.position) {
+            self.spawn_food();
+        }
+    }
+
+    // Roll a new spot and kind for the food, clear of the snake and any walls. With
+    // nowhere left to put it the snake has filled the board, and the run is won
+    pub fn spawn_food(&mut self) {
+        let position = if self.walls.is_empty() {
+            self.food_policy
+                .spawn(&self.snake, &self.config, &mut self.rng)
+        } else {
+            // the head stays first, for the policies that care where it is
+            let occupied: Vec<Position> = self.snake.iter().chain(&self.walls).copied().collect();
+            self.food_policy
+                .spawn(&occupied, &self.config, &mut self.rng)
+        };
+        match position {
+            Some(position) => self.food = Food::new(position, self.food_mix.roll(&mut self.rng)),
+            None => self.game_over = true,
+        }
+    }
+
+    // Whether the run ended with the snake filling every cell the walls leave free
+    pub fn won(&self) -> bool {
+        self.game_over
+            && self
+                .config
+                .cells()
+                .all(|cell| self.snake.contains(&cell) || self.walls.contains(&cell))
+    }
+
+    // One tick of the rules - turn, move, eat and weather - with no timing involved, so
+    // a frontend can drive the run at its own pace
+    pub fn step(&mut self) {
+        if self.hold_for_grace() {
+            return;
+        }
+        self.apply_turn();
+        self.move_snake();
+        if !self.game_over {
+            self.rot_food();
+        }
+        self.update_weather();
+    }
+
+    // Whether the snake is still waiting out the grace period
+    pub fn in_grace(&self) -> bool {
+        self.grace_left != GracePeriod::Off && !self.game_over
+    }
+
+    // Use up a tick of the grace period instead of moving, true while it lasts
+    fn hold_for_grace(&mut self) -> bool {
+        match self.grace_left {
+            GracePeriod::Off => false,
+            GracePeriod::UntilInput => true,
+            GracePeriod::Ticks(ticks) => {
+                self.grace_left = if ticks > 1 {
+                    GracePeriod::Ticks(ticks - 1)
+                } else {
+                    GracePeriod::Off
+                };
+                true
+            }
+        }
+    }
+
+    // Turn the snake to the queued direction - unless it's sliding on a wet cell,
+    // in which case the turn waits until the next tick.
+    // On ice the turn waits (possibly several ticks) until the head reaches a grip cell.
+    pub fn apply_turn(&mut self) {
+        self.turned_at = None; // a tick, so the turn assist lets the next turn through
+        let head = self.snake[0];
+        if self.ice && !self.grip_cells.contains(&head) {
+            return;
+        }
+        if self.is_slippery(head) {
+            return;
+        }
+        if !self.turn_queue.is_empty() {
+            self.next_direction = self.turn_queue.remove(0);
+        }
+        self.direction = self.next_direction;
+    }
+
+    pub fn is_slippery(&self, position: Position) -> bool {
+        self.slippery_cells
+            .iter()
+            .any(|(cell, _)| *cell == position)
+    }
+
+    // Dry out old slippery cells and let rain wet new ones (called once per tick)
+    pub fn update_weather(&mut self) {
+        for (_, ticks_left) in &mut self.slippery_cells {
+            *ticks_left -= 1;
+        }
+        self.slippery_cells
+            .retain(|(_, ticks_left)| *ticks_left > 0);
+
+        if self.weather == Weather::Rain {
+            if let Some(Hazard::Puddle) = self.hazards.tick(&mut self.rng) {
+                let cell = Position::new(
+                    self.rng.gen_range(0..self.config.grid_width),
+                    self.rng.gen_range(0..self.config.grid_height),
+                );
+                self.slippery_cells.push((cell, SLIPPERY_TICKS));
+            }
+        }
+    }
+
+    // Whether a cell can be seen through the fog (always true without fog)
+    pub fn is_visible(&self, position: Position) -> bool {
+        match self.weather {
+            Weather::Fog { radius } => {
+                let head = self.snake[0];
+                let dx = position.x - head.x;
+                let dy = position.y - head.y;
+                dx * dx + dy * dy <= radius * radius
+            }
+            _ => true,
+        }
+    }
+
+    // Check if a position would cause a collision
+    pub fn would_collide(&self, new_head: Position) -> bool {
+        crate::rules::collides(&self.snake, &self.walls, &self.config, new_head)
+    }
+
+    // Move the snek, by the rules, then see to whatever it ate
+    pub fn move_snake(&mut self) {
+        let moved = move_snake(
+            &mut self.snake,
+            self.direction,
+            &self.config,
+            &self.walls,
+            self.food.position,
+        );
+        match moved {
+            Move::Crashed => self.game_over = true,
+            Move::Ate => {
+                self.eat(self.food.kind);
+                self.spawn_food();
+            }
+            Move::Moved => {}
+        }
+    }
+
+    // What each kind of food does once the head's moved onto it
+    fn eat(&mut self, kind: FoodKind) {
+        let points = self.handicap.food_points();
+        match kind {
+            FoodKind::Normal | FoodKind::Golden => {
+                let bonus = if kind == FoodKind::Golden { 5 } else { 1 };
+                self.score += points * bonus;
+                // Increase game speed
+                self.speed = self.speed.faster();
+            }
+            FoodKind::SlowDown => {
+                self.score += points;
+                for _ in 0..SLOW_DOWN_STEPS {
+                    self.speed = self.speed.slower();
+                }
+            }
+            FoodKind::Shrink => {
+                self.score += points;
+                self.snake.pop();
+                let length = self.snake.len().saturating_sub(SHRINK_CELLS);
+                self.snake.truncate(length.max(MIN_SHRINK_LENGTH));
+            }
+            FoodKind::Poison => {
+                self.score = self.score.saturating_sub(points);
+                self.snake.pop();
+            }
+        }
+    }
+
+    // Food that rots counts down a tick at a time, and is swapped for a fresh piece
+    // when it runs out
+    fn rot_food(&mut self) {
+        if let Some(ticks) = &mut self.food.ticks_left {
+            *ticks = ticks.saturating_sub(1);
+            if *ticks == 0 {
+                self.spawn_food();
+            }
+        }
+    }
+
+    // Set up the snake for this player's handicap - call at the start of a run
+    pub fn apply_handicap(&mut self) {
+        // grow the tail straight back from the head, as far as the board allows
+        let length = self.handicap.starting_length.unwrap_or(self.snake.len());
+        self.snake.truncate(length.max(1));
+        while self.snake.len() < length {
+            let tail = self.snake[self.snake.len() - 1];
+            let new_tail = tail.move_in_direction(self.direction.opposite());
+            if !self.config.contains(new_tail) || self.snake.contains(&new_tail) {
+                break;
+            }
+            self.snake.push(new_tail);
+        }
+        if self.snake.contains(&self.food.position) {
+            self.spawn_food();
+        }
+
+        let seconds = self.speed.seconds_per_cell() + self.handicap.speed_offset;
+        self.speed = Speed::from_seconds_per_cell(seconds).min(Speed::MAX);
+    }
+
+    // Roughly what the board's cells and queues take on the heap, in bytes
+    pub fn heap_bytes(&self) -> usize {
+        use core::mem::size_of;
+        (self.snake.capacity() + self.grip_cells.capacity() + self.walls.capacity())
+            * size_of::<Position>()
+            + self.slippery_cells.capacity() * size_of::<(Position, u32)>()
+            + self.turn_queue.capacity() * size_of::<Direction>()
+            + self.spawns.candidates.capacity() * size_of::<SnakeStart>()
+    }
+
+    // Fingerprint of the state that decides the game (not timers or cosmetics), for checking
+    // two peers agree
+    pub fn state_hash(&self) -> u64 {
+        let direction_byte = |direction: Direction| direction as u8;
+        let mut bytes = Vec::with_capacity(self.snake.len() * 8 + 16);
+        for segment in self.snake.iter().chain([&self.food.position]) {
+            bytes.extend_from_slice(&segment.x.to_le_bytes());
+            bytes.extend_from_slice(&segment.y.to_le_bytes());
+        }
+        if self.food.kind != FoodKind::Normal {
+            // left out for normal food, so classic games hash the same as they always have
+            bytes.push(self.food.kind as u8);
+        }
+        bytes.push(direction_byte(self.direction));
+        bytes.push(direction_byte(self.next_direction));
+        bytes.extend_from_slice(&self.score.to_le_bytes());
+        bytes.push(self.game_over as u8);
+        fnv1a(&bytes)
+    }
+
+    // How `other` differs from this state - segments are compared as cells, so a snake that
+    // moved one step shows up as its new head added and its old tail removed
+    pub fn diff(&self, other: &Run) -> StateDiff {
+        StateDiff {
+            segments_added: other
+                .snake
+                .iter()
+                .filter(|cell| !self.snake.contains(cell))
+                .copied()
+                .collect(),
+            segments_removed: self
+                .snake
+                .iter()
+                .filter(|cell| !other.snake.contains(cell))
+                .copied()
+                .collect(),
+            food_moved: (self.food.position != other.food.position)
+                .then_some((self.food.position, other.food.position)),
+            score_delta: other.score as i64 - self.score as i64,
+            direction_changed: (self.direction != other.direction)
+                .then_some((self.direction, other.direction)),
+            game_over_changed: (self.game_over != other.game_over).then_some(other.game_over),
+        }
+    }
+
+    // Catch up a reconnecting peer with the host's state. `host_hash` is the hash the host sent
+    // alongside the state - if they don't agree the state was damaged on the way, so keep ours
+    pub fn resync_from(&mut self, host: &Run, host_hash: u64) -> bool {
+        if host.state_hash() != host_hash {
+            return false;
+        }
+        self.snake = host.snake.clone();
+        self.direction = host.direction;
+        self.next_direction = host.next_direction;
+        self.food = host.food;
+        self.score = host.score;
+        self.game_over = host.game_over;
+        self.speed = host.speed;
+        self.elapsed = host.elapsed;
+        self.slippery_cells = host.slippery_cells.clone();
+        self.turn_queue = host.turn_queue.clone();
+        true
+    }
+
+    // Fraction of the board covered by the snake, from 0.0 to 1.0
+    pub fn coverage(&self) -> f32 {
+        self.snake.len() as f32 / self.config.cell_count() as f32
+    }
+
+    // Handle input to change direction
+    pub fn handle_input(&mut self, direction: Direction) {
+        // steering means the player's ready
+        self.grace_left = GracePeriod::Off;
+        if self.coop {
+            self.queue_coop_turn(direction);
+            return;
+        }
+
+        // Prevent snake from reversing into itself
+        if direction == self.direction.opposite() {
+            return;
+        }
+        // Turn assist: mashing two turns between ticks keeps the first, so a slip of the
+        // finger can't undo it. Once the snake has moved the next turn always counts
+        if self.turn_cooldown > 0.0 {
+            if let Some(turned_at) = self.turned_at {
+                if self.elapsed - turned_at < self.turn_cooldown {
+                    return;
+                }
+            }
+            if direction != self.direction {
+                self.turned_at = Some(self.elapsed);
+            }
+        }
+        self.next_direction = direction;
+    }
+
+    // Co-op input arbitration: both players can turn in the same tick without one overwriting
+    // the other. Each turn has to be perpendicular to the one before it (so it always comes
+    // from the other player) and is queued to apply on its own tick, in the order pressed.
+    pub fn queue_coop_turn(&mut self, direction: Direction) {
+        let previous = self.turn_queue.last().copied().unwrap_or(self.direction);
+        if direction == previous || direction == previous.opposite() {
+            return;
+        }
+        if self.turn_queue.len() < MAX_QUEUED_TURNS {
+            self.turn_queue.push(direction);
+        }
+    }
+
+    // A sync keyframe of the board, then the speed, the run time, the RNG and the
+    // food's kind on their own lines
+    pub fn suspend_text(&self) -> String {
+        let keyframe = SyncFrame::Keyframe {
+            tick: 0,
+            snake: self.snake.clone(),
+            direction: self.direction,
+            next_direction: self.next_direction,
+            food: self.food.position,
+            score: self.score,
+            game_over: self.game_over,
+        };
+        let ticks_left = match self.food.ticks_left {
+            Some(ticks) => ticks.to_string(),
+            None => "-".to_string(),
+        };
+        format!(
+            "{}\n{}\n{}\nrng {} {} {}\nfood {} {}\n",
+            keyframe.to_line(),
+            self.speed,
+            self.elapsed,
+            self.rng.kind().name(),
+            self.rng.seed(),
+            self.rng.position(),
+            self.food.kind.name(),
+            ticks_left
+        )
+    }
+
+    // Pick up a run saved by suspend_text, keeping this game's settings. Nothing changes
+    // if the text can't be read
+    pub fn resume_text(&mut self, text: &str) -> bool {
+        let mut lines = text.lines();
+        let Some(SyncFrame::Keyframe {
+            snake,
+            direction,
+            next_direction,
+            food,
+            score,
+            game_over,
+            ..
+        }) = lines.next().and_then(SyncFrame::parse)
+        else {
+            return false;
+        };
+        // older saves have the seconds between moves, which Speed::parse converts
+        let speed = lines.next().and_then(|line| Speed::parse(line).ok());
+        let elapsed = lines.next().and_then(|line| line.trim().parse().ok());
+        let (Some(speed), Some(elapsed)) = (speed, elapsed) else {
+            return false;
+        };
+        // older saves go on with this game's RNG
+        let rng = lines.next().and_then(|line| {
+            let mut parts = line.strip_prefix("rng ")?.split(' ');
+            let kind = RngKind::parse(parts.next()?).ok()?;
+            let seed = parts.next()?.parse().ok()?;
+            let position = parts.next()?.parse().ok()?;
+            GameRng::resume(kind, seed, position)
+        });
+        // and older saves only ever had normal food
+        let food = match lines.next().and_then(|line| line.strip_prefix("food ")) {
+            Some(line) => {
+                let mut parts = line.split(' ');
+                let kind = parts.next().and_then(FoodKind::parse);
+                let ticks_left = match parts.next() {
+                    Some("-") => Some(None),
+                    Some(ticks) => ticks.parse().ok().map(Some),
+                    None => None,
+                };
+                let (Some(kind), Some(ticks_left)) = (kind, ticks_left) else {
+                    return false;
+                };
+                Food {
+                    position: food,
+                    kind,
+                    ticks_left,
+                }
+            }
+            None => Food::normal(food),
+        };
+        if !snake.iter().all(|cell| self.config.contains(*cell)) {
+            return false;
+        }
+        self.snake = snake;
+        self.direction = direction;
+        self.next_direction = next_direction;
+        self.food = food;
+        self.score = score;
+        self.game_over = game_over;
+        self.speed = speed;
+        self.elapsed = elapsed;
+        if let Some(rng) = rng {
+            self.seed = rng.seed();
+            self.rng = rng;
+        }
+        true
+    }
+}
@@ -0,0 +1,612 @@
+//! The level editor: walls painted onto a level's board with a brush, a line or a
+//! rectangle, mirrored or rotated as they go, with undo and redo. It saves the same level
+//! files `--levels` plays.
+
+use super::*;
+
+// The editor's last tool and symmetry, kept with the player's other settings
+pub const EDITOR_PREFS_FILE: &str = "editor.txt";
+// How many edits back undo can go
+pub const EDITOR_UNDO_LIMIT: usize = 200;
+// Height of the status strip along the bottom of the board
+const EDITOR_STATUS_HEIGHT: f32 = 24.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorTool {
+    Brush,     // paints every cell dragged over
+    Line,      // a straight line from where the drag began
+    Rectangle, // the outline of the box the drag spans
+}
+
+impl EditorTool {
+    pub const ALL: [EditorTool; 3] = [EditorTool::Brush, EditorTool::Line, EditorTool::Rectangle];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EditorTool::Brush => "brush",
+            EditorTool::Line => "line",
+            EditorTool::Rectangle => "rectangle",
+        }
+    }
+}
+
+// Where else a painted cell lands, so one side of an arena can be drawn for all of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    Off,
+    MirrorLeftRight,
+    MirrorTopBottom,
+    MirrorBoth,    // all four quarters
+    RotateHalf,    // turned 180 degrees about the middle
+    RotateQuarter, // turned 90 degrees at a time - cells that land off the board are skipped
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 6] = [
+        Symmetry::Off,
+        Symmetry::MirrorLeftRight,
+        Symmetry::MirrorTopBottom,
+        Symmetry::MirrorBoth,
+        Symmetry::RotateHalf,
+        Symmetry::RotateQuarter,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Symmetry::Off => "off",
+            Symmetry::MirrorLeftRight => "mirror-x",
+            Symmetry::MirrorTopBottom => "mirror-y",
+            Symmetry::MirrorBoth => "mirror-xy",
+            Symmetry::RotateHalf => "rotate-2",
+            Symmetry::RotateQuarter => "rotate-4",
+        }
+    }
+
+    pub fn next(self) -> Symmetry {
+        let index = Self::ALL.iter().position(|s| *s == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    // `cell` and everywhere it's copied to on a board the config's size, without repeats
+    pub fn images(self, cell: Position, config: &GameConfig) -> Vec<Position> {
+        // twice the distance from the middle, so boards an even number of cells across
+        // still turn about a whole number
+        let (w, h) = (config.grid_width - 1, config.grid_height - 1);
+        let (x, y) = (2 * cell.x - w, 2 * cell.y - h);
+        let doubled: Vec<(i32, i32)> = match self {
+            Symmetry::Off => vec![(x, y)],
+            Symmetry::MirrorLeftRight => vec![(x, y), (-x, y)],
+            Symmetry::MirrorTopBottom => vec![(x, y), (x, -y)],
+            Symmetry::MirrorBoth => vec![(x, y), (-x, y), (x, -y), (-x, -y)],
+            Symmetry::RotateHalf => vec![(x, y), (-x, -y)],
+            Symmetry::RotateQuarter => vec![(x, y), (-y, x), (-x, -y), (y, -x)],
+        };
+        let mut images = Vec::with_capacity(doubled.len());
+        for (x, y) in doubled {
+            // a quarter turn of a board that isn't square can land between cells
+            if (x + w) % 2 != 0 || (y + h) % 2 != 0 {
+                continue;
+            }
+            let image = Position::new((x + w) / 2, (y + h) / 2);
+            if config.contains(image) && !images.contains(&image) {
+                images.push(image);
+            }
+        }
+        images
+    }
+}
+
+// The editor settings that carry over between sessions: `tool = line` and
+// `symmetry = mirror-x` lines, like the other settings files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EditorPrefs {
+    pub tool: EditorTool,
+    pub symmetry: Symmetry,
+}
+
+impl Default for EditorPrefs {
+    fn default() -> Self {
+        Self {
+            tool: EditorTool::Brush,
+            symmetry: Symmetry::Off,
+        }
+    }
+}
+
+impl EditorPrefs {
+    pub fn parse(text: &str) -> Result<EditorPrefs, String> {
+        let mut prefs = EditorPrefs::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fail = |message: String| format!("line {}: {}", number + 1, message);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| fail(format!("expected `key = value`, got `{}`", line)))?;
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "tool" => {
+                    prefs.tool = EditorTool::ALL
+                        .into_iter()
+                        .find(|tool| tool.name() == value)
+                        .ok_or_else(|| fail(format!("no tool called `{}`", value)))?
+                }
+                "symmetry" => {
+                    prefs.symmetry = Symmetry::ALL
+                        .into_iter()
+                        .find(|symmetry| symmetry.name() == value)
+                        .ok_or_else(|| fail(format!("no symmetry called `{}`", value)))?
+                }
+                _ => return Err(fail(format!("unknown key `{}`", key))),
+            }
+        }
+        Ok(prefs)
+    }
+
+    pub fn to_text(&self) -> String {
+        format!(
+            "tool = {}\nsymmetry = {}\n",
+            self.tool.name(),
+            self.symmetry.name()
+        )
+    }
+
+    // The saved preferences, or the defaults if there aren't any or they don't parse
+    pub fn load(storage: &dyn StorageBackend) -> EditorPrefs {
+        let Some(text) = storage.read(EDITOR_PREFS_FILE) else {
+            return EditorPrefs::default();
+        };
+        EditorPrefs::parse(&text).unwrap_or_else(|e| {
+            eprintln!("Ignoring {}: {}", EDITOR_PREFS_FILE, e);
+            EditorPrefs::default()
+        })
+    }
+
+    pub fn save(&self, storage: &dyn StorageBackend) {
+        if let Err(e) = storage.write(EDITOR_PREFS_FILE, &self.to_text()) {
+            eprintln!("Failed to save the editor settings: {}", e);
+        }
+    }
+}
+
+// The cells on a straight line between two cells, ends included
+pub fn line_cells(from: Position, to: Position) -> Vec<Position> {
+    let (dx, dy) = ((to.x - from.x).abs(), -(to.y - from.y).abs());
+    let (step_x, step_y) = ((to.x - from.x).signum(), (to.y - from.y).signum());
+    let (mut cell, mut error) = (from, dx + dy);
+    let mut cells = vec![cell];
+    while cell != to {
+        if 2 * error >= dy {
+            error += dy;
+            cell.x += step_x;
+        }
+        if 2 * error <= dx {
+            error += dx;
+            cell.y += step_y;
+        }
+        cells.push(cell);
+    }
+    cells
+}
+
+// The outline of the box with these two cells at opposite corners
+pub fn rectangle_cells(from: Position, to: Position) -> Vec<Position> {
+    let (left, right) = (from.x.min(to.x), from.x.max(to.x));
+    let (top, bottom) = (from.y.min(to.y), from.y.max(to.y));
+    let mut cells = Vec::new();
+    for y in top..=bottom {
+        for x in left..=right {
+            if y == top || y == bottom || x == left || x == right {
+                cells.push(Position::new(x, y));
+            }
+        }
+    }
+    cells
+}
+
+// A drag in progress: where it began and has got to, whether it puts walls down or takes
+// them up, and the walls from before it for undo
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stroke {
+    pub from: Position,
+    pub to: Position,
+    pub wall: bool,
+    pub before: Vec<Position>,
+}
+
+pub struct LevelEditor {
+    pub level: Level,
+    pub path: String,
+    pub prefs: EditorPrefs,
+    pub undo: Vec<Vec<Position>>, // the walls before each edit, oldest first
+    pub redo: Vec<Vec<Position>>, // and the ones undone, most recent last
+    pub stroke: Option<Stroke>,
+    pub cursor: Option<Position>,
+    pub status: String, // the last thing that happened, shown along the bottom
+    pub unsaved: bool,  // there are edits since the last save
+    pub leaving: bool,  // Esc was pressed with unsaved edits, and again quits anyway
+    pub exiting: bool,
+    pub storage: Arc<dyn StorageBackend>,
+}
+
+impl LevelEditor {
+    pub fn new(level: Level, path: &str, storage: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            prefs: EditorPrefs::load(storage.as_ref()),
+            level,
+            path: path.to_string(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            stroke: None,
+            cursor: None,
+            status: "Left mouse paints walls, right mouse erases".to_string(),
+            unsaved: false,
+            leaving: false,
+            exiting: false,
+            storage,
+        }
+    }
+
+    // The level at `path`, or a new empty one named after the file if there isn't one yet
+    pub fn open(path: &str, storage: Arc<dyn StorageBackend>) -> Result<Self, String> {
+        let file = std::path::Path::new(path);
+        let level = if file.exists() {
+            Level::load(file)?
+        } else {
+            let name = file.file_stem().map_or("New level".to_string(), |stem| {
+                stem.to_string_lossy().into_owned()
+            });
+            Level::parse(&format!("name = \"{}\"\ntarget_score = 10", name))?
+        };
+        Ok(Self::new(level, path, storage))
+    }
+
+    // The snake's start and first move, which can't be walled
+    fn is_kept_clear(&self, cell: Position) -> bool {
+        let start = SnakeStart::centered(&self.level.config);
+        start.cells().contains(&cell) || start.head.move_in_direction(start.direction) == cell
+    }
+
+    // What the running stroke covers, symmetry included
+    pub fn stroke_cells(&self) -> Vec<Position> {
+        let Some(stroke) = &self.stroke else {
+            return Vec::new();
+        };
+        let drawn = match self.prefs.tool {
+            // the brush paints as it goes, so only the cell under it is still to do
+            EditorTool::Brush => vec![stroke.to],
+            EditorTool::Line => line_cells(stroke.from, stroke.to),
+            EditorTool::Rectangle => rectangle_cells(stroke.from, stroke.to),
+        };
+        let mut cells = Vec::new();
+        for cell in drawn {
+            for image in self.prefs.symmetry.images(cell, &self.level.config) {
+                if !cells.contains(&image) {
+                    cells.push(image);
+                }
+            }
+        }
+        cells
+    }
+
+    fn paint(&mut self, cells: &[Position], wall: bool) {
+        for cell in cells {
+            if self.is_kept_clear(*cell) {
+                continue;
+            }
+            let index = self.level.walls.iter().position(|w| w == cell);
+            match (wall, index) {
+                (true, None) => self.level.walls.push(*cell),
+                (false, Some(index)) => {
+                    self.level.walls.swap_remove(index);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Start a stroke at a cell: walls go down with `wall`, otherwise they come up
+    pub fn press(&mut self, cell: Position, wall: bool) {
+        if !self.level.config.contains(cell) {
+            return;
+        }
+        self.stroke = Some(Stroke {
+            from: cell,
+            to: cell,
+            wall,
+            before: self.level.walls.clone(),
+        });
+        if self.prefs.tool == EditorTool::Brush {
+            let cells = self.stroke_cells();
+            self.paint(&cells, wall);
+        }
+    }
+
+    // Carry the stroke on to a cell. The brush fills in any cells a quick drag skipped
+    pub fn drag(&mut self, cell: Position) {
+        self.cursor = Some(cell).filter(|cell| self.level.config.contains(*cell));
+        let Some(stroke) = &mut self.stroke else {
+            return;
+        };
+        let config = &self.level.config;
+        let cell = Position::new(
+            cell.x.clamp(0, config.grid_width - 1),
+            cell.y.clamp(0, config.grid_height - 1),
+        );
+        if self.prefs.tool == EditorTool::Brush {
+            let (last, wall) = (stroke.to, stroke.wall);
+            for step in line_cells(last, cell) {
+                if let Some(stroke) = &mut self.stroke {
+                    stroke.to = step;
+                }
+                let cells = self.stroke_cells();
+                self.paint(&cells, wall);
+            }
+        } else {
+            stroke.to = cell;
+        }
+    }
+
+    // Finish the stroke, putting down a line or rectangle, and keep it for undo
+    pub fn release(&mut self) {
+        if self.prefs.tool != EditorTool::Brush {
+            if let Some(stroke) = &self.stroke {
+                let wall = stroke.wall;
+                let cells = self.stroke_cells();
+                self.paint(&cells, wall);
+            }
+        }
+        let Some(stroke) = self.stroke.take() else {
+            return;
+        };
+        // a stroke that came to nothing isn't worth an undo
+        let sorted = |walls: &[Position]| {
+            let mut walls = walls.to_vec();
+            walls.sort_by_key(|cell| (cell.y, cell.x));
+            walls
+        };
+        if sorted(&stroke.before) != sorted(&self.level.walls) {
+            self.remember(stroke.before);
+        }
+    }
+
+    fn remember(&mut self, before: Vec<Position>) {
+        self.undo.push(before);
+        if self.undo.len() > EDITOR_UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        self.unsaved = true;
+    }
+
+    pub fn undo(&mut self) {
+        match self.undo.pop() {
+            Some(walls) => {
+                self.redo
+                    .push(std::mem::replace(&mut self.level.walls, walls));
+                self.unsaved = true;
+                self.status = format!("Undone, {} more to undo", self.undo.len());
+            }
+            None => self.status = "Nothing to undo".to_string(),
+        }
+    }
+
+    pub fn redo(&mut self) {
+        match self.redo.pop() {
+            Some(walls) => {
+                self.undo
+                    .push(std::mem::replace(&mut self.level.walls, walls));
+                self.unsaved = true;
+                self.status = format!("Redone, {} more to redo", self.redo.len());
+            }
+            None => self.status = "Nothing to redo".to_string(),
+        }
+    }
+
+    pub fn set_tool(&mut self, tool: EditorTool) {
+        self.prefs.tool = tool;
+        self.prefs.save(self.storage.as_ref());
+        self.status = format!("Tool: {}", tool.name());
+    }
+
+    pub fn cycle_symmetry(&mut self) {
+        self.prefs.symmetry = self.prefs.symmetry.next();
+        self.prefs.save(self.storage.as_ref());
+        self.status = format!("Symmetry: {}", self.prefs.symmetry.name());
+    }
+
+    // Write the level file, as long as it'd load again - a level with floor the snake
+    // can't reach is kept back until it's fixed
+    pub fn save(&mut self) -> Result<(), String> {
+        let text = self.level.to_text();
+        Level::parse(&text)?;
+        std::fs::write(&self.path, text).map_err(|e| e.to_string())?;
+        self.unsaved = false;
+        Ok(())
+    }
+
+    fn save_and_report(&mut self) {
+        self.status = match self.save() {
+            Ok(()) => format!("Saved {}", self.path),
+            Err(e) => format!("Not saved: {}", e),
+        };
+    }
+
+    pub fn cell_at(&self, x: f32, y: f32) -> Position {
+        let cell_size = self.level.config.cell_size;
+        Position::new(
+            (x / cell_size).floor() as i32,
+            (y / cell_size).floor() as i32,
+        )
+    }
+
+    pub fn key_down(&mut self, key_input: KeyInput) {
+        let Some(keycode) = key_input.keycode else {
+            return;
+        };
+        let ctrl = key_input.mods.contains(KeyMods::CTRL);
+        let shift = key_input.mods.contains(KeyMods::SHIFT);
+        if keycode != KeyCode::Escape {
+            self.leaving = false;
+        }
+        match keycode {
+            KeyCode::Z if ctrl && shift => self.redo(),
+            KeyCode::Z if ctrl => self.undo(),
+            KeyCode::Y if ctrl => self.redo(),
+            KeyCode::S if ctrl => self.save_and_report(),
+            KeyCode::B => self.set_tool(EditorTool::Brush),
+            KeyCode::L => self.set_tool(EditorTool::Line),
+            KeyCode::R => self.set_tool(EditorTool::Rectangle),
+            KeyCode::M => self.cycle_symmetry(),
+            KeyCode::Escape if self.unsaved && !self.leaving => {
+                self.leaving = true;
+                self.status = "Unsaved changes - Esc again to quit without saving".to_string();
+            }
+            KeyCode::Escape => self.exiting = true,
+            _ => {}
+        }
+    }
+}
+
+impl EventHandler for LevelEditor {
+    fn update(&mut self, _ctx: &mut Context) -> GameResult {
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let config = self.level.config;
+        let mut canvas = graphics::Canvas::from_frame(ctx, Color::BLACK);
+        let cell_size = config.cell_size;
+        let cell_rect = |cell: Position| {
+            Rect::new(
+                cell.x as f32 * cell_size,
+                cell.y as f32 * cell_size,
+                cell_size - 2.0,
+                cell_size - 2.0,
+            )
+        };
+
+        let start = SnakeStart::centered(&config);
+        let preview = self.stroke_cells();
+        let wall = self.stroke.as_ref().is_some_and(|stroke| stroke.wall);
+        let mut cells: Vec<(Rect, Color)> = Vec::new();
+        for y in 0..config.grid_height {
+            for x in 0..config.grid_width {
+                cells.push((
+                    cell_rect(Position::new(x, y)),
+                    Color::new(1.0, 1.0, 1.0, 0.04),
+                ));
+            }
+        }
+        cells.extend(
+            self.level
+                .walls
+                .iter()
+                .map(|cell| (cell_rect(*cell), Color::new(0.55, 0.55, 0.6, 1.0))),
+        );
+        cells.extend(
+            start
+                .cells()
+                .into_iter()
+                .map(|cell| (cell_rect(cell), Color::new(0.0, 0.6, 0.0, 0.6))),
+        );
+        let preview_color = if wall {
+            Color::new(1.0, 0.85, 0.2, 0.5)
+        } else {
+            Color::new(1.0, 0.3, 0.3, 0.5)
+        };
+        cells.extend(preview.iter().map(|cell| (cell_rect(*cell), preview_color)));
+        if let Some(cursor) = self.cursor.filter(|_| self.stroke.is_none()) {
+            for image in self.prefs.symmetry.images(cursor, &config) {
+                cells.push((cell_rect(image), Color::new(1.0, 1.0, 1.0, 0.25)));
+            }
+        }
+        let mut instances = graphics::InstanceArray::new(ctx, None);
+        instances.set(
+            cells
+                .into_iter()
+                .map(|(rect, color)| cell_instance(rect, color)),
+        );
+        canvas.draw(&instances, graphics::DrawParam::default());
+
+        // what's selected, and the last thing that happened
+        let strip = Rect::new(
+            0.0,
+            config.screen_height() - EDITOR_STATUS_HEIGHT,
+            config.screen_width(),
+            EDITOR_STATUS_HEIGHT,
+        );
+        let background =
+            Mesh::new_rectangle(ctx, DrawMode::fill(), strip, Color::new(0.0, 0.0, 0.0, 0.7))?;
+        canvas.draw(&background, graphics::DrawParam::default());
+        let line = format!(
+            "{}{} - {} - symmetry {} - {}",
+            self.level.name,
+            if self.unsaved { "*" } else { "" },
+            self.prefs.tool.name(),
+            self.prefs.symmetry.name(),
+            self.status
+        );
+        let text = Text::new(TextFragment::new(line).scale(graphics::PxScale::from(16.0)));
+        canvas.draw(
+            &text,
+            graphics::DrawParam::default().dest([6.0, strip.y + 4.0]),
+        );
+        canvas.finish(ctx)
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        key_input: KeyInput,
+        _repeat: bool,
+    ) -> GameResult {
+        self.key_down(key_input);
+        if self.exiting {
+            ctx.request_quit();
+        }
+        Ok(())
+    }
+
+    fn mouse_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) -> GameResult {
+        let cell = self.cell_at(x, y);
+        match button {
+            MouseButton::Left => self.press(cell, true),
+            MouseButton::Right => self.press(cell, false),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn mouse_motion_event(
+        &mut self,
+        _ctx: &mut Context,
+        x: f32,
+        y: f32,
+        _dx: f32,
+        _dy: f32,
+    ) -> GameResult {
+        let cell = self.cell_at(x, y);
+        self.drag(cell);
+        Ok(())
+    }
+
+    fn mouse_button_up_event(
+        &mut self,
+        _ctx: &mut Context,
+        _button: MouseButton,
+        _x: f32,
+        _y: f32,
+    ) -> GameResult {
+        self.release();
+        Ok(())
+    }
+}
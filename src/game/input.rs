@@ -0,0 +1,300 @@
+//! Turning keys, gamepad buttons and touches into moves, and the scripted input that
+//! drives the game without a window.
+
+use super::*;
+
+// What a key press does, for deciding whether the OS's key repeat counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyRole {
+    Steer,    // turning the snake
+    Navigate, // moving through a menu, list or timeline
+    Action,   // toggles, restart, confirm and the like
+}
+
+// What holding a key down does. ggez passes on the OS's key repeat as more presses, so
+// each handler says what its keys are for and asks this whether a repeat counts. By
+// default a held direction turns once while menus scroll, and actions never repeat -
+// a held P shouldn't flicker party mode on and off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyRepeat {
+    pub steer: bool,
+    pub navigate: bool,
+}
+
+impl Default for KeyRepeat {
+    fn default() -> Self {
+        Self {
+            steer: false,
+            navigate: true,
+        }
+    }
+}
+
+impl KeyRepeat {
+    pub fn accepts(&self, role: KeyRole, repeat: bool) -> bool {
+        !repeat
+            || match role {
+                KeyRole::Steer => self.steer,
+                KeyRole::Navigate => self.navigate,
+                KeyRole::Action => false,
+            }
+    }
+}
+
+// The keys the d-pad and Start stand in for
+pub fn pad_key(button: gilrs::Button) -> Option<KeyCode> {
+    match button {
+        gilrs::Button::DPadUp => Some(KeyCode::Up),
+        gilrs::Button::DPadDown => Some(KeyCode::Down),
+        gilrs::Button::DPadLeft => Some(KeyCode::Left),
+        gilrs::Button::DPadRight => Some(KeyCode::Right),
+        gilrs::Button::Start => Some(KeyCode::R),
+        gilrs::Button::Select => Some(KeyCode::Space),
+        _ => None,
+    }
+}
+
+pub fn synthetic_key(keycode: KeyCode, mods: KeyMods) -> KeyInput {
+    KeyInput {
+        scancode: 0,
+        keycode: Some(keycode),
+        mods,
+    }
+}
+
+// What a ggez event loop feeds the game, minus the Context - so tests, bots and demos
+// can drive the whole input path (pause, the timeline, key repeat, the turn assist)
+// without a window. The EventHandler impls pass their events on to these
+pub trait Drivable {
+    fn key_down(&mut self, key_input: KeyInput, repeat: bool);
+    fn pad_down(&mut self, button: gilrs::Button);
+    fn advance(&mut self, frame: std::time::Duration); // one frame's worth of time
+}
+
+// Frames are fed in at 60 a second, and a held key starts repeating after half a second,
+// 30 times a second - about what desktop OSes do
+pub const SYNTHETIC_FRAME: std::time::Duration = std::time::Duration::from_micros(16_667);
+pub const SYNTHETIC_REPEAT_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+pub const SYNTHETIC_REPEAT_RATE: std::time::Duration = std::time::Duration::from_micros(33_333);
+
+// One step of a scripted session
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyntheticInput {
+    Press(KeyCode),
+    PressWith(KeyCode, KeyMods),
+    Hold(KeyCode, std::time::Duration), // pressed, then OS repeats until let go
+    Pad(gilrs::Button),
+    Wait(std::time::Duration), // frames go by
+}
+
+// Play a script into `target`, frame by frame
+pub fn drive(target: &mut impl Drivable, script: &[SyntheticInput]) {
+    for input in script {
+        match *input {
+            SyntheticInput::Press(keycode) => {
+                target.key_down(synthetic_key(keycode, KeyMods::empty()), false)
+            }
+            SyntheticInput::PressWith(keycode, mods) => {
+                target.key_down(synthetic_key(keycode, mods), false)
+            }
+            SyntheticInput::Hold(keycode, held) => {
+                let key = synthetic_key(keycode, KeyMods::empty());
+                target.key_down(key, false);
+                let mut waited = std::time::Duration::ZERO;
+                let mut next_repeat = SYNTHETIC_REPEAT_DELAY;
+                while waited < held {
+                    target.advance(SYNTHETIC_FRAME);
+                    waited += SYNTHETIC_FRAME;
+                    while waited >= next_repeat && next_repeat < held {
+                        target.key_down(key, true);
+                        next_repeat += SYNTHETIC_REPEAT_RATE;
+                    }
+                }
+            }
+            SyntheticInput::Pad(button) => target.pad_down(button),
+            SyntheticInput::Wait(time) => {
+                let mut waited = std::time::Duration::ZERO;
+                while waited < time {
+                    target.advance(SYNTHETIC_FRAME);
+                    waited += SYNTHETIC_FRAME;
+                }
+            }
+        }
+    }
+}
+
+// How the touch controls are drawn
+impl TouchControls {
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        config: &GameConfig,
+    ) -> GameResult {
+        if !self.visible {
+            return Ok(());
+        }
+        for (_, area) in Self::buttons(config) {
+            let rect = Rect::new(area.x, area.y, area.w, area.h);
+            let button =
+                Mesh::new_rectangle(ctx, DrawMode::fill(), rect, Color::new(1.0, 1.0, 1.0, 0.15))?;
+            canvas.draw(&button, graphics::DrawParam::default());
+        }
+        Ok(())
+    }
+}
+
+impl EventHandler for GameState {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        self.update(ctx)
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        self.draw(ctx)
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        key_input: KeyInput,
+        repeat: bool,
+    ) -> GameResult {
+        self.key_down(key_input, repeat);
+        if self.menu.quit {
+            ctx.request_quit();
+        }
+        Ok(())
+    }
+
+    fn gamepad_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        button: gilrs::Button,
+        _id: ggez::input::gamepad::GamepadId,
+    ) -> GameResult {
+        self.pad_down(button);
+        if self.menu.quit {
+            ctx.request_quit();
+        }
+        Ok(())
+    }
+}
+
+impl GameState {
+    pub fn is_blocked_in_coop(keycode: KeyCode) -> bool {
+        matches!(
+            keycode,
+            KeyCode::W | KeyCode::S | KeyCode::Left | KeyCode::Right
+        )
+    }
+
+    // A key press, without needing a ggez Context - key_down_event passes everything on
+    // to this
+    pub fn key_down(&mut self, key_input: KeyInput, repeat: bool) {
+        let role = match key_input.keycode {
+            Some(keycode) if self.screen.is_menu() => {
+                UiInput::from_key(keycode).map_or(KeyRole::Action, UiInput::role)
+            }
+            Some(
+                KeyCode::Up
+                | KeyCode::W
+                | KeyCode::Down
+                | KeyCode::S
+                | KeyCode::Left
+                | KeyCode::A
+                | KeyCode::Right
+                | KeyCode::D,
+            ) => KeyRole::Steer,
+            _ => KeyRole::Action,
+        };
+        if !self.key_repeat.accepts(role, repeat) {
+            return;
+        }
+
+        // Any key skips a running transition, and still counts as input
+        self.skip_transition();
+
+        // Any key skips the splash screen, onto the main menu
+        if self.screen == Screen::Splash {
+            self.change_screen_with(Screen::MainMenu, TransitionKind::Fade);
+            return;
+        }
+
+        if self.screen.is_menu() {
+            if let Some(input) = key_input.keycode.and_then(UiInput::from_key) {
+                self.screen_input(input);
+            }
+            return;
+        }
+
+        if let Some(keycode) = key_input.keycode {
+            match keycode {
+                _ if self.coop && Self::is_blocked_in_coop(keycode) => {}
+                KeyCode::Up | KeyCode::W if !self.game_over => {
+                    self.handle_input(Direction::Up);
+                }
+                KeyCode::Down | KeyCode::S if !self.game_over => {
+                    self.handle_input(Direction::Down);
+                }
+                KeyCode::Left | KeyCode::A if !self.game_over => {
+                    self.handle_input(Direction::Left);
+                }
+                KeyCode::Right | KeyCode::D if !self.game_over => {
+                    self.handle_input(Direction::Right);
+                }
+                KeyCode::Space => {
+                    self.pause();
+                }
+                KeyCode::Escape if self.game_over => {
+                    self.screen_input(UiInput::Cancel);
+                }
+                KeyCode::P => {
+                    self.toggle_rainbow();
+                }
+                KeyCode::V => {
+                    self.toggle_haptics();
+                }
+                KeyCode::M => {
+                    self.toggle_mute();
+                }
+                KeyCode::N => {
+                    self.toggle_best_notifications();
+                }
+                KeyCode::T => {
+                    self.toggle_turn_assist();
+                }
+                // Reset game with Ctrl+R or just R
+                KeyCode::R if key_input.mods.contains(KeyMods::CTRL) || !self.game_over => {
+                    self.restart();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // A gamepad button - the d-pad steers, Start does what R does and Select pauses. On
+    // the menus the buttons move around them instead
+    pub fn pad_down(&mut self, button: gilrs::Button) {
+        if self.screen.is_menu() {
+            if let Some(input) = UiInput::from_button(button) {
+                self.screen_input(input);
+            }
+        } else if let Some(keycode) = pad_key(button) {
+            self.key_down(synthetic_key(keycode, KeyMods::empty()), false);
+        }
+    }
+}
+
+impl Drivable for GameState {
+    fn key_down(&mut self, key_input: KeyInput, repeat: bool) {
+        GameState::key_down(self, key_input, repeat);
+    }
+
+    fn pad_down(&mut self, button: gilrs::Button) {
+        GameState::pad_down(self, button);
+    }
+
+    fn advance(&mut self, frame: std::time::Duration) {
+        GameState::advance(self, frame);
+    }
+}
@@ -0,0 +1,538 @@
+//! The screens around the game itself - the main menu, the settings and the pause screen -
+//! and how keys, buttons and taps are routed on them.
+
+use super::*;
+
+// Height of one entry on the main menu and the settings screen, when the board has room
+const MENU_ROW_HEIGHT: f32 = 40.0;
+
+// Where the settings screen's choices are kept, next to the other save data
+pub const SETTINGS_FILE: &str = "settings.toml";
+
+// The boards the settings step through, smallest first
+pub const GRID_PRESETS: [(i32, i32); 4] = [(16, 12), (20, 15), (28, 21), (40, 30)];
+
+// The main menu's entries, top to bottom
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuEntry {
+    Play,
+    Settings,
+    Quit,
+}
+
+impl MenuEntry {
+    pub const ALL: [MenuEntry; 3] = [MenuEntry::Play, MenuEntry::Settings, MenuEntry::Quit];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MenuEntry::Play => "Play",
+            MenuEntry::Settings => "Settings",
+            MenuEntry::Quit => "Quit",
+        }
+    }
+}
+
+// Which entry has the focus on the main menu or the settings screen, and whether Quit was
+// picked - whoever runs the window closes it
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MainMenu {
+    pub focus: usize,
+    pub quit: bool,
+}
+
+// What can be changed on the settings screen, top to bottom
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    Speed,
+    Walls,
+    Theme,
+    Grid,
+    Sound,
+    Vibration,
+    ReducedMotion,
+    Hud,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 8] = [
+        Setting::Speed,
+        Setting::Walls,
+        Setting::Theme,
+        Setting::Grid,
+        Setting::Sound,
+        Setting::Vibration,
+        Setting::ReducedMotion,
+        Setting::Hud,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Setting::Speed => "Speed",
+            Setting::Walls => "Walls",
+            Setting::Theme => "Colors",
+            Setting::Grid => "Board",
+            Setting::Sound => "Sound",
+            Setting::Vibration => "Vibration",
+            Setting::ReducedMotion => "Reduced motion",
+            Setting::Hud => "Score display",
+        }
+    }
+}
+
+// Everything on the settings screen, as kept in settings.toml and read at startup. Flags
+// and SNAKE_* variables still win for the board size, and a theme file for the colors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    pub speed: SpeedPreset,
+    pub walls: WallMode,
+    pub theme: ThemePreset,
+    pub grid: (i32, i32),
+    pub sound: bool,
+    pub vibration: bool,
+    pub reduced_motion: bool,
+    pub show_hud: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            speed: SpeedPreset::Normal,
+            walls: WallMode::Solid,
+            theme: ThemePreset::Classic,
+            grid: (GRID_WIDTH, GRID_HEIGHT),
+            sound: true,
+            vibration: true,
+            reduced_motion: false,
+            show_hud: true,
+        }
+    }
+}
+
+// The entry of `all` called `name`
+fn named<T: Copy>(all: &[T], name: impl Fn(T) -> &'static str, value: &str) -> Option<T> {
+    all.iter().copied().find(|item| name(*item) == value)
+}
+
+// The entry after (or before) `current` in `all`, wrapping around the ends
+fn cycle<T: Copy + PartialEq>(all: &[T], current: T, forward: bool) -> T {
+    let index = all.iter().position(|item| *item == current).unwrap_or(0);
+    let step = if forward { 1 } else { all.len() - 1 };
+    all[(index + step) % all.len()]
+}
+
+impl Settings {
+    // Flat TOML: `speed`, `walls`, `theme` and `grid` (as "WIDTHxHEIGHT") are strings, the
+    // rest true or false. The quotes around strings can be left off
+    pub fn parse(text: &str) -> Result<Settings, String> {
+        let mut settings = Settings::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = match line.find(" # ") {
+                Some(comment) => &line[..comment],
+                None if line.trim_start().starts_with('#') => "",
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fail = |message: String| format!("line {}: {}", number + 1, message);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| fail(format!("expected `key = value`, got `{}`", line)))?;
+            let (key, value) = (key.trim(), value.trim().trim_matches('"'));
+            let unknown = || fail(format!("unknown {} `{}`", key, value));
+            let flag = || {
+                value
+                    .parse::<bool>()
+                    .map_err(|_| fail(format!("{} should be true or false, got `{}`", key, value)))
+            };
+            match key {
+                "speed" => {
+                    settings.speed =
+                        named(&SpeedPreset::ALL, SpeedPreset::name, value).ok_or_else(unknown)?
+                }
+                "walls" => {
+                    settings.walls =
+                        named(&WallMode::ALL, WallMode::name, value).ok_or_else(unknown)?
+                }
+                "theme" => {
+                    settings.theme =
+                        named(&ThemePreset::ALL, ThemePreset::name, value).ok_or_else(unknown)?
+                }
+                "grid" => {
+                    let size = value.split_once('x').and_then(|(width, height)| {
+                        Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+                    });
+                    let (width, height) = size.ok_or_else(|| {
+                        fail(format!("grid should look like \"20x15\", got `{}`", value))
+                    })?;
+                    GameConfig::new(width, height, CELL_SIZE).map_err(fail)?;
+                    settings.grid = (width, height);
+                }
+                "sound" => settings.sound = flag()?,
+                "vibration" => settings.vibration = flag()?,
+                "reduced_motion" => settings.reduced_motion = flag()?,
+                "show_hud" => settings.show_hud = flag()?,
+                _ => return Err(fail(format!("unknown key `{}`", key))),
+            }
+        }
+        Ok(settings)
+    }
+
+    pub fn to_text(&self) -> String {
+        format!(
+            "speed = \"{}\"\nwalls = \"{}\"\ntheme = \"{}\"\ngrid = \"{}x{}\"\nsound = {}\nvibration = {}\nreduced_motion = {}\nshow_hud = {}\n",
+            self.speed.name(),
+            self.walls.name(),
+            self.theme.name(),
+            self.grid.0,
+            self.grid.1,
+            self.sound,
+            self.vibration,
+            self.reduced_motion,
+            self.show_hud
+        )
+    }
+
+    // The saved settings, or the default if there aren't any or they don't parse
+    pub fn load(storage: &dyn StorageBackend) -> Settings {
+        let Some(text) = storage.read(SETTINGS_FILE) else {
+            return Settings::default();
+        };
+        Settings::parse(&text).unwrap_or_else(|e| {
+            eprintln!("Ignoring {}: {}", SETTINGS_FILE, e);
+            Settings::default()
+        })
+    }
+
+    pub fn save(&self, storage: &dyn StorageBackend) {
+        if let Err(e) = storage.write(SETTINGS_FILE, &self.to_text()) {
+            eprintln!("Failed to save the settings: {}", e);
+        }
+    }
+}
+
+impl Screen {
+    // Whether keys on this screen move around a menu rather than steer
+    pub fn is_menu(self) -> bool {
+        matches!(self, Screen::MainMenu | Screen::Settings | Screen::Paused)
+    }
+}
+
+// Move a focus up or down a list of `count` entries, wrapping around the ends
+fn move_focus(focus: usize, count: usize, input: UiInput) -> usize {
+    match input {
+        UiInput::Up => (focus + count - 1) % count,
+        UiInput::Down => (focus + 1) % count,
+        _ => focus,
+    }
+}
+
+impl GameState {
+    // What a setting is at, as the settings screen shows it
+    pub fn setting_text(&self, setting: Setting) -> String {
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        match setting {
+            Setting::Speed => self.settings.speed.name().to_string(),
+            Setting::Walls => self.settings.walls.name().to_string(),
+            Setting::Theme => self.settings.theme.name().to_string(),
+            Setting::Grid => {
+                let (width, height) = self.settings.grid;
+                // a new board size needs a new window, so it waits for the next launch
+                if (width, height) == (self.config.grid_width, self.config.grid_height) {
+                    format!("{}x{}", width, height)
+                } else {
+                    format!("{}x{} (next launch)", width, height)
+                }
+            }
+            Setting::Sound => on_off(!self.mixer.muted),
+            Setting::Vibration => on_off(self.haptics),
+            Setting::ReducedMotion => on_off(self.reduced_motion),
+            Setting::Hud => on_off(self.show_hud),
+        }
+    }
+
+    // Step a setting to its next (or previous) choice and save the lot. Speed and walls
+    // wait for the next run rather than change the rules of this one
+    pub fn change_setting(&mut self, setting: Setting, forward: bool) {
+        match setting {
+            Setting::Speed => {
+                self.settings.speed = cycle(&SpeedPreset::ALL, self.settings.speed, forward)
+            }
+            Setting::Walls => {
+                self.settings.walls = cycle(&WallMode::ALL, self.settings.walls, forward)
+            }
+            Setting::Theme => {
+                self.settings.theme = cycle(&ThemePreset::ALL, self.settings.theme, forward);
+                self.theme = self.settings.theme.theme();
+            }
+            Setting::Grid => self.settings.grid = cycle(&GRID_PRESETS, self.settings.grid, forward),
+            Setting::Sound => self.toggle_mute(),
+            Setting::Vibration => self.toggle_haptics(),
+            Setting::ReducedMotion => self.reduced_motion = !self.reduced_motion,
+            Setting::Hud => self.show_hud = !self.show_hud,
+        }
+        self.save_settings();
+    }
+
+    // Take on saved settings: the colors and switches straight away, and the speed and
+    // walls too unless a run is under way or the levels set their own
+    pub fn apply_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+        self.theme = settings.theme.theme();
+        self.mixer.muted = !settings.sound;
+        self.haptics = settings.vibration;
+        self.reduced_motion = settings.reduced_motion;
+        self.show_hud = settings.show_hud;
+        if self.levels.is_none() && self.elapsed == 0.0 {
+            self.config.walls = settings.walls;
+            self.config.speed = settings.speed;
+            self.speed = settings.speed.start();
+            // each set of rules keeps its own best
+            self.load_records();
+        }
+    }
+
+    // Write settings.toml, picking up switches flipped outside the settings screen
+    pub fn save_settings(&mut self) {
+        self.settings.sound = !self.mixer.muted;
+        self.settings.vibration = self.haptics;
+        self.settings.reduced_motion = self.reduced_motion;
+        self.settings.show_hud = self.show_hud;
+        self.settings.save(self.storage.as_ref());
+    }
+
+    // Go to the main menu or the settings with the focus on the first entry
+    fn open_screen(&mut self, screen: Screen) {
+        self.menu.focus = 0;
+        self.change_screen_with(screen, TransitionKind::Fade);
+    }
+
+    // Play from the main menu: carry on with a run left from the pause screen, or start a
+    // new one if the last is over
+    pub fn play(&mut self) {
+        if self.game_over {
+            self.restart();
+        } else {
+            self.change_screen_with(Screen::Playing, TransitionKind::Fade);
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if self.screen == Screen::Playing && !self.game_over {
+            self.change_screen(Screen::Paused);
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.screen == Screen::Paused {
+            self.change_screen(Screen::Playing);
+        }
+    }
+
+    // Do what a main menu entry says
+    pub fn pick(&mut self, entry: MenuEntry) {
+        match entry {
+            MenuEntry::Play => self.play(),
+            MenuEntry::Settings => self.open_screen(Screen::Settings),
+            MenuEntry::Quit => self.menu.quit = true,
+        }
+    }
+
+    // A menu move on the screens around the game. Returns the main menu entry picked, if
+    // any, so whoever hosts the game can follow up on it
+    pub fn screen_input(&mut self, input: UiInput) -> Option<MenuEntry> {
+        match (self.screen, input) {
+            (Screen::MainMenu, UiInput::Up | UiInput::Down) => {
+                self.menu.focus = move_focus(self.menu.focus, MenuEntry::ALL.len(), input);
+            }
+            (Screen::MainMenu, UiInput::Accept) => {
+                let entry = MenuEntry::ALL[self.menu.focus];
+                self.pick(entry);
+                return Some(entry);
+            }
+            (Screen::MainMenu, UiInput::Cancel) => {
+                self.pick(MenuEntry::Quit);
+                return Some(MenuEntry::Quit);
+            }
+            (Screen::Settings, UiInput::Up | UiInput::Down) => {
+                self.menu.focus = move_focus(self.menu.focus, Setting::ALL.len(), input);
+            }
+            (Screen::Settings, UiInput::Accept | UiInput::Right) => {
+                self.change_setting(Setting::ALL[self.menu.focus], true);
+            }
+            (Screen::Settings, UiInput::Left) => {
+                self.change_setting(Setting::ALL[self.menu.focus], false);
+            }
+            (Screen::Settings, UiInput::Cancel) => {
+                self.open_screen(Screen::MainMenu);
+                self.menu.focus = MenuEntry::Settings as usize;
+            }
+            (Screen::Paused, UiInput::Accept) => self.resume(),
+            (Screen::Paused | Screen::GameOver, UiInput::Cancel) => {
+                self.open_screen(Screen::MainMenu)
+            }
+            _ => {}
+        }
+        None
+    }
+
+    // A tap on the screens around the game picks or switches the entry under it, and
+    // carries on from the pause screen. Tapping off the settings goes back to the menu
+    pub fn screen_tap(&mut self, x: f32, y: f32) -> Option<MenuEntry> {
+        let count = match self.screen {
+            Screen::MainMenu => MenuEntry::ALL.len(),
+            Screen::Settings => Setting::ALL.len(),
+            Screen::Paused => return self.screen_input(UiInput::Accept),
+            _ => return None,
+        };
+        match (0..count).find(|index| self.menu_row(*index, count).contains([x, y])) {
+            Some(index) => {
+                self.menu.focus = index;
+                self.screen_input(UiInput::Accept)
+            }
+            None if self.screen == Screen::Settings => self.screen_input(UiInput::Cancel),
+            None => None,
+        }
+    }
+
+    // Where a menu's entry goes in a list of `count`: a column of rows from just above the
+    // middle of the board, squeezed so a long list still fits
+    fn menu_row(&self, index: usize, count: usize) -> Rect {
+        let (screen_width, screen_height) =
+            (self.config.screen_width(), self.config.screen_height());
+        let width = screen_width * 0.6;
+        let height = MENU_ROW_HEIGHT.min(screen_height * 0.6 / count as f32);
+        // leave room under the rows for the help line
+        let top = (screen_height / 2.0 - MENU_ROW_HEIGHT)
+            .min(screen_height - 60.0 - count as f32 * height);
+        Rect::new(
+            (screen_width - width) / 2.0,
+            top + index as f32 * height,
+            width,
+            height,
+        )
+    }
+
+    // The title over a menu of `count` entries, a quarter of the way down or higher if the
+    // rows need the room
+    fn draw_menu_title(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        title: &str,
+        color: Color,
+        count: usize,
+    ) -> GameResult {
+        let screen_width = self.config.screen_width();
+        let y = (self.config.screen_height() / 4.0).min(self.menu_row(0, count).y - 70.0);
+        self.text_cache
+            .draw(ctx, canvas, title, 56.0, color, |bounds| {
+                [(screen_width - bounds.x) / 2.0, y]
+            })?;
+        Ok(())
+    }
+
+    // A menu's entries, the focused one highlighted, and a line of help under them
+    fn draw_menu_rows(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        labels: &[String],
+        help: &str,
+    ) -> GameResult {
+        let count = labels.len();
+        for (index, label) in labels.iter().enumerate() {
+            let row = self.menu_row(index, count);
+            let color = if index == self.menu.focus {
+                let highlight = Mesh::new_rectangle(
+                    ctx,
+                    DrawMode::fill(),
+                    row,
+                    Color::new(1.0, 1.0, 1.0, 0.15),
+                )?;
+                canvas.draw(&highlight, graphics::DrawParam::default());
+                Color::YELLOW
+            } else {
+                Color::WHITE
+            };
+            self.text_cache.draw(
+                ctx,
+                canvas,
+                label,
+                (row.h * 0.6).min(24.0),
+                color,
+                |bounds| {
+                    [
+                        row.x + (row.w - bounds.x) / 2.0,
+                        row.y + (row.h - bounds.y) / 2.0,
+                    ]
+                },
+            )?;
+        }
+        let below = self.menu_row(count, count);
+        let screen_width = self.config.screen_width();
+        self.text_cache.draw(
+            ctx,
+            canvas,
+            help,
+            16.0,
+            Color::new(1.0, 1.0, 1.0, 0.6),
+            |bounds| [(screen_width - bounds.x) / 2.0, below.y + 20.0],
+        )?;
+        Ok(())
+    }
+
+    pub(super) fn draw_main_menu(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+    ) -> GameResult {
+        self.draw_menu_title(ctx, canvas, "SNAKE", Color::GREEN, MenuEntry::ALL.len())?;
+        let labels = MenuEntry::ALL.map(|entry| entry.label().to_string());
+        self.draw_menu_rows(ctx, canvas, &labels, "Arrows to choose, Enter to pick")
+    }
+
+    pub(super) fn draw_settings(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+    ) -> GameResult {
+        self.draw_menu_title(ctx, canvas, "SETTINGS", Color::WHITE, Setting::ALL.len())?;
+        let labels = Setting::ALL
+            .map(|setting| format!("{}: {}", setting.label(), self.setting_text(setting)));
+        self.draw_menu_rows(ctx, canvas, &labels, "Left/Right to change, Esc to go back")
+    }
+
+    // The board dimmed behind the word PAUSED
+    pub(super) fn draw_pause(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+    ) -> GameResult {
+        let (screen_width, screen_height) =
+            (self.config.screen_width(), self.config.screen_height());
+        let dim = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, screen_width, screen_height),
+            Color::new(0.0, 0.0, 0.0, 0.6),
+        )?;
+        canvas.draw(&dim, graphics::DrawParam::default());
+        let center = |bounds: mint::Vector2<f32>, y: f32| [(screen_width - bounds.x) / 2.0, y];
+        let title_y = screen_height / 2.0 - 40.0;
+        self.text_cache
+            .draw(ctx, canvas, "PAUSED", 48.0, Color::WHITE, |bounds| {
+                center(bounds, title_y)
+            })?;
+        self.text_cache.draw(
+            ctx,
+            canvas,
+            "Space to carry on, Esc for the menu",
+            18.0,
+            Color::YELLOW,
+            |bounds| center(bounds, title_y + 60.0),
+        )?;
+        Ok(())
+    }
+}
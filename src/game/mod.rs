@@ -0,0 +1,9754 @@
+//! Everything that needs std: the full game and its modes, saves, networking and
+//! audio. Drawing is in `render`, input in `input`, the menus in `menu` and the level
+//! editor in `editor`. A run itself - the snake, food, score and what a tick does - is
+//! [`crate::core::Run`], on top of the bare rules in [`crate::rules`]; both build without
+//! std.
+
+use crate::core::*;
+use crate::rules::{
+    self, Direction, GameConfig, Position, Speed, SpeedPreset, TickClock, WallMode, CELL_SIZE,
+    GRID_HEIGHT, GRID_WIDTH, MAX_CELLS_PER_SECOND, MAX_GRID_SIDE,
+};
+use crate::storage::*;
+use crate::synth;
+use ggez::event::{EventHandler, MouseButton};
+use ggez::graphics::{self, Color, DrawMode, Mesh, Rect, Text, TextFragment};
+use ggez::input::gamepad::gilrs;
+use ggez::input::keyboard::{KeyCode, KeyInput, KeyMods};
+use ggez::winit::event::TouchPhase;
+use ggez::{mint, Context, GameResult};
+use rand::Rng;
+use std::sync::Arc;
+
+mod editor;
+mod input;
+mod menu;
+mod net;
+mod render;
+#[cfg(feature = "tui")]
+mod tui;
+pub use editor::*;
+pub use input::*;
+pub use menu::*;
+pub use net::*;
+pub use render::*;
+#[cfg(feature = "tui")]
+pub use tui::*;
+
+// Which screen the game is showing - update/draw/input behave differently per screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Screen {
+    Splash,
+    MainMenu,
+    Playing,
+    Paused,
+    GameOver,
+    Settings,
+}
+
+// The different ways one screen can hand over to the next
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransitionKind {
+    Fade,  // black cover that fades away
+    Wipe,  // black cover that shrinks from left to right
+    Slide, // black cover that slides down off the screen
+}
+
+// A transition in progress - drawn as a cover over the new screen that gradually reveals it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    pub elapsed: f64,
+    pub duration: f64,
+}
+
+impl Transition {
+    pub fn new(kind: TransitionKind, duration: f64) -> Self {
+        Self {
+            kind,
+            elapsed: 0.0,
+            duration,
+        }
+    }
+
+    pub fn advance(&mut self, delta: f64) {
+        self.elapsed += delta;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    // How much of the new screen is still hidden, from 1.0 (all of it) down to 0.0
+    pub fn remaining(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 0.0;
+        }
+        (1.0 - self.elapsed / self.duration).clamp(0.0, 1.0) as f32
+    }
+}
+
+pub const TRANSITION_DURATION: f64 = 0.4;
+
+// Colors used to draw the board
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub background: Color,
+    pub snake_head: Color, // the snake fades from head color to tail color along its body
+    pub snake_tail: Color,
+    pub food: Color,
+    pub rainbow: bool,         // party mode - the snake cycles through hues
+    pub day_night_period: f64, // seconds for a full dawn -> day -> dusk -> night cycle, 0 turns it off
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            background: Color::BLACK,
+            snake_head: Color::GREEN,
+            snake_tail: Color::new(0.0, 0.45, 0.2, 1.0),
+            food: Color::RED,
+            rainbow: false,
+            day_night_period: 240.0,
+        }
+    }
+}
+
+// The color schemes the settings offer. A theme file still wins over any of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemePreset {
+    Classic,
+    Ocean,
+    Ember,
+    Mono,
+}
+
+impl ThemePreset {
+    pub const ALL: [ThemePreset; 4] = [
+        ThemePreset::Classic,
+        ThemePreset::Ocean,
+        ThemePreset::Ember,
+        ThemePreset::Mono,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ThemePreset::Classic => "classic",
+            ThemePreset::Ocean => "ocean",
+            ThemePreset::Ember => "ember",
+            ThemePreset::Mono => "mono",
+        }
+    }
+
+    pub fn theme(self) -> Theme {
+        let (snake_head, snake_tail, food) = match self {
+            ThemePreset::Classic => return Theme::default(),
+            ThemePreset::Ocean => (
+                Color::new(0.3, 0.85, 1.0, 1.0),
+                Color::new(0.05, 0.3, 0.6, 1.0),
+                Color::new(1.0, 0.5, 0.4, 1.0),
+            ),
+            ThemePreset::Ember => (
+                Color::new(1.0, 0.6, 0.1, 1.0),
+                Color::new(0.55, 0.1, 0.05, 1.0),
+                Color::new(1.0, 0.95, 0.4, 1.0),
+            ),
+            ThemePreset::Mono => (
+                Color::WHITE,
+                Color::new(0.35, 0.35, 0.35, 1.0),
+                Color::new(0.75, 0.75, 0.75, 1.0),
+            ),
+        };
+        Theme {
+            snake_head,
+            snake_tail,
+            food,
+            ..Theme::default()
+        }
+    }
+}
+
+// Background tints for the day/night cycle, kept dark so the snake and food stay readable
+const DAY_NIGHT_PALETTE: [Color; 4] = [
+    Color::new(0.12, 0.07, 0.14, 1.0), // dawn
+    Color::new(0.05, 0.10, 0.16, 1.0), // day
+    Color::new(0.14, 0.07, 0.04, 1.0), // dusk
+    Color::new(0.0, 0.0, 0.0, 1.0),    // night
+];
+
+impl Theme {
+    // Background for a point in the run, easing through the day/night palette
+    pub fn background_at(&self, elapsed: f64) -> Color {
+        if self.day_night_period <= 0.0 {
+            return self.background;
+        }
+        let phase = (elapsed / self.day_night_period).rem_euclid(1.0) * 4.0;
+        let from = phase as usize % 4;
+        let to = (from + 1) % 4;
+        lerp_color(
+            DAY_NIGHT_PALETTE[from],
+            DAY_NIGHT_PALETTE[to],
+            phase.fract() as f32,
+        )
+    }
+
+    // Color for a segment, blending from head to tail by how far down the body it is
+    pub fn segment_color(&self, index: usize, length: usize) -> Color {
+        let t = if length > 1 {
+            index as f32 / (length - 1) as f32
+        } else {
+            0.0
+        };
+        lerp_color(self.snake_head, self.snake_tail, t)
+    }
+}
+
+pub const THEME_FILE: &str = "theme.txt";
+
+impl Theme {
+    // Read a theme file of `key = value` lines, e.g. `food = #ff8800` or
+    // `background = 0.1 0.1 0.2`. Keys left out keep their default, and comments
+    // start with `# ` (a `#` straight before a value is a hex color)
+    pub fn parse(text: &str) -> Result<Theme, String> {
+        let mut theme = Theme::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = match line.find(" # ") {
+                Some(comment) => &line[..comment],
+                None if line.trim_start().starts_with('#') => "",
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fail = |message: String| format!("line {}: {}", number + 1, message);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| fail(format!("expected `key = value`, got `{}`", line)))?;
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "background" => theme.background = parse_color(value).map_err(fail)?,
+                "snake_head" => theme.snake_head = parse_color(value).map_err(fail)?,
+                "snake_tail" => theme.snake_tail = parse_color(value).map_err(fail)?,
+                "food" => theme.food = parse_color(value).map_err(fail)?,
+                "day_night_period" => {
+                    theme.day_night_period = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|period| *period >= 0.0)
+                        .ok_or_else(|| fail(format!("bad day_night_period `{}`", value)))?
+                }
+                _ => return Err(fail(format!("unknown key `{}`", key))),
+            }
+        }
+        Ok(theme)
+    }
+
+    pub fn load(path: &str) -> Result<Theme, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Theme::parse(&text)
+    }
+}
+
+// A color as `#rrggbb` or three 0.0-1.0 floats
+fn parse_color(value: &str) -> Result<Color, String> {
+    let bad = || format!("bad color `{}`", value);
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(bad());
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+        return match (channel(0), channel(2), channel(4)) {
+            (Ok(r), Ok(g), Ok(b)) => Ok(Color::from_rgb(r, g, b)),
+            _ => Err(bad()),
+        };
+    }
+    let channels: Vec<f32> = value
+        .split_whitespace()
+        .map(|channel| channel.parse::<f32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| bad())?;
+    match channels[..] {
+        [r, g, b] if channels.iter().all(|c| (0.0..=1.0).contains(c)) => {
+            Ok(Color::new(r, g, b, 1.0))
+        }
+        _ => Err(bad()),
+    }
+}
+
+// Watches the theme file, so edits show up without restarting the game
+pub struct ThemeWatcher {
+    pub path: String,
+    _watcher: notify::RecommendedWatcher,
+    events: std::sync::mpsc::Receiver<notify::Result<notify::Event>>,
+}
+
+impl ThemeWatcher {
+    pub fn new(path: &str) -> notify::Result<Self> {
+        use notify::Watcher;
+
+        // watch the folder rather than the file - editors often save by replacing it
+        let (sender, events) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        let file = std::path::Path::new(path);
+        let dir = match file.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => std::path::Path::new("."),
+        };
+        watcher.watch(dir, notify::RecursiveMode::NonRecursive)?;
+        Ok(Self {
+            path: path.to_string(),
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    // Whether the file was written, created or replaced since the last check
+    pub fn changed(&self) -> bool {
+        let name = std::path::Path::new(&self.path).file_name();
+        let mut changed = false;
+        while let Ok(event) = self.events.try_recv() {
+            changed |= event.is_ok_and(|event| {
+                !event.kind.is_access() && event.paths.iter().any(|path| path.file_name() == name)
+            });
+        }
+        changed
+    }
+}
+
+pub const TOAST_DURATION: f64 = 3.0;
+
+// A short message shown over the board, e.g. after reloading the theme
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toast {
+    pub message: String,
+    pub time_left: f64,
+}
+
+pub const MODS_DIR: &str = "mods";
+pub const PACK_MANIFEST: &str = "manifest.toml";
+
+// What a content pack says about itself in its manifest.toml
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackManifest {
+    pub name: String, // how the pack is picked, letters, digits, `-` and `_` only
+    pub version: String,
+    pub author: String,
+    pub description: String,
+}
+
+impl PackManifest {
+    // Only the flat `key = "value"` part of TOML, which is all a manifest needs
+    pub fn parse(text: &str) -> Result<PackManifest, String> {
+        let mut fields: Vec<(&str, String)> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fail = |message: String| format!("line {}: {}", number + 1, message);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| fail(format!("expected `key = \"value\"`, got `{}`", line)))?;
+            let key = key.trim();
+            if !["name", "version", "author", "description"].contains(&key) {
+                return Err(fail(format!("unknown key `{}`", key)));
+            }
+            if fields.iter().any(|(seen, _)| *seen == key) {
+                return Err(fail(format!("`{}` is set twice", key)));
+            }
+            let value = value
+                .trim()
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .filter(|value| !value.contains('"'))
+                .ok_or_else(|| fail(format!("`{}` needs a quoted string", key)))?;
+            fields.push((key, value.to_string()));
+        }
+        let field = |key: &str| {
+            fields
+                .iter()
+                .find(|(seen, _)| *seen == key)
+                .map(|(_, value)| value.clone())
+        };
+        let name = field("name").ok_or("missing `name`")?;
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "name `{}` can only use letters, digits, `-` and `_`",
+                name
+            ));
+        }
+        Ok(PackManifest {
+            name,
+            version: field("version").ok_or("missing `version`")?,
+            author: field("author").unwrap_or_default(),
+            description: field("description").unwrap_or_default(),
+        })
+    }
+}
+
+// A pack folder from the mods directory. So far a pack can bring its own theme.txt,
+// a start.txt saying where the snake starts, and a feedback.txt changing what events set off
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pack {
+    pub dir: std::path::PathBuf,
+    pub manifest: PackManifest,
+    pub theme: Option<Theme>,
+    pub start: Option<Spawns>,
+    pub feedback: Option<FeedbackRouter>,
+}
+
+impl Pack {
+    pub fn load(dir: &std::path::Path) -> Result<Pack, PackError> {
+        let invalid = |reason: String| PackError::Invalid {
+            dir: dir.to_path_buf(),
+            reason,
+        };
+        let manifest = std::fs::read_to_string(dir.join(PACK_MANIFEST))
+            .map_err(|e| invalid(format!("can't read {}: {}", PACK_MANIFEST, e)))?;
+        let manifest = PackManifest::parse(&manifest)
+            .map_err(|e| invalid(format!("{}: {}", PACK_MANIFEST, e)))?;
+        let theme_path = dir.join(THEME_FILE);
+        let theme = if theme_path.exists() {
+            let theme = Theme::load(&theme_path.to_string_lossy())
+                .map_err(|e| invalid(format!("{}: {}", THEME_FILE, e)))?;
+            Some(theme)
+        } else {
+            None
+        };
+        let start_path = dir.join(START_FILE);
+        let start = if start_path.exists() {
+            let start = Spawns::load(&start_path.to_string_lossy())
+                .map_err(|e| invalid(format!("{}: {}", START_FILE, e)))?;
+            Some(start)
+        } else {
+            None
+        };
+        let feedback_path = dir.join(FEEDBACK_FILE);
+        let feedback = if feedback_path.exists() {
+            let feedback = std::fs::read_to_string(&feedback_path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| FeedbackRouter::parse(&text))
+                .map_err(|e| invalid(format!("{}: {}", FEEDBACK_FILE, e)))?;
+            Some(feedback)
+        } else {
+            None
+        };
+        Ok(Pack {
+            dir: dir.to_path_buf(),
+            manifest,
+            theme,
+            start,
+            feedback,
+        })
+    }
+
+    pub fn theme_path(&self) -> String {
+        self.dir.join(THEME_FILE).to_string_lossy().into_owned()
+    }
+
+    // Put the pack's content into a game
+    pub fn apply(&self, game: &mut GameState) {
+        if let Some(theme) = self.theme {
+            game.theme = theme;
+        }
+        if let Some(start) = &self.start {
+            // a start only has to fit the board it's played on
+            match start.fit(&game.config) {
+                Ok(()) => game.set_spawns(start.clone()),
+                Err(e) => eprintln!(
+                    "Not using {}'s {} on this board: {}",
+                    self.manifest.name, START_FILE, e
+                ),
+            }
+        }
+    }
+}
+
+// Why a pack was left out when scanning the mods directory
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackError {
+    Invalid {
+        dir: std::path::PathBuf,
+        reason: String,
+    },
+    Conflict {
+        name: String,
+        dir: std::path::PathBuf, // the folder that lost out
+    },
+}
+
+impl std::fmt::Display for PackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PackError::Invalid { dir, reason } => {
+                write!(f, "skipping {}: {}", dir.display(), reason)
+            }
+            PackError::Conflict { name, dir } => write!(
+                f,
+                "skipping {}: another pack is already called `{}`",
+                dir.display(),
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PackError {}
+
+// Every pack folder in `dir`, in folder name order. Broken packs are skipped and when
+// two share a name the first one wins - the rest come back as errors to report
+pub fn discover_packs(dir: &str) -> (Vec<Pack>, Vec<PackError>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return (Vec::new(), Vec::new()); // no mods folder, no packs
+    };
+    let mut dirs: Vec<std::path::PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+
+    let mut packs: Vec<Pack> = Vec::new();
+    let mut errors = Vec::new();
+    for dir in dirs {
+        match Pack::load(&dir) {
+            Ok(pack) if packs.iter().any(|p| p.manifest.name == pack.manifest.name) => {
+                errors.push(PackError::Conflict {
+                    name: pack.manifest.name,
+                    dir,
+                });
+            }
+            Ok(pack) => packs.push(pack),
+            Err(e) => errors.push(e),
+        }
+    }
+    (packs, errors)
+}
+
+pub const LEVELS_DIR: &str = "levels";
+// How long the level complete overlay stays up before the next level starts
+pub const LEVEL_COMPLETE_DURATION: f64 = 2.5;
+
+// One level of a level run: its own board, walls and starting speed, and the score that
+// clears it. Level files are TOML, with the walls drawn as a multi-line string - `#` for
+// a wall and `.` for open floor, a row per line:
+//   name = "Pillars"
+//   size = "12x8"
+//   speed = 6
+//   target_score = 10
+//   walls = """
+//   ............
+//   ..#......#..
+//   ...
+//   """
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    pub name: String,
+    pub config: GameConfig,
+    pub walls: Vec<Position>,
+    pub speed: Speed,
+    pub target_score: u32, // points scored on this level that clear it
+    pub seed: Option<u64>, // what a generated level was made from, so it can be made again
+}
+
+// A level file as it's written, before Level::parse checks it
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelFile {
+    name: String,
+    size: Option<String>,
+    speed: Option<u32>, // cells per second
+    target_score: Option<u32>,
+    walls: Option<String>,
+}
+
+// How random levels are grown: scatter walls, smooth them into blobs a few times, then
+// keep the first try whose walls cover a fair share of the board
+const GENERATOR_WALL_CHANCE: f64 = 0.42;
+const GENERATOR_SMOOTHING: usize = 3;
+const GENERATOR_WALL_SHARE: std::ops::RangeInclusive<f64> = 0.08..=0.3;
+const GENERATOR_ATTEMPTS: usize = 100;
+pub const GENERATED_TARGET_SCORE: u32 = 100;
+
+impl Level {
+    pub fn parse(text: &str) -> Result<Level, String> {
+        let file: LevelFile = toml::from_str(text).map_err(|e| e.to_string())?;
+
+        let config = match &file.size {
+            Some(size) => {
+                let (width, height) = size
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                    .ok_or_else(|| format!("size should look like 20x15, not `{}`", size))?;
+                GameConfig::new(width, height, CELL_SIZE)?
+            }
+            None => GameConfig::default(),
+        };
+        let speed = match file.speed {
+            Some(cells @ 1..=MAX_CELLS_PER_SECOND) => Speed::cells_per_second(cells),
+            Some(_) => {
+                return Err(format!(
+                    "`speed` has to be from 1 to {}",
+                    MAX_CELLS_PER_SECOND
+                ))
+            }
+            None => Speed::START,
+        };
+        let target_score = match file.target_score {
+            Some(0) | None => return Err("`target_score` has to be at least 1".to_string()),
+            Some(score) => score,
+        };
+
+        let mut walls = Vec::new();
+        if let Some(map) = &file.walls {
+            let rows: Vec<&str> = map.lines().map(str::trim).collect();
+            if rows.len() != config.grid_height as usize {
+                return Err(format!(
+                    "the wall map has {} rows but the board is {} high",
+                    rows.len(),
+                    config.grid_height
+                ));
+            }
+            for (y, row) in rows.iter().enumerate() {
+                if row.chars().count() != config.grid_width as usize {
+                    return Err(format!(
+                        "wall row {} should be {} wide",
+                        y + 1,
+                        config.grid_width
+                    ));
+                }
+                for (x, cell) in row.chars().enumerate() {
+                    match cell {
+                        '#' => walls.push(Position::new(x as i32, y as i32)),
+                        '.' => {}
+                        other => {
+                            return Err(format!(
+                                "wall row {} has `{}` - use `#` for walls and `.` for floor",
+                                y + 1,
+                                other
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+
+        // every run of the level starts in the middle, so that and the first move need to be clear
+        let start = SnakeStart::centered(&config);
+        let mut needed = start.cells();
+        needed.push(start.head.move_in_direction(start.direction));
+        if let Some(cell) = needed.iter().find(|cell| walls.contains(cell)) {
+            return Err(format!(
+                "the wall at {} is in the snake's way at the start",
+                cell.to_text()
+            ));
+        }
+
+        let level = Level {
+            name: file.name,
+            config,
+            walls,
+            speed,
+            target_score,
+            seed: None,
+        };
+        // food can land on any floor, so the snake has to be able to get to all of it
+        if let Some(cell) = level.unreachable().first() {
+            return Err(format!(
+                "the floor at {} is walled off from the start",
+                cell.to_text()
+            ));
+        }
+        Ok(level)
+    }
+
+    // Floor cells the snake can't get to from where it starts, row by row
+    pub fn unreachable(&self) -> Vec<Position> {
+        let config = &self.config;
+        let index = |cell: Position| (cell.y * config.grid_width + cell.x) as usize;
+        let mut seen = vec![false; config.cell_count()];
+        for wall in &self.walls {
+            seen[index(*wall)] = true;
+        }
+        let start = SnakeStart::centered(config).head;
+        let mut queue = std::collections::VecDeque::from([start]);
+        seen[index(start)] = true;
+        while let Some(cell) = queue.pop_front() {
+            for direction in [
+                Direction::Up,
+                Direction::Down,
+                Direction::Left,
+                Direction::Right,
+            ] {
+                let next = cell.move_in_direction(direction);
+                if config.contains(next) && !seen[index(next)] {
+                    seen[index(next)] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        (0..config.grid_height)
+            .flat_map(|y| (0..config.grid_width).map(move |x| Position::new(x, y)))
+            .filter(|cell| !seen[index(*cell)])
+            .collect()
+    }
+
+    // A level of wall blobs on a board the config's size, the same every time for a seed.
+    // The snake's start is kept clear, and any pocket it couldn't reach is filled in
+    pub fn generate(seed: u64, config: GameConfig) -> Level {
+        let mut rng = GameRng::new(RngKind::Std, seed);
+        let (width, height) = (config.grid_width, config.grid_height);
+        let start = SnakeStart::centered(&config);
+        let mut clear = start.cells();
+        let ahead = start.head.move_in_direction(start.direction);
+        clear.extend([ahead, ahead.move_in_direction(start.direction)]);
+        let index = |cell: Position| (cell.y * width + cell.x) as usize;
+        let cells: Vec<Position> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| Position::new(x, y)))
+            .collect();
+
+        let mut level = Level {
+            name: format!("Random {}", seed),
+            config,
+            walls: Vec::new(),
+            speed: Speed::START,
+            target_score: GENERATED_TARGET_SCORE,
+            seed: Some(seed),
+        };
+        for _ in 0..GENERATOR_ATTEMPTS {
+            let mut wall: Vec<bool> = cells
+                .iter()
+                .map(|_| rng.gen_bool(GENERATOR_WALL_CHANCE))
+                .collect();
+            // a cell with 5 or more walls around it becomes one, and with 3 or fewer it
+            // opens up. Off the board counts as open, so the edges stay clear
+            for _ in 0..GENERATOR_SMOOTHING {
+                wall = cells
+                    .iter()
+                    .map(|cell| {
+                        let around = (-1..=1)
+                            .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                            .filter(|&(dx, dy)| (dx, dy) != (0, 0))
+                            .map(|(dx, dy)| Position::new(cell.x + dx, cell.y + dy))
+                            .filter(|near| config.contains(*near) && wall[index(*near)])
+                            .count();
+                        match around {
+                            5.. => true,
+                            0..=3 => false,
+                            _ => wall[index(*cell)],
+                        }
+                    })
+                    .collect();
+            }
+            for cell in &clear {
+                wall[index(*cell)] = false;
+            }
+            level.walls = cells
+                .iter()
+                .copied()
+                .filter(|cell| wall[index(*cell)])
+                .collect();
+            level.walls.extend(level.unreachable());
+            let share = level.walls.len() as f64 / cells.len() as f64;
+            if GENERATOR_WALL_SHARE.contains(&share) {
+                break;
+            }
+        }
+        level
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Level, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse(&text)
+    }
+
+    // The level as a level file, which parses back to the same level
+    pub fn to_text(&self) -> String {
+        let rows: Vec<String> = (0..self.config.grid_height)
+            .map(|y| {
+                (0..self.config.grid_width)
+                    .map(|x| {
+                        if self.walls.contains(&Position::new(x, y)) {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        format!(
+            "name = {}\nsize = \"{}x{}\"\nspeed = {}\ntarget_score = {}\nwalls = \"\"\"\n{}\n\"\"\"\n",
+            toml::Value::String(self.name.clone()),
+            self.config.grid_width,
+            self.config.grid_height,
+            self.speed.millicells_per_second / 1000,
+            self.target_score,
+            rows.join("\n")
+        )
+    }
+
+    // The level as it starts, for picking one from a list
+    pub fn thumbnail(&self, cell_size: usize) -> Thumbnail {
+        Thumbnail::of(&GameState::with_level(self.clone()), cell_size)
+    }
+}
+
+// The .toml levels in `dir` in file name order, which is the order they're played in,
+// and a line for each file that couldn't be loaded
+pub fn discover_levels(dir: &str) -> (Vec<Level>, Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return (Vec::new(), Vec::new());
+    };
+    let mut paths: Vec<std::path::PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+
+    let mut levels = Vec::new();
+    let mut problems = Vec::new();
+    for path in paths {
+        match Level::load(&path) {
+            Ok(level) => levels.push(level),
+            Err(e) => problems.push(format!("skipping {}: {}", path.display(), e)),
+        }
+    }
+    (levels, problems)
+}
+
+// Where a game playing through levels has got to
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelRun {
+    pub levels: Vec<Level>,
+    pub current: usize,
+    pub start_score: u32,          // the score when the current level began
+    pub complete_for: Option<f64>, // seconds left on the level complete overlay
+    pub board_changed: bool,       // the window needs fitting to a new board size
+    pub cleared: bool,             // every level's been beaten
+    pub first: usize,              // the level the run started on, where a restart goes back to
+    pub start_time: f64,           // the run's time when the current level began
+}
+
+impl LevelRun {
+    pub fn level(&self) -> &Level {
+        &self.levels[self.current]
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.current + 1 == self.levels.len()
+    }
+}
+
+// Where the player's got to in the levels, saved with their other records
+pub const LEVEL_PROGRESS_FILE: &str = "level_progress.txt";
+
+// The best a player's done on one level
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LevelRecord {
+    pub cleared: bool,
+    pub best_score: u32, // points scored on the level itself
+    pub best_time: f64,  // seconds lasted on it
+}
+
+// Every level's record, by the level's name. The first level is always open, and each
+// one after opens once the one before it has been cleared
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LevelProgress {
+    pub records: std::collections::BTreeMap<String, LevelRecord>,
+}
+
+impl LevelProgress {
+    pub fn load(storage: &dyn StorageBackend) -> Self {
+        storage
+            .read(LEVEL_PROGRESS_FILE)
+            .map(|text| Self::parse(&text))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &dyn StorageBackend) -> std::io::Result<()> {
+        storage.write(LEVEL_PROGRESS_FILE, &self.to_text())
+    }
+
+    // A line per level: cleared (0 or 1), best score, best time, then the name. Lines that
+    // don't read are skipped
+    pub fn parse(text: &str) -> Self {
+        let records = text
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(4, ' ');
+                let cleared = fields.next()? == "1";
+                let best_score = fields.next()?.parse().ok()?;
+                let best_time = fields.next()?.parse().ok()?;
+                let record = LevelRecord {
+                    cleared,
+                    best_score,
+                    best_time,
+                };
+                Some((fields.next()?.to_string(), record))
+            })
+            .collect();
+        Self { records }
+    }
+
+    pub fn to_text(&self) -> String {
+        self.records
+            .iter()
+            .map(|(name, record)| {
+                format!(
+                    "{} {} {} {}\n",
+                    u8::from(record.cleared),
+                    record.best_score,
+                    record.best_time,
+                    name
+                )
+            })
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> LevelRecord {
+        self.records.get(name).copied().unwrap_or_default()
+    }
+
+    // Keep the better of this try and the record. Clearing a level is never undone
+    pub fn record(&mut self, name: &str, score: u32, time: f64, cleared: bool) {
+        let record = self.records.entry(name.to_string()).or_default();
+        record.cleared |= cleared;
+        record.best_score = record.best_score.max(score);
+        record.best_time = record.best_time.max(time);
+    }
+
+    pub fn is_unlocked(&self, levels: &[Level], index: usize) -> bool {
+        index == 0 || self.get(&levels[index - 1].name).cleared
+    }
+}
+
+// Bot games a level's difficulty is judged on, and the most ticks each gets to clear it
+pub const DIFFICULTY_GAMES: u64 = 8;
+const DIFFICULTY_TICK_LIMIT: u64 = 3000;
+
+// How hard a level looks, from its layout and from how the bot gets on with it, so pack
+// authors can balance their levels and players can see what they're in for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Difficulty {
+    pub open_share: f64,     // of the board that isn't wall
+    pub corridor_width: f64, // how many cells across the floor is the narrow way, on average
+    pub bot_games: u64,
+    pub bot_cleared: u64, // bot games that reached the target score
+    pub stars: u8,        // 1 to 5
+}
+
+impl Difficulty {
+    // Measure the layout, then have the bot play `games` seeded games of the level
+    pub fn estimate(level: &Level, games: u64) -> Self {
+        let config = &level.config;
+        let (width, height) = (config.grid_width, config.grid_height);
+        let open = |x: i32, y: i32| !level.walls.contains(&Position::new(x, y));
+        // the run of floor a cell is part of, along a row or a column
+        let run = |x: i32, y: i32, (dx, dy): (i32, i32)| {
+            let along = |sign: i32| {
+                (1..)
+                    .map(|step| (x + dx * step * sign, y + dy * step * sign))
+                    .take_while(|&(x, y)| config.contains(Position::new(x, y)) && open(x, y))
+                    .count()
+            };
+            1 + along(1) + along(-1)
+        };
+        let widths: Vec<usize> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| open(x, y))
+            .map(|(x, y)| run(x, y, (1, 0)).min(run(x, y, (0, 1))))
+            .collect();
+        let open_share = widths.len() as f64 / config.cell_count() as f64;
+        let corridor_width = widths.iter().sum::<usize>() as f64 / widths.len().max(1) as f64;
+
+        let bot_cleared = (0..games)
+            .filter(|seed| {
+                let mut game = GameState::with_level(level.clone());
+                // the bot's runs aren't the player's, so nothing it does gets saved
+                game.storage = Arc::new(InMemoryStorage::default());
+                game.high_score = u32::MAX;
+                game.best_time = f64::MAX;
+                game.reseed(*seed);
+                game.load_level(0);
+                let mut ticks = 0;
+                while !game.game_over && !game.level_complete() && ticks < DIFFICULTY_TICK_LIMIT {
+                    let direction = bot_direction(&game);
+                    game.handle_input(direction);
+                    game.step();
+                    ticks += 1;
+                }
+                game.level_complete()
+            })
+            .count() as u64;
+
+        // the bot failing counts for half, crowded boards and narrow corridors a quarter each
+        let failing = 1.0 - bot_cleared as f64 / games.max(1) as f64;
+        let crowding = ((1.0 - open_share) / 0.3).min(1.0);
+        let narrowness = (1.0 / corridor_width.max(1.0)).min(1.0);
+        let rating = 0.5 * failing + 0.25 * crowding + 0.25 * narrowness;
+        Self {
+            open_share,
+            corridor_width,
+            bot_games: games,
+            bot_cleared,
+            stars: 1 + (rating * 4.0).round() as u8,
+        }
+    }
+
+    // e.g. `***··` for three stars - the font has no star glyphs
+    pub fn stars_text(&self) -> String {
+        let stars = self.stars.min(5) as usize;
+        format!("{}{}", "*".repeat(stars), "·".repeat(5 - stars))
+    }
+}
+
+impl std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({} stars): {:.0}% open, corridors {:.1} cells wide, the bot cleared {} of {}",
+            self.stars_text(),
+            self.stars,
+            self.open_share * 100.0,
+            self.corridor_width,
+            self.bot_cleared,
+            self.bot_games
+        )
+    }
+}
+
+pub const PACK_ARCHIVE_VERSION: u8 = 1;
+const PACK_ARCHIVE_MAGIC: &[u8; 4] = b"SNKP";
+pub const PACK_ARCHIVE_EXTENSION: &str = "snakepack";
+pub const MAX_PACK_FILES: usize = 256;
+pub const MAX_PACK_ASSET_SIZE: usize = 1024 * 1024;
+pub const MAX_PACK_ARCHIVE_SIZE: usize = 16 * 1024 * 1024;
+
+// A whole pack in one file, for sharing. After the magic and version comes a file
+// count, then each file's path and contents, and last a checksum of everything before it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackArchive {
+    pub files: Vec<(String, Vec<u8>)>, // path inside the pack, always with `/`
+}
+
+// Why a pack archive couldn't be made, read or installed
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackArchiveError {
+    NotAPack,
+    UnsupportedVersion(u8),
+    Truncated,
+    ChecksumMismatch,
+    UnsafePath(String),
+    TooLarge(String),
+    TooManyFiles,
+    Invalid(String),
+    AlreadyInstalled(String),
+    Io(String),
+}
+
+impl std::fmt::Display for PackArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PackArchiveError::NotAPack => write!(f, "not a snake content pack"),
+            PackArchiveError::UnsupportedVersion(version) => write!(
+                f,
+                "pack version {} is newer than this game supports ({})",
+                version, PACK_ARCHIVE_VERSION
+            ),
+            PackArchiveError::Truncated => write!(f, "pack is cut short"),
+            PackArchiveError::ChecksumMismatch => write!(f, "pack is corrupted"),
+            PackArchiveError::UnsafePath(path) => {
+                write!(f, "pack has a file outside its folder: {}", path)
+            }
+            PackArchiveError::TooLarge(what) => write!(f, "{} is too large", what),
+            PackArchiveError::TooManyFiles => {
+                write!(f, "pack has more than {} files", MAX_PACK_FILES)
+            }
+            PackArchiveError::Invalid(reason) => write!(f, "{}", reason),
+            PackArchiveError::AlreadyInstalled(name) => {
+                write!(f, "a pack called `{}` is already installed", name)
+            }
+            PackArchiveError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PackArchiveError {}
+
+impl From<std::io::Error> for PackArchiveError {
+    fn from(e: std::io::Error) -> Self {
+        PackArchiveError::Io(e.to_string())
+    }
+}
+
+// Paths in a pack must stay inside it - no `..`, no roots, no drive letters
+fn is_safe_pack_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.contains('\\')
+        && !path.contains(':')
+        && path
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != "..")
+}
+
+struct PackReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> PackReader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], PackArchiveError> {
+        if count > self.bytes.len() {
+            return Err(PackArchiveError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn length(&mut self) -> Result<usize, PackArchiveError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize)
+    }
+}
+
+pub const PACK_PROFILE_FILE: &str = "packs.txt";
+
+// Which packs are switched on, one name per line in load order - later packs
+// override earlier ones
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PackProfile {
+    pub enabled: Vec<String>,
+}
+
+impl PackProfile {
+    pub fn parse(text: &str) -> Self {
+        let mut enabled: Vec<String> = Vec::new();
+        for name in text.lines().map(str::trim).filter(|name| !name.is_empty()) {
+            if !enabled.iter().any(|seen| seen == name) {
+                enabled.push(name.to_string());
+            }
+        }
+        Self { enabled }
+    }
+
+    pub fn load(path: &str) -> Self {
+        std::fs::read_to_string(path)
+            .map(|text| Self::parse(&text))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let text: String = self
+            .enabled
+            .iter()
+            .map(|name| format!("{}\n", name))
+            .collect();
+        std::fs::write(path, text)
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.enabled.iter().any(|enabled| enabled == name)
+    }
+
+    // Switch a pack on (at the end of the load order) or off
+    pub fn toggle(&mut self, name: &str) {
+        if self.is_enabled(name) {
+            self.enabled.retain(|enabled| enabled != name);
+        } else {
+            self.enabled.push(name.to_string());
+        }
+    }
+
+    // Move an enabled pack earlier (-1) or later (+1) in the load order
+    pub fn shift(&mut self, name: &str, offset: isize) {
+        let Some(index) = self.enabled.iter().position(|enabled| enabled == name) else {
+            return;
+        };
+        let target = index.saturating_add_signed(offset);
+        if target < self.enabled.len() {
+            self.enabled.swap(index, target);
+        }
+    }
+
+    // The enabled packs that are actually installed, in load order
+    pub fn resolve<'a>(&self, packs: &'a [Pack]) -> Vec<&'a Pack> {
+        self.enabled
+            .iter()
+            .filter_map(|name| packs.iter().find(|pack| pack.manifest.name == *name))
+            .collect()
+    }
+}
+
+impl PackArchive {
+    // Pack up every file in a pack folder
+    pub fn from_dir(dir: &std::path::Path) -> Result<Self, PackArchiveError> {
+        let mut files = Vec::new();
+        let mut folders = vec![dir.to_path_buf()];
+        while let Some(folder) = folders.pop() {
+            for entry in std::fs::read_dir(&folder)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    folders.push(path);
+                    continue;
+                }
+                let relative = path.strip_prefix(dir).unwrap_or(&path);
+                let name = relative
+                    .components()
+                    .map(|part| part.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                files.push((name, std::fs::read(&path)?));
+            }
+        }
+        files.sort();
+        let archive = Self { files };
+        archive.check()?;
+        Ok(archive)
+    }
+
+    // Everything an archive has to pass before it's written or installed
+    fn check(&self) -> Result<PackManifest, PackArchiveError> {
+        if self.files.len() > MAX_PACK_FILES {
+            return Err(PackArchiveError::TooManyFiles);
+        }
+        for (path, data) in &self.files {
+            if !is_safe_pack_path(path) {
+                return Err(PackArchiveError::UnsafePath(path.clone()));
+            }
+            if data.len() > MAX_PACK_ASSET_SIZE {
+                return Err(PackArchiveError::TooLarge(path.clone()));
+            }
+        }
+        let text = |name: &str| {
+            self.file(name)
+                .map(|data| String::from_utf8_lossy(data).into_owned())
+        };
+        let manifest = text(PACK_MANIFEST)
+            .ok_or_else(|| PackArchiveError::Invalid(format!("no {}", PACK_MANIFEST)))?;
+        let manifest = PackManifest::parse(&manifest)
+            .map_err(|e| PackArchiveError::Invalid(format!("{}: {}", PACK_MANIFEST, e)))?;
+        if let Some(theme) = text(THEME_FILE) {
+            Theme::parse(&theme)
+                .map_err(|e| PackArchiveError::Invalid(format!("{}: {}", THEME_FILE, e)))?;
+        }
+        Ok(manifest)
+    }
+
+    pub fn file(&self, path: &str) -> Option<&[u8]> {
+        self.files
+            .iter()
+            .find(|(name, _)| name == path)
+            .map(|(_, data)| data.as_slice())
+    }
+
+    pub fn manifest(&self) -> Result<PackManifest, PackArchiveError> {
+        self.check()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = PACK_ARCHIVE_MAGIC.to_vec();
+        bytes.push(PACK_ARCHIVE_VERSION);
+        bytes.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+        for (path, data) in &self.files {
+            bytes.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(path.as_bytes());
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(data);
+        }
+        bytes.extend_from_slice(&fnv1a(&bytes).to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PackArchiveError> {
+        if bytes.len() < 5 || &bytes[..4] != PACK_ARCHIVE_MAGIC {
+            return Err(PackArchiveError::NotAPack);
+        }
+        if bytes[4] > PACK_ARCHIVE_VERSION {
+            return Err(PackArchiveError::UnsupportedVersion(bytes[4]));
+        }
+        if bytes.len() > MAX_PACK_ARCHIVE_SIZE {
+            return Err(PackArchiveError::TooLarge("pack".to_string()));
+        }
+        if bytes.len() < 17 {
+            return Err(PackArchiveError::Truncated);
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - 8);
+        if fnv1a(body).to_le_bytes() != checksum {
+            return Err(PackArchiveError::ChecksumMismatch);
+        }
+
+        let mut reader = PackReader { bytes: &body[5..] };
+        let count = reader.length()?;
+        if count > MAX_PACK_FILES {
+            return Err(PackArchiveError::TooManyFiles);
+        }
+        let mut files = Vec::with_capacity(count);
+        for _ in 0..count {
+            let path_length = reader.length()?;
+            let path = String::from_utf8_lossy(reader.take(path_length)?).into_owned();
+            let data_length = reader.length()?;
+            files.push((path, reader.take(data_length)?.to_vec()));
+        }
+        let archive = Self { files };
+        archive.check()?;
+        Ok(archive)
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: &str) -> Result<Self, PackArchiveError> {
+        let bytes = std::fs::read(path)?;
+        Self::from_bytes(&bytes)
+    }
+
+    // Unpack into its own folder in `mods_dir`, named after the pack. An installed
+    // pack is never overwritten
+    pub fn install(&self, mods_dir: &str) -> Result<std::path::PathBuf, PackArchiveError> {
+        let manifest = self.check()?;
+        let (installed, _) = discover_packs(mods_dir);
+        let dir = std::path::Path::new(mods_dir).join(&manifest.name);
+        if dir.exists() || installed.iter().any(|p| p.manifest.name == manifest.name) {
+            return Err(PackArchiveError::AlreadyInstalled(manifest.name));
+        }
+        for (path, data) in &self.files {
+            let file = dir.join(path);
+            if let Some(parent) = file.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(file, data)?;
+        }
+        Ok(dir)
+    }
+}
+
+pub const SAVE_ARCHIVE_VERSION: u8 = 1;
+const SAVE_ARCHIVE_MAGIC: &[u8; 4] = b"SNKS";
+pub const SAVE_ARCHIVE_EXTENSION: &str = "snakesave";
+pub const MAX_SAVE_ARCHIVE_SIZE: usize = 256 * 1024 * 1024;
+
+// Which saved values are the player's, by how their key starts - scores for every mode
+// and board, a run left in the background, and their settings. Anything else in the data
+// directory is left alone by exporting and resetting
+pub const SAVE_DATA_PREFIXES: [&str; 14] = [
+    "high_score",
+    "checked_high_score",
+    "best_time",
+    "suspended",
+    "autosave",
+    "slot",
+    LEVEL_PROGRESS_FILE,
+    MIXER_FILE,
+    VOICE_FILE,
+    RATINGS_FILE,
+    GHOST_FILE,
+    PACK_PROFILE_FILE,
+    EDITOR_PREFS_FILE,
+    SETTINGS_FILE,
+];
+
+pub fn is_save_data(key: &str) -> bool {
+    SAVE_DATA_PREFIXES
+        .iter()
+        .any(|prefix| key.starts_with(prefix))
+}
+
+// All of a player's data in one file, for moving it to another machine: their saved
+// values from the storage backend as `data/<key>`, and their replays as `replays/<file>`,
+// named without REPLAY_PREFIX. Laid out like a PackArchive, under its own magic
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SaveArchive {
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+impl SaveArchive {
+    // Gather everything of the player's from `storage`
+    pub fn collect(storage: &dyn StorageBackend) -> Self {
+        let mut files: Vec<(String, Vec<u8>)> = storage
+            .keys()
+            .into_iter()
+            .filter_map(|key| match key.strip_prefix(REPLAY_PREFIX) {
+                Some(name) => Some((format!("replays/{}", name), storage.read_bytes(&key)?)),
+                None if is_save_data(&key) => {
+                    Some((format!("data/{}", key), storage.read(&key)?.into_bytes()))
+                }
+                None => None,
+            })
+            .collect();
+        files.sort();
+        Self { files }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = SAVE_ARCHIVE_MAGIC.to_vec();
+        bytes.push(SAVE_ARCHIVE_VERSION);
+        bytes.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+        for (path, data) in &self.files {
+            bytes.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(path.as_bytes());
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(data);
+        }
+        bytes.extend_from_slice(&fnv1a(&bytes).to_le_bytes());
+        bytes
+    }
+
+    // Every path is checked before anything is restored, so a bad file writes nothing
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 5 || &bytes[..4] != SAVE_ARCHIVE_MAGIC {
+            return Err("not a snake save file".to_string());
+        }
+        if bytes[4] > SAVE_ARCHIVE_VERSION {
+            return Err(format!(
+                "save version {} is newer than this game supports ({})",
+                bytes[4], SAVE_ARCHIVE_VERSION
+            ));
+        }
+        if bytes.len() > MAX_SAVE_ARCHIVE_SIZE {
+            return Err("save file is too large".to_string());
+        }
+        if bytes.len() < 17 {
+            return Err("save file is cut short".to_string());
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - 8);
+        if fnv1a(body).to_le_bytes() != checksum {
+            return Err("save file is corrupted".to_string());
+        }
+
+        let cut_short = |_| "save file is cut short".to_string();
+        let mut reader = PackReader { bytes: &body[5..] };
+        let count = reader.length().map_err(cut_short)?;
+        let mut files = Vec::new();
+        for _ in 0..count {
+            let path_length = reader.length().map_err(cut_short)?;
+            let path =
+                String::from_utf8_lossy(reader.take(path_length).map_err(cut_short)?).into_owned();
+            let data_length = reader.length().map_err(cut_short)?;
+            let data = reader.take(data_length).map_err(cut_short)?.to_vec();
+            let allowed = match path.split_once('/') {
+                Some(("data", key)) => is_save_data(key) && std::str::from_utf8(&data).is_ok(),
+                Some(("replays", name)) => !name.is_empty(),
+                _ => false,
+            };
+            if !allowed || !is_safe_pack_path(&path) || path.matches('/').count() != 1 {
+                return Err(format!("save file has something it shouldn't: {}", path));
+            }
+            files.push((path, data));
+        }
+        Ok(Self { files })
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        Self::from_bytes(&bytes)
+    }
+
+    // Put everything back, over whatever's there already. Data that isn't in the
+    // archive is kept. Returns how many files were restored
+    pub fn restore(&self, storage: &dyn StorageBackend) -> std::io::Result<usize> {
+        for (path, data) in &self.files {
+            match path.split_once('/') {
+                Some(("data", key)) => {
+                    storage.write(key, &String::from_utf8_lossy(data))?;
+                }
+                Some(("replays", name)) => {
+                    storage.write_bytes(&format!("{}{}", REPLAY_PREFIX, name), data)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(self.files.len())
+    }
+}
+
+// Wipe the player's scores, settings and replays, as if the game was just installed.
+// Returns how many files went
+pub fn reset_save_data(storage: &dyn StorageBackend) -> std::io::Result<usize> {
+    let mut removed = 0;
+    for key in storage.keys() {
+        if is_save_data(&key) || key.starts_with(REPLAY_PREFIX) {
+            storage.remove(&key)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+// What the save data section of a settings screen can do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveDataAction {
+    Export,
+    Import,
+    Reset,
+}
+
+impl SaveDataAction {
+    pub const ALL: [SaveDataAction; 3] = [
+        SaveDataAction::Export,
+        SaveDataAction::Import,
+        SaveDataAction::Reset,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SaveDataAction::Export => "Export save data",
+            SaveDataAction::Import => "Import save data",
+            SaveDataAction::Reset => "Reset everything",
+        }
+    }
+
+    // Buttons in the order of ALL, for a settings screen
+    pub fn panel() -> Panel {
+        let mut panel = Panel::default();
+        panel.push(Widget::title("Save data"));
+        for action in Self::ALL {
+            panel.push(Widget::button(action.label()));
+        }
+        panel
+    }
+
+    // Resetting can't be undone, so it's asked about first
+    pub fn confirm_reset() -> ConfirmDialog<bool> {
+        ConfirmDialog::yes_no(
+            "Erase every score, setting and replay? This can't be undone.",
+            "Erase everything",
+        )
+    }
+}
+
+// Fully saturated color for a hue, where 0.0 and 1.0 are both red
+pub fn hue_color(hue: f32) -> Color {
+    let h = hue.rem_euclid(1.0) * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    let (r, g, b) = match h as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    Color::new(r, g, b, 1.0)
+}
+
+// Blend between two colors, t = 0.0 gives `from` and t = 1.0 gives `to`
+pub fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    Color::new(
+        from.r + (to.r - from.r) * t,
+        from.g + (to.g - from.g) * t,
+        from.b + (to.b - from.b) * t,
+        from.a + (to.a - from.a) * t,
+    )
+}
+
+// The most time one call to GameState::tick counts - a few ticks' worth even at the slowest
+// speed, and far short of anything that could overflow a Duration
+pub const MAX_TICK_SECONDS: f64 = 1.0;
+
+pub const START_FILE: &str = "start.txt";
+
+impl Spawns {
+    // Spawns from a file, in the lines `parse` reads
+    pub fn load(path: &str) -> Result<Spawns, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Spawns::parse(&text)
+    }
+}
+
+// Setup for a versus match
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchSettings {
+    pub target_score: u32,
+    pub best_of: u32,
+    pub handicaps: [Handicap; 2],
+    pub player_names: [String; 2], // ratings are tracked per name
+    pub config: GameConfig,        // both boards are the same
+}
+
+impl MatchSettings {
+    pub fn new(target_score: u32) -> Self {
+        Self {
+            target_score,
+            best_of: 5,
+            handicaps: [Handicap::default(); 2],
+            player_names: ["P1".to_string(), "P2".to_string()],
+            config: GameConfig::default(),
+        }
+    }
+}
+
+// Elo-style ratings for local versus players
+pub const RATINGS_FILE: &str = "ratings.txt";
+pub const DEFAULT_RATING: f64 = 1500.0;
+pub const RATING_K_FACTOR: f64 = 32.0;
+
+// New ratings for the winner and loser of a match
+pub fn elo_update(winner: f64, loser: f64) -> (f64, f64) {
+    // how likely the winner was to win going in - beating a stronger player is worth more
+    let expected = 1.0 / (1.0 + 10f64.powf((loser - winner) / 400.0));
+    let change = RATING_K_FACTOR * (1.0 - expected);
+    (winner + change, loser - change)
+}
+
+// Every player's rating by name, saved as one "name<TAB>rating" line each
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ratings {
+    pub ratings: std::collections::BTreeMap<String, f64>,
+}
+
+impl Ratings {
+    // Lines that don't read as a name and a rating are skipped
+    pub fn parse(text: &str) -> Self {
+        let mut ratings = Self::default();
+        for line in text.lines() {
+            if let Some((name, rating)) = line.rsplit_once('\t') {
+                if let Ok(rating) = rating.trim().parse() {
+                    ratings.ratings.insert(name.to_string(), rating);
+                }
+            }
+        }
+        ratings
+    }
+
+    pub fn to_text(&self) -> String {
+        self.ratings
+            .iter()
+            .map(|(name, rating)| format!("{}\t{}\n", name, rating))
+            .collect()
+    }
+
+    pub fn load(storage: &dyn StorageBackend) -> Self {
+        storage
+            .read(RATINGS_FILE)
+            .map(|text| Self::parse(&text))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &dyn StorageBackend) {
+        if let Err(e) = storage.write(RATINGS_FILE, &self.to_text()) {
+            eprintln!("Failed to save ratings: {}", e);
+        }
+    }
+
+    // New players start at the default rating
+    pub fn get(&self, name: &str) -> f64 {
+        self.ratings.get(name).copied().unwrap_or(DEFAULT_RATING)
+    }
+
+    // Update both players after a match, returning (before, after) for the winner and loser
+    pub fn record_match(&mut self, winner: &str, loser: &str) -> [(f64, f64); 2] {
+        let before = (self.get(winner), self.get(loser));
+        let after = elo_update(before.0, before.1);
+        self.ratings.insert(winner.to_string(), after.0);
+        self.ratings.insert(loser.to_string(), after.1);
+        [(before.0, after.0), (before.1, after.1)]
+    }
+}
+
+// Rounds of a best-of-N versus match
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub best_of: u32,
+    pub round_winners: Vec<Option<usize>>, // None when both players crashed on the same tick
+}
+
+impl Series {
+    pub fn new(best_of: u32) -> Self {
+        Self {
+            best_of,
+            round_winners: Vec::new(),
+        }
+    }
+
+    pub fn record_round(&mut self, winner: Option<usize>) {
+        self.round_winners.push(winner);
+    }
+
+    pub fn wins(&self, player: usize) -> u32 {
+        self.round_winners
+            .iter()
+            .filter(|winner| **winner == Some(player))
+            .count() as u32
+    }
+
+    // Rounds needed to take the match, e.g. 3 in a best of 5
+    pub fn wins_needed(&self) -> u32 {
+        self.best_of / 2 + 1
+    }
+
+    // Drawn rounds don't count towards anyone, so the match goes on until someone gets there
+    pub fn match_winner(&self) -> Option<usize> {
+        (0..2).find(|player| self.wins(*player) >= self.wins_needed())
+    }
+}
+
+// Netplay protocol version - bump it whenever the messages peers send each other change.
+// Peers on anything from OLDEST_COMPATIBLE_PROTOCOL up to PROTOCOL_VERSION can play together
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const OLDEST_COMPATIBLE_PROTOCOL: u32 = 1;
+
+// Which game a netplay session is for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetMode {
+    Versus,
+    Coop,
+}
+
+impl NetMode {
+    fn name(&self) -> &'static str {
+        match self {
+            NetMode::Versus => "versus",
+            NetMode::Coop => "coop",
+        }
+    }
+}
+
+// First message each peer sends, so mismatched builds refuse to play instead of desyncing.
+// Sent as one line: "snake <version> <mode> <width>x<height> <seed>"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Handshake {
+    pub protocol_version: u32,
+    pub mode: NetMode,
+    pub grid_width: i32,
+    pub grid_height: i32,
+    pub seed: u64, // the host's seed is the one both peers play with
+}
+
+// Why a handshake was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub enum HandshakeError {
+    Malformed(String),
+    UnsupportedVersion {
+        ours: u32,
+        theirs: u32,
+    },
+    ModeMismatch {
+        ours: NetMode,
+        theirs: NetMode,
+    },
+    GridMismatch {
+        ours: (i32, i32),
+        theirs: (i32, i32),
+    },
+}
+
+impl std::fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HandshakeError::Malformed(line) => write!(f, "not a snake handshake: {:?}", line),
+            HandshakeError::UnsupportedVersion { ours, theirs } => write!(
+                f,
+                "protocol version {} can't play with version {} - both players need a compatible release",
+                theirs, ours
+            ),
+            HandshakeError::ModeMismatch { ours, theirs } => write!(
+                f,
+                "the other player wants {} but this game is {}",
+                theirs.name(),
+                ours.name()
+            ),
+            HandshakeError::GridMismatch { ours, theirs } => write!(
+                f,
+                "the other player's board is {}x{} but this one is {}x{}",
+                theirs.0, theirs.1, ours.0, ours.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HandshakeError {}
+
+// Whether a peer on `version` can play with this build
+pub fn is_supported_protocol(version: u32) -> bool {
+    (OLDEST_COMPATIBLE_PROTOCOL..=PROTOCOL_VERSION).contains(&version)
+}
+
+impl Handshake {
+    pub fn new(mode: NetMode, config: &GameConfig, seed: u64) -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            mode,
+            grid_width: config.grid_width,
+            grid_height: config.grid_height,
+            seed,
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        format!(
+            "snake {} {} {}x{} {}",
+            self.protocol_version,
+            self.mode.name(),
+            self.grid_width,
+            self.grid_height,
+            self.seed
+        )
+    }
+
+    pub fn parse(line: &str) -> Result<Self, HandshakeError> {
+        let malformed = || HandshakeError::Malformed(line.to_string());
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let [magic, version, mode, grid, seed] = parts[..] else {
+            return Err(malformed());
+        };
+        if magic != "snake" {
+            return Err(malformed());
+        }
+        let mode = match mode {
+            "versus" => NetMode::Versus,
+            "coop" => NetMode::Coop,
+            _ => return Err(malformed()),
+        };
+        let (width, height) = grid.split_once('x').ok_or_else(malformed)?;
+        Ok(Self {
+            protocol_version: version.parse().map_err(|_| malformed())?,
+            mode,
+            grid_width: width.parse().map_err(|_| malformed())?,
+            grid_height: height.parse().map_err(|_| malformed())?,
+            seed: seed.parse().map_err(|_| malformed())?,
+        })
+    }
+
+    // Check the peer's handshake against ours before any game messages are exchanged
+    pub fn check_compatible(&self, peer: &Handshake) -> Result<(), HandshakeError> {
+        if !is_supported_protocol(peer.protocol_version) {
+            return Err(HandshakeError::UnsupportedVersion {
+                ours: self.protocol_version,
+                theirs: peer.protocol_version,
+            });
+        }
+        if peer.mode != self.mode {
+            return Err(HandshakeError::ModeMismatch {
+                ours: self.mode,
+                theirs: peer.mode,
+            });
+        }
+        if (peer.grid_width, peer.grid_height) != (self.grid_width, self.grid_height) {
+            return Err(HandshakeError::GridMismatch {
+                ours: (self.grid_width, self.grid_height),
+                theirs: (peer.grid_width, peer.grid_height),
+            });
+        }
+        Ok(())
+    }
+}
+
+// How long a dropped netplay peer has to reconnect before the match is abandoned
+pub const RECONNECT_WINDOW: f64 = 30.0;
+
+// A netplay peer's connection, as seen by the host - the match is paused while it's reconnecting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PeerStatus {
+    Connected,
+    Reconnecting { waited: f64 },
+    Dropped, // didn't come back in time
+}
+
+impl PeerStatus {
+    pub fn disconnect(&mut self) {
+        if *self == PeerStatus::Connected {
+            *self = PeerStatus::Reconnecting { waited: 0.0 };
+        }
+    }
+
+    pub fn advance(&mut self, delta: f64) {
+        if let PeerStatus::Reconnecting { waited } = self {
+            *waited += delta;
+            if *waited >= RECONNECT_WINDOW {
+                *self = PeerStatus::Dropped;
+            }
+        }
+    }
+
+    // Let the peer back in if it made it within the window
+    pub fn reconnect(&mut self) -> bool {
+        match self {
+            PeerStatus::Reconnecting { .. } => {
+                *self = PeerStatus::Connected;
+                true
+            }
+            PeerStatus::Connected => true,
+            PeerStatus::Dropped => false,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        matches!(self, PeerStatus::Reconnecting { .. })
+    }
+}
+
+// Relay for players who can't reach each other directly (e.g. both behind NAT): both connect
+// out to the relay, send "join <ROOM CODE>" as their first line, and get piped together
+pub const RELAY_PORT: u16 = 7878;
+pub const ROOM_CODE_LENGTH: usize = 4;
+// How long to try a direct connection before falling back to the relay
+pub const DIRECT_CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+// Random room code to share with the other player - no I or O, they're easy to misread
+pub fn room_code() -> String {
+    const LETTERS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+    let mut rng = rand::thread_rng();
+    (0..ROOM_CODE_LENGTH)
+        .map(|_| LETTERS[rng.gen_range(0..LETTERS.len())] as char)
+        .collect()
+}
+
+// Accept players forever, pairing up the two that join the same room
+pub fn run_relay(listener: std::net::TcpListener) -> std::io::Result<()> {
+    let mut waiting: std::collections::HashMap<String, std::net::TcpStream> =
+        std::collections::HashMap::new();
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        let room = match read_join_line(&mut stream) {
+            Ok(room) => room,
+            Err(e) => {
+                eprintln!("Dropped a relay client: {}", e);
+                continue;
+            }
+        };
+        match waiting.remove(&room) {
+            Some(other) => pipe_together(other, stream)?,
+            None => {
+                waiting.insert(room, stream);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Read the "join <ROOM>" line a byte at a time, so nothing the client sends after it is lost
+fn read_join_line(stream: &mut std::net::TcpStream) -> std::io::Result<String> {
+    use std::io::{Error, ErrorKind, Read};
+
+    let mut line = Vec::new();
+    let mut byte = [0u8];
+    while line.len() <= ROOM_CODE_LENGTH + 8 {
+        stream.read_exact(&mut byte)?;
+        if byte[0] == b'\n' {
+            let line = String::from_utf8_lossy(&line);
+            return match line.trim().strip_prefix("join ") {
+                Some(room) if !room.is_empty() => Ok(room.to_uppercase()),
+                _ => Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "expected \"join <ROOM>\"",
+                )),
+            };
+        }
+        line.push(byte[0]);
+    }
+    Err(Error::new(ErrorKind::InvalidData, "join line too long"))
+}
+
+// Forward everything each player sends to the other, until either hangs up
+fn pipe_together(a: std::net::TcpStream, b: std::net::TcpStream) -> std::io::Result<()> {
+    for (mut from, mut to) in [(a.try_clone()?, b.try_clone()?), (b, a)] {
+        std::thread::spawn(move || {
+            let _ = std::io::copy(&mut from, &mut to);
+            let _ = to.shutdown(std::net::Shutdown::Both);
+        });
+    }
+    Ok(())
+}
+
+// How to reach the other player: their address, a relay and room code, or both
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeerRoute {
+    pub direct: Option<String>,
+    pub relay: Option<(String, String)>, // relay address, room code
+}
+
+impl PeerRoute {
+    // Connect straight to the other player if we know their address and it answers within
+    // `timeout`, otherwise through the relay using the room code
+    pub fn connect(&self, timeout: std::time::Duration) -> std::io::Result<std::net::TcpStream> {
+        use std::io::{Error, ErrorKind, Write};
+
+        if let Some(address) = &self.direct {
+            match connect_within(address, timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(e) if self.relay.is_none() => return Err(e),
+                Err(_) => {}
+            }
+        }
+        let Some((relay, room)) = &self.relay else {
+            return Err(Error::new(ErrorKind::InvalidInput, "nowhere to connect to"));
+        };
+        let mut stream = connect_within(relay, timeout)?;
+        writeln!(stream, "join {}", room)?;
+        Ok(stream)
+    }
+}
+
+// The first of `address`'s socket addresses that answers within `timeout`
+fn connect_within(
+    address: &str,
+    timeout: std::time::Duration,
+) -> std::io::Result<std::net::TcpStream> {
+    use std::net::{TcpStream, ToSocketAddrs};
+
+    let mut last_error = std::io::Error::from(std::io::ErrorKind::AddrNotAvailable);
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+// Chat between online players - messages are capped in length and fade out after a while
+pub const MAX_CHAT_LENGTH: usize = 80;
+pub const CHAT_MESSAGE_LIFETIME: f64 = 6.0;
+pub const CHAT_FADE: f64 = 1.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub sender: String,
+    pub text: String,
+    pub age: f64,
+}
+
+// Messages on screen, plus whether the other player's messages are muted
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChatLog {
+    pub messages: Vec<ChatMessage>,
+    pub muted: bool,
+}
+
+impl ChatLog {
+    pub fn add(&mut self, sender: &str, text: &str) {
+        let text: String = text.trim().chars().take(MAX_CHAT_LENGTH).collect();
+        if !text.is_empty() {
+            self.messages.push(ChatMessage {
+                sender: sender.to_string(),
+                text,
+                age: 0.0,
+            });
+        }
+    }
+
+    // A message that came over the network - dropped while muted
+    pub fn receive(&mut self, line: &str) {
+        if let Some((sender, text)) = decode_chat_line(line) {
+            if !self.muted {
+                self.add(&sender, &text);
+            }
+        }
+    }
+
+    pub fn advance(&mut self, delta: f64) {
+        for message in &mut self.messages {
+            message.age += delta;
+        }
+        self.messages
+            .retain(|message| message.age < CHAT_MESSAGE_LIFETIME);
+    }
+
+    // Messages still showing, with how opaque each one should be drawn
+    pub fn visible(&self) -> impl Iterator<Item = (&ChatMessage, f32)> {
+        self.messages.iter().map(|message| {
+            let left = CHAT_MESSAGE_LIFETIME - message.age;
+            (message, (left / CHAT_FADE).clamp(0.0, 1.0) as f32)
+        })
+    }
+}
+
+// Chat goes over the same connection as the game, one "chat <sender> <text>" line per
+// message - the host passes each on with the name the sender joined under
+pub fn encode_chat_line(sender: &str, text: &str) -> String {
+    let text: String = text
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_CHAT_LENGTH)
+        .collect();
+    format!("chat {} {}", net_name(sender), text)
+}
+
+pub fn decode_chat_line(line: &str) -> Option<(String, String)> {
+    let (sender, text) = line
+        .trim_end_matches(['\r', '\n'])
+        .strip_prefix("chat ")?
+        .split_once(' ')?;
+    Some((
+        sender.to_string(),
+        text.chars().take(MAX_CHAT_LENGTH).collect(),
+    ))
+}
+
+// Round-trip time to the other player, smoothed so one slow ping doesn't make the HUD jump
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Latency {
+    pub round_trip: Option<f64>, // seconds, None until the first pong
+}
+
+impl Latency {
+    // How much each new sample counts towards the average
+    const SMOOTHING: f64 = 0.2;
+
+    pub fn record(&mut self, sample: f64) {
+        self.round_trip = Some(match self.round_trip {
+            Some(average) => average + (sample - average) * Self::SMOOTHING,
+            None => sample,
+        });
+    }
+
+    // HUD text, e.g. "42 ms"
+    pub fn label(&self) -> String {
+        match self.round_trip {
+            Some(round_trip) => format!("{:.0} ms", round_trip * 1000.0),
+            None => "-- ms".to_string(),
+        }
+    }
+
+    // Ticks of input delay that make up for this much latency: the other player's turns
+    // reach the host a one-way trip late, so the host's own wait about as long
+    pub fn suggested_delay(&self, tick_time: f64) -> u32 {
+        let one_way = self.round_trip.unwrap_or(0.0) / 2.0;
+        (one_way / tick_time).ceil() as u32
+    }
+}
+
+// Turns held back a fixed number of ticks before they're applied, so the player at the
+// host has no head start on the one whose turns come over the network
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InputDelay {
+    pub delay_ticks: u32,
+    pub pending: std::collections::VecDeque<(u64, Direction)>, // tick to apply on, turn
+}
+
+impl InputDelay {
+    pub fn new(delay_ticks: u32) -> Self {
+        Self {
+            delay_ticks,
+            pending: std::collections::VecDeque::new(),
+        }
+    }
+
+    // A turn pressed on `tick` - returns the tick it'll apply on
+    pub fn push(&mut self, tick: u64, direction: Direction) -> u64 {
+        let due = tick + self.delay_ticks as u64;
+        self.pending.push_back((due, direction));
+        due
+    }
+
+    // Turns due on `tick`, oldest first
+    pub fn take_due(&mut self, tick: u64) -> Vec<Direction> {
+        let mut due = Vec::new();
+        while let Some((when, direction)) = self.pending.front().copied() {
+            if when > tick {
+                break;
+            }
+            self.pending.pop_front();
+            due.push(direction);
+        }
+        due
+    }
+}
+
+// Who has the final say over the game state in an online match
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetRole {
+    Host,
+    Client,
+    Spectator, // watches the match but can't steer
+}
+
+impl NetRole {
+    // Whether turns from a peer in this role are applied
+    pub fn can_steer(&self) -> bool {
+        *self != NetRole::Spectator
+    }
+}
+
+// Most spectators one match will take
+pub const MAX_SPECTATORS: usize = 8;
+
+// Spectators watching a match, by name - they get the same state as the players
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpectatorSlots {
+    pub names: Vec<String>,
+}
+
+impl SpectatorSlots {
+    // Take a free slot, false if the match is full
+    pub fn join(&mut self, name: &str) -> bool {
+        if self.names.len() >= MAX_SPECTATORS {
+            return false;
+        }
+        self.names.push(name.to_string());
+        true
+    }
+
+    pub fn leave(&mut self, name: &str) {
+        self.names.retain(|spectator| spectator != name);
+    }
+}
+
+// A client's fallback for the host dropping out: the last state it confirmed matched the
+// host's, so it can take over from there instead of the match being abandoned
+#[derive(Clone)]
+pub struct HostMigration {
+    pub role: NetRole,
+    pub last_verified: Option<(u64, GameState)>, // tick and state on that tick
+}
+
+impl HostMigration {
+    pub fn new(role: NetRole) -> Self {
+        Self {
+            role,
+            last_verified: None,
+        }
+    }
+
+    // Compare our state on `tick` with the hash the host sent for it, keeping it if they agree
+    pub fn verify(&mut self, tick: u64, host_hash: u64, local: &GameState) -> bool {
+        let agrees = local.state_hash() == host_hash;
+        if agrees {
+            self.last_verified = Some((tick, local.clone()));
+        }
+        agrees
+    }
+
+    // The host is gone - roll back to the last verified state and carry on as host.
+    // Returns the tick to resume from, or None if nothing was ever verified
+    pub fn host_lost(&mut self, game: &mut GameState) -> Option<u64> {
+        // spectators can't run the match
+        if self.role == NetRole::Spectator {
+            return None;
+        }
+        let (tick, verified) = self.last_verified.as_ref()?;
+        let hash = verified.state_hash();
+        game.resync_from(verified, hash);
+        self.role = NetRole::Host;
+        Some(*tick)
+    }
+}
+
+// Sender side: turns the game into a frame each tick
+#[derive(Clone, Default)]
+pub struct SyncEncoder {
+    pub tick: u64,
+    pub last: Option<GameState>, // what the receiver should have now
+}
+
+impl SyncEncoder {
+    // The receiver lost track (e.g. a hash mismatch) - send everything next time
+    pub fn request_keyframe(&mut self) {
+        self.last = None;
+    }
+
+    pub fn encode(&mut self, game: &GameState) -> SyncFrame {
+        self.tick += 1;
+        let frame = match &self.last {
+            Some(last) if !self.tick.is_multiple_of(KEYFRAME_INTERVAL) => {
+                Self::delta(self.tick, last, game)
+            }
+            _ => None,
+        };
+        self.last = Some(game.clone());
+        frame.unwrap_or_else(|| SyncFrame::Keyframe {
+            tick: self.tick,
+            snake: game.snake.clone(),
+            direction: game.direction,
+            next_direction: game.next_direction,
+            food: game.food.position,
+            score: game.score,
+            game_over: game.game_over,
+        })
+    }
+
+    // A delta from `last` to `game`, or None if the snake changed in a way a delta can't
+    // describe (like a restart)
+    fn delta(tick: u64, last: &GameState, game: &GameState) -> Option<SyncFrame> {
+        let moved = game.snake[0] != last.snake[0];
+        let tail_removed = moved && game.snake.len() == last.snake.len();
+        let kept = game.snake.len() - usize::from(moved);
+        let expected_len = last.snake.len() + usize::from(moved) - usize::from(tail_removed);
+        if game.snake.len() != expected_len
+            || game.snake[usize::from(moved)..] != last.snake[..kept]
+        {
+            return None;
+        }
+        Some(SyncFrame::Delta {
+            tick,
+            head: game.snake[0],
+            tail_removed,
+            food: (game.food.position != last.food.position).then_some(game.food.position),
+            score: game.score,
+            direction: game.direction,
+            next_direction: game.next_direction,
+            game_over: game.game_over,
+            hash: game.state_hash(),
+        })
+    }
+}
+
+// Why a sync frame couldn't be applied - all of them are fixed by asking for a keyframe
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncError {
+    Malformed,
+    NeedKeyframe, // a delta arrived without the frame before it
+    HashMismatch,
+}
+
+// Receiver side: rebuilds the sender's game from frames
+#[derive(Clone, Default)]
+pub struct SyncDecoder {
+    pub tick: u64,
+    pub state: Option<GameState>,
+}
+
+impl SyncDecoder {
+    pub fn apply(&mut self, line: &str) -> Result<&GameState, SyncError> {
+        match SyncFrame::parse(line).ok_or(SyncError::Malformed)? {
+            SyncFrame::Keyframe {
+                tick,
+                snake,
+                direction,
+                next_direction,
+                food,
+                score,
+                game_over,
+            } => {
+                let state = self.state.get_or_insert_with(GameState::new);
+                state.snake = snake;
+                state.direction = direction;
+                state.next_direction = next_direction;
+                state.food = Food::normal(food);
+                state.score = score;
+                state.game_over = game_over;
+                self.tick = tick;
+            }
+            SyncFrame::Delta {
+                tick,
+                head,
+                tail_removed,
+                food,
+                score,
+                direction,
+                next_direction,
+                game_over,
+                hash,
+            } => {
+                let state = match &mut self.state {
+                    Some(state) if tick == self.tick + 1 => state,
+                    _ => return Err(SyncError::NeedKeyframe),
+                };
+                if head != state.snake[0] {
+                    state.snake.insert(0, head);
+                }
+                if tail_removed && state.snake.len() > 1 {
+                    state.snake.pop();
+                }
+                if let Some(food) = food {
+                    state.food = Food::normal(food);
+                }
+                state.score = score;
+                state.direction = direction;
+                state.next_direction = next_direction;
+                state.game_over = game_over;
+                self.tick = tick;
+                if state.state_hash() != hash {
+                    self.state = None;
+                    return Err(SyncError::HashMismatch);
+                }
+            }
+        }
+        self.state.as_ref().ok_or(SyncError::NeedKeyframe)
+    }
+}
+
+// Files in the platform's data folder (~/.local/share on Linux, Application Support on
+// macOS, AppData on Windows), so the game finds its saves wherever it's started from
+pub struct PlatformStorage {
+    pub files: FileStorage,
+}
+
+impl PlatformStorage {
+    pub fn new(dir: impl Into<std::path::PathBuf>) -> Self {
+        Self {
+            files: FileStorage::new(dir),
+        }
+    }
+
+    // None on a system with no home folder to put one in
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn locate() -> Option<Self> {
+        let dirs = directories::ProjectDirs::from("", "", "snake_game")?;
+        let storage = Self::new(dirs.data_dir());
+        storage.adopt_legacy(".");
+        Some(storage)
+    }
+
+    // Copy the saves an older version left in `legacy` - the working directory - into the
+    // data folder, the first time there is one to make. Only save data is copied, never over
+    // a save that's already there, and the originals are left alone: from then on the
+    // legacy folder isn't read, written or cleared. Returns how many were copied
+    pub fn adopt_legacy(&self, legacy: impl Into<std::path::PathBuf>) -> usize {
+        if self.files.dir.exists() {
+            return 0;
+        }
+        let legacy = FileStorage::new(legacy);
+        let mut copied = 0;
+        for key in legacy.keys().into_iter().filter(|key| is_save_data(key)) {
+            let Some(value) = legacy.read(&key) else {
+                continue;
+            };
+            match self.write(&key, &value) {
+                Ok(()) => copied += 1,
+                Err(e) => eprintln!(
+                    "Failed to copy {} to {}: {}",
+                    key,
+                    self.files.dir.display(),
+                    e
+                ),
+            }
+        }
+        copied
+    }
+}
+
+impl StorageBackend for PlatformStorage {
+    fn read(&self, key: &str) -> Option<String> {
+        self.files.read(key)
+    }
+
+    // The folder is only made on the first save, so just running the game leaves nothing
+    fn write(&self, key: &str, value: &str) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.files.dir)?;
+        self.files.write(key, value)
+    }
+
+    fn remove(&self, key: &str) -> std::io::Result<()> {
+        self.files.remove(key)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.files.keys()
+    }
+
+    fn read_bytes(&self, key: &str) -> Option<Vec<u8>> {
+        self.files.read_bytes(key)
+    }
+
+    fn write_bytes(&self, key: &str, value: &[u8]) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.files.dir)?;
+        self.files.write_bytes(key, value)
+    }
+}
+
+// Where saved files go when nothing says otherwise - set once at launch, from
+// SNAKE_DATA_DIR or --data-dir
+static DATA_DIR: std::sync::OnceLock<std::path::PathBuf> = std::sync::OnceLock::new();
+
+// Returns false if the data directory was already set
+pub fn set_data_dir(dir: impl Into<std::path::PathBuf>) -> bool {
+    DATA_DIR.set(dir.into()).is_ok()
+}
+
+// Files in the data directory if one was set, otherwise in the platform's data folder
+// (or the working directory, on a system without one)
+#[cfg(all(not(test), not(target_arch = "wasm32")))]
+pub fn default_storage() -> Arc<dyn StorageBackend> {
+    if let Some(dir) = DATA_DIR.get() {
+        return Arc::new(FileStorage::new(dir.clone()));
+    }
+    match PlatformStorage::locate() {
+        Some(storage) => Arc::new(storage),
+        None => Arc::new(FileStorage::new(".")),
+    }
+}
+
+// There's no writable filesystem in a browser
+#[cfg(all(not(test), target_arch = "wasm32"))]
+pub fn default_storage() -> Arc<dyn StorageBackend> {
+    Arc::new(LocalStorage::default())
+}
+
+// Unit tests each get their own empty storage, so they can't clobber the player's files
+#[cfg(test)]
+pub fn default_storage() -> Arc<dyn StorageBackend> {
+    Arc::new(InMemoryStorage::default())
+}
+
+// Where high scores are saved - co-op gets its own leaderboard since it's a different game
+pub const HIGH_SCORE_FILE: &str = "high_score.txt";
+pub const COOP_HIGH_SCORE_FILE: &str = "high_score_coop.txt";
+// and the longest runs, kept per mode the same way
+pub const BEST_TIME_FILE: &str = "best_time.txt";
+pub const COOP_BEST_TIME_FILE: &str = "best_time_coop.txt";
+// and the best score whose replay played back to it, when that's not the high score
+pub const CHECKED_HIGH_SCORE_FILE: &str = "checked_high_score.txt";
+pub const COOP_CHECKED_HIGH_SCORE_FILE: &str = "checked_high_score_coop.txt";
+
+// Party mode unlocks once the high score reaches this
+pub const RAINBOW_UNLOCK_SCORE: u32 = 200;
+// How long the screen pulses after eating in party mode
+pub const FOOD_PULSE_DURATION: f64 = 0.3;
+
+// How long the intro splash stays up, and how long it takes to fade in and out
+pub const SPLASH_DURATION: f64 = 2.5;
+pub const SPLASH_FADE: f64 = 0.5;
+
+// Game state struct - track all the game state
+#[derive(Clone)]
+pub struct GameState {
+    pub run: Run, // The snake, food and score and the rest the rules decide
+    pub high_score: u32,
+    pub high_score_checked: bool, // whether the high score's replay has played back to it
+    pub best_time: f64,           // Longest run survived, in seconds
+    pub anim_time: f64,           // Cosmetic animation clock, keeps running when the game doesn't
+    pub screen: Screen,
+    pub screen_time: f64,   // Time spent on the current screen, for fades
+    pub menu: MainMenu,     // Focus on the main menu and settings, and whether Quit was picked
+    pub settings: Settings, // What the player chose on the settings screen
+    pub transition: Option<Transition>,
+    pub reduced_motion: bool, // Skip transitions and other non-essential motion
+    pub theme: Theme,
+    pub photosensitive: bool, // Never flash or cycle colors
+    pub food_pulse: f64,      // Time left on the party mode screen pulse
+    pub toast: Option<Toast>,
+    pub packs: Vec<String>,       // content packs in use, in load order
+    pub haptics: bool,            // Rumble or vibrate on eating and dying
+    pub haptic_intensity: f32,    // 0.0 to 1.0
+    pub notify_best: bool,        // Desktop notification on a new high score
+    pub show_hud: bool,           // Score, times and the game over overlay
+    pub levels: Option<LevelRun>, // The levels being played through, if any
+    pub key_repeat: KeyRepeat,    // Which held keys repeat
+    pub mixer: Mixer,             // Music and sound effect volumes
+    pub text_cache: TextCache,    // Labels already laid out, shared by every frame
+    pub storage: Arc<dyn StorageBackend>, // Where high scores and the like are saved
+}
+
+// The game reads and steers its run as if the run's fields were its own
+impl std::ops::Deref for GameState {
+    type Target = Run;
+
+    fn deref(&self) -> &Run {
+        &self.run
+    }
+}
+
+impl std::ops::DerefMut for GameState {
+    fn deref_mut(&mut self) -> &mut Run {
+        &mut self.run
+    }
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameState {
+    pub fn new() -> Self {
+        Self::with_storage(default_storage())
+    }
+
+    // A new game on a board of another size
+    pub fn new_with_config(config: GameConfig) -> Self {
+        Self::with_storage_and_config(default_storage(), config)
+    }
+
+    // A new game whose every roll comes from `seed`, so it plays out the same each time
+    // given the same moves
+    pub fn new_with_seed(seed: u64) -> Self {
+        let mut game = Self::new();
+        game.reseed(seed);
+        game
+    }
+
+    // A new game that saves its high score and best time to `storage`
+    pub fn with_storage(storage: Arc<dyn StorageBackend>) -> Self {
+        Self::with_storage_and_config(storage, GameConfig::default())
+    }
+
+    pub fn with_storage_and_config(storage: Arc<dyn StorageBackend>, config: GameConfig) -> Self {
+        // Initialize snake in the center, moving right
+        let mut game = Self {
+            run: Run::new(config, rand::random()),
+            high_score: 0, // the records are loaded below, once the mode is known
+            high_score_checked: true,
+            best_time: 0.0,
+            anim_time: 0.0,
+            screen: Screen::Playing,
+            screen_time: 0.0,
+            menu: MainMenu::default(),
+            settings: Settings::default(),
+            transition: None,
+            reduced_motion: false,
+            theme: Theme::default(),
+            photosensitive: false,
+            food_pulse: 0.0,
+            levels: None,
+            toast: None,
+            packs: Vec::new(),
+            haptics: true,
+            haptic_intensity: DEFAULT_HAPTIC_INTENSITY,
+            notify_best: false,
+            show_hud: true,
+            key_repeat: KeyRepeat::default(),
+            mixer: Mixer::load(storage.as_ref()),
+            text_cache: TextCache::default(),
+            storage,
+        };
+        game.load_records();
+        game
+    }
+
+    // Play with another mix of food kinds. Each mix keeps its own high score
+    pub fn set_food_mix(&mut self, mix: FoodMix) {
+        self.food_mix = mix;
+        self.spawn_food();
+        self.load_records();
+    }
+
+    // Play through `levels` in order, starting on the first
+    pub fn with_levels(levels: Vec<Level>) -> Result<Self, String> {
+        let first = levels.first().ok_or("there are no levels to play")?;
+        let mut game = Self::new_with_config(first.config);
+        game.levels = Some(LevelRun {
+            levels,
+            current: 0,
+            start_score: 0,
+            complete_for: None,
+            board_changed: false,
+            cleared: false,
+            first: 0,
+            start_time: 0.0,
+        });
+        game.load_level(0);
+        game.load_records();
+        Ok(game)
+    }
+
+    // A level run of just `level`
+    pub fn with_level(level: Level) -> Self {
+        Self::with_levels(vec![level]).expect("a run of one level has a level to start on")
+    }
+
+    // Set up the board for level `index` of the level run, keeping the score
+    pub fn load_level(&mut self, index: usize) {
+        let (score, elapsed, config) = (self.score, self.elapsed, self.config);
+        let Some(run) = &mut self.levels else {
+            return;
+        };
+        run.current = index;
+        run.start_score = score;
+        run.start_time = elapsed;
+        run.complete_for = None;
+        run.cleared = false;
+        let level = run.level().clone();
+        run.board_changed |= level.config != config;
+
+        self.config = level.config;
+        self.walls = level.walls;
+        self.speed = level.speed;
+        self.clock = TickClock::default();
+        let start = SnakeStart::centered(&self.config);
+        self.spawns = start.into();
+        self.snake = start.cells();
+        self.direction = start.direction;
+        self.next_direction = start.direction;
+        self.turn_queue.clear();
+        self.slippery_cells.clear();
+        self.grip_cells.clear();
+        let was_over = self.game_over;
+        self.spawn_food();
+        self.settle(was_over);
+    }
+
+    // Whether the level complete overlay is up, holding the game until the next level
+    pub fn level_complete(&self) -> bool {
+        self.levels
+            .as_ref()
+            .is_some_and(|run| run.complete_for.is_some())
+    }
+
+    // Put up the level complete overlay once this level's target score is reached
+    fn check_level_target(&mut self) {
+        let score = self.score;
+        if let Some(run) = &mut self.levels {
+            if run.complete_for.is_none()
+                && score.saturating_sub(run.start_score) >= run.level().target_score
+            {
+                run.complete_for = Some(LEVEL_COMPLETE_DURATION);
+                self.record_level(true);
+            }
+        }
+    }
+
+    // Put how this level went into the player's level progress
+    // Generated levels are left out - there'd be a new record for every one
+    fn record_level(&self, cleared: bool) {
+        let Some(run) = &self.levels else {
+            return;
+        };
+        if run.level().seed.is_some() {
+            return;
+        }
+        let mut progress = LevelProgress::load(self.storage.as_ref());
+        progress.record(
+            &run.level().name,
+            self.score.saturating_sub(run.start_score),
+            self.elapsed - run.start_time,
+            cleared,
+        );
+        if let Err(e) = progress.save(self.storage.as_ref()) {
+            eprintln!("Failed to save level progress: {}", e);
+        }
+    }
+
+    // Swap a generated level being played for a new one the same size, and start on it
+    pub fn regenerate_level(&mut self) {
+        let seed = self.rng.gen();
+        let Some(run) = &mut self.levels else {
+            return;
+        };
+        if run.level().seed.is_none() {
+            return;
+        }
+        run.levels[run.current] = Level::generate(seed, run.level().config);
+        run.first = run.current;
+        self.restart();
+    }
+
+    // Start a fresh level run from level `index`, e.g. one picked from the level select
+    pub fn start_from_level(&mut self, index: usize) {
+        let Some(run) = &mut self.levels else {
+            return;
+        };
+        run.first = index.min(run.levels.len() - 1);
+        self.restart();
+    }
+
+    // Move on from a completed level - after the last one, the run's over
+    pub fn next_level(&mut self) {
+        let Some(run) = &mut self.levels else {
+            return;
+        };
+        if run.is_last() {
+            run.complete_for = None;
+            run.cleared = true;
+            self.end_run();
+        } else {
+            let next = run.current + 1;
+            self.load_level(next);
+        }
+    }
+
+    // Resize the window after a level changes the board's size
+    pub fn fit_window(&mut self, ctx: &mut Context) -> GameResult {
+        if let Some(run) = &mut self.levels {
+            if std::mem::take(&mut run.board_changed) {
+                ctx.gfx
+                    .set_drawable_size(self.config.screen_width(), self.config.screen_height())?;
+            }
+        }
+        Ok(())
+    }
+
+    // Generate a random food position on the default board that doesn't overlap with snake,
+    // or None if the snake covers the whole board
+    pub fn generate_food_position(snake: &[Position]) -> Option<Position> {
+        Self::generate_food_position_in(snake, &GameConfig::default())
+    }
+
+    // The same on a board of any size
+    pub fn generate_food_position_in(snake: &[Position], config: &GameConfig) -> Option<Position> {
+        Self::generate_food_position_with(snake, config, &mut rand::thread_rng())
+    }
+
+    // The same, rolling `rng`
+    pub fn generate_food_position_with(
+        snake: &[Position],
+        config: &GameConfig,
+        rng: &mut impl Rng,
+    ) -> Option<Position> {
+        rules::free_cell(snake, config, rng)
+    }
+
+    // Read a single saved number, falling back to the default (zero)
+    fn load_saved_value<T: std::str::FromStr + Default>(
+        storage: &dyn StorageBackend,
+        key: &str,
+    ) -> T {
+        match storage.read(key) {
+            Some(content) => content.trim().parse().unwrap_or_default(),
+            None => T::default(), // Nothing saved yet or it can't be read, start with 0
+        }
+    }
+
+    // Each mode keeps its own high score
+    // and content packs change the game too, so each set of packs gets its own as well
+    pub fn high_score_file(&self) -> String {
+        self.record_file(if self.coop {
+            COOP_HIGH_SCORE_FILE
+        } else {
+            HIGH_SCORE_FILE
+        })
+    }
+
+    // The same for the longest run survived
+    pub fn best_time_file(&self) -> String {
+        self.record_file(if self.coop {
+            COOP_BEST_TIME_FILE
+        } else {
+            BEST_TIME_FILE
+        })
+    }
+
+    // The same for the best score that's been checked
+    pub fn checked_high_score_file(&self) -> String {
+        self.record_file(if self.coop {
+            COOP_CHECKED_HIGH_SCORE_FILE
+        } else {
+            CHECKED_HIGH_SCORE_FILE
+        })
+    }
+
+    // `file` with this run's mode, board, rules and packs added on
+    fn record_file(&self, file: &str) -> String {
+        let mut name = file.trim_end_matches(".txt").to_string();
+        if self.levels.is_some() {
+            // the board changes from level to level, so the levels share one record
+            name += "_levels";
+        } else {
+            let board = GameConfig {
+                walls: WallMode::Solid,
+                speed: SpeedPreset::Normal,
+                ..self.config
+            };
+            if board != GameConfig::default() {
+                name += &format!("_{}x{}", self.config.grid_width, self.config.grid_height);
+            }
+            if self.config.walls == WallMode::Wrap {
+                name += "_wrap";
+            }
+            if self.config.speed != SpeedPreset::Normal {
+                name += &format!("_{}", self.config.speed.name());
+            }
+        }
+        if self.food_mix != FoodMix::classic() {
+            name += "_mixed";
+        }
+        if !self.packs.is_empty() {
+            let mut packs = self.packs.clone();
+            packs.sort();
+            name += &format!("_{}", packs.join("+"));
+        }
+        name + ".txt"
+    }
+
+    // Save high score
+    fn save_high_score(&self) {
+        let key = self.high_score_file();
+        if let Err(e) = self.storage.write(&key, &self.high_score.to_string()) {
+            eprintln!("Failed to save high score: {}", e);
+        }
+    }
+
+    // Pick up the high score and best time for the mode being played
+    pub fn load_records(&mut self) {
+        self.high_score = Self::load_saved_value(self.storage.as_ref(), &self.high_score_file());
+        self.best_time = Self::load_saved_value(self.storage.as_ref(), &self.best_time_file());
+        // high scores from before they were checked count as checked
+        let checked: Option<u32> = self
+            .storage
+            .read(&self.checked_high_score_file())
+            .and_then(|text| text.trim().parse().ok());
+        self.high_score_checked = checked.is_none_or(|checked| checked >= self.high_score);
+    }
+
+    // The high score's replay played back to it
+    pub fn mark_high_score_checked(&mut self) {
+        self.high_score_checked = true;
+        let key = self.checked_high_score_file();
+        if let Err(e) = self.storage.write(&key, &self.high_score.to_string()) {
+            eprintln!("Failed to save checked high score: {}", e);
+        }
+    }
+
+    // Save best survival time
+    fn save_best_time(&self) {
+        let key = self.best_time_file();
+        if let Err(e) = self.storage.write(&key, &self.best_time.to_string()) {
+            eprintln!("Failed to save best time: {}", e);
+        }
+    }
+
+    // Check if current score is a new high score and update if necessary
+    pub fn update_high_score(&mut self) {
+        if self.score > self.high_score {
+            if self.high_score_checked {
+                // keep hold of the last checked one before it's beaten
+                self.mark_high_score_checked();
+            }
+            self.high_score = self.score;
+            self.high_score_checked = false;
+            self.save_high_score();
+        }
+    }
+
+    // Same as update_high_score but for how long the run lasted
+    pub fn update_best_time(&mut self) {
+        if self.elapsed > self.best_time {
+            self.best_time = self.elapsed;
+            self.save_best_time();
+        }
+    }
+
+    // Update game state (called every frame)
+    pub fn update(&mut self, ctx: &mut Context) -> GameResult {
+        self.fit_window(ctx)?;
+        self.tick(ctx.time.delta().as_secs_f64());
+        Ok(())
+    }
+
+    // `dt` seconds of game time - timers, animation and any moves that fall due - for
+    // tests, bots and loops that count time in seconds. Negative, NaN and infinite times
+    // count as none, and a long stall counts as MAX_TICK_SECONDS
+    pub fn tick(&mut self, dt: f64) {
+        let dt = if dt.is_finite() {
+            dt.clamp(0.0, MAX_TICK_SECONDS)
+        } else {
+            0.0
+        };
+        self.advance(std::time::Duration::from_secs_f64(dt));
+    }
+
+    // A frame's worth of time, without needing a ggez Context
+    pub fn advance(&mut self, frame: std::time::Duration) {
+        // the animation ticker is separate from the game tick so idle screens still move
+        let delta = frame.as_secs_f64();
+        self.anim_time += delta;
+        self.screen_time += delta;
+        self.food_pulse = (self.food_pulse - delta).max(0.0);
+        self.mixer
+            .set_intensity(music_intensity(self.speed, self.game_over));
+        self.mixer.advance(delta);
+        if let Some(toast) = &mut self.toast {
+            toast.time_left -= delta;
+            if toast.time_left <= 0.0 {
+                self.toast = None;
+            }
+        }
+
+        // Hold the game still while a transition plays out
+        if let Some(transition) = &mut self.transition {
+            transition.advance(delta);
+            if transition.is_finished() {
+                self.transition = None;
+            }
+            return;
+        }
+
+        if self.screen == Screen::Splash {
+            if self.screen_time >= SPLASH_DURATION {
+                self.change_screen_with(Screen::MainMenu, TransitionKind::Fade);
+            }
+            return;
+        }
+
+        // The menus and the pause screen hold the run where it is
+        if self.screen.is_menu() {
+            return;
+        }
+
+        if self.game_over {
+            return;
+        }
+
+        // Hold the game on the level complete overlay, then start the next level
+        if let Some(left) = self
+            .levels
+            .as_mut()
+            .and_then(|run| run.complete_for.as_mut())
+        {
+            *left -= delta;
+            if *left <= 0.0 {
+                self.next_level();
+            }
+            return;
+        }
+
+        // only count time while the run is live, so the clock stops on game over and
+        // doesn't start until the grace period's over
+        if !self.in_grace() {
+            self.elapsed += delta;
+        }
+
+        // Only move snake if enough time has passed
+        let speed = self.speed;
+        if self.clock.advance(frame, speed) {
+            self.step();
+        }
+    }
+
+    // One game tick - the run's rules, then the records and effects that follow from them -
+    // with no timing or drawing involved, so frontends other than ggez can drive the game
+    // at their own pace
+    pub fn step(&mut self) {
+        if self.level_complete() {
+            return;
+        }
+        let (score_before, was_over) = (self.score, self.game_over);
+        self.run.step();
+        self.settle(was_over);
+        self.check_level_target();
+
+        // Party mode flashes the screen whenever food gets eaten
+        if self.score > score_before && self.party_mode_active() {
+            self.food_pulse = FOOD_PULSE_DURATION;
+        }
+
+        // Wipe over to the game over screen
+        if self.game_over {
+            self.start_transition(TransitionKind::Wipe);
+        }
+    }
+
+    // Move the snake by the rules, recording the run if that ended it
+    pub fn move_snake(&mut self) {
+        let was_over = self.game_over;
+        self.run.move_snake();
+        self.settle(was_over);
+    }
+
+    // The records and the game over screen for a run the rules have just ended
+    fn settle(&mut self, was_over: bool) {
+        if self.game_over && !was_over {
+            self.end_run();
+        }
+    }
+
+    // Ice variant: the whole board is slippery apart from a lattice of grip cells
+    pub fn new_ice() -> Self {
+        let mut game = Self::new();
+        game.ice = true;
+        let height = game.config.grid_height;
+        game.grip_cells = (0..game.config.grid_width)
+            .step_by(ICE_GRIP_SPACING)
+            .flat_map(|x| {
+                (0..height)
+                    .step_by(ICE_GRIP_SPACING)
+                    .map(move |y| Position::new(x, y))
+            })
+            .collect();
+        game
+    }
+
+    // Co-op: two players share one snake
+    pub fn new_coop() -> Self {
+        let mut game = Self::new();
+        game.set_coop(true);
+        game
+    }
+
+    // Switch co-op on or off, picking up that mode's high score
+    pub fn set_coop(&mut self, coop: bool) {
+        self.coop = coop;
+        self.load_records();
+    }
+
+    // Play with these packs, applied in order so later ones win, picking up their high score
+    pub fn use_packs(&mut self, packs: &[&Pack]) {
+        for pack in packs {
+            pack.apply(self);
+        }
+        self.packs = packs
+            .iter()
+            .map(|pack| pack.manifest.name.clone())
+            .collect();
+        self.load_records();
+    }
+
+    // End the current run, recording the high score and best time
+    pub fn end_run(&mut self) {
+        self.game_over = true;
+        self.change_screen(Screen::GameOver);
+        self.mixer.duck(); // under the death sound
+
+        // a run that dies partway through a level still counts towards its records
+        if self
+            .levels
+            .as_ref()
+            .is_some_and(|run| run.complete_for.is_none() && !run.cleared)
+        {
+            self.record_level(false);
+        }
+        let previous_best = self.high_score;
+        self.update_high_score();
+        self.update_best_time();
+        if self.notify_best && self.high_score > previous_best {
+            notify_personal_best(self.personal_best_summary());
+        }
+    }
+
+    // One line about the run that set a new high score, for the notification
+    pub fn personal_best_summary(&self) -> String {
+        let mode = if self.coop { " in co-op" } else { "" };
+        format!(
+            "New best{}: {} points, {} long after {}",
+            mode,
+            self.score,
+            self.snake.len(),
+            format_time(self.elapsed)
+        )
+    }
+
+    pub fn toggle_best_notifications(&mut self) {
+        if !cfg!(feature = "notifications") {
+            self.show_toast("Notifications need the notifications feature");
+            return;
+        }
+        self.notify_best = !self.notify_best;
+        self.show_toast(if self.notify_best {
+            "High score notifications on"
+        } else {
+            "High score notifications off"
+        });
+    }
+
+    pub fn show_toast(&mut self, message: impl Into<String>) {
+        self.toast = Some(Toast {
+            message: message.into(),
+            time_left: TOAST_DURATION,
+        });
+    }
+
+    // Swap in the theme from `path`, keeping party mode as it was. A broken file
+    // leaves the current theme alone and says what's wrong in a toast
+    pub fn reload_theme(&mut self, path: &str) -> bool {
+        match Theme::load(path) {
+            Ok(theme) => {
+                self.theme = Theme {
+                    rainbow: self.theme.rainbow,
+                    ..theme
+                };
+                true
+            }
+            Err(e) => {
+                self.show_toast(format!("{}: {}", path, e));
+                false
+            }
+        }
+    }
+
+    // Switch to another screen and restart its clock
+    pub fn change_screen(&mut self, screen: Screen) {
+        self.screen = screen;
+        self.screen_time = 0.0;
+    }
+
+    // Same as change_screen but with a transition effect
+    pub fn change_screen_with(&mut self, screen: Screen, kind: TransitionKind) {
+        self.change_screen(screen);
+        self.start_transition(kind);
+    }
+
+    // Start a transition over whatever is currently showing (unless the player wants less motion)
+    pub fn start_transition(&mut self, kind: TransitionKind) {
+        if !self.reduced_motion {
+            self.transition = Some(Transition::new(kind, TRANSITION_DURATION));
+        }
+    }
+
+    // Cut any running transition short
+    pub fn skip_transition(&mut self) {
+        self.transition = None;
+    }
+
+    // Start a fresh game, keeping the player's preferences
+    pub fn restart(&mut self) {
+        let (storage, mut config) = (self.storage.clone(), self.config);
+        if self.levels.is_none() {
+            // rules changed in the settings take over from the next run
+            config.walls = self.settings.walls;
+            config.speed = self.settings.speed;
+        }
+        let previous = std::mem::replace(self, GameState::with_storage_and_config(storage, config));
+        // what carries over from run to run
+        self.reduced_motion = previous.reduced_motion;
+        self.settings = previous.settings;
+        self.photosensitive = previous.photosensitive;
+        self.theme = previous.theme;
+        self.weather = previous.run.weather;
+        self.ice = previous.run.ice;
+        self.grip_cells = previous.run.grip_cells;
+        self.levels = previous.levels;
+        self.handicap = previous.run.handicap;
+        self.food_policy = previous.run.food_policy;
+        self.food_mix = previous.run.food_mix;
+        self.hazards = previous.run.hazards;
+        self.packs = previous.packs;
+        self.haptics = previous.haptics;
+        self.haptic_intensity = previous.haptic_intensity;
+        self.notify_best = previous.notify_best;
+        self.show_hud = previous.show_hud;
+        self.turn_cooldown = previous.run.turn_cooldown;
+        self.key_repeat = previous.key_repeat;
+        self.mixer = previous.mixer;
+        self.rng = previous.run.rng;
+        self.text_cache = previous.text_cache;
+        // each run gets its own seed, drawn from the last, so a seeded session repeats
+        // and any one run can be played again from its seed alone
+        let seed = self.rng.gen();
+        self.reseed(seed);
+        self.set_spawns(previous.run.spawns);
+        self.set_coop(previous.run.coop);
+        self.apply_handicap();
+        if let Some(run) = &self.levels {
+            // a level run starts over from the level it started on
+            self.load_level(run.first);
+            self.load_records();
+        }
+        self.start_transition(TransitionKind::Slide);
+    }
+
+    // Party mode is a reward for getting a good high score
+    pub fn rainbow_unlocked(&self) -> bool {
+        self.high_score >= RAINBOW_UNLOCK_SCORE
+    }
+
+    // Turn party mode on or off, if it's been unlocked
+    pub fn toggle_rainbow(&mut self) {
+        if self.rainbow_unlocked() {
+            self.theme.rainbow = !self.theme.rainbow;
+        }
+    }
+
+    // Party mode only runs if the photosensitivity setting allows it
+    pub fn party_mode_active(&self) -> bool {
+        self.theme.rainbow && !self.photosensitive
+    }
+
+    // Color for a snake segment, taking party mode into account
+    pub fn segment_color(&self, index: usize) -> Color {
+        if self.party_mode_active() {
+            // hue drifts over time and shifts a little per segment so colors roll along the body
+            hue_color(self.anim_time as f32 * 0.5 - index as f32 * 0.05)
+        } else {
+            self.theme.segment_color(index, self.snake.len())
+        }
+    }
+
+    pub fn toggle_turn_assist(&mut self) {
+        self.turn_cooldown = if self.turn_cooldown > 0.0 {
+            0.0
+        } else {
+            TURN_COOLDOWN
+        };
+        self.turned_at = None;
+        self.show_toast(if self.turn_cooldown > 0.0 {
+            "Turn assist on"
+        } else {
+            "Turn assist off"
+        });
+    }
+
+    // In co-op each player only gets their own axis: A/D for player one, Up/Down arrows for
+    // player two, so the other direction keys do nothing
+    // Buzz for something that happened, unless the player has switched haptics off.
+    // Gives how long in milliseconds and how hard, 0.0 to 1.0
+    pub fn haptic_pulse(&self, event: HapticEvent) -> Option<(u32, f32)> {
+        let (duration, strength) = event.pulse();
+        self.rumble(duration, strength)
+    }
+
+    // A buzz as it should play, turned down by the player's settings, or None if off
+    pub fn rumble(&self, duration_ms: u32, strength: f32) -> Option<(u32, f32)> {
+        let intensity = self.haptic_intensity.clamp(0.0, 1.0);
+        if !self.haptics || intensity == 0.0 {
+            return None;
+        }
+        Some((duration_ms, strength * intensity))
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.mixer.muted = !self.mixer.muted;
+        self.mixer.save(self.storage.as_ref());
+        self.save_settings();
+        self.show_toast(if self.mixer.muted {
+            "Sound muted"
+        } else {
+            "Sound on"
+        });
+    }
+
+    pub fn toggle_haptics(&mut self) {
+        self.haptics = !self.haptics;
+        self.save_settings();
+        self.show_toast(if self.haptics {
+            "Vibration on"
+        } else {
+            "Vibration off"
+        });
+    }
+}
+
+// A desktop notification, for players streaming or keeping the window tucked in a
+// corner. Sent from its own thread so a slow notification daemon can't stall the game
+#[cfg(feature = "notifications")]
+fn notify_personal_best(summary: String) {
+    std::thread::spawn(move || {
+        if let Err(e) = notify_rust::Notification::new()
+            .summary("Super Sick Snake Game")
+            .body(&summary)
+            .show()
+        {
+            eprintln!("Couldn't show notification: {}", e);
+        }
+    });
+}
+
+#[cfg(not(feature = "notifications"))]
+fn notify_personal_best(_summary: String) {}
+
+// Format a duration in seconds as m:ss for the HUD
+pub fn format_time(seconds: f64) -> String {
+    let whole_seconds = seconds.max(0.0) as u64;
+    format!("{}:{:02}", whole_seconds / 60, whole_seconds % 60)
+}
+
+// How the two boards of a DualGame are played
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DualMode {
+    Mirrored,                     // one player steers both boards with the same keys
+    Versus { target_score: u32 }, // WASD vs arrows, first to the target score wins
+}
+
+// Two games side by side in one window - either a solo challenge where both boards are
+// steered by the same keys, or local versus where each player gets their own board.
+// Losing on either board ends the run.
+pub struct DualGame {
+    pub boards: [GameState; 2],
+    pub mode: DualMode,
+    pub series: Series,       // only used in versus
+    pub round_recorded: bool, // whether the current round's result is in the series yet
+    pub handicaps: [Handicap; 2],
+    pub player_names: [String; 2],
+    pub rating_changes: Option<[(f64, f64); 2]>, // (before, after) per player, once the match is decided
+}
+
+impl Default for DualGame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DualGame {
+    pub fn new() -> Self {
+        Self::with_config(GameConfig::default())
+    }
+
+    // Both boards the size `config` says
+    pub fn with_config(config: GameConfig) -> Self {
+        let storage = default_storage();
+        Self {
+            boards: [
+                GameState::with_storage_and_config(storage.clone(), config),
+                GameState::with_storage_and_config(storage, config),
+            ],
+            mode: DualMode::Mirrored,
+            series: Series::new(1),
+            round_recorded: false,
+            handicaps: [Handicap::default(); 2],
+            player_names: ["P1".to_string(), "P2".to_string()],
+            rating_changes: None,
+        }
+    }
+
+    // Local versus, player one on the left board (WASD) and player two on the right (arrows)
+    pub fn new_versus(target_score: u32) -> Self {
+        Self::new_match(MatchSettings::new(target_score))
+    }
+
+    // Local versus between two named players, whose ratings get updated after each match
+    pub fn new_named_versus(target_score: u32, player_names: [String; 2]) -> Self {
+        Self::new_match(MatchSettings {
+            player_names,
+            ..MatchSettings::new(target_score)
+        })
+    }
+
+    // Local versus with each player's handicap applied to their board
+    pub fn new_match(settings: MatchSettings) -> Self {
+        let mut dual = Self {
+            mode: DualMode::Versus {
+                target_score: settings.target_score,
+            },
+            series: Series::new(settings.best_of),
+            handicaps: settings.handicaps,
+            player_names: settings.player_names,
+            ..Self::with_config(settings.config)
+        };
+        dual.reset_boards();
+        dual
+    }
+
+    // Fresh boards for the next round, with handicaps applied
+    fn reset_boards(&mut self) {
+        for (board, handicap) in self.boards.iter_mut().zip(self.handicaps) {
+            // restart applies the board's handicap for us
+            board.handicap = handicap;
+            board.restart();
+        }
+        self.round_recorded = false;
+    }
+
+    // Put the finished round into the series (once)
+    pub fn record_round(&mut self) {
+        if matches!(self.mode, DualMode::Versus { .. }) && self.is_over() && !self.round_recorded {
+            self.series.record_round(self.winner());
+            self.round_recorded = true;
+
+            if self.series.match_winner().is_some() {
+                // both boards save to the same place
+                let storage = self.boards[0].storage.clone();
+                let mut ratings = Ratings::load(storage.as_ref());
+                self.update_ratings(&mut ratings);
+                ratings.save(storage.as_ref());
+            }
+        }
+    }
+
+    // Apply the decided match to the players' ratings
+    pub fn update_ratings(&mut self, ratings: &mut Ratings) {
+        let Some(winner) = self.series.match_winner() else {
+            return;
+        };
+        let loser = 1 - winner;
+        let [winner_change, loser_change] =
+            ratings.record_match(&self.player_names[winner], &self.player_names[loser]);
+        let mut changes = [winner_change; 2];
+        changes[loser] = loser_change;
+        self.rating_changes = Some(changes);
+    }
+
+    // Move on after a finished round: the next round, or a rematch once the match is decided
+    pub fn next_round(&mut self) {
+        if !self.round_recorded {
+            return;
+        }
+        if self.series.match_winner().is_some() {
+            self.series = Series::new(self.series.best_of);
+            self.rating_changes = None;
+        }
+        self.reset_boards();
+    }
+
+    // Which board a key should go to - None means both
+    pub fn board_for_key(&self, keycode: KeyCode) -> Option<usize> {
+        match (self.mode, keycode) {
+            (DualMode::Versus { .. }, KeyCode::W | KeyCode::A | KeyCode::S | KeyCode::D) => Some(0),
+            (
+                DualMode::Versus { .. },
+                KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right,
+            ) => Some(1),
+            _ => None,
+        }
+    }
+
+    // In versus, the first board to reach the target wins, or the one still alive if the other crashed
+    pub fn winner(&self) -> Option<usize> {
+        let DualMode::Versus { target_score } = self.mode else {
+            return None;
+        };
+        if let Some(index) = self
+            .boards
+            .iter()
+            .position(|board| board.score >= target_score)
+        {
+            return Some(index);
+        }
+        match (self.boards[0].game_over, self.boards[1].game_over) {
+            (true, false) => Some(1),
+            (false, true) => Some(0),
+            _ => None,
+        }
+    }
+
+    // The board size both boards share
+    pub fn config(&self) -> &GameConfig {
+        &self.boards[0].config
+    }
+
+    // Both boards with a cell's width between them
+    pub fn window_width(config: &GameConfig) -> f32 {
+        config.screen_width() * 2.0 + Self::gap(config)
+    }
+
+    pub fn gap(config: &GameConfig) -> f32 {
+        config.cell_size
+    }
+
+    // Where a board starts horizontally in the window
+    pub fn board_offset(config: &GameConfig, index: usize) -> f32 {
+        index as f32 * (config.screen_width() + Self::gap(config))
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.boards.iter().any(|board| board.game_over) || self.winner().is_some()
+    }
+
+    // Once one board is lost (or won), end the other run too so both show their final score
+    pub fn end_if_either_lost(&mut self) {
+        if self.is_over() {
+            for board in &mut self.boards {
+                if !board.game_over {
+                    board.end_run();
+                }
+            }
+        }
+    }
+}
+
+impl EventHandler for DualGame {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        for board in &mut self.boards {
+            board.update(ctx)?;
+        }
+        self.end_if_either_lost();
+        self.record_round();
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        self.draw(ctx)
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        key_input: KeyInput,
+        repeat: bool,
+    ) -> GameResult {
+        // In versus, R/Enter move the match along instead of restarting a board on its own
+        if matches!(self.mode, DualMode::Versus { .. })
+            && matches!(key_input.keycode, Some(KeyCode::R | KeyCode::Return))
+        {
+            if !repeat {
+                self.next_round();
+            }
+            return Ok(());
+        }
+
+        let target = key_input
+            .keycode
+            .and_then(|keycode| self.board_for_key(keycode));
+        for (index, board) in self.boards.iter_mut().enumerate() {
+            if target.is_none() || target == Some(index) {
+                board.key_down_event(ctx, key_input, repeat)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Hotseat: players take turns at full runs on one board, then compare results
+pub struct Hotseat {
+    pub players: Vec<String>,
+    pub results: Vec<HotseatResult>, // one per finished turn, in turn order
+    pub game: GameState,
+    pub turn_recorded: bool, // whether the run on the board has been written down yet
+    pub naming: Option<Panel>, // the players typing their names in, before the first turn
+    pub name_filter: NameFilter,
+}
+
+// Most players a hotseat session asks names for, and how long a name can be
+pub const HOTSEAT_MAX_PLAYERS: usize = 4;
+pub const PLAYER_NAME_LENGTH: usize = 12;
+
+// How a player's turn went
+#[derive(Debug, Clone, PartialEq)]
+pub struct HotseatResult {
+    pub player: String,
+    pub score: u32,
+    pub time: f64,
+}
+
+impl Hotseat {
+    pub fn new(players: Vec<String>) -> Self {
+        Self {
+            players,
+            results: Vec::new(),
+            game: GameState::new(),
+            turn_recorded: false,
+            naming: None,
+            name_filter: NameFilter::default(),
+        }
+    }
+
+    // Start by asking who's playing. Blank names are left out
+    pub fn with_name_entry() -> Self {
+        let mut naming = Panel::default();
+        naming.push(Widget::title("Who's playing?"));
+        for player in 0..HOTSEAT_MAX_PLAYERS {
+            let name = if player < 2 {
+                format!("P{}", player + 1)
+            } else {
+                String::new()
+            };
+            let input = TextInput::new(CharFilter::Name, PLAYER_NAME_LENGTH).with_text(&name);
+            naming.push(Widget::input(format!("Player {}", player + 1), input));
+        }
+        naming.push(Widget::button("Start"));
+        naming.push(Widget::hint(
+            "Type a name, or pick letters with LB/RB. Enter or A to start",
+        ));
+        Self {
+            naming: Some(naming),
+            ..Self::new(Vec::new())
+        }
+    }
+
+    // Feed the name entry, starting the first turn once it's done. A name the filter
+    // turns down says why in place of the hint, and the players fix it
+    pub fn naming_input(&mut self, input: UiInput) {
+        let Some(naming) = &mut self.naming else {
+            return;
+        };
+        if !matches!(naming.input(input), Some(UiEvent::Pressed(_))) {
+            return;
+        }
+        let names: Result<Vec<String>, String> = naming
+            .widgets
+            .iter()
+            .filter_map(|widget| match widget {
+                Widget::Input { label, input } if !input.value().trim().is_empty() => Some(
+                    input
+                        .name(&self.name_filter)
+                        .map_err(|e| format!("{}: {}", label, e)),
+                ),
+                _ => None,
+            })
+            .collect();
+        let players = match names {
+            Ok(players) => players,
+            Err(e) => {
+                if let Some(hint) = naming.widgets.last_mut() {
+                    *hint = Widget::label(e, 14.0, Color::new(1.0, 0.4, 0.4, 1.0));
+                }
+                return;
+            }
+        };
+        if !players.is_empty() {
+            self.players = players;
+            self.naming = None;
+            self.game.restart();
+        }
+    }
+
+    // Whose turn it is, or None once everyone has played
+    pub fn current_player(&self) -> Option<&str> {
+        self.players.get(self.results.len()).map(String::as_str)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current_player().is_none()
+    }
+
+    // Once the current run ends, write down the player's result
+    pub fn record_turn(&mut self) {
+        if !self.game.game_over || self.turn_recorded {
+            return;
+        }
+        if let Some(player) = self.current_player() {
+            self.results.push(HotseatResult {
+                player: player.to_string(),
+                score: self.game.score,
+                time: self.game.elapsed,
+            });
+        }
+        self.turn_recorded = true;
+    }
+
+    // Results for the comparison table, best score first (longer survival breaks ties)
+    pub fn standings(&self) -> Vec<HotseatResult> {
+        let mut standings = self.results.clone();
+        standings.sort_by(|a, b| b.score.cmp(&a.score).then(b.time.total_cmp(&a.time)));
+        standings
+    }
+
+    // Hand the board to the next player, or start a new session once everyone's played
+    pub fn advance(&mut self) {
+        if !self.turn_recorded {
+            return;
+        }
+        if self.is_finished() {
+            self.results.clear();
+        }
+        self.game.restart();
+        self.turn_recorded = false;
+    }
+}
+
+impl EventHandler for Hotseat {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        if self.naming.is_some() {
+            return Ok(());
+        }
+        self.game.update(ctx)?;
+        self.record_turn();
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        self.draw(ctx)
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
+        if self.naming.as_ref().is_some_and(Panel::is_typing) {
+            self.naming_input(UiInput::Type(character));
+        }
+        Ok(())
+    }
+
+    fn gamepad_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        button: gilrs::Button,
+        _id: ggez::input::gamepad::GamepadId,
+    ) -> GameResult {
+        match UiInput::from_button(button) {
+            Some(input) if self.naming.is_some() => self.naming_input(input),
+            _ => self.game.pad_down(button),
+        }
+        Ok(())
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        key_input: KeyInput,
+        repeat: bool,
+    ) -> GameResult {
+        if let (Some(naming), Some(key)) = (&self.naming, key_input.keycode) {
+            let input = if naming.is_typing() {
+                UiInput::from_key_while_typing(key)
+            } else {
+                UiInput::from_key(key)
+            };
+            if let Some(input) =
+                input.filter(|input| KeyRepeat::default().accepts(input.role(), repeat))
+            {
+                self.naming_input(input);
+            }
+            return Ok(());
+        }
+        match key_input.keycode {
+            Some(KeyCode::Return) if !repeat => self.advance(),
+            Some(KeyCode::Return) => {}
+            // no restarting a bad run - everyone gets one go
+            Some(KeyCode::R) => {}
+            _ => self.game.key_down_event(ctx, key_input, repeat)?,
+        }
+        Ok(())
+    }
+}
+
+// Replay files: "SNKR", a version byte and a flags byte, then the body - optionally zstd
+// compressed - ending in a checksum of everything before it
+pub const REPLAY_VERSION: u8 = 5; // 2 added keyframes, 3 stores speeds in cells per second, 4 the RNG, 5 the walls
+                                  // Ticks between the keyframes stored in a replay, for seeking without replaying from the start
+pub const REPLAY_KEYFRAME_INTERVAL: usize = 100;
+
+// Memory limits for long sessions on small machines, like a Raspberry Pi cabinet
+pub const MEMORY_FILE: &str = "memory.txt";
+
+// What a long session may hold in memory. A run's replay keeps its last `replay_ticks`
+// ticks, and the replay browser keeps the newest replays that fit in `browser_bytes` -
+// older ones stay on disk but aren't loaded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryCaps {
+    pub replay_ticks: usize,
+    pub browser_bytes: usize,
+}
+
+impl Default for MemoryCaps {
+    fn default() -> Self {
+        Self {
+            replay_ticks: 200_000, // over five hours at top speed
+            browser_bytes: 32 * 1024 * 1024,
+        }
+    }
+}
+
+impl MemoryCaps {
+    // `key = value` lines: `replay_ticks = 50000` and `browser_mib = 8`
+    pub fn parse(text: &str) -> Result<MemoryCaps, String> {
+        let mut caps = MemoryCaps::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = match line.find(" # ") {
+                Some(comment) => &line[..comment],
+                None if line.trim_start().starts_with('#') => "",
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fail = |message: String| format!("line {}: {}", number + 1, message);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| fail(format!("expected `key = value`, got `{}`", line)))?;
+            let (key, value) = (key.trim(), value.trim());
+            let number: usize = value
+                .parse()
+                .map_err(|_| fail(format!("`{}` isn't a whole number", value)))?;
+            match key {
+                // trimming cuts at keyframes, so there has to be room for a couple
+                "replay_ticks" if number < REPLAY_KEYFRAME_INTERVAL * 2 => {
+                    return Err(fail(format!(
+                        "replay_ticks should be at least {}",
+                        REPLAY_KEYFRAME_INTERVAL * 2
+                    )))
+                }
+                "replay_ticks" => caps.replay_ticks = number,
+                "browser_mib" => caps.browser_bytes = number * 1024 * 1024,
+                _ => return Err(fail(format!("unknown key `{}`", key))),
+            }
+        }
+        Ok(caps)
+    }
+
+    pub fn load(path: &str) -> Result<MemoryCaps, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        MemoryCaps::parse(&text)
+    }
+}
+
+// Sizes for the debug overlay, e.g. "34.2 KiB"
+pub fn format_bytes(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+const REPLAY_MAGIC: &[u8; 4] = b"SNKR";
+const REPLAY_COMPRESSED: u8 = 1;
+
+// Everything needed to play a run back. Every food placed is stored rather than
+// regenerated from a seed, so unseeded runs play back too
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub seed: u64,    // the run's GameState::seed, 0 in replays from before runs had one
+    pub rng: RngKind, // what the seed was rolled with, Std before version 4
+    pub recorded_at: u64, // unix seconds
+    pub mode: String, // e.g. "classic" or "coop", for the replay browser
+    pub config: GameConfig, // the board it was played on - its size and walls
+    pub score: u32,
+    pub duration: f64,
+    pub start: Vec<Position>, // snake at the start, head first
+    pub start_direction: Direction,
+    pub directions: Vec<Direction>, // the direction the snake moved on each tick
+    pub food: Vec<Position>,        // every food placed, in order
+    pub keyframes: Vec<ReplayKeyframe>,
+}
+
+// Full state partway through a replay, so playback can jump there directly
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayKeyframe {
+    pub tick: usize,
+    pub snake: Vec<Position>,
+    pub direction: Direction,
+    pub food: Position,
+    pub next_food: usize, // index of the next food to be placed
+    pub score: u32,
+    pub speed: Speed,
+    pub game_over: bool,
+}
+
+// A point in a replay's playback - the game on `tick`, and which food goes down next
+#[derive(Clone)]
+pub struct ReplayCursor {
+    pub tick: usize,
+    pub game: GameState,
+    pub next_food: usize,
+}
+
+// What a determinism audit found: how many ticks the two runs agreed on, and if they
+// split, the tick it happened on with each run's state hash
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditReport {
+    pub ticks: usize,
+    pub divergence: Option<(usize, u64, u64)>,
+}
+
+impl AuditReport {
+    // Ticks count from 1, like the replay storyboard. A run that ends early diverges
+    // on the first tick the other one has and it doesn't
+    pub fn compare(first: &[u64], second: &[u64]) -> Self {
+        let agreed = first
+            .iter()
+            .zip(second)
+            .take_while(|(first, second)| first == second)
+            .count();
+        let divergence = (agreed < first.len().max(second.len())).then(|| {
+            (
+                agreed + 1,
+                first.get(agreed).copied().unwrap_or(0),
+                second.get(agreed).copied().unwrap_or(0),
+            )
+        });
+        Self {
+            ticks: agreed,
+            divergence,
+        }
+    }
+}
+
+impl std::fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.divergence {
+            None => write!(f, "deterministic over {} ticks", self.ticks),
+            Some((tick, first, second)) => write!(
+                f,
+                "diverged at tick {}: state hash {:016x} vs {:016x}",
+                tick, first, second
+            ),
+        }
+    }
+}
+
+// Why a replay couldn't be read
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayError {
+    NotAReplay,
+    UnsupportedVersion(u8),
+    Truncated,
+    ChecksumMismatch,
+    Compression(String),
+    BadBoard(String), // a board size this build can't play on
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ReplayError::NotAReplay => write!(f, "not a snake replay"),
+            ReplayError::UnsupportedVersion(version) => write!(
+                f,
+                "replay version {} is newer than this game supports ({})",
+                version, REPLAY_VERSION
+            ),
+            ReplayError::Truncated => write!(f, "replay is cut short"),
+            ReplayError::ChecksumMismatch => write!(f, "replay is corrupted"),
+            ReplayError::Compression(e) => write!(f, "couldn't decompress replay: {}", e),
+            ReplayError::BadBoard(e) => write!(f, "replay's board doesn't fit: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+fn direction_bits(direction: Direction) -> u8 {
+    match direction {
+        Direction::Up => 0,
+        Direction::Down => 1,
+        Direction::Left => 2,
+        Direction::Right => 3,
+    }
+}
+
+fn direction_from_bits(bits: u8) -> Direction {
+    match bits & 0b11 {
+        0 => Direction::Up,
+        1 => Direction::Down,
+        2 => Direction::Left,
+        _ => Direction::Right,
+    }
+}
+
+// Reads the replay body front to back, failing with Truncated instead of panicking
+struct ReplayReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ReplayReader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], ReplayError> {
+        if count > self.bytes.len() {
+            return Err(ReplayError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, ReplayError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ReplayError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ReplayError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn cell(&mut self) -> Result<Position, ReplayError> {
+        let cell = self.take(2)?;
+        Ok(Position::new(cell[0] as i32, cell[1] as i32))
+    }
+
+    fn cells(&mut self) -> Result<Vec<Position>, ReplayError> {
+        let count = self.u32()? as usize;
+        if count * 2 > self.bytes.len() {
+            return Err(ReplayError::Truncated);
+        }
+        (0..count).map(|_| self.cell()).collect()
+    }
+}
+
+impl Replay {
+    // Start recording from the state a run begins in
+    pub fn start(game: &GameState) -> Self {
+        Self {
+            seed: game.seed,
+            rng: game.rng.kind(),
+            recorded_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+            mode: if game.coop {
+                "coop"
+            } else if game.ice {
+                "ice"
+            } else {
+                "classic"
+            }
+            .to_string(),
+            config: game.config,
+            score: game.score,
+            duration: 0.0,
+            start: game.snake.clone(),
+            start_direction: game.direction,
+            directions: Vec::new(),
+            food: vec![game.food.position],
+            keyframes: Vec::new(),
+        }
+    }
+
+    // Call after each tick of the run being recorded
+    pub fn record_tick(&mut self, game: &GameState) {
+        self.directions.push(game.direction);
+        if self.food.last() != Some(&game.food.position) {
+            self.food.push(game.food.position);
+        }
+        self.score = game.score;
+        self.duration = game.elapsed;
+
+        if self
+            .directions
+            .len()
+            .is_multiple_of(REPLAY_KEYFRAME_INTERVAL)
+        {
+            self.keyframes.push(ReplayKeyframe {
+                tick: self.directions.len(),
+                snake: game.snake.clone(),
+                direction: game.direction,
+                food: game.food.position,
+                next_food: self.food.len(),
+                score: game.score,
+                speed: game.speed,
+                game_over: game.game_over,
+            });
+        }
+    }
+
+    pub fn tick_count(&self) -> usize {
+        self.directions.len()
+    }
+
+    pub fn cursor_at_start(&self) -> ReplayCursor {
+        // a trimmed replay starts from a keyframe, score and speed and all
+        if let Some(keyframe) = self.keyframes.first().filter(|keyframe| keyframe.tick == 0) {
+            return self.cursor_at(keyframe);
+        }
+        let mut game = GameState::new_with_config(self.config);
+        game.snake = self.start.clone();
+        game.direction = self.start_direction;
+        game.next_direction = self.start_direction;
+        if let Some(food) = self.food.first() {
+            game.food = Food::normal(*food);
+        }
+        ReplayCursor {
+            tick: 0,
+            game,
+            next_food: 1,
+        }
+    }
+
+    // Play one tick forward, false if the replay's already over
+    pub fn step(&self, cursor: &mut ReplayCursor) -> bool {
+        let Some(direction) = self.directions.get(cursor.tick) else {
+            return false;
+        };
+        let game = &mut cursor.game;
+        let score_before = game.score;
+        game.direction = *direction;
+        game.next_direction = *direction;
+        game.move_snake();
+        if game.score > score_before {
+            if let Some(food) = self.food.get(cursor.next_food) {
+                game.food = Food::normal(*food);
+            }
+            cursor.next_food += 1;
+        }
+        cursor.tick += 1;
+        true
+    }
+
+    // Jump to `tick`, starting from the nearest keyframe before it
+    pub fn seek(&self, tick: usize) -> ReplayCursor {
+        let tick = tick.min(self.tick_count());
+        let mut cursor = match self
+            .keyframes
+            .iter()
+            .rev()
+            .find(|keyframe| keyframe.tick <= tick)
+        {
+            Some(keyframe) => self.cursor_at(keyframe),
+            None => self.cursor_at_start(),
+        };
+        while cursor.tick < tick && self.step(&mut cursor) {}
+        cursor
+    }
+
+    fn cursor_at(&self, keyframe: &ReplayKeyframe) -> ReplayCursor {
+        let mut game = GameState::new_with_config(self.config);
+        game.snake = keyframe.snake.clone();
+        game.direction = keyframe.direction;
+        game.next_direction = keyframe.direction;
+        game.food = Food::normal(keyframe.food);
+        game.score = keyframe.score;
+        game.speed = keyframe.speed;
+        game.game_over = keyframe.game_over;
+        ReplayCursor {
+            tick: keyframe.tick,
+            game,
+            next_food: keyframe.next_food,
+        }
+    }
+
+    // Roughly what the replay holds on the heap, in bytes
+    pub fn heap_bytes(&self) -> usize {
+        use std::mem::size_of;
+        let keyframes: usize = self
+            .keyframes
+            .iter()
+            .map(|keyframe| keyframe.snake.capacity() * size_of::<Position>())
+            .sum();
+        self.mode.capacity()
+            + (self.start.capacity() + self.food.capacity()) * size_of::<Position>()
+            + self.directions.capacity() * size_of::<Direction>()
+            + self.keyframes.capacity() * size_of::<ReplayKeyframe>()
+            + keyframes
+    }
+
+    // Drop the oldest ticks so no more than `max_ticks` are left. The cut is made at a
+    // keyframe, which becomes the new start, so what's left still plays back. Gives the
+    // number of ticks dropped
+    pub fn trim_to(&mut self, max_ticks: usize) -> usize {
+        let oldest = self.tick_count().saturating_sub(max_ticks);
+        if oldest == 0 {
+            return 0;
+        }
+        let Some(cut) = self
+            .keyframes
+            .iter()
+            .position(|keyframe| keyframe.tick >= oldest)
+        else {
+            return 0;
+        };
+        self.keyframes.drain(..cut);
+        let (ticks, foods) = (self.keyframes[0].tick, self.keyframes[0].next_food - 1);
+        self.start = self.keyframes[0].snake.clone();
+        self.start_direction = self.keyframes[0].direction;
+        self.directions.drain(..ticks);
+        self.food.drain(..foods);
+        for keyframe in &mut self.keyframes {
+            keyframe.tick -= ticks;
+            keyframe.next_food -= foods;
+        }
+        ticks
+    }
+
+    // Play the run back, giving the state after every tick (the first is the start)
+    pub fn states(&self) -> Vec<GameState> {
+        let mut cursor = self.cursor_at_start();
+        let mut states = vec![cursor.game.clone()];
+        while self.step(&mut cursor) {
+            states.push(cursor.game.clone());
+        }
+        states
+    }
+
+    pub fn to_bytes(&self, compress: bool) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.seed.to_le_bytes());
+        body.push(self.rng.id());
+        body.extend_from_slice(&self.recorded_at.to_le_bytes());
+        let mode = &self.mode.as_bytes()[..self.mode.len().min(u8::MAX as usize)];
+        body.push(mode.len() as u8);
+        body.extend_from_slice(mode);
+        body.extend_from_slice(&self.score.to_le_bytes());
+        body.extend_from_slice(&self.duration.to_le_bytes());
+        body.push(self.config.grid_width as u8);
+        body.push(self.config.grid_height as u8);
+        body.push(self.config.walls as u8);
+        body.push(direction_bits(self.start_direction));
+        for cells in [&self.start, &self.food] {
+            body.extend_from_slice(&(cells.len() as u32).to_le_bytes());
+            for cell in cells {
+                body.extend_from_slice(&[cell.x as u8, cell.y as u8]);
+            }
+        }
+        // four directions to a byte
+        body.extend_from_slice(&(self.directions.len() as u32).to_le_bytes());
+        for chunk in self.directions.chunks(4) {
+            body.push(
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |byte, (index, direction)| {
+                        byte | direction_bits(*direction) << (index * 2)
+                    }),
+            );
+        }
+        body.extend_from_slice(&(self.keyframes.len() as u32).to_le_bytes());
+        for keyframe in &self.keyframes {
+            body.extend_from_slice(&(keyframe.tick as u32).to_le_bytes());
+            body.extend_from_slice(&(keyframe.next_food as u32).to_le_bytes());
+            body.extend_from_slice(&keyframe.score.to_le_bytes());
+            body.extend_from_slice(&keyframe.speed.millicells_per_second.to_le_bytes());
+            body.push(direction_bits(keyframe.direction));
+            body.push(u8::from(keyframe.game_over));
+            body.extend_from_slice(&[keyframe.food.x as u8, keyframe.food.y as u8]);
+            body.extend_from_slice(&(keyframe.snake.len() as u32).to_le_bytes());
+            for cell in &keyframe.snake {
+                body.extend_from_slice(&[cell.x as u8, cell.y as u8]);
+            }
+        }
+        body.extend_from_slice(&fnv1a(&body).to_le_bytes());
+
+        let (flags, body) = Self::compress(body, compress);
+        let mut bytes = REPLAY_MAGIC.to_vec();
+        bytes.push(REPLAY_VERSION);
+        bytes.push(flags);
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    #[cfg(feature = "zstd")]
+    fn compress(body: Vec<u8>, compress: bool) -> (u8, Vec<u8>) {
+        match compress.then(|| zstd::encode_all(body.as_slice(), 0)) {
+            Some(Ok(compressed)) => (REPLAY_COMPRESSED, compressed),
+            _ => (0, body),
+        }
+    }
+
+    // Without zstd replays are always stored as they are
+    #[cfg(not(feature = "zstd"))]
+    fn compress(body: Vec<u8>, _compress: bool) -> (u8, Vec<u8>) {
+        (0, body)
+    }
+
+    #[cfg(feature = "zstd")]
+    fn decompress(body: &[u8]) -> Result<Vec<u8>, ReplayError> {
+        zstd::decode_all(body).map_err(|e| ReplayError::Compression(e.to_string()))
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    fn decompress(_body: &[u8]) -> Result<Vec<u8>, ReplayError> {
+        Err(ReplayError::Compression(
+            "this build doesn't have the zstd feature".to_string(),
+        ))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayError> {
+        if bytes.len() < 6 || &bytes[..4] != REPLAY_MAGIC {
+            return Err(ReplayError::NotAReplay);
+        }
+        // older versions get upgraded here as the format changes
+        let version = bytes[4];
+        if version > REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        let body = if bytes[5] & REPLAY_COMPRESSED != 0 {
+            Self::decompress(&bytes[6..])?
+        } else {
+            bytes[6..].to_vec()
+        };
+
+        if body.len() < 8 {
+            return Err(ReplayError::Truncated);
+        }
+        let (body, checksum) = body.split_at(body.len() - 8);
+        if fnv1a(body).to_le_bytes() != checksum {
+            return Err(ReplayError::ChecksumMismatch);
+        }
+
+        let mut reader = ReplayReader { bytes: body };
+        let seed = reader.u64()?;
+        // the food is all stored, so a kind this build doesn't know is only a label
+        let rng = if version >= 4 {
+            RngKind::from_id(reader.u8()?).unwrap_or_default()
+        } else {
+            RngKind::Std
+        };
+        let recorded_at = reader.u64()?;
+        let mode_length = reader.u8()? as usize;
+        let mode = String::from_utf8_lossy(reader.take(mode_length)?).into_owned();
+        let score = reader.u32()?;
+        let duration = f64::from_bits(reader.u64()?);
+        let (grid_width, grid_height) = (reader.u8()? as i32, reader.u8()? as i32);
+        let mut config =
+            GameConfig::new(grid_width, grid_height, CELL_SIZE).map_err(ReplayError::BadBoard)?;
+        // before version 5 the walls were always solid
+        if version >= 5 {
+            config.walls = match reader.u8()? {
+                0 => WallMode::Solid,
+                _ => WallMode::Wrap,
+            };
+        }
+        let start_direction = direction_from_bits(reader.u8()?);
+        let start = reader.cells()?;
+        let food = reader.cells()?;
+        let tick_count = reader.u32()? as usize;
+        let packed = reader.take(tick_count.div_ceil(4))?;
+        let directions = (0..tick_count)
+            .map(|tick| direction_from_bits(packed[tick / 4] >> ((tick % 4) * 2)))
+            .collect();
+        // version 1 replays have no keyframes, and just seek from the start
+        let mut keyframes = Vec::new();
+        if version >= 2 {
+            for _ in 0..reader.u32()? {
+                let tick = reader.u32()? as usize;
+                let next_food = reader.u32()? as usize;
+                let score = reader.u32()?;
+                // version 2 kept the seconds between moves
+                let speed = if version >= 3 {
+                    Speed {
+                        millicells_per_second: reader.u32()?,
+                    }
+                } else {
+                    Speed::from_seconds_per_cell(f64::from_bits(reader.u64()?))
+                };
+                let direction = direction_from_bits(reader.u8()?);
+                let game_over = reader.u8()? != 0;
+                let food = reader.cell()?;
+                let snake = reader.cells()?;
+                if snake.is_empty() || tick > tick_count {
+                    return Err(ReplayError::Truncated);
+                }
+                keyframes.push(ReplayKeyframe {
+                    tick,
+                    snake,
+                    direction,
+                    food,
+                    next_food,
+                    score,
+                    speed,
+                    game_over,
+                });
+            }
+        }
+        if start.is_empty() {
+            return Err(ReplayError::Truncated);
+        }
+
+        Ok(Self {
+            seed,
+            rng,
+            recorded_at,
+            mode,
+            config,
+            score,
+            duration,
+            start,
+            start_direction,
+            directions,
+            food,
+            keyframes,
+        })
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes(true))
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::from_bytes(&std::fs::read(path)?)?)
+    }
+
+    // The replay as text for bug reports and forums: a board drawn every `every` ticks, plus
+    // whenever food is eaten or the run ends, each with a line saying what happened
+    pub fn storyboard(&self, every: usize) -> String {
+        let every = every.max(1);
+        let mut cursor = self.cursor_at_start();
+        let mut frames = vec![format!("tick 0 - start\n{}", ascii_board(&cursor.game))];
+        loop {
+            let score_before = cursor.game.score;
+            if !self.step(&mut cursor) {
+                break;
+            }
+            let game = &cursor.game;
+            let event = if game.game_over {
+                Some(format!("crashed with {} points", game.score))
+            } else if game.score > score_before {
+                Some(format!("ate food, score {}", game.score))
+            } else {
+                None
+            };
+            if event.is_some() || cursor.tick.is_multiple_of(every) {
+                frames.push(format!(
+                    "tick {}{}\n{}",
+                    cursor.tick,
+                    event.map_or(String::new(), |event| format!(" - {}", event)),
+                    ascii_board(game)
+                ));
+            }
+            if game.game_over {
+                break;
+            }
+        }
+        frames.join("\n")
+    }
+
+    // Play the replay's moves through the real game loop twice - once plainly, once with
+    // the heap churned and game time fed in uneven frames - and compare the state hash
+    // after every tick. Anything that sneaks in from outside the recorded moves and food
+    // (addresses, hash map order, float time, an unseeded roll) shows up as a divergence
+    pub fn audit_determinism(&self) -> AuditReport {
+        AuditReport::compare(&self.audit_run(false), &self.audit_run(true))
+    }
+
+    fn audit_run(&self, perturbed: bool) -> Vec<u64> {
+        let mut churn: Vec<Vec<u8>> = Vec::new();
+        let mut game = self.cursor_at_start().game;
+        let mut next_food = 1;
+        let mut hashes = Vec::with_capacity(self.tick_count());
+        for (tick, direction) in self.directions.iter().enumerate() {
+            if perturbed {
+                // a different heap layout every tick
+                churn.push(vec![tick as u8; 1 + tick * 37 % 4096]);
+                if churn.len() > 8 {
+                    churn.swap_remove(tick % 8);
+                }
+                // the same tick of game time, but summed from frames that round differently
+                let mut left = game.speed.seconds_per_cell();
+                for share in [0.37, 0.11, 0.29] {
+                    game.elapsed += left * share;
+                    left -= left * share;
+                }
+                game.elapsed += left;
+            } else {
+                game.elapsed += game.speed.seconds_per_cell();
+            }
+
+            game.next_direction = *direction;
+            let score_before = game.score;
+            game.step();
+            if game.score > score_before {
+                if let Some(food) = self.food.get(next_food) {
+                    game.food = Food::normal(*food);
+                }
+                next_food += 1;
+            }
+            hashes.push(game.state_hash());
+            if game.game_over {
+                break;
+            }
+        }
+        hashes
+    }
+
+    // Play the run back and check it could really have happened: it starts from nothing,
+    // every food lands on a free cell of the board, nothing moves after the crash, and it
+    // plays back to the score it claims. Gives that score
+    pub fn verify(&self) -> Result<u32, String> {
+        if self
+            .keyframes
+            .first()
+            .is_some_and(|keyframe| keyframe.tick == 0)
+        {
+            return Err("the start of the run was trimmed off".to_string());
+        }
+        let config = GameConfig::default();
+        let in_one_piece = self
+            .start
+            .windows(2)
+            .all(|pair| (pair[0].x - pair[1].x).abs() + (pair[0].y - pair[1].y).abs() == 1);
+        if self.start.is_empty()
+            || !in_one_piece
+            || !self.start.iter().all(|cell| config.contains(*cell))
+        {
+            return Err("the snake doesn't start in one piece on the board".to_string());
+        }
+        let free =
+            |food: Position, snake: &[Position]| config.contains(food) && !snake.contains(&food);
+        if !self
+            .food
+            .first()
+            .is_some_and(|food| free(*food, &self.start))
+        {
+            return Err("the first food isn't on a free cell".to_string());
+        }
+
+        let mut cursor = self.cursor_at_start();
+        while cursor.tick < self.tick_count() {
+            if cursor.game.game_over {
+                return Err(format!(
+                    "the run carries on after it ended, on tick {}",
+                    cursor.tick + 1
+                ));
+            }
+            let placing = cursor.next_food;
+            self.step(&mut cursor);
+            if cursor.next_food > placing {
+                if let Some(food) = self.food.get(placing) {
+                    if !free(*food, &cursor.game.snake) {
+                        return Err(format!(
+                            "food {} isn't on a free cell, on tick {}",
+                            placing + 1,
+                            cursor.tick
+                        ));
+                    }
+                }
+            }
+        }
+        if cursor.next_food < self.food.len() {
+            return Err("the replay has food that never went down".to_string());
+        }
+        if cursor.game.score != self.score {
+            return Err(format!(
+                "the run plays back to {} points, not {}",
+                cursor.game.score, self.score
+            ));
+        }
+        Ok(self.score)
+    }
+
+    // Where this replay goes in the game's storage
+    pub fn key(&self) -> String {
+        format!("{}{}-{}.snkr", REPLAY_PREFIX, self.recorded_at, self.score)
+    }
+
+    pub fn save_to(&self, storage: &dyn StorageBackend) -> std::io::Result<()> {
+        storage.write_bytes(&self.key(), &self.to_bytes(true))
+    }
+
+    // Every replay that can be read from `storage`, newest first
+    pub fn load_all(storage: &dyn StorageBackend) -> Vec<Self> {
+        let mut replays: Vec<Self> = storage
+            .keys()
+            .into_iter()
+            .filter(|key| key.starts_with(REPLAY_PREFIX))
+            .filter_map(|key| Self::from_bytes(&storage.read_bytes(&key)?).ok())
+            .collect();
+        replays.sort_by_key(|replay| std::cmp::Reverse(replay.recorded_at));
+        replays
+    }
+}
+
+pub const SCORE_SUBMISSION_VERSION: u8 = 1;
+const SCORE_SUBMISSION_MAGIC: &[u8; 4] = b"SNKU";
+
+// A score sent to an online leaderboard. It carries the whole replay and the run's seed,
+// so the server can play it back with `verify` - the same check the game makes - rather
+// than take the number on trust
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreSubmission {
+    pub name: String,
+    pub score: u32,
+    pub seed: u64,
+    pub replay: Replay,
+}
+
+impl ScoreSubmission {
+    // Checked before it's made, so nothing that would be turned away gets sent
+    pub fn new(name: &str, replay: Replay, filter: &NameFilter) -> Result<Self, String> {
+        let submission = Self {
+            name: filter.check(name)?,
+            score: replay.score,
+            seed: replay.seed,
+            replay,
+        };
+        submission.verify(filter)?;
+        Ok(submission)
+    }
+
+    // Everything a leaderboard should check before taking the score
+    pub fn verify(&self, filter: &NameFilter) -> Result<(), String> {
+        if filter.check(&self.name)? != self.name {
+            return Err("the name isn't tidied up".to_string());
+        }
+        if self.seed != self.replay.seed {
+            return Err("the seed isn't the replay's".to_string());
+        }
+        if self.score != self.replay.score {
+            return Err(format!(
+                "the score is {} but the replay's is {}",
+                self.score, self.replay.score
+            ));
+        }
+        self.replay.verify().map(|_| ())
+    }
+
+    // After the magic and version: the name, score and seed, the replay as it's saved to
+    // disk, and a checksum of everything before it
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = SCORE_SUBMISSION_MAGIC.to_vec();
+        bytes.push(SCORE_SUBMISSION_VERSION);
+        bytes.extend_from_slice(&(self.name.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.name.as_bytes());
+        bytes.extend_from_slice(&self.score.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        let replay = self.replay.to_bytes(true);
+        bytes.extend_from_slice(&(replay.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&replay);
+        bytes.extend_from_slice(&fnv1a(&bytes).to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 5 || &bytes[..4] != SCORE_SUBMISSION_MAGIC {
+            return Err("not a score submission".to_string());
+        }
+        if bytes[4] > SCORE_SUBMISSION_VERSION {
+            return Err(format!(
+                "submission version {} is newer than this game supports ({})",
+                bytes[4], SCORE_SUBMISSION_VERSION
+            ));
+        }
+        if bytes.len() < 13 {
+            return Err("submission is cut short".to_string());
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - 8);
+        if fnv1a(body).to_le_bytes() != checksum {
+            return Err("submission is corrupted".to_string());
+        }
+        let cut_short = |_| "submission is cut short".to_string();
+        let mut reader = PackReader { bytes: &body[5..] };
+        let name_length = reader.length().map_err(cut_short)?;
+        let name = String::from_utf8(reader.take(name_length).map_err(cut_short)?.to_vec())
+            .map_err(|_| "the name isn't text".to_string())?;
+        let score = reader.length().map_err(cut_short)? as u32;
+        let seed = u64::from_le_bytes(reader.take(8).map_err(cut_short)?.try_into().unwrap());
+        let replay_length = reader.length().map_err(cut_short)?;
+        let replay = Replay::from_bytes(reader.take(replay_length).map_err(cut_short)?)
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            name,
+            score,
+            seed,
+            replay,
+        })
+    }
+}
+
+// Where the outbox is kept between launches, and a name to put on leaderboard scores
+pub const OUTBOX_FILE: &str = "outbox.txt";
+pub const PLAYER_NAME_FILE: &str = "player_name.txt";
+// Oldest items are dropped past this, so a long time offline can't fill the disk
+pub const OUTBOX_LIMIT: usize = 100;
+// At most one send per interval, and failures back off from the first delay, doubling
+// up to the cap
+pub const OUTBOX_SEND_INTERVAL: f64 = 1.0;
+pub const OUTBOX_RETRY_DELAY: f64 = 2.0;
+pub const OUTBOX_RETRY_CAP: f64 = 300.0;
+const OUTBOX_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+// Something that can carry an outbox item to a server. It runs on the outbox's own
+// thread, so it can take as long as it likes
+pub trait Transport: Send {
+    fn send(&mut self, kind: &str, body: &[u8]) -> Result<(), String>;
+}
+
+// Plain TCP to a leaderboard server: a `kind length` line, then the body, and the
+// server answers `ok` or says what's wrong
+pub struct TcpTransport {
+    pub address: String,
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, kind: &str, body: &[u8]) -> Result<(), String> {
+        use std::io::{BufRead, Write};
+        use std::net::ToSocketAddrs;
+        let address = self
+            .address
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or_else(|| format!("can't find {}", self.address))?;
+        let mut stream = std::net::TcpStream::connect_timeout(&address, OUTBOX_TIMEOUT)
+            .map_err(|e| e.to_string())?;
+        stream
+            .set_read_timeout(Some(OUTBOX_TIMEOUT))
+            .map_err(|e| e.to_string())?;
+        stream
+            .write_all(format!("{} {}\n", kind, body.len()).as_bytes())
+            .and_then(|_| stream.write_all(body))
+            .map_err(|e| e.to_string())?;
+        let mut reply = String::new();
+        std::io::BufReader::new(stream)
+            .read_line(&mut reply)
+            .map_err(|e| e.to_string())?;
+        match reply.trim() {
+            "ok" => Ok(()),
+            other => Err(format!("server said `{}`", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxItem {
+    pub kind: String, // what the server should do with it, e.g. "score"
+    pub body: Vec<u8>,
+    pub attempts: u32, // failed sends so far
+}
+
+// The outbox's thread: items go out on `jobs`, and how each send went comes back
+struct OutboxLink {
+    jobs: std::sync::mpsc::Sender<OutboxItem>,
+    results: std::sync::mpsc::Receiver<Result<(), String>>,
+}
+
+// Everything the game sends online goes through here. Items are saved as soon as they're
+// queued and only dropped once the server has them, so nothing's lost offline or on a
+// crash. Sends happen one at a time on another thread - the game only ever checks in
+// with `advance` - and are rate limited, with failures backing off
+pub struct Outbox {
+    pub pending: std::collections::VecDeque<OutboxItem>,
+    pub sent: u32,
+    pub last_error: Option<String>,
+    pub wait: f64, // seconds until the next send is allowed
+    in_flight: bool,
+    link: Option<OutboxLink>, // no link is offline - items just wait
+    storage: Arc<dyn StorageBackend>,
+}
+
+impl Outbox {
+    // Picks up whatever was still waiting last time
+    pub fn load(storage: Arc<dyn StorageBackend>) -> Self {
+        let pending = storage
+            .read(OUTBOX_FILE)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(' ');
+                let kind = fields.next().filter(|kind| !kind.is_empty())?;
+                let attempts = fields.next()?.parse().ok()?;
+                let body = from_hex(fields.next()?)?;
+                Some(OutboxItem {
+                    kind: kind.to_string(),
+                    body,
+                    attempts,
+                })
+            })
+            .collect();
+        Self {
+            pending,
+            sent: 0,
+            last_error: None,
+            wait: 0.0,
+            in_flight: false,
+            link: None,
+            storage,
+        }
+    }
+
+    // Start sending through `transport` on a thread of its own
+    pub fn connect(&mut self, mut transport: Box<dyn Transport>) {
+        let (jobs, job_queue) = std::sync::mpsc::channel::<OutboxItem>();
+        let (results, result_queue) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for item in job_queue {
+                if results
+                    .send(transport.send(&item.kind, &item.body))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        self.link = Some(OutboxLink {
+            jobs,
+            results: result_queue,
+        });
+    }
+
+    // Send to the server set at launch, if there is one
+    pub fn connect_to_server(&mut self) {
+        if let Some(address) = SERVER.get() {
+            self.connect(Box::new(TcpTransport {
+                address: address.clone(),
+            }));
+        }
+    }
+
+    pub fn push(&mut self, kind: &str, body: Vec<u8>) {
+        self.pending.push_back(OutboxItem {
+            kind: kind.to_string(),
+            body,
+            attempts: 0,
+        });
+        while self.pending.len() > OUTBOX_LIMIT {
+            self.pending.pop_front();
+        }
+        self.save();
+    }
+
+    // Collect how the last send went and start the next one if it's due. Never waits
+    pub fn advance(&mut self, delta: f64) {
+        self.wait = (self.wait - delta).max(0.0);
+        let Some(link) = &self.link else {
+            return;
+        };
+        if self.in_flight {
+            let Ok(result) = link.results.try_recv() else {
+                return; // still going
+            };
+            self.in_flight = false;
+            match result {
+                Ok(()) => {
+                    self.pending.pop_front();
+                    self.sent += 1;
+                    self.last_error = None;
+                    self.wait = OUTBOX_SEND_INTERVAL;
+                }
+                Err(e) => {
+                    if let Some(item) = self.pending.front_mut() {
+                        item.attempts += 1;
+                        self.wait = retry_delay(item.attempts);
+                    }
+                    self.last_error = Some(e);
+                }
+            }
+            self.save();
+        }
+        if self.wait > 0.0 {
+            return;
+        }
+        if let Some(item) = self.pending.front() {
+            self.in_flight = link.jobs.send(item.clone()).is_ok();
+        }
+    }
+
+    // One line for the debug overlay
+    pub fn status_line(&self) -> String {
+        let mut line = format!("outbox: {} queued, {} sent", self.pending.len(), self.sent);
+        if self.link.is_none() {
+            line += ", offline";
+        } else if self.in_flight {
+            line += ", sending";
+        } else if !self.pending.is_empty() && self.wait > 0.0 {
+            line += &format!(", next try in {:.0}s", self.wait.ceil());
+        }
+        if let Some(e) = &self.last_error {
+            line += &format!(" (last failed: {})", e);
+        }
+        line
+    }
+
+    fn save(&self) {
+        let text: String = self
+            .pending
+            .iter()
+            .map(|item| format!("{} {} {}\n", item.kind, item.attempts, to_hex(&item.body)))
+            .collect();
+        if let Err(e) = self.storage.write(OUTBOX_FILE, &text) {
+            eprintln!("Failed to save the outbox: {}", e);
+        }
+    }
+}
+
+// How long to hold off after a send has failed `attempts` times in a row
+pub fn retry_delay(attempts: u32) -> f64 {
+    let doublings = attempts.saturating_sub(1).min(16) as i32;
+    (OUTBOX_RETRY_DELAY * 2f64.powi(doublings)).min(OUTBOX_RETRY_CAP)
+}
+
+// Where online features send to - set once at launch, from SNAKE_SERVER or --server.
+// Without one the outbox stays offline
+static SERVER: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+// Returns false if the server was already set
+pub fn set_server(address: String) -> bool {
+    SERVER.set(address).is_ok()
+}
+
+// Biggest cell size that fits the whole board on a display, at least one pixel
+pub fn fit_cell_size(config: &GameConfig, display: (usize, usize)) -> usize {
+    let (columns, rows) = config.grid();
+    (display.0 / columns).min(display.1 / rows).max(1)
+}
+
+// The color a display pixel gets from a centered frame, black outside it
+fn centered_pixel(
+    frame: &[u8],
+    (width, height): (usize, usize),
+    display: (usize, usize),
+    (x, y): (usize, usize),
+) -> [u8; 3] {
+    let (left, top) = (
+        display.0.saturating_sub(width) / 2,
+        display.1.saturating_sub(height) / 2,
+    );
+    if x < left || y < top || x - left >= width || y - top >= height {
+        return [0, 0, 0];
+    }
+    let start = ((y - top) * width + x - left) * 4;
+    [frame[start], frame[start + 1], frame[start + 2]]
+}
+
+// An LED matrix fed over a serial line (or any writer) with the Adalight protocol,
+// which most Arduino/ESP LED controller sketches speak. Strips wired back and forth
+// get every other row reversed
+pub struct AdalightMatrix<W: std::io::Write> {
+    pub output: W,
+    pub width: usize,
+    pub height: usize,
+    pub serpentine: bool,
+    pub brightness: f32, // 0.0-1.0, full white on a big matrix draws a lot of power
+}
+
+impl<W: std::io::Write> AdalightMatrix<W> {
+    pub fn new(output: W, width: usize, height: usize) -> Self {
+        Self {
+            output,
+            width,
+            height,
+            serpentine: false,
+            brightness: 0.5,
+        }
+    }
+}
+
+impl<W: std::io::Write> DisplayBackend for AdalightMatrix<W> {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn show(&mut self, frame: &[u8], width: usize, height: usize) -> std::io::Result<()> {
+        // "Ada", then the LED count minus one (big endian) and a checksum of it
+        let last = (self.width * self.height).saturating_sub(1) as u16;
+        let [high, low] = last.to_be_bytes();
+        let mut packet = vec![b'A', b'd', b'a', high, low, high ^ low ^ 0x55];
+        for y in 0..self.height {
+            for column in 0..self.width {
+                let x = if self.serpentine && y % 2 == 1 {
+                    self.width - 1 - column
+                } else {
+                    column
+                };
+                let pixel = centered_pixel(frame, (width, height), self.size(), (x, y));
+                packet.extend(pixel.map(|c| (c as f32 * self.brightness) as u8));
+            }
+        }
+        self.output.write_all(&packet)?;
+        self.output.flush()
+    }
+}
+
+// A Linux framebuffer device like /dev/fb0, for small TFT screens and HDMI panels
+// without a desktop. Handles the usual 32 (BGRA) and 16 (RGB565) bits per pixel
+pub struct Framebuffer {
+    pub device: std::fs::File,
+    pub width: usize,
+    pub height: usize,
+    pub bits_per_pixel: usize,
+}
+
+impl Framebuffer {
+    // Open e.g. /dev/fb0, reading its size from /sys/class/graphics/fb0
+    pub fn open(path: &str) -> std::io::Result<Self> {
+        let name = std::path::Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let sys =
+            |file: &str| std::fs::read_to_string(format!("/sys/class/graphics/{}/{}", name, file));
+        let invalid = |what: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, what);
+        let size = sys("virtual_size")?;
+        let (width, height) = size
+            .trim()
+            .split_once(',')
+            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+            .ok_or_else(|| invalid("unreadable framebuffer size"))?;
+        let bits_per_pixel = sys("bits_per_pixel")?
+            .trim()
+            .parse()
+            .map_err(|_| invalid("unreadable framebuffer depth"))?;
+        if bits_per_pixel != 32 && bits_per_pixel != 16 {
+            return Err(invalid("only 16 and 32 bit framebuffers are supported"));
+        }
+        let device = std::fs::OpenOptions::new().write(true).open(path)?;
+        Ok(Self {
+            device,
+            width,
+            height,
+            bits_per_pixel,
+        })
+    }
+
+    // One display pixel in the framebuffer's own format
+    pub fn encode(&self, [r, g, b]: [u8; 3]) -> Vec<u8> {
+        if self.bits_per_pixel == 16 {
+            let rgb565 = ((r as u16 >> 3) << 11) | ((g as u16 >> 2) << 5) | (b as u16 >> 3);
+            rgb565.to_le_bytes().to_vec()
+        } else {
+            vec![b, g, r, 255]
+        }
+    }
+}
+
+impl DisplayBackend for Framebuffer {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn show(&mut self, frame: &[u8], width: usize, height: usize) -> std::io::Result<()> {
+        use std::io::{Seek, Write};
+
+        let mut bytes = Vec::with_capacity(self.width * self.height * self.bits_per_pixel / 8);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let pixel = centered_pixel(frame, (width, height), self.size(), (x, y));
+                bytes.extend(self.encode(pixel));
+            }
+        }
+        self.device.seek(std::io::SeekFrom::Start(0))?;
+        self.device.write_all(&bytes)
+    }
+
+    fn show_cells(
+        &mut self,
+        frame: &[u8],
+        (width, height): (usize, usize),
+        cell_size: usize,
+        cells: &[Position],
+    ) -> std::io::Result<()> {
+        use std::io::{Seek, Write};
+
+        // every cell of the board is the whole frame anyway
+        if cells.len() == (width / cell_size) * (height / cell_size) {
+            return self.show(frame, width, height);
+        }
+        let (left, top) = (
+            self.width.saturating_sub(width) / 2,
+            self.height.saturating_sub(height) / 2,
+        );
+        let bytes_per_pixel = self.bits_per_pixel / 8;
+        for cell in cells {
+            let x = left + cell.x as usize * cell_size;
+            let columns = cell_size.min(self.width.saturating_sub(x));
+            // each pixel row of the cell is one short write into the device
+            for row in 0..cell_size {
+                let y = top + cell.y as usize * cell_size + row;
+                if y >= self.height || columns == 0 {
+                    break;
+                }
+                let mut bytes = Vec::with_capacity(columns * bytes_per_pixel);
+                for column in 0..columns {
+                    let pixel =
+                        centered_pixel(frame, (width, height), self.size(), (x + column, y));
+                    bytes.extend(self.encode(pixel));
+                }
+                let offset = (y * self.width + x) * bytes_per_pixel;
+                self.device.seek(std::io::SeekFrom::Start(offset as u64))?;
+                self.device.write_all(&bytes)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// What a key on a keypad or GPIO button does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PanelInput {
+    Steer(Direction),
+    Restart,
+    Quit,
+}
+
+// Size of a Linux `struct input_event` on 64-bit: a timeval, type, code and value
+pub const INPUT_EVENT_SIZE: usize = 24;
+
+// Read a key press from a Linux input device (/dev/input/eventN). USB keypads show up
+// there, and so do GPIO buttons set up with the gpio-keys overlay. Arrows, WASD and
+// the number pad steer, R and the keypad 5 restart, Esc quits
+pub fn parse_input_event(event: &[u8; INPUT_EVENT_SIZE]) -> Option<PanelInput> {
+    const EV_KEY: u16 = 1;
+    let kind = u16::from_ne_bytes([event[16], event[17]]);
+    let code = u16::from_ne_bytes([event[18], event[19]]);
+    let value = i32::from_ne_bytes([event[20], event[21], event[22], event[23]]);
+    if kind != EV_KEY || value != 1 {
+        return None; // not a key, or a release or autorepeat
+    }
+    Some(match code {
+        103 | 17 | 72 => PanelInput::Steer(Direction::Up), // Up, W, keypad 8
+        108 | 31 | 80 => PanelInput::Steer(Direction::Down), // Down, S, keypad 2
+        105 | 30 | 75 => PanelInput::Steer(Direction::Left), // Left, A, keypad 4
+        106 | 32 | 77 => PanelInput::Steer(Direction::Right), // Right, D, keypad 6
+        19 | 76 => PanelInput::Restart,                    // R, keypad 5
+        1 => PanelInput::Quit,                             // Esc
+        _ => return None,
+    })
+}
+
+// Finished runs are saved under keys starting with this, for the replay browser
+pub const REPLAY_PREFIX: &str = "replay_";
+
+// "YYYY-MM-DD HH:MM" (UTC) for a unix timestamp
+pub fn format_date(unix_seconds: u64) -> String {
+    // days since 1970 to a civil date - see Howard Hinnant's chrono-compatible algorithms
+    let days = (unix_seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let seconds_today = unix_seconds % 86400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds_today / 3600,
+        seconds_today % 3600 / 60
+    )
+}
+
+pub const MIXER_FILE: &str = "mixer.txt";
+// How long the music dips for when the death sound plays, and how far (as a share of
+// its volume) - it stays down for the first half, then fades back up
+pub const DUCK_SECONDS: f64 = 1.5;
+pub const DUCK_LEVEL: f32 = 0.25;
+// The music is layered stems - a bass line, drums, then a lead - that come in as the
+// snake speeds up. Intensity eases towards where the game is at this rate per second
+pub const MUSIC_LAYERS: usize = 3;
+pub const MUSIC_INTENSITY_RATE: f32 = 0.5;
+// How much quicker the music plays at full intensity
+pub const MUSIC_TEMPO_LIFT: f32 = 0.1;
+
+// How intense the music should be for a game going at `speed`, from 0.0 to 1.0. It
+// drops right off on the game over screen
+pub fn music_intensity(speed: Speed, game_over: bool) -> f32 {
+    if game_over {
+        return 0.0;
+    }
+    let start = Speed::START.millicells_per_second as f32;
+    let max = Speed::MAX.millicells_per_second as f32;
+    ((speed.millicells_per_second as f32 - start) / (max - start)).clamp(0.0, 1.0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Music,
+    Sfx,
+}
+
+// How loud music and sound effects play, each from 0.0 to 1.0, and a mute over both.
+// Only the volumes and mute are saved; ducking is just for the moment
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mixer {
+    pub music: f32,
+    pub sfx: f32,
+    pub muted: bool,
+    pub duck_left: f64,        // seconds of ducking still to go
+    pub intensity: f32,        // how much of the music is playing, 0.0 to 1.0
+    pub target_intensity: f32, // and where it's heading
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self {
+            music: 0.6,
+            sfx: 0.8,
+            muted: false,
+            duck_left: 0.0,
+            intensity: 0.0,
+            target_intensity: 0.0,
+        }
+    }
+}
+
+impl Mixer {
+    // Same `key = value` lines as the other settings files: `music`, `sfx` and `muted`
+    pub fn parse(text: &str) -> Result<Mixer, String> {
+        let mut mixer = Mixer::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = match line.find(" # ") {
+                Some(comment) => &line[..comment],
+                None if line.trim_start().starts_with('#') => "",
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fail = |message: String| format!("line {}: {}", number + 1, message);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| fail(format!("expected `key = value`, got `{}`", line)))?;
+            let (key, value) = (key.trim(), value.trim());
+            let volume = || {
+                value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|volume| (0.0..=1.0).contains(volume))
+                    .ok_or_else(|| fail(format!("{} should be from 0 to 1, got `{}`", key, value)))
+            };
+            match key {
+                "music" => mixer.music = volume()?,
+                "sfx" => mixer.sfx = volume()?,
+                "muted" => {
+                    mixer.muted = value.parse().map_err(|_| {
+                        fail(format!("muted should be true or false, got `{}`", value))
+                    })?
+                }
+                _ => return Err(fail(format!("unknown key `{}`", key))),
+            }
+        }
+        Ok(mixer)
+    }
+
+    pub fn to_text(&self) -> String {
+        format!(
+            "music = {}\nsfx = {}\nmuted = {}\n",
+            self.music, self.sfx, self.muted
+        )
+    }
+
+    // The saved mix, or the default if there isn't one or it doesn't parse
+    pub fn load(storage: &dyn StorageBackend) -> Mixer {
+        let Some(text) = storage.read(MIXER_FILE) else {
+            return Mixer::default();
+        };
+        Mixer::parse(&text).unwrap_or_else(|e| {
+            eprintln!("Ignoring {}: {}", MIXER_FILE, e);
+            Mixer::default()
+        })
+    }
+
+    pub fn save(&self, storage: &dyn StorageBackend) {
+        if let Err(e) = storage.write(MIXER_FILE, &self.to_text()) {
+            eprintln!("Failed to save the volume settings: {}", e);
+        }
+    }
+
+    pub fn volume(&self, channel: Channel) -> f32 {
+        match channel {
+            Channel::Music => self.music,
+            Channel::Sfx => self.sfx,
+        }
+    }
+
+    pub fn set_volume(&mut self, channel: Channel, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        match channel {
+            Channel::Music => self.music = volume,
+            Channel::Sfx => self.sfx = volume,
+        }
+    }
+
+    // What a channel should actually play at right now, after mute and ducking
+    pub fn gain(&self, channel: Channel) -> f32 {
+        if self.muted {
+            return 0.0;
+        }
+        match channel {
+            Channel::Music => self.music * self.duck_factor(),
+            Channel::Sfx => self.sfx,
+        }
+    }
+
+    fn duck_factor(&self) -> f32 {
+        let recovery = DUCK_SECONDS / 2.0;
+        let down = (self.duck_left / recovery).clamp(0.0, 1.0) as f32;
+        1.0 - (1.0 - DUCK_LEVEL) * down
+    }
+
+    // Dip the music under a sound that needs to be heard, like dying
+    pub fn duck(&mut self) {
+        self.duck_left = DUCK_SECONDS;
+    }
+
+    pub fn advance(&mut self, delta: f64) {
+        self.duck_left = (self.duck_left - delta).max(0.0);
+        let step = MUSIC_INTENSITY_RATE * delta as f32;
+        self.intensity += (self.target_intensity - self.intensity).clamp(-step, step);
+    }
+
+    pub fn set_intensity(&mut self, target: f32) {
+        self.target_intensity = target.clamp(0.0, 1.0);
+    }
+
+    // What one of the music's layers plays at. The first is always in; each one after
+    // fades in over its share of the intensity
+    pub fn layer_gain(&self, layer: usize) -> f32 {
+        if layer >= MUSIC_LAYERS {
+            return 0.0;
+        }
+        let share = 1.0 / (MUSIC_LAYERS - 1) as f32;
+        let fade_in = if layer == 0 {
+            1.0
+        } else {
+            ((self.intensity - share * (layer - 1) as f32) / share).clamp(0.0, 1.0)
+        };
+        self.gain(Channel::Music) * fade_in
+    }
+
+    // How fast the music plays, 1.0 being its normal tempo
+    pub fn playback_rate(&self) -> f32 {
+        1.0 + MUSIC_TEMPO_LIFT * self.intensity
+    }
+
+    // Sliders for each channel and a mute switch, for a settings screen
+    pub fn panel(&self) -> Panel {
+        let mut panel = Panel::default();
+        panel.push(Widget::title("Sound"));
+        panel.push(Widget::slider("Music", self.music, 0.0, 1.0, 0.1));
+        panel.push(Widget::slider("Sound effects", self.sfx, 0.0, 1.0, 0.1));
+        panel.push(Widget::toggle("Mute", self.muted));
+        panel
+    }
+
+    // Take the volumes and mute back from a panel made by `panel`
+    pub fn apply(&mut self, panel: &Panel) {
+        for widget in &panel.widgets {
+            match widget {
+                Widget::Slider { label, value, .. } if label == "Music" => {
+                    self.set_volume(Channel::Music, *value)
+                }
+                Widget::Slider { value, .. } => self.set_volume(Channel::Sfx, *value),
+                Widget::Toggle { on, .. } => self.muted = *on,
+                _ => {}
+            }
+        }
+    }
+}
+
+// Sound files that replace the built-in beeps: eat, turn and die, as .ogg, .wav or .flac
+pub const SOUNDS_DIR: &str = "sounds";
+pub const SOUND_EXTENSIONS: [&str; 3] = ["ogg", "wav", "flac"];
+
+// Things in the game that make a noise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundEvent {
+    Ate,
+    Turned,
+    Died,
+}
+
+impl SoundEvent {
+    pub const ALL: [SoundEvent; 3] = [SoundEvent::Ate, SoundEvent::Turned, SoundEvent::Died];
+
+    // The file name, without an extension, that replaces this sound
+    pub fn name(&self) -> &'static str {
+        match self {
+            SoundEvent::Ate => "eat",
+            SoundEvent::Turned => "turn",
+            SoundEvent::Died => "die",
+        }
+    }
+
+    // What plays when there's no file for it: a rising blip for food, a tick for a
+    // turn, and a falling tone into a crunch for a crash
+    pub fn beeps(&self) -> Vec<synth::Beep> {
+        use synth::Beep;
+        match self {
+            SoundEvent::Ate => vec![
+                Beep::square(660.0, 880.0, 0.05),
+                Beep::square(990.0, 1320.0, 0.08),
+            ],
+            SoundEvent::Turned => vec![Beep::square(220.0, 180.0, 0.02).volume(0.15)],
+            SoundEvent::Died => vec![
+                Beep::square(440.0, 90.0, 0.35),
+                Beep::noise(2000.0, 0.3).volume(0.3),
+            ],
+        }
+    }
+}
+
+// A sound for every event, ready to play: the file from the sounds folder if there's one
+// that can be played, otherwise beeps synthesized at startup, so the game is never silent
+pub struct SoundBank {
+    sounds: Vec<(SoundEvent, ggez::audio::SoundData, bool)>, // and whether it's synthesized
+}
+
+impl SoundBank {
+    pub fn load(dir: &str) -> Self {
+        let sounds = SoundEvent::ALL
+            .into_iter()
+            .map(|event| match Self::load_file(dir, event) {
+                Some(data) => (event, data, false),
+                None => {
+                    let samples = synth::render(&event.beeps(), synth::SAMPLE_RATE);
+                    let wav = synth::wav(&samples, synth::SAMPLE_RATE);
+                    (event, ggez::audio::SoundData::from_bytes(&wav), true)
+                }
+            })
+            .collect();
+        Self { sounds }
+    }
+
+    fn load_file(dir: &str, event: SoundEvent) -> Option<ggez::audio::SoundData> {
+        let path = SOUND_EXTENSIONS
+            .iter()
+            .map(|extension| format!("{}/{}.{}", dir, event.name(), extension))
+            .find(|path| std::path::Path::new(path).exists())?;
+        let data = ggez::audio::SoundData::from_bytes(&std::fs::read(&path).ok()?);
+        if !data.can_play() {
+            eprintln!("Can't play {}, using the built-in sound instead", path);
+            return None;
+        }
+        Some(data)
+    }
+
+    pub fn is_synthesized(&self, event: SoundEvent) -> bool {
+        self.sounds
+            .iter()
+            .any(|(sound, _, synthesized)| *sound == event && *synthesized)
+    }
+
+    pub fn play(&self, ctx: &Context, event: SoundEvent, volume: f32) -> GameResult {
+        use ggez::audio::SoundSource;
+        let Some((_, data, _)) = self.sounds.iter().find(|(sound, ..)| *sound == event) else {
+            return Ok(());
+        };
+        if volume <= 0.0 {
+            return Ok(());
+        }
+        let mut source = ggez::audio::Source::from_data(ctx, data.clone())?;
+        source.set_volume(volume);
+        source.play_detached(ctx)
+    }
+}
+
+// Announcer voices: a voice pack is a folder of clips named after what they say, either
+// on its own under VOICES_DIR or in a content pack's VOICE_DIR. The chosen one is saved
+pub const VOICES_DIR: &str = "voices";
+pub const VOICE_DIR: &str = "voice";
+pub const VOICE_FILE: &str = "voice.txt";
+// The countdown starts being called out from here
+pub const COUNTDOWN_FROM: u32 = 3;
+
+// What the announcer says
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceLine {
+    NewHighScore,
+    LevelUp, // the snake's reached another whole cell per second
+    Countdown(u32),
+}
+
+impl VoiceLine {
+    // The clip's file name, without an extension: new_high_score, level_up, 3, 2, 1
+    pub fn file_stem(&self) -> String {
+        match self {
+            VoiceLine::NewHighScore => "new_high_score".to_string(),
+            VoiceLine::LevelUp => "level_up".to_string(),
+            VoiceLine::Countdown(number) => number.to_string(),
+        }
+    }
+}
+
+// A voice's clips, by file stem. Lines it has no clip for go unsaid
+pub struct VoicePack {
+    pub name: String,
+    clips: Vec<(String, ggez::audio::SoundData)>,
+}
+
+impl VoicePack {
+    // The clips in `dir` that can be played, or None if there aren't any
+    pub fn load(name: &str, dir: &std::path::Path) -> Option<VoicePack> {
+        let mut clips = Vec::new();
+        for entry in std::fs::read_dir(dir).ok()?.flatten() {
+            let path = entry.path();
+            let extension = path.extension().and_then(|extension| extension.to_str());
+            if !extension.is_some_and(|extension| SOUND_EXTENSIONS.contains(&extension)) {
+                continue;
+            }
+            let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let Ok(bytes) = std::fs::read(&path) else {
+                continue;
+            };
+            let data = ggez::audio::SoundData::from_bytes(&bytes);
+            if data.can_play() {
+                clips.push((stem.to_string(), data));
+            } else {
+                eprintln!("Can't play {}, leaving it out", path.display());
+            }
+        }
+        clips.sort_by(|a, b| a.0.cmp(&b.0));
+        (!clips.is_empty()).then(|| VoicePack {
+            name: name.to_string(),
+            clips,
+        })
+    }
+
+    pub fn clip(&self, line: VoiceLine) -> Option<&ggez::audio::SoundData> {
+        let stem = line.file_stem();
+        self.clips
+            .iter()
+            .find(|(clip, _)| *clip == stem)
+            .map(|(_, data)| data)
+    }
+
+    pub fn play(&self, ctx: &Context, line: VoiceLine, volume: f32) -> GameResult {
+        use ggez::audio::SoundSource;
+        let Some(data) = self.clip(line).filter(|_| volume > 0.0) else {
+            return Ok(());
+        };
+        let mut source = ggez::audio::Source::from_data(ctx, data.clone())?;
+        source.set_volume(volume);
+        source.play_detached(ctx)
+    }
+}
+
+// Every voice there is: the folders in `dir`, then the packs that bring one
+pub fn discover_voices(dir: &str, packs: &[Pack]) -> Vec<VoicePack> {
+    let mut folders: Vec<std::path::PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    folders.sort();
+    let mut voices: Vec<VoicePack> = folders
+        .iter()
+        .filter_map(|folder| {
+            let name = folder.file_name()?.to_str()?;
+            VoicePack::load(name, folder)
+        })
+        .collect();
+    for pack in packs {
+        if voices.iter().any(|voice| voice.name == pack.manifest.name) {
+            continue;
+        }
+        voices.extend(VoicePack::load(
+            &pack.manifest.name,
+            &pack.dir.join(VOICE_DIR),
+        ));
+    }
+    voices
+}
+
+// The voice saved in VOICE_FILE, if it's still around. No file means no announcer
+pub fn chosen_voice(storage: &dyn StorageBackend, voices: Vec<VoicePack>) -> Option<VoicePack> {
+    let name = storage.read(VOICE_FILE)?;
+    voices.into_iter().find(|voice| voice.name == name.trim())
+}
+
+// Off, then each voice, for a settings screen - choosing one saves it
+pub fn voice_panel(voices: &[VoicePack], chosen: Option<&str>) -> Panel {
+    let mut rows = vec!["Off".to_string()];
+    rows.extend(voices.iter().map(|voice| voice.name.clone()));
+    let selected = chosen
+        .and_then(|name| rows.iter().position(|row| row == name))
+        .unwrap_or(0);
+    let mut panel = Panel::default();
+    panel.push(Widget::title("Announcer"));
+    panel.push(Widget::list(rows, selected));
+    panel
+}
+
+pub fn save_voice_choice(storage: &dyn StorageBackend, voice: Option<&str>) {
+    let saved = match voice {
+        Some(name) => storage.write(VOICE_FILE, name),
+        None => storage.remove(VOICE_FILE),
+    };
+    if let Err(e) = saved {
+        eprintln!("Failed to save the announcer choice: {}", e);
+    }
+}
+
+pub const FEEDBACK_FILE: &str = "feedback.txt";
+
+// What the game sets off for each event, one output a line. A pack's feedback.txt
+// replaces the lines for any event it mentions and leaves the rest alone
+pub const DEFAULT_FEEDBACK: &str = "\
+ate = sound eat
+ate = rumble 60 0.4
+turned = sound turn
+died = sound die
+died = rumble 350 1.0
+new_high_score = voice
+level_up = voice
+countdown = voice
+";
+
+// Something that happened on a tick that the player might want to see, hear or feel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedbackEvent {
+    Ate,
+    Turned,
+    Died,
+    NewHighScore,
+    LevelUp,
+    Countdown(u32),
+}
+
+impl FeedbackEvent {
+    pub const NAMES: [&'static str; 6] = [
+        "ate",
+        "turned",
+        "died",
+        "new_high_score",
+        "level_up",
+        "countdown",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FeedbackEvent::Ate => "ate",
+            FeedbackEvent::Turned => "turned",
+            FeedbackEvent::Died => "died",
+            FeedbackEvent::NewHighScore => "new_high_score",
+            FeedbackEvent::LevelUp => "level_up",
+            FeedbackEvent::Countdown(_) => "countdown",
+        }
+    }
+
+    // What the announcer says for it, if anything
+    pub fn voice_line(&self) -> Option<VoiceLine> {
+        match self {
+            FeedbackEvent::NewHighScore => Some(VoiceLine::NewHighScore),
+            FeedbackEvent::LevelUp => Some(VoiceLine::LevelUp),
+            FeedbackEvent::Countdown(number) => Some(VoiceLine::Countdown(*number)),
+            _ => None,
+        }
+    }
+}
+
+// The bits of a game that feedback compares a tick against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickSnapshot {
+    pub score: u32,
+    pub game_over: bool,
+    pub direction: Direction,
+    pub speed: Speed,
+    pub grace_left: GracePeriod,
+}
+
+impl TickSnapshot {
+    pub fn of(game: &GameState) -> TickSnapshot {
+        TickSnapshot {
+            score: game.score,
+            game_over: game.game_over,
+            direction: game.direction,
+            speed: game.speed,
+            grace_left: game.grace_left,
+        }
+    }
+
+    // What happened between this and `game`. Crashing drowns out eating, and eating
+    // drowns out turning. The new high score is said once a run, and not on the first
+    pub fn events(&self, game: &GameState) -> Vec<FeedbackEvent> {
+        let mut events = Vec::new();
+        if game.game_over && !self.game_over {
+            events.push(FeedbackEvent::Died);
+        } else if game.score > self.score {
+            events.push(FeedbackEvent::Ate);
+        } else if game.direction != self.direction && !game.game_over {
+            events.push(FeedbackEvent::Turned);
+        }
+        if let (GracePeriod::Ticks(before), GracePeriod::Ticks(left)) =
+            (self.grace_left, game.grace_left)
+        {
+            if left < before && left <= COUNTDOWN_FROM {
+                events.push(FeedbackEvent::Countdown(left));
+            }
+        }
+        let level = |speed: Speed| speed.millicells_per_second / 1000;
+        if level(game.speed) > level(self.speed) {
+            events.push(FeedbackEvent::LevelUp);
+        }
+        if game.high_score > 0 && self.score <= game.high_score && game.score > game.high_score {
+            events.push(FeedbackEvent::NewHighScore);
+        }
+        events
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeedbackOutput {
+    Sound(SoundEvent),
+    Voice, // the announcer's line for the event
+    Rumble { duration_ms: u32, strength: f32 },
+    Toast(String),
+}
+
+// Which outputs each event sets off, so a new event is one line here rather than a
+// change to every system that reacts to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedbackRouter {
+    routes: Vec<(&'static str, Vec<FeedbackOutput>)>,
+}
+
+impl Default for FeedbackRouter {
+    fn default() -> Self {
+        FeedbackRouter::parse(DEFAULT_FEEDBACK).expect("the default feedback parses")
+    }
+}
+
+impl FeedbackRouter {
+    // `event = output` lines, where an output is `sound eat`, `voice`, `rumble 60 0.4`
+    // (milliseconds and strength), `toast Some message` or `nothing`. An event can
+    // have as many lines as it likes. Only the events mentioned are routed
+    pub fn parse(text: &str) -> Result<FeedbackRouter, String> {
+        let mut routes: Vec<(&'static str, Vec<FeedbackOutput>)> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = match line.find(" # ") {
+                Some(comment) => &line[..comment],
+                None if line.trim_start().starts_with('#') => "",
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fail = |message: String| format!("line {}: {}", number + 1, message);
+            let (event, output) = line
+                .split_once('=')
+                .ok_or_else(|| fail(format!("expected `event = output`, got `{}`", line)))?;
+            let event = event.trim();
+            let event = FeedbackEvent::NAMES
+                .into_iter()
+                .find(|name| *name == event)
+                .ok_or_else(|| fail(format!("unknown event `{}`", event)))?;
+            let output = output.trim();
+            let (kind, rest) = output.split_once(' ').unwrap_or((output, ""));
+            let rest = rest.trim();
+            let output = match kind {
+                "nothing" if rest.is_empty() => None,
+                "sound" => {
+                    let sound = SoundEvent::ALL
+                        .into_iter()
+                        .find(|sound| sound.name() == rest)
+                        .ok_or_else(|| fail(format!("unknown sound `{}`", rest)))?;
+                    Some(FeedbackOutput::Sound(sound))
+                }
+                "voice" if rest.is_empty() => {
+                    if !["new_high_score", "level_up", "countdown"].contains(&event) {
+                        return Err(fail(format!("the announcer has nothing for `{}`", event)));
+                    }
+                    Some(FeedbackOutput::Voice)
+                }
+                "rumble" => {
+                    let (duration, strength) = rest
+                        .split_once(' ')
+                        .and_then(|(duration, strength)| {
+                            Some((duration.parse().ok()?, strength.trim().parse().ok()?))
+                        })
+                        .filter(|(_, strength): &(u32, f32)| (0.0..=1.0).contains(strength))
+                        .ok_or_else(|| {
+                            fail(format!(
+                                "expected `rumble <milliseconds> <0.0 to 1.0>`, got `{}`",
+                                output
+                            ))
+                        })?;
+                    Some(FeedbackOutput::Rumble {
+                        duration_ms: duration,
+                        strength,
+                    })
+                }
+                "toast" if !rest.is_empty() => Some(FeedbackOutput::Toast(rest.to_string())),
+                _ => return Err(fail(format!("unknown output `{}`", output))),
+            };
+            let route = match routes.iter().position(|(seen, _)| *seen == event) {
+                Some(index) => &mut routes[index].1,
+                None => {
+                    routes.push((event, Vec::new()));
+                    &mut routes.last_mut().unwrap().1
+                }
+            };
+            route.extend(output);
+        }
+        Ok(FeedbackRouter { routes })
+    }
+
+    // Replace the routes for every event `other` mentions
+    pub fn override_with(&mut self, other: &FeedbackRouter) {
+        for (event, outputs) in &other.routes {
+            match self.routes.iter_mut().find(|(seen, _)| seen == event) {
+                Some(route) => route.1 = outputs.clone(),
+                None => self.routes.push((event, outputs.clone())),
+            }
+        }
+    }
+
+    // The defaults, overridden by each pack in turn
+    pub fn for_packs<'a>(packs: impl IntoIterator<Item = &'a Pack>) -> FeedbackRouter {
+        let mut router = FeedbackRouter::default();
+        for feedback in packs.into_iter().filter_map(|pack| pack.feedback.as_ref()) {
+            router.override_with(feedback);
+        }
+        router
+    }
+
+    pub fn outputs(&self, event: FeedbackEvent) -> &[FeedbackOutput] {
+        self.routes
+            .iter()
+            .find(|(name, _)| *name == event.name())
+            .map_or(&[], |(_, outputs)| outputs.as_slice())
+    }
+}
+
+// How strongly haptics play unless the player turns them down
+pub const DEFAULT_HAPTIC_INTENSITY: f32 = 0.6;
+
+// Things in the game worth feeling through the controller or phone
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HapticEvent {
+    Ate,
+    Died,
+}
+
+impl HapticEvent {
+    // How long to buzz in milliseconds, and how hard at full intensity
+    pub fn pulse(&self) -> (u32, f32) {
+        match self {
+            HapticEvent::Ate => (60, 0.4),
+            HapticEvent::Died => (350, 1.0),
+        }
+    }
+}
+
+// Something that can buzz - gamepad rumble motors, a phone's vibration motor
+pub trait Haptics {
+    fn pulse(&mut self, duration_ms: u32, strength: f32);
+}
+
+// Rumble on every connected gamepad that has force feedback. ggez keeps its own
+// gamepad handle to itself, so this opens another one
+pub struct GamepadRumble {
+    gilrs: gilrs::Gilrs,
+    effect: Option<gilrs::ff::Effect>, // an effect stops when it's dropped, so hold on to it
+}
+
+impl GamepadRumble {
+    pub fn new() -> Result<Self, String> {
+        let gilrs = match gilrs::Gilrs::new() {
+            // no gamepad support on this platform - rumble just does nothing
+            Ok(gilrs) | Err(gilrs::Error::NotImplemented(gilrs)) => gilrs,
+            Err(e) => return Err(e.to_string()),
+        };
+        Ok(Self {
+            gilrs,
+            effect: None,
+        })
+    }
+}
+
+impl Haptics for GamepadRumble {
+    fn pulse(&mut self, duration_ms: u32, strength: f32) {
+        use gilrs::ff::{BaseEffect, BaseEffectType, EffectBuilder, Repeat, Replay, Ticks};
+
+        // catch up on gamepads plugged in or unplugged since the last pulse
+        while self.gilrs.next_event().is_some() {}
+        let gamepads: Vec<_> = self
+            .gilrs
+            .gamepads()
+            .filter(|(_, gamepad)| gamepad.is_ff_supported())
+            .map(|(id, _)| id)
+            .collect();
+        if gamepads.is_empty() {
+            return;
+        }
+
+        let duration = Ticks::from_ms(duration_ms);
+        let magnitude = (strength.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+        let effect = EffectBuilder::new()
+            .add_effect(BaseEffect {
+                kind: BaseEffectType::Strong { magnitude },
+                scheduling: Replay {
+                    play_for: duration,
+                    ..Default::default()
+                },
+                envelope: Default::default(),
+            })
+            .gamepads(&gamepads)
+            .repeat(Repeat::For(duration))
+            .finish(&mut self.gilrs)
+            .and_then(|effect| effect.play().map(|_| effect));
+        match effect {
+            Ok(effect) => self.effect = Some(effect),
+            Err(e) => eprintln!("Couldn't rumble: {}", e),
+        }
+    }
+}
+
+// A run put away when the game goes to the background (a phone switching apps, a
+// window closing mid-run), so the next launch can carry on from it
+pub const SUSPEND_FILE: &str = "suspended.txt";
+
+// A copy of the run kept every AUTOSAVE_INTERVAL seconds of play and when the window loses
+// focus, in case the game never gets to suspend it (a crash, the power going). The next
+// launch asks whether to carry on from it
+pub const AUTOSAVE_FILE: &str = "autosave.txt";
+pub const AUTOSAVE_INTERVAL: f64 = 15.0;
+
+impl GameState {
+    // Like the high score, each mode and set of packs keeps its own suspended run
+    pub fn suspend_file(&self) -> String {
+        self.run_file(SUSPEND_FILE)
+    }
+
+    // And so does each autosave
+    pub fn autosave_file(&self) -> String {
+        self.run_file(AUTOSAVE_FILE)
+    }
+
+    fn run_file(&self, file: &str) -> String {
+        self.high_score_file().replacen(
+            HIGH_SCORE_FILE.trim_end_matches(".txt"),
+            file.trim_end_matches(".txt"),
+            1,
+        )
+    }
+
+    // Save the run if it's still going, so it can be picked up next launch. A run
+    // that's over clears out any older save instead
+    pub fn suspend(&self) {
+        self.save_run(&self.suspend_file(), "suspended run");
+    }
+
+    // The same for the autosave
+    pub fn autosave(&self) {
+        self.save_run(&self.autosave_file(), "autosave");
+    }
+
+    fn save_run(&self, key: &str, what: &str) {
+        // the save has no room for which level a level run was on, so those aren't kept
+        if !self.can_save_run() {
+            let _ = self.storage.remove(key);
+            return;
+        }
+        if let Err(e) = self.storage.write(key, &self.suspend_text()) {
+            eprintln!("Failed to save {}: {}", what, e);
+        }
+    }
+
+    // Carry on from a suspended run if there is one. The save is removed either way,
+    // so a broken one doesn't come back every launch
+    pub fn resume_suspended(&mut self) -> bool {
+        self.resume_from(&self.suspend_file())
+    }
+
+    // The same for the autosave, once the player has said to carry on
+    pub fn resume_autosave(&mut self) -> bool {
+        self.resume_from(&self.autosave_file())
+    }
+
+    pub fn has_autosave(&self) -> bool {
+        self.storage.read(&self.autosave_file()).is_some()
+    }
+
+    pub fn clear_autosave(&self) {
+        let _ = self.storage.remove(&self.autosave_file());
+    }
+
+    fn resume_from(&mut self, key: &str) -> bool {
+        let Some(text) = self.storage.read(key) else {
+            return false;
+        };
+        let _ = self.storage.remove(key);
+        self.resume_text(&text)
+    }
+}
+
+// Manual saves the player keeps until they overwrite or delete them, each with the run as
+// a suspend saves it, when it was saved, the score and a picture of the board
+pub const SAVE_SLOTS: usize = 3;
+// How many times bigger than a pixel to a cell the slots show their thumbnails
+pub const THUMBNAIL_SCALE: f32 = 3.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveSlot {
+    pub saved_at: u64, // unix seconds
+    pub score: u32,
+    pub thumbnail: Thumbnail, // a pixel to a cell
+    pub run: String,          // as suspend_text wrote it
+}
+
+impl SaveSlot {
+    pub fn of(game: &GameState) -> Self {
+        let thumbnail = Thumbnail::of(game, 1);
+        Self {
+            saved_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+            score: game.score,
+            thumbnail,
+            run: game.suspend_text(),
+        }
+    }
+
+    // A line each for the time, score and thumbnail (its size, then its pixels), then the run
+    pub fn to_text(&self) -> String {
+        format!(
+            "saved_at {}\nscore {}\nthumbnail {}x{} {}\n{}",
+            self.saved_at,
+            self.score,
+            self.thumbnail.width,
+            self.thumbnail.height,
+            to_hex(&self.thumbnail.pixels),
+            self.run
+        )
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.splitn(4, '\n');
+        let mut field = |name: &str| lines.next()?.strip_prefix(name)?.strip_prefix(' ');
+        let saved_at = field("saved_at")?.parse().ok()?;
+        let score = field("score")?.parse().ok()?;
+        // slots saved before thumbnails had a size were all of the default board
+        let thumbnail = field("thumbnail")?;
+        let (width, height, pixels) = match thumbnail.split_once(' ') {
+            Some((size, pixels)) => {
+                let (width, height) = size.split_once('x')?;
+                (width.parse().ok()?, height.parse().ok()?, pixels)
+            }
+            None => (GRID_WIDTH as usize, GRID_HEIGHT as usize, thumbnail),
+        };
+        // a pixel to a cell, so a damaged size is caught before it's used for anything
+        if width > MAX_GRID_SIDE as usize || height > MAX_GRID_SIDE as usize {
+            return None;
+        }
+        let bytes = width.checked_mul(height)?.checked_mul(4)?;
+        let pixels = from_hex(pixels)?;
+        if pixels.len() != bytes {
+            return None;
+        }
+        let thumbnail = Thumbnail {
+            width,
+            height,
+            pixels,
+        };
+        Some(Self {
+            saved_at,
+            score,
+            thumbnail,
+            run: lines.next()?.to_string(),
+        })
+    }
+
+    pub fn label(&self) -> String {
+        format!("Score {} - {}", self.score, format_date(self.saved_at))
+    }
+}
+
+impl GameState {
+    // Slots are kept per mode and set of packs, like suspended runs. `slot` counts from 0
+    pub fn save_slot_file(&self, slot: usize) -> String {
+        self.run_file(&format!("slot{}.txt", slot + 1))
+    }
+
+    // Only a run that's going can be saved, and not a level run. A run left on the pause
+    // screen or behind the menu still counts, a fresh one waiting behind the menu doesn't
+    pub fn can_save_run(&self) -> bool {
+        let going = match self.screen {
+            Screen::Playing => true,
+            Screen::Paused | Screen::MainMenu | Screen::Settings => self.elapsed > 0.0,
+            Screen::Splash | Screen::GameOver => false,
+        };
+        going && !self.game_over && self.levels.is_none()
+    }
+
+    pub fn save_to_slot(&self, slot: usize) -> std::io::Result<()> {
+        self.storage
+            .write(&self.save_slot_file(slot), &SaveSlot::of(self).to_text())
+    }
+
+    pub fn save_slot(&self, slot: usize) -> Option<SaveSlot> {
+        SaveSlot::parse(&self.storage.read(&self.save_slot_file(slot))?)
+    }
+
+    // Carry on from a slot's run. Unlike a suspended run the slot stays, to go back to
+    pub fn load_from_slot(&mut self, slot: usize) -> bool {
+        self.save_slot(slot)
+            .is_some_and(|saved| self.resume_text(&saved.run))
+    }
+
+    pub fn delete_slot(&self, slot: usize) {
+        let _ = self.storage.remove(&self.save_slot_file(slot));
+    }
+}
+
+// The parts of a frame the profiler times separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePhase {
+    Input,   // key, pad and touch handling
+    Tick,    // the game's update and moves
+    Effects, // recording, rumble, theme reloads
+    Render,  // drawing the board
+    Ui,      // overlays drawn on top of it
+}
+
+impl FramePhase {
+    pub const ALL: [FramePhase; 5] = [
+        FramePhase::Input,
+        FramePhase::Tick,
+        FramePhase::Effects,
+        FramePhase::Render,
+        FramePhase::Ui,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FramePhase::Input => "input",
+            FramePhase::Tick => "tick",
+            FramePhase::Effects => "effects",
+            FramePhase::Render => "render",
+            FramePhase::Ui => "ui",
+        }
+    }
+}
+
+// Frames the profiler remembers - ten seconds at 60fps
+pub const PROFILE_WINDOW: usize = 600;
+// Upper edges of the histogram buckets in milliseconds, around 60fps and 30fps frames.
+// Anything slower goes in one last bucket
+pub const PROFILE_BUCKETS_MS: [f64; 6] = [1.0, 2.0, 4.0, 8.0, 16.7, 33.3];
+// Bumped whenever a field in the profile JSON is renamed, removed or changes meaning
+pub const PROFILE_SCHEMA_VERSION: u32 = 1;
+
+// The last PROFILE_WINDOW timings of one thing, in microseconds
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RollingTimes {
+    samples: std::collections::VecDeque<u32>,
+}
+
+impl RollingTimes {
+    pub fn push(&mut self, time: std::time::Duration) {
+        if self.samples.len() == PROFILE_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples
+            .push_back(time.as_micros().min(u32::MAX as u128) as u32);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn mean_ms(&self) -> f64 {
+        let total: u64 = self.samples.iter().map(|micros| *micros as u64).sum();
+        total as f64 / self.samples.len().max(1) as f64 / 1000.0
+    }
+
+    // The time `fraction` of samples came in under, e.g. 0.99 for the 99th percentile
+    pub fn percentile_ms(&self, fraction: f64) -> f64 {
+        let mut sorted: Vec<u32> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let index = ((sorted.len() as f64 * fraction).ceil() as usize).saturating_sub(1);
+        sorted
+            .get(index)
+            .map_or(0.0, |micros| *micros as f64 / 1000.0)
+    }
+
+    pub fn max_ms(&self) -> f64 {
+        self.samples
+            .iter()
+            .max()
+            .map_or(0.0, |micros| *micros as f64 / 1000.0)
+    }
+
+    // How many samples fall in each of PROFILE_BUCKETS_MS, then how many are slower
+    pub fn histogram(&self) -> [usize; PROFILE_BUCKETS_MS.len() + 1] {
+        let mut counts = [0; PROFILE_BUCKETS_MS.len() + 1];
+        for micros in &self.samples {
+            let ms = *micros as f64 / 1000.0;
+            let bucket = PROFILE_BUCKETS_MS
+                .iter()
+                .position(|edge| ms <= *edge)
+                .unwrap_or(PROFILE_BUCKETS_MS.len());
+            counts[bucket] += 1;
+        }
+        counts
+    }
+
+    fn to_json(&self) -> String {
+        let histogram: Vec<String> = self.histogram().iter().map(usize::to_string).collect();
+        format!(
+            "{{\"mean_ms\":{:.3},\"p50_ms\":{:.3},\"p95_ms\":{:.3},\"p99_ms\":{:.3},\"max_ms\":{:.3},\"histogram\":[{}]}}",
+            self.mean_ms(),
+            self.percentile_ms(0.5),
+            self.percentile_ms(0.95),
+            self.percentile_ms(0.99),
+            self.max_ms(),
+            histogram.join(",")
+        )
+    }
+}
+
+// Where each frame's time goes, over the last few seconds - shown with F3, and saved as
+// JSON with F4 so a report of stutter can come with numbers
+#[derive(Debug, Clone, Default)]
+pub struct FrameProfiler {
+    pub phases: [RollingTimes; 5], // in FramePhase::ALL order
+    pub frames: RollingTimes,      // start of one frame to the start of the next
+    current: [std::time::Duration; 5],
+    last_frame: Option<std::time::Instant>,
+}
+
+impl FrameProfiler {
+    // Add time spent on `phase` to the frame in progress
+    pub fn record(&mut self, phase: FramePhase, time: std::time::Duration) {
+        self.current[phase as usize] += time;
+    }
+
+    // Close the frame in progress, at `now`
+    pub fn end_frame(&mut self, now: std::time::Instant) {
+        for (times, current) in self.phases.iter_mut().zip(&mut self.current) {
+            times.push(std::mem::take(current));
+        }
+        if let Some(last) = self.last_frame {
+            self.frames.push(now.saturating_duration_since(last));
+        }
+        self.last_frame = Some(now);
+    }
+
+    pub fn phase(&self, phase: FramePhase) -> &RollingTimes {
+        &self.phases[phase as usize]
+    }
+
+    // For the overlay: the whole frame, then where the time went
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "frame  mean {:.1}ms  p99 {:.1}ms  max {:.1}ms  ({} frames)",
+            self.frames.mean_ms(),
+            self.frames.percentile_ms(0.99),
+            self.frames.max_ms(),
+            self.frames.len()
+        )];
+        for phase in FramePhase::ALL {
+            let times = self.phase(phase);
+            lines.push(format!(
+                "{:<8} mean {:.2}ms  p99 {:.2}ms  max {:.2}ms",
+                phase.name(),
+                times.mean_ms(),
+                times.percentile_ms(0.99),
+                times.max_ms()
+            ));
+        }
+        lines
+    }
+
+    pub fn to_json(&self) -> String {
+        let phases: Vec<String> = FramePhase::ALL
+            .iter()
+            .map(|phase| format!("\"{}\":{}", phase.name(), self.phase(*phase).to_json()))
+            .collect();
+        let buckets: Vec<String> = PROFILE_BUCKETS_MS.iter().map(f64::to_string).collect();
+        format!(
+            "{{\"schema\":{},\"frames\":{},\"buckets_ms\":[{}],\"frame\":{},\"phases\":{{{}}}}}",
+            PROFILE_SCHEMA_VERSION,
+            self.frames.len(),
+            buckets.join(","),
+            self.frames.to_json(),
+            phases.join(",")
+        )
+    }
+}
+
+// Records the run being played, saving it to the replay directory when it ends
+pub struct ReplayRecorder {
+    pub game: GameState,
+    pub replay: Replay,
+    pub saved: bool,
+    pub theme_watcher: Option<ThemeWatcher>,
+    pub touch: TouchControls,
+    pub paused: bool, // waiting for a tap or key after coming back from the background
+    pub haptics: Option<Box<dyn Haptics>>,
+    pub sounds: Option<SoundBank>,
+    pub heard: Vec<SoundEvent>, // sounds waiting for the next update to play them
+    pub voice: Option<VoicePack>,
+    pub announced: Vec<VoiceLine>, // and the same for the announcer
+    pub feedback: FeedbackRouter,
+    pub time_travel: Option<TimeTravel>,
+    pub caps: MemoryCaps,
+    pub trimmed: usize, // ticks dropped from the front of this run's replay
+    pub profiler: FrameProfiler,
+    pub show_debug: bool,                                       // the F3 overlay
+    pub confirm: Option<ConfirmDialog<Option<RecorderAction>>>, // holds the game while up
+    pub exiting: bool,
+    pub outbox: Outbox,
+    pub since_autosave: f64, // seconds
+    pub slot_menu: Option<SlotMenu>,
+    pub level_select: Option<LevelSelect>,
+}
+
+// What the recorder asks about before doing, since it throws a run away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecorderAction {
+    Restart,
+    Quit,
+    Continue, // pick up the autosave from last time
+    NewRun,   // and throw it away
+    Save(usize),
+    Load(usize),
+    Delete(usize),
+    PlayLevel(usize), // start over from this level
+    Regenerate,       // a new random level in place of this one
+}
+
+// The save slots over the game - F5 saves the run into one, F6 loads one
+pub struct SlotMenu {
+    pub saving: bool,
+    pub focus: usize,
+    pub slots: Vec<Option<SaveSlot>>, // what's in each, as of the last look
+}
+
+// The levels over the game (L, and at launch), to pick where a level run starts
+pub struct LevelSelect {
+    pub focus: usize,
+    pub progress: LevelProgress,
+    pub thumbnails: Vec<Thumbnail>, // each level's board as it starts, a pixel to a cell
+    pub difficulty: Vec<Option<Difficulty>>, // filled in a level a frame while it's open
+}
+
+impl LevelSelect {
+    // Rate the next level that hasn't been yet - the bot games take a moment each, so
+    // they're spread over frames rather than holding up opening the list
+    pub fn rate_next(&mut self, levels: &[Level]) {
+        if let Some(index) = self.difficulty.iter().position(Option::is_none) {
+            self.difficulty[index] = Some(Difficulty::estimate(&levels[index], DIFFICULTY_GAMES));
+        }
+    }
+}
+
+// Level select rows: how tall each is and the box its thumbnail is fitted into
+const LEVEL_ROW_HEIGHT: f32 = 56.0;
+const LEVEL_THUMBNAIL_SIZE: [f32; 2] = [64.0, 48.0];
+
+// The debug timeline (F9): the live game is frozen while any earlier tick of the run is
+// shown, and playing on from there branches the run
+pub struct TimeTravel {
+    pub tick: usize,
+    pub game: GameState, // the live game's settings with the state from `tick`
+    pub dragging: bool,
+}
+
+impl ReplayRecorder {
+    pub fn new(game: GameState) -> Self {
+        let replay = Replay::start(&game);
+        let outbox = Outbox::load(game.storage.clone());
+        Self {
+            game,
+            replay,
+            saved: false,
+            theme_watcher: None,
+            touch: TouchControls::default(),
+            paused: false,
+            haptics: None,
+            sounds: None,
+            heard: Vec::new(),
+            voice: None,
+            announced: Vec::new(),
+            feedback: FeedbackRouter::default(),
+            time_travel: None,
+            caps: MemoryCaps::default(),
+            trimmed: 0,
+            profiler: FrameProfiler::default(),
+            show_debug: false,
+            confirm: None,
+            exiting: false,
+            outbox,
+            since_autosave: 0.0,
+            slot_menu: None,
+            level_select: None,
+        }
+    }
+
+    // Whether there's a run going that a restart or quit would lose
+    pub fn mid_run(&self) -> bool {
+        self.game.screen == Screen::Playing && !self.game.game_over && self.game.elapsed > 0.0
+    }
+
+    // Ask before doing something that ends the run or throws a save away, or just do it
+    // if there's nothing to lose
+    pub fn ask(&mut self, action: RecorderAction) {
+        let occupied = |slot: usize| {
+            self.slot_menu
+                .as_ref()
+                .is_some_and(|menu| menu.slots[slot].is_some())
+        };
+        let (message, yes) = match action {
+            RecorderAction::Restart if self.mid_run() => {
+                ("Restart? This run will be lost.".to_string(), "Restart")
+            }
+            RecorderAction::Quit if self.mid_run() => (
+                "Quit? This run will be saved to pick up later.".to_string(),
+                "Quit",
+            ),
+            RecorderAction::Save(slot) if occupied(slot) => {
+                (format!("Overwrite slot {}?", slot + 1), "Overwrite")
+            }
+            RecorderAction::Load(slot) if self.mid_run() => (
+                format!("Load slot {}? This run will be lost.", slot + 1),
+                "Load",
+            ),
+            RecorderAction::Delete(slot) => (format!("Delete slot {}?", slot + 1), "Delete"),
+            RecorderAction::PlayLevel(index) if self.mid_run() => (
+                format!("Start from level {}? This run will be lost.", index + 1),
+                "Start",
+            ),
+            RecorderAction::Regenerate if self.mid_run() => (
+                "New random level? This run will be lost.".to_string(),
+                "New level",
+            ),
+            _ => return self.act(action),
+        };
+        // backing out of the slots or levels goes back to them, not the game
+        let no = if self.slot_menu.is_some() || self.level_select.is_some() {
+            "Cancel"
+        } else {
+            "Keep playing"
+        };
+        self.confirm = Some(ConfirmDialog::new(
+            message,
+            vec![(yes.to_string(), Some(action)), (no.to_string(), None)],
+            None,
+        ));
+    }
+
+    // Ask whether to carry on from the autosave a run left behind last time
+    pub fn offer_autosave(&mut self) {
+        self.confirm = Some(ConfirmDialog::new(
+            "Continue last run?",
+            vec![
+                ("New run".to_string(), Some(RecorderAction::NewRun)),
+                ("Continue".to_string(), Some(RecorderAction::Continue)),
+            ],
+            Some(RecorderAction::NewRun),
+        ));
+    }
+
+    fn act(&mut self, action: RecorderAction) {
+        match action {
+            RecorderAction::Restart => {
+                self.game.clear_autosave();
+                self.game.restart();
+                self.restart_recording();
+            }
+            RecorderAction::Quit => self.exiting = true,
+            RecorderAction::Continue => {
+                if self.game.resume_autosave() {
+                    self.picked_up_run();
+                }
+            }
+            RecorderAction::NewRun => self.game.clear_autosave(),
+            RecorderAction::Save(slot) => {
+                self.slot_menu = None;
+                match self.game.save_to_slot(slot) {
+                    Ok(()) => self.game.show_toast(format!("Saved to slot {}", slot + 1)),
+                    Err(e) => self.game.show_toast(format!("Couldn't save: {}", e)),
+                }
+            }
+            RecorderAction::Load(slot) => {
+                if self.game.load_from_slot(slot) {
+                    self.slot_menu = None;
+                    self.picked_up_run();
+                }
+            }
+            RecorderAction::Delete(slot) => {
+                self.game.delete_slot(slot);
+                self.look_at_slots();
+            }
+            RecorderAction::PlayLevel(index) => {
+                self.level_select = None;
+                self.game.clear_autosave();
+                self.game.start_from_level(index);
+                self.restart_recording();
+            }
+            RecorderAction::Regenerate => {
+                self.game.clear_autosave();
+                self.game.regenerate_level();
+                self.restart_recording();
+            }
+        }
+    }
+
+    // Open the level select on the level being played, if this is a level run
+    pub fn open_level_select(&mut self) {
+        let Some(run) = &self.game.levels else {
+            return;
+        };
+        self.level_select = Some(LevelSelect {
+            focus: run.current,
+            progress: LevelProgress::load(self.game.storage.as_ref()),
+            thumbnails: run.levels.iter().map(|level| level.thumbnail(1)).collect(),
+            difficulty: vec![None; run.levels.len()],
+        });
+    }
+
+    fn level_select_input(&mut self, input: UiInput) {
+        let (Some(select), Some(run)) = (&mut self.level_select, &self.game.levels) else {
+            return;
+        };
+        let count = run.levels.len();
+        let index = select.focus;
+        match input {
+            UiInput::Up => select.focus = (index + count - 1) % count,
+            UiInput::Down => select.focus = (index + 1) % count,
+            UiInput::Accept if select.progress.is_unlocked(&run.levels, index) => {
+                self.ask(RecorderAction::PlayLevel(index))
+            }
+            UiInput::Accept => {
+                let before = run.levels[index - 1].name.clone();
+                self.game.show_toast(format!("Clear {} first", before));
+            }
+            UiInput::Cancel => self.level_select = None,
+            _ => {}
+        }
+    }
+
+    // A saved run was just loaded: held like a suspended run, and recorded from here
+    fn picked_up_run(&mut self) {
+        self.game.change_screen(Screen::Playing);
+        self.game.skip_transition();
+        self.paused = true;
+        self.replay = Replay::start(&self.game);
+        self.saved = false;
+        self.trimmed = 0;
+    }
+
+    // Open the save slots, to save the run into one or to load one
+    pub fn open_slots(&mut self, saving: bool) {
+        if saving && !self.game.can_save_run() {
+            self.game.show_toast("There's no run to save");
+            return;
+        }
+        self.slot_menu = Some(SlotMenu {
+            saving,
+            focus: 0,
+            slots: Vec::new(),
+        });
+        self.look_at_slots();
+    }
+
+    fn look_at_slots(&mut self) {
+        let slots = (0..SAVE_SLOTS)
+            .map(|slot| self.game.save_slot(slot))
+            .collect();
+        if let Some(menu) = &mut self.slot_menu {
+            menu.slots = slots;
+        }
+    }
+
+    fn slot_input(&mut self, input: UiInput) {
+        let Some(menu) = &mut self.slot_menu else {
+            return;
+        };
+        let slot = menu.focus;
+        match input {
+            UiInput::Up => menu.focus = (slot + SAVE_SLOTS - 1) % SAVE_SLOTS,
+            UiInput::Down => menu.focus = (slot + 1) % SAVE_SLOTS,
+            UiInput::Accept if menu.saving => self.ask(RecorderAction::Save(slot)),
+            UiInput::Accept if menu.slots[slot].is_some() => self.ask(RecorderAction::Load(slot)),
+            UiInput::Cancel => self.slot_menu = None,
+            _ => {}
+        }
+    }
+
+    fn answer(&mut self, input: UiInput) {
+        let Some(dialog) = &mut self.confirm else {
+            return;
+        };
+        if let Some(answer) = dialog.input(input) {
+            self.confirm = None;
+            if let Some(action) = answer {
+                self.act(action);
+            }
+        }
+    }
+
+    // Write the frame profile next to the game, e.g. profile-1700000000.json
+    pub fn save_profile(&mut self) {
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let path = format!("profile-{}.json", stamp);
+        match std::fs::write(&path, self.profiler.to_json()) {
+            Ok(()) => self.game.show_toast(format!("Saved {}", path)),
+            Err(e) => eprintln!("Couldn't save {}: {}", path, e),
+        }
+    }
+
+    // For the debug overlay: what the run's history is using, against its cap
+    pub fn memory_line(&self) -> String {
+        let mut line = format!(
+            "memory: replay {} ({}/{} ticks",
+            format_bytes(self.replay.heap_bytes()),
+            self.replay.tick_count(),
+            self.caps.replay_ticks
+        );
+        if self.trimmed > 0 {
+            line += &format!(", {} trimmed", self.trimmed);
+        }
+        line += &format!("), board {}", format_bytes(self.game.heap_bytes()));
+        if let Some(travel) = &self.time_travel {
+            line += &format!(", timeline {}", format_bytes(travel.game.heap_bytes()));
+        }
+        line
+    }
+
+    // Open the timeline at the current tick, or close it and carry on the live run
+    pub fn toggle_time_travel(&mut self) {
+        if self.time_travel.take().is_none() {
+            self.time_travel = Some(TimeTravel {
+                tick: 0,
+                game: self.game.clone(),
+                dragging: false,
+            });
+            self.travel_to(self.replay.tick_count());
+        }
+    }
+
+    // Show the run as it was after `tick`
+    pub fn travel_to(&mut self, tick: usize) {
+        let Some(travel) = &mut self.time_travel else {
+            return;
+        };
+        let cursor = self.replay.seek(tick);
+        let past = &cursor.game;
+        let game = &mut travel.game;
+        game.snake = past.snake.clone();
+        game.direction = past.direction;
+        game.next_direction = past.direction;
+        game.food = past.food;
+        game.score = past.score;
+        game.speed = past.speed;
+        game.game_over = past.game_over;
+        game.turn_queue.clear();
+        game.slippery_cells.clear();
+        game.transition = None;
+        // replays don't keep the clock, so share the run's time out evenly
+        let fraction = cursor.tick as f64 / self.replay.tick_count().max(1) as f64;
+        game.elapsed = self.replay.duration * fraction;
+        travel.tick = cursor.tick;
+    }
+
+    // Where the scrubber is, from 0.0 to 1.0
+    pub fn travel_progress(&self) -> f32 {
+        let tick = self.time_travel.as_ref().map_or(0, |travel| travel.tick);
+        tick as f32 / self.replay.tick_count().max(1) as f32
+    }
+
+    // Drop everything after the tick on the timeline and play on from it, as a new
+    // recording that keeps the ticks before
+    pub fn branch(&mut self) {
+        let Some(travel) = self.time_travel.take() else {
+            return;
+        };
+        let cursor = self.replay.seek(travel.tick);
+        self.replay.directions.truncate(travel.tick);
+        self.replay.food.truncate(cursor.next_food);
+        self.replay
+            .keyframes
+            .retain(|keyframe| keyframe.tick <= travel.tick);
+        self.replay.score = travel.game.score;
+        self.replay.duration = travel.game.elapsed;
+        self.replay.recorded_at = Replay::start(&travel.game).recorded_at;
+        let clock = self.game.clock;
+        self.game = travel.game;
+        self.game.clock = clock;
+        self.saved = false;
+    }
+
+    // Set off whatever the feedback router says for what happened since `before`:
+    // sounds and announcer lines are queued for the next update, the rest happen now
+    pub fn react(&mut self, before: TickSnapshot) {
+        for event in before.events(&self.game) {
+            for output in self.feedback.outputs(event).to_vec() {
+                match output {
+                    FeedbackOutput::Sound(sound) => self.heard.push(sound),
+                    FeedbackOutput::Voice => self.announced.extend(event.voice_line()),
+                    FeedbackOutput::Rumble {
+                        duration_ms,
+                        strength,
+                    } => {
+                        if let (Some(haptics), Some((duration, strength))) =
+                            (&mut self.haptics, self.game.rumble(duration_ms, strength))
+                        {
+                            haptics.pulse(duration, strength);
+                        }
+                    }
+                    FeedbackOutput::Toast(message) => self.game.show_toast(message),
+                }
+            }
+        }
+    }
+
+    // A run only goes on the local board if its replay plays back to the same score.
+    // If it doesn't, the high score goes back to `best_before`. A run the replay can't
+    // play back is kept, but marked unchecked, and never sent to the leaderboard
+    pub fn vet_high_score(&mut self, best_before: u32) {
+        if self.game.high_score == best_before {
+            return;
+        }
+        if let Some(reason) = self.uncheckable() {
+            self.game
+                .show_toast(format!("High score kept, but unchecked: {}", reason));
+            return;
+        }
+        if let Err(e) = self.replay.verify() {
+            self.game.high_score = best_before;
+            self.game.save_high_score();
+            self.game.load_records();
+            self.game.show_toast(format!("Score not saved: {}", e));
+        } else {
+            self.game.mark_high_score_checked();
+            self.queue_score();
+        }
+    }
+
+    // Why the replay can't be played back to check the run, if it can't
+    fn uncheckable(&self) -> Option<&'static str> {
+        if self.trimmed > 0 {
+            Some("the replay was trimmed")
+        } else if self.game.handicap.score_multiplier != 1.0 {
+            Some("replays don't keep handicaps")
+        } else if self.game.config != GameConfig::default() {
+            Some("replays only play back the classic board")
+        } else if self.game.food_mix != FoodMix::classic() {
+            Some("replays don't keep special food")
+        } else if self.game.levels.is_some() {
+            Some("replays don't keep levels")
+        } else {
+            None
+        }
+    }
+
+    // A checked high score goes to the leaderboard, if the player's given a name for it
+    fn queue_score(&mut self) {
+        let Some(name) = self.game.storage.read(PLAYER_NAME_FILE) else {
+            return;
+        };
+        let filter = NameFilter::load(NAME_BLOCKLIST_FILE);
+        match ScoreSubmission::new(name.trim(), self.replay.clone(), &filter) {
+            Ok(submission) => self.outbox.push("score", submission.to_bytes()),
+            Err(e) => eprintln!("Not sending the score: {}", e),
+        }
+    }
+
+    // Follow up on what was picked on the main menu: a level run starts by picking the level
+    fn follow_menu(&mut self, picked: Option<MenuEntry>) {
+        match picked {
+            Some(MenuEntry::Play) => {
+                if self.game.levels.is_some() {
+                    self.open_level_select();
+                }
+                self.restart_recording();
+            }
+            Some(MenuEntry::Quit) => self.exiting = true,
+            _ => {}
+        }
+    }
+
+    // A restart starts a new recording
+    fn restart_recording(&mut self) {
+        if self.game.elapsed == 0.0 && !self.replay.directions.is_empty() {
+            self.replay = Replay::start(&self.game);
+            self.saved = false;
+            self.trimmed = 0;
+        }
+    }
+
+    // Touch and mouse both steer through the touch controls
+    fn pointer_down(&mut self, x: f32, y: f32) {
+        if self.paused {
+            self.paused = false;
+            return;
+        }
+        let turn = self.touch.press(x, y, &self.game.config);
+        let playing = self.game.screen == Screen::Playing && !self.game.game_over;
+        if let Some(direction) = turn.filter(|_| playing) {
+            self.game.handle_input(direction);
+        }
+    }
+
+    fn pointer_up(&mut self, x: f32, y: f32) {
+        let swipe = self.touch.release(x, y);
+        if self.game.screen == Screen::Splash {
+            self.game
+                .change_screen_with(Screen::MainMenu, TransitionKind::Fade);
+        } else if self.game.screen.is_menu() {
+            let picked = self.game.screen_tap(x, y);
+            self.follow_menu(picked);
+        } else if self.game.game_over {
+            // there's no R key on a phone, so a tap after the game ends plays again
+            self.game.restart();
+            self.restart_recording();
+        } else if let Some(direction) = swipe {
+            self.game.handle_input(direction);
+        }
+    }
+
+    // Follow the game after it's updated - a tick happened if the snake moved or crashed
+    pub fn track(&mut self, head_before: Position, was_over: bool) {
+        let crashed = self.game.game_over && !was_over;
+        if self.game.snake[0] != head_before || crashed {
+            self.replay.record_tick(&self.game);
+            self.trimmed += self.replay.trim_to(self.caps.replay_ticks);
+        }
+        // replays can't hold walls, a board that changes size or special food, so those
+        // runs aren't kept
+        let replayable = self.game.levels.is_none()
+            && self.game.food_mix == FoodMix::classic()
+            && self.game.config.walls == WallMode::Solid;
+        if crashed && !self.saved && replayable {
+            self.save();
+        }
+    }
+
+    fn scrub_timeline(&mut self, x: f32) {
+        let fraction = (x / (self.game.config.screen_width())).clamp(0.0, 1.0);
+        self.travel_to((fraction * self.replay.tick_count() as f32).round() as usize);
+    }
+
+    fn save(&mut self) {
+        self.saved = true;
+        if let Err(e) = self.replay.save_to(self.game.storage.as_ref()) {
+            eprintln!("Failed to save replay: {}", e);
+        }
+    }
+}
+
+impl Drivable for ReplayRecorder {
+    fn advance(&mut self, frame: std::time::Duration) {
+        let started = std::time::Instant::now();
+        if let Some(watcher) = &self.theme_watcher {
+            if watcher.changed() && self.game.reload_theme(&watcher.path) {
+                self.game.show_toast("Theme reloaded");
+            }
+        }
+        // the outbox keeps going whatever the game's doing
+        self.outbox.advance(frame.as_secs_f64());
+        if let (Some(select), Some(run)) = (&mut self.level_select, &self.game.levels) {
+            select.rate_next(&run.levels);
+        }
+        // the tick clock only moves in update, so the snake picks up where it left off
+        if self.paused
+            || self.time_travel.is_some()
+            || self.confirm.is_some()
+            || self.slot_menu.is_some()
+            || self.level_select.is_some()
+        {
+            self.profiler.record(FramePhase::Effects, started.elapsed());
+            return;
+        }
+        let head_before = self.game.snake[0];
+        let before = TickSnapshot::of(&self.game);
+        let best_before = self.game.high_score;
+        let ticking = std::time::Instant::now();
+        self.game.advance(frame);
+        let ticked = std::time::Instant::now();
+        self.track(head_before, before.game_over);
+        if self.game.game_over && !before.game_over {
+            self.vet_high_score(best_before);
+            self.game.clear_autosave();
+        }
+        self.since_autosave += frame.as_secs_f64();
+        if self.since_autosave >= AUTOSAVE_INTERVAL {
+            self.since_autosave = 0.0;
+            self.game.autosave();
+        }
+        self.react(before);
+        self.profiler
+            .record(FramePhase::Tick, ticked.duration_since(ticking));
+        self.profiler.record(
+            FramePhase::Effects,
+            ticking.duration_since(started) + ticked.elapsed(),
+        );
+    }
+
+    fn key_down(&mut self, key_input: KeyInput, repeat: bool) {
+        let started = std::time::Instant::now();
+        self.handle_key(key_input, repeat);
+        self.profiler.record(FramePhase::Input, started.elapsed());
+    }
+
+    // the d-pad only steers the live game - the timeline is for the keyboard
+    fn pad_down(&mut self, button: gilrs::Button) {
+        let started = std::time::Instant::now();
+        if self.paused {
+            self.paused = false;
+        } else if self.confirm.is_some() {
+            if let Some(input) = UiInput::from_button(button) {
+                self.answer(input);
+            }
+        } else if self.slot_menu.is_some() {
+            if let Some(input) = UiInput::from_button(button) {
+                self.slot_input(input);
+            }
+        } else if self.level_select.is_some() {
+            if let Some(input) = UiInput::from_button(button) {
+                self.level_select_input(input);
+            }
+        } else if self.game.screen.is_menu() {
+            if let Some(input) = UiInput::from_button(button) {
+                let picked = self.game.screen_input(input);
+                self.follow_menu(picked);
+            }
+        } else if button == gilrs::Button::Start && self.mid_run() {
+            self.ask(RecorderAction::Restart);
+        } else if self.time_travel.is_none() {
+            self.game.pad_down(button);
+            self.restart_recording();
+        }
+        self.profiler.record(FramePhase::Input, started.elapsed());
+    }
+}
+
+impl ReplayRecorder {
+    fn handle_key(&mut self, key_input: KeyInput, repeat: bool) {
+        if self.paused {
+            if !repeat {
+                self.paused = false;
+            }
+            return;
+        }
+        if key_input.keycode == Some(KeyCode::F9) {
+            if !repeat {
+                self.toggle_time_travel();
+            }
+            return;
+        }
+        match key_input.keycode {
+            Some(KeyCode::F3) if !repeat => {
+                self.show_debug = !self.show_debug;
+                return;
+            }
+            Some(KeyCode::F4) if self.show_debug && !repeat => {
+                self.save_profile();
+                return;
+            }
+            _ => {}
+        }
+        if let Some(travel) = &self.time_travel {
+            let role = match key_input.keycode {
+                Some(KeyCode::Left | KeyCode::Right | KeyCode::Home | KeyCode::End) => {
+                    KeyRole::Navigate
+                }
+                _ => KeyRole::Action,
+            };
+            if !self.game.key_repeat.accepts(role, repeat) {
+                return;
+            }
+            let tick = travel.tick;
+            let step = if key_input.mods.contains(KeyMods::SHIFT) {
+                10
+            } else {
+                1
+            };
+            match key_input.keycode {
+                Some(KeyCode::Left) => self.travel_to(tick.saturating_sub(step)),
+                Some(KeyCode::Right) => self.travel_to(tick + step),
+                Some(KeyCode::Home) => self.travel_to(0),
+                Some(KeyCode::End) => self.travel_to(self.replay.tick_count()),
+                Some(KeyCode::Return) => self.branch(),
+                _ => {}
+            }
+            return;
+        }
+        if let Some(keycode) = key_input.keycode {
+            let role = UiInput::from_key(keycode).map_or(KeyRole::Action, UiInput::role);
+            if self.confirm.is_some() {
+                if let Some(input) = UiInput::from_key(keycode) {
+                    if self.game.key_repeat.accepts(role, repeat) {
+                        self.answer(input);
+                    }
+                }
+                return;
+            }
+            if let Some(menu) = &self.slot_menu {
+                let slot = menu.focus;
+                if matches!(keycode, KeyCode::Delete | KeyCode::Back) {
+                    if !repeat && menu.slots[slot].is_some() {
+                        self.ask(RecorderAction::Delete(slot));
+                    }
+                } else if let Some(input) = UiInput::from_key(keycode) {
+                    if self.game.key_repeat.accepts(role, repeat) {
+                        self.slot_input(input);
+                    }
+                }
+                return;
+            }
+            if self.level_select.is_some() {
+                if let Some(input) = UiInput::from_key(keycode) {
+                    if self.game.key_repeat.accepts(role, repeat) {
+                        self.level_select_input(input);
+                    }
+                }
+                return;
+            }
+            if self.game.screen.is_menu() {
+                if let Some(input) = UiInput::from_key(keycode) {
+                    if self.game.key_repeat.accepts(role, repeat) {
+                        let picked = self.game.screen_input(input);
+                        self.follow_menu(picked);
+                    }
+                }
+                return;
+            }
+            if matches!(keycode, KeyCode::F5 | KeyCode::F6) {
+                if !repeat {
+                    self.open_slots(keycode == KeyCode::F5);
+                }
+                return;
+            }
+            if keycode == KeyCode::L && self.game.levels.is_some() {
+                if !repeat {
+                    self.open_level_select();
+                }
+                return;
+            }
+            let generated = self
+                .game
+                .levels
+                .as_ref()
+                .is_some_and(|run| run.level().seed.is_some());
+            if keycode == KeyCode::G && generated {
+                if !repeat {
+                    self.ask(RecorderAction::Regenerate);
+                }
+                return;
+            }
+            // a restart mid-run and quitting are asked about first
+            let ends_run = match keycode {
+                KeyCode::R if self.mid_run() && !self.game.coop => Some(RecorderAction::Restart),
+                KeyCode::Escape if self.game.screen == Screen::Playing => {
+                    Some(RecorderAction::Quit)
+                }
+                _ => None,
+            };
+            if let Some(action) = ends_run {
+                if !repeat {
+                    self.ask(action);
+                }
+                return;
+            }
+        }
+        self.game.key_down(key_input, repeat);
+        self.restart_recording();
+    }
+}
+
+impl EventHandler for ReplayRecorder {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        self.game.fit_window(ctx)?;
+        self.advance(ctx.time.delta());
+        let volume = self.game.mixer.gain(Channel::Sfx);
+        for event in self.heard.drain(..) {
+            if let Some(sounds) = &self.sounds {
+                sounds.play(ctx, event, volume)?;
+            }
+        }
+        for line in self.announced.drain(..) {
+            if let Some(voice) = &self.voice {
+                self.game.mixer.duck(); // so the announcer can be heard over the music
+                voice.play(ctx, line, volume)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        self.draw(ctx)
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        key_input: KeyInput,
+        repeat: bool,
+    ) -> GameResult {
+        self.key_down(key_input, repeat);
+        if self.exiting {
+            ctx.request_quit();
+        }
+        Ok(())
+    }
+
+    fn gamepad_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        button: gilrs::Button,
+        _id: ggez::input::gamepad::GamepadId,
+    ) -> GameResult {
+        self.pad_down(button);
+        if self.exiting {
+            ctx.request_quit();
+        }
+        Ok(())
+    }
+
+    fn touch_event(&mut self, _ctx: &mut Context, phase: TouchPhase, x: f64, y: f64) -> GameResult {
+        let started = std::time::Instant::now();
+        match phase {
+            TouchPhase::Started => self.pointer_down(x as f32, y as f32),
+            TouchPhase::Ended => self.pointer_up(x as f32, y as f32),
+            TouchPhase::Cancelled => self.touch.swipe_start = None,
+            TouchPhase::Moved => {}
+        }
+        self.profiler.record(FramePhase::Input, started.elapsed());
+        Ok(())
+    }
+
+    fn mouse_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) -> GameResult {
+        if let Some(travel) = &mut self.time_travel {
+            if button == MouseButton::Left && y >= timeline_bar_top(&self.game.config) {
+                travel.dragging = true;
+                self.scrub_timeline(x);
+            }
+            return Ok(());
+        }
+        if button == MouseButton::Left {
+            self.pointer_down(x, y);
+        }
+        Ok(())
+    }
+
+    fn mouse_motion_event(
+        &mut self,
+        _ctx: &mut Context,
+        x: f32,
+        _y: f32,
+        _dx: f32,
+        _dy: f32,
+    ) -> GameResult {
+        if self
+            .time_travel
+            .as_ref()
+            .is_some_and(|travel| travel.dragging)
+        {
+            self.scrub_timeline(x);
+        }
+        Ok(())
+    }
+
+    fn mouse_button_up_event(
+        &mut self,
+        _ctx: &mut Context,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) -> GameResult {
+        if let Some(travel) = &mut self.time_travel {
+            travel.dragging = false;
+            return Ok(());
+        }
+        if button == MouseButton::Left {
+            self.pointer_up(x, y);
+        }
+        Ok(())
+    }
+
+    // Losing focus is as close as ggez gets to a phone app being sent to the
+    // background: hold the run and save it in case the app never comes back
+    fn focus_event(&mut self, _ctx: &mut Context, gained: bool) -> GameResult {
+        if !gained && self.game.screen == Screen::Playing && !self.game.game_over {
+            self.paused = true;
+            self.game.suspend();
+            self.game.autosave();
+        }
+        Ok(())
+    }
+
+    fn quit_event(&mut self, _ctx: &mut Context) -> Result<bool, ggez::GameError> {
+        self.game.suspend();
+        Ok(false)
+    }
+}
+
+// Playback of one replay, with pause, fast forward, jumping between food and scrubbing
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    pub cursor: ReplayCursor,
+    pub food_ticks: Vec<usize>, // ticks where food got eaten
+    pub paused: bool,
+    pub speed: f64, // 1x, 2x or 4x
+    pub timer: f64, // time since the last tick
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: &Replay) -> Self {
+        let states = replay.states();
+        let food_ticks = (1..states.len())
+            .filter(|tick| states[*tick].score > states[tick - 1].score)
+            .collect();
+        Self {
+            replay: replay.clone(),
+            cursor: replay.cursor_at_start(),
+            food_ticks,
+            paused: false,
+            speed: 1.0,
+            timer: 0.0,
+        }
+    }
+
+    pub fn current(&self) -> &GameState {
+        &self.cursor.game
+    }
+
+    pub fn tick(&self) -> usize {
+        self.cursor.tick
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cursor.tick >= self.replay.tick_count()
+    }
+
+    // How far through the replay playback is, from 0.0 to 1.0
+    pub fn progress(&self) -> f32 {
+        self.cursor.tick as f32 / self.replay.tick_count().max(1) as f32
+    }
+
+    // Ticks play back at the speed the run was played at, times the playback speed
+    pub fn advance(&mut self, delta: f64) {
+        if self.paused {
+            return;
+        }
+        self.timer += delta * self.speed;
+        while !self.is_finished() && self.timer >= self.current().speed.seconds_per_cell() {
+            self.timer -= self.current().speed.seconds_per_cell();
+            self.replay.step(&mut self.cursor);
+        }
+    }
+
+    pub fn cycle_speed(&mut self) {
+        self.speed = if self.speed >= 4.0 {
+            1.0
+        } else {
+            self.speed * 2.0
+        };
+    }
+
+    pub fn seek_next_food(&mut self) {
+        if let Some(tick) = self.food_ticks.iter().find(|tick| **tick > self.tick()) {
+            self.seek(*tick);
+        }
+    }
+
+    // Back to the food before this one, or the start
+    pub fn seek_previous_food(&mut self) {
+        let tick = self
+            .food_ticks
+            .iter()
+            .rev()
+            .find(|tick| **tick < self.tick())
+            .copied()
+            .unwrap_or(0);
+        self.seek(tick);
+    }
+
+    // Jump to a point on the scrub bar, 0.0 being the start and 1.0 the end
+    pub fn scrub(&mut self, fraction: f32) {
+        let tick = (fraction.clamp(0.0, 1.0) * self.replay.tick_count() as f32).round();
+        self.seek(tick as usize);
+    }
+
+    pub fn seek(&mut self, tick: usize) {
+        self.cursor = self.replay.seek(tick);
+        self.timer = 0.0;
+    }
+}
+
+// Height of the scrub bar along the bottom of the replay player
+const SCRUB_BAR_HEIGHT: f32 = 8.0;
+
+// Clicks below this grab the scrub bar - it's thin, so there's some room above it
+fn timeline_bar_top(config: &GameConfig) -> f32 {
+    config.screen_height() - SCRUB_BAR_HEIGHT * 2.0
+}
+
+// What a menu-like screen is asked to do, whichever key was pressed for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UiInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Accept,
+    Cancel,
+    Type(char),   // a typed character, for text inputs
+    Erase,        // backspace
+    PickNext,     // the next character under the cursor, for entering text on a pad
+    PickPrevious, // and the one before
+}
+
+impl UiInput {
+    pub fn from_key(key: KeyCode) -> Option<Self> {
+        Some(match key {
+            KeyCode::Up | KeyCode::W => Self::Up,
+            KeyCode::Down | KeyCode::S => Self::Down,
+            KeyCode::Left | KeyCode::A => Self::Left,
+            KeyCode::Right | KeyCode::D => Self::Right,
+            KeyCode::Return | KeyCode::NumpadEnter | KeyCode::Space => Self::Accept,
+            KeyCode::Escape => Self::Cancel,
+            _ => return None,
+        })
+    }
+
+    // While a text input has focus, letters and Space are typing, so only the keys that
+    // can't be typed move around - the characters themselves come from text events
+    pub fn from_key_while_typing(key: KeyCode) -> Option<Self> {
+        Some(match key {
+            KeyCode::Up => Self::Up,
+            KeyCode::Down => Self::Down,
+            KeyCode::Left => Self::Left,
+            KeyCode::Right => Self::Right,
+            KeyCode::Return | KeyCode::NumpadEnter => Self::Accept,
+            KeyCode::Escape => Self::Cancel,
+            KeyCode::Back => Self::Erase,
+            _ => return None,
+        })
+    }
+
+    // The d-pad moves, A (or cross) accepts and B (or circle) backs out. In a text input
+    // the shoulder buttons pick the character under the cursor and X (or square) erases
+    pub fn from_button(button: gilrs::Button) -> Option<Self> {
+        Some(match button {
+            gilrs::Button::DPadUp => Self::Up,
+            gilrs::Button::DPadDown => Self::Down,
+            gilrs::Button::DPadLeft => Self::Left,
+            gilrs::Button::DPadRight => Self::Right,
+            gilrs::Button::South | gilrs::Button::Start => Self::Accept,
+            gilrs::Button::East | gilrs::Button::Select => Self::Cancel,
+            gilrs::Button::West => Self::Erase,
+            gilrs::Button::RightTrigger => Self::PickNext,
+            gilrs::Button::LeftTrigger => Self::PickPrevious,
+            _ => return None,
+        })
+    }
+
+    pub fn role(self) -> KeyRole {
+        match self {
+            Self::Accept | Self::Cancel => KeyRole::Action,
+            _ => KeyRole::Navigate,
+        }
+    }
+}
+
+// The pieces menus, lists and settings are put together from. Only labels can't take focus
+#[derive(Debug, Clone, PartialEq)]
+pub enum Widget {
+    Label {
+        text: String,
+        size: f32,
+        color: Color,
+    },
+    Button {
+        label: String,
+    },
+    Toggle {
+        label: String,
+        on: bool,
+    },
+    Slider {
+        label: String,
+        value: f32,
+        min: f32,
+        max: f32,
+        step: f32,
+    },
+    // rows to pick one of - Up and Down move through the rows before leaving the list
+    List {
+        rows: Vec<String>,
+        selected: usize,
+    },
+    Input {
+        label: String,
+        input: TextInput,
+    },
+}
+
+impl Widget {
+    pub fn label(text: impl Into<String>, size: f32, color: Color) -> Self {
+        Self::Label {
+            text: text.into(),
+            size,
+            color,
+        }
+    }
+
+    // A title in the usual gold
+    pub fn title(text: impl Into<String>) -> Self {
+        Self::label(text, 32.0, Color::new(1.0, 0.84, 0.0, 1.0))
+    }
+
+    // Small, dim text for which keys do what
+    pub fn hint(text: impl Into<String>) -> Self {
+        Self::label(text, 14.0, Color::new(1.0, 1.0, 1.0, 0.5))
+    }
+
+    pub fn button(label: impl Into<String>) -> Self {
+        Self::Button {
+            label: label.into(),
+        }
+    }
+
+    pub fn toggle(label: impl Into<String>, on: bool) -> Self {
+        Self::Toggle {
+            label: label.into(),
+            on,
+        }
+    }
+
+    pub fn slider(label: impl Into<String>, value: f32, min: f32, max: f32, step: f32) -> Self {
+        Self::Slider {
+            label: label.into(),
+            value: value.clamp(min, max),
+            min,
+            max,
+            step,
+        }
+    }
+
+    pub fn list(rows: Vec<String>, selected: usize) -> Self {
+        let selected = selected.min(rows.len().saturating_sub(1));
+        Self::List { rows, selected }
+    }
+
+    pub fn input(label: impl Into<String>, input: TextInput) -> Self {
+        Self::Input {
+            label: label.into(),
+            input,
+        }
+    }
+
+    pub fn is_focusable(&self) -> bool {
+        !matches!(self, Self::Label { .. })
+    }
+
+    // The lines this widget draws as, given the text size of the panel's rows
+    fn lines(&self, row_size: f32) -> Vec<(String, f32, Option<usize>)> {
+        let row = |text: String| vec![(text, row_size, None)];
+        match self {
+            Self::Label { text, size, .. } => vec![(text.clone(), *size, None)],
+            Self::Button { label } => row(label.clone()),
+            Self::Toggle { label, on } => {
+                row(format!("{}: {}", label, if *on { "on" } else { "off" }))
+            }
+            Self::Slider {
+                label,
+                value,
+                min,
+                max,
+                ..
+            } => {
+                let filled = if max > min {
+                    ((value - min) / (max - min) * 10.0).round() as usize
+                } else {
+                    10
+                };
+                row(format!(
+                    "{}: [{}{}] {}",
+                    label,
+                    "#".repeat(filled),
+                    "-".repeat(10 - filled),
+                    value
+                ))
+            }
+            Self::List { rows, .. } => rows
+                .iter()
+                .enumerate()
+                .map(|(index, text)| (text.clone(), row_size, Some(index)))
+                .collect(),
+            Self::Input { label, input } => row(format!("{}: {}", label, input.display())),
+        }
+    }
+}
+
+// What a panel did with an input, for the screen to act on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UiEvent {
+    Pressed(usize),      // a button, or Enter in a text input, by widget index
+    Changed(usize),      // a toggle flipped, a slider moved or a list's selection moved
+    Chose(usize, usize), // a list's row was accepted: widget index, then row
+    Cancelled,
+}
+
+// A column of widgets with one of them focused. Screens build one from their own state
+// when they draw or take input, so there's nothing to keep in sync
+#[derive(Debug, Clone, PartialEq)]
+pub struct Panel {
+    pub widgets: Vec<Widget>,
+    pub focus: Option<usize>,
+    pub row_size: f32, // text size of everything but labels
+}
+
+impl Default for Panel {
+    fn default() -> Self {
+        Self {
+            widgets: Vec::new(),
+            focus: None,
+            row_size: 16.0,
+        }
+    }
+}
+
+impl Panel {
+    // Add a widget, focusing it if it's the first that can be. Returns its index
+    pub fn push(&mut self, widget: Widget) -> usize {
+        if self.focus.is_none() && widget.is_focusable() {
+            self.focus = Some(self.widgets.len());
+        }
+        self.widgets.push(widget);
+        self.widgets.len() - 1
+    }
+
+    pub fn focused(&self) -> Option<&Widget> {
+        self.widgets.get(self.focus?)
+    }
+
+    // Whether keys should type into a text input rather than move around
+    pub fn is_typing(&self) -> bool {
+        matches!(self.focused(), Some(Widget::Input { .. }))
+    }
+
+    // The next focusable widget after (or before) the focused one, wrapping around the
+    // ends - back to the same one if nothing else can take focus
+    fn step_focus(&self, forward: bool) -> Option<usize> {
+        let focus = self.focus?;
+        let count = self.widgets.len();
+        (1..=count)
+            .map(|step| {
+                if forward {
+                    (focus + step) % count
+                } else {
+                    (focus + count - step) % count
+                }
+            })
+            .find(|&i| self.widgets[i].is_focusable())
+    }
+
+    pub fn input(&mut self, input: UiInput) -> Option<UiEvent> {
+        if input == UiInput::Cancel {
+            return Some(UiEvent::Cancelled);
+        }
+        let focus = self.focus?;
+        match (&mut self.widgets[focus], input) {
+            (Widget::List { selected, .. }, UiInput::Up) if *selected > 0 => {
+                *selected -= 1;
+                return Some(UiEvent::Changed(focus));
+            }
+            (Widget::List { rows, selected }, UiInput::Down) if *selected + 1 < rows.len() => {
+                *selected += 1;
+                return Some(UiEvent::Changed(focus));
+            }
+            (Widget::List { rows, selected }, UiInput::Accept) => {
+                return (!rows.is_empty()).then_some(UiEvent::Chose(focus, *selected));
+            }
+            (Widget::Button { .. } | Widget::Input { .. }, UiInput::Accept) => {
+                return Some(UiEvent::Pressed(focus))
+            }
+            (Widget::Input { input: text, .. }, _) => {
+                let changed = match input {
+                    UiInput::Type(c) => text.insert(c),
+                    UiInput::Erase => text.backspace(),
+                    UiInput::Left => text.move_cursor(-1),
+                    UiInput::Right => text.move_cursor(1),
+                    UiInput::PickNext => text.pick(true),
+                    UiInput::PickPrevious => text.pick(false),
+                    _ => false,
+                };
+                if changed {
+                    return Some(UiEvent::Changed(focus));
+                }
+            }
+            (Widget::Toggle { on, .. }, UiInput::Accept | UiInput::Left | UiInput::Right) => {
+                *on = !*on;
+                return Some(UiEvent::Changed(focus));
+            }
+            (
+                Widget::Slider {
+                    value,
+                    min,
+                    max,
+                    step,
+                    ..
+                },
+                UiInput::Left | UiInput::Right,
+            ) => {
+                let delta = if input == UiInput::Left {
+                    -*step
+                } else {
+                    *step
+                };
+                let moved = (*value + delta).clamp(*min, *max);
+                if moved == *value {
+                    return None;
+                }
+                *value = moved;
+                return Some(UiEvent::Changed(focus));
+            }
+            _ => {}
+        }
+        match input {
+            UiInput::Up | UiInput::Down => {
+                let down = input == UiInput::Down;
+                let next = self.step_focus(down)?;
+                self.focus = Some(next);
+                // a list is entered at the end it was come at from
+                if let Widget::List { rows, selected } = &mut self.widgets[next] {
+                    *selected = if down {
+                        0
+                    } else {
+                        rows.len().saturating_sub(1)
+                    };
+                    return Some(UiEvent::Changed(next));
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+// A question over a darkened screen that has to be answered before anything else
+// happens. Each button gives back its answer, and backing out gives `cancel`. Focus
+// starts on the last button, so keep the safe choice there
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfirmDialog<T> {
+    pub message: String,
+    pub buttons: Vec<(String, T)>,
+    pub cancel: T,
+    pub focus: usize,
+}
+
+impl<T: Copy> ConfirmDialog<T> {
+    pub fn new(message: impl Into<String>, buttons: Vec<(String, T)>, cancel: T) -> Self {
+        let focus = buttons.len().saturating_sub(1);
+        Self {
+            message: message.into(),
+            buttons,
+            cancel,
+            focus,
+        }
+    }
+
+    fn panel(&self) -> Panel {
+        let mut panel = Panel {
+            row_size: 22.0,
+            ..Panel::default()
+        };
+        panel.push(Widget::label(self.message.as_str(), 24.0, Color::WHITE));
+        let first = panel.widgets.len();
+        for (label, _) in &self.buttons {
+            panel.push(Widget::button(label.as_str()));
+        }
+        panel.focus = Some(first + self.focus);
+        panel
+    }
+
+    // The answer, once there is one
+    pub fn input(&mut self, input: UiInput) -> Option<T> {
+        let mut panel = self.panel();
+        let first = panel.focus? - self.focus;
+        let event = panel.input(input);
+        self.focus = panel.focus? - first;
+        match event? {
+            UiEvent::Pressed(index) => Some(self.buttons[index - first].1),
+            UiEvent::Cancelled => Some(self.cancel),
+            _ => None,
+        }
+    }
+}
+
+impl ConfirmDialog<bool> {
+    // The usual "are you sure": `yes` gives true, Cancel (focused) and backing out false
+    pub fn yes_no(message: impl Into<String>, yes: impl Into<String>) -> Self {
+        Self::new(
+            message,
+            vec![(yes.into(), true), ("Cancel".to_string(), false)],
+            false,
+        )
+    }
+}
+
+// Which characters a text input takes. Each also has the alphabet a pad cycles through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharFilter {
+    Name,     // letters, digits, spaces and - _ .
+    Digits,   // seeds
+    RoomCode, // the letters room_code uses, typed in any case
+}
+
+impl CharFilter {
+    pub fn alphabet(self) -> &'static str {
+        match self {
+            Self::Name => "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789 -_.",
+            Self::Digits => "0123456789",
+            Self::RoomCode => "ABCDEFGHJKLMNPQRSTUVWXYZ",
+        }
+    }
+
+    // The character as it's stored, or None if it isn't allowed
+    pub fn accept(self, c: char) -> Option<char> {
+        let c = match self {
+            Self::RoomCode => c.to_ascii_uppercase(),
+            _ => c,
+        };
+        self.alphabet().contains(c).then_some(c)
+    }
+}
+
+// A line of text being entered, from a keyboard or by picking characters on a pad. The
+// cursor sits between characters, and a pad picks the one just after it
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextInput {
+    pub text: Vec<char>,
+    pub cursor: usize,
+    pub max_len: usize,
+    pub filter: CharFilter,
+}
+
+impl TextInput {
+    pub fn new(filter: CharFilter, max_len: usize) -> Self {
+        Self {
+            text: Vec::new(),
+            cursor: 0,
+            max_len,
+            filter,
+        }
+    }
+
+    // Start with `text` (whatever of it the filter allows), cursor at the end
+    pub fn with_text(mut self, text: &str) -> Self {
+        for c in text.chars() {
+            self.insert(c);
+        }
+        self
+    }
+
+    pub fn value(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    // These all say whether anything changed
+    pub fn insert(&mut self, c: char) -> bool {
+        let Some(c) = self.filter.accept(c) else {
+            return false;
+        };
+        if self.text.len() >= self.max_len {
+            return false;
+        }
+        self.text.insert(self.cursor, c);
+        self.cursor += 1;
+        true
+    }
+
+    pub fn backspace(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.cursor -= 1;
+        self.text.remove(self.cursor);
+        true
+    }
+
+    pub fn move_cursor(&mut self, offset: isize) -> bool {
+        let moved = self
+            .cursor
+            .saturating_add_signed(offset)
+            .min(self.text.len());
+        std::mem::replace(&mut self.cursor, moved) != moved
+    }
+
+    // Swap the character after the cursor for the next (or previous) one in the filter's
+    // alphabet, adding one at the end if there's room
+    pub fn pick(&mut self, forward: bool) -> bool {
+        let alphabet: Vec<char> = self.filter.alphabet().chars().collect();
+        let Some(current) = self.text.get(self.cursor) else {
+            if self.text.len() >= self.max_len {
+                return false;
+            }
+            let first = if forward {
+                alphabet[0]
+            } else {
+                alphabet[alphabet.len() - 1]
+            };
+            self.text.push(first);
+            return true;
+        };
+        let index = alphabet.iter().position(|c| c == current).unwrap_or(0);
+        let step = if forward { 1 } else { alphabet.len() - 1 };
+        self.text[self.cursor] = alphabet[(index + step) % alphabet.len()];
+        true
+    }
+
+    // The text with a bar where the cursor is
+    pub fn display(&self) -> String {
+        let mut shown: String = self.text[..self.cursor].iter().collect();
+        shown.push('|');
+        shown.extend(&self.text[self.cursor..]);
+        shown
+    }
+
+    // The text as a player name, or why it can't be one
+    pub fn name(&self, filter: &NameFilter) -> Result<String, String> {
+        filter.check(&self.value())
+    }
+}
+
+// Words added to the built-in blocklist, one a line
+pub const NAME_BLOCKLIST_FILE: &str = "blocked_names.txt";
+const BLOCKED_WORDS: [&str; 6] = ["fuck", "shit", "cunt", "bitch", "dick", "twat"];
+
+// What a player name has to pass before it's shown, saved or sent anywhere: it fits,
+// it only uses the characters a name can have, and it has no blocked word in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameFilter {
+    pub blocked: Vec<String>,
+    pub max_len: usize,
+}
+
+impl Default for NameFilter {
+    fn default() -> Self {
+        Self {
+            blocked: BLOCKED_WORDS.iter().map(|word| word.to_string()).collect(),
+            max_len: PLAYER_NAME_LENGTH,
+        }
+    }
+}
+
+impl NameFilter {
+    // The built-in words plus the ones in `path`, if there is one
+    pub fn load(path: &str) -> NameFilter {
+        let mut filter = NameFilter::default();
+        if let Ok(text) = std::fs::read_to_string(path) {
+            filter.add_words(&text);
+        }
+        filter
+    }
+
+    // One word a line, lines starting with `#` left out
+    pub fn add_words(&mut self, text: &str) {
+        for line in text.lines().map(str::trim) {
+            let word = squash(line);
+            if !line.starts_with('#') && !word.is_empty() && !self.blocked.contains(&word) {
+                self.blocked.push(word);
+            }
+        }
+    }
+
+    // The name tidied up - trimmed, with runs of spaces made one - or why it isn't allowed
+    pub fn check(&self, name: &str) -> Result<String, String> {
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            return Err("a name can't be blank".to_string());
+        }
+        if name.chars().count() > self.max_len {
+            return Err(format!("names can be at most {} characters", self.max_len));
+        }
+        if let Some(c) = name.chars().find(|c| CharFilter::Name.accept(*c).is_none()) {
+            return Err(format!("`{}` can't be used in a name", c));
+        }
+        let squashed = squash(&name);
+        if self
+            .blocked
+            .iter()
+            .any(|word| squashed.contains(word.as_str()))
+        {
+            return Err("that name isn't allowed".to_string());
+        }
+        Ok(name)
+    }
+}
+
+// Lower-case letters only, with digits that look like letters read as them, so spacing
+// a word out or writing it as "5h1t" doesn't get it past the blocklist
+fn squash(text: &str) -> String {
+    text.chars()
+        .filter_map(|c| match c.to_ascii_lowercase() {
+            '0' => Some('o'),
+            '1' => Some('i'),
+            '3' => Some('e'),
+            '4' => Some('a'),
+            '5' => Some('s'),
+            '7' => Some('t'),
+            c if c.is_ascii_lowercase() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+// Lists saved replays and plays the chosen one
+pub struct ReplayBrowser {
+    pub replays: Vec<Replay>,
+    pub selected: usize,
+    pub player: Option<ReplayPlayer>,
+    pub key_repeat: KeyRepeat,
+    pub left_on_disk: usize, // older replays not loaded, to stay under the memory cap
+    pub exiting: bool,
+    pub config: GameConfig, // the window's board - replays on other boards are scaled to it
+    preview: Option<(usize, Thumbnail)>, // how the selected replay ended
+}
+
+impl ReplayBrowser {
+    pub fn new(replays: Vec<Replay>, config: GameConfig) -> Self {
+        Self {
+            replays,
+            selected: 0,
+            player: None,
+            key_repeat: KeyRepeat::default(),
+            left_on_disk: 0,
+            exiting: false,
+            config,
+            preview: None,
+        }
+    }
+
+    // The board at the end of the selected replay, played out the first time it's asked
+    // for and kept until the selection moves
+    pub fn preview(&mut self, ctx: Option<&mut Context>) -> Option<&Thumbnail> {
+        let replay = self.replays.get(self.selected)?;
+        if self.preview.as_ref().map(|(index, _)| *index) != Some(self.selected) {
+            let end = replay.states().pop()?;
+            self.preview = Some((self.selected, Thumbnail::render(ctx, &end, 4)));
+        }
+        self.preview.as_ref().map(|(_, thumbnail)| thumbnail)
+    }
+
+    // Keep the newest replays that fit in `bytes`, letting go of the rest
+    pub fn evict_to(&mut self, bytes: usize) {
+        let mut used = 0;
+        let keep = self
+            .replays
+            .iter()
+            .take_while(|replay| {
+                used += replay.heap_bytes();
+                used <= bytes
+            })
+            .count();
+        self.left_on_disk += self.replays.len() - keep;
+        self.replays.truncate(keep);
+        self.selected = self.selected.min(keep.saturating_sub(1));
+    }
+
+    // One row of the list: date, mode, score and how long the run lasted
+    pub fn describe(replay: &Replay) -> String {
+        format!(
+            "{}  {:<8} {:>4} pts  {}",
+            format_date(replay.recorded_at),
+            replay.mode,
+            replay.score,
+            format_time(replay.duration)
+        )
+    }
+
+    // The list of replays, with the chosen one focused
+    pub fn panel(&self) -> Panel {
+        let mut panel = Panel::default();
+        panel.push(Widget::title("Replays"));
+        if self.replays.is_empty() {
+            panel.push(Widget::label(
+                "No replays yet - go play!",
+                18.0,
+                Color::WHITE,
+            ));
+        } else {
+            let rows = self.replays.iter().map(Self::describe).collect();
+            panel.push(Widget::list(rows, self.selected));
+        }
+        if self.left_on_disk > 0 {
+            panel.push(Widget::label(
+                format!("{} older replays not loaded", self.left_on_disk),
+                14.0,
+                Color::new(1.0, 1.0, 1.0, 0.5),
+            ));
+        }
+        panel.push(Widget::hint(
+            "Up/Down to choose, Enter or A to watch, Esc or B to quit",
+        ));
+        panel
+    }
+
+    // Move through the list, or pick a replay to watch
+    fn list_input(&mut self, input: UiInput) {
+        let mut panel = self.panel();
+        match panel.input(input) {
+            Some(UiEvent::Changed(_)) => {
+                if let Some(Widget::List { selected, .. }) = panel.focused() {
+                    self.selected = *selected;
+                }
+            }
+            Some(UiEvent::Chose(_, row)) => {
+                self.player = Some(ReplayPlayer::new(&self.replays[row]));
+            }
+            Some(UiEvent::Cancelled) => self.exiting = true,
+            _ => {}
+        }
+    }
+}
+
+// Lists the installed packs, to switch them on and off and set their load order
+pub struct PackBrowser {
+    pub packs: Vec<Pack>,
+    pub problems: Vec<String>, // packs that were skipped, and why
+    pub profile: PackProfile,
+    pub profile_path: String,
+    pub selected: usize,
+    pub key_repeat: KeyRepeat,
+    pub exiting: bool,
+}
+
+impl PackBrowser {
+    pub fn new(packs: Vec<Pack>, problems: Vec<String>, profile_path: &str) -> Self {
+        Self {
+            packs,
+            problems,
+            profile: PackProfile::load(profile_path),
+            profile_path: profile_path.to_string(),
+            selected: 0,
+            key_repeat: KeyRepeat::default(),
+            exiting: false,
+        }
+    }
+
+    // One row of the list: on/off and load order, then what the manifest says
+    pub fn describe(&self, pack: &Pack) -> String {
+        let manifest = &pack.manifest;
+        let state = match self
+            .profile
+            .enabled
+            .iter()
+            .position(|n| *n == manifest.name)
+        {
+            Some(order) => format!("[{}]", order + 1),
+            None => "[ ]".to_string(),
+        };
+        let mut row = format!("{} {} {}", state, manifest.name, manifest.version);
+        if !manifest.author.is_empty() {
+            row += &format!(" by {}", manifest.author);
+        }
+        if !manifest.description.is_empty() {
+            row += &format!(" - {}", manifest.description);
+        }
+        row
+    }
+
+    pub fn toggle_selected(&mut self) {
+        if let Some(pack) = self.packs.get(self.selected) {
+            self.profile.toggle(&pack.manifest.name);
+            self.save();
+        }
+    }
+
+    pub fn shift_selected(&mut self, offset: isize) {
+        if let Some(pack) = self.packs.get(self.selected) {
+            self.profile.shift(&pack.manifest.name, offset);
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        if let Err(e) = self.profile.save(&self.profile_path) {
+            eprintln!("Failed to save enabled packs: {}", e);
+        }
+    }
+
+    // The installed packs, then any that were skipped
+    pub fn panel(&self) -> Panel {
+        let mut panel = Panel::default();
+        panel.push(Widget::title("Content Packs"));
+        if self.packs.is_empty() {
+            panel.push(Widget::label(
+                format!("No packs yet - put them in {}/", MODS_DIR),
+                18.0,
+                Color::WHITE,
+            ));
+        } else {
+            let rows = self.packs.iter().map(|pack| self.describe(pack)).collect();
+            panel.push(Widget::list(rows, self.selected));
+        }
+        for problem in &self.problems {
+            panel.push(Widget::label(
+                problem.clone(),
+                14.0,
+                Color::new(1.0, 0.4, 0.4, 0.8),
+            ));
+        }
+        panel.push(Widget::hint(
+            "Space or A to switch on/off, [ and ] or LB and RB to change load order, Esc or B to quit",
+        ));
+        panel
+    }
+
+    fn list_input(&mut self, input: UiInput) {
+        let mut panel = self.panel();
+        match panel.input(input) {
+            Some(UiEvent::Changed(_)) => {
+                if let Some(Widget::List { selected, .. }) = panel.focused() {
+                    self.selected = *selected;
+                }
+            }
+            Some(UiEvent::Chose(..)) => self.toggle_selected(),
+            Some(UiEvent::Cancelled) => self.exiting = true,
+            _ => {}
+        }
+    }
+}
+
+impl Drivable for PackBrowser {
+    fn key_down(&mut self, key_input: KeyInput, repeat: bool) {
+        let Some(keycode) = key_input.keycode else {
+            return;
+        };
+        let input = UiInput::from_key(keycode);
+        let role = input.map_or(KeyRole::Action, UiInput::role);
+        if !self.key_repeat.accepts(role, repeat) {
+            return;
+        }
+        match keycode {
+            KeyCode::LBracket => self.shift_selected(-1),
+            KeyCode::RBracket => self.shift_selected(1),
+            _ => {
+                if let Some(input) = input {
+                    self.list_input(input);
+                }
+            }
+        }
+    }
+
+    fn pad_down(&mut self, button: gilrs::Button) {
+        match button {
+            gilrs::Button::LeftTrigger => self.shift_selected(-1),
+            gilrs::Button::RightTrigger => self.shift_selected(1),
+            _ => {
+                if let Some(input) = UiInput::from_button(button) {
+                    self.list_input(input);
+                }
+            }
+        }
+    }
+
+    fn advance(&mut self, _frame: std::time::Duration) {}
+}
+
+impl EventHandler for PackBrowser {
+    fn update(&mut self, _ctx: &mut Context) -> GameResult {
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        self.draw(ctx)
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        key_input: KeyInput,
+        repeat: bool,
+    ) -> GameResult {
+        self.key_down(key_input, repeat);
+        if self.exiting {
+            ctx.request_quit();
+        }
+        Ok(())
+    }
+
+    fn gamepad_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        button: gilrs::Button,
+        _id: ggez::input::gamepad::GamepadId,
+    ) -> GameResult {
+        self.pad_down(button);
+        if self.exiting {
+            ctx.request_quit();
+        }
+        Ok(())
+    }
+}
+
+impl Drivable for ReplayBrowser {
+    fn key_down(&mut self, key_input: KeyInput, repeat: bool) {
+        let Some(keycode) = key_input.keycode else {
+            return;
+        };
+        // Up and Down scroll the list, Left and Right skip between foods
+        let role = match keycode {
+            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right => KeyRole::Navigate,
+            _ => KeyRole::Action,
+        };
+        if !self.key_repeat.accepts(role, repeat) {
+            return;
+        }
+        match &mut self.player {
+            Some(player) => match keycode {
+                KeyCode::Space => player.paused = !player.paused,
+                KeyCode::S => player.cycle_speed(),
+                KeyCode::Right => player.seek_next_food(),
+                KeyCode::Left => player.seek_previous_food(),
+                KeyCode::Escape => self.player = None,
+                _ => {}
+            },
+            None => {
+                if let Some(input) = UiInput::from_key(keycode) {
+                    self.list_input(input);
+                }
+            }
+        }
+    }
+
+    fn pad_down(&mut self, button: gilrs::Button) {
+        let Some(input) = UiInput::from_button(button) else {
+            return;
+        };
+        match &mut self.player {
+            Some(player) => match input {
+                UiInput::Accept => player.paused = !player.paused,
+                UiInput::Right => player.seek_next_food(),
+                UiInput::Left => player.seek_previous_food(),
+                UiInput::Cancel => self.player = None,
+                _ => {}
+            },
+            None => self.list_input(input),
+        }
+    }
+
+    fn advance(&mut self, frame: std::time::Duration) {
+        if let Some(player) = &mut self.player {
+            player.advance(frame.as_secs_f64());
+        }
+    }
+}
+
+impl EventHandler for ReplayBrowser {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        self.advance(ctx.time.delta());
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        self.draw(ctx)
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        key_input: KeyInput,
+        repeat: bool,
+    ) -> GameResult {
+        self.key_down(key_input, repeat);
+        if self.exiting {
+            ctx.request_quit();
+        }
+        Ok(())
+    }
+
+    fn gamepad_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        button: gilrs::Button,
+        _id: ggez::input::gamepad::GamepadId,
+    ) -> GameResult {
+        self.pad_down(button);
+        if self.exiting {
+            ctx.request_quit();
+        }
+        Ok(())
+    }
+
+    fn mouse_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        _button: ggez::input::mouse::MouseButton,
+        x: f32,
+        y: f32,
+    ) -> GameResult {
+        if let Some(player) = &mut self.player {
+            // clicks come in window pixels, and the replay's board is stretched over the window
+            if y >= timeline_bar_top(&self.config) {
+                player.scrub(x / self.config.screen_width());
+            }
+        }
+        Ok(())
+    }
+}
+
+// Your last run is saved here as a ghost, so you can race it or send it to a friend
+pub const GHOST_FILE: &str = "ghost.txt";
+
+// A recorded run, enough to play it back as a ghost: where the head was on every tick,
+// and the tick and time each piece of food was eaten (the splits)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GhostRun {
+    pub path: Vec<Position>,
+    pub food: Vec<(usize, f64)>,
+}
+
+impl GhostRun {
+    pub fn start(game: &GameState) -> Self {
+        Self {
+            path: vec![game.snake[0]],
+            food: Vec::new(),
+        }
+    }
+
+    // Call after each tick of the run being recorded
+    pub fn record_tick(&mut self, game: &GameState, ate: bool) {
+        self.path.push(game.snake[0]);
+        if ate {
+            self.food.push((self.path.len() - 1, game.elapsed));
+        }
+    }
+
+    // The ghost snake on `tick`, head first - the body follows the head's path, growing by
+    // one for each food eaten
+    pub fn body_at(&self, tick: usize) -> &[Position] {
+        if self.path.is_empty() {
+            return &[];
+        }
+        let tick = tick.min(self.path.len().saturating_sub(1));
+        let eaten = self.food.iter().filter(|(when, _)| *when <= tick).count();
+        let length = SnakeStart::default().length + eaten;
+        &self.path[(tick + 1).saturating_sub(length)..=tick]
+    }
+
+    // How far ahead (negative) or behind (positive) of the ghost eating food number `index`
+    // at `time` is, or None if the ghost never got that far
+    pub fn split(&self, index: usize, time: f64) -> Option<f64> {
+        self.food
+            .get(index)
+            .map(|(_, ghost_time)| time - ghost_time)
+    }
+
+    // Two lines: "path x,y x,y ..." and "food tick:time ..."
+    pub fn to_text(&self) -> String {
+        let path: Vec<String> = self.path.iter().map(Position::to_text).collect();
+        let food: Vec<String> = self
+            .food
+            .iter()
+            .map(|(tick, time)| format!("{}:{}", tick, time))
+            .collect();
+        format!("path {}\nfood {}\n", path.join(" "), food.join(" "))
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut ghost = Self::default();
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("path") => {
+                    for cell in words {
+                        ghost.path.push(Position::parse(cell)?);
+                    }
+                }
+                Some("food") => {
+                    for split in words {
+                        let (tick, time) = split.split_once(':')?;
+                        ghost.food.push((tick.parse().ok()?, time.parse().ok()?));
+                    }
+                }
+                _ => {}
+            }
+        }
+        (!ghost.path.is_empty()).then_some(ghost)
+    }
+
+    // Your own last run, from the game's storage
+    pub fn load(storage: &dyn StorageBackend) -> Option<Self> {
+        Self::parse(&storage.read(GHOST_FILE)?)
+    }
+
+    pub fn save(&self, storage: &dyn StorageBackend) {
+        if let Err(e) = storage.write(GHOST_FILE, &self.to_text()) {
+            eprintln!("Failed to save ghost: {}", e);
+        }
+    }
+
+    // A ghost someone sent you
+    pub fn load_file(path: &str) -> Option<Self> {
+        Self::parse(&std::fs::read_to_string(path).ok()?)
+    }
+}
+
+// Racing a ghost: the ghost snake is drawn see-through on the board, and each food shows
+// how far ahead or behind of the ghost you ate it
+pub struct GhostRace {
+    pub game: GameState,
+    pub ghost: GhostRun,
+    pub run: GhostRun, // this run, saved as the next ghost once it ends
+    pub last_split: Option<f64>,
+    pub saved: bool,
+}
+
+impl GhostRace {
+    pub fn new(ghost: GhostRun, config: GameConfig) -> Self {
+        let game = GameState::new_with_config(config);
+        let run = GhostRun::start(&game);
+        Self {
+            game,
+            ghost,
+            run,
+            last_split: None,
+            saved: false,
+        }
+    }
+
+    // Follow the game after it's updated, recording a tick whenever the snake moved
+    pub fn track(&mut self, head_before: Position, score_before: u32) {
+        if self.game.snake[0] != head_before {
+            let ate = self.game.score > score_before;
+            self.run.record_tick(&self.game, ate);
+            if ate {
+                self.last_split = self.ghost.split(self.run.food.len() - 1, self.game.elapsed);
+            }
+        }
+        if self.game.game_over && !self.saved {
+            self.run.save(self.game.storage.as_ref());
+            self.saved = true;
+        }
+    }
+}
+
+impl EventHandler for GhostRace {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        let head_before = self.game.snake[0];
+        let score_before = self.game.score;
+        self.game.update(ctx)?;
+        self.track(head_before, score_before);
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        self.draw(ctx)
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        key_input: KeyInput,
+        repeat: bool,
+    ) -> GameResult {
+        self.game.key_down_event(ctx, key_input, repeat)?;
+        // a restart starts the race over against the same ghost
+        if self.game.elapsed == 0.0 && self.run.path.len() > 1 {
+            self.run = GhostRun::start(&self.game);
+            self.last_split = None;
+            self.saved = false;
+        }
+        Ok(())
+    }
+}
+
+// The game as a piece of someone else's ggez app, like a launcher or a minigame
+// collection. The host forwards its events and decides where on screen the board goes
+pub trait SnakeGamePlugin {
+    fn start(&mut self); // skip the splash and start playing
+    fn pause(&mut self);
+    fn resume(&mut self);
+    fn is_paused(&self) -> bool;
+    fn reset(&mut self); // throw the current run away and start a new one
+    fn update(&mut self, ctx: &mut Context) -> GameResult;
+    // Draw the board scaled to fill `area` of the host's canvas
+    fn draw_in(&self, ctx: &mut Context, canvas: &mut graphics::Canvas, area: Rect) -> GameResult;
+    fn key_down(&mut self, ctx: &mut Context, key_input: KeyInput) -> GameResult;
+}
+
+pub struct EmbeddedSnake {
+    pub game: GameState,
+    pub paused: bool,
+}
+
+impl Default for EmbeddedSnake {
+    fn default() -> Self {
+        Self::new(GameState::new())
+    }
+}
+
+impl EmbeddedSnake {
+    pub fn new(game: GameState) -> Self {
+        Self {
+            game,
+            paused: false,
+        }
+    }
+}
+
+impl SnakeGamePlugin for EmbeddedSnake {
+    fn start(&mut self) {
+        self.game.change_screen(Screen::Playing);
+        self.paused = false;
+    }
+
+    fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn reset(&mut self) {
+        self.game.restart();
+        self.game.skip_transition();
+    }
+
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        // the tick clock only moves in update, so the snake picks up where it left off
+        if self.paused {
+            return Ok(());
+        }
+        self.game.update(ctx)
+    }
+
+    fn draw_in(&self, ctx: &mut Context, canvas: &mut graphics::Canvas, area: Rect) -> GameResult {
+        self.draw_in(ctx, canvas, area)
+    }
+
+    fn key_down(&mut self, ctx: &mut Context, key_input: KeyInput) -> GameResult {
+        if self.paused {
+            return Ok(());
+        }
+        self.game.key_down_event(ctx, key_input, false)
+    }
+}
+
+// Cells from every free cell to `goal`, walking around the snake. None where it can't
+// get to
+fn distances_to(game: &GameState, goal: Position) -> Vec<Option<u32>> {
+    let index = |cell: Position| (cell.y * game.config.grid_width + cell.x) as usize;
+    let mut distances = vec![None; game.config.cell_count()];
+    // the tail moves out of the way on the next tick, so it doesn't block
+    let body = &game.snake[..game.snake.len() - 1];
+    let mut queue = std::collections::VecDeque::from([goal]);
+    distances[index(goal)] = Some(0);
+    while let Some(cell) = queue.pop_front() {
+        let distance = distances[index(cell)].unwrap_or(0);
+        for direction in [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ] {
+            let next = game.config.step(cell, direction);
+            if game.config.contains(next)
+                && !body.contains(&next)
+                && !game.walls.contains(&next)
+                && distances[index(next)].is_none()
+            {
+                distances[index(next)] = Some(distance + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+    distances
+}
+
+// A simple autopilot for the screensaver: the shortest way to the food, or when the
+// food is walled off, whichever safe move leaves the most room
+pub fn bot_direction(game: &GameState) -> Direction {
+    let head = game.snake[0];
+    let safe: Vec<Direction> = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ]
+    .into_iter()
+    .filter(|direction| {
+        *direction != game.direction.opposite()
+            && !game.would_collide(game.config.step(head, *direction))
+    })
+    .collect();
+
+    let to_food = distances_to(game, game.food.position);
+    let index = |cell: Position| (cell.y * game.config.grid_width + cell.x) as usize;
+    let towards_food = safe
+        .iter()
+        .filter_map(|direction| {
+            let distance = to_food[index(game.config.step(head, *direction))]?;
+            Some((distance, *direction))
+        })
+        .min_by_key(|(distance, _)| *distance);
+    if let Some((_, direction)) = towards_food {
+        return direction;
+    }
+
+    safe.into_iter()
+        .max_by_key(|direction| {
+            let room = distances_to(game, game.config.step(head, *direction));
+            room.iter().filter(|distance| distance.is_some()).count()
+        })
+        .unwrap_or(game.direction) // boxed in - nothing left to do
+}
+
+// The biggest board that fits a screen of this size, in the middle
+pub fn fullscreen_board_area(config: &GameConfig, width: f32, height: f32) -> Rect {
+    let board = (config.screen_width(), config.screen_height());
+    let scale = (width / board.0).min(height / board.1);
+    Rect::new(
+        (width - board.0 * scale) / 2.0,
+        (height - board.1 * scale) / 2.0,
+        board.0 * scale,
+        board.1 * scale,
+    )
+}
+
+// How long a finished screensaver game stays up before the next one
+pub const SCREENSAVER_RESTART_DELAY: f64 = 2.0;
+// How far the mouse has to move to wake the screen, so a nudged desk doesn't
+pub const SCREENSAVER_WAKE_DISTANCE: f32 = 20.0;
+
+// Endless bot games, fullscreen with no HUD. Any key, click or real mouse movement quits
+pub struct Screensaver {
+    pub snake: EmbeddedSnake,
+    pub over_for: f64,
+    pub mouse_moved: f32,
+}
+
+impl Default for Screensaver {
+    fn default() -> Self {
+        let mut game = GameState::new();
+        game.show_hud = false;
+        game.reduced_motion = true;
+        // out of reach, so the bot's runs never replace the player's records
+        game.high_score = u32::MAX;
+        game.best_time = f64::MAX;
+        Self {
+            snake: EmbeddedSnake::new(game),
+            over_for: 0.0,
+            mouse_moved: 0.0,
+        }
+    }
+}
+
+impl EventHandler for Screensaver {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        let game = &mut self.snake.game;
+        if game.game_over {
+            self.over_for += ctx.time.delta().as_secs_f64();
+            if self.over_for >= SCREENSAVER_RESTART_DELAY {
+                self.snake.reset();
+                self.over_for = 0.0;
+            }
+            return Ok(());
+        }
+        let direction = bot_direction(game);
+        game.handle_input(direction);
+        self.snake.update(ctx)
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        self.draw(ctx)
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        _key_input: KeyInput,
+        _repeat: bool,
+    ) -> GameResult {
+        ctx.request_quit();
+        Ok(())
+    }
+
+    fn mouse_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        _button: MouseButton,
+        _x: f32,
+        _y: f32,
+    ) -> GameResult {
+        ctx.request_quit();
+        Ok(())
+    }
+
+    fn mouse_motion_event(
+        &mut self,
+        ctx: &mut Context,
+        _x: f32,
+        _y: f32,
+        dx: f32,
+        dy: f32,
+    ) -> GameResult {
+        self.mouse_moved += dx.abs() + dy.abs();
+        if self.mouse_moved > SCREENSAVER_WAKE_DISTANCE {
+            ctx.request_quit();
+        }
+        Ok(())
+    }
+
+    fn touch_event(
+        &mut self,
+        ctx: &mut Context,
+        _phase: TouchPhase,
+        _x: f64,
+        _y: f64,
+    ) -> GameResult {
+        ctx.request_quit();
+        Ok(())
+    }
+}
+
+pub const KIOSK_CONFIG_FILE: &str = "kiosk.txt";
+pub const KIOSK_COUNTERS_FILE: &str = "kiosk_counters.txt";
+// How long the game over screen stays up before the cabinet goes back to attract
+pub const KIOSK_GAME_OVER_DELAY: f64 = 4.0;
+
+// Cabinet settings: the keys the coin and start buttons send, the operator menu key
+// (pressed with Ctrl+Shift), how many coins buy a credit, and free play
+#[derive(Debug, Clone, PartialEq)]
+pub struct KioskConfig {
+    pub coin_key: KeyCode,
+    pub start_key: KeyCode,
+    pub operator_key: KeyCode,
+    pub coins_per_credit: u32,
+    pub free_play: bool,
+}
+
+impl Default for KioskConfig {
+    fn default() -> Self {
+        // the usual MAME mapping: 5 drops a coin and 1 is player one start
+        Self {
+            coin_key: KeyCode::Key5,
+            start_key: KeyCode::Key1,
+            operator_key: KeyCode::F2,
+            coins_per_credit: 1,
+            free_play: false,
+        }
+    }
+}
+
+impl KioskConfig {
+    // Same `key = value` lines as a theme file, e.g. `coin = 5` or `free_play = true`
+    pub fn parse(text: &str) -> Result<KioskConfig, String> {
+        let mut config = KioskConfig::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = match line.find(" # ") {
+                Some(comment) => &line[..comment],
+                None if line.trim_start().starts_with('#') => "",
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fail = |message: String| format!("line {}: {}", number + 1, message);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| fail(format!("expected `key = value`, got `{}`", line)))?;
+            let (key, value) = (key.trim(), value.trim());
+            let key_code =
+                || parse_key_name(value).ok_or_else(|| fail(format!("unknown key `{}`", value)));
+            match key {
+                "coin" => config.coin_key = key_code()?,
+                "start" => config.start_key = key_code()?,
+                "operator" => config.operator_key = key_code()?,
+                "coins_per_credit" => {
+                    config.coins_per_credit = value
+                        .parse()
+                        .ok()
+                        .filter(|coins| *coins > 0)
+                        .ok_or_else(|| fail(format!("bad coins_per_credit `{}`", value)))?
+                }
+                "free_play" => {
+                    config.free_play = value.parse().map_err(|_| {
+                        fail(format!(
+                            "free_play should be true or false, got `{}`",
+                            value
+                        ))
+                    })?
+                }
+                _ => return Err(fail(format!("unknown key `{}`", key))),
+            }
+        }
+        if config.coin_key == config.start_key {
+            return Err("coin and start can't be the same key".to_string());
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &str) -> Result<KioskConfig, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        KioskConfig::parse(&text)
+    }
+}
+
+// Key names for config files: letters, digits, F1 to F12 and a few named keys
+pub fn parse_key_name(name: &str) -> Option<KeyCode> {
+    use KeyCode::*;
+    const LETTERS: [KeyCode; 26] = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    const DIGITS: [KeyCode; 10] = [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    const FUNCTION_KEYS: [KeyCode; 12] = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+
+    let name = name.to_ascii_uppercase();
+    if let [single] = name.as_bytes() {
+        return match single {
+            b'A'..=b'Z' => Some(LETTERS[(single - b'A') as usize]),
+            b'0'..=b'9' => Some(DIGITS[(single - b'0') as usize]),
+            _ => None,
+        };
+    }
+    if let Some(number) = name.strip_prefix('F').and_then(|n| n.parse::<usize>().ok()) {
+        return FUNCTION_KEYS.get(number.checked_sub(1)?).copied();
+    }
+    match name.as_str() {
+        "SPACE" => Some(Space),
+        "ENTER" | "RETURN" => Some(Return),
+        "TAB" => Some(Tab),
+        "BACKSPACE" => Some(Back),
+        "INSERT" => Some(Insert),
+        "DELETE" => Some(Delete),
+        "HOME" => Some(Home),
+        "END" => Some(End),
+        "PAGEUP" => Some(PageUp),
+        "PAGEDOWN" => Some(PageDown),
+        _ => None,
+    }
+}
+
+// The operator's bookkeeping, saved as "coins plays"
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct KioskCounters {
+    pub coins: u64,
+    pub plays: u64,
+}
+
+impl KioskCounters {
+    pub fn parse(text: &str) -> Self {
+        let mut numbers = text.split_whitespace().map(|number| number.parse().ok());
+        match (numbers.next().flatten(), numbers.next().flatten()) {
+            (Some(coins), Some(plays)) => Self { coins, plays },
+            _ => Self::default(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        format!("{} {}", self.coins, self.plays)
+    }
+}
+
+// What the rows of the operator menu do
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OperatorAction {
+    ToggleFreePlay,
+    AddCredit,
+    ClearCounters,
+    Exit,
+    Close,
+}
+
+pub const OPERATOR_MENU: [OperatorAction; 5] = [
+    OperatorAction::ToggleFreePlay,
+    OperatorAction::AddCredit,
+    OperatorAction::ClearCounters,
+    OperatorAction::Exit,
+    OperatorAction::Close,
+];
+
+// An arcade cabinet: the bot plays an attract loop until someone pays (or free play
+// is on), then each credit buys one run. Nothing a player can press quits the game -
+// only the operator menu can
+pub struct Kiosk {
+    pub config: KioskConfig,
+    pub credits: u32,
+    pub coins_toward_credit: u32,
+    pub counters: KioskCounters,
+    pub counters_path: Option<String>, // where counters are saved, if anywhere
+    pub attract: Screensaver,
+    pub player: Option<EmbeddedSnake>, // the paid-for run, while there is one
+    pub over_for: f64,
+    pub operator_menu: Option<usize>, // the selected row while the menu is open
+    pub confirm: Option<ConfirmDialog<Option<OperatorAction>>>, // over the operator menu
+    pub exiting: bool,
+    pub key_repeat: KeyRepeat, // for the operator menu - players steer with their run's
+}
+
+impl Kiosk {
+    pub fn new(config: KioskConfig) -> Self {
+        Self {
+            config,
+            credits: 0,
+            coins_toward_credit: 0,
+            counters: KioskCounters::default(),
+            counters_path: None,
+            attract: Screensaver::default(),
+            player: None,
+            over_for: 0.0,
+            operator_menu: None,
+            confirm: None,
+            exiting: false,
+            key_repeat: KeyRepeat::default(),
+        }
+    }
+
+    pub fn insert_coin(&mut self) {
+        self.counters.coins += 1;
+        self.coins_toward_credit += 1;
+        if self.coins_toward_credit >= self.config.coins_per_credit {
+            self.coins_toward_credit = 0;
+            self.credits += 1;
+        }
+        self.save_counters();
+    }
+
+    pub fn can_start(&self) -> bool {
+        self.player.is_none() && (self.config.free_play || self.credits > 0)
+    }
+
+    // Spend a credit on a run, if there's one to spend
+    pub fn press_start(&mut self) -> bool {
+        if !self.can_start() {
+            return false;
+        }
+        if !self.config.free_play {
+            self.credits -= 1;
+        }
+        self.counters.plays += 1;
+        self.save_counters();
+        self.player = Some(EmbeddedSnake::default());
+        self.over_for = 0.0;
+        true
+    }
+
+    pub fn open_operator_menu(&mut self) {
+        self.operator_menu = Some(0);
+        if let Some(player) = &mut self.player {
+            player.pause();
+        }
+    }
+
+    pub fn operator(&mut self, action: OperatorAction) {
+        match action {
+            OperatorAction::ToggleFreePlay => self.config.free_play = !self.config.free_play,
+            OperatorAction::AddCredit => self.credits += 1,
+            OperatorAction::ClearCounters => {
+                self.counters = KioskCounters::default();
+                self.save_counters();
+            }
+            OperatorAction::Exit => self.exiting = true,
+            OperatorAction::Close => {
+                self.operator_menu = None;
+                if let Some(player) = &mut self.player {
+                    player.resume();
+                }
+            }
+        }
+    }
+
+    // Move through the operator menu, or run the selected row - asking first for the
+    // rows that can't be undone
+    pub fn operator_input(&mut self, selected: usize, input: UiInput) {
+        if let Some(dialog) = &mut self.confirm {
+            if let Some(answer) = dialog.input(input) {
+                self.confirm = None;
+                if let Some(action) = answer {
+                    self.operator(action);
+                }
+            }
+            return;
+        }
+        let mut panel = self.operator_panel(selected);
+        let first = panel.focus.unwrap_or(0) - selected;
+        match panel.input(input) {
+            Some(UiEvent::Pressed(index)) => match OPERATOR_MENU[index - first] {
+                action @ (OperatorAction::ClearCounters | OperatorAction::Exit) => {
+                    let message = if action == OperatorAction::Exit {
+                        "Exit to the desktop?"
+                    } else {
+                        "Clear the coin and play counters?"
+                    };
+                    self.confirm = Some(ConfirmDialog::new(
+                        message,
+                        vec![
+                            (self.operator_label(action), Some(action)),
+                            ("Cancel".to_string(), None),
+                        ],
+                        None,
+                    ));
+                }
+                action => self.operator(action),
+            },
+            Some(UiEvent::Cancelled) => self.operator(OperatorAction::Close),
+            _ => self.operator_menu = panel.focus.map(|focus| focus - first),
+        }
+    }
+
+    fn operator_label(&self, action: OperatorAction) -> String {
+        match action {
+            OperatorAction::ToggleFreePlay => format!(
+                "Free play: {}",
+                if self.config.free_play { "on" } else { "off" }
+            ),
+            OperatorAction::AddCredit => "Add credit".to_string(),
+            OperatorAction::ClearCounters => "Clear counters".to_string(),
+            OperatorAction::Exit => "Exit to desktop".to_string(),
+            OperatorAction::Close => "Close".to_string(),
+        }
+    }
+
+    fn save_counters(&self) {
+        let Some(path) = &self.counters_path else {
+            return;
+        };
+        if let Err(e) = std::fs::write(path, self.counters.to_text()) {
+            eprintln!("Failed to save kiosk counters: {}", e);
+        }
+    }
+
+    // The line along the bottom of the attract loop
+    fn attract_message(&self) -> String {
+        if self.config.free_play {
+            "FREE PLAY - PRESS START".to_string()
+        } else if self.credits > 0 {
+            format!("CREDITS {} - PRESS START", self.credits)
+        } else {
+            "INSERT COIN".to_string()
+        }
+    }
+
+    // Counters, then a button per row of OPERATOR_MENU with the selected one focused
+    pub fn operator_panel(&self, selected: usize) -> Panel {
+        let mut panel = Panel {
+            row_size: 28.0,
+            ..Panel::default()
+        };
+        panel.push(Widget::label("OPERATOR", 28.0, Color::WHITE));
+        panel.push(Widget::label(
+            format!(
+                "Coins {}   Plays {}   Credits {}",
+                self.counters.coins, self.counters.plays, self.credits
+            ),
+            28.0,
+            Color::WHITE,
+        ));
+        let first = panel.widgets.len();
+        for action in OPERATOR_MENU {
+            panel.push(Widget::button(self.operator_label(action)));
+        }
+        panel.focus = Some(first + selected);
+        panel
+    }
+}
+
+impl EventHandler for Kiosk {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        if self.operator_menu.is_some() {
+            return Ok(());
+        }
+        let Some(player) = &mut self.player else {
+            return self.attract.update(ctx);
+        };
+        player.update(ctx)?;
+        if player.game.game_over {
+            self.over_for += ctx.time.delta().as_secs_f64();
+            if self.over_for >= KIOSK_GAME_OVER_DELAY {
+                self.player = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        self.draw(ctx)
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        key_input: KeyInput,
+        repeat: bool,
+    ) -> GameResult {
+        let Some(key) = key_input.keycode else {
+            return Ok(());
+        };
+        if let Some(selected) = self.operator_menu {
+            let Some(input) = UiInput::from_key(key) else {
+                return Ok(());
+            };
+            if !self.key_repeat.accepts(input.role(), repeat) {
+                return Ok(());
+            }
+            self.operator_input(selected, input);
+            if self.exiting {
+                ctx.request_quit();
+            }
+            return Ok(());
+        }
+
+        // a held coin or start button is one press
+        if repeat && (key == self.config.coin_key || key == self.config.start_key) {
+            return Ok(());
+        }
+        if key == self.config.operator_key
+            && key_input.mods.contains(KeyMods::CTRL | KeyMods::SHIFT)
+        {
+            self.open_operator_menu();
+        } else if key == self.config.coin_key {
+            self.insert_coin();
+        } else if key == self.config.start_key {
+            self.press_start();
+        } else if let Some(player) = &mut self.player {
+            // steering only - restart, party mode and the rest stay off on a cabinet
+            let direction = match key {
+                KeyCode::Up | KeyCode::W => Direction::Up,
+                KeyCode::Down | KeyCode::S => Direction::Down,
+                KeyCode::Left | KeyCode::A => Direction::Left,
+                KeyCode::Right | KeyCode::D => Direction::Right,
+                _ => return Ok(()),
+            };
+            let held = !player.game.key_repeat.accepts(KeyRole::Steer, repeat);
+            if !player.game.game_over && !player.is_paused() && !held {
+                player.game.handle_input(direction);
+            }
+        }
+        Ok(())
+    }
+
+    // A pad only works the operator menu - players steer with the cabinet's controls
+    fn gamepad_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        button: gilrs::Button,
+        _id: ggez::input::gamepad::GamepadId,
+    ) -> GameResult {
+        if let (Some(selected), Some(input)) = (self.operator_menu, UiInput::from_button(button)) {
+            self.operator_input(selected, input);
+            if self.exiting {
+                ctx.request_quit();
+            }
+        }
+        Ok(())
+    }
+
+    // Alt+F4 and the window's close button do nothing unless the operator asked to exit
+    fn quit_event(&mut self, _ctx: &mut Context) -> Result<bool, ggez::GameError> {
+        Ok(!self.exiting)
+    }
+}
+
+// How launch settings are layered, each overriding the last: the defaults, then the
+// SNAKE_* environment variables, then command line flags
+//   SNAKE_SEED=42         --seed 42
+//   SNAKE_GRID=20x15      --grid 20x15
+//   SNAKE_CELL_SIZE=30    --cell-size 30
+//   SNAKE_HEADLESS=1      --headless
+//   SNAKE_DATA_DIR=saves  --data-dir saves
+//   SNAKE_OUTPUT=json     --output json
+//   SNAKE_PER_TICK=1      --per-tick
+//   SNAKE_SERVER=host:80  --server host:80
+//   SNAKE_MIXED_FOOD=1    --mixed-food
+//   SNAKE_RNG=pcg         --rng pcg
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LaunchOptions {
+    pub seed: Option<u64>,
+    pub grid: Option<(i32, i32)>,
+    pub cell_size: Option<f32>,
+    pub headless: bool,
+    pub data_dir: Option<std::path::PathBuf>,
+    pub output: OutputFormat,
+    pub per_tick: bool,
+    pub server: Option<String>,
+    pub mixed_food: bool,
+    pub rng: RngKind,
+}
+
+impl LaunchOptions {
+    // Defaults with the environment and then `args` laid over them
+    pub fn load(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        options.apply_env(std::env::vars())?;
+        options.apply_args(args)?;
+        Ok(options)
+    }
+
+    // Picks out the SNAKE_* variables and ignores everything else. Empty ones count
+    // as unset, so `SNAKE_SEED= snake` doesn't fail
+    pub fn apply_env(
+        &mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<(), String> {
+        for (name, value) in vars {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            let fail = |message: String| format!("{}: {}", name, message);
+            match name.as_str() {
+                "SNAKE_SEED" => self.seed = Some(parse_seed(value).map_err(fail)?),
+                "SNAKE_GRID" => self.grid = Some(parse_grid(value).map_err(fail)?),
+                "SNAKE_CELL_SIZE" => self.cell_size = Some(parse_cell_size(value).map_err(fail)?),
+                "SNAKE_HEADLESS" => self.headless = parse_switch(value).map_err(fail)?,
+                "SNAKE_DATA_DIR" => self.data_dir = Some(value.into()),
+                "SNAKE_OUTPUT" => self.output = OutputFormat::parse(value).map_err(fail)?,
+                "SNAKE_PER_TICK" => self.per_tick = parse_switch(value).map_err(fail)?,
+                "SNAKE_SERVER" => self.server = Some(value.to_string()),
+                "SNAKE_MIXED_FOOD" => self.mixed_food = parse_switch(value).map_err(fail)?,
+                "SNAKE_RNG" => self.rng = RngKind::parse(value).map_err(fail)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Flags the game doesn't know about are left for main to deal with
+    pub fn apply_args(&mut self, args: &[String]) -> Result<(), String> {
+        let value_after = |flag| flag_value(args, flag);
+        let fail = |flag: &'static str| move |message| format!("{}: {}", flag, message);
+        if let Some(seed) = value_after("--seed")? {
+            self.seed = Some(parse_seed(seed).map_err(fail("--seed"))?);
+        }
+        if let Some(grid) = value_after("--grid")? {
+            self.grid = Some(parse_grid(grid).map_err(fail("--grid"))?);
+        }
+        if let Some(size) = value_after("--cell-size")? {
+            self.cell_size = Some(parse_cell_size(size).map_err(fail("--cell-size"))?);
+        }
+        if args.iter().any(|arg| arg == "--headless") {
+            self.headless = true;
+        }
+        if let Some(dir) = value_after("--data-dir")? {
+            self.data_dir = Some(dir.into());
+        }
+        if let Some(output) = value_after("--output")? {
+            self.output = OutputFormat::parse(output).map_err(fail("--output"))?;
+        }
+        if args.iter().any(|arg| arg == "--per-tick") {
+            self.per_tick = true;
+        }
+        if let Some(server) = value_after("--server")? {
+            self.server = Some(server.to_string());
+        }
+        if args.iter().any(|arg| arg == "--mixed-food") {
+            self.mixed_food = true;
+        }
+        if let Some(rng) = value_after("--rng")? {
+            self.rng = RngKind::parse(rng).map_err(fail("--rng"))?;
+        }
+        Ok(())
+    }
+
+    // A new game with everything these options set
+    pub fn new_game(&self) -> GameState {
+        self.game_on(self.game_config())
+    }
+
+    // The same with the player's settings for anything these options leave out
+    pub fn new_game_with(&self, settings: &Settings) -> GameState {
+        let mut game = self.game_on(self.game_config_with(settings));
+        game.apply_settings(*settings);
+        game
+    }
+
+    fn game_on(&self, config: GameConfig) -> GameState {
+        let mut game = GameState::new_with_config(config);
+        game.set_rng(self.rng);
+        if let Some(seed) = self.seed {
+            game.reseed(seed);
+        }
+        if self.mixed_food {
+            game.set_food_mix(FoodMix::mixed());
+        }
+        game
+    }
+
+    // The board to play on - the defaults for anything not given
+    pub fn game_config(&self) -> GameConfig {
+        self.game_config_with(&Settings::default())
+    }
+
+    // The same, going by the player's settings for anything not given
+    pub fn game_config_with(&self, settings: &Settings) -> GameConfig {
+        let (grid_width, grid_height) = self.grid.unwrap_or(settings.grid);
+        GameConfig {
+            grid_width,
+            grid_height,
+            cell_size: self.cell_size.unwrap_or(CELL_SIZE),
+            walls: settings.walls,
+            speed: settings.speed,
+        }
+    }
+}
+
+// The argument after `flag`, if the flag was given
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a str>, String> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) => args
+            .get(index + 1)
+            .map(|value| Some(value.as_str()))
+            .ok_or_else(|| format!("{} needs a value", flag)),
+        None => Ok(None),
+    }
+}
+
+fn parse_seed(value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("`{}` isn't a whole number", value))
+}
+
+// "20x15", within the sizes GameConfig allows
+fn parse_grid(value: &str) -> Result<(i32, i32), String> {
+    let (width, height) = value
+        .split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .ok_or_else(|| format!("expected a size like 20x15, got `{}`", value))?;
+    GameConfig::new(width, height, CELL_SIZE)?;
+    Ok((width, height))
+}
+
+// Pixels to a cell, e.g. "24"
+fn parse_cell_size(value: &str) -> Result<f32, String> {
+    let size = value
+        .parse()
+        .map_err(|_| format!("`{}` isn't a number", value))?;
+    GameConfig::new(GRID_WIDTH, GRID_HEIGHT, size)?;
+    Ok(size)
+}
+
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("expected 1 or 0, got `{}`", value)),
+    }
+}
+
+// Ticks a headless game can run before it's called off, in case the bot circles forever
+pub const HEADLESS_TICK_LIMIT: u64 = 100_000;
+
+// Play one game with the bot and no window, as fast as it'll go, calling `on_tick` after
+// every tick. Returns the ticks played
+pub fn play_headless(game: &mut GameState, mut on_tick: impl FnMut(u64, &GameState)) -> u64 {
+    let mut ticks = 0;
+    while !game.game_over && ticks < HEADLESS_TICK_LIMIT {
+        let direction = bot_direction(game);
+        game.handle_input(direction);
+        game.step();
+        game.elapsed += game.speed.seconds_per_cell(); // game time, not however long the tick took
+        ticks += 1;
+        on_tick(ticks, game);
+    }
+    ticks
+}
+
+// Ways of keeping the snake's body, for the `bench` subcommand to compare. The game
+// itself keeps a Vec, head first
+pub trait SnakeBody {
+    fn name(&self) -> &'static str;
+    fn reset(&mut self, cells: &[Position], config: &GameConfig);
+    // Whether moving onto `cell` would hit the body - the tail's about to move on, so
+    // it doesn't count
+    fn blocks(&self, cell: Position) -> bool;
+    fn advance(&mut self, head: Position, grow: bool);
+}
+
+// What the game does: a linear scan, and every move shifts the whole body along
+#[derive(Default)]
+pub struct VecBody {
+    cells: Vec<Position>,
+}
+
+impl SnakeBody for VecBody {
+    fn name(&self) -> &'static str {
+        "Vec"
+    }
+
+    fn reset(&mut self, cells: &[Position], _config: &GameConfig) {
+        self.cells = cells.to_vec();
+    }
+
+    fn blocks(&self, cell: Position) -> bool {
+        self.cells[..self.cells.len() - 1].contains(&cell)
+    }
+
+    fn advance(&mut self, head: Position, grow: bool) {
+        self.cells.insert(0, head);
+        if !grow {
+            self.cells.pop();
+        }
+    }
+}
+
+// A ring buffer, so moves don't shift anything, and a set of the cells for lookups
+#[derive(Default)]
+pub struct DequeBody {
+    cells: std::collections::VecDeque<Position>,
+    occupied: std::collections::HashSet<(i32, i32)>,
+}
+
+impl SnakeBody for DequeBody {
+    fn name(&self) -> &'static str {
+        "VecDeque+HashSet"
+    }
+
+    fn reset(&mut self, cells: &[Position], _config: &GameConfig) {
+        self.cells = cells.iter().copied().collect();
+        self.occupied = cells.iter().map(|cell| (cell.x, cell.y)).collect();
+    }
+
+    fn blocks(&self, cell: Position) -> bool {
+        self.cells.back() != Some(&cell) && self.occupied.contains(&(cell.x, cell.y))
+    }
+
+    fn advance(&mut self, head: Position, grow: bool) {
+        if !grow {
+            if let Some(tail) = self.cells.pop_back() {
+                self.occupied.remove(&(tail.x, tail.y));
+            }
+        }
+        self.cells.push_front(head);
+        self.occupied.insert((head.x, head.y));
+    }
+}
+
+// A ring buffer and a bit per board cell
+#[derive(Default)]
+pub struct BitboardBody {
+    cells: std::collections::VecDeque<Position>,
+    bits: Vec<u64>,
+    width: i32,
+}
+
+impl BitboardBody {
+    fn bit(&self, cell: Position) -> (usize, u64) {
+        let index = (cell.y * self.width + cell.x) as usize;
+        (index / 64, 1 << (index % 64))
+    }
+
+    fn set(&mut self, cell: Position, on: bool) {
+        let (word, mask) = self.bit(cell);
+        if on {
+            self.bits[word] |= mask;
+        } else {
+            self.bits[word] &= !mask;
+        }
+    }
+}
+
+impl SnakeBody for BitboardBody {
+    fn name(&self) -> &'static str {
+        "bitboard"
+    }
+
+    fn reset(&mut self, cells: &[Position], config: &GameConfig) {
+        self.width = config.grid_width;
+        self.bits = vec![0; config.cell_count().div_ceil(64)];
+        self.cells = cells.iter().copied().collect();
+        for cell in cells {
+            self.set(*cell, true);
+        }
+    }
+
+    fn blocks(&self, cell: Position) -> bool {
+        if cell.x < 0 || cell.y < 0 || cell.x >= self.width || self.cells.back() == Some(&cell) {
+            return false;
+        }
+        let (word, mask) = self.bit(cell);
+        self.bits.get(word).is_some_and(|bits| bits & mask != 0)
+    }
+
+    fn advance(&mut self, head: Position, grow: bool) {
+        if !grow {
+            if let Some(tail) = self.cells.pop_back() {
+                self.set(tail, false);
+            }
+        }
+        self.cells.push_front(head);
+        self.set(head, true);
+    }
+}
+
+// One of the bot's games as the body sees it: where it started, and each move's new
+// head and whether it ate
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchGame {
+    pub start: Vec<Position>,
+    pub moves: Vec<(Position, bool)>,
+}
+
+// The bot's games from fixed seeds, so every backend gets exactly the same work. Each
+// move looks at all four cells around the head first, like a bot choosing a way to go
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchWorkload {
+    pub config: GameConfig,
+    pub games: Vec<BenchGame>,
+    pub blocked: usize, // how many of the lookups hit the body, to check the backends by
+}
+
+impl BenchWorkload {
+    pub fn record(config: GameConfig, seed: u64, games: usize) -> Self {
+        let games: Vec<BenchGame> = (0..games as u64)
+            .map(|game_number| {
+                let mut game = GameState::new_with_config(config);
+                game.reseed(seed.wrapping_add(game_number));
+                // out of reach, so the bench never replaces the player's records
+                game.high_score = u32::MAX;
+                game.best_time = f64::MAX;
+                let start = game.snake.clone();
+                let mut length = start.len();
+                let mut moves = Vec::new();
+                play_headless(&mut game, |_, game| {
+                    if !game.game_over {
+                        moves.push((game.snake[0], game.snake.len() > length));
+                        length = game.snake.len();
+                    }
+                });
+                BenchGame { start, moves }
+            })
+            .collect();
+        let mut workload = Self {
+            config,
+            games,
+            blocked: 0,
+        };
+        workload.blocked = workload.play(&mut VecBody::default());
+        workload
+    }
+
+    // Put `body` through every game, returning how many lookups hit it
+    pub fn play(&self, body: &mut dyn SnakeBody) -> usize {
+        let mut blocked = 0;
+        for game in &self.games {
+            body.reset(&game.start, &self.config);
+            let mut head = game.start[0];
+            for &(next, grow) in &game.moves {
+                for direction in [
+                    Direction::Up,
+                    Direction::Down,
+                    Direction::Left,
+                    Direction::Right,
+                ] {
+                    blocked += usize::from(body.blocks(head.move_in_direction(direction)));
+                }
+                body.advance(next, grow);
+                head = next;
+            }
+        }
+        blocked
+    }
+
+    pub fn move_count(&self) -> usize {
+        self.games.iter().map(|game| game.moves.len()).sum()
+    }
+}
+
+// How one backend did on the workload
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub name: &'static str,
+    pub nanos_per_move: f64,
+    pub correct: bool, // came to the same lookups as the Vec
+}
+
+// Time each backend over `rounds` runs of the workload, keeping its best round so a
+// busy moment on the machine doesn't count against it
+pub fn bench_bodies(workload: &BenchWorkload, rounds: usize) -> Vec<BenchResult> {
+    let mut bodies: Vec<Box<dyn SnakeBody>> = vec![
+        Box::new(VecBody::default()),
+        Box::new(DequeBody::default()),
+        Box::new(BitboardBody::default()),
+    ];
+    let moves = workload.move_count().max(1) as f64;
+    bodies
+        .iter_mut()
+        .map(|body| {
+            let mut best = f64::MAX;
+            let mut correct = true;
+            for _ in 0..rounds.max(1) {
+                let started = std::time::Instant::now();
+                let blocked = workload.play(body.as_mut());
+                best = best.min(started.elapsed().as_nanos() as f64);
+                correct &= blocked == workload.blocked;
+            }
+            BenchResult {
+                name: body.name(),
+                nanos_per_move: best / moves,
+                correct,
+            }
+        })
+        .collect()
+}
+
+// The results as a table, each against the Vec the game uses
+pub fn bench_table(workload: &BenchWorkload, results: &[BenchResult]) -> String {
+    let baseline = results
+        .iter()
+        .find(|result| result.name == "Vec")
+        .map_or(1.0, |result| result.nanos_per_move);
+    let mut table = format!(
+        "{} games, {} moves on a {}x{} board\n{:<18} {:>10} {:>8}\n",
+        workload.games.len(),
+        workload.move_count(),
+        workload.config.grid_width,
+        workload.config.grid_height,
+        "backend",
+        "ns/move",
+        "vs Vec"
+    );
+    for result in results {
+        let speedup = baseline / result.nanos_per_move.max(f64::MIN_POSITIVE);
+        table += &format!(
+            "{:<18} {:>10.1} {:>7.2}x{}\n",
+            result.name,
+            result.nanos_per_move,
+            speedup,
+            if result.correct { "" } else { "  WRONG" }
+        );
+    }
+    table
+}
+
+// Bumped whenever a field is renamed, removed or changes meaning - new fields don't
+pub const HEADLESS_SCHEMA_VERSION: u32 = 1;
+
+// How a headless run reports: a line for people, or JSON Lines or CSV for tools.
+// The schema is in the README under Scripts and CI
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!("expected text, json or csv, got `{}`", value)),
+        }
+    }
+
+    // The CSV header, written once before anything else
+    pub fn header(&self) -> Option<&'static str> {
+        match self {
+            Self::Csv => {
+                Some("type,tick,score,length,head_x,head_y,food_x,food_y,direction,seconds,outcome")
+            }
+            _ => None,
+        }
+    }
+
+    // One line for the state after tick `tick`
+    pub fn tick_line(&self, tick: u64, game: &GameState) -> String {
+        let (head, food) = (game.snake[0], game.food.position);
+        let direction = direction_letter(game.direction);
+        match self {
+            Self::Text => format!(
+                "tick {}: score {}, length {}, head {}, food {}, heading {}",
+                tick,
+                game.score,
+                game.snake.len(),
+                head.to_text(),
+                food.to_text(),
+                direction
+            ),
+            Self::Json => format!(
+                "{{\"type\":\"tick\",\"tick\":{},\"score\":{},\"length\":{},\"head\":[{},{}],\"food\":[{},{}],\"direction\":\"{}\"}}",
+                tick,
+                game.score,
+                game.snake.len(),
+                head.x,
+                head.y,
+                food.x,
+                food.y,
+                direction
+            ),
+            Self::Csv => format!(
+                "tick,{},{},{},{},{},{},{},{},,",
+                tick,
+                game.score,
+                game.snake.len(),
+                head.x,
+                head.y,
+                food.x,
+                food.y,
+                direction
+            ),
+        }
+    }
+
+    // The line for the finished game. The outcome is `crashed`, or `tick_limit` if the
+    // game was called off
+    pub fn summary_line(&self, ticks: u64, game: &GameState) -> String {
+        let outcome = if game.game_over {
+            "crashed"
+        } else {
+            "tick_limit"
+        };
+        let (head, food) = (game.snake[0], game.food.position);
+        match self {
+            Self::Text => format!(
+                "score {}, length {}, {} ticks, {}{}",
+                game.score,
+                game.snake.len(),
+                ticks,
+                format_time(game.elapsed),
+                if game.game_over { "" } else { " (stopped)" }
+            ),
+            Self::Json => format!(
+                "{{\"type\":\"game\",\"schema\":{},\"score\":{},\"length\":{},\"ticks\":{},\"seconds\":{:.2},\"outcome\":\"{}\"}}",
+                HEADLESS_SCHEMA_VERSION,
+                game.score,
+                game.snake.len(),
+                ticks,
+                game.elapsed,
+                outcome
+            ),
+            Self::Csv => format!(
+                "game,{},{},{},{},{},{},{},{},{:.2},{}",
+                ticks,
+                game.score,
+                game.snake.len(),
+                head.x,
+                head.y,
+                food.x,
+                food.y,
+                direction_letter(game.direction),
+                game.elapsed,
+                outcome
+            ),
+        }
+    }
+}
@@ -0,0 +1,1122 @@
+//! Online play. The host runs the match and streams it to the other player a sync frame
+//! per board per step, over a connection that carries one line per message; the other
+//! player only sends their turns back. A player who drops has RECONNECT_WINDOW to come
+//! back, with the match paused until they do, and picks up again from a keyframe.
+
+use super::*;
+
+// Where a host listens unless told otherwise
+pub const NET_PORT: u16 = 7879;
+// The host's fixed step in seconds - the boards move, and turns land, on these
+pub const NET_STEP: f64 = 1.0 / 60.0;
+// Longest line a peer may send, so a broken one can't grow our buffer forever. A keyframe
+// of a snake filling the board is well under this
+pub const MAX_LINE_LENGTH: usize = 64 * 1024;
+// How often a client that lost the host tries to get back in, and how long each try waits
+pub const REJOIN_INTERVAL: f64 = 1.0;
+pub const REJOIN_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(250);
+// How long a client keeps trying to get back to the host before taking the match over
+pub const HOST_TAKEOVER_WAIT: f64 = 5.0;
+// How often each end pings the other to measure the round trip
+pub const PING_INTERVAL: f64 = 1.0;
+
+// A connection to another peer, a line at a time. Neither end ever blocks: a line that
+// hasn't fully arrived yet is Ok(None), and a closed connection is an error
+pub trait NetLink: Send {
+    fn send(&mut self, line: &str) -> std::io::Result<()>;
+    fn recv(&mut self) -> std::io::Result<Option<String>>;
+}
+
+// A NetLink over TCP
+pub struct TcpLink {
+    stream: std::net::TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>, // what the socket wasn't ready to take yet
+}
+
+impl TcpLink {
+    pub fn new(stream: std::net::TcpStream) -> std::io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        })
+    }
+
+    // Write out as much as the socket will take right now
+    fn flush(&mut self) -> std::io::Result<()> {
+        use std::io::{ErrorKind, Write};
+
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl NetLink for TcpLink {
+    fn send(&mut self, line: &str) -> std::io::Result<()> {
+        self.outgoing.extend_from_slice(line.as_bytes());
+        self.outgoing.push(b'\n');
+        self.flush()
+    }
+
+    fn recv(&mut self) -> std::io::Result<Option<String>> {
+        use std::io::{ErrorKind, Read};
+
+        self.flush()?;
+        loop {
+            if let Some(end) = self.incoming.iter().position(|&byte| byte == b'\n') {
+                let line: Vec<u8> = self.incoming.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line[..end]);
+                return Ok(Some(line.trim_end_matches('\r').to_string()));
+            }
+            if self.incoming.len() > MAX_LINE_LENGTH {
+                return Err(ErrorKind::InvalidData.into());
+            }
+            let mut buffer = [0u8; 4096];
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(read) => self.incoming.extend_from_slice(&buffer[..read]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+// Everything peers say to each other after the handshake, one line each
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetMessage {
+    // "hello <player|spectator> <name>", from whoever's joining
+    Hello { role: NetRole, name: String },
+    // "welcome <player>", the host's answer: which player they are, or "-" for a spectator
+    Welcome { player: Option<usize> },
+    // "players <name> <name>", both players' names whenever someone joins
+    Players([String; 2]),
+    // "refused <why>", just before the host hangs up
+    Refused(String),
+    // "turn <U|D|L|R>", a turn the other player pressed
+    Turn(Direction),
+    // "sync <board> <frame>", see SyncFrame
+    Sync { board: usize, frame: String },
+    // "keyframe <board>", from a client that lost track of a board
+    NeedKeyframe { board: usize },
+    // "paused <name>", waiting on that player to reconnect
+    Paused { name: String },
+    // "resumed"
+    Resumed,
+    // "chat <sender> <text>", see encode_chat_line
+    Chat { sender: String, text: String },
+    // "ping <time>", the sender's session clock, and "pong <time>" sending it straight back
+    Ping(f64),
+    Pong(f64),
+}
+
+impl NetMessage {
+    pub fn to_line(&self) -> String {
+        match self {
+            NetMessage::Hello { role, name } => {
+                let role = match role {
+                    NetRole::Spectator => "spectator",
+                    _ => "player",
+                };
+                format!("hello {} {}", role, name)
+            }
+            NetMessage::Welcome { player } => {
+                format!(
+                    "welcome {}",
+                    player.map_or("-".to_string(), |player| player.to_string())
+                )
+            }
+            NetMessage::Players([first, second]) => format!("players {} {}", first, second),
+            NetMessage::Refused(reason) => format!("refused {}", reason),
+            NetMessage::Turn(direction) => format!("turn {}", direction_letter(*direction)),
+            NetMessage::Sync { board, frame } => format!("sync {} {}", board, frame),
+            NetMessage::NeedKeyframe { board } => format!("keyframe {}", board),
+            NetMessage::Paused { name } => format!("paused {}", name),
+            NetMessage::Resumed => "resumed".to_string(),
+            NetMessage::Chat { sender, text } => encode_chat_line(sender, text),
+            NetMessage::Ping(time) => format!("ping {}", time),
+            NetMessage::Pong(time) => format!("pong {}", time),
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<Self> {
+        let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let board = |text: &str| text.parse::<usize>().ok().filter(|board| *board < 2);
+        Some(match word {
+            "hello" => {
+                let (role, name) = rest.split_once(' ')?;
+                let role = match role {
+                    "player" => NetRole::Client,
+                    "spectator" => NetRole::Spectator,
+                    _ => return None,
+                };
+                NetMessage::Hello {
+                    role,
+                    name: net_name(name),
+                }
+            }
+            "welcome" => NetMessage::Welcome {
+                player: match rest {
+                    "-" => None,
+                    player => Some(board(player)?),
+                },
+            },
+            "players" => {
+                let (first, second) = rest.split_once(' ')?;
+                NetMessage::Players([net_name(first), net_name(second)])
+            }
+            "refused" => NetMessage::Refused(rest.to_string()),
+            "turn" => NetMessage::Turn(parse_direction_letter(rest)?),
+            "sync" => {
+                let (index, frame) = rest.split_once(' ')?;
+                NetMessage::Sync {
+                    board: board(index)?,
+                    frame: frame.to_string(),
+                }
+            }
+            "keyframe" => NetMessage::NeedKeyframe {
+                board: board(rest)?,
+            },
+            "paused" => NetMessage::Paused {
+                name: net_name(rest),
+            },
+            "resumed" => NetMessage::Resumed,
+            "ping" => NetMessage::Ping(rest.parse().ok().filter(|time: &f64| time.is_finite())?),
+            "pong" => NetMessage::Pong(rest.parse().ok().filter(|time: &f64| time.is_finite())?),
+            "chat" => {
+                let (sender, text) = decode_chat_line(line)?;
+                NetMessage::Chat { sender, text }
+            }
+            _ => return None,
+        })
+    }
+}
+
+// A player's name as it goes over the wire: one word, no longer than a hotseat name
+pub fn net_name(name: &str) -> String {
+    let name: String = name
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("_")
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(PLAYER_NAME_LENGTH)
+        .collect();
+    if name.is_empty() {
+        "Player".to_string()
+    } else {
+        name
+    }
+}
+
+// Someone connected to this session - for the host, every peer that connects; for a
+// client, just the host
+pub struct NetPeer {
+    pub link: Box<dyn NetLink>,
+    pub greeted: bool,         // its handshake checked out
+    pub role: Option<NetRole>, // what it joined as, once it's said hello (or been welcomed)
+    pub name: String,
+    pub closed: bool, // the link failed - dropped on the next update
+}
+
+impl NetPeer {
+    fn new(link: Box<dyn NetLink>) -> Self {
+        Self {
+            link,
+            greeted: false,
+            role: None,
+            name: String::new(),
+            closed: false,
+        }
+    }
+
+    fn send(&mut self, message: &NetMessage) {
+        if self.link.send(&message.to_line()).is_err() {
+            self.closed = true;
+        }
+    }
+}
+
+// One end of an online match. The host steps the boards and sends out what happened;
+// a client mirrors the host's boards and sends its turns
+pub struct NetSession {
+    pub role: NetRole,
+    pub mode: NetMode,
+    pub config: GameConfig, // the board, the same size at every end
+    pub player: usize,      // which player we are - the host is 0
+    pub name: String,       // ours, as the others see it
+    pub names: [String; 2],
+    pub handshake: Handshake, // ours - a client takes the host's seed once it's heard it
+    pub boards: Vec<GameState>, // one per player in versus, one shared in co-op
+    pub peers: Vec<NetPeer>,
+    pub other: Option<PeerStatus>, // the other player's connection, None until they first join
+    pub notice: Option<String>,    // shown over the boards - who we're waiting on, who won
+    pub chat: ChatLog,
+    pub spectators: SpectatorSlots,     // host: who's watching
+    pub encoders: Vec<SyncEncoder>,     // host: one per board
+    pub decoders: Vec<SyncDecoder>,     // client: one per board
+    pub keyframe_asked: Vec<bool>,      // client: per board, a keyframe's been asked for already
+    pub migrations: Vec<HostMigration>, // client: per board, the last state the host's hash vouched for
+    pub step_time: f64,                 // host: time not yet stepped
+    pub tick: u64,                      // host: steps taken
+    pub clock: f64,                     // seconds since the session started, for pings
+    pub ping_in: f64,
+    pub latency: Latency,        // round trip to the other player (or the host)
+    pub input_delay: InputDelay, // host: our own player's turns, held back to match theirs
+    pub fixed_delay: bool,       // the delay was chosen, rather than following the latency
+}
+
+impl NetSession {
+    // Host a match, with the boards rolled from `seed`
+    pub fn host(mode: NetMode, config: GameConfig, name: &str, seed: u64) -> Self {
+        let mut session = Self::new(NetRole::Host, mode, config, name, seed);
+        session.notice = Some("Waiting for another player to join".to_string());
+        session
+    }
+
+    // Join the match at the other end of `link`, which has to be on a board like `config`
+    pub fn join(link: Box<dyn NetLink>, mode: NetMode, config: GameConfig, name: &str) -> Self {
+        let mut session = Self::new(NetRole::Client, mode, config, name, 0);
+        session.player = 1;
+        session.names.swap(0, 1);
+        session.rejoin(link);
+        session
+    }
+
+    // Watch the match at the other end of `link` - a spectator mirrors the boards like a
+    // client but has no seat, so its turns go nowhere
+    pub fn spectate(link: Box<dyn NetLink>, mode: NetMode, config: GameConfig, name: &str) -> Self {
+        let mut session = Self::new(NetRole::Spectator, mode, config, name, 0);
+        session.names[0] = "?".to_string();
+        session.rejoin(link);
+        session
+    }
+
+    fn new(role: NetRole, mode: NetMode, config: GameConfig, name: &str, seed: u64) -> Self {
+        let board_count = match mode {
+            NetMode::Versus => 2,
+            NetMode::Coop => 1,
+        };
+        let mut session = Self {
+            role,
+            mode,
+            config,
+            player: 0,
+            name: net_name(name),
+            names: [net_name(name), "?".to_string()],
+            handshake: Handshake::new(mode, &config, seed),
+            boards: Vec::new(),
+            peers: Vec::new(),
+            other: None,
+            notice: None,
+            chat: ChatLog::default(),
+            spectators: SpectatorSlots::default(),
+            encoders: vec![SyncEncoder::default(); board_count],
+            decoders: vec![SyncDecoder::default(); board_count],
+            keyframe_asked: vec![false; board_count],
+            migrations: vec![HostMigration::new(role); board_count],
+            step_time: 0.0,
+            tick: 0,
+            clock: 0.0,
+            ping_in: 0.0,
+            latency: Latency::default(),
+            input_delay: InputDelay::new(0),
+            fixed_delay: false,
+        };
+        session.boards = (0..board_count).map(|_| session.new_board()).collect();
+        session
+    }
+
+    // A board for this match: rolled from the match seed, and keeping its records to itself
+    // so online runs never touch the player's own
+    fn new_board(&self) -> GameState {
+        let mut board =
+            GameState::with_storage_and_config(Arc::new(InMemoryStorage::default()), self.config);
+        board.set_coop(self.mode == NetMode::Coop);
+        board.reseed(self.handshake.seed);
+        board
+    }
+
+    // Which board a player steers
+    pub fn board_of(&self, player: usize) -> usize {
+        match self.mode {
+            NetMode::Versus => player,
+            NetMode::Coop => 0,
+        }
+    }
+
+    // Host: someone new connected - they hear our handshake straight away
+    pub fn accept(&mut self, link: Box<dyn NetLink>) {
+        let mut peer = NetPeer::new(link);
+        if peer.link.send(&self.handshake.to_line()).is_err() {
+            peer.closed = true;
+        }
+        self.peers.push(peer);
+    }
+
+    // Client or spectator: a new connection to the host, for the first time or after losing it
+    pub fn rejoin(&mut self, link: Box<dyn NetLink>) {
+        self.peers.clear();
+        self.decoders = vec![SyncDecoder::default(); self.boards.len()];
+        self.keyframe_asked = vec![false; self.boards.len()];
+        let mut host = NetPeer::new(link);
+        if host.link.send(&self.handshake.to_line()).is_err() {
+            host.closed = true;
+        }
+        host.send(&NetMessage::Hello {
+            role: match self.role {
+                NetRole::Spectator => NetRole::Spectator,
+                _ => NetRole::Client,
+            },
+            name: self.name.clone(),
+        });
+        self.peers.push(host);
+    }
+
+    // Client or spectator: the connection to the host is gone and we're waiting to get back in
+    pub fn lost_host(&self) -> bool {
+        self.role != NetRole::Host
+            && self.peers.is_empty()
+            && self.other.is_some_and(|status| status.is_paused())
+    }
+
+    // Host: the other player hasn't joined yet, or has dropped and may come back
+    pub fn wants_player(&self) -> bool {
+        self.role == NetRole::Host
+            && !matches!(
+                self.other,
+                Some(PeerStatus::Connected | PeerStatus::Dropped)
+            )
+    }
+
+    // Both players are in and nobody's being waited on
+    pub fn is_playing(&self) -> bool {
+        self.other == Some(PeerStatus::Connected)
+    }
+
+    // Someone's board has ended - in versus that's the whole match
+    pub fn is_over(&self) -> bool {
+        self.boards.iter().any(|board| board.game_over)
+    }
+
+    // Our player pressed a turn
+    pub fn steer(&mut self, direction: Direction) {
+        match self.role {
+            // the other player's turns reach us a one-way trip late, so ours wait as long
+            NetRole::Host => {
+                self.input_delay.push(self.tick, direction);
+            }
+            NetRole::Client => self.send_to_all(&NetMessage::Turn(direction)),
+            NetRole::Spectator => {}
+        }
+    }
+
+    // We said something - the host passes it on to everyone else
+    pub fn say(&mut self, text: &str) {
+        let sender = self.name.clone();
+        let count = self.chat.messages.len();
+        self.chat.add(&sender, text);
+        if self.chat.messages.len() > count {
+            self.send_to_all(&NetMessage::Chat {
+                sender,
+                text: text.to_string(),
+            });
+        }
+    }
+
+    // Hold our turns back this many steps from now on, or None to follow the latency
+    pub fn set_input_delay(&mut self, ticks: Option<u32>) {
+        self.fixed_delay = ticks.is_some();
+        self.input_delay.delay_ticks =
+            ticks.unwrap_or_else(|| self.latency.suggested_delay(NET_STEP));
+    }
+
+    // Host: play the same match again, from the next seed
+    pub fn rematch(&mut self) {
+        if self.role != NetRole::Host || !self.is_over() {
+            return;
+        }
+        for board in &mut self.boards {
+            board.restart();
+        }
+        for encoder in &mut self.encoders {
+            encoder.request_keyframe();
+        }
+        self.notice = None;
+        self.broadcast_boards();
+    }
+
+    // `dt` seconds of the match: whatever the peers sent, then the boards' steps
+    pub fn update(&mut self, dt: f64) {
+        self.clock += dt;
+        self.poll_peers();
+        self.chat.advance(dt);
+        self.ping_in -= dt;
+        if self.ping_in <= 0.0 {
+            self.ping_in = PING_INTERVAL;
+            self.send_to_all(&NetMessage::Ping(self.clock));
+        }
+        if let Some(status) = &mut self.other {
+            let was_paused = status.is_paused();
+            status.advance(dt);
+            if was_paused && *status == PeerStatus::Dropped {
+                self.other_dropped();
+            }
+        }
+        if let (NetRole::Client, Some(PeerStatus::Reconnecting { waited })) =
+            (self.role, self.other)
+        {
+            if waited >= HOST_TAKEOVER_WAIT {
+                self.take_over();
+            }
+        }
+        match self.role {
+            NetRole::Host if self.is_playing() => {
+                self.step_time += dt;
+                while self.step_time >= NET_STEP {
+                    self.step_time -= NET_STEP;
+                    self.step();
+                }
+            }
+            NetRole::Host => {}
+            _ if self.is_playing() => {
+                // the host doesn't send its clock - ours runs alongside it
+                for board in self.boards.iter_mut().filter(|board| !board.game_over) {
+                    board.elapsed += dt;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Host: one step of every board, sent out to everyone
+    fn step(&mut self) {
+        self.tick += 1;
+        let board = self.board_of(self.player);
+        for direction in self.input_delay.take_due(self.tick) {
+            self.boards[board].handle_input(direction);
+        }
+        let was_over = self.is_over();
+        for board in &mut self.boards {
+            board.tick(NET_STEP);
+        }
+        if !was_over && self.is_over() {
+            self.match_ended();
+        }
+        self.broadcast_boards();
+    }
+
+    // The first board to end decides a versus match - the other player wins
+    fn match_ended(&mut self) {
+        if self.mode == NetMode::Versus {
+            if let Some(loser) = self.boards.iter().position(|board| board.game_over) {
+                self.notice = Some(format!("{} wins!", self.names[1 - loser]));
+            }
+            for board in self.boards.iter_mut().filter(|board| !board.game_over) {
+                board.end_run();
+            }
+        } else {
+            self.notice = Some(format!("Game over - {} points", self.boards[0].score));
+        }
+    }
+
+    fn broadcast_boards(&mut self) {
+        for index in 0..self.boards.len() {
+            let frame = self.encoders[index].encode(&self.boards[index]);
+            self.send_to_all(&NetMessage::Sync {
+                board: index,
+                frame: frame.to_line(),
+            });
+        }
+    }
+
+    // Send to every peer that's joined
+    fn send_to_all(&mut self, message: &NetMessage) {
+        for peer in self.peers.iter_mut().filter(|peer| peer.role.is_some()) {
+            peer.send(message);
+        }
+    }
+
+    // Everything that's come in since the last update, then the peers whose links broke
+    fn poll_peers(&mut self) {
+        for index in 0..self.peers.len() {
+            loop {
+                let peer = &mut self.peers[index];
+                if peer.closed {
+                    break;
+                }
+                match peer.link.recv() {
+                    Ok(Some(line)) => self.handle_line(index, &line),
+                    Ok(None) => break,
+                    Err(_) => peer.closed = true,
+                }
+            }
+        }
+        let mut index = 0;
+        while index < self.peers.len() {
+            if self.peers[index].closed {
+                let peer = self.peers.remove(index);
+                self.peer_left(&peer);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    fn handle_line(&mut self, index: usize, line: &str) {
+        if !self.peers[index].greeted {
+            self.handle_handshake(index, line);
+            return;
+        }
+        let Some(message) = NetMessage::parse(line) else {
+            return; // something newer than us - nothing we need
+        };
+        match message {
+            NetMessage::Ping(time) => {
+                self.peers[index].send(&NetMessage::Pong(time));
+                return;
+            }
+            NetMessage::Pong(time) => {
+                self.pong(index, time);
+                return;
+            }
+            _ => {}
+        }
+        match self.role {
+            NetRole::Host => self.host_message(index, message),
+            _ => self.client_message(message),
+        }
+    }
+
+    // A ping of ours came back - only the other player's (or the host's) counts
+    fn pong(&mut self, index: usize, time: f64) {
+        let from_other = match self.role {
+            NetRole::Host => self.peers[index].role == Some(NetRole::Client),
+            _ => self.peers[index].role == Some(NetRole::Host),
+        };
+        if from_other && time <= self.clock {
+            self.latency.record(self.clock - time);
+            if !self.fixed_delay {
+                self.input_delay.delay_ticks = self.latency.suggested_delay(NET_STEP);
+            }
+        }
+    }
+
+    // The first line from a peer has to be a handshake we can play with
+    fn handle_handshake(&mut self, index: usize, line: &str) {
+        let result = Handshake::parse(line).and_then(|theirs| {
+            self.handshake.check_compatible(&theirs)?;
+            Ok(theirs)
+        });
+        match result {
+            Ok(theirs) => {
+                self.peers[index].greeted = true;
+                if self.role != NetRole::Host && theirs.seed != self.handshake.seed {
+                    // the host's seed is the match's - start over from it
+                    self.handshake.seed = theirs.seed;
+                    self.boards = (0..self.boards.len()).map(|_| self.new_board()).collect();
+                }
+            }
+            Err(e) => {
+                if self.role == NetRole::Host {
+                    self.peers[index].send(&NetMessage::Refused(e.to_string()));
+                } else {
+                    self.notice = Some(format!("Can't join: {}", e));
+                }
+                self.peers[index].closed = true;
+            }
+        }
+    }
+
+    fn host_message(&mut self, index: usize, message: NetMessage) {
+        let peer_role = self.peers[index].role;
+        match message {
+            NetMessage::Hello {
+                role: NetRole::Spectator,
+                name,
+            } if peer_role.is_none() => self.spectator_joined(index, name),
+            NetMessage::Hello { name, .. } if peer_role.is_none() => {
+                self.player_joined(index, name)
+            }
+            NetMessage::Turn(direction) if peer_role.is_some_and(|role| role.can_steer()) => {
+                let board = self.board_of(1 - self.player);
+                self.boards[board].handle_input(direction);
+            }
+            NetMessage::NeedKeyframe { board } if board < self.encoders.len() => {
+                self.encoders[board].request_keyframe();
+            }
+            NetMessage::Chat { text, .. } if peer_role.is_some() => {
+                // under the name they joined with, whatever the line says
+                let message = NetMessage::Chat {
+                    sender: self.peers[index].name.clone(),
+                    text,
+                };
+                self.chat.receive(&message.to_line());
+                for (other, peer) in self.peers.iter_mut().enumerate() {
+                    if other != index && peer.role.is_some() {
+                        peer.send(&message);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Host: the other player said hello - their first time in, or back after dropping out
+    fn player_joined(&mut self, index: usize, name: String) {
+        let refusal = match self.other {
+            None => None,
+            Some(PeerStatus::Reconnecting { .. }) if name == self.names[1 - self.player] => None,
+            Some(PeerStatus::Dropped) => Some("the match is over"),
+            Some(_) => Some("the match already has two players"),
+        };
+        if let Some(reason) = refusal {
+            let peer = &mut self.peers[index];
+            peer.send(&NetMessage::Refused(reason.to_string()));
+            peer.closed = true;
+            return;
+        }
+
+        let rejoining = self.other.is_some();
+        match &mut self.other {
+            Some(status) => {
+                status.reconnect();
+            }
+            None => self.other = Some(PeerStatus::Connected),
+        }
+        self.names[1 - self.player] = name.clone();
+        let peer = &mut self.peers[index];
+        peer.role = Some(NetRole::Client);
+        peer.name = name;
+        if rejoining {
+            self.send_to_all(&NetMessage::Resumed);
+        }
+        self.notice = None;
+        self.welcome(index, Some(1 - self.player));
+    }
+
+    // Host: someone wants to watch - they get the boards as they are, if there's room
+    fn spectator_joined(&mut self, index: usize, name: String) {
+        let peer = &mut self.peers[index];
+        if !self.spectators.join(&name) {
+            peer.send(&NetMessage::Refused(
+                "all spectator slots are taken".to_string(),
+            ));
+            peer.closed = true;
+            return;
+        }
+        peer.role = Some(NetRole::Spectator);
+        peer.name = name;
+        self.welcome(index, None);
+        if let Some(PeerStatus::Reconnecting { .. }) = self.other {
+            let name = self.names[1 - self.player].clone();
+            self.peers[index].send(&NetMessage::Paused { name });
+        }
+    }
+
+    // Host: let a newcomer in, in `player`'s seat or none. Everyone hears who's playing -
+    // before the newcomer's welcome, so it knows by the time it's in - and starts over
+    // from a keyframe, so the newcomer isn't sent a delta it has nothing to apply to
+    fn welcome(&mut self, index: usize, player: Option<usize>) {
+        self.send_to_all(&NetMessage::Players(self.names.clone()));
+        self.peers[index].send(&NetMessage::Welcome { player });
+        for encoder in &mut self.encoders {
+            encoder.request_keyframe();
+        }
+        self.broadcast_boards();
+    }
+
+    fn client_message(&mut self, message: NetMessage) {
+        match message {
+            NetMessage::Welcome { player } => {
+                // the old host takes whichever seat is free if it comes back after a takeover
+                if let Some(player) = player {
+                    self.player = player;
+                }
+                self.peers[0].role = Some(NetRole::Host);
+                match &mut self.other {
+                    Some(status) => {
+                        status.reconnect();
+                    }
+                    None => self.other = Some(PeerStatus::Connected),
+                }
+                self.notice = None;
+            }
+            NetMessage::Players(names) => self.names = names,
+            NetMessage::Refused(reason) => {
+                self.notice = Some(format!("Can't join: {}", reason));
+                self.other = Some(PeerStatus::Dropped);
+                self.peers[0].closed = true;
+            }
+            NetMessage::Sync { board, frame } if board < self.boards.len() => {
+                self.apply_frame(board, &frame);
+            }
+            NetMessage::Paused { name } => {
+                self.notice = Some(format!("Waiting for {} to reconnect", name));
+            }
+            NetMessage::Resumed => self.notice = None,
+            message @ NetMessage::Chat { .. } => self.chat.receive(&message.to_line()),
+            _ => {}
+        }
+    }
+
+    // Client: bring a board up to date with the host's, or ask for a keyframe if we can't
+    fn apply_frame(&mut self, index: usize, frame: &str) {
+        match self.decoders[index].apply(frame) {
+            Ok(state) => {
+                // a delta only applies if it lands on the host's hash, so this is checked
+                let hash = state.state_hash();
+                let board = &mut self.boards[index];
+                let elapsed = board.elapsed;
+                board.resync_from(state, hash);
+                board.elapsed = elapsed;
+                self.migrations[index].verify(self.decoders[index].tick, hash, board);
+                self.keyframe_asked[index] = false;
+                if self.boards.iter().any(|board| board.game_over) && self.notice.is_none() {
+                    self.notice = Some(self.result_line());
+                }
+            }
+            Err(_) if !self.keyframe_asked[index] => {
+                self.keyframe_asked[index] = true;
+                self.send_to_all(&NetMessage::NeedKeyframe { board: index });
+            }
+            Err(_) => {}
+        }
+    }
+
+    // How the match ended, from a client's side
+    fn result_line(&self) -> String {
+        match self.mode {
+            NetMode::Versus => match self.boards.iter().position(|board| board.game_over) {
+                Some(loser) => format!("{} wins!", self.names[1 - loser]),
+                None => String::new(),
+            },
+            NetMode::Coop => format!("Game over - {} points", self.boards[0].score),
+        }
+    }
+
+    // A peer's link broke
+    fn peer_left(&mut self, peer: &NetPeer) {
+        match (self.role, peer.role) {
+            (NetRole::Host, Some(NetRole::Client)) => {
+                if let Some(status) = &mut self.other {
+                    status.disconnect();
+                }
+                self.notice = Some(format!("Waiting for {} to reconnect", peer.name));
+                self.send_to_all(&NetMessage::Paused {
+                    name: peer.name.clone(),
+                });
+            }
+            (NetRole::Host, Some(NetRole::Spectator)) => self.spectators.leave(&peer.name),
+            (_, Some(NetRole::Host)) => {
+                if let Some(status) = &mut self.other {
+                    status.disconnect();
+                }
+                self.notice = Some("Lost the host - reconnecting".to_string());
+            }
+            _ => {}
+        }
+    }
+
+    // Client: the host isn't coming back, so carry on as the host from the boards as they
+    // last matched its hash. The old host's player can rejoin within the reconnect window
+    fn take_over(&mut self) {
+        let mut boards = self.boards.clone();
+        let mut migrations = self.migrations.clone();
+        let resumed: Option<Vec<u64>> = migrations
+            .iter_mut()
+            .zip(&mut boards)
+            .map(|(migration, board)| migration.host_lost(board))
+            .collect();
+        let Some(ticks) = resumed else {
+            return; // nothing verified to carry on from - wait the window out
+        };
+        self.boards = boards;
+        self.migrations = migrations;
+        self.role = NetRole::Host;
+        self.tick = ticks.into_iter().max().unwrap_or(0);
+        self.peers.clear();
+        self.encoders = vec![SyncEncoder::default(); self.boards.len()];
+        self.input_delay = InputDelay::new(self.input_delay.delay_ticks);
+        self.other = Some(PeerStatus::Reconnecting { waited: 0.0 });
+        self.notice = Some(format!(
+            "The host left - you're hosting now, waiting for {} to rejoin",
+            self.names[1 - self.player]
+        ));
+    }
+
+    // The player we were waiting on never came back
+    fn other_dropped(&mut self) {
+        match self.role {
+            NetRole::Host => {
+                let board = self.board_of(1 - self.player);
+                if !self.boards[board].game_over {
+                    self.boards[board].end_run();
+                }
+                self.match_ended();
+                self.notice = Some(format!(
+                    "{} didn't come back - {}",
+                    self.names[1 - self.player],
+                    self.notice.take().unwrap_or_default()
+                ));
+                self.broadcast_boards();
+            }
+            _ => self.notice = Some("The host is gone".to_string()),
+        }
+    }
+}
+
+// An online match in a window: a NetSession plus the sockets it talks over
+pub struct OnlineGame {
+    pub session: NetSession,
+    pub listener: Option<std::net::TcpListener>, // host: where other peers connect
+    pub room: Option<(String, String)>, // host: the relay it waits at too, and its room code
+    pub route: Option<PeerRoute>,       // client: how to get back in
+    pub retry_in: f64,
+    pub typing: Option<String>, // a chat message being written, from when Enter was pressed
+}
+
+impl OnlineGame {
+    // Host a match on `port`, and at a room on `relay` too for players who can't reach us
+    pub fn host(
+        mode: NetMode,
+        config: GameConfig,
+        name: &str,
+        port: u16,
+        relay: Option<&str>,
+    ) -> std::io::Result<Self> {
+        let listener = std::net::TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        let mut online = Self {
+            session: NetSession::host(mode, config, name, rand::random()),
+            listener: Some(listener),
+            room: relay.map(|relay| (relay.to_string(), room_code())),
+            route: None,
+            retry_in: 0.0,
+            typing: None,
+        };
+        if let Some((relay, room)) = &online.room {
+            online.session.notice = Some(format!(
+                "Waiting for another player to join - room {}",
+                room
+            ));
+            online.wait_at_relay(relay.clone(), room.clone())?;
+        }
+        Ok(online)
+    }
+
+    // Join the match at the end of `route`
+    pub fn join(
+        mode: NetMode,
+        config: GameConfig,
+        name: &str,
+        route: PeerRoute,
+    ) -> std::io::Result<Self> {
+        let link = TcpLink::new(route.connect(DIRECT_CONNECT_TIMEOUT)?)?;
+        Ok(Self::through(
+            NetSession::join(Box::new(link), mode, config, name),
+            route,
+        ))
+    }
+
+    // Watch a hosted match, found the same way as joining one
+    pub fn spectate(
+        mode: NetMode,
+        config: GameConfig,
+        name: &str,
+        route: PeerRoute,
+    ) -> std::io::Result<Self> {
+        let link = TcpLink::new(route.connect(DIRECT_CONNECT_TIMEOUT)?)?;
+        Ok(Self::through(
+            NetSession::spectate(Box::new(link), mode, config, name),
+            route,
+        ))
+    }
+
+    fn through(session: NetSession, route: PeerRoute) -> Self {
+        Self {
+            session,
+            listener: None,
+            room: None,
+            route: Some(route),
+            retry_in: REJOIN_INTERVAL,
+            typing: None,
+        }
+    }
+
+    // The room code a hosted match can be joined with through the relay
+    pub fn room_code(&self) -> Option<&str> {
+        self.room.as_ref().map(|(_, room)| room.as_str())
+    }
+
+    // Host: take the next player through the relay - the handshake waits there until
+    // someone joins the room
+    fn wait_at_relay(&mut self, relay: String, room: String) -> std::io::Result<()> {
+        let route = PeerRoute {
+            direct: None,
+            relay: Some((relay, room)),
+        };
+        let link = TcpLink::new(route.connect(REJOIN_TIMEOUT)?)?;
+        self.session.accept(Box::new(link));
+        Ok(())
+    }
+
+    // Whatever's come in over the network, then `dt` seconds of the match
+    pub fn poll(&mut self, dt: f64) {
+        self.connect(dt);
+        self.session.update(dt);
+    }
+
+    pub fn window_width(&self) -> f32 {
+        match self.session.mode {
+            NetMode::Versus => DualGame::window_width(&self.session.config),
+            NetMode::Coop => self.session.config.screen_width(),
+        }
+    }
+
+    // New connections for a host, another try at the host for a client that lost it
+    fn connect(&mut self, dt: f64) {
+        if let (NetRole::Host, Some(route)) = (self.session.role, &self.route) {
+            self.take_players_like(&route.clone());
+        }
+        if let Some(listener) = &self.listener {
+            while let Ok((stream, _)) = listener.accept() {
+                if let Ok(link) = TcpLink::new(stream) {
+                    self.session.accept(Box::new(link));
+                }
+            }
+        }
+        if let (true, Some(route)) = (self.session.lost_host(), &self.route) {
+            self.retry_in -= dt;
+            if self.retry_in <= 0.0 {
+                self.retry_in = REJOIN_INTERVAL;
+                let link = route.connect(REJOIN_TIMEOUT).and_then(TcpLink::new);
+                if let Ok(link) = link {
+                    self.session.rejoin(Box::new(link));
+                }
+            }
+        }
+
+        // The relay pairs one player per wait, so a host still short of a player (or
+        // waiting on one to come back) goes back to the room once nobody's there for it
+        let waiting = self.session.peers.iter().any(|peer| peer.role.is_none());
+        if let (true, false, Some((relay, room))) =
+            (self.session.wants_player(), waiting, self.room.clone())
+        {
+            self.retry_in -= dt;
+            if self.retry_in <= 0.0 {
+                self.retry_in = REJOIN_INTERVAL;
+                let _ = self.wait_at_relay(relay, room);
+            }
+        }
+    }
+
+    // After taking the match over: wait for players where the old host did - the same port,
+    // and the same room on the relay
+    fn take_players_like(&mut self, route: &PeerRoute) {
+        let port = route
+            .direct
+            .as_ref()
+            .and_then(|address| address.rsplit_once(':')?.1.parse().ok())
+            .unwrap_or(NET_PORT);
+        self.listener = std::net::TcpListener::bind(("0.0.0.0", port))
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            .ok();
+        self.room = route.relay.clone();
+        self.route = None;
+        self.retry_in = 0.0;
+    }
+
+    // Keys while a chat message is being written: Enter sends it, Escape throws it away
+    fn typing_key(&mut self, key: KeyCode) {
+        let Some(typing) = &mut self.typing else {
+            return;
+        };
+        match key {
+            KeyCode::Return | KeyCode::NumpadEnter => {
+                let text = std::mem::take(typing);
+                self.typing = None;
+                self.session.say(&text);
+            }
+            KeyCode::Escape => self.typing = None,
+            KeyCode::Back => {
+                typing.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl EventHandler for OnlineGame {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        self.poll(ctx.time.delta().as_secs_f64().min(MAX_TICK_SECONDS));
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        self.draw(ctx)
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
+        if let Some(typing) = &mut self.typing {
+            if !character.is_control() && typing.chars().count() < MAX_CHAT_LENGTH {
+                typing.push(character);
+            }
+        }
+        Ok(())
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        key_input: KeyInput,
+        repeat: bool,
+    ) -> GameResult {
+        let Some(key) = key_input.keycode else {
+            return Ok(());
+        };
+        if self.typing.is_some() {
+            self.typing_key(key);
+            return Ok(());
+        }
+        let direction = match key {
+            KeyCode::Up | KeyCode::W => Direction::Up,
+            KeyCode::Down | KeyCode::S => Direction::Down,
+            KeyCode::Left | KeyCode::A => Direction::Left,
+            KeyCode::Right | KeyCode::D => Direction::Right,
+            KeyCode::R if !repeat => {
+                self.session.rematch();
+                return Ok(());
+            }
+            KeyCode::Return if !repeat => {
+                self.typing = Some(String::new());
+                return Ok(());
+            }
+            KeyCode::M if !repeat => {
+                self.session.chat.muted = !self.session.chat.muted;
+                return Ok(());
+            }
+            KeyCode::Escape => {
+                ctx.request_quit();
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+        if !repeat {
+            self.session.steer(direction);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1940 @@
+//! Drawing the game with ggez - the board, the HUD and overlays, every mode's screens, and
+//! the text cache that keeps labels from being laid out again every frame - plus the
+//! board rasterized to pixels and text for displays that aren't a window.
+
+use super::*;
+
+impl GameState {
+    // Draw the game
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas = graphics::Canvas::from_frame(ctx, self.theme.background_at(self.elapsed));
+        self.draw_board(ctx, &mut canvas)?;
+        canvas.finish(ctx)?;
+        Ok(())
+    }
+
+    // Draw everything onto a canvas in board coordinates, so (0, 0) is the board's top-left
+    // corner - other views (like split screen) can shift the canvas to place the board
+    pub fn draw_board(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        // The splash and the menus are drawn instead of the board, the pause screen over it
+        let on_board = match self.screen {
+            Screen::Splash => self.draw_splash(ctx, canvas).map(|_| false)?,
+            Screen::MainMenu => self.draw_main_menu(ctx, canvas).map(|_| false)?,
+            Screen::Settings => self.draw_settings(ctx, canvas).map(|_| false)?,
+            Screen::Playing | Screen::Paused | Screen::GameOver => true,
+        };
+        if !on_board {
+            self.draw_toast(ctx, canvas)?;
+            self.draw_transition(ctx, canvas)?;
+            return Ok(());
+        }
+
+        // Every cell on the board goes out in one instanced draw of the plain white
+        // image, each instance scaled to its cell and tinted
+        let mut cells = graphics::InstanceArray::new(ctx, None);
+        cells.set(
+            self.board_cells()
+                .into_iter()
+                .map(|(rect, color)| cell_instance(rect, color)),
+        );
+        canvas.draw(&cells, graphics::DrawParam::default());
+
+        // Brief color pulse over the whole screen after eating in party mode
+        if self.food_pulse > 0.0 && self.party_mode_active() {
+            let screen_rect = Rect::new(
+                0.0,
+                0.0,
+                self.config.screen_width(),
+                self.config.screen_height(),
+            );
+            let mut pulse_color = hue_color(self.anim_time as f32 * 0.5);
+            pulse_color.a = (self.food_pulse / FOOD_PULSE_DURATION) as f32 * 0.3;
+            let pulse_mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), screen_rect, pulse_color)?;
+            canvas.draw(&pulse_mesh, graphics::DrawParam::default());
+        }
+
+        if self.show_hud {
+            self.draw_hud(ctx, canvas)?;
+        }
+        if self.screen == Screen::Paused {
+            self.draw_pause(ctx, canvas)?;
+        }
+        self.draw_toast(ctx, canvas)?;
+        self.draw_transition(ctx, canvas)?;
+
+        Ok(())
+    }
+
+    // The board's cells in draw order - grip, puddles, walls, snake, then food - as the rectangle
+    // each covers in board coordinates and its color. Cells hidden in the fog are left out.
+    pub fn board_cells(&self) -> Vec<(Rect, Color)> {
+        let cell_size = self.config.cell_size;
+        let cell_rect = |cell: Position, inset: f32| {
+            Rect::new(
+                // this is scaling the grid coordinates to the screen pixel coordinates
+                cell.x as f32 * cell_size + inset / 2.0,
+                cell.y as f32 * cell_size + inset / 2.0,
+                // - 2.0 to make the snake segments clearer
+                cell_size - 2.0 - inset,
+                cell_size - 2.0 - inset,
+            )
+        };
+        let mut cells = Vec::with_capacity(
+            self.grip_cells.len()
+                + self.slippery_cells.len()
+                + self.walls.len()
+                + self.snake.len()
+                + 1,
+        );
+
+        // grip cells on ice, then slippery cells, under everything else
+        for cell in &self.grip_cells {
+            if self.is_visible(*cell) {
+                cells.push((cell_rect(*cell, 0.0), Color::new(0.6, 0.45, 0.3, 0.35)));
+            }
+        }
+        for (cell, _) in &self.slippery_cells {
+            if self.is_visible(*cell) {
+                cells.push((cell_rect(*cell, 0.0), Color::new(0.3, 0.5, 1.0, 0.3)));
+            }
+        }
+        for cell in &self.walls {
+            if self.is_visible(*cell) {
+                cells.push((cell_rect(*cell, 0.0), Color::new(0.55, 0.55, 0.6, 1.0)));
+            }
+        }
+
+        for (index, segment) in self.snake.iter().enumerate() {
+            if !self.is_visible(*segment) {
+                continue;
+            }
+            // the snake "breathes" while the game isn't running so the screen doesn't look frozen
+            let inset = if self.game_over {
+                idle_pulse(self.anim_time, index) * 4.0
+            } else {
+                0.0
+            };
+            let mut color = self.segment_color(index);
+            if index == 0 && self.game_over && is_blinking(self.anim_time) {
+                color = lerp_color(color, Color::BLACK, 0.5);
+            }
+            cells.push((cell_rect(*segment, inset), color));
+        }
+
+        // each kind of food has its own color and size, and food about to rot flickers
+        let food = self.food;
+        if self.is_visible(food.position) {
+            let inset = match food.kind {
+                FoodKind::Golden if !self.reduced_motion => {
+                    (self.anim_time * 6.0).sin().abs() as f32 * 6.0
+                }
+                FoodKind::Shrink => cell_size * 0.4,
+                FoodKind::Poison => cell_size * 0.2,
+                _ => 0.0,
+            };
+            let mut color = food.kind.color(&self.theme);
+            if food
+                .ticks_left
+                .is_some_and(|ticks| ticks <= FOOD_TIMEOUT_TICKS / 5)
+                && is_blinking(self.anim_time)
+            {
+                color.a = 0.4;
+            }
+            cells.push((cell_rect(food.position, inset), color));
+        }
+        cells
+    }
+
+    // Score, time and high score along the top, coverage along the bottom, and the
+    // game over overlay
+    fn draw_hud(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        // The HUD's labels only change when the score or the second does, so they come
+        // out of the text cache
+        let cache = &self.text_cache;
+        let hud = |string: &str,
+                   canvas: &mut graphics::Canvas,
+                   place: &dyn Fn(mint::Vector2<f32>) -> [f32; 2]| {
+            cache.draw(ctx, canvas, string, 16.0, Color::WHITE, place)
+        };
+
+        // Draw score at top-left
+        hud(&format!("Score: {}", self.score), canvas, &|_| [10.0, 10.0])?;
+
+        // Draw run time at top-center
+        let screen_width = self.config.screen_width();
+        let time = format!("Time: {}", format_time(self.elapsed));
+        hud(&time, canvas, &|bounds: mint::Vector2<f32>| {
+            [(screen_width - bounds.x) / 2.0, 10.0]
+        })?;
+
+        // Draw high score at top-right
+        let unchecked = if self.high_score_checked {
+            ""
+        } else {
+            " (unchecked)"
+        };
+        let high_score = format!("High Score: {}{}", self.high_score, unchecked);
+        hud(&high_score, canvas, &|bounds: mint::Vector2<f32>| {
+            [screen_width - bounds.x - 10.0, 10.0]
+        })?;
+
+        // Draw the level and what it takes to clear it under the score
+        if let Some(run) = self.levels.as_ref().filter(|run| !run.cleared) {
+            let progress = format!(
+                "Level {}: {} ({}/{})",
+                run.current + 1,
+                run.level().name,
+                self.score.saturating_sub(run.start_score),
+                run.level().target_score
+            );
+            hud(&progress, canvas, &|_| [10.0, 32.0])?;
+        }
+
+        // Draw board coverage as a thin bar along the bottom edge
+        self.draw_coverage_bar(ctx, canvas)?;
+
+        // Draw game over overlay if game is over
+        if self.game_over {
+            self.draw_game_over_overlay(ctx, canvas)?;
+        } else if self.level_complete() {
+            self.draw_level_complete_overlay(ctx, canvas)?;
+        }
+
+        if self.in_grace() {
+            let message = if self.grace_left == GracePeriod::UntilInput {
+                "Steer to start"
+            } else {
+                "Get ready"
+            };
+            let screen_height = self.config.screen_height();
+            self.text_cache.draw(
+                ctx,
+                canvas,
+                message,
+                28.0,
+                Color::new(1.0, 1.0, 1.0, 0.8),
+                |bounds| [(screen_width - bounds.x) / 2.0, screen_height / 3.0],
+            )?;
+        }
+        Ok(())
+    }
+
+    // Intro logo that fades in, holds and fades back out
+    fn draw_splash(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let screen_width = self.config.screen_width();
+        let screen_height = self.config.screen_height();
+        let alpha = fade_alpha(self.screen_time, SPLASH_DURATION, SPLASH_FADE);
+
+        let title_text = Text::new(
+            TextFragment::new("SNAKE")
+                .color(Color::new(0.0, 1.0, 0.0, alpha))
+                .scale(graphics::PxScale::from(72.0)),
+        );
+        let title_bounds = title_text.measure(ctx)?;
+        let title_y = screen_height / 2.0 - title_bounds.y;
+        canvas.draw(
+            &title_text,
+            graphics::DrawParam::default().dest([(screen_width - title_bounds.x) / 2.0, title_y]),
+        );
+
+        let skip_text = Text::new(
+            TextFragment::new("Press any key")
+                .color(Color::new(1.0, 1.0, 1.0, alpha * 0.6))
+                .scale(graphics::PxScale::from(18.0)),
+        );
+        let skip_bounds = skip_text.measure(ctx)?;
+        canvas.draw(
+            &skip_text,
+            graphics::DrawParam::default()
+                .dest([(screen_width - skip_bounds.x) / 2.0, title_y + 100.0]),
+        );
+
+        Ok(())
+    }
+
+    // Cover the screen according to the running transition, if any
+    fn draw_transition(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let Some(transition) = self.transition else {
+            return Ok(());
+        };
+        let remaining = transition.remaining();
+        if remaining <= 0.0 {
+            return Ok(());
+        }
+
+        let screen_width = self.config.screen_width();
+        let screen_height = self.config.screen_height();
+        let (cover_rect, alpha) = match transition.kind {
+            TransitionKind::Fade => (Rect::new(0.0, 0.0, screen_width, screen_height), remaining),
+            TransitionKind::Wipe => {
+                let width = screen_width * remaining;
+                (
+                    Rect::new(screen_width - width, 0.0, width, screen_height),
+                    1.0,
+                )
+            }
+            TransitionKind::Slide => {
+                let offset = screen_height * (1.0 - remaining);
+                (Rect::new(0.0, offset, screen_width, screen_height), 1.0)
+            }
+        };
+
+        let cover_mesh = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            cover_rect,
+            Color::new(0.0, 0.0, 0.0, alpha),
+        )?;
+        canvas.draw(&cover_mesh, graphics::DrawParam::default());
+
+        Ok(())
+    }
+
+    // Subtle progress bar showing how much of the board the snake fills
+    fn draw_coverage_bar(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let screen_width = self.config.screen_width();
+        let bar_height = 4.0;
+        let bar_y = self.config.screen_height() - bar_height;
+
+        let track_rect = Rect::new(0.0, bar_y, screen_width, bar_height);
+        let track_mesh = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            track_rect,
+            Color::new(1.0, 1.0, 1.0, 0.1),
+        )?;
+        canvas.draw(&track_mesh, graphics::DrawParam::default());
+
+        // nothing to fill yet (ggez doesn't like zero-width rectangles anyway)
+        let fill_width = screen_width * self.coverage();
+        if fill_width > 0.0 {
+            let fill_rect = Rect::new(0.0, bar_y, fill_width, bar_height);
+            let fill_mesh = Mesh::new_rectangle(
+                ctx,
+                DrawMode::fill(),
+                fill_rect,
+                Color::new(0.0, 1.0, 0.0, 0.4),
+            )?;
+            canvas.draw(&fill_mesh, graphics::DrawParam::default());
+        }
+
+        Ok(())
+    }
+
+    // Add a game overlay for when the game is over
+    // Toast along the top of the board, fading out over its last second
+    fn draw_toast(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let Some(toast) = &self.toast else {
+            return Ok(());
+        };
+        let alpha = toast.time_left.min(1.0) as f32;
+        let text = Text::new(
+            TextFragment::new(toast.message.as_str())
+                .color(Color::new(1.0, 1.0, 1.0, alpha))
+                .scale(graphics::PxScale::from(18.0)),
+        );
+        let bounds = text.measure(ctx)?;
+        let screen_width = self.config.screen_width();
+        let x = (screen_width - bounds.x) / 2.0;
+        let backing = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(x - 8.0, 36.0, bounds.x + 16.0, bounds.y + 8.0),
+            Color::new(0.0, 0.0, 0.0, 0.6 * alpha),
+        )?;
+        canvas.draw(&backing, graphics::DrawParam::default());
+        canvas.draw(&text, graphics::DrawParam::default().dest([x, 40.0]));
+        Ok(())
+    }
+
+    fn draw_level_complete_overlay(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+    ) -> GameResult {
+        let Some(run) = &self.levels else {
+            return Ok(());
+        };
+        let screen_width = self.config.screen_width();
+        let screen_height = self.config.screen_height();
+        let overlay_mesh = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, screen_width, screen_height),
+            Color::new(0.0, 0.0, 0.0, 0.6),
+        )?;
+        canvas.draw(&overlay_mesh, graphics::DrawParam::default());
+
+        let center = |bounds: mint::Vector2<f32>, y: f32| [(screen_width - bounds.x) / 2.0, y];
+        let title_y = screen_height / 2.0 - 60.0;
+        let gold = Color::new(1.0, 0.84, 0.0, 1.0);
+        self.text_cache
+            .draw(ctx, canvas, "LEVEL COMPLETE", 40.0, gold, |bounds| {
+                center(bounds, title_y)
+            })?;
+        let next = match run.levels.get(run.current + 1) {
+            Some(level) => format!("Next up: {}", level.name),
+            None => "That was the last one!".to_string(),
+        };
+        self.text_cache
+            .draw(ctx, canvas, &next, 20.0, Color::WHITE, |bounds| {
+                center(bounds, title_y + 60.0)
+            })?;
+        Ok(())
+    }
+
+    fn draw_game_over_overlay(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+    ) -> GameResult {
+        let screen_width = self.config.screen_width();
+
+        // Create semi-transparent overlay covering the game area
+        let overlay_rect = Rect::new(0.0, 0.0, screen_width, self.config.screen_height());
+        let overlay_mesh = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            overlay_rect,
+            Color::new(0.0, 0.0, 0.0, 0.7),
+        )?;
+        canvas.draw(&overlay_mesh, graphics::DrawParam::default());
+
+        // Create game over text
+        // note TextFragment is basically a string (or substring) with formatting options
+        // this confused me at first it seems redundant - but imagine you wanted two or more colors! duh
+        // these come out of the text cache, which measures each one the first time it's drawn
+        let center = |bounds: mint::Vector2<f32>, y: f32| [(screen_width - bounds.x) / 2.0, y];
+        let game_over_y = self.config.screen_height() / 2.0 - 80.0;
+        let (title, color) = if self.levels.as_ref().is_some_and(|run| run.cleared) {
+            ("ALL LEVELS CLEARED", Color::GREEN)
+        } else if self.won() {
+            ("BOARD FILLED", Color::GREEN)
+        } else {
+            ("GAME OVER", Color::RED)
+        };
+        self.text_cache
+            .draw(ctx, canvas, title, 48.0, color, |bounds| {
+                center(bounds, game_over_y) // so easy to center text
+            })?;
+
+        // Create final score text - same thing basically
+        let score_y = game_over_y + 60.0; // just a bit below the game over text
+        let final_score = format!("Final Score: {}", self.score);
+        self.text_cache
+            .draw(ctx, canvas, &final_score, 24.0, Color::WHITE, |bounds| {
+                center(bounds, score_y)
+            })?;
+
+        // Create survival time text, with the best time for comparison
+        let time_y = score_y + 30.0;
+        let final_time = format!(
+            "Time: {} (Best: {})",
+            format_time(self.elapsed),
+            format_time(self.best_time)
+        );
+        self.text_cache
+            .draw(ctx, canvas, &final_time, 18.0, Color::WHITE, |bounds| {
+                center(bounds, time_y)
+            })?;
+
+        // Show "NEW HIGH SCORE!" if applicable
+        if self.score == self.high_score && self.score > 0 {
+            let gold = Color::new(1.0, 0.84, 0.0, 1.0);
+            self.text_cache.draw(
+                ctx,
+                canvas,
+                "🎉 NEW HIGH SCORE! 🎉",
+                20.0,
+                gold,
+                |bounds| center(bounds, time_y + 30.0),
+            )?;
+        }
+
+        // Create restart instruction text
+        let generated = self
+            .levels
+            .as_ref()
+            .is_some_and(|run| run.level().seed.is_some());
+        let restart = if generated {
+            "Press Ctrl+R to restart, G for a new level"
+        } else if self.levels.is_some() {
+            "Press Ctrl+R to restart, L for levels"
+        } else {
+            "Press Ctrl+R to restart, Esc for the menu"
+        };
+        self.text_cache
+            .draw(ctx, canvas, restart, 18.0, Color::YELLOW, |bounds| {
+                center(bounds, time_y + 60.0)
+            })?;
+
+        Ok(())
+    }
+}
+
+// How each kind of food is drawn
+impl FoodKind {
+    pub fn color(&self, theme: &Theme) -> Color {
+        match self {
+            FoodKind::Normal => theme.food,
+            FoodKind::Golden => Color::new(1.0, 0.84, 0.0, 1.0),
+            FoodKind::SlowDown => Color::new(0.35, 0.75, 1.0, 1.0),
+            FoodKind::Shrink => Color::new(0.75, 0.4, 1.0, 1.0),
+            FoodKind::Poison => Color::new(0.45, 0.6, 0.1, 1.0),
+        }
+    }
+}
+
+// Opacity for something shown for `duration` seconds that fades in and out over `fade` seconds
+// can be reused by any screen that wants to ease in/out instead of popping
+pub fn fade_alpha(time: f64, duration: f64, fade: f64) -> f32 {
+    if fade <= 0.0 {
+        return if (0.0..duration).contains(&time) {
+            1.0
+        } else {
+            0.0
+        };
+    }
+    let fade_in = time / fade;
+    let fade_out = (duration - time) / fade;
+    fade_in.min(fade_out).clamp(0.0, 1.0) as f32
+}
+
+// How many texts a TextCache keeps before starting over - plenty for a screen's labels
+// plus the last few values of the score and clock
+pub const TEXT_CACHE_CAPACITY: usize = 128;
+
+// A string in a size and color, as a TextCache key
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TextKey {
+    pub string: String,
+    scale_bits: u32,
+    rgba: [u8; 4],
+}
+
+impl TextKey {
+    pub fn new(string: &str, scale: f32, color: Color) -> Self {
+        let (r, g, b, a) = color.to_rgba();
+        Self {
+            string: string.to_string(),
+            scale_bits: scale.to_bits(),
+            rgba: [r, g, b, a],
+        }
+    }
+}
+
+#[derive(Default)]
+struct TextCacheEntries {
+    texts: std::collections::HashMap<TextKey, (Text, mint::Vector2<f32>)>,
+    hits: u64,
+    misses: u64,
+}
+
+// Texts already laid out and measured, so labels that don't change from frame to frame
+// (or change once a second, like the clock) aren't rebuilt every frame. Behind a lock so
+// a game can be drawn through `&self` and still be shared with other threads
+#[derive(Default)]
+pub struct TextCache {
+    entries: std::sync::Mutex<TextCacheEntries>,
+}
+
+// A copy of a game (a replay keyframe, a rewind point) starts with an empty cache
+impl Clone for TextCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl TextCache {
+    // Draw a string, laid out and measured the first time it's seen. `place` gets the
+    // text's size and says where it goes, and the size is returned for anything around it
+    pub fn draw(
+        &self,
+        ctx: &Context,
+        canvas: &mut graphics::Canvas,
+        string: &str,
+        scale: f32,
+        color: Color,
+        place: impl FnOnce(mint::Vector2<f32>) -> [f32; 2],
+    ) -> GameResult<mint::Vector2<f32>> {
+        let key = TextKey::new(string, scale, color);
+        let build = || {
+            let text = Text::new(
+                TextFragment::new(string)
+                    .color(color)
+                    .scale(graphics::PxScale::from(scale)),
+            );
+            let bounds = text.measure(ctx)?;
+            Ok((text, bounds))
+        };
+        self.with_text(key, build, |text, bounds| {
+            canvas.draw(text, graphics::DrawParam::default().dest(place(bounds)));
+            bounds
+        })
+    }
+
+    // Look up a text, building it on a miss, and hand it to `use_text`
+    pub fn with_text<R>(
+        &self,
+        key: TextKey,
+        build: impl FnOnce() -> GameResult<(Text, mint::Vector2<f32>)>,
+        use_text: impl FnOnce(&Text, mint::Vector2<f32>) -> R,
+    ) -> GameResult<R> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.texts.contains_key(&key) {
+            entries.hits += 1;
+        } else {
+            entries.misses += 1;
+            if entries.texts.len() >= TEXT_CACHE_CAPACITY {
+                entries.texts.clear();
+            }
+            let built = build()?;
+            entries.texts.insert(key.clone(), built);
+        }
+        let (text, bounds) = &entries.texts[&key];
+        Ok(use_text(text, *bounds))
+    }
+
+    // Entries held, hits and misses so far
+    pub fn stats(&self) -> (usize, u64, u64) {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        (entries.texts.len(), entries.hits, entries.misses)
+    }
+
+    // For the debug overlay
+    pub fn stats_line(&self) -> String {
+        let (len, hits, misses) = self.stats();
+        let lookups = (hits + misses).max(1);
+        format!(
+            "text cache: {}/{} entries, {:.1}% hits ({} misses)",
+            len,
+            TEXT_CACHE_CAPACITY,
+            hits as f64 * 100.0 / lookups as f64,
+            misses
+        )
+    }
+}
+
+// One instance of the 1x1 white image stretched over a cell and tinted its color
+pub fn cell_instance(rect: Rect, color: Color) -> graphics::DrawParam {
+    graphics::DrawParam::default()
+        .dest([rect.x, rect.y])
+        .scale([rect.w, rect.h])
+        .color(color)
+}
+
+// Idle "breathing" amount for a segment, from 0.0 to 1.0
+// each segment lags the one in front so the pulse ripples from head to tail
+pub fn idle_pulse(anim_time: f64, segment_index: usize) -> f32 {
+    let phase = anim_time * 2.0 - segment_index as f64 * 0.4;
+    ((phase.sin() + 1.0) / 2.0) as f32
+}
+
+// The head blinks for a short moment every few seconds
+pub fn is_blinking(anim_time: f64) -> bool {
+    anim_time % 3.0 > 2.85
+}
+
+impl DualGame {
+    // Per-board player label, the target, and a banner on the winner's board
+    fn draw_versus_hud(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        index: usize,
+        target_score: u32,
+    ) -> GameResult {
+        let board_height = self.config().screen_height();
+        let label_text = Text::new(
+            TextFragment::new(format!(
+                "{} - first to {} - rounds won: {}/{}",
+                self.player_names[index],
+                target_score,
+                self.series.wins(index),
+                self.series.wins_needed()
+            ))
+            .color(Color::new(1.0, 1.0, 1.0, 0.7))
+            .scale(graphics::PxScale::from(16.0)),
+        );
+        canvas.draw(
+            &label_text,
+            graphics::DrawParam::default().dest([10.0, board_height - 26.0]),
+        );
+
+        if self.winner() == Some(index) {
+            let winner_text = Text::new(
+                TextFragment::new(format!("{} WINS!", self.player_names[index]))
+                    .color(Color::new(1.0, 0.84, 0.0, 1.0))
+                    .scale(graphics::PxScale::from(32.0)),
+            );
+            let winner_bounds = winner_text.measure(ctx)?;
+            let winner_x = (self.config().screen_width() - winner_bounds.x) / 2.0;
+            canvas.draw(
+                &winner_text,
+                graphics::DrawParam::default().dest([winner_x, 50.0]),
+            );
+        }
+
+        Ok(())
+    }
+
+    // End of match: who won each round, the final tally and how to rematch
+    fn draw_match_summary(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let Some(match_winner) = self.series.match_winner() else {
+            return Ok(());
+        };
+        let window_width = Self::window_width(self.config());
+        let window_height = self.config().screen_height();
+
+        let backdrop = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, window_width, window_height),
+            Color::new(0.0, 0.0, 0.0, 0.85),
+        )?;
+        canvas.draw(&backdrop, graphics::DrawParam::default());
+
+        let mut lines = vec![(
+            format!(
+                "{} wins the match {}-{}",
+                self.player_names[match_winner],
+                self.series.wins(match_winner),
+                self.series.wins(1 - match_winner)
+            ),
+            36.0,
+            Color::new(1.0, 0.84, 0.0, 1.0),
+        )];
+        for (round, winner) in self.series.round_winners.iter().enumerate() {
+            let result = match winner {
+                Some(player) => format!("Round {}: {}", round + 1, self.player_names[*player]),
+                None => format!("Round {}: draw", round + 1),
+            };
+            lines.push((result, 20.0, Color::WHITE));
+        }
+        if let Some(changes) = self.rating_changes {
+            for (name, (before, after)) in self.player_names.iter().zip(changes) {
+                lines.push((
+                    format!(
+                        "{}: {:.0} -> {:.0} ({:+.0})",
+                        name,
+                        before,
+                        after,
+                        after - before
+                    ),
+                    18.0,
+                    Color::new(0.6, 0.8, 1.0, 1.0),
+                ));
+            }
+        }
+        lines.push(("Press Enter for a rematch".to_string(), 18.0, Color::YELLOW));
+
+        let mut y = 60.0;
+        for (line, size, color) in lines {
+            let text = Text::new(
+                TextFragment::new(line)
+                    .color(color)
+                    .scale(graphics::PxScale::from(size)),
+            );
+            let bounds = text.measure(ctx)?;
+            canvas.draw(
+                &text,
+                graphics::DrawParam::default().dest([(window_width - bounds.x) / 2.0, y]),
+            );
+            y += size + 14.0;
+        }
+
+        Ok(())
+    }
+
+    // Both boards side by side, with each player's HUD and the match summary once it's won
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas = graphics::Canvas::from_frame(ctx, Color::new(0.15, 0.15, 0.15, 1.0));
+        let config = *self.config();
+        let window_rect = Rect::new(
+            0.0,
+            0.0,
+            Self::window_width(&config),
+            config.screen_height(),
+        );
+        let board_rect = Rect::new(0.0, 0.0, config.screen_width(), config.screen_height());
+
+        for (index, board) in self.boards.iter().enumerate() {
+            // shift the view so the board draws at its own offset with its usual coordinates
+            let mut view = window_rect;
+            view.x = -Self::board_offset(&config, index);
+            canvas.set_screen_coordinates(view);
+
+            let background = board.theme.background_at(board.elapsed);
+            let background_mesh =
+                Mesh::new_rectangle(ctx, DrawMode::fill(), board_rect, background)?;
+            canvas.draw(&background_mesh, graphics::DrawParam::default());
+            board.draw_board(ctx, &mut canvas)?;
+
+            if let DualMode::Versus { target_score } = self.mode {
+                self.draw_versus_hud(ctx, &mut canvas, index, target_score)?;
+            }
+        }
+
+        // Match summary over the whole window once someone has taken the series
+        canvas.set_screen_coordinates(window_rect);
+        if self.series.match_winner().is_some() {
+            self.draw_match_summary(ctx, &mut canvas)?;
+        }
+
+        canvas.finish(ctx)?;
+        Ok(())
+    }
+}
+
+impl Hotseat {
+    // Who's up next between turns, or the comparison table once everyone's played
+    fn draw_hotseat_overlay(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let screen_width = self.game.config.screen_width();
+        let screen_height = self.game.config.screen_height();
+
+        let mut lines = Vec::new();
+        let mut y = screen_height - 60.0;
+        if self.is_finished() {
+            let backdrop = Mesh::new_rectangle(
+                ctx,
+                DrawMode::fill(),
+                Rect::new(0.0, 0.0, screen_width, screen_height),
+                Color::new(0.0, 0.0, 0.0, 0.9),
+            )?;
+            canvas.draw(&backdrop, graphics::DrawParam::default());
+
+            lines.push(("Results".to_string(), 36.0, Color::new(1.0, 0.84, 0.0, 1.0)));
+            for (place, result) in self.standings().iter().enumerate() {
+                lines.push((
+                    format!(
+                        "{}. {} - {} pts, {}",
+                        place + 1,
+                        result.player,
+                        result.score,
+                        format_time(result.time)
+                    ),
+                    20.0,
+                    Color::WHITE,
+                ));
+            }
+            lines.push(("Press Enter to play again".to_string(), 18.0, Color::YELLOW));
+            y = 40.0;
+        } else if let Some(player) = self.current_player() {
+            if self.turn_recorded {
+                lines.push((
+                    format!("Next up: {} - press Enter", player),
+                    20.0,
+                    Color::YELLOW,
+                ));
+            } else {
+                lines.push((format!("{}'s turn", player), 18.0, Color::WHITE));
+            }
+        }
+
+        for (line, size, color) in lines {
+            let text = Text::new(
+                TextFragment::new(line)
+                    .color(color)
+                    .scale(graphics::PxScale::from(size)),
+            );
+            let bounds = text.measure(ctx)?;
+            canvas.draw(
+                &text,
+                graphics::DrawParam::default().dest([(screen_width - bounds.x) / 2.0, y]),
+            );
+            y += size + 10.0;
+        }
+
+        Ok(())
+    }
+
+    // The name prompt, or the board with whose turn it is and the results over it
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas =
+            graphics::Canvas::from_frame(ctx, self.game.theme.background_at(self.game.elapsed));
+        match &self.naming {
+            Some(naming) => naming.draw(ctx, &mut canvas, [20.0, 20.0])?,
+            None => {
+                self.game.draw_board(ctx, &mut canvas)?;
+                self.draw_hotseat_overlay(ctx, &mut canvas)?;
+            }
+        }
+        canvas.finish(ctx)?;
+        Ok(())
+    }
+}
+
+impl ReplayRecorder {
+    // F3: frame times, memory and the outbox over the live game
+    fn draw_debug_overlay(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let mut lines = self.profiler.summary_lines();
+        lines.push(self.memory_line());
+        lines.push(self.game.text_cache.stats_line());
+        lines.push(self.outbox.status_line());
+        lines.push("F4 saves this as JSON, F3 hides it".to_string());
+        let board = Rect::new(
+            0.0,
+            0.0,
+            self.game.config.screen_width(),
+            20.0 * lines.len() as f32 + 10.0,
+        );
+        let dim =
+            Mesh::new_rectangle(ctx, DrawMode::fill(), board, Color::new(0.0, 0.0, 0.0, 0.6))?;
+        canvas.draw(&dim, graphics::DrawParam::default());
+        for (row, line) in lines.into_iter().enumerate() {
+            let text = Text::new(
+                TextFragment::new(line)
+                    .color(Color::new(1.0, 1.0, 1.0, 0.8))
+                    .scale(graphics::PxScale::from(14.0)),
+            );
+            canvas.draw(
+                &text,
+                graphics::DrawParam::default().dest([10.0, 5.0 + 20.0 * row as f32]),
+            );
+        }
+        Ok(())
+    }
+
+    // The levels down the board, each with its thumbnail and the player's best on it, or
+    // that it's locked. As many as fit are shown, scrolling to keep the focused one in view
+    fn draw_level_select(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        select: &LevelSelect,
+    ) -> GameResult {
+        let Some(run) = &self.game.levels else {
+            return Ok(());
+        };
+        let (width, height) = (
+            self.game.config.screen_width(),
+            self.game.config.screen_height(),
+        );
+        let dim = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, width, height),
+            Color::new(0.0, 0.0, 0.0, 0.85),
+        )?;
+        canvas.draw(&dim, graphics::DrawParam::default());
+        let line = |text: &str, size: f32, color: Color, dest: [f32; 2]| {
+            let text = Text::new(
+                TextFragment::new(text)
+                    .scale(graphics::PxScale::from(size))
+                    .color(color),
+            );
+            (text, graphics::DrawParam::default().dest(dest))
+        };
+        let (text, param) = line("Levels", 24.0, Color::WHITE, [10.0, 8.0]);
+        canvas.draw(&text, param);
+
+        let visible = (((height - 70.0) / LEVEL_ROW_HEIGHT) as usize).max(1);
+        let first = (select.focus + 1).saturating_sub(visible);
+        let [box_width, box_height] = LEVEL_THUMBNAIL_SIZE;
+        for (index, level) in run.levels.iter().enumerate().skip(first).take(visible) {
+            let top = 42.0 + LEVEL_ROW_HEIGHT * (index - first) as f32;
+            let unlocked = select.progress.is_unlocked(&run.levels, index);
+            let color = match (index == select.focus, unlocked) {
+                (true, _) => Color::YELLOW,
+                (false, true) => Color::WHITE,
+                (false, false) => Color::new(1.0, 1.0, 1.0, 0.4),
+            };
+            let frame = Mesh::new_rectangle(
+                ctx,
+                DrawMode::stroke(2.0),
+                Rect::new(8.0, top - 2.0, box_width + 4.0, box_height + 4.0),
+                color,
+            )?;
+            canvas.draw(&frame, graphics::DrawParam::default());
+            select.thumbnails[index].draw(ctx, canvas, Rect::new(10.0, top, box_width, box_height));
+            let record = select.progress.get(&level.name);
+            let status = if !unlocked {
+                "Locked".to_string()
+            } else if record.best_score == 0 && record.best_time == 0.0 {
+                format!("Score {} to clear", level.target_score)
+            } else {
+                format!(
+                    "Best {} - {}{}",
+                    record.best_score,
+                    format_time(record.best_time),
+                    if record.cleared { " - Cleared" } else { "" }
+                )
+            };
+            let left = 20.0 + box_width;
+            let mut name = format!("{}. {}", index + 1, level.name);
+            if let Some(difficulty) = &select.difficulty[index] {
+                name += &format!("  {}", difficulty.stars_text());
+            }
+            let (text, param) = line(&name, 18.0, color, [left, top]);
+            canvas.draw(&text, param);
+            let (text, param) = line(&status, 14.0, color, [left, top + 24.0]);
+            canvas.draw(&text, param);
+        }
+        let (text, param) = line(
+            "Enter plays, Esc goes back",
+            14.0,
+            Color::WHITE,
+            [10.0, height - 22.0],
+        );
+        canvas.draw(&text, param);
+        Ok(())
+    }
+
+    // The slots down the board, each with its thumbnail, score and when it was saved
+    fn draw_slot_menu(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        menu: &SlotMenu,
+    ) -> GameResult {
+        let (width, height) = (
+            self.game.config.screen_width(),
+            self.game.config.screen_height(),
+        );
+        let dim = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, width, height),
+            Color::new(0.0, 0.0, 0.0, 0.8),
+        )?;
+        canvas.draw(&dim, graphics::DrawParam::default());
+        let (title, hint) = if menu.saving {
+            ("Save Game", "Enter saves, Delete deletes, Esc goes back")
+        } else {
+            ("Load Game", "Enter loads, Delete deletes, Esc goes back")
+        };
+        let line = |text: &str, size: f32, color: Color, dest: [f32; 2]| {
+            let text = Text::new(
+                TextFragment::new(text)
+                    .scale(graphics::PxScale::from(size))
+                    .color(color),
+            );
+            (text, graphics::DrawParam::default().dest(dest))
+        };
+        let (text, param) = line(title, 28.0, Color::WHITE, [20.0, 15.0]);
+        canvas.draw(&text, param);
+
+        // thumbnails are a pixel to a cell, blown up to fill the frames - less on boards
+        // too tall for all the slots to fit down the window
+        let (columns, rows) = (
+            self.game.config.grid_width as f32,
+            self.game.config.grid_height as f32,
+        );
+        let scale = THUMBNAIL_SCALE.min(((height - 100.0) / SAVE_SLOTS as f32 - 15.0) / rows);
+        let row_height = rows * scale + 15.0;
+        for (slot, saved) in menu.slots.iter().enumerate() {
+            let top = 60.0 + row_height * slot as f32;
+            let focused = slot == menu.focus;
+            let color = if focused { Color::YELLOW } else { Color::WHITE };
+            let frame = Mesh::new_rectangle(
+                ctx,
+                DrawMode::stroke(2.0),
+                Rect::new(18.0, top - 2.0, columns * scale + 4.0, rows * scale + 4.0),
+                color,
+            )?;
+            canvas.draw(&frame, graphics::DrawParam::default());
+            let label = match saved {
+                Some(saved) => {
+                    let area = Rect::new(20.0, top, columns * scale, rows * scale);
+                    saved.thumbnail.draw(ctx, canvas, area);
+                    saved.label()
+                }
+                None => "Empty".to_string(),
+            };
+            let left = 30.0 + columns * scale;
+            let (text, param) = line(&format!("Slot {}", slot + 1), 22.0, color, [left, top]);
+            canvas.draw(&text, param);
+            let (text, param) = line(&label, 16.0, color, [left, top + 28.0]);
+            canvas.draw(&text, param);
+        }
+        canvas.set_default_sampler();
+        let (text, param) = line(hint, 14.0, Color::WHITE, [20.0, height - 25.0]);
+        canvas.draw(&text, param);
+        Ok(())
+    }
+
+    // The scrubber along the bottom and what the game looked like on that tick
+    fn draw_timeline(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let Some(travel) = &self.time_travel else {
+            return Ok(());
+        };
+        let board_width = self.game.config.screen_width();
+        let bar_y = self.game.config.screen_height() - SCRUB_BAR_HEIGHT;
+        for (width, color) in [
+            (board_width, Color::new(1.0, 1.0, 1.0, 0.2)),
+            (
+                board_width * self.travel_progress(),
+                Color::new(0.4, 0.8, 1.0, 0.8),
+            ),
+        ] {
+            if width > 0.0 {
+                let bar = Mesh::new_rectangle(
+                    ctx,
+                    DrawMode::fill(),
+                    Rect::new(0.0, bar_y, width, SCRUB_BAR_HEIGHT),
+                    color,
+                )?;
+                canvas.draw(&bar, graphics::DrawParam::default());
+            }
+        }
+
+        let game = &travel.game;
+        let lines = [
+            format!("tick {}/{}", travel.tick, self.replay.tick_count()),
+            format!("score {}  length {}", game.score, game.snake.len()),
+            format!(
+                "head {}  food {}  heading {:?}",
+                game.snake[0].to_text(),
+                game.food.position.to_text(),
+                game.direction
+            ),
+            format!("speed {}  hash {:016x}", game.speed, game.state_hash()),
+            self.memory_line(),
+            "Left/Right step (Shift for 10), drag to scrub, Enter plays on from here, F9 back"
+                .to_string(),
+        ];
+        let mut y = bar_y - 20.0 * lines.len() as f32;
+        for line in lines {
+            let text = Text::new(
+                TextFragment::new(line)
+                    .color(Color::new(1.0, 1.0, 1.0, 0.8))
+                    .scale(graphics::PxScale::from(14.0)),
+            );
+            canvas.draw(&text, graphics::DrawParam::default().dest([10.0, y]));
+            y += 20.0;
+        }
+        Ok(())
+    }
+
+    // The game with the touch controls, and whichever overlay is open over it
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let started = std::time::Instant::now();
+        let mut canvas =
+            graphics::Canvas::from_frame(ctx, self.game.theme.background_at(self.game.elapsed));
+        if let Some(travel) = &self.time_travel {
+            travel.game.draw_board(ctx, &mut canvas)?;
+            self.draw_timeline(ctx, &mut canvas)?;
+            canvas.finish(ctx)?;
+            self.profiler.record(FramePhase::Render, started.elapsed());
+            self.profiler.end_frame(std::time::Instant::now());
+            return Ok(());
+        }
+        self.game.draw_board(ctx, &mut canvas)?;
+        let board_drawn = std::time::Instant::now();
+        self.touch.draw(ctx, &mut canvas, &self.game.config)?;
+        if self.paused {
+            let board = Rect::new(
+                0.0,
+                0.0,
+                self.game.config.screen_width(),
+                self.game.config.screen_height(),
+            );
+            let dim =
+                Mesh::new_rectangle(ctx, DrawMode::fill(), board, Color::new(0.0, 0.0, 0.0, 0.6))?;
+            canvas.draw(&dim, graphics::DrawParam::default());
+            let text = Text::new(
+                TextFragment::new("PAUSED - tap or press a key to carry on")
+                    .scale(graphics::PxScale::from(24.0)),
+            );
+            let bounds = text.measure(ctx)?;
+            canvas.draw(
+                &text,
+                graphics::DrawParam::default()
+                    .dest([(board.w - bounds.x) / 2.0, (board.h - bounds.y) / 2.0]),
+            );
+        }
+        if let Some(menu) = &self.slot_menu {
+            self.draw_slot_menu(ctx, &mut canvas, menu)?;
+        }
+        if let Some(select) = &self.level_select {
+            self.draw_level_select(ctx, &mut canvas, select)?;
+        }
+        if let Some(dialog) = &self.confirm {
+            let board = Rect::new(
+                0.0,
+                0.0,
+                self.game.config.screen_width(),
+                self.game.config.screen_height(),
+            );
+            dialog.draw(ctx, &mut canvas, board)?;
+        }
+        if self.show_debug {
+            self.draw_debug_overlay(ctx, &mut canvas)?;
+        }
+        let ui_drawn = std::time::Instant::now();
+        canvas.finish(ctx)?;
+        // submitting the canvas is the GPU work, so it counts as rendering
+        self.profiler.record(
+            FramePhase::Render,
+            board_drawn.duration_since(started) + ui_drawn.elapsed(),
+        );
+        self.profiler
+            .record(FramePhase::Ui, ui_drawn.duration_since(board_drawn));
+        self.profiler.end_frame(std::time::Instant::now());
+        Ok(())
+    }
+}
+
+impl Panel {
+    // Draw top to bottom from `origin`, the focused widget (or list row) in yellow with a
+    // ring around it, so it can be found without a mouse
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        origin: [f32; 2],
+    ) -> GameResult {
+        let mut y = origin[1];
+        for (index, widget) in self.widgets.iter().enumerate() {
+            let focused = self.focus == Some(index);
+            let selected_row = match widget {
+                Widget::List { selected, .. } => Some(*selected),
+                _ => None,
+            };
+            for (line, size, row) in widget.lines(self.row_size) {
+                let color = match widget {
+                    Widget::Label { color, .. } => *color,
+                    _ if focused && row == selected_row => Color::YELLOW,
+                    _ if row.is_some() && row == selected_row => Color::WHITE,
+                    _ => Color::new(1.0, 1.0, 1.0, 0.7),
+                };
+                let text = Text::new(
+                    TextFragment::new(line)
+                        .color(color)
+                        .scale(graphics::PxScale::from(size)),
+                );
+                if focused && row == selected_row {
+                    let bounds = text.measure(ctx)?;
+                    let ring = Mesh::new_rectangle(
+                        ctx,
+                        DrawMode::stroke(2.0),
+                        Rect::new(origin[0] - 6.0, y - 4.0, bounds.x + 12.0, size + 8.0),
+                        Color::YELLOW,
+                    )?;
+                    canvas.draw(&ring, graphics::DrawParam::default());
+                }
+                canvas.draw(&text, graphics::DrawParam::default().dest([origin[0], y]));
+                y += size + 8.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: Copy> ConfirmDialog<T> {
+    // Dim all of `area` and put the question in the middle of it
+    pub fn draw(&self, ctx: &mut Context, canvas: &mut graphics::Canvas, area: Rect) -> GameResult {
+        let backdrop =
+            Mesh::new_rectangle(ctx, DrawMode::fill(), area, Color::new(0.0, 0.0, 0.0, 0.75))?;
+        canvas.draw(&backdrop, graphics::DrawParam::default());
+        let height = 32.0 + 30.0 * self.buttons.len() as f32;
+        let top = area.y + (area.h - height) / 2.0;
+        self.panel().draw(ctx, canvas, [area.x + 40.0, top])
+    }
+}
+
+impl ReplayBrowser {
+    fn draw_playback_hud(
+        player: &ReplayPlayer,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+    ) -> GameResult {
+        // scrub bar along the bottom - click it to jump
+        let config = &player.current().config;
+        let board_width = config.screen_width();
+        let bar_y = config.screen_height() - SCRUB_BAR_HEIGHT;
+        for (width, color) in [
+            (board_width, Color::new(1.0, 1.0, 1.0, 0.2)),
+            (
+                board_width * player.progress(),
+                Color::new(1.0, 0.84, 0.0, 0.8),
+            ),
+        ] {
+            if width > 0.0 {
+                let bar = Mesh::new_rectangle(
+                    ctx,
+                    DrawMode::fill(),
+                    Rect::new(0.0, bar_y, width, SCRUB_BAR_HEIGHT),
+                    color,
+                )?;
+                canvas.draw(&bar, graphics::DrawParam::default());
+            }
+        }
+
+        let status = format!(
+            "tick {}/{}  {}x{}  Space/A pause, S speed, Left/Right jump to food, Esc/B back",
+            player.tick(),
+            player.replay.tick_count(),
+            player.speed,
+            if player.paused { "  PAUSED" } else { "" }
+        );
+        let text = Text::new(
+            TextFragment::new(status)
+                .color(Color::new(1.0, 1.0, 1.0, 0.7))
+                .scale(graphics::PxScale::from(14.0)),
+        );
+        canvas.draw(
+            &text,
+            graphics::DrawParam::default().dest([10.0, bar_y - 20.0]),
+        );
+        Ok(())
+    }
+
+    // The replay being played back, or the list with a preview of the picked replay
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas = graphics::Canvas::from_frame(ctx, Theme::default().background);
+        match &self.player {
+            Some(player) => {
+                // the replay's board fills the window, whatever size either is
+                let board = player.current();
+                canvas.set_screen_coordinates(Rect::new(
+                    0.0,
+                    0.0,
+                    board.config.screen_width(),
+                    board.config.screen_height(),
+                ));
+                board.draw_board(ctx, &mut canvas)?;
+                Self::draw_playback_hud(player, ctx, &mut canvas)?;
+            }
+            None => {
+                self.panel().draw(ctx, &mut canvas, [20.0, 20.0])?;
+                let window = (self.config.screen_width(), self.config.screen_height());
+                let grid = self
+                    .replays
+                    .get(self.selected)
+                    .map(|replay| replay.config.grid());
+                if let (Some((columns, rows)), Some(preview)) = (grid, self.preview(Some(ctx))) {
+                    // in the bottom corner, shrunk to a third of the window for big boards
+                    let cell = (THUMBNAIL_SCALE * 2.0)
+                        .min(window.0 / 3.0 / columns as f32)
+                        .min(window.1 / 3.0 / rows as f32);
+                    let (width, height) = (columns as f32 * cell, rows as f32 * cell);
+                    let area = Rect::new(
+                        window.0 - width - 20.0,
+                        window.1 - height - 40.0,
+                        width,
+                        height,
+                    );
+                    preview.draw(ctx, &mut canvas, area);
+                }
+            }
+        }
+        canvas.finish(ctx)?;
+        Ok(())
+    }
+}
+
+impl GhostRace {
+    fn draw_ghost(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let tick = self.run.path.len() - 1;
+        let cell_size = self.game.config.cell_size;
+        for segment in self.ghost.body_at(tick) {
+            let rect = Rect::new(
+                segment.x as f32 * cell_size,
+                segment.y as f32 * cell_size,
+                cell_size - 2.0,
+                cell_size - 2.0,
+            );
+            let mesh =
+                Mesh::new_rectangle(ctx, DrawMode::fill(), rect, Color::new(1.0, 1.0, 1.0, 0.25))?;
+            canvas.draw(&mesh, graphics::DrawParam::default());
+        }
+
+        if let Some(split) = self.last_split {
+            // green when ahead of the ghost, red when behind
+            let color = if split <= 0.0 {
+                Color::GREEN
+            } else {
+                Color::RED
+            };
+            let text = Text::new(
+                TextFragment::new(format!("{:+.1}s", split))
+                    .color(color)
+                    .scale(graphics::PxScale::from(20.0)),
+            );
+            let bounds = text.measure(ctx)?;
+            canvas.draw(
+                &text,
+                graphics::DrawParam::default()
+                    .dest([self.game.config.screen_width() - bounds.x - 10.0, 10.0]),
+            );
+        }
+        Ok(())
+    }
+
+    // The game with the ghost's run over it
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas =
+            graphics::Canvas::from_frame(ctx, self.game.theme.background_at(self.game.elapsed));
+        self.game.draw_board(ctx, &mut canvas)?;
+        if self.game.screen == Screen::Playing && !self.game.game_over {
+            self.draw_ghost(ctx, &mut canvas)?;
+        }
+        canvas.finish(ctx)?;
+        Ok(())
+    }
+}
+
+impl Kiosk {
+    fn draw_operator_menu(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        selected: usize,
+    ) -> GameResult {
+        let (width, height) = ctx.gfx.drawable_size();
+        let dim = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, width, height),
+            Color::new(0.0, 0.0, 0.0, 0.85),
+        )?;
+        canvas.draw(&dim, graphics::DrawParam::default());
+        self.operator_panel(selected)
+            .draw(ctx, canvas, [40.0, 40.0])
+    }
+
+    // The attract loop, the credits, and the operator menu when it's open
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas = graphics::Canvas::from_frame(ctx, Color::BLACK);
+        let (width, height) = ctx.gfx.drawable_size();
+        let snake = self.player.as_ref().unwrap_or(&self.attract.snake);
+        let area = fullscreen_board_area(&snake.game.config, width, height);
+        snake.draw_in(ctx, &mut canvas, area)?;
+
+        // blink the attract message, and keep the credits up during a run
+        let blink_on = ((self.attract.snake.game.anim_time * 2.0) as u64).is_multiple_of(2);
+        let message = match &self.player {
+            Some(_) if !self.config.free_play => format!("CREDITS {}", self.credits),
+            Some(_) => String::new(),
+            None if blink_on => self.attract_message(),
+            None => String::new(),
+        };
+        let text =
+            Text::new(TextFragment::new(message).scale(graphics::PxScale::from(area.h / 14.0)));
+        let bounds = text.measure(ctx)?;
+        canvas.draw(
+            &text,
+            graphics::DrawParam::default()
+                .dest([(width - bounds.x) / 2.0, area.bottom() - bounds.y * 2.0]),
+        );
+
+        if let Some(selected) = self.operator_menu {
+            self.draw_operator_menu(ctx, &mut canvas, selected)?;
+        }
+        if let Some(dialog) = &self.confirm {
+            dialog.draw(ctx, &mut canvas, Rect::new(0.0, 0.0, width, height))?;
+        }
+        canvas.finish(ctx)
+    }
+}
+
+impl PackBrowser {
+    // The list of packs
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas = graphics::Canvas::from_frame(ctx, Theme::default().background);
+        self.panel().draw(ctx, &mut canvas, [20.0, 20.0])?;
+        canvas.finish(ctx)?;
+        Ok(())
+    }
+}
+
+impl Screensaver {
+    // The bot's game filling the window
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas = graphics::Canvas::from_frame(ctx, Color::BLACK);
+        let (width, height) = ctx.gfx.drawable_size();
+        let area = fullscreen_board_area(&self.snake.game.config, width, height);
+        self.snake.draw_in(ctx, &mut canvas, area)?;
+        canvas.finish(ctx)
+    }
+}
+
+impl EmbeddedSnake {
+    // The embedded game inside `area` of someone else's canvas
+    pub fn draw_in(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        area: Rect,
+    ) -> GameResult {
+        // stretch the view so board coordinates land inside `area`, then put it back
+        let (window_width, window_height) = ctx.gfx.drawable_size();
+        let board_rect = Rect::new(
+            0.0,
+            0.0,
+            self.game.config.screen_width(),
+            self.game.config.screen_height(),
+        );
+        let scale_x = area.w / board_rect.w;
+        let scale_y = area.h / board_rect.h;
+        let host_view = canvas.screen_coordinates();
+        canvas.set_screen_coordinates(Rect::new(
+            -area.x / scale_x,
+            -area.y / scale_y,
+            window_width / scale_x,
+            window_height / scale_y,
+        ));
+
+        let background = self.game.theme.background_at(self.game.elapsed);
+        let background_mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), board_rect, background)?;
+        canvas.draw(&background_mesh, graphics::DrawParam::default());
+        let drawn = self.game.draw_board(ctx, canvas);
+
+        canvas.set_screen_coordinates(host_view.unwrap_or(Rect::new(
+            0.0,
+            0.0,
+            window_width,
+            window_height,
+        )));
+        drawn
+    }
+}
+
+impl OnlineGame {
+    // Each player's name under their board, and what the match is waiting on over both
+    fn draw_online_hud(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let config = &self.session.config;
+        let board_height = config.screen_height();
+        for index in 0..self.session.boards.len() {
+            let label = match self.session.mode {
+                NetMode::Versus => self.session.names[index].clone(),
+                NetMode::Coop => self.session.names.join(" & "),
+            };
+            let label = Text::new(
+                TextFragment::new(label)
+                    .color(Color::new(1.0, 1.0, 1.0, 0.7))
+                    .scale(graphics::PxScale::from(16.0)),
+            );
+            canvas.draw(
+                &label,
+                graphics::DrawParam::default().dest([
+                    DualGame::board_offset(config, index) + 10.0,
+                    board_height - 26.0,
+                ]),
+            );
+        }
+
+        // Ping in the top-right corner, and how far the host holds its own turns back
+        let mut ping = self.session.latency.label();
+        if self.session.role == NetRole::Host {
+            ping = format!("{} - delay {}", ping, self.session.input_delay.delay_ticks);
+        }
+        let ping = Text::new(
+            TextFragment::new(ping)
+                .color(Color::new(1.0, 1.0, 1.0, 0.7))
+                .scale(graphics::PxScale::from(16.0)),
+        );
+        let bounds = ping.measure(ctx)?;
+        canvas.draw(
+            &ping,
+            graphics::DrawParam::default()
+                .dest([self.window_width() - bounds.x - 10.0, board_height - 26.0]),
+        );
+
+        if let Some(notice) = &self.session.notice {
+            let text = Text::new(
+                TextFragment::new(notice.as_str())
+                    .color(Color::YELLOW)
+                    .scale(graphics::PxScale::from(24.0)),
+            );
+            let bounds = text.measure(ctx)?;
+            let backdrop = Mesh::new_rectangle(
+                ctx,
+                DrawMode::fill(),
+                Rect::new(0.0, 14.0, self.window_width(), bounds.y + 12.0),
+                Color::new(0.0, 0.0, 0.0, 0.7),
+            )?;
+            canvas.draw(&backdrop, graphics::DrawParam::default());
+            canvas.draw(
+                &text,
+                graphics::DrawParam::default().dest([(self.window_width() - bounds.x) / 2.0, 20.0]),
+            );
+        }
+        Ok(())
+    }
+
+    // Chat in the bottom-left corner, newest last, with what's being typed under it
+    fn draw_chat(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let mut lines: Vec<(String, Color)> = self
+            .session
+            .chat
+            .visible()
+            .map(|(message, alpha)| {
+                (
+                    format!("{}: {}", message.sender, message.text),
+                    Color::new(1.0, 1.0, 1.0, alpha),
+                )
+            })
+            .collect();
+        if self.session.chat.muted {
+            lines.push((
+                "Chat muted - M to unmute".to_string(),
+                Color::new(1.0, 1.0, 1.0, 0.5),
+            ));
+        }
+        if let Some(typing) = &self.typing {
+            lines.push((format!("> {}_", typing), Color::YELLOW));
+        }
+
+        let mut y = self.session.config.screen_height() - 50.0;
+        for (line, color) in lines.into_iter().rev() {
+            let text = Text::new(
+                TextFragment::new(line)
+                    .color(color)
+                    .scale(graphics::PxScale::from(16.0)),
+            );
+            let bounds = text.measure(ctx)?;
+            let backdrop = Mesh::new_rectangle(
+                ctx,
+                DrawMode::fill(),
+                Rect::new(6.0, y - 2.0, bounds.x + 8.0, bounds.y + 4.0),
+                Color::new(0.0, 0.0, 0.0, 0.5 * color.a),
+            )?;
+            canvas.draw(&backdrop, graphics::DrawParam::default());
+            canvas.draw(&text, graphics::DrawParam::default().dest([10.0, y]));
+            y -= bounds.y + 6.0;
+        }
+        Ok(())
+    }
+
+    // Both boards of the match, with the names, the wait and the chat over them
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas = graphics::Canvas::from_frame(ctx, Color::new(0.15, 0.15, 0.15, 1.0));
+        let config = self.session.config;
+        let window_rect = Rect::new(0.0, 0.0, self.window_width(), config.screen_height());
+        let board_rect = Rect::new(0.0, 0.0, config.screen_width(), config.screen_height());
+
+        for (index, board) in self.session.boards.iter().enumerate() {
+            // the same layout as local versus, each board at its own offset
+            let mut view = window_rect;
+            view.x = -DualGame::board_offset(&config, index);
+            canvas.set_screen_coordinates(view);
+
+            let background = board.theme.background_at(board.elapsed);
+            let background_mesh =
+                Mesh::new_rectangle(ctx, DrawMode::fill(), board_rect, background)?;
+            canvas.draw(&background_mesh, graphics::DrawParam::default());
+            board.draw_board(ctx, &mut canvas)?;
+        }
+
+        canvas.set_screen_coordinates(window_rect);
+        self.draw_online_hud(ctx, &mut canvas)?;
+        self.draw_chat(ctx, &mut canvas)?;
+        canvas.finish(ctx)?;
+        Ok(())
+    }
+}
+
+// The board as pixels and text, for the terminal, LED panels and save slot thumbnails
+
+// The board as text: '@' the head, 'o' the body, '*' the food, inside a '#' border
+pub fn ascii_board(game: &GameState) -> String {
+    let border = "#".repeat(game.config.grid_width as usize + 2);
+    let mut board = format!("{}\n", border);
+    for y in 0..game.config.grid_height {
+        board.push('#');
+        for x in 0..game.config.grid_width {
+            let cell = Position::new(x, y);
+            board.push(if game.snake[0] == cell {
+                '@'
+            } else if game.snake.contains(&cell) {
+                'o'
+            } else if game.food.position == cell {
+                '*'
+            } else {
+                ' '
+            });
+        }
+        board.push_str("#\n");
+    }
+    board.push_str(&border);
+    board.push('\n');
+    board
+}
+
+// The board drawn straight into an RGBA buffer, `cell_size` pixels to a cell, for
+// frontends that push pixels instead of meshes. The work grows with the number of
+// pixels, not draw calls, so it stays cheap on big boards
+pub fn rasterize(game: &GameState, frame: &mut [u8], cell_size: usize) {
+    let grid = game.config.grid();
+    assert_eq!(
+        frame.len(),
+        grid.0 * cell_size * grid.1 * cell_size * 4,
+        "frame doesn't match the board size"
+    );
+    paint_board(game, frame, grid, cell_size);
+}
+
+// The background, then every cell of a `grid` sized board that isn't empty
+fn paint_board(game: &GameState, frame: &mut [u8], grid: (usize, usize), cell_size: usize) {
+    let background = rgba(game.theme.background_at(game.elapsed));
+    for pixel in frame.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&background);
+    }
+    for (index, color) in cell_paint(game, grid).into_iter().enumerate() {
+        if let Some(color) = color {
+            paint_cell(frame, grid.0, cell_size, index, color, background);
+        }
+    }
+}
+
+fn rgba(color: Color) -> [u8; 4] {
+    let (r, g, b, a) = color.to_rgba();
+    [r, g, b, a]
+}
+
+// The color rasterize paints over each cell, row by row, or None where the background
+// shows through. Later paints win, so the snake ends up over food and puddles. Cells
+// outside the `grid` (columns, rows) are left out
+fn cell_paint(game: &GameState, (columns, rows): (usize, usize)) -> Vec<Option<[u8; 4]>> {
+    let mut cells = vec![None; columns * rows];
+    let mut paint = |cell: Position, color: [u8; 4]| {
+        if cell.is_within(columns as i32, rows as i32) && game.is_visible(cell) {
+            cells[cell.y as usize * columns + cell.x as usize] = Some(color);
+        }
+    };
+    for (cell, _) in &game.slippery_cells {
+        paint(*cell, rgba(Color::new(0.2, 0.35, 0.7, 1.0)));
+    }
+    for cell in &game.walls {
+        paint(*cell, rgba(Color::new(0.55, 0.55, 0.6, 1.0)));
+    }
+    paint(game.food.position, rgba(game.food.kind.color(&game.theme)));
+    for (index, segment) in game.snake.iter().enumerate().rev() {
+        paint(
+            *segment,
+            rgba(game.theme.segment_color(index, game.snake.len())),
+        );
+    }
+    cells
+}
+
+// Fill the cell at `index` (row by row, `columns` to a row) with a color, and the one
+// pixel gap along its right and bottom edges with the background
+fn paint_cell(
+    frame: &mut [u8],
+    columns: usize,
+    cell_size: usize,
+    index: usize,
+    color: [u8; 4],
+    background: [u8; 4],
+) {
+    let width = columns * cell_size;
+    let (x, y) = (index % columns, index / columns);
+    // leave a one pixel gap between cells when there's room, like the mesh renderer
+    let fill = if cell_size >= 4 {
+        cell_size - 1
+    } else {
+        cell_size
+    };
+    for row in 0..cell_size {
+        let start = ((y * cell_size + row) * width + x * cell_size) * 4;
+        for (column, pixel) in frame[start..start + cell_size * 4]
+            .chunks_exact_mut(4)
+            .enumerate()
+        {
+            let inside = row < fill && column < fill;
+            pixel.copy_from_slice(if inside { &color } else { &background });
+        }
+    }
+}
+
+// A rasterized board kept between frames that only repaints the cells that changed,
+// for displays where pushing every pixel each frame is the slow part (framebuffers,
+// LED walls) and for boards too big to redraw in full. The whole board is repainted
+// when the background color moves, which with a day/night cycle is every so often
+pub struct DirtyRaster {
+    pub grid: (usize, usize), // columns and rows
+    pub cell_size: usize,
+    pub frame: Vec<u8>,
+    painted: Vec<Option<[u8; 4]>>,
+    background: Option<[u8; 4]>,
+}
+
+impl DirtyRaster {
+    // A frame for boards the size `config` says
+    pub fn new(config: &GameConfig, cell_size: usize) -> Self {
+        let grid = config.grid();
+        Self {
+            grid,
+            cell_size,
+            frame: vec![0; grid.0 * cell_size * grid.1 * cell_size * 4],
+            painted: vec![None; config.cell_count()],
+            background: None,
+        }
+    }
+
+    // Size of the frame in pixels
+    pub fn size(&self) -> (usize, usize) {
+        (self.grid.0 * self.cell_size, self.grid.1 * self.cell_size)
+    }
+
+    // Bring the frame up to date with the game, returning the cells that were repainted
+    pub fn update(&mut self, game: &GameState) -> Vec<Position> {
+        let background = rgba(game.theme.background_at(game.elapsed));
+        let full = self.background != Some(background);
+        self.background = Some(background);
+
+        let mut dirty = Vec::new();
+        let grid = self.grid;
+        for (index, color) in cell_paint(game, grid).into_iter().enumerate() {
+            if !full && self.painted[index] == color {
+                continue;
+            }
+            self.painted[index] = color;
+            let cell = color.unwrap_or(background);
+            paint_cell(
+                &mut self.frame,
+                grid.0,
+                self.cell_size,
+                index,
+                cell,
+                background,
+            );
+            dirty.push(Position::new(
+                (index % grid.0) as i32,
+                (index / grid.0) as i32,
+            ));
+        }
+        dirty
+    }
+
+    // Forget what's on the frame so the next update repaints everything, e.g. after
+    // something else drew over the display
+    pub fn invalidate(&mut self) {
+        self.background = None;
+    }
+}
+
+// A small picture of a board, for save slots, the replay browser and the level selector.
+// `of` paints it in software, so tools and tests can make one without a window; `render`
+// draws it offscreen the way the game draws its board when there's a Context to do it with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thumbnail {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>, // RGBA, row by row
+}
+
+impl Thumbnail {
+    // The whole board, whatever its size, at `cell_size` pixels to a cell
+    pub fn of(game: &GameState, cell_size: usize) -> Self {
+        let grid = game.config.grid();
+        let (width, height) = (grid.0 * cell_size, grid.1 * cell_size);
+        let mut pixels = vec![0; width * height * 4];
+        paint_board(game, &mut pixels, grid, cell_size);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    // On the GPU with the game's own board drawing - fog, puddles, the food's pulse and
+    // all - falling back to `of` without a Context or if the GPU can't do it
+    pub fn render(ctx: Option<&mut Context>, game: &GameState, cell_size: usize) -> Self {
+        ctx.and_then(|ctx| Self::render_offscreen(ctx, game, cell_size).ok())
+            .unwrap_or_else(|| Self::of(game, cell_size))
+    }
+
+    fn render_offscreen(ctx: &mut Context, game: &GameState, cell_size: usize) -> GameResult<Self> {
+        let (columns, rows) = (game.config.grid_width, game.config.grid_height);
+        let (width, height) = (columns as usize * cell_size, rows as usize * cell_size);
+        let image = graphics::Image::new_canvas_image(
+            ctx,
+            graphics::ImageFormat::Rgba8UnormSrgb,
+            width as u32,
+            height as u32,
+            1,
+        );
+        let mut canvas = graphics::Canvas::from_image(
+            ctx,
+            image.clone(),
+            game.theme.background_at(game.elapsed),
+        );
+        // board_cells works in window pixels, so squeeze the whole board into the image
+        let board_size = game.config.cell_size;
+        canvas.set_screen_coordinates(Rect::new(
+            0.0,
+            0.0,
+            columns as f32 * board_size,
+            rows as f32 * board_size,
+        ));
+        let cells = game.board_cells();
+        if !cells.is_empty() {
+            let mut mesh = graphics::MeshBuilder::new();
+            for (rect, color) in cells {
+                mesh.rectangle(DrawMode::fill(), rect, color)?;
+            }
+            canvas.draw(
+                &Mesh::from_data(ctx, mesh.build()),
+                graphics::DrawParam::default(),
+            );
+        }
+        canvas.finish(ctx)?;
+        Ok(Self {
+            width,
+            height,
+            pixels: image.to_pixels(ctx)?,
+        })
+    }
+
+    // As a texture to draw
+    pub fn image(&self, ctx: &Context) -> graphics::Image {
+        graphics::Image::from_pixels(
+            ctx,
+            &self.pixels,
+            graphics::ImageFormat::Rgba8UnormSrgb,
+            self.width as u32,
+            self.height as u32,
+        )
+    }
+
+    // Scaled up as far as it fits in `area`, without smoothing so the cells stay sharp
+    pub fn draw(&self, ctx: &Context, canvas: &mut graphics::Canvas, area: Rect) {
+        let scale = (area.w / self.width as f32).min(area.h / self.height as f32);
+        canvas.set_sampler(graphics::Sampler::nearest_clamp());
+        canvas.draw(
+            &self.image(ctx),
+            graphics::DrawParam::default()
+                .dest([area.x, area.y])
+                .scale([scale, scale]),
+        );
+        canvas.set_default_sampler();
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+        Ok(bytes)
+    }
+
+    pub fn save_png(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_png().map_err(std::io::Error::other)?)
+    }
+}
+
+// Somewhere rasterized frames can be shown without a window, like an LED wall
+pub trait DisplayBackend {
+    // Size of the display in pixels (or LEDs)
+    fn size(&self) -> (usize, usize);
+    // Show an RGBA frame of `width` x `height`, centered if it's smaller than the display
+    fn show(&mut self, frame: &[u8], width: usize, height: usize) -> std::io::Result<()>;
+
+    // Show only the given cells of a frame drawn `cell_size` pixels to a cell, for
+    // displays that can update part of themselves. The rest of the display must
+    // already show the same frame. Sends the whole frame unless overridden
+    fn show_cells(
+        &mut self,
+        frame: &[u8],
+        (width, height): (usize, usize),
+        _cell_size: usize,
+        _cells: &[Position],
+    ) -> std::io::Result<()> {
+        self.show(frame, width, height)
+    }
+}
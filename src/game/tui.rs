@@ -0,0 +1,140 @@
+//! The game in a terminal: the board drawn as characters with ratatui, steered with the
+//! arrow keys. Handy over ssh or anywhere without a window.
+use super::*;
+use ratatui::crossterm::event::{self, Event, KeyCode as TermKey, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color as TermColor, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::Frame;
+use std::time::{Duration, Instant};
+
+// How long to wait for a key before drawing again
+const TUI_FRAME: Duration = Duration::from_millis(33);
+
+// What a key does in the terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TuiAction {
+    Steer(Direction),
+    Restart,
+    Quit,
+}
+
+impl TuiAction {
+    pub fn from_key(key: TermKey) -> Option<Self> {
+        match key {
+            TermKey::Up | TermKey::Char('w') => Some(TuiAction::Steer(Direction::Up)),
+            TermKey::Down | TermKey::Char('s') => Some(TuiAction::Steer(Direction::Down)),
+            TermKey::Left | TermKey::Char('a') => Some(TuiAction::Steer(Direction::Left)),
+            TermKey::Right | TermKey::Char('d') => Some(TuiAction::Steer(Direction::Right)),
+            TermKey::Char('r') => Some(TuiAction::Restart),
+            TermKey::Char('q') | TermKey::Esc => Some(TuiAction::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// Play in the terminal instead of a window, with the same rules and settings as `run_game`.
+pub fn run_game_tui(options: &LaunchOptions) -> std::io::Result<()> {
+    let settings = Settings::load(default_storage().as_ref());
+    let game = options.new_game_with(&settings);
+    let mut terminal = ratatui::init();
+    let result = play_in_terminal(&mut terminal, game);
+    // put the terminal back even when drawing failed
+    ratatui::restore();
+    result
+}
+
+fn play_in_terminal(
+    terminal: &mut ratatui::DefaultTerminal,
+    mut game: GameState,
+) -> std::io::Result<()> {
+    game.skip_transition();
+    let mut last_frame = Instant::now();
+    loop {
+        terminal.draw(|frame| draw_tui(frame, &game))?;
+        if event::poll(TUI_FRAME)? {
+            if let Event::Key(key) = event::read()? {
+                // terminals that report releases would otherwise steer twice
+                if key.kind == KeyEventKind::Press {
+                    match TuiAction::from_key(key.code) {
+                        Some(TuiAction::Steer(direction)) if !game.game_over => {
+                            game.handle_input(direction)
+                        }
+                        Some(TuiAction::Restart) => {
+                            game.restart();
+                            game.skip_transition();
+                        }
+                        Some(TuiAction::Quit) => return Ok(()),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        game.tick(last_frame.elapsed().as_secs_f64());
+        last_frame = Instant::now();
+    }
+}
+
+// Score along the top, the board in a box, and the keys along the bottom
+pub fn draw_tui(frame: &mut Frame, game: &GameState) {
+    let [status, board, help] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(game.config.grid_height as u16 + 2),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    frame.render_widget(
+        Paragraph::new(format!(
+            "Score {}   Best {}   {}",
+            game.score,
+            game.high_score,
+            format_time(game.elapsed)
+        )),
+        status,
+    );
+    frame.render_widget(
+        Paragraph::new(tui_board(game)).block(Block::bordered().title("Snake")),
+        board,
+    );
+    let keys = if game.game_over {
+        "Game over - r to play again, q to quit"
+    } else {
+        "Arrows or WASD to steer, r to restart, q to quit"
+    };
+    frame.render_widget(Paragraph::new(keys), help);
+}
+
+// The board as lines of text, two characters to a cell so the cells come out roughly square:
+// the snake, food and walls in their colors over the background. Fogged cells stay blank
+pub fn tui_board(game: &GameState) -> Vec<Line<'static>> {
+    let background = Style::new().bg(term_color(game.theme.background_at(game.elapsed)));
+    (0..game.config.grid_height)
+        .map(|y| {
+            let cells = (0..game.config.grid_width).map(|x| {
+                let cell = Position::new(x, y);
+                let color = if !game.is_visible(cell) {
+                    None
+                } else if let Some(index) = game.snake.iter().position(|segment| *segment == cell) {
+                    Some(game.segment_color(index))
+                } else if game.food.position == cell {
+                    Some(game.food.kind.color(&game.theme))
+                } else if game.walls.contains(&cell) {
+                    Some(Color::new(0.55, 0.55, 0.6, 1.0))
+                } else {
+                    None
+                };
+                match color {
+                    Some(color) => Span::styled("██", background.fg(term_color(color))),
+                    None => Span::styled("  ", background),
+                }
+            });
+            Line::from(cells.collect::<Vec<_>>())
+        })
+        .collect()
+}
+
+fn term_color(color: Color) -> TermColor {
+    let (r, g, b) = color.to_rgb();
+    TermColor::Rgb(r, g, b)
+}
@@ -2,443 +2,449 @@
 //!
 //! This module contains the core game logic for the Snake game.
 //! It's structured as a library to enable comprehensive testing.
+//!
+//! [`rules`] holds positions, directions and the rules of a step, and [`core`] a whole
+//! run on top of them - the snake, food, score, speed and RNG, and what a tick does -
+//! with no graphics, files or std. The game built on that lives in `game`, with its
+//! ggez drawing in `game::render` and its input handling in `game::input`; all of it is
+//! re-exported here.
+//!
+//! Without the default `std` feature only [`rules`], [`core`] and [`synth`] are built,
+//! as `no_std` + `alloc`. The `storage` feature adds the save backends in `storage` on
+//! their own, which `std` always includes.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
+pub mod core;
+pub mod rules;
+#[cfg(feature = "storage")]
+pub mod storage;
+pub mod synth;
+
+pub use crate::core::*;
+#[cfg(feature = "std")]
 pub use crate::game::*;
+pub use crate::rules::{
+    Direction, FoodPolicy, FoodSpawner, GameConfig, Position, Speed, SpeedPreset, TickClock,
+    WallMode, CELL_SIZE, GRID_HEIGHT, GRID_WIDTH, MAX_CELLS_PER_SECOND, MAX_GRID_SIDE,
+    MIN_GRID_SIZE,
+};
+#[cfg(feature = "storage")]
+pub use crate::storage::*;
+
+#[cfg(feature = "std")]
+mod game;
+
+/// Run the two-board challenge, with both boards side by side in one window
+#[cfg(feature = "std")]
+pub fn run_dual_game(config: GameConfig) -> ggez::GameResult {
+    run_split_screen(DualGame::with_config(config))
+}
 
-mod game {
-    use ggez::event::EventHandler;
-    use ggez::graphics::{self, Color, DrawMode, Mesh, Rect, Text, TextFragment};
-    use ggez::input::keyboard::{KeyCode, KeyInput, KeyMods};
-    use ggez::{Context, GameResult};
-    use rand::Rng;
-
-    // Game constants
-    pub const GRID_WIDTH: i32 = 20;
-    pub const GRID_HEIGHT: i32 = 15;
-    pub const CELL_SIZE: f32 = 30.0;
-
-    // Direction enum for snake movement
-    #[derive(Debug, Clone, Copy, PartialEq)]
-    pub enum Direction {
-        Up,
-        Down,
-        Left,
-        Right,
-    }
-
-    // useful so the snake can't reverse into itself
-    impl Direction {
-        pub fn opposite(&self) -> Direction {
-            match self {
-                Direction::Up => Direction::Down,
-                Direction::Down => Direction::Up,
-                Direction::Left => Direction::Right,
-                Direction::Right => Direction::Left,
-            }
-        }
-    }
+/// Run local versus on split screen, racing to `target_score`
+#[cfg(feature = "std")]
+pub fn run_versus_game(
+    config: GameConfig,
+    target_score: u32,
+    player_names: [String; 2],
+) -> ggez::GameResult {
+    run_split_screen(DualGame::new_match(MatchSettings {
+        player_names,
+        config,
+        ..MatchSettings::new(target_score)
+    }))
+}
 
-    // Position struct for grid coordinates
-    #[derive(Debug, Clone, Copy, PartialEq)]
-    pub struct Position {
-        pub x: i32,
-        pub y: i32,
-    }
+#[cfg(feature = "std")]
+fn run_split_screen(dual_game: DualGame) -> ggez::GameResult {
+    use ggez::{event, ContextBuilder};
 
-    impl Position {
-        pub fn new(x: i32, y: i32) -> Self {
-            Self { x, y }
-        }
+    let config = *dual_game.config();
+    let (ctx, event_loop) = ContextBuilder::new("snake_game", "ben!")
+        .window_setup(ggez::conf::WindowSetup::default().title("Super Sick Snake Game x2"))
+        .window_mode(
+            ggez::conf::WindowMode::default()
+                .dimensions(DualGame::window_width(&config), config.screen_height()),
+        )
+        .build()?;
 
-        // Move position in a given direction
-        pub fn move_in_direction(&self, direction: Direction) -> Self {
-            match direction {
-                // note coordinate system starts at top left and increases down/right in the grid
-                Direction::Up => Position::new(self.x, self.y - 1),
-                Direction::Down => Position::new(self.x, self.y + 1),
-                Direction::Left => Position::new(self.x - 1, self.y),
-                Direction::Right => Position::new(self.x + 1, self.y),
-            }
-        }
+    event::run(ctx, event_loop, dual_game)
+}
 
-        // Check if position is within bounds
-        pub fn is_valid(&self) -> bool {
-            self.x >= 0 && self.x < GRID_WIDTH && self.y >= 0 && self.y < GRID_HEIGHT
-        }
-    }
+/// Run an online match - hosting one, or joining someone else's
+#[cfg(feature = "std")]
+pub fn run_online_game(online: OnlineGame) -> ggez::GameResult {
+    use ggez::{event, ContextBuilder};
 
-    // Game state struct - track all the game state
-    #[derive(Clone)]
-    pub struct GameState {
-        pub snake: Vec<Position>,
-        pub direction: Direction,
-        pub next_direction: Direction,
-        pub food: Position,
-        pub score: u32,
-        pub high_score: u32,
-        pub game_over: bool,
-        pub game_speed: f64, // Time between moves in seconds
-        pub last_update: f64,
-    }
+    let (ctx, event_loop) = ContextBuilder::new("snake_game", "ben!")
+        .window_setup(ggez::conf::WindowSetup::default().title("Super Sick Snake Game - Online"))
+        .window_mode(
+            ggez::conf::WindowMode::default()
+                .dimensions(online.window_width(), online.session.config.screen_height()),
+        )
+        .build()?;
 
-    impl Default for GameState {
-        fn default() -> Self {
-            Self::new()
-        }
-    }
+    event::run(ctx, event_loop, online)
+}
 
-    impl GameState {
-        pub fn new() -> Self {
-            // Initialize snake in the center, moving right
-            // the snake is a vector of positions, have to consider how I update this if the snake grows!
-            let initial_snake: Vec<Position> = vec![
-                Position::new(GRID_WIDTH / 2, GRID_HEIGHT / 2),
-                Position::new(GRID_WIDTH / 2 - 1, GRID_HEIGHT / 2),
-                Position::new(GRID_WIDTH / 2 - 2, GRID_HEIGHT / 2),
-            ];
+/// Run the snake game as the launch options say
+#[cfg(feature = "std")]
+pub fn run_game(options: &LaunchOptions) -> ggez::GameResult {
+    let settings = Settings::load(default_storage().as_ref());
+    run_with_enabled_packs(options.new_game_with(&settings))
+}
 
-            Self {
-                snake: initial_snake.clone(),
-                direction: Direction::Right,
-                next_direction: Direction::Right,
-                food: Self::generate_food_position(&initial_snake),
-                score: 0,
-                high_score: Self::load_high_score(),
-                game_over: false,
-                game_speed: 0.2, // Start with 5 moves per second
-                last_update: 0.0,
-            }
-        }
+/// Play through levels in order. Packs stay out of it, since the levels set the board
+#[cfg(feature = "std")]
+pub fn run_level_game(levels: Vec<Level>) -> ggez::GameResult {
+    let game = GameState::with_levels(levels).map_err(ggez::GameError::ResourceLoadError)?;
+    run_single_board(game, THEME_FILE)
+}
 
-        // Generate a random food position that doesn't overlap with snake
-        pub fn generate_food_position(snake: &[Position]) -> Position {
-            let mut rng = rand::thread_rng();
-            loop {
-                let food: Position =
-                    Position::new(rng.gen_range(0..GRID_WIDTH), rng.gen_range(0..GRID_HEIGHT));
+/// Run the classic game with a content pack from the mods directory
+#[cfg(feature = "std")]
+pub fn run_pack_game(config: GameConfig, pack: &Pack) -> ggez::GameResult {
+    let mut game = GameState::new_with_config(config);
+    game.use_packs(&[pack]);
+    run_single_board(game, &pack.theme_path())
+}
 
-                // Make sure food doesn't spawn on snake
-                if !snake.contains(&food) {
-                    return food;
-                }
-            }
-        }
+/// Browse the packs in the mods directory, switching them on and off
+#[cfg(feature = "std")]
+pub fn run_pack_browser(config: GameConfig) -> ggez::GameResult {
+    use ggez::{event, ContextBuilder};
 
-        // Load high score from file, return 0 if file doesn't exist or can't be read
-        fn load_high_score() -> u32 {
-            match std::fs::read_to_string("high_score.txt") {
-                Ok(content) => {
-                    let trimmed = content.trim();
-                    if trimmed.is_empty() {
-                        0
-                    } else {
-                        trimmed.parse().unwrap_or(0)
-                    }
-                }
-                Err(_) => 0, // File doesn't exist or can't be read, start with 0
-            }
-        }
+    let (packs, problems) = discover_packs(MODS_DIR);
+    let problems = problems.iter().map(ToString::to_string).collect();
+    let (ctx, event_loop) = ContextBuilder::new("snake_game", "ben!")
+        .window_setup(ggez::conf::WindowSetup::default().title("Super Sick Snake Game - Packs"))
+        .window_mode(
+            ggez::conf::WindowMode::default()
+                .dimensions(config.screen_width(), config.screen_height()),
+        )
+        .build()?;
 
-        // Save high score to file
-        fn save_high_score(score: u32) {
-            if let Err(e) = std::fs::write("high_score.txt", score.to_string()) {
-                eprintln!("Failed to save high score: {}", e);
-            }
-        }
+    event::run(
+        ctx,
+        event_loop,
+        PackBrowser::new(packs, problems, PACK_PROFILE_FILE),
+    )
+}
 
-        // Check if current score is a new high score and update if necessary
-        pub fn update_high_score(&mut self) {
-            if self.score > self.high_score {
-                self.high_score = self.score;
-                Self::save_high_score(self.high_score);
-            }
-        }
+// Play with the packs switched on in the pack browser. The last one with a theme
+// supplies the theme file to watch
+#[cfg(feature = "std")]
+fn run_with_enabled_packs(mut game: GameState) -> ggez::GameResult {
+    let (packs, problems) = discover_packs(MODS_DIR);
+    for problem in &problems {
+        eprintln!("{}", problem);
+    }
+    let enabled = PackProfile::load(PACK_PROFILE_FILE).resolve(&packs);
+    game.use_packs(&enabled);
+    let theme_file = match enabled.iter().rev().find(|pack| pack.theme.is_some()) {
+        Some(pack) => pack.theme_path(),
+        None => THEME_FILE.to_string(),
+    };
+    run_single_board(game, &theme_file)
+}
 
-        // Update game state (called every frame)
-        pub fn update(&mut self, ctx: &mut Context) -> GameResult {
-            if self.game_over {
-                return Ok(());
-            }
+// A borderless fullscreen window with no mouse cursor, for the screensaver and kiosk
+#[cfg(feature = "std")]
+fn build_fullscreen() -> ggez::GameResult<(ggez::Context, ggez::event::EventLoop<()>)> {
+    let (mut ctx, event_loop) = ggez::ContextBuilder::new("snake_game", "ben!")
+        .window_setup(ggez::conf::WindowSetup::default().title("Super Sick Snake Game"))
+        .window_mode(
+            ggez::conf::WindowMode::default().fullscreen_type(ggez::conf::FullscreenType::Desktop),
+        )
+        .build()?;
+    ggez::input::mouse::set_cursor_hidden(&mut ctx, true);
+    Ok((ctx, event_loop))
+}
 
-            let current_time: f64 = ctx.time.time_since_start().as_secs_f64();
+/// Run the screensaver: a bot playing endless games fullscreen until there's any input
+#[cfg(feature = "std")]
+pub fn run_screensaver() -> ggez::GameResult {
+    let (ctx, event_loop) = build_fullscreen()?;
+    ggez::event::run(ctx, event_loop, Screensaver::default())
+}
 
-            // Only move snake if enough time has passed
-            if current_time - self.last_update >= self.game_speed {
-                self.direction = self.next_direction;
-                self.move_snake();
-                self.last_update = current_time;
-            }
+/// Run as an arcade cabinet, set up by kiosk.txt if there is one
+#[cfg(feature = "std")]
+pub fn run_kiosk() -> ggez::GameResult {
+    // a cabinet with a broken config should say so, not quietly take coins on defaults
+    let config = if std::path::Path::new(KIOSK_CONFIG_FILE).exists() {
+        KioskConfig::load(KIOSK_CONFIG_FILE)
+            .map_err(|e| ggez::GameError::ConfigError(format!("{}: {}", KIOSK_CONFIG_FILE, e)))?
+    } else {
+        KioskConfig::default()
+    };
+    let mut kiosk = Kiosk::new(config);
+    kiosk.counters =
+        KioskCounters::parse(&std::fs::read_to_string(KIOSK_COUNTERS_FILE).unwrap_or_default());
+    kiosk.counters_path = Some(KIOSK_COUNTERS_FILE.to_string());
+
+    let (ctx, event_loop) = build_fullscreen()?;
+    ggez::event::run(ctx, event_loop, kiosk)
+}
 
-            Ok(())
-        }
+/// Run co-op, with two players steering the same snake
+#[cfg(feature = "std")]
+pub fn run_coop_game(config: GameConfig) -> ggez::GameResult {
+    let mut game = GameState::new_with_config(config);
+    game.set_coop(true);
+    run_with_enabled_packs(game)
+}
 
-        // Check if a position would cause a collision
-        pub fn would_collide(&self, new_head: Position) -> bool {
-            // check: not in a wall, in it's own body (minus the behind that's about to be removed)
-            !new_head.is_valid() || self.snake[..self.snake.len() - 1].contains(&new_head)
-        }
+/// Run hotseat, with the named players taking turns on one board - or, with no names,
+/// asking for them first
+#[cfg(feature = "std")]
+pub fn run_hotseat_game(config: GameConfig, players: Vec<String>) -> ggez::GameResult {
+    use ggez::{event, ContextBuilder};
 
-        // Move the snek
-        pub fn move_snake(&mut self) {
-            let head: Position = self.snake[0];
-            let new_head: Position = head.move_in_direction(self.direction);
+    let (ctx, event_loop) = ContextBuilder::new("snake_game", "ben!")
+        .window_setup(ggez::conf::WindowSetup::default().title("Super Sick Snake Game - Hotseat"))
+        .window_mode(
+            ggez::conf::WindowMode::default()
+                .dimensions(config.screen_width(), config.screen_height()),
+        )
+        .build()?;
 
-            // Check for collisions
-            if self.would_collide(new_head) {
-                self.game_over = true;
-                // Update high score when game ends
-                self.update_high_score();
-                return;
-            }
+    let mut hotseat = if players.is_empty() {
+        Hotseat::with_name_entry()
+    } else {
+        Hotseat::new(players)
+    };
+    hotseat.game = GameState::new_with_config(config);
+    hotseat.name_filter = NameFilter::load(NAME_BLOCKLIST_FILE);
+    event::run(ctx, event_loop, hotseat)
+}
 
-            // Update head location
-            self.snake.insert(0, new_head);
+/// Race a ghost of an earlier run loaded from `path`, or your own last run without one
+#[cfg(feature = "std")]
+pub fn run_ghost_race(config: GameConfig, path: Option<&str>) -> ggez::GameResult {
+    use ggez::{event, ContextBuilder};
 
-            // Check if food was chomped
-            if new_head == self.food {
-                self.score += 10;
-                self.food = Self::generate_food_position(&self.snake);
+    let ghost = match path {
+        Some(path) => GhostRun::load_file(path).ok_or_else(|| {
+            ggez::GameError::ResourceLoadError(format!("No ghost to race in {}", path))
+        })?,
+        // with no ghost of your own yet, the first run just records one
+        None => GhostRun::load(default_storage().as_ref()).unwrap_or_default(),
+    };
 
-                // Increase game speed
-                self.game_speed = (self.game_speed * 0.95).max(0.1);
-            } else {
-                // Remove tail if the snake is still hungry
-                self.snake.pop();
-            }
-        }
+    let (ctx, event_loop) = ContextBuilder::new("snake_game", "ben!")
+        .window_setup(
+            ggez::conf::WindowSetup::default().title("Super Sick Snake Game - Ghost Race"),
+        )
+        .window_mode(
+            ggez::conf::WindowMode::default()
+                .dimensions(config.screen_width(), config.screen_height()),
+        )
+        .build()?;
 
-        // Handle input to change direction
-        pub fn handle_input(&mut self, direction: Direction) {
-            // Prevent snake from reversing into itself
-            if direction != self.direction.opposite() {
-                self.next_direction = direction;
-            }
-        }
+    event::run(ctx, event_loop, GhostRace::new(ghost, config))
+}
 
-        // Draw the game
-        pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
-            let mut canvas = graphics::Canvas::from_frame(ctx, Color::BLACK);
-
-            // Draw snake
-            for segment in &self.snake {
-                let rect = Rect::new(
-                    // boy is this a bit weird, I'd have been stuck for ages without an LLM
-                    // this is scaling the grid coordinates to the screen pixel coordinates (sounds obvious once you know it)
-                    segment.x as f32 * CELL_SIZE,
-                    segment.y as f32 * CELL_SIZE,
-                    // - 2.0 to make the snake segments clearer
-                    CELL_SIZE - 2.0,
-                    CELL_SIZE - 2.0,
-                );
-                let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, Color::GREEN)?;
-                canvas.draw(&mesh, graphics::DrawParam::default());
-            }
+/// Edit the level file at `path`, starting a new level there if there isn't one yet
+#[cfg(feature = "std")]
+pub fn run_level_editor(path: &str) -> ggez::GameResult {
+    use ggez::{event, ContextBuilder};
 
-            // Draw food
-            let food_rect = Rect::new(
-                self.food.x as f32 * CELL_SIZE,
-                self.food.y as f32 * CELL_SIZE,
-                CELL_SIZE - 2.0,
-                CELL_SIZE - 2.0,
-            );
-            let food_mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), food_rect, Color::RED)?;
-            canvas.draw(&food_mesh, graphics::DrawParam::default());
-
-            // Draw score at top-left
-            let score_text = graphics::Text::new(format!("Score: {}", self.score));
-            canvas.draw(
-                &score_text,
-                graphics::DrawParam::default().dest([10.0, 10.0]),
-            );
+    let editor =
+        LevelEditor::open(path, default_storage()).map_err(ggez::GameError::ResourceLoadError)?;
+    let (ctx, event_loop) = ContextBuilder::new("snake_game", "ben!")
+        .window_setup(
+            ggez::conf::WindowSetup::default().title("Super Sick Snake Game - Level Editor"),
+        )
+        .window_mode(ggez::conf::WindowMode::default().dimensions(
+            editor.level.config.screen_width(),
+            editor.level.config.screen_height(),
+        ))
+        .build()?;
 
-            // Draw high score at top-right
-            let high_score_text = graphics::Text::new(format!("High Score: {}", self.high_score));
-            let high_score_bounds = high_score_text.measure(ctx)?;
-            let screen_width = GRID_WIDTH as f32 * CELL_SIZE;
-            let high_score_x = screen_width - high_score_bounds.x - 10.0;
-            canvas.draw(
-                &high_score_text,
-                graphics::DrawParam::default().dest([high_score_x, 10.0]),
-            );
+    event::run(ctx, event_loop, editor)
+}
 
-            // Draw game over overlay if game is over
-            if self.game_over {
-                self.draw_game_over_overlay(ctx, &mut canvas)?;
-            }
+/// Browse and watch saved replays, in a window the size of `config`'s board
+#[cfg(feature = "std")]
+pub fn run_replay_browser(config: GameConfig) -> ggez::GameResult {
+    use ggez::{event, ContextBuilder};
 
-            canvas.finish(ctx)?;
-            Ok(())
-        }
+    let (ctx, event_loop) = ContextBuilder::new("snake_game", "ben!")
+        .window_setup(ggez::conf::WindowSetup::default().title("Super Sick Snake Game - Replays"))
+        .window_mode(
+            ggez::conf::WindowMode::default()
+                .dimensions(config.screen_width(), config.screen_height()),
+        )
+        .build()?;
 
-        // Add a game overlay for when the game is over
-        fn draw_game_over_overlay(
-            &self,
-            ctx: &mut Context,
-            canvas: &mut graphics::Canvas,
-        ) -> GameResult {
-            let screen_width = GRID_WIDTH as f32 * CELL_SIZE;
-
-            // Create semi-transparent overlay covering the game area
-            let overlay_rect = Rect::new(0.0, 0.0, screen_width, GRID_HEIGHT as f32 * CELL_SIZE);
-            let overlay_mesh = Mesh::new_rectangle(
-                ctx,
-                DrawMode::fill(),
-                overlay_rect,
-                Color::new(0.0, 0.0, 0.0, 0.7),
-            )?;
-            canvas.draw(&overlay_mesh, graphics::DrawParam::default());
-
-            // Create game over text
-            // note TextFragment is basically a string (or substring) with formatting options
-            // this confused me at first it seems redundant - but imagine you wanted two or more colors! duh
-            let game_over_text = Text::new(
-                TextFragment::new("GAME OVER")
-                    .color(Color::RED)
-                    .scale(graphics::PxScale::from(48.0)),
-            );
+    let mut browser = ReplayBrowser::new(Replay::load_all(default_storage().as_ref()), config);
+    browser.evict_to(memory_caps()?.browser_bytes);
+    event::run(ctx, event_loop, browser)
+}
+
+// The memory limits from memory.txt, or the defaults without one
+#[cfg(feature = "std")]
+fn memory_caps() -> ggez::GameResult<MemoryCaps> {
+    if !std::path::Path::new(MEMORY_FILE).exists() {
+        return Ok(MemoryCaps::default());
+    }
+    MemoryCaps::load(MEMORY_FILE)
+        .map_err(|e| ggez::GameError::ConfigError(format!("{}: {}", MEMORY_FILE, e)))
+}
 
-            let game_over_bounds = game_over_text.measure(ctx)?; // this is so cool btw. note: it returns a Rect!
-            let game_over_x = (screen_width - game_over_bounds.x) / 2.0;
-            let game_over_y = (GRID_HEIGHT as f32 * CELL_SIZE) / 2.0 - 80.0;
+#[cfg(feature = "std")]
+fn run_single_board(mut game_state: GameState, theme_file: &str) -> ggez::GameResult {
+    use ggez::{event, ContextBuilder};
 
-            canvas.draw(
-                &game_over_text,
-                graphics::DrawParam::default().dest([game_over_x, game_over_y]), // so easy to center text
-            );
+    // Create ggez context
+    let (ctx, event_loop) = ContextBuilder::new("snake_game", "ben!")
+        .window_setup(ggez::conf::WindowSetup::default().title("Super Sick Snake Game"))
+        .window_mode(ggez::conf::WindowMode::default().dimensions(
+            game_state.config.screen_width(),
+            game_state.config.screen_height(),
+        ))
+        .build()?;
 
-            // Create final score text - same thing basically
-            let final_score_text = Text::new(
-                TextFragment::new(format!("Final Score: {}", self.score))
-                    .color(Color::WHITE)
-                    .scale(graphics::PxScale::from(24.0)),
-            );
+    // Carry on from a run left in the background, otherwise start on the intro splash and
+    // then the main menu
+    let resumed = game_state.resume_suspended();
+    if resumed {
+        game_state.clear_autosave(); // the same run
+    } else {
+        game_state.change_screen(Screen::Splash);
+    }
 
-            let score_bounds = final_score_text.measure(ctx)?;
-            let score_x = (screen_width - score_bounds.x) / 2.0;
-            let score_y = game_over_y + 60.0; // just a bit below the game over text
+    // Use the theme file if there is one, and pick up any edits to it while playing
+    if std::path::Path::new(theme_file).exists() {
+        game_state.reload_theme(theme_file);
+    }
+    let mut recorder = ReplayRecorder::new(game_state);
+    recorder.outbox.connect_to_server();
+    recorder.paused = resumed;
+    if !resumed && recorder.game.has_autosave() {
+        recorder.offer_autosave();
+    }
+    recorder.caps = memory_caps()?;
+    recorder.sounds = Some(SoundBank::load(SOUNDS_DIR));
+    let (packs, _) = discover_packs(MODS_DIR);
+    recorder.feedback = FeedbackRouter::for_packs(
+        packs
+            .iter()
+            .filter(|pack| recorder.game.packs.contains(&pack.manifest.name)),
+    );
+    recorder.voice = chosen_voice(
+        recorder.game.storage.as_ref(),
+        discover_voices(VOICES_DIR, &packs),
+    );
+    match GamepadRumble::new() {
+        Ok(rumble) => recorder.haptics = Some(Box::new(rumble)),
+        Err(e) => eprintln!("No gamepad rumble: {}", e),
+    }
+    match ThemeWatcher::new(theme_file) {
+        Ok(watcher) => recorder.theme_watcher = Some(watcher),
+        Err(e) => eprintln!("Not watching {} for changes: {}", theme_file, e),
+    }
 
-            canvas.draw(
-                &final_score_text,
-                graphics::DrawParam::default().dest([score_x, score_y]),
-            );
+    // Run the game, keeping a replay of every run
+    event::run(ctx, event_loop, recorder)
+}
 
-            // Show "NEW HIGH SCORE!" if applicable
-            if self.score == self.high_score && self.score > 0 {
-                let new_high_score_text = Text::new(
-                    TextFragment::new("🎉 NEW HIGH SCORE! 🎉")
-                        .color(Color::new(1.0, 0.84, 0.0, 1.0)) // Gold color
-                        .scale(graphics::PxScale::from(20.0)),
-                );
-
-                let new_high_bounds = new_high_score_text.measure(ctx)?;
-                let new_high_x = (screen_width - new_high_bounds.x) / 2.0;
-                let new_high_y = score_y + 40.0;
-
-                canvas.draw(
-                    &new_high_score_text,
-                    graphics::DrawParam::default().dest([new_high_x, new_high_y]),
-                );
-            }
+/// The game's rules for Bevy apps: add [`bevy_plugin::SnakePlugin`] (plus Bevy's
+/// `TimePlugin`, which `MinimalPlugins` includes), steer with [`bevy_plugin::SnakeTurn`]
+/// events and draw the [`bevy_plugin::SnakeGame`] resource with your own renderer
+#[cfg(feature = "bevy")]
+pub mod bevy_plugin {
+    use crate::game::GameState;
+    use crate::rules::{Direction, TickClock};
+    use bevy_app::{App, Plugin, Update};
+    use bevy_ecs::prelude::*;
+    use bevy_time::Time;
+
+    #[derive(Resource, Default)]
+    pub struct SnakeGame {
+        pub state: GameState,
+        pub clock: TickClock,
+    }
 
-            // Create restart instruction text
-            let restart_text = Text::new(
-                TextFragment::new("Press Ctrl+R to restart")
-                    .color(Color::YELLOW)
-                    .scale(graphics::PxScale::from(18.0)),
-            );
+    // Ask the snake to turn, the same as pressing an arrow key
+    #[derive(Event, Debug, Clone, Copy)]
+    pub struct SnakeTurn(pub Direction);
 
-            let restart_bounds = restart_text.measure(ctx)?;
-            let restart_x = (screen_width - restart_bounds.x) / 2.0;
-            let restart_y = score_y + 50.0;
+    // Start a new run
+    #[derive(Event, Debug, Clone, Copy)]
+    pub struct SnakeRestart;
 
-            canvas.draw(
-                &restart_text,
-                graphics::DrawParam::default().dest([restart_x, restart_y]),
-            );
+    // Sent after every tick, for sounds, effects and so on
+    #[derive(Event, Debug, Clone, Copy, PartialEq)]
+    pub struct SnakeTicked {
+        pub ate: bool,
+        pub game_over: bool,
+    }
 
-            Ok(())
+    pub struct SnakePlugin;
+
+    impl Plugin for SnakePlugin {
+        fn build(&self, app: &mut App) {
+            app.init_resource::<SnakeGame>()
+                .add_event::<SnakeTurn>()
+                .add_event::<SnakeRestart>()
+                .add_event::<SnakeTicked>()
+                .add_systems(Update, (steer_snake, tick_snake).chain());
         }
     }
 
-    // Implement EventHandler trait for ggez. Required for event::run.
-    impl EventHandler for GameState {
-        fn update(&mut self, ctx: &mut Context) -> GameResult {
-            self.update(ctx)
-        }
-
-        fn draw(&mut self, ctx: &mut Context) -> GameResult {
-            self.draw(ctx)
-        }
-
-        fn key_down_event(
-            &mut self,
-            _ctx: &mut Context,
-            key_input: KeyInput,
-            _repeat: bool,
-        ) -> GameResult {
-            if let Some(keycode) = key_input.keycode {
-                match keycode {
-                    KeyCode::Up | KeyCode::W => {
-                        if !self.game_over {
-                            self.handle_input(Direction::Up);
-                        }
-                    }
-                    KeyCode::Down | KeyCode::S => {
-                        if !self.game_over {
-                            self.handle_input(Direction::Down);
-                        }
-                    }
-                    KeyCode::Left | KeyCode::A => {
-                        if !self.game_over {
-                            self.handle_input(Direction::Left);
-                        }
-                    }
-                    KeyCode::Right | KeyCode::D => {
-                        if !self.game_over {
-                            self.handle_input(Direction::Right);
-                        }
-                    }
-                    KeyCode::R => {
-                        // Reset game with Ctrl+R or just R
-                        if key_input.mods.contains(KeyMods::CTRL) || !self.game_over {
-                            *self = GameState::new();
-                        }
-                    }
-                    _ => {}
-                }
+    pub fn steer_snake(
+        mut game: ResMut<SnakeGame>,
+        mut turns: EventReader<SnakeTurn>,
+        mut restarts: EventReader<SnakeRestart>,
+    ) {
+        if restarts.read().count() > 0 {
+            game.state.restart();
+            game.state.skip_transition(); // transitions are the ggez frontend's business
+            game.clock = TickClock::default();
+        }
+        for SnakeTurn(direction) in turns.read() {
+            if !game.state.game_over {
+                game.state.handle_input(*direction);
             }
-            Ok(())
         }
     }
-}
-
-/// Run the snake game
-pub fn run_game() -> ggez::GameResult {
-    use ggez::{event, ContextBuilder};
-
-    // Create ggez context
-    let (ctx, event_loop) = ContextBuilder::new("snake_game", "ben!")
-        .window_setup(ggez::conf::WindowSetup::default().title("Super Sick Snake Game"))
-        .window_mode(ggez::conf::WindowMode::default().dimensions(
-            GRID_WIDTH as f32 * CELL_SIZE,
-            GRID_HEIGHT as f32 * CELL_SIZE,
-        ))
-        .build()?;
 
-    // Create game state
-    let game_state = GameState::new();
-
-    // Run the game
-    event::run(ctx, event_loop, game_state)
+    pub fn tick_snake(
+        time: Res<Time>,
+        mut game: ResMut<SnakeGame>,
+        mut ticked: EventWriter<SnakeTicked>,
+    ) {
+        if game.state.game_over {
+            return;
+        }
+        let game = &mut *game;
+        game.state.elapsed += time.delta_secs_f64();
+        if game.clock.advance(time.delta(), game.state.speed) {
+            let score_before = game.state.score;
+            game.state.step();
+            ticked.write(SnakeTicked {
+                ate: game.state.score > score_before,
+                game_over: game.state.game_over,
+            });
+        }
+    }
 }
 
 // this is mind blowing to be, seeing the tests in the same code feels very unintuitive to me. it looks ugly
 // yet I do hear that it makes more sense without a bunch of supers:: and it does make the tests be right there, so maybe I need to open my mind
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
+    use ggez::graphics::Color;
+    use ggez::input::keyboard::{KeyCode, KeyInput, KeyMods};
     #[cfg(feature = "proptest")]
     use proptest::prelude::*;
+    use rand::{Rng, SeedableRng};
+    use std::sync::Arc;
 
     // Unit tests for Direction
     #[test]
@@ -486,18 +492,24 @@ mod tests {
     }
 
     #[test]
-    fn test_position_is_valid() {
+    fn test_config_contains() {
+        let config = GameConfig::default();
         // Valid positions
-        assert!(Position::new(0, 0).is_valid());
-        assert!(Position::new(GRID_WIDTH - 1, GRID_HEIGHT - 1).is_valid());
-        assert!(Position::new(5, 5).is_valid());
+        assert!(config.contains(Position::new(0, 0)));
+        assert!(config.contains(Position::new(GRID_WIDTH - 1, GRID_HEIGHT - 1)));
+        assert!(config.contains(Position::new(5, 5)));
 
         // Invalid positions (out of bounds)
-        assert!(!Position::new(-1, 5).is_valid());
-        assert!(!Position::new(5, -1).is_valid());
-        assert!(!Position::new(GRID_WIDTH, 5).is_valid());
-        assert!(!Position::new(5, GRID_HEIGHT).is_valid());
-        assert!(!Position::new(-1, -1).is_valid());
+        assert!(!config.contains(Position::new(-1, 5)));
+        assert!(!config.contains(Position::new(5, -1)));
+        assert!(!config.contains(Position::new(GRID_WIDTH, 5)));
+        assert!(!config.contains(Position::new(5, GRID_HEIGHT)));
+        assert!(!config.contains(Position::new(-1, -1)));
+
+        // A bigger board takes in what the default one doesn't
+        let bigger = GameConfig::new(30, 20, CELL_SIZE).unwrap();
+        assert!(bigger.contains(Position::new(GRID_WIDTH, GRID_HEIGHT)));
+        assert!(!bigger.contains(Position::new(30, 5)));
     }
 
     // Unit tests for GameState
@@ -523,10 +535,10 @@ mod tests {
         assert!(!game.game_over);
 
         // Check food is not on snake
-        assert!(!game.snake.contains(&game.food));
+        assert!(!game.snake.contains(&game.food.position));
 
         // Check food is within bounds
-        assert!(game.food.is_valid());
+        assert!(game.config.contains(game.food.position));
     }
 
     #[test]
@@ -565,7 +577,7 @@ mod tests {
         ];
 
         for pos in valid_moves {
-            if pos.is_valid() {
+            if game.config.contains(pos) {
                 // Only check if it's not colliding with snake body (excluding tail)
                 let body_collision = game.snake[..game.snake.len() - 1].contains(&pos);
                 if !body_collision {
@@ -589,6 +601,87 @@ mod tests {
         assert_eq!(game.next_direction, Direction::Up);
     }
 
+    #[test]
+    fn test_turn_assist() {
+        let mut game = GameState::new();
+        // off, the last turn before the tick wins
+        game.handle_input(Direction::Up);
+        game.handle_input(Direction::Down);
+        assert_eq!(game.next_direction, Direction::Down);
+
+        game.toggle_turn_assist();
+        game.next_direction = Direction::Right;
+        // mashing Up then Down keeps the Up
+        game.handle_input(Direction::Up);
+        game.handle_input(Direction::Down);
+        assert_eq!(game.next_direction, Direction::Up);
+        // but not once the window has gone by
+        game.elapsed += TURN_COOLDOWN * 2.0;
+        game.handle_input(Direction::Down);
+        assert_eq!(game.next_direction, Direction::Down);
+
+        // going straight on or trying to reverse isn't a turn, so it doesn't hold the next one
+        let mut game = GameState::new();
+        game.toggle_turn_assist();
+        game.handle_input(Direction::Right);
+        game.handle_input(Direction::Left);
+        game.handle_input(Direction::Up);
+        assert_eq!(game.next_direction, Direction::Up);
+
+        // after a tick the next turn counts straight away, so quick zigzags still work
+        game.step();
+        assert_eq!(game.direction, Direction::Up);
+        game.handle_input(Direction::Right);
+        assert_eq!(game.next_direction, Direction::Right);
+        game.handle_input(Direction::Left);
+        assert_eq!(game.next_direction, Direction::Right);
+
+        game.restart();
+        assert_eq!(game.turn_cooldown, TURN_COOLDOWN);
+        game.toggle_turn_assist();
+        assert_eq!(game.turn_cooldown, 0.0);
+    }
+
+    fn key(keycode: KeyCode) -> KeyInput {
+        synthetic_key(keycode, KeyMods::empty())
+    }
+
+    #[test]
+    fn test_held_keys() {
+        let repeat = KeyRepeat::default();
+        assert!(!repeat.accepts(KeyRole::Steer, true));
+        assert!(repeat.accepts(KeyRole::Navigate, true));
+        assert!(!repeat.accepts(KeyRole::Action, true));
+        assert!(repeat.accepts(KeyRole::Action, false));
+
+        let mut game = GameState::new();
+        game.screen = Screen::Playing;
+        game.key_down(key(KeyCode::Up), false);
+        assert_eq!(game.next_direction, Direction::Up);
+        // a held Left arriving as OS repeats doesn't turn
+        game.key_down(key(KeyCode::Left), true);
+        assert_eq!(game.next_direction, Direction::Up);
+
+        // holding V toggles vibration once, not on and off with every repeat
+        let haptics = game.haptics;
+        game.key_down(key(KeyCode::V), false);
+        game.key_down(key(KeyCode::V), true);
+        game.key_down(key(KeyCode::V), true);
+        assert_eq!(game.haptics, !haptics);
+
+        // and a held R is one restart
+        game.score = 30;
+        game.key_down(key(KeyCode::R), true);
+        assert_eq!(game.score, 30);
+
+        // steering repeats can be turned on, and stay on across a restart
+        game.key_repeat.steer = true;
+        game.key_down(key(KeyCode::Down), true);
+        assert_eq!(game.next_direction, Direction::Down);
+        game.restart();
+        assert!(game.key_repeat.steer);
+    }
+
     #[test]
     fn test_snake_movement_and_growth() {
         let mut game = GameState::new();
@@ -598,14 +691,14 @@ mod tests {
         // Place food in front of snake head
         let head = game.snake[0];
         let food_pos = head.move_in_direction(game.direction);
-        game.food = food_pos;
+        game.food = Food::normal(food_pos);
 
         // Move snake (should eat food and grow)
         game.move_snake();
 
         assert_eq!(game.snake.len(), initial_length + 1);
         assert_eq!(game.score, initial_score + 10);
-        assert_ne!(game.food, food_pos); // Food should be regenerated
+        assert_ne!(game.food.position, food_pos); // Food should be regenerated
     }
 
     #[test]
@@ -616,7 +709,7 @@ mod tests {
         // Ensure food is not in front of snake
         let head = game.snake[0];
         let _food_pos = head.move_in_direction(game.direction);
-        game.food = Position::new(0, 0); // Place food elsewhere
+        game.food = Food::normal(Position::new(0, 0)); // Place food elsewhere
 
         // Move snake (should not eat food)
         game.move_snake();
@@ -637,21 +730,127 @@ mod tests {
         assert!(game.game_over);
     }
 
+    #[test]
+    fn test_speed_in_cells_per_second() {
+        assert_eq!(Speed::START.to_string(), "5 cells/s");
+        assert_eq!(
+            Speed::parse("7.5 cells/s").unwrap().millicells_per_second,
+            7500
+        );
+        assert_eq!(
+            Speed::parse("7.5 cells/s").unwrap().to_string(),
+            "7.5 cells/s"
+        );
+        // the old seconds between moves
+        assert_eq!(Speed::parse("0.2"), Ok(Speed::START));
+        assert_eq!(Speed::parse("0.1"), Ok(Speed::MAX));
+        assert!(Speed::parse("fast").is_err());
+        assert!(Speed::parse("0 cells/s").is_err());
+
+        // speeding up settles on the maximum, like the old 0.1s floor
+        let mut speed = Speed::START;
+        for _ in 0..100 {
+            speed = speed.faster();
+        }
+        assert_eq!(speed, Speed::MAX);
+
+        // uneven frames add up to exactly one tick per fifth of a second
+        let mut clock = TickClock::default();
+        let frames = [7, 16, 16, 17, 33, 16, 16, 17, 50, 12];
+        let mut ticks = 0;
+        for frame in frames.iter().cycle().take(frames.len() * 3000) {
+            if clock.advance(std::time::Duration::from_millis(*frame), Speed::START) {
+                ticks += 1;
+            }
+        }
+        assert_eq!(ticks, 200 * 3000 / 200);
+
+        // a long stall is one tick, not a burst
+        let mut clock = TickClock::default();
+        assert!(clock.advance(std::time::Duration::from_secs(5), Speed::START));
+        assert!(!clock.advance(std::time::Duration::ZERO, Speed::START));
+    }
+
     #[test]
     fn test_game_speed_increases_after_eating() {
         let mut game = GameState::new();
-        let initial_speed = game.game_speed;
+        let initial_speed = game.speed;
 
         // Place food in front of snake head
         let head = game.snake[0];
         let food_pos = head.move_in_direction(game.direction);
-        game.food = food_pos;
+        game.food = Food::normal(food_pos);
 
         // Move snake to eat food
         game.move_snake();
 
-        // Game speed should increase (get smaller number = faster)
-        assert!(game.game_speed < initial_speed);
+        // Game speed should increase (more cells per second)
+        assert!(game.speed > initial_speed);
+    }
+
+    #[test]
+    fn test_coverage() {
+        let mut game = GameState::new();
+        let total_cells = (GRID_WIDTH * GRID_HEIGHT) as f32;
+        assert_eq!(game.coverage(), 3.0 / total_cells);
+
+        // Eating food grows the snake and so the coverage
+        let head = game.snake[0];
+        game.food = Food::normal(head.move_in_direction(game.direction));
+        game.move_snake();
+        assert_eq!(game.coverage(), 4.0 / total_cells);
+    }
+
+    #[test]
+    fn test_filling_the_board_wins() {
+        let mut game = GameState::new();
+        let food = game.snake[0].move_in_direction(game.direction);
+        game.food = Food::normal(food);
+        // walls everywhere else, so the last piece of food leaves nowhere for the next
+        game.walls = game
+            .config
+            .cells()
+            .filter(|cell| *cell != food && !game.snake.contains(cell))
+            .collect();
+        assert!(!game.won());
+
+        game.move_snake();
+        assert!(game.game_over);
+        assert!(game.won());
+        assert_eq!(game.screen, Screen::GameOver);
+    }
+
+    #[cfg(feature = "bevy")]
+    #[test]
+    fn test_bevy_plugin_steers_and_ticks() {
+        use crate::bevy_plugin::{SnakeGame, SnakePlugin, SnakeTicked, SnakeTurn};
+        use bevy_ecs::event::Events;
+
+        let mut app = bevy_app::App::new();
+        app.add_plugins(SnakePlugin)
+            .init_resource::<bevy_time::Time>();
+        let head = app.world().resource::<SnakeGame>().state.snake[0];
+        let tick = app
+            .world()
+            .resource::<SnakeGame>()
+            .state
+            .speed
+            .seconds_per_cell();
+
+        // not enough time for a tick yet - the turn is queued but the snake stays put
+        app.world_mut().send_event(SnakeTurn(Direction::Down));
+        app.update();
+        assert_eq!(app.world().resource::<SnakeGame>().state.snake[0], head);
+
+        app.world_mut()
+            .resource_mut::<bevy_time::Time>()
+            .advance_by(std::time::Duration::from_secs_f64(tick));
+        app.update();
+        let game = app.world().resource::<SnakeGame>();
+        assert_eq!(game.state.direction, Direction::Down);
+        assert_eq!(game.state.snake[0], head.move_in_direction(Direction::Down));
+        let ticks = app.world().resource::<Events<SnakeTicked>>();
+        assert_eq!(ticks.len(), 1);
     }
 
     // Property-based tests using proptest
@@ -686,6 +885,14 @@ mod tests {
             assert_ne!(opposite, direction);
         }
 
+        #[test]
+        fn test_sync_decoder_fuzz(line in "[KD0-9 ,.UDLR-]{0,40}") {
+            // whatever arrives, the decoder either applies it or says why not
+            let mut decoder = SyncDecoder::default();
+            let _ = decoder.apply("K 1 R R 0 0 5,5 10,7 9,7 8,7");
+            let _ = decoder.apply(&line);
+        }
+
         #[test]
         fn test_snake_growth_invariant(
             snake_length in 3..20usize
@@ -704,7 +911,7 @@ mod tests {
             let initial_length = game.snake.len();
 
             // Place food elsewhere so snake doesn't eat
-            game.food = Position::new(0, 0);
+            game.food = Food::normal(Position::new(0, 0));
 
             game.move_snake();
 
@@ -737,7 +944,7 @@ mod tests {
 
         // All snake segments should be valid positions
         for segment in &game.snake {
-            assert!(segment.is_valid());
+            assert!(game.config.contains(*segment));
         }
 
         // Snake segments should be adjacent (no gaps)
@@ -758,15 +965,59 @@ mod tests {
 
     fn create_custom_game_state(snake: Vec<Position>, direction: Direction) -> GameState {
         GameState {
-            snake: snake.clone(),
-            direction,
-            next_direction: direction,
-            food: GameState::generate_food_position(&snake),
-            score: 0,
+            run: Run {
+                snake: snake.clone(),
+                direction,
+                next_direction: direction,
+                food: Food::normal(GameState::generate_food_position(&snake).unwrap()),
+                score: 0,
+                game_over: false,
+                speed: Speed::START,
+                clock: TickClock::default(),
+                elapsed: 0.0,
+                weather: Weather::Clear,
+                slippery_cells: Vec::new(),
+                ice: false,
+                grip_cells: Vec::new(),
+                walls: Vec::new(),
+                coop: false,
+                turn_queue: Vec::new(),
+                handicap: Handicap::default(),
+                food_policy: FoodPolicy::default(),
+                food_mix: FoodMix::default(),
+                hazards: Spawner::rain(),
+                spawns: Spawns::default(),
+                grace_left: GracePeriod::Off,
+                turn_cooldown: 0.0,
+                turned_at: None,
+                rng: rand::SeedableRng::seed_from_u64(0),
+                seed: 0,
+                config: GameConfig::default(),
+            },
             high_score: 0,
-            game_over: false,
-            game_speed: 0.2,
-            last_update: 0.0,
+            high_score_checked: true,
+            best_time: 0.0,
+            anim_time: 0.0,
+            screen: Screen::Playing,
+            screen_time: 0.0,
+            menu: MainMenu::default(),
+            settings: Settings::default(),
+            transition: None,
+            reduced_motion: false,
+            theme: Theme::default(),
+            photosensitive: false,
+            food_pulse: 0.0,
+            levels: None,
+            toast: None,
+            packs: Vec::new(),
+            haptics: true,
+            haptic_intensity: DEFAULT_HAPTIC_INTENSITY,
+            notify_best: false,
+            show_hud: true,
+            key_repeat: KeyRepeat::default(),
+            mixer: Mixer::default(),
+            text_cache: TextCache::default(),
+            storage: default_storage(),
         }
     }
 
@@ -781,15 +1032,215 @@ mod tests {
 
         assert_eq!(game.snake, snake);
         assert_eq!(game.direction, Direction::Right);
-        assert!(!game.snake.contains(&game.food));
+        assert!(!game.snake.contains(&game.food.position));
     }
 
     #[test]
     fn test_high_score_persistence() {
         // Test that high score is loaded on game creation
-        let game = GameState::new();
-        // High score should be loaded (could be 0 or a saved value)
-        assert_eq!(game.high_score, game.high_score); // This is always true, but tests the field exists
+        let storage: Arc<dyn StorageBackend> = Arc::new(InMemoryStorage::default());
+        let mut game = GameState::with_storage(storage.clone());
+        assert_eq!(game.high_score, 0);
+
+        game.score = 120;
+        game.elapsed = 42.0;
+        game.end_run();
+        let game = GameState::with_storage(storage.clone());
+        assert_eq!(game.high_score, 120);
+        assert_eq!(game.best_time, 42.0);
+
+        // a restart keeps saving to the same place
+        let mut game = game;
+        game.restart();
+        assert!(Arc::ptr_eq(&game.storage, &storage));
+        assert_eq!(game.high_score, 120);
+
+        // unit tests never see the real files
+        assert!(default_storage().read(HIGH_SCORE_FILE).is_none());
+    }
+
+    #[test]
+    fn test_file_storage() {
+        let dir = std::env::temp_dir().join(format!("snake_storage_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let storage = FileStorage::new(&dir);
+        assert_eq!(storage.read("best_time.txt"), None);
+        storage.write("best_time.txt", "12.5").unwrap();
+        assert_eq!(storage.read("best_time.txt").as_deref(), Some("12.5"));
+        // written through a temporary file that's gone once it's done
+        assert_eq!(storage.keys(), vec!["best_time.txt".to_string()]);
+        storage.remove("best_time.txt").unwrap();
+        assert_eq!(storage.read("best_time.txt"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_platform_storage() {
+        let root = std::env::temp_dir().join(format!("snake_platform_{}", std::process::id()));
+        let (dir, legacy) = (root.join("data"), root.join("cwd"));
+        std::fs::create_dir_all(&legacy).unwrap();
+        std::fs::write(legacy.join(HIGH_SCORE_FILE), "50").unwrap();
+        // not the game's, even if the name looks like it could be
+        std::fs::write(legacy.join("slot_machine.txt"), "notes").unwrap();
+        std::fs::write(legacy.join("README.md"), "mine").unwrap();
+        let storage = PlatformStorage::new(&dir);
+
+        // a score saved in the working directory by an older version is copied over once,
+        // before there's a data folder, and never again after
+        assert_eq!(storage.adopt_legacy(&legacy), 2);
+        std::fs::write(legacy.join(HIGH_SCORE_FILE), "60").unwrap();
+        assert_eq!(storage.adopt_legacy(&legacy), 0);
+        let game = GameState::with_storage(Arc::new(PlatformStorage::new(&dir)));
+        assert_eq!(game.high_score, 50);
+        assert!(!storage.keys().contains(&"README.md".to_string()));
+
+        // new saves go to the data folder and win from then on
+        storage.write(HIGH_SCORE_FILE, "80").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join(HIGH_SCORE_FILE)).unwrap(),
+            "80"
+        );
+        assert_eq!(storage.read(HIGH_SCORE_FILE).as_deref(), Some("80"));
+
+        // a reset clears the data folder and leaves the working directory alone
+        assert_eq!(reset_save_data(&storage).unwrap(), 2);
+        assert!(storage.keys().is_empty());
+        for (file, text) in [
+            (HIGH_SCORE_FILE, "60"),
+            ("slot_machine.txt", "notes"),
+            ("README.md", "mine"),
+        ] {
+            assert_eq!(std::fs::read_to_string(legacy.join(file)).unwrap(), text);
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_suspend_goes_through_storage() {
+        let mut game = GameState::new();
+        game.score = 30;
+        game.suspend();
+        assert!(game.storage.read(SUSPEND_FILE).is_some());
+
+        let mut resumed = GameState::with_storage(game.storage.clone());
+        assert!(resumed.resume_suspended());
+        assert_eq!(resumed.score, 30);
+        // picked up once only
+        assert!(!resumed.resume_suspended());
+    }
+
+    #[test]
+    fn test_save_slots() {
+        let storage: Arc<dyn StorageBackend> = Arc::new(InMemoryStorage::default());
+        let mut recorder = ReplayRecorder::new(GameState::with_storage(storage.clone()));
+        recorder.game.score = 40;
+        let press = |recorder: &mut ReplayRecorder, key| {
+            drive(recorder, &[SyntheticInput::Press(key)]);
+        };
+
+        // F5 and Enter saves into the empty first slot straight away
+        press(&mut recorder, KeyCode::F5);
+        assert!(recorder.slot_menu.as_ref().is_some_and(|menu| menu.saving));
+        press(&mut recorder, KeyCode::Return);
+        assert!(recorder.slot_menu.is_none());
+        let saved = recorder.game.save_slot(0).unwrap();
+        assert_eq!(saved.score, 40);
+        assert_eq!(
+            (saved.thumbnail.width, saved.thumbnail.height),
+            (GRID_WIDTH as usize, GRID_HEIGHT as usize)
+        );
+        assert_eq!(SaveSlot::parse(&saved.to_text()), Some(saved.clone()));
+        // and slots from before thumbnails had a size still load
+        let old = saved.to_text().replace(
+            &format!("thumbnail {}x{} ", GRID_WIDTH, GRID_HEIGHT),
+            "thumbnail ",
+        );
+        assert_eq!(SaveSlot::parse(&old), Some(saved.clone()));
+        // a damaged size is turned down rather than overflowing
+        for size in ["99999999999x99999999999", "201x1", "0x0"] {
+            let damaged = saved.to_text().replace(
+                &format!("thumbnail {}x{} ", GRID_WIDTH, GRID_HEIGHT),
+                &format!("thumbnail {} ", size),
+            );
+            assert_eq!(SaveSlot::parse(&damaged), None, "{}", size);
+        }
+        assert!(storage.read("slot1.txt").is_some());
+
+        // saving over it asks first, and backing out leaves it alone
+        recorder.game.score = 70;
+        press(&mut recorder, KeyCode::F5);
+        press(&mut recorder, KeyCode::Return);
+        assert!(recorder.confirm.is_some());
+        press(&mut recorder, KeyCode::Escape);
+        assert!(recorder.confirm.is_none() && recorder.slot_menu.is_some());
+        assert_eq!(recorder.game.save_slot(0).unwrap().score, 40);
+        press(&mut recorder, KeyCode::Escape);
+        assert!(recorder.slot_menu.is_none());
+
+        // F6 loads it back, held until a key
+        press(&mut recorder, KeyCode::F6);
+        press(&mut recorder, KeyCode::Return);
+        assert_eq!(recorder.game.score, 40);
+        assert!(recorder.paused);
+        press(&mut recorder, KeyCode::Space);
+
+        // an empty slot has nothing to load, and deleting asks first
+        press(&mut recorder, KeyCode::F6);
+        press(&mut recorder, KeyCode::Down);
+        press(&mut recorder, KeyCode::Return);
+        assert!(recorder.slot_menu.is_some());
+        press(&mut recorder, KeyCode::Up);
+        press(&mut recorder, KeyCode::Delete);
+        press(&mut recorder, KeyCode::Up); // from Cancel up to Delete
+        press(&mut recorder, KeyCode::Return);
+        assert!(recorder.game.save_slot(0).is_none());
+        assert!(recorder
+            .slot_menu
+            .unwrap()
+            .slots
+            .iter()
+            .all(Option::is_none));
+    }
+
+    #[test]
+    fn test_autosave_offered_at_launch() {
+        let storage: Arc<dyn StorageBackend> = Arc::new(InMemoryStorage::default());
+        let mut recorder = ReplayRecorder::new(GameState::with_storage(storage.clone()));
+        recorder.game.score = 40;
+        recorder.since_autosave = AUTOSAVE_INTERVAL - 0.001;
+        drive(&mut recorder, &[SyntheticInput::Wait(SYNTHETIC_FRAME)]);
+        assert!(storage.read(AUTOSAVE_FILE).is_some());
+
+        // the next launch asks, and Continue (focused) picks the run up held
+        let mut next = ReplayRecorder::new(GameState::with_storage(storage.clone()));
+        next.game.change_screen(Screen::Splash);
+        assert!(next.game.has_autosave());
+        next.offer_autosave();
+        drive(&mut next, &[SyntheticInput::Press(KeyCode::Return)]);
+        assert_eq!(next.game.score, 40);
+        assert_eq!(next.game.screen, Screen::Playing);
+        assert!(next.paused);
+        assert!(!next.game.has_autosave());
+
+        // backing out starts a new run and throws the autosave away
+        next.game.autosave();
+        let mut declined = ReplayRecorder::new(GameState::with_storage(storage.clone()));
+        declined.offer_autosave();
+        drive(&mut declined, &[SyntheticInput::Press(KeyCode::Escape)]);
+        assert!(declined.confirm.is_none());
+        assert_eq!(declined.game.score, 0);
+        assert!(!declined.game.has_autosave());
+
+        // and a run that ends takes its autosave with it
+        next.game.autosave();
+        next.paused = false;
+        next.game.high_score = u32::MAX;
+        drive(
+            &mut next,
+            &[SyntheticInput::Wait(std::time::Duration::from_secs(10))],
+        );
+        assert!(next.game.game_over);
+        assert!(!next.game.has_autosave());
     }
 
     #[test]
@@ -812,6 +1263,4348 @@ mod tests {
         assert_eq!(game.high_score, 50); // Should not change
     }
 
+    #[test]
+    fn test_best_time_update() {
+        let mut game = GameState::new();
+        game.elapsed = 95.0;
+        game.best_time = 60.0;
+
+        game.update_best_time();
+        assert_eq!(game.best_time, 95.0);
+
+        // A shorter run shouldn't replace the best time
+        game.elapsed = 30.0;
+        game.update_best_time();
+        assert_eq!(game.best_time, 95.0);
+    }
+
+    #[test]
+    fn test_new_game_skips_splash() {
+        // Restarting goes straight back into play, only run_game shows the splash
+        let game = GameState::new();
+        assert_eq!(game.screen, Screen::Playing);
+    }
+
+    #[test]
+    fn test_change_screen_resets_screen_time() {
+        let mut game = GameState::new();
+        game.screen_time = 5.0;
+
+        game.change_screen(Screen::Splash);
+        assert_eq!(game.screen, Screen::Splash);
+        assert_eq!(game.screen_time, 0.0);
+    }
+
+    #[test]
+    fn test_fade_alpha() {
+        // Fades in, holds at full opacity, then fades out
+        assert_eq!(fade_alpha(0.0, 2.5, 0.5), 0.0);
+        assert_eq!(fade_alpha(0.25, 2.5, 0.5), 0.5);
+        assert_eq!(fade_alpha(1.25, 2.5, 0.5), 1.0);
+        assert_eq!(fade_alpha(2.25, 2.5, 0.5), 0.5);
+        assert_eq!(fade_alpha(3.0, 2.5, 0.5), 0.0);
+
+        // No fade just pops in and out
+        assert_eq!(fade_alpha(1.0, 2.5, 0.0), 1.0);
+        assert_eq!(fade_alpha(3.0, 2.5, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_transition_progress() {
+        let mut transition = Transition::new(TransitionKind::Fade, 0.4);
+        assert_eq!(transition.remaining(), 1.0);
+        assert!(!transition.is_finished());
+
+        transition.advance(0.2);
+        assert_eq!(transition.remaining(), 0.5);
+
+        transition.advance(0.3);
+        assert_eq!(transition.remaining(), 0.0);
+        assert!(transition.is_finished());
+    }
+
+    #[test]
+    fn test_change_screen_with_transition() {
+        let mut game = GameState::new();
+        game.change_screen_with(Screen::Splash, TransitionKind::Wipe);
+        assert_eq!(game.screen, Screen::Splash);
+        assert_eq!(
+            game.transition.map(|transition| transition.kind),
+            Some(TransitionKind::Wipe)
+        );
+
+        game.skip_transition();
+        assert!(game.transition.is_none());
+    }
+
+    #[test]
+    fn test_reduced_motion_disables_transitions() {
+        let mut game = GameState::new();
+        game.reduced_motion = true;
+
+        game.change_screen_with(Screen::Playing, TransitionKind::Fade);
+        assert!(game.transition.is_none());
+
+        // The preference survives a restart
+        game.restart();
+        assert!(game.reduced_motion);
+        assert!(game.transition.is_none());
+    }
+
+    #[test]
+    fn test_main_menu() {
+        let mut game = GameState::with_storage(Arc::new(InMemoryStorage::default()));
+        game.change_screen(Screen::Splash);
+        while game.screen == Screen::Splash {
+            game.tick(1.0);
+        }
+        assert_eq!((game.screen, game.menu.focus), (Screen::MainMenu, 0));
+
+        // Settings switch things on and off, and Esc comes back to the menu on Settings
+        drive(
+            &mut game,
+            &[
+                SyntheticInput::Press(KeyCode::Down),
+                SyntheticInput::Press(KeyCode::Return),
+            ],
+        );
+        assert_eq!(game.screen, Screen::Settings);
+        assert_eq!(game.setting_text(Setting::Sound), "on");
+        let to_sound = Setting::ALL
+            .iter()
+            .position(|s| *s == Setting::Sound)
+            .unwrap();
+        for _ in 0..to_sound {
+            drive(&mut game, &[SyntheticInput::Press(KeyCode::Down)]);
+        }
+        drive(&mut game, &[SyntheticInput::Press(KeyCode::Return)]);
+        assert!(game.mixer.muted);
+        assert_eq!(game.setting_text(Setting::Sound), "off");
+        drive(&mut game, &[SyntheticInput::Press(KeyCode::Escape)]);
+        assert_eq!((game.screen, game.menu.focus), (Screen::MainMenu, 1));
+
+        // the menu holds the game still until Play
+        let head = game.snake[0];
+        game.tick(1.0);
+        assert_eq!(game.snake[0], head);
+        drive(
+            &mut game,
+            &[
+                SyntheticInput::Press(KeyCode::Up),
+                SyntheticInput::Press(KeyCode::Return),
+            ],
+        );
+        assert_eq!(game.screen, Screen::Playing);
+        game.skip_transition();
+        game.tick(1.0);
+        assert_ne!(game.snake[0], head);
+
+        // Space pauses and carries on, and Esc from the pause leaves the run to come back to
+        drive(&mut game, &[SyntheticInput::Press(KeyCode::Space)]);
+        assert_eq!(game.screen, Screen::Paused);
+        let (head, elapsed) = (game.snake[0], game.elapsed);
+        game.tick(1.0);
+        assert_eq!((game.snake[0], game.elapsed), (head, elapsed));
+        drive(&mut game, &[SyntheticInput::Press(KeyCode::Space)]);
+        assert_eq!(game.screen, Screen::Playing);
+        drive(
+            &mut game,
+            &[
+                SyntheticInput::Press(KeyCode::Space),
+                SyntheticInput::Press(KeyCode::Escape),
+            ],
+        );
+        assert_eq!(game.screen, Screen::MainMenu);
+        assert!(game.can_save_run());
+        drive(&mut game, &[SyntheticInput::Press(KeyCode::Return)]);
+        assert_eq!((game.screen, game.elapsed), (Screen::Playing, elapsed));
+
+        // a run that ends goes to the game over screen, and Play from the menu starts anew
+        game.end_run();
+        assert_eq!(game.screen, Screen::GameOver);
+        drive(&mut game, &[SyntheticInput::Press(KeyCode::Escape)]);
+        assert_eq!(game.screen, Screen::MainMenu);
+        drive(&mut game, &[SyntheticInput::Press(KeyCode::Return)]);
+        assert!(!game.game_over && game.elapsed == 0.0);
+        assert_eq!(game.screen, Screen::Playing);
+
+        // tapping an entry picks it, and Quit asks the window to close
+        game.change_screen(Screen::MainMenu);
+        let (x, y) = (
+            game.config.screen_width() / 2.0,
+            game.config.screen_height() / 2.0 + 60.0,
+        );
+        assert_eq!(game.screen_tap(x, y), Some(MenuEntry::Quit));
+        assert!(game.menu.quit);
+
+        // the recorder quits from the menu, and Play opens the level select on a level run
+        let mut recorder = ReplayRecorder::new(GameState::with_storage(Arc::new(
+            InMemoryStorage::default(),
+        )));
+        recorder.game.change_screen(Screen::MainMenu);
+        drive(&mut recorder, &[SyntheticInput::Press(KeyCode::Escape)]);
+        assert!(recorder.exiting);
+        let level = Level::parse("name = \"Only\"\ntarget_score = 10").unwrap();
+        let mut levels = ReplayRecorder::new(GameState::with_level(level));
+        levels.game.change_screen(Screen::MainMenu);
+        drive(&mut levels, &[SyntheticInput::Press(KeyCode::Return)]);
+        assert!(levels.level_select.is_some());
+    }
+
+    #[test]
+    fn test_settings_file() {
+        let settings = Settings {
+            speed: SpeedPreset::Fast,
+            walls: WallMode::Wrap,
+            theme: ThemePreset::Ocean,
+            grid: (28, 21),
+            sound: false,
+            ..Settings::default()
+        };
+        assert_eq!(Settings::parse(&settings.to_text()), Ok(settings));
+        assert_eq!(
+            Settings::parse("# mine\nspeed = slow\n").map(|s| s.speed),
+            Ok(SpeedPreset::Slow)
+        );
+        for bad in [
+            "walls = \"sticky\"",
+            "grid = \"3x3\"",
+            "grid = big",
+            "sound = maybe",
+        ] {
+            assert!(
+                Settings::parse(bad).unwrap_err().starts_with("line 1:"),
+                "{}",
+                bad
+            );
+        }
+
+        // Settings saved from the screen are what the next launch starts with
+        let storage = Arc::new(InMemoryStorage::default());
+        let mut game = GameState::with_storage(storage.clone());
+        game.change_screen(Screen::Settings);
+        drive(&mut game, &[SyntheticInput::Press(KeyCode::Right)]);
+        drive(
+            &mut game,
+            &[
+                SyntheticInput::Press(KeyCode::Down),
+                SyntheticInput::Press(KeyCode::Left),
+            ],
+        );
+        assert_eq!(game.setting_text(Setting::Speed), "fast");
+        assert_eq!(game.setting_text(Setting::Walls), "wrap");
+        let saved = Settings::load(storage.as_ref());
+        assert_eq!(
+            (saved.speed, saved.walls),
+            (SpeedPreset::Fast, WallMode::Wrap)
+        );
+        // ...but not this run's rules
+        assert_eq!(game.config.walls, WallMode::Solid);
+
+        // The next run wraps around the edges and keeps its own best
+        game.restart();
+        assert_eq!(game.config.speed, SpeedPreset::Fast);
+        assert_eq!(game.high_score_file(), "high_score_wrap_fast.txt");
+        let edge = Position::new(game.config.grid_width - 1, 4);
+        assert_eq!(
+            game.config.step(edge, Direction::Right),
+            Position::new(0, 4)
+        );
+
+        // A launch picks the board size up from the settings unless a flag says otherwise
+        let saved = Settings {
+            grid: (16, 12),
+            ..saved
+        };
+        let game = LaunchOptions::default().new_game_with(&saved);
+        assert_eq!(
+            (game.config.grid_width, game.config.walls),
+            (16, WallMode::Wrap)
+        );
+        let options = LaunchOptions {
+            grid: Some((30, 20)),
+            ..LaunchOptions::default()
+        };
+        assert_eq!(options.game_config_with(&saved).grid_width, 30);
+    }
+
+    #[test]
+    fn test_segment_color_gradient() {
+        let theme = Theme::default();
+
+        // Head and tail get the theme's end colors
+        assert_eq!(theme.segment_color(0, 5), theme.snake_head);
+        assert_eq!(theme.segment_color(4, 5), theme.snake_tail);
+
+        // The middle segment sits halfway between them
+        let middle = theme.segment_color(2, 5);
+        assert_eq!(middle, lerp_color(theme.snake_head, theme.snake_tail, 0.5));
+
+        // A single segment snake is all head
+        assert_eq!(theme.segment_color(0, 1), theme.snake_head);
+    }
+
+    #[test]
+    fn test_day_night_cycle() {
+        let theme = Theme::default();
+        let period = theme.day_night_period;
+
+        // The cycle wraps back around to where it started
+        assert_eq!(theme.background_at(0.0), theme.background_at(period));
+
+        // Quarter points land exactly on the palette colors
+        assert_ne!(theme.background_at(0.0), theme.background_at(period / 4.0));
+        assert_eq!(
+            theme.background_at(period * 0.75),
+            Color::new(0.0, 0.0, 0.0, 1.0)
+        );
+
+        // Turning the cycle off keeps the plain background
+        let static_theme = Theme {
+            day_night_period: 0.0,
+            ..Theme::default()
+        };
+        assert_eq!(static_theme.background_at(100.0), static_theme.background);
+    }
+
+    #[test]
+    fn test_theme_survives_restart() {
+        let mut game = GameState::new();
+        game.theme.snake_head = Color::BLUE;
+
+        game.restart();
+        assert_eq!(game.theme.snake_head, Color::BLUE);
+    }
+
+    #[test]
+    fn test_theme_file_parsing() {
+        let theme = Theme::parse(
+            "# sunset\nbackground = #201008\nfood = 1.0 0.5 0.0 # orange\n\nday_night_period = 0\n",
+        )
+        .unwrap();
+        assert_eq!(theme.background, Color::from_rgb(0x20, 0x10, 0x08));
+        assert_eq!(theme.food, Color::new(1.0, 0.5, 0.0, 1.0));
+        assert_eq!(theme.day_night_period, 0.0);
+        assert_eq!(theme.snake_head, Theme::default().snake_head);
+
+        assert_eq!(
+            Theme::parse("food = #ff00zz").unwrap_err(),
+            "line 1: bad color `#ff00zz`"
+        );
+        assert_eq!(
+            Theme::parse("\nsnake = #ffffff").unwrap_err(),
+            "line 2: unknown key `snake`"
+        );
+        assert!(Theme::parse("food = 2.0 0.0 0.0").is_err());
+        assert!(Theme::parse("food").is_err());
+    }
+
+    #[test]
+    fn test_broken_theme_file_keeps_current_theme() {
+        let path = std::env::temp_dir().join(format!("snake-theme-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut game = GameState::new();
+        game.theme.rainbow = true;
+
+        std::fs::write(path, "snake_head = #0000ff\n").unwrap();
+        assert!(game.reload_theme(path));
+        assert_eq!(game.theme.snake_head, Color::from_rgb(0, 0, 255));
+        assert!(game.theme.rainbow);
+        assert!(game.toast.is_none());
+
+        std::fs::write(path, "snake_head = blue\n").unwrap();
+        assert!(!game.reload_theme(path));
+        assert_eq!(game.theme.snake_head, Color::from_rgb(0, 0, 255));
+        let toast = game.toast.expect("a broken theme should say why");
+        assert!(toast.message.ends_with("line 1: bad color `blue`"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_pack_manifest_validation() {
+        let manifest = PackManifest::parse(
+            "# my pack\nname = \"neon\"\nversion = \"1.2\"\nauthor = \"Sam\"\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.name, "neon");
+        assert_eq!(manifest.version, "1.2");
+        assert_eq!(manifest.author, "Sam");
+        assert_eq!(manifest.description, "");
+
+        assert_eq!(
+            PackManifest::parse("name = \"neon\"").unwrap_err(),
+            "missing `version`"
+        );
+        assert_eq!(
+            PackManifest::parse("name = neon").unwrap_err(),
+            "line 1: `name` needs a quoted string"
+        );
+        assert_eq!(
+            PackManifest::parse("name = \"a\"\nname = \"b\"").unwrap_err(),
+            "line 2: `name` is set twice"
+        );
+        assert!(PackManifest::parse("name = \"../up\"\nversion = \"1\"").is_err());
+        assert!(PackManifest::parse("colour = \"red\"").is_err());
+    }
+
+    #[test]
+    fn test_discover_packs_skips_broken_and_duplicate_packs() {
+        let mods = std::env::temp_dir().join(format!("snake-mods-{}", std::process::id()));
+        let write = |pack: &str, file: &str, text: &str| {
+            std::fs::create_dir_all(mods.join(pack)).unwrap();
+            std::fs::write(mods.join(pack).join(file), text).unwrap();
+        };
+        write(
+            "a-neon",
+            PACK_MANIFEST,
+            "name = \"neon\"\nversion = \"1\"\n",
+        );
+        write("a-neon", THEME_FILE, "food = #00ffff\n");
+        write(
+            "b-neon-copy",
+            PACK_MANIFEST,
+            "name = \"neon\"\nversion = \"2\"\n",
+        );
+        write(
+            "c-broken",
+            PACK_MANIFEST,
+            "name = \"broken\"\nversion = \"1\"\n",
+        );
+        write("c-broken", THEME_FILE, "food = cyan\n");
+        write(
+            "d-plain",
+            PACK_MANIFEST,
+            "name = \"plain\"\nversion = \"1\"\n",
+        );
+        write("d-plain", FEEDBACK_FILE, "died = nothing\n");
+
+        let (packs, errors) = discover_packs(&mods.to_string_lossy());
+        let names: Vec<&str> = packs.iter().map(|p| p.manifest.name.as_str()).collect();
+        assert_eq!(names, ["neon", "plain"]);
+        assert_eq!(packs[0].manifest.version, "1"); // the first folder wins
+        assert_eq!(packs[1].theme, None);
+        let feedback = FeedbackRouter::for_packs(&packs);
+        assert!(feedback.outputs(FeedbackEvent::Died).is_empty());
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(&errors[0], PackError::Conflict { name, .. } if name == "neon"));
+        assert!(errors[1]
+            .to_string()
+            .contains("theme.txt: line 1: bad color"));
+
+        let mut game = GameState::new();
+        packs[0].apply(&mut game);
+        assert_eq!(game.theme.food, Color::from_rgb(0, 255, 255));
+
+        std::fs::remove_dir_all(&mods).unwrap();
+        assert!(discover_packs(&mods.to_string_lossy()).0.is_empty());
+    }
+
+    #[test]
+    fn test_replays_are_saved_to_storage() {
+        let storage = Arc::new(InMemoryStorage::default());
+        let mut recorder = ReplayRecorder::new(GameState::with_storage(storage.clone()));
+        let head = recorder.game.snake[0];
+        recorder.game.end_run();
+        recorder.track(head, false);
+
+        let replays = Replay::load_all(storage.as_ref());
+        assert_eq!(replays.len(), 1);
+        assert_eq!(storage.keys(), [replays[0].key()]);
+        assert_eq!(replays[0].tick_count(), recorder.replay.tick_count());
+    }
+
+    #[test]
+    fn test_save_archive_export_import_and_reset() {
+        let storage = InMemoryStorage::default();
+        storage.write_bytes("replay_1.snkr", &[1, 2, 3]).unwrap();
+        storage.write(HIGH_SCORE_FILE, "120").unwrap();
+        storage.write("high_score_coop_neon.txt", "40").unwrap();
+        storage.write(MIXER_FILE, "music = 0.5\n").unwrap();
+        storage.write("unrelated.txt", "not ours").unwrap();
+        // the ghost and ratings live in storage too, so they go along
+        let ghost = GhostRun::start(&GameState::new());
+        ghost.save(&storage);
+        let mut ratings = Ratings::default();
+        ratings.record_match("Ann", "Bob");
+        ratings.save(&storage);
+
+        let archive = SaveArchive::collect(&storage);
+        let paths: Vec<&str> = archive
+            .files
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "data/ghost.txt",
+                "data/high_score.txt",
+                "data/high_score_coop_neon.txt",
+                "data/mixer.txt",
+                "data/ratings.txt",
+                "replays/1.snkr"
+            ]
+        );
+        let bytes = archive.to_bytes();
+        assert_eq!(SaveArchive::from_bytes(&bytes).unwrap(), archive);
+
+        // reset asks first, then takes everything of the player's and nothing else
+        let mut confirm = SaveDataAction::confirm_reset();
+        assert_eq!(confirm.input(UiInput::Accept), Some(false));
+        assert_eq!(reset_save_data(&storage).unwrap(), 6);
+        assert_eq!(storage.keys(), ["unrelated.txt"]);
+
+        // and importing brings it all back
+        let imported = SaveArchive::from_bytes(&bytes).unwrap();
+        assert_eq!(imported.restore(&storage).unwrap(), 6);
+        assert_eq!(storage.read(HIGH_SCORE_FILE).as_deref(), Some("120"));
+        assert_eq!(GhostRun::load(&storage), Some(ghost));
+        assert_eq!(Ratings::load(&storage), ratings);
+        assert_eq!(storage.read_bytes("replay_1.snkr").unwrap(), [1, 2, 3]);
+        assert_eq!(GameState::with_storage(Arc::new(storage)).high_score, 120);
+
+        let mut corrupted = bytes.clone();
+        corrupted[10] ^= 1;
+        assert_eq!(
+            SaveArchive::from_bytes(&corrupted).unwrap_err(),
+            "save file is corrupted"
+        );
+        assert_eq!(
+            SaveArchive::from_bytes(&PackArchive { files: Vec::new() }.to_bytes()).unwrap_err(),
+            "not a snake save file"
+        );
+        let sneaky = SaveArchive {
+            files: vec![("replays/../../evil".to_string(), Vec::new())],
+        };
+        assert!(SaveArchive::from_bytes(&sneaky.to_bytes())
+            .unwrap_err()
+            .starts_with("save file has something it shouldn't"));
+    }
+
+    #[test]
+    fn test_pack_archive_round_trip_and_install() {
+        let root = std::env::temp_dir().join(format!("snake-archive-{}", std::process::id()));
+        let source = root.join("source");
+        std::fs::create_dir_all(source.join("levels")).unwrap();
+        std::fs::write(
+            source.join(PACK_MANIFEST),
+            "name = \"neon\"\nversion = \"1\"\n",
+        )
+        .unwrap();
+        std::fs::write(source.join(THEME_FILE), "food = #00ffff\n").unwrap();
+        std::fs::write(source.join("levels").join("one.txt"), "#####\n").unwrap();
+
+        let archive = PackArchive::from_dir(&source).unwrap();
+        let paths: Vec<&str> = archive
+            .files
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect();
+        assert_eq!(paths, ["levels/one.txt", PACK_MANIFEST, THEME_FILE]);
+        let bytes = archive.to_bytes();
+        assert_eq!(PackArchive::from_bytes(&bytes).unwrap(), archive);
+
+        let mods = root.join("mods");
+        let mods = mods.to_str().unwrap();
+        let installed = archive.install(mods).unwrap();
+        assert!(installed.join("levels").join("one.txt").exists());
+        let (packs, _) = discover_packs(mods);
+        assert_eq!(packs[0].theme.unwrap().food, Color::from_rgb(0, 255, 255));
+        assert_eq!(
+            archive.install(mods).unwrap_err(),
+            PackArchiveError::AlreadyInstalled("neon".to_string())
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_pack_archive_rejects_unsafe_packs() {
+        let manifest = (
+            PACK_MANIFEST.to_string(),
+            b"name = \"neon\"\nversion = \"1\"\n".to_vec(),
+        );
+        let with = |file: (&str, usize)| PackArchive {
+            files: vec![manifest.clone(), (file.0.to_string(), vec![b'x'; file.1])],
+        };
+
+        for path in [
+            "../evil.txt",
+            "/etc/passwd",
+            "levels/../../evil",
+            "C:evil",
+            "a\\b",
+        ] {
+            assert_eq!(
+                PackArchive::from_bytes(&with((path, 1)).to_bytes()),
+                Err(PackArchiveError::UnsafePath(path.to_string()))
+            );
+        }
+        assert_eq!(
+            PackArchive::from_bytes(&with(("big.ogg", MAX_PACK_ASSET_SIZE + 1)).to_bytes()),
+            Err(PackArchiveError::TooLarge("big.ogg".to_string()))
+        );
+
+        let mut bytes = with(("levels/one.txt", 10)).to_bytes();
+        assert!(PackArchive::from_bytes(&bytes).is_ok());
+        bytes[20] ^= 1;
+        assert_eq!(
+            PackArchive::from_bytes(&bytes),
+            Err(PackArchiveError::ChecksumMismatch)
+        );
+        assert_eq!(
+            PackArchive::from_bytes(b"PK\x03\x04"),
+            Err(PackArchiveError::NotAPack)
+        );
+        let no_manifest = PackArchive {
+            files: vec![("theme.txt".to_string(), Vec::new())],
+        };
+        assert!(matches!(
+            PackArchive::from_bytes(&no_manifest.to_bytes()),
+            Err(PackArchiveError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_pack_profile_load_order() {
+        let pack = |name: &str| Pack {
+            dir: std::path::PathBuf::from(name),
+            manifest: PackManifest::parse(&format!("name = \"{}\"\nversion = \"1\"", name))
+                .unwrap(),
+            theme: None,
+            start: None,
+            feedback: None,
+        };
+        let packs = [pack("neon"), pack("retro"), pack("pastel")];
+
+        let mut profile = PackProfile::parse("retro\ngone\n\nretro\nneon\n");
+        assert_eq!(profile.enabled, ["retro", "gone", "neon"]);
+        let resolved: Vec<&str> = profile
+            .resolve(&packs)
+            .iter()
+            .map(|p| p.manifest.name.as_str())
+            .collect();
+        assert_eq!(resolved, ["retro", "neon"]); // uninstalled packs are left out
+
+        profile.toggle("gone");
+        profile.toggle("pastel");
+        profile.shift("neon", 1);
+        profile.shift("retro", -1); // already first
+        assert_eq!(profile.enabled, ["retro", "pastel", "neon"]);
+        profile.shift("neon", -1);
+        assert_eq!(profile.enabled, ["retro", "neon", "pastel"]);
+        assert!(!profile.is_enabled("gone"));
+
+        let path = std::env::temp_dir().join(format!("snake-packs-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        profile.save(path).unwrap();
+        assert_eq!(PackProfile::load(path), profile);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_packs_get_their_own_high_score_file() {
+        let mut game = GameState::new();
+        game.packs = vec!["retro".to_string(), "neon".to_string()];
+        assert_eq!(game.high_score_file(), "high_score_neon+retro.txt");
+        game.coop = true;
+        assert_eq!(game.high_score_file(), "high_score_coop_neon+retro.txt");
+
+        game.restart();
+        assert_eq!(game.packs, ["retro", "neon"]);
+    }
+
+    #[test]
+    fn test_rainbow_needs_unlocking() {
+        let mut game = GameState::new();
+        game.high_score = RAINBOW_UNLOCK_SCORE - 10;
+        game.toggle_rainbow();
+        assert!(!game.theme.rainbow);
+
+        game.high_score = RAINBOW_UNLOCK_SCORE;
+        game.toggle_rainbow();
+        assert!(game.theme.rainbow);
+        assert!(game.party_mode_active());
+    }
+
+    #[test]
+    fn test_photosensitive_disables_party_mode() {
+        let mut game = GameState::new();
+        game.theme.rainbow = true;
+        game.photosensitive = true;
+
+        assert!(!game.party_mode_active());
+        // Falls back to the normal gradient
+        assert_eq!(game.segment_color(0), game.theme.snake_head);
+    }
+
+    #[test]
+    fn test_hue_color() {
+        assert_eq!(hue_color(0.0), Color::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(hue_color(1.0 / 3.0), Color::new(0.0, 1.0, 0.0, 1.0));
+        assert_eq!(hue_color(1.0), hue_color(0.0));
+    }
+
+    #[test]
+    fn test_slippery_cell_delays_turn() {
+        let mut game = GameState::new();
+        let head = game.snake[0];
+        game.slippery_cells.push((head, SLIPPERY_TICKS));
+
+        // Turning while on a wet cell doesn't take effect this tick
+        game.handle_input(Direction::Up);
+        game.apply_turn();
+        assert_eq!(game.direction, Direction::Right);
+        game.move_snake();
+
+        // Off the wet cell the queued turn goes through
+        game.apply_turn();
+        assert_eq!(game.direction, Direction::Up);
+    }
+
+    #[test]
+    fn test_ice_only_turns_on_grip_cells() {
+        let mut game = GameState::new_ice();
+        game.grip_cells = vec![Position::new(GRID_WIDTH / 2 + 2, GRID_HEIGHT / 2)];
+
+        // Off grip the snake keeps sliding
+        game.handle_input(Direction::Up);
+        game.apply_turn();
+        assert_eq!(game.direction, Direction::Right);
+        game.move_snake();
+        game.apply_turn();
+        assert_eq!(game.direction, Direction::Right);
+        game.move_snake();
+
+        // The queued turn goes through once the head is on the grip cell
+        game.apply_turn();
+        assert_eq!(game.direction, Direction::Up);
+    }
+
+    #[test]
+    fn test_ice_survives_restart() {
+        let mut game = GameState::new_ice();
+        let grip_cells = game.grip_cells.clone();
+        assert!(!grip_cells.is_empty());
+
+        game.restart();
+        assert!(game.ice);
+        assert_eq!(game.grip_cells, grip_cells);
+    }
+
+    #[test]
+    fn test_slippery_cells_dry_out() {
+        let mut game = GameState::new();
+        game.slippery_cells.push((Position::new(0, 0), 2));
+
+        game.update_weather();
+        assert!(game.is_slippery(Position::new(0, 0)));
+        game.update_weather();
+        assert!(!game.is_slippery(Position::new(0, 0)));
+    }
+
+    #[test]
+    fn test_fog_visibility() {
+        let mut game = GameState::new();
+        let head = game.snake[0];
+        let far_away = Position::new(head.x - 5, head.y);
+
+        assert!(game.is_visible(far_away));
+
+        game.weather = Weather::Fog { radius: 3 };
+        assert!(game.is_visible(head));
+        assert!(game.is_visible(Position::new(head.x, head.y + 3)));
+        assert!(!game.is_visible(far_away));
+    }
+
+    #[test]
+    fn test_text_cache() {
+        let cache = TextCache::default();
+        // a stand-in for measuring, which needs a window: 8 pixels a character
+        let width = |key: TextKey| {
+            let text = ggez::graphics::Text::new(key.string.as_str());
+            let bounds = [key.string.len() as f32 * 8.0, 16.0].into();
+            cache
+                .with_text(key, || Ok((text, bounds)), |_, bounds| bounds.x)
+                .unwrap()
+        };
+
+        // built and measured once, then reused until the string or its style changes
+        assert_eq!(width(TextKey::new("Score: 1", 16.0, Color::WHITE)), 64.0);
+        assert_eq!(width(TextKey::new("Score: 1", 16.0, Color::WHITE)), 64.0);
+        width(TextKey::new("Score: 1", 24.0, Color::WHITE));
+        width(TextKey::new("Score: 1", 16.0, Color::RED));
+        assert_eq!(cache.stats(), (3, 1, 3));
+        assert!(cache.stats_line().contains("3/128 entries, 25.0% hits"));
+
+        // a full cache starts over instead of growing with every score
+        for score in 0..TEXT_CACHE_CAPACITY {
+            width(TextKey::new(
+                &format!("Score: {}", score + 2),
+                16.0,
+                Color::WHITE,
+            ));
+        }
+        assert!(cache.stats().0 < TEXT_CACHE_CAPACITY);
+
+        // copies of a game don't carry the cache around
+        assert_eq!(cache.clone().stats(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_board_cells_batch() {
+        let mut game = GameState::new();
+        let head = game.snake[0];
+        game.grip_cells = vec![Position::new(0, 0)];
+        game.slippery_cells = vec![(Position::new(1, 0), 5)];
+
+        // grip and puddles under the snake, food on top, one instance each
+        let cells = game.board_cells();
+        assert_eq!(cells.len(), 2 + game.snake.len() + 1);
+        assert_eq!(
+            cells[0].0,
+            ggez::graphics::Rect::new(0.0, 0.0, CELL_SIZE - 2.0, CELL_SIZE - 2.0)
+        );
+        assert_eq!(cells[1].0.x, CELL_SIZE);
+        assert_eq!(cells[2].0.x, head.x as f32 * CELL_SIZE);
+        assert_eq!(cells[2].1, game.segment_color(0));
+        assert_eq!(cells.last().unwrap().1, game.theme.food);
+
+        // fogged cells aren't drawn at all
+        game.weather = Weather::Fog { radius: 1 };
+        let fogged = game.board_cells();
+        assert!(fogged.len() < cells.len());
+        assert!(fogged.iter().all(|(rect, _)| {
+            let cell = Position::new((rect.x / CELL_SIZE) as i32, (rect.y / CELL_SIZE) as i32);
+            game.is_visible(cell)
+        }));
+    }
+
+    #[test]
+    fn test_dual_game_ends_when_either_board_is_lost() {
+        let mut dual = DualGame::new();
+        assert!(!dual.is_over());
+
+        dual.boards[1].game_over = true;
+        dual.end_if_either_lost();
+        assert!(dual.is_over());
+        assert!(dual.boards[0].game_over);
+    }
+
+    #[test]
+    fn test_versus_key_routing() {
+        let dual = DualGame::new_versus(100);
+        assert_eq!(dual.board_for_key(KeyCode::W), Some(0));
+        assert_eq!(dual.board_for_key(KeyCode::Up), Some(1));
+        assert_eq!(dual.board_for_key(KeyCode::R), None);
+
+        // The solo challenge sends everything to both boards
+        let mirrored = DualGame::new();
+        assert_eq!(mirrored.board_for_key(KeyCode::W), None);
+    }
+
+    #[test]
+    fn test_versus_winner() {
+        let mut dual = DualGame::new_versus(50);
+        assert_eq!(dual.winner(), None);
+
+        // Reaching the target wins
+        dual.boards[1].score = 50;
+        assert_eq!(dual.winner(), Some(1));
+        dual.end_if_either_lost();
+        assert!(dual.boards[0].game_over);
+
+        // Crashing hands the win to the other player
+        let mut dual = DualGame::new_versus(50);
+        dual.boards[1].game_over = true;
+        assert_eq!(dual.winner(), Some(0));
+    }
+
+    #[test]
+    fn test_coop_turns_from_both_players_are_kept() {
+        let mut game = GameState::new();
+        game.coop = true;
+
+        // Both players turn in the same tick - neither input is lost
+        game.handle_input(Direction::Up);
+        game.handle_input(Direction::Left);
+        assert_eq!(game.turn_queue, vec![Direction::Up, Direction::Left]);
+
+        game.apply_turn();
+        assert_eq!(game.direction, Direction::Up);
+        game.move_snake();
+        game.apply_turn();
+        assert_eq!(game.direction, Direction::Left);
+        assert!(game.turn_queue.is_empty());
+    }
+
+    #[test]
+    fn test_coop_ignores_same_axis_turns() {
+        let mut game = GameState::new();
+        game.coop = true;
+
+        // Already moving right, so left (reverse) and right (no-op) are both dropped
+        game.handle_input(Direction::Left);
+        game.handle_input(Direction::Right);
+        assert!(game.turn_queue.is_empty());
+
+        // Queue is capped so mashing doesn't build up a backlog
+        game.handle_input(Direction::Up);
+        game.handle_input(Direction::Right);
+        game.handle_input(Direction::Down);
+        assert_eq!(game.turn_queue.len(), MAX_QUEUED_TURNS);
+    }
+
+    #[test]
+    fn test_coop_key_ownership() {
+        assert!(!GameState::is_blocked_in_coop(KeyCode::A));
+        assert!(!GameState::is_blocked_in_coop(KeyCode::Up));
+        assert!(GameState::is_blocked_in_coop(KeyCode::W));
+        assert!(GameState::is_blocked_in_coop(KeyCode::Right));
+    }
+
+    #[test]
+    fn test_rasterize_paints_cells() {
+        let mut game = GameState::new();
+        game.theme.day_night_period = 0.0;
+        game.food = Food::normal(Position::new(0, 0));
+        let cell_size = 4;
+        let width = GRID_WIDTH as usize * cell_size;
+        let mut frame = vec![0; width * GRID_HEIGHT as usize * cell_size * 4];
+        rasterize(&game, &mut frame, cell_size);
+
+        let pixel = |x: usize, y: usize| {
+            let start = (y * width + x) * 4;
+            frame[start..start + 4].to_vec()
+        };
+        let rgba = |color: Color| {
+            let (r, g, b, a) = color.to_rgba();
+            vec![r, g, b, a]
+        };
+        assert_eq!(pixel(0, 0), rgba(game.theme.food));
+        assert_eq!(pixel(3, 3), rgba(game.theme.background)); // the gap between cells
+        let head = game.snake[0];
+        let (x, y) = (head.x as usize * cell_size, head.y as usize * cell_size);
+        assert_eq!(pixel(x + 1, y + 2), rgba(game.theme.snake_head));
+        assert_eq!(pixel(width - 1, 0), rgba(game.theme.background));
+    }
+
+    #[test]
+    fn test_thumbnails() {
+        // on the default board a thumbnail is what rasterize paints, with or without a window
+        let mut game = GameState::new();
+        game.theme.day_night_period = 0.0;
+        let thumbnail = Thumbnail::of(&game, 2);
+        assert_eq!(
+            (thumbnail.width, thumbnail.height),
+            (GRID_WIDTH as usize * 2, GRID_HEIGHT as usize * 2)
+        );
+        let mut frame = vec![0; thumbnail.pixels.len()];
+        rasterize(&game, &mut frame, 2);
+        assert!(thumbnail.pixels == frame);
+        assert_eq!(Thumbnail::render(None, &game, 2), thumbnail);
+
+        // a level's is its own size, with its walls
+        let corner = "name = \"Corner\"\nsize = \"8x6\"\ntarget_score = 5\nwalls = \"\"\"\n\
+            #.......\n........\n........\n........\n........\n........\n\"\"\"\n";
+        let thumbnail = Level::parse(corner).unwrap().thumbnail(1);
+        assert_eq!((thumbnail.width, thumbnail.height), (8, 6));
+        let (r, g, b, a) = Color::new(0.55, 0.55, 0.6, 1.0).to_rgba();
+        assert_eq!(thumbnail.pixels[..4], [r, g, b, a]);
+
+        // and comes back out of a PNG the same
+        let png = thumbnail.to_png().unwrap();
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (8, 6));
+        assert_eq!(pixels, thumbnail.pixels);
+    }
+
+    #[test]
+    fn test_dirty_raster_repaints_only_changes() {
+        // on a board that isn't the default size, so nothing falls back to it
+        let config = GameConfig::new(12, 10, CELL_SIZE).unwrap();
+        let mut game = GameState::new_with_config(config);
+        game.theme.day_night_period = 0.0;
+        let cell_size = 4;
+        let mut raster = DirtyRaster::new(&config, cell_size);
+        let (width, height) = raster.size();
+        assert_eq!((width, height), (48, 40));
+        let mut full = vec![0; width * height * 4];
+
+        // the first frame is painted in full
+        let dirty = raster.update(&game);
+        assert_eq!(dirty.len(), config.cell_count());
+        rasterize(&game, &mut full, cell_size);
+        assert!(raster.frame == full);
+        assert!(raster.update(&game).is_empty());
+
+        // a step only touches the snake's cells (its colors shift along) and the one it left
+        let tail = *game.snake.last().unwrap();
+        game.step();
+        let dirty = raster.update(&game);
+        assert!(dirty.contains(&game.snake[0]) && dirty.contains(&tail));
+        assert!(dirty.len() <= game.snake.len() + 1, "{:?}", dirty);
+        rasterize(&game, &mut full, cell_size);
+        assert!(raster.frame == full);
+
+        raster.invalidate();
+        assert_eq!(raster.update(&game).len(), config.cell_count());
+    }
+
+    #[test]
+    fn test_adalight_matrix_packet() {
+        let mut matrix = AdalightMatrix::new(Vec::new(), 3, 2);
+        matrix.serpentine = true;
+        matrix.brightness = 1.0;
+        assert_eq!(fit_cell_size(&GameConfig::default(), matrix.size()), 1);
+
+        // a 2x1 frame, red then green, sits in the top left of the 3x2 matrix
+        let frame = [255, 0, 0, 255, 0, 255, 0, 255];
+        matrix.show(&frame, 2, 1).unwrap();
+        let mut expected = vec![b'A', b'd', b'a', 0, 5, 5 ^ 0x55];
+        expected.extend([255, 0, 0, 0, 255, 0, 0, 0, 0]);
+        expected.extend([0; 9]);
+        assert_eq!(matrix.output, expected);
+
+        // the second row runs backwards on a serpentine strip
+        let mut matrix = AdalightMatrix::new(Vec::new(), 2, 2);
+        matrix.serpentine = true;
+        matrix.brightness = 1.0;
+        let frame = [1, 1, 1, 255, 2, 2, 2, 255, 3, 3, 3, 255, 4, 4, 4, 255];
+        matrix.show(&frame, 2, 2).unwrap();
+        assert_eq!(&matrix.output[6..], [1, 1, 1, 2, 2, 2, 4, 4, 4, 3, 3, 3]);
+    }
+
+    #[test]
+    fn test_parse_input_event() {
+        let event = |kind: u16, code: u16, value: i32| {
+            let mut bytes = [0; INPUT_EVENT_SIZE];
+            bytes[16..18].copy_from_slice(&kind.to_ne_bytes());
+            bytes[18..20].copy_from_slice(&code.to_ne_bytes());
+            bytes[20..24].copy_from_slice(&value.to_ne_bytes());
+            bytes
+        };
+        assert_eq!(
+            parse_input_event(&event(1, 103, 1)),
+            Some(PanelInput::Steer(Direction::Up))
+        );
+        assert_eq!(
+            parse_input_event(&event(1, 77, 1)),
+            Some(PanelInput::Steer(Direction::Right))
+        );
+        assert_eq!(
+            parse_input_event(&event(1, 19, 1)),
+            Some(PanelInput::Restart)
+        );
+        assert_eq!(parse_input_event(&event(1, 103, 0)), None); // release
+        assert_eq!(parse_input_event(&event(1, 103, 2)), None); // autorepeat
+        assert_eq!(parse_input_event(&event(0, 0, 0)), None); // sync
+    }
+
+    // Always rolls the same number, so food lands where the test expects
+    struct FixedRng(usize);
+
+    impl rules::RulesRng for FixedRng {
+        fn below(&mut self, bound: usize) -> usize {
+            self.0.min(bound - 1)
+        }
+    }
+
+    #[test]
+    fn test_no_std_rules_move_eat_and_crash() {
+        use crate::rules::{move_snake, Move};
+
+        let mut config = GameConfig::new(8, 6, CELL_SIZE).unwrap();
+        let mut snake = vec![
+            Position::new(3, 2),
+            Position::new(2, 2),
+            Position::new(1, 2),
+        ];
+        let food = Position::new(4, 2);
+        assert_eq!(
+            move_snake(&mut snake, Direction::Right, &config, &[], food),
+            Move::Ate
+        );
+        assert_eq!(snake.len(), 4);
+        assert_eq!(
+            move_snake(&mut snake, Direction::Right, &config, &[], food),
+            Move::Moved
+        );
+        assert_eq!(snake[0], Position::new(5, 2));
+        assert_eq!(snake.len(), 4);
+
+        // a blocked cell or the edge is a crash, and the snake stays put
+        let wall = [Position::new(6, 2)];
+        let before = snake.clone();
+        assert_eq!(
+            move_snake(&mut snake, Direction::Right, &config, &wall, food),
+            Move::Crashed
+        );
+        assert_eq!(snake, before);
+        snake[0] = Position::new(7, 2);
+        assert_eq!(
+            move_snake(&mut snake, Direction::Right, &config, &[], food),
+            Move::Crashed
+        );
+
+        // unless the walls wrap
+        config.walls = WallMode::Wrap;
+        assert_eq!(
+            move_snake(&mut snake, Direction::Right, &config, &[], food),
+            Move::Moved
+        );
+        assert_eq!(snake[0], Position::new(0, 2));
+    }
+
+    #[test]
+    fn test_no_std_rules_place_food() {
+        use crate::rules::{free_cell, RulesRng, XorShift32};
+
+        let config = GameConfig::new(8, 6, CELL_SIZE).unwrap();
+        let snake = [Position::new(0, 0), Position::new(1, 0)];
+        assert_eq!(
+            free_cell(&snake, &config, &mut FixedRng(0)),
+            Some(Position::new(2, 0))
+        );
+        assert_eq!(
+            free_cell(&snake, &config, &mut FixedRng(usize::MAX)),
+            Some(Position::new(7, 5))
+        );
+        let full: Vec<Position> = config.cells().collect();
+        assert_eq!(free_cell(&full, &config, &mut FixedRng(0)), None);
+
+        // the policies the game picks from place food by the same rules
+        let mut far = FoodPolicy::FarFromHead;
+        let food = far.spawn(&snake, &config, &mut FixedRng(usize::MAX));
+        assert_eq!(food, Some(Position::new(7, 5)));
+
+        let mut rng = XorShift32(0x2545_f491);
+        assert!((0..100).all(|_| rng.below(3) < 3));
+    }
+
+    #[test]
+    fn test_embedded_snake_lifecycle() {
+        let mut snake = EmbeddedSnake::default();
+        snake.game.change_screen(Screen::Splash);
+        snake.start();
+        assert_eq!(snake.game.screen, Screen::Playing);
+
+        snake.pause();
+        assert!(snake.is_paused());
+        snake.resume();
+        assert!(!snake.is_paused());
+
+        snake.game.score = 30;
+        snake.game.game_over = true;
+        snake.reset();
+        assert_eq!(snake.game.score, 0);
+        assert!(!snake.game.game_over);
+        assert!(snake.game.transition.is_none());
+    }
+
+    #[test]
+    fn test_mixer() {
+        let mut mixer = Mixer::parse("music = 0.5 # quieter\nsfx = 1\n").unwrap();
+        assert_eq!((mixer.music, mixer.sfx, mixer.muted), (0.5, 1.0, false));
+        assert_eq!(Mixer::parse(&mixer.to_text()), Ok(mixer.clone()));
+        assert!(Mixer::parse("music = 2").is_err());
+        assert!(Mixer::parse("volume = 1").is_err());
+
+        // the music dips under the death sound, then comes back, and sound effects don't
+        mixer.duck();
+        assert_eq!(mixer.gain(Channel::Music), 0.5 * DUCK_LEVEL);
+        assert_eq!(mixer.gain(Channel::Sfx), 1.0);
+        mixer.advance(DUCK_SECONDS * 0.75);
+        let recovering = mixer.gain(Channel::Music);
+        assert!(recovering > 0.5 * DUCK_LEVEL && recovering < 0.5);
+        mixer.advance(DUCK_SECONDS);
+        assert_eq!(mixer.gain(Channel::Music), 0.5);
+
+        mixer.muted = true;
+        assert_eq!(mixer.gain(Channel::Music), 0.0);
+        assert_eq!(mixer.gain(Channel::Sfx), 0.0);
+
+        // through the settings panel: turn the music down a step and unmute
+        let mut panel = mixer.panel();
+        panel.input(UiInput::Left);
+        panel.input(UiInput::Down);
+        panel.input(UiInput::Down);
+        panel.input(UiInput::Accept);
+        mixer.apply(&panel);
+        assert!((mixer.music - 0.4).abs() < 1e-6);
+        assert_eq!(mixer.volume(Channel::Sfx), 1.0);
+        assert!(!mixer.muted);
+
+        // the game keeps its mix across restarts and saves the mute switch
+        let storage = Arc::new(InMemoryStorage::default());
+        let mut game = GameState::with_storage(storage.clone());
+        game.mixer.music = 0.2;
+        game.toggle_mute();
+        game.restart();
+        assert!(game.mixer.muted && game.mixer.music == 0.2);
+        assert!(GameState::with_storage(storage).mixer.muted);
+        game.mixer.muted = false;
+        game.end_run();
+        assert!(game.mixer.gain(Channel::Music) < 0.2);
+    }
+
+    #[test]
+    fn test_music_intensity_follows_speed() {
+        assert_eq!(music_intensity(Speed::START, false), 0.0);
+        assert_eq!(music_intensity(Speed::MAX, false), 1.0);
+        assert_eq!(music_intensity(Speed::MAX, true), 0.0);
+
+        let mut mixer = Mixer {
+            music: 1.0,
+            ..Mixer::default()
+        };
+        assert_eq!(mixer.layer_gain(0), 1.0);
+        assert_eq!(mixer.layer_gain(1), 0.0);
+        // eases in rather than jumping
+        mixer.set_intensity(1.0);
+        mixer.advance(1.0);
+        assert_eq!(mixer.intensity, MUSIC_INTENSITY_RATE);
+        assert_eq!(mixer.layer_gain(1), 1.0);
+        assert_eq!(mixer.layer_gain(2), 0.0);
+        mixer.advance(10.0);
+        assert_eq!(mixer.layer_gain(2), 1.0);
+        assert_eq!(mixer.layer_gain(MUSIC_LAYERS), 0.0);
+        assert_eq!(mixer.playback_rate(), 1.0 + MUSIC_TEMPO_LIFT);
+
+        // the game drives it: faster snake, more music, and it falls away on game over
+        let mut game = GameState::new();
+        game.speed = Speed::MAX;
+        game.advance(std::time::Duration::from_secs(5));
+        assert_eq!(game.mixer.intensity, 1.0);
+        game.end_run();
+        game.advance(std::time::Duration::from_secs(1));
+        assert!(game.mixer.intensity < 1.0);
+        game.advance(std::time::Duration::from_secs(5));
+        assert_eq!(game.mixer.intensity, 0.0);
+    }
+
+    #[test]
+    fn test_synthesized_sounds() {
+        let beeps = [synth::Beep::square(441.0, 441.0, 0.1)];
+        let samples = synth::render(&beeps, 44_100);
+        assert_eq!(samples.len(), 4410);
+        // fades in from silence and back out, and a square wave swings both ways
+        assert_eq!(samples[0], 0);
+        assert!(samples[samples.len() - 1].abs() < 100);
+        assert!(samples.iter().any(|sample| *sample > 5000));
+        assert!(samples.iter().any(|sample| *sample < -5000));
+        let noise = synth::render(&[synth::Beep::noise(2000.0, 0.05)], 44_100);
+        assert!(noise.windows(2).filter(|pair| pair[0] != pair[1]).count() > 50);
+
+        let wav = synth::wav(&samples, 44_100);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(wav.len(), 44 + samples.len() * 2);
+        assert!(ggez::audio::SoundData::from_bytes(&wav).can_play());
+
+        // a file in the sounds folder wins if it plays, otherwise it's beeps
+        let dir = std::env::temp_dir().join(format!("snake_sounds_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("eat.wav"), &wav).unwrap();
+        std::fs::write(dir.join("die.ogg"), b"not really audio").unwrap();
+        let bank = SoundBank::load(dir.to_str().unwrap());
+        assert!(!bank.is_synthesized(SoundEvent::Ate));
+        assert!(bank.is_synthesized(SoundEvent::Died));
+        assert!(bank.is_synthesized(SoundEvent::Turned));
+        std::fs::remove_dir_all(&dir).unwrap();
+        for event in SoundEvent::ALL {
+            let samples = synth::render(&event.beeps(), synth::SAMPLE_RATE);
+            assert!(!samples.is_empty(), "{:?}", event);
+        }
+
+        // the recorder queues a sound for each tick that does something
+        let mut recorder = ReplayRecorder::new(GameState::new());
+        let before = TickSnapshot::of(&recorder.game);
+        recorder.game.handle_input(Direction::Up);
+        recorder.game.step();
+        recorder.react(before);
+        let before = TickSnapshot::of(&recorder.game);
+        recorder.game.end_run();
+        recorder.react(before);
+        assert_eq!(recorder.heard, vec![SoundEvent::Turned, SoundEvent::Died]);
+    }
+
+    #[test]
+    fn test_voice_packs() {
+        let dir = std::env::temp_dir().join(format!("snake_voices_{}", std::process::id()));
+        let clip = synth::wav(&synth::render(&SoundEvent::Ate.beeps(), 8000), 8000);
+        let alice = dir.join("voices").join("alice");
+        std::fs::create_dir_all(&alice).unwrap();
+        std::fs::write(alice.join("new_high_score.wav"), &clip).unwrap();
+        std::fs::write(alice.join("3.wav"), &clip).unwrap();
+        std::fs::write(alice.join("notes.txt"), "not a clip").unwrap();
+        // a content pack can bring a voice too
+        let pack_dir = dir.join("mods").join("robot");
+        std::fs::create_dir_all(pack_dir.join(VOICE_DIR)).unwrap();
+        std::fs::write(pack_dir.join(VOICE_DIR).join("level_up.wav"), &clip).unwrap();
+        let pack = Pack {
+            dir: pack_dir,
+            manifest: PackManifest::parse("name = \"robot\"\nversion = \"1\"").unwrap(),
+            theme: None,
+            start: None,
+            feedback: None,
+        };
+
+        let voices = discover_voices(dir.join("voices").to_str().unwrap(), &[pack]);
+        let names: Vec<&str> = voices.iter().map(|voice| voice.name.as_str()).collect();
+        assert_eq!(names, ["alice", "robot"]);
+        assert!(voices[0].clip(VoiceLine::Countdown(3)).is_some());
+        assert!(voices[0].clip(VoiceLine::LevelUp).is_none());
+        assert!(voices[1].clip(VoiceLine::LevelUp).is_some());
+
+        // picked on the settings panel and saved
+        let mut panel = voice_panel(&voices, None);
+        panel.input(UiInput::Down);
+        assert!(matches!(
+            panel.input(UiInput::Accept),
+            Some(UiEvent::Chose(_, 1))
+        ));
+        let storage = InMemoryStorage::default();
+        save_voice_choice(&storage, Some("alice"));
+        assert_eq!(
+            chosen_voice(&storage, voices).map(|voice| voice.name),
+            Some("alice".to_string())
+        );
+        save_voice_choice(&storage, None);
+        assert!(chosen_voice(&storage, discover_voices("no_such_dir", &[])).is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // what the announcer says about a run
+        let mut recorder = ReplayRecorder::new(GameState::new());
+        recorder.game.grace_left = GracePeriod::Ticks(4);
+        for _ in 0..4 {
+            let before = TickSnapshot::of(&recorder.game);
+            recorder.game.step();
+            recorder.react(before);
+        }
+        assert_eq!(
+            recorder.announced,
+            [3, 2, 1].map(VoiceLine::Countdown).to_vec()
+        );
+        recorder.announced.clear();
+        recorder.game.high_score = 50;
+        recorder.game.score = 50;
+        let before = TickSnapshot::of(&recorder.game);
+        recorder.game.score = 60;
+        recorder.game.speed = Speed::cells_per_second(6);
+        recorder.react(before);
+        assert_eq!(
+            recorder.announced,
+            [VoiceLine::LevelUp, VoiceLine::NewHighScore]
+        );
+        recorder.announced.clear();
+        let before = TickSnapshot::of(&recorder.game);
+        recorder.game.score = 70;
+        recorder.react(before); // only the once
+        assert!(recorder.announced.is_empty());
+    }
+
+    #[test]
+    fn test_haptic_pulse_respects_settings() {
+        let mut game = GameState::new();
+        let (duration, strength) = game.haptic_pulse(HapticEvent::Died).unwrap();
+        assert_eq!(duration, 350);
+        assert_eq!(strength, DEFAULT_HAPTIC_INTENSITY);
+
+        game.haptic_intensity = 0.5;
+        assert_eq!(game.haptic_pulse(HapticEvent::Ate), Some((60, 0.2)));
+        game.haptic_intensity = 0.0;
+        assert_eq!(game.haptic_pulse(HapticEvent::Ate), None);
+
+        game.haptic_intensity = 1.0;
+        game.toggle_haptics();
+        assert_eq!(game.haptic_pulse(HapticEvent::Died), None);
+        game.restart();
+        assert!(!game.haptics);
+        assert_eq!(game.haptic_intensity, 1.0);
+    }
+
+    #[test]
+    fn test_recorder_buzzes_on_eating_and_dying() {
+        struct Buzzes(std::rc::Rc<std::cell::RefCell<Vec<u32>>>);
+        impl Haptics for Buzzes {
+            fn pulse(&mut self, duration_ms: u32, _strength: f32) {
+                self.0.borrow_mut().push(duration_ms);
+            }
+        }
+
+        let buzzes = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut recorder = ReplayRecorder::new(GameState::new());
+        recorder.haptics = Some(Box::new(Buzzes(buzzes.clone())));
+
+        recorder.react(TickSnapshot::of(&recorder.game));
+        assert!(buzzes.borrow().is_empty());
+        let before = TickSnapshot::of(&recorder.game);
+        recorder.game.score += FOOD_POINTS;
+        recorder.react(before);
+        let before = TickSnapshot::of(&recorder.game);
+        recorder.game.game_over = true;
+        recorder.react(before);
+        assert_eq!(*buzzes.borrow(), vec![60, 350]);
+    }
+
+    #[test]
+    fn test_feedback_router() {
+        let router = FeedbackRouter::default();
+        assert_eq!(
+            router.outputs(FeedbackEvent::Died),
+            [
+                FeedbackOutput::Sound(SoundEvent::Died),
+                FeedbackOutput::Rumble {
+                    duration_ms: 350,
+                    strength: 1.0
+                }
+            ]
+        );
+        assert_eq!(
+            router.outputs(FeedbackEvent::Countdown(2)),
+            [FeedbackOutput::Voice]
+        );
+
+        for (text, error) in [
+            ("ate sound eat", "line 1: expected `event = output`"),
+            ("\nwon = toast Yay", "line 2: unknown event `won`"),
+            ("ate = sound burp", "line 1: unknown sound `burp`"),
+            ("ate = voice", "line 1: the announcer has nothing for `ate`"),
+            (
+                "died = rumble 100 2.0",
+                "line 1: expected `rumble <milliseconds>",
+            ),
+            ("died = toast", "line 1: unknown output `toast`"),
+        ] {
+            let e = FeedbackRouter::parse(text).unwrap_err();
+            assert!(e.starts_with(error), "{}: {}", text, e);
+        }
+
+        // a pack replaces the events it mentions, and can silence one
+        let pack = FeedbackRouter::parse(
+            "# louder eating\nate = sound eat\nate = rumble 120 0.8 # longer\n\
+             new_high_score = toast Best, ever!\nturned = nothing\n",
+        )
+        .unwrap();
+        let mut router = FeedbackRouter::default();
+        router.override_with(&pack);
+        assert_eq!(
+            router.outputs(FeedbackEvent::Ate)[1],
+            FeedbackOutput::Rumble {
+                duration_ms: 120,
+                strength: 0.8
+            }
+        );
+        assert!(router.outputs(FeedbackEvent::Turned).is_empty());
+        assert_eq!(
+            router.outputs(FeedbackEvent::Died),
+            FeedbackRouter::default().outputs(FeedbackEvent::Died)
+        );
+
+        let mut recorder = ReplayRecorder::new(GameState::new());
+        recorder.feedback = router;
+        recorder.game.high_score = 10;
+        let before = TickSnapshot::of(&recorder.game);
+        recorder.game.handle_input(Direction::Up);
+        recorder.game.step();
+        recorder.game.score = 20;
+        recorder.react(before);
+        assert_eq!(recorder.heard, vec![SoundEvent::Ate]); // eating drowns out the turn
+        assert!(recorder.announced.is_empty());
+        assert_eq!(
+            recorder
+                .game
+                .toast
+                .as_ref()
+                .map(|toast| toast.message.as_str()),
+            Some("Best, ever!")
+        );
+    }
+
+    #[test]
+    fn test_replays_are_verified_before_scores_count() {
+        let mut game = GameState::new();
+        game.high_score = u32::MAX;
+        let mut replay = Replay::start(&game);
+        play_headless(&mut game, |_, game| replay.record_tick(game));
+        assert!(game.score > 0);
+        assert_eq!(replay.seed, game.seed);
+        assert_eq!(replay.verify(), Ok(game.score));
+
+        let mut padded = replay.clone();
+        padded.score += FOOD_POINTS;
+        assert_eq!(
+            padded.verify().unwrap_err(),
+            format!(
+                "the run plays back to {} points, not {}",
+                game.score, padded.score
+            )
+        );
+        let mut planted = replay.clone();
+        planted.food[1] = planted.food[0]; // right under the head that just ate
+        assert!(planted
+            .verify()
+            .unwrap_err()
+            .starts_with("food 2 isn't on a free cell"));
+        let mut undead = replay.clone();
+        undead.directions.push(Direction::Up);
+        assert!(undead
+            .verify()
+            .unwrap_err()
+            .starts_with("the run carries on"));
+
+        // online, the name, seed and replay all travel together and are checked again
+        let filter = NameFilter::default();
+        let submission = ScoreSubmission::new(" Ann ", replay.clone(), &filter).unwrap();
+        assert_eq!(submission.name, "Ann");
+        let received = ScoreSubmission::from_bytes(&submission.to_bytes()).unwrap();
+        assert_eq!(received, submission);
+        assert_eq!(received.verify(&filter), Ok(()));
+        let mut forged = received.clone();
+        forged.score += 1;
+        assert!(forged.verify(&filter).is_err());
+        forged = received;
+        forged.seed ^= 1;
+        assert_eq!(
+            forged.verify(&filter).unwrap_err(),
+            "the seed isn't the replay's"
+        );
+        assert!(ScoreSubmission::new("Ann", padded, &filter).is_err());
+
+        // locally, a score whose replay doesn't add up doesn't make the board
+        let mut recorder = ReplayRecorder::new(GameState::new());
+        recorder.game.food = Food::normal(Position::new(0, 0));
+        recorder.game.step();
+        recorder.track(recorder.game.snake[1], false);
+        recorder.game.score = 500; // not from eating anything
+        recorder.game.end_run();
+        assert_eq!(recorder.game.high_score, 500);
+        recorder.replay.score = 500;
+        recorder.vet_high_score(0);
+        assert_eq!(recorder.game.high_score, 0);
+        assert_eq!(
+            recorder.game.storage.read(HIGH_SCORE_FILE).as_deref(),
+            Some("0")
+        );
+        assert!(recorder.game.toast.is_some());
+        assert!(recorder.game.high_score_checked);
+
+        // one the replay can't play back is kept, but isn't taken as checked
+        let config = GameConfig::new(16, 12, CELL_SIZE).unwrap();
+        let mut recorder = ReplayRecorder::new(GameState::new_with_config(config));
+        recorder.game.score = 30;
+        recorder.game.end_run();
+        recorder.vet_high_score(0);
+        assert_eq!(recorder.game.high_score, 30);
+        assert!(!recorder.game.high_score_checked);
+        let toast = recorder.game.toast.as_ref().unwrap();
+        assert!(toast.message.contains("unchecked"), "{}", toast.message);
+        let storage = recorder.game.storage.clone();
+        let mut reloaded = GameState::with_storage_and_config(storage, config);
+        assert_eq!(reloaded.high_score, 30);
+        assert!(!reloaded.high_score_checked);
+
+        // and a checked one is remembered as checked
+        reloaded.score = 40;
+        reloaded.update_high_score();
+        reloaded.mark_high_score_checked();
+        reloaded.load_records();
+        assert!(reloaded.high_score_checked);
+    }
+
+    type Received = Arc<std::sync::Mutex<Vec<(String, Vec<u8>)>>>;
+
+    // Fails until it's been called `failures` times, keeping what got through
+    struct FlakyTransport {
+        failures: u32,
+        received: Received,
+    }
+
+    impl Transport for FlakyTransport {
+        fn send(&mut self, kind: &str, body: &[u8]) -> Result<(), String> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err("connection refused".to_string());
+            }
+            self.received
+                .lock()
+                .unwrap()
+                .push((kind.to_string(), body.to_vec()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_outbox_queues_offline_and_retries() {
+        let storage: Arc<dyn StorageBackend> = Arc::new(InMemoryStorage::default());
+        let mut outbox = Outbox::load(storage.clone());
+        outbox.push("score", vec![0, 1, 254]);
+        outbox.push("score", b"second".to_vec());
+        outbox.advance(10.0);
+        assert_eq!(outbox.pending.len(), 2);
+        assert!(outbox.status_line().contains("offline"));
+
+        // the queue survives a restart
+        let mut outbox = Outbox::load(storage.clone());
+        assert_eq!(outbox.pending[0].body, [0, 1, 254]);
+        assert_eq!(outbox.pending[1].kind, "score");
+
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        outbox.connect(Box::new(FlakyTransport {
+            failures: 2,
+            received: received.clone(),
+        }));
+        // advance never blocks, so keep checking in until the sends are done
+        let mut waits = Vec::new();
+        let give_up = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !outbox.pending.is_empty() && std::time::Instant::now() < give_up {
+            let before = outbox.wait;
+            outbox.advance(0.5);
+            if outbox.wait > before {
+                waits.push(outbox.wait);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert!(outbox.pending.is_empty());
+        assert_eq!(outbox.sent, 2);
+        // two failures back off, then the sends are rate limited
+        assert_eq!(
+            waits,
+            [2.0, 4.0, OUTBOX_SEND_INTERVAL, OUTBOX_SEND_INTERVAL]
+        );
+        assert_eq!(
+            *received.lock().unwrap(),
+            [
+                ("score".to_string(), vec![0, 1, 254]),
+                ("score".to_string(), b"second".to_vec())
+            ]
+        );
+        assert!(Outbox::load(storage).pending.is_empty());
+
+        assert_eq!(retry_delay(1), OUTBOX_RETRY_DELAY);
+        assert_eq!(retry_delay(3), OUTBOX_RETRY_DELAY * 4.0);
+        assert_eq!(retry_delay(40), OUTBOX_RETRY_CAP);
+
+        // a long time offline only keeps the newest items
+        let mut outbox = Outbox::load(Arc::new(InMemoryStorage::default()));
+        for i in 0..OUTBOX_LIMIT + 5 {
+            outbox.push("score", vec![i as u8]);
+        }
+        assert_eq!(outbox.pending.len(), OUTBOX_LIMIT);
+        assert_eq!(outbox.pending[0].body, [5]);
+    }
+
+    #[test]
+    fn test_time_travel_branches_the_run() {
+        let mut recorder = ReplayRecorder::new(GameState::new());
+        recorder.game.food = Food::normal(Position::new(0, 0)); // out of the way
+        for _ in 0..6 {
+            let (head_before, was_over) = (recorder.game.snake[0], recorder.game.game_over);
+            recorder.game.step();
+            recorder.track(head_before, was_over);
+        }
+        let live_head = recorder.game.snake[0];
+
+        recorder.toggle_time_travel();
+        assert_eq!(recorder.time_travel.as_ref().unwrap().tick, 6);
+        recorder.travel_to(2);
+        let past = recorder.replay.seek(2).game;
+        assert_eq!(
+            recorder.time_travel.as_ref().unwrap().game.snake,
+            past.snake
+        );
+        assert_eq!(recorder.travel_progress(), 2.0 / 6.0);
+
+        // closing without branching leaves the live run alone
+        recorder.toggle_time_travel();
+        assert_eq!(recorder.game.snake[0], live_head);
+        assert_eq!(recorder.replay.tick_count(), 6);
+
+        recorder.toggle_time_travel();
+        recorder.travel_to(2);
+        recorder.branch();
+        assert!(recorder.time_travel.is_none());
+        assert_eq!(recorder.game.snake, past.snake);
+        assert_eq!(recorder.replay.tick_count(), 2);
+
+        // the new timeline records on from the branch
+        recorder.game.handle_input(Direction::Down);
+        let head_before = recorder.game.snake[0];
+        recorder.game.step();
+        recorder.track(head_before, false);
+        assert_eq!(recorder.replay.tick_count(), 3);
+        assert_eq!(recorder.replay.seek(3).game.snake, recorder.game.snake);
+    }
+
+    #[test]
+    fn test_personal_best_summary() {
+        let mut game = GameState::new();
+        game.score = 120;
+        game.elapsed = 75.0;
+        assert_eq!(
+            game.personal_best_summary(),
+            "New best: 120 points, 3 long after 1:15"
+        );
+        game.coop = true;
+        assert!(game
+            .personal_best_summary()
+            .starts_with("New best in co-op:"));
+
+        // the toggle only sticks when there's a way to send notifications
+        game.toggle_best_notifications();
+        assert_eq!(game.notify_best, cfg!(feature = "notifications"));
+        game.restart();
+        assert_eq!(game.notify_best, cfg!(feature = "notifications"));
+    }
+
+    #[test]
+    fn test_bot_heads_for_food_and_dodges_walls() {
+        let mut game = create_custom_game_state(
+            vec![
+                Position::new(5, 5),
+                Position::new(4, 5),
+                Position::new(3, 5),
+            ],
+            Direction::Right,
+        );
+        game.food = Food::normal(Position::new(5, 9));
+        assert_eq!(bot_direction(&game), Direction::Down);
+
+        // against the right wall with the food behind it, it has to turn off the wall
+        let mut game = create_custom_game_state(
+            vec![
+                Position::new(GRID_WIDTH - 1, 0),
+                Position::new(GRID_WIDTH - 2, 0),
+                Position::new(GRID_WIDTH - 3, 0),
+            ],
+            Direction::Right,
+        );
+        game.food = Food::normal(Position::new(0, 0));
+        assert_eq!(bot_direction(&game), Direction::Down);
+        // unless the walls wrap, when the food is one step across the edge
+        game.config.walls = WallMode::Wrap;
+        assert_eq!(bot_direction(&game), Direction::Right);
+
+        // left to itself it eats
+        let mut game = GameState::new();
+        for _ in 0..300 {
+            let turn = bot_direction(&game);
+            game.handle_input(turn);
+            game.step();
+        }
+        assert!(game.score > 0);
+    }
+
+    #[test]
+    fn test_screensaver_hides_hud_and_keeps_records() {
+        let screensaver = Screensaver::default();
+        assert!(!screensaver.snake.game.show_hud);
+        let mut game = screensaver.snake.game;
+        game.score = 500;
+        game.update_high_score();
+        assert_eq!(game.high_score, u32::MAX);
+    }
+
+    #[test]
+    fn test_kiosk_config_parsing() {
+        let config = KioskConfig::parse(
+            "# cabinet wiring\ncoin = C\nstart = enter # the big button\ncoins_per_credit = 2\nfree_play = false\n",
+        )
+        .unwrap();
+        assert_eq!(config.coin_key, KeyCode::C);
+        assert_eq!(config.start_key, KeyCode::Return);
+        assert_eq!(config.operator_key, KeyCode::F2);
+        assert_eq!(config.coins_per_credit, 2);
+        assert_eq!(KioskConfig::parse("").unwrap(), KioskConfig::default());
+
+        assert_eq!(parse_key_name("f12"), Some(KeyCode::F12));
+        assert_eq!(parse_key_name("7"), Some(KeyCode::Key7));
+        assert_eq!(parse_key_name("F13"), None);
+        assert_eq!(parse_key_name("F0"), None);
+
+        assert!(KioskConfig::parse("coin = Nope")
+            .unwrap_err()
+            .starts_with("line 1:"));
+        assert!(KioskConfig::parse("coins_per_credit = 0").is_err());
+        assert!(KioskConfig::parse("free_play = yes").is_err());
+        assert!(KioskConfig::parse("start = 5").is_err()); // same as the coin key
+    }
+
+    #[test]
+    fn test_kiosk_credits() {
+        let mut kiosk = Kiosk::new(KioskConfig {
+            coins_per_credit: 2,
+            ..KioskConfig::default()
+        });
+        assert!(!kiosk.press_start());
+
+        kiosk.insert_coin();
+        assert_eq!(kiosk.credits, 0);
+        kiosk.insert_coin();
+        kiosk.insert_coin();
+        kiosk.insert_coin();
+        assert_eq!(kiosk.credits, 2);
+
+        assert!(kiosk.press_start());
+        assert_eq!(kiosk.credits, 1);
+        assert!(!kiosk.press_start()); // one run at a time
+        kiosk.player = None;
+        assert!(kiosk.press_start());
+        assert_eq!(kiosk.credits, 0);
+        assert_eq!(kiosk.counters, KioskCounters { coins: 4, plays: 2 });
+        assert_eq!(
+            KioskCounters::parse(&kiosk.counters.to_text()),
+            kiosk.counters
+        );
+
+        kiosk.player = None;
+        kiosk.operator(OperatorAction::ToggleFreePlay);
+        assert!(kiosk.press_start());
+        assert_eq!(kiosk.credits, 0);
+    }
+
+    #[test]
+    fn test_panel_focus_and_widgets() {
+        let mut panel = Panel::default();
+        panel.push(Widget::title("Settings"));
+        let list = panel.push(Widget::list(vec!["a".into(), "b".into()], 0));
+        let toggle = panel.push(Widget::toggle("Sound", true));
+        panel.push(Widget::hint("Esc to go back"));
+        let slider = panel.push(Widget::slider("Volume", 0.5, 0.0, 1.0, 0.25));
+        let button = panel.push(Widget::button("Done"));
+
+        // labels are skipped, and a list is walked through before focus leaves it
+        assert_eq!(panel.focus, Some(list));
+        assert_eq!(panel.input(UiInput::Down), Some(UiEvent::Changed(list)));
+        assert_eq!(panel.input(UiInput::Accept), Some(UiEvent::Chose(list, 1)));
+        assert_eq!(panel.input(UiInput::Down), None);
+        assert_eq!(panel.focus, Some(toggle));
+        assert_eq!(panel.input(UiInput::Accept), Some(UiEvent::Changed(toggle)));
+        assert_eq!(panel.widgets[toggle], Widget::toggle("Sound", false));
+
+        panel.input(UiInput::Down);
+        assert_eq!(panel.focus, Some(slider));
+        panel.input(UiInput::Right);
+        assert_eq!(panel.input(UiInput::Right), Some(UiEvent::Changed(slider)));
+        assert_eq!(panel.input(UiInput::Right), None, "already at the top");
+        assert_eq!(
+            panel.widgets[slider],
+            Widget::slider("Volume", 1.0, 0.0, 1.0, 0.25)
+        );
+
+        panel.input(UiInput::Down);
+        assert_eq!(panel.input(UiInput::Accept), Some(UiEvent::Pressed(button)));
+        // focus wraps around, entering a list at the end it comes from
+        assert_eq!(panel.input(UiInput::Down), Some(UiEvent::Changed(list)));
+        assert_eq!(
+            panel.focused(),
+            Some(&Widget::list(vec!["a".into(), "b".into()], 0))
+        );
+        assert_eq!(panel.input(UiInput::Up), None);
+        assert_eq!(panel.focus, Some(button));
+        assert_eq!(panel.input(UiInput::Cancel), Some(UiEvent::Cancelled));
+
+        // a lone list wraps from its last row to its first
+        let mut panel = Panel::default();
+        panel.push(Widget::list(vec!["a".into(), "b".into()], 1));
+        assert_eq!(panel.input(UiInput::Down), Some(UiEvent::Changed(0)));
+        assert_eq!(
+            panel.focused(),
+            Some(&Widget::list(vec!["a".into(), "b".into()], 0))
+        );
+    }
+
+    #[test]
+    fn test_confirm_dialog() {
+        let mut dialog = ConfirmDialog::yes_no("Delete it?", "Delete");
+        assert_eq!(dialog.focus, 1, "focus starts on the safe choice");
+        assert_eq!(dialog.input(UiInput::Accept), Some(false));
+        assert_eq!(dialog.input(UiInput::Up), None);
+        assert_eq!(dialog.input(UiInput::Accept), Some(true));
+        assert_eq!(dialog.input(UiInput::Cancel), Some(false));
+
+        // a restart mid-run waits for an answer, and holds the game meanwhile
+        let mut app = ReplayRecorder::new(GameState::with_storage(Arc::new(
+            InMemoryStorage::default(),
+        )));
+        app.game.screen = Screen::Playing;
+        app.game.skip_transition();
+        app.game.grace_left = GracePeriod::Off;
+        app.game.food = Food::normal(Position::new(0, 0));
+        drive(
+            &mut app,
+            &[SyntheticInput::Wait(std::time::Duration::from_millis(500))],
+        );
+        assert!(app.mid_run());
+        let head = app.game.snake[0];
+        drive(
+            &mut app,
+            &[
+                SyntheticInput::Press(KeyCode::R),
+                SyntheticInput::Wait(std::time::Duration::from_secs(1)),
+            ],
+        );
+        assert!(app.confirm.is_some());
+        assert_eq!(app.game.snake[0], head);
+        drive(&mut app, &[SyntheticInput::Press(KeyCode::Return)]);
+        assert!(
+            app.confirm.is_none() && app.game.elapsed > 0.0,
+            "kept playing"
+        );
+
+        drive(
+            &mut app,
+            &[
+                SyntheticInput::Pad(ggez::input::gamepad::gilrs::Button::Start),
+                SyntheticInput::Pad(ggez::input::gamepad::gilrs::Button::DPadUp),
+                SyntheticInput::Pad(ggez::input::gamepad::gilrs::Button::South),
+            ],
+        );
+        assert_eq!(app.game.elapsed, 0.0, "restarted");
+
+        // with no run to lose there's nothing to ask
+        drive(&mut app, &[SyntheticInput::Press(KeyCode::Escape)]);
+        assert!(app.confirm.is_none() && app.exiting);
+    }
+
+    #[test]
+    fn test_kiosk_operator_menu() {
+        let mut kiosk = Kiosk::new(KioskConfig::default());
+        kiosk.insert_coin();
+        kiosk.press_start();
+        kiosk.open_operator_menu();
+        assert!(kiosk.player.as_ref().unwrap().is_paused());
+
+        kiosk.operator(OperatorAction::AddCredit);
+        kiosk.operator(OperatorAction::ClearCounters);
+        assert_eq!(kiosk.credits, 1);
+        assert_eq!(kiosk.counters, KioskCounters::default());
+
+        // from the menu itself, clearing the counters asks first
+        kiosk.insert_coin();
+        let clear = OPERATOR_MENU
+            .iter()
+            .position(|action| *action == OperatorAction::ClearCounters)
+            .unwrap();
+        kiosk.operator_input(clear, UiInput::Accept);
+        assert!(kiosk.confirm.is_some());
+        kiosk.operator_input(clear, UiInput::Cancel);
+        assert!(kiosk.confirm.is_none());
+        assert_ne!(kiosk.counters, KioskCounters::default());
+        kiosk.operator_input(clear, UiInput::Accept);
+        kiosk.operator_input(clear, UiInput::Up);
+        kiosk.operator_input(clear, UiInput::Accept);
+        assert_eq!(kiosk.counters, KioskCounters::default());
+
+        kiosk.operator(OperatorAction::Close);
+        assert_eq!(kiosk.operator_menu, None);
+        assert!(!kiosk.player.as_ref().unwrap().is_paused());
+        assert!(!kiosk.exiting);
+        kiosk.operator(OperatorAction::Exit);
+        assert!(kiosk.exiting);
+    }
+
+    #[test]
+    fn test_launch_options_layering() {
+        let vars = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        let mut options = LaunchOptions::default();
+        options
+            .apply_env(vars(&[
+                ("SNAKE_SEED", "42"),
+                ("SNAKE_GRID", "20x15"),
+                ("SNAKE_HEADLESS", "true"),
+                ("SNAKE_DATA_DIR", "saves"),
+                ("PATH", "/usr/bin"),
+            ]))
+            .unwrap();
+        assert_eq!(options.seed, Some(42));
+        assert_eq!(options.grid, Some((GRID_WIDTH, GRID_HEIGHT)));
+        assert!(options.headless);
+
+        // flags win over the environment
+        let args: Vec<String> = ["snake", "--seed", "7", "--data-dir", "ci"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        options.apply_args(&args).unwrap();
+        assert_eq!(options.seed, Some(7));
+        assert_eq!(options.data_dir, Some("ci".into()));
+
+        // blank is unset, nonsense is an error that names the variable
+        let mut options = LaunchOptions::default();
+        options.apply_env(vars(&[("SNAKE_SEED", "")])).unwrap();
+        assert_eq!(options.seed, None);
+        let error = options
+            .apply_env(vars(&[("SNAKE_HEADLESS", "maybe")]))
+            .unwrap_err();
+        assert!(error.starts_with("SNAKE_HEADLESS:"));
+        assert!(options.apply_env(vars(&[("SNAKE_GRID", "4x3")])).is_err());
+        assert!(options.apply_args(&["--seed".to_string()]).is_err());
+
+        // other boards and cell sizes make it to the game's config
+        let args: Vec<String> = ["snake", "--grid", "40x30", "--cell-size", "16"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        options.apply_args(&args).unwrap();
+        assert_eq!(
+            options.game_config(),
+            GameConfig::new(40, 30, 16.0).unwrap()
+        );
+        assert_eq!(
+            LaunchOptions::default().game_config(),
+            GameConfig::default()
+        );
+        assert!(options
+            .apply_env(vars(&[("SNAKE_CELL_SIZE", "1")]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_game_config_board_size() {
+        let config = GameConfig::new(30, 10, 20.0).unwrap();
+        assert!(GameConfig::new(300, 10, 20.0).is_err());
+        assert!(GameConfig::new(30, 10, 0.5).is_err());
+        assert_eq!(config.screen_width(), 600.0);
+        assert_eq!(config.quadrant(Position::new(29, 0)), 1);
+
+        let mut game =
+            GameState::with_storage_and_config(Arc::new(InMemoryStorage::default()), config);
+        assert_eq!(game.snake[0], Position::new(15, 5));
+        assert!(game.food.position.is_within(30, 10));
+        // past the edge of the default board, but not this one
+        assert!(!game.would_collide(Position::new(25, 5)));
+        assert!(game.would_collide(Position::new(5, 10)));
+        assert_eq!(game.coverage(), 3.0 / 300.0);
+        assert_eq!(game.high_score_file(), "high_score_30x10.txt");
+        let right_edge = game
+            .board_cells()
+            .iter()
+            .map(|(rect, _)| rect.right())
+            .fold(0.0, f32::max);
+        assert!(right_edge <= config.screen_width());
+
+        game.score = 40;
+        game.end_run();
+        game.restart();
+        assert_eq!(game.config, config);
+        assert_eq!(game.high_score, 40);
+        // the default board keeps its own record
+        assert_eq!(GameState::with_storage(game.storage.clone()).high_score, 0);
+
+        // the bot finds its way round the bigger board too
+        let mut game = GameState::new_with_config(GameConfig::new(24, 12, 10.0).unwrap());
+        game.high_score = u32::MAX;
+        game.best_time = f64::MAX;
+        play_headless(&mut game, |_, game| {
+            assert!(game.snake.iter().all(|cell| game.config.contains(*cell)));
+        });
+        assert!(game.score > 0);
+        assert_eq!(ascii_board(&game).lines().next().unwrap().len(), 26);
+    }
+
+    #[test]
+    fn test_play_headless() {
+        let mut game = GameState::new();
+        let ticks = play_headless(&mut game, |_, _| {});
+        assert!(ticks > 0);
+        assert!(game.game_over || ticks == HEADLESS_TICK_LIMIT);
+        assert!(game.score > 0); // the bot always finds the first food
+    }
+
+    #[test]
+    fn test_headless_output() {
+        let mut game = GameState::new();
+        let mut lines = Vec::new();
+        let ticks = play_headless(&mut game, |tick, game| {
+            lines.push(OutputFormat::Json.tick_line(tick, game))
+        });
+        assert_eq!(lines.len() as u64, ticks);
+        assert!(lines[0].starts_with("{\"type\":\"tick\",\"tick\":1,"));
+
+        let summary = OutputFormat::Json.summary_line(ticks, &game);
+        assert!(summary.starts_with("{\"type\":\"game\",\"schema\":1,"));
+        assert!(summary.ends_with("\"outcome\":\"crashed\"}"));
+
+        // every CSV row has as many columns as the header
+        let columns = OutputFormat::Csv.header().unwrap().split(',').count();
+        assert_eq!(
+            OutputFormat::Csv.tick_line(1, &game).split(',').count(),
+            columns
+        );
+        let row = OutputFormat::Csv.summary_line(ticks, &game);
+        assert_eq!(row.split(',').count(), columns);
+        assert!(row.starts_with(&format!("game,{},{},", ticks, game.score)));
+        assert_eq!(OutputFormat::Text.header(), None);
+        assert!(OutputFormat::parse("xml").is_err());
+    }
+
+    #[test]
+    fn test_touch_controls_swipe_and_buttons() {
+        let mut touch = TouchControls::default();
+        let board = GameConfig::default();
+        assert!(!touch.visible);
+
+        // a drag along the board turns along its longer axis
+        assert_eq!(touch.press(100.0, 100.0, &board), None);
+        assert!(touch.visible);
+        assert_eq!(touch.release(160.0, 120.0), Some(Direction::Right));
+        touch.press(100.0, 100.0, &board);
+        assert_eq!(touch.release(90.0, 40.0), Some(Direction::Up));
+
+        // too short to be a swipe, and a release with no press is ignored
+        touch.press(100.0, 100.0, &board);
+        assert_eq!(touch.release(110.0, 105.0), None);
+        assert_eq!(touch.release(300.0, 100.0), None);
+
+        // each arrow button turns its own way as soon as it's pressed
+        for (direction, rect) in TouchControls::buttons(&board) {
+            let (x, y) = rect.center();
+            assert_eq!(touch.press(x, y, &board), Some(direction));
+            assert_eq!(touch.release(0.0, 0.0), None);
+        }
+    }
+
+    #[test]
+    fn test_suspended_run_round_trip() {
+        let mut game = create_custom_game_state(
+            vec![
+                Position::new(4, 2),
+                Position::new(4, 3),
+                Position::new(4, 4),
+            ],
+            Direction::Up,
+        );
+        game.score = 40;
+        game.speed = Speed::from_seconds_per_cell(0.15);
+        game.elapsed = 12.5;
+
+        let mut resumed = GameState::new();
+        assert!(resumed.resume_text(&game.suspend_text()));
+        assert_eq!(resumed.snake, game.snake);
+        assert_eq!(resumed.direction, Direction::Up);
+        assert_eq!(resumed.food, game.food);
+        assert_eq!(resumed.score, 40);
+        assert_eq!(resumed.speed, game.speed);
+        assert_eq!(resumed.elapsed, 12.5);
+
+        // saves from before speeds were in cells per second still load
+        let keyframe = game.suspend_text().lines().next().unwrap().to_string();
+        assert!(resumed.resume_text(&format!("{}\n0.25\n3\n", keyframe)));
+        assert_eq!(resumed.speed, Speed::cells_per_second(4));
+
+        let before = resumed.snake.clone();
+        // a bonus food comes back as the same kind, just as close to rotting
+        game.food = Food::new(Position::new(9, 9), FoodKind::Golden);
+        game.food.ticks_left = Some(7);
+        assert!(resumed.resume_text(&game.suspend_text()));
+        assert_eq!(resumed.food, game.food);
+        assert_eq!(resumed.suspend_text(), game.suspend_text());
+
+        assert!(!resumed.resume_text("not a run"));
+        assert!(!resumed.resume_text("K 0 R R 0 0 1,1 99,99\n0.2\n0\n"));
+        assert_eq!(resumed.snake, before);
+    }
+
+    #[test]
+    fn test_suspend_file_follows_mode() {
+        let mut game = GameState::new();
+        assert_eq!(game.suspend_file(), SUSPEND_FILE);
+        game.coop = true;
+        assert_eq!(game.suspend_file(), "suspended_coop.txt");
+    }
+
+    #[test]
+    fn test_coop_has_its_own_high_score_file() {
+        let mut game = GameState::new();
+        assert_eq!(game.high_score_file(), HIGH_SCORE_FILE);
+        game.coop = true;
+        assert_eq!(game.high_score_file(), COOP_HIGH_SCORE_FILE);
+    }
+
+    #[test]
+    fn test_best_time_per_mode() {
+        let storage = Arc::new(InMemoryStorage::default());
+        let mut game = GameState::with_storage(storage.clone());
+        game.elapsed = 30.0;
+        game.update_best_time();
+        assert_eq!(storage.read(BEST_TIME_FILE).as_deref(), Some("30"));
+
+        // a longer co-op run doesn't touch the solo record, and each mode reads its own
+        game.set_coop(true);
+        assert_eq!(game.best_time, 0.0);
+        game.elapsed = 90.0;
+        game.update_best_time();
+        assert_eq!(storage.read(COOP_BEST_TIME_FILE).as_deref(), Some("90"));
+        assert_eq!(storage.read(BEST_TIME_FILE).as_deref(), Some("30"));
+        game.set_coop(false);
+        assert_eq!(game.best_time, 30.0);
+        let wrap = GameConfig {
+            walls: WallMode::Wrap,
+            ..GameConfig::default()
+        };
+        let game = GameState::with_storage_and_config(storage, wrap);
+        assert_eq!(game.best_time_file(), "best_time_wrap.txt");
+        assert_eq!(game.best_time, 0.0);
+    }
+
+    #[test]
+    fn test_handicap_applied_to_match() {
+        let mut settings = MatchSettings::new(100);
+        settings.handicaps[1] = Handicap {
+            starting_length: Some(6),
+            speed_offset: 0.05,
+            score_multiplier: 0.5,
+        };
+        let mut dual = DualGame::new_match(settings);
+
+        assert_eq!(dual.boards[0].snake.len(), 3);
+        assert_eq!(dual.boards[1].snake.len(), 6);
+        assert_eq!(
+            dual.boards[1].speed,
+            Speed::from_seconds_per_cell(dual.boards[0].speed.seconds_per_cell() + 0.05)
+        );
+
+        // Eating scores according to the multiplier
+        let board = &mut dual.boards[1];
+        board.food = Food::normal(board.snake[0].move_in_direction(board.direction));
+        board.move_snake();
+        assert_eq!(board.score, 5);
+
+        // and the handicap sticks around for a rematch
+        board.restart();
+        assert_eq!(board.snake.len(), 6);
+        assert_eq!(board.score, 0);
+    }
+
+    #[test]
+    fn test_handicap_starting_length_fits_board() {
+        let mut game = GameState::new();
+        game.handicap.starting_length = Some(100);
+        game.apply_handicap();
+
+        // Can only grow back to the left wall
+        assert_eq!(game.snake.len(), (GRID_WIDTH / 2 + 1) as usize);
+        assert!(game
+            .snake
+            .iter()
+            .all(|segment| game.config.contains(*segment)));
+    }
+
+    #[test]
+    fn test_snake_start() {
+        let spawns = Spawns::parse(
+            "# along the top\nlength = 5\nhead = 6,0 # corner-ish\ndirection = Left\n",
+        )
+        .unwrap();
+        let start = spawns.candidates[0];
+        assert_eq!(start.cells()[4], Position::new(10, 0));
+        assert_eq!(Spawns::parse("").unwrap(), Spawns::default());
+
+        let mut game = GameState::new();
+        game.set_spawns(spawns);
+        assert_eq!(game.snake.len(), 5);
+        assert_eq!(game.next_direction, Direction::Left);
+        assert!(!game.snake.contains(&game.food.position));
+        game.restart();
+        assert_eq!(game.snake, start.cells());
+
+        // off the board, facing straight into a wall, or nonsense
+        let board = GameConfig::default();
+        let fit = |text: &str| Spawns::parse(text).and_then(|spawns| spawns.fit(&board));
+        assert!(fit("length = 12\nhead = 5,5")
+            .unwrap_err()
+            .contains("runs off the board"));
+        assert!(fit("head = 19,3").is_err());
+        assert!(Spawns::parse("length = 0").is_err());
+        // ...on this board: a bigger one has room, and wrapping walls can't be run into
+        let bigger = GameConfig::new(30, 20, CELL_SIZE).unwrap();
+        let facing_edge = Spawns::parse("head = 19,3").unwrap();
+        assert!(facing_edge.fit(&bigger).is_ok());
+        let wrap = GameConfig {
+            walls: WallMode::Wrap,
+            ..board
+        };
+        assert!(facing_edge.fit(&wrap).is_ok());
+        assert!(Spawns::parse("direction = sideways")
+            .unwrap_err()
+            .starts_with("line 1:"));
+    }
+
+    #[test]
+    fn test_spawn_points() {
+        use rand::SeedableRng;
+        let spawns =
+            Spawns::parse("length = 4\nspawn = 5,2 right\nspawn = 14,12 left\nspawn = 10,3 down\n")
+                .unwrap();
+        assert_eq!(spawns.candidates.len(), 3);
+        assert!(spawns.candidates.iter().all(|start| start.length == 4));
+
+        // the same seed picks the same spawns, and every one gets a turn
+        let picks = |seed| {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            (0..30)
+                .map(|_| spawns.pick(&mut rng).head)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(picks(9), picks(9));
+        assert!(spawns
+            .candidates
+            .iter()
+            .all(|start| picks(9).contains(&start.head)));
+
+        // one bad candidate sinks the lot, naming its cell
+        let error = Spawns::parse("length = 4\nspawn = 5,2 right\nspawn = 1,7 right\n")
+            .unwrap()
+            .fit(&GameConfig::default())
+            .unwrap_err();
+        assert!(error.contains("at 1,7"), "{}", error);
+        assert!(Spawns::parse("spawn = 5,2 right\nhead = 4,4").is_err());
+        assert!(Spawns::parse("spawn = 5,2").is_err());
+    }
+
+    #[test]
+    fn test_grace_period() {
+        // facing a wall two cells away - without grace it crashes on tick three
+        let spawns = Spawns::parse("head = 18,7\ngrace = 3\n").unwrap();
+        assert_eq!(spawns.grace, GracePeriod::Ticks(3));
+        let mut game = GameState::new();
+        game.set_spawns(spawns);
+        let start = game.snake.clone();
+        for _ in 0..3 {
+            assert!(game.in_grace());
+            game.step();
+            assert_eq!(game.snake, start);
+        }
+        assert!(!game.in_grace());
+        game.step();
+        assert_ne!(game.snake, start);
+
+        // waiting for the player holds as long as it takes, and their steer counts
+        let mut game = GameState::new();
+        game.set_spawns(Spawns::parse("head = 18,7\ngrace = input").unwrap());
+        for _ in 0..50 {
+            game.step();
+        }
+        assert!(!game.game_over);
+        game.handle_input(Direction::Down);
+        assert!(!game.in_grace());
+        game.step();
+        assert_eq!(game.snake[0], Position::new(18, 8));
+
+        // and it comes back with every restart
+        game.restart();
+        assert_eq!(game.grace_left, GracePeriod::UntilInput);
+        assert!(Spawns::parse("grace = soon").is_err());
+    }
+
+    #[test]
+    fn test_best_of_series() {
+        let mut series = Series::new(5);
+        assert_eq!(series.wins_needed(), 3);
+
+        series.record_round(Some(0));
+        series.record_round(None);
+        series.record_round(Some(1));
+        series.record_round(Some(0));
+        assert_eq!(series.match_winner(), None);
+
+        series.record_round(Some(0));
+        assert_eq!(series.wins(0), 3);
+        assert_eq!(series.match_winner(), Some(0));
+    }
+
+    #[test]
+    fn test_match_round_flow() {
+        let mut dual = DualGame::new_versus(50);
+
+        // Can't skip a round that's still being played
+        dual.next_round();
+        assert!(dual.series.round_winners.is_empty());
+
+        dual.boards[0].score = 50;
+        dual.end_if_either_lost();
+        dual.record_round();
+        dual.record_round(); // only counted once
+        assert_eq!(dual.series.round_winners, vec![Some(0)]);
+
+        // Next round starts on fresh boards
+        dual.next_round();
+        assert_eq!(dual.boards[0].score, 0);
+        assert!(!dual.is_over());
+
+        // Once the match is decided the next round is a rematch
+        dual.series.round_winners = vec![Some(1), Some(1), Some(1)];
+        dual.boards[1].game_over = true;
+        dual.record_round();
+        dual.next_round();
+        assert!(dual.series.round_winners.is_empty());
+
+        // the ratings went through the game's storage
+        let ratings = Ratings::load(dual.boards[0].storage.as_ref());
+        assert_eq!(ratings.get("P2"), 1516.0);
+        assert_eq!(ratings.get("P1"), 1484.0);
+    }
+
+    #[test]
+    fn test_elo_update() {
+        // Evenly matched players swap half the K factor
+        let (winner, loser) = elo_update(1500.0, 1500.0);
+        assert_eq!(winner, 1516.0);
+        assert_eq!(loser, 1484.0);
+
+        // Beating a much stronger player is worth more than beating a weaker one
+        let (upset, _) = elo_update(1400.0, 1600.0);
+        let (expected, _) = elo_update(1600.0, 1400.0);
+        assert!(upset - 1400.0 > expected - 1600.0);
+    }
+
+    #[test]
+    fn test_match_updates_ratings() {
+        let mut dual = DualGame::new_named_versus(50, ["Ann".to_string(), "Bob".to_string()]);
+        dual.series.round_winners = vec![Some(1), Some(1), Some(1)];
+
+        let mut ratings = Ratings::default();
+        dual.update_ratings(&mut ratings);
+
+        assert_eq!(ratings.get("Bob"), 1516.0);
+        assert_eq!(ratings.get("Ann"), 1484.0);
+        assert_eq!(
+            dual.rating_changes,
+            Some([(1500.0, 1484.0), (1500.0, 1516.0)])
+        );
+    }
+
+    #[test]
+    fn test_handshake_round_trip() {
+        let handshake = Handshake::new(NetMode::Versus, &GameConfig::default(), 42);
+        assert_eq!(
+            handshake.to_line(),
+            format!("snake {} versus 20x15 42", PROTOCOL_VERSION)
+        );
+        assert_eq!(Handshake::parse(&handshake.to_line()), Ok(handshake));
+
+        for line in [
+            "",
+            "hello",
+            "snake 1 versus 20x15",
+            "snake 1 solo 20x15 1",
+            "snake 1 coop 20 1",
+        ] {
+            assert!(matches!(
+                Handshake::parse(line),
+                Err(HandshakeError::Malformed(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_handshake_compatibility_matrix() {
+        let ours = Handshake::new(NetMode::Versus, &GameConfig::default(), 1);
+
+        // (peer version, peer mode, peer grid, accepted)
+        let matrix = [
+            (
+                PROTOCOL_VERSION,
+                NetMode::Versus,
+                (GRID_WIDTH, GRID_HEIGHT),
+                true,
+            ),
+            (
+                OLDEST_COMPATIBLE_PROTOCOL,
+                NetMode::Versus,
+                (GRID_WIDTH, GRID_HEIGHT),
+                true,
+            ),
+            (
+                OLDEST_COMPATIBLE_PROTOCOL - 1,
+                NetMode::Versus,
+                (GRID_WIDTH, GRID_HEIGHT),
+                false,
+            ),
+            (
+                PROTOCOL_VERSION + 1,
+                NetMode::Versus,
+                (GRID_WIDTH, GRID_HEIGHT),
+                false,
+            ),
+            (
+                PROTOCOL_VERSION,
+                NetMode::Coop,
+                (GRID_WIDTH, GRID_HEIGHT),
+                false,
+            ),
+            (
+                PROTOCOL_VERSION,
+                NetMode::Versus,
+                (GRID_WIDTH + 1, GRID_HEIGHT),
+                false,
+            ),
+        ];
+        for (version, mode, (width, height), accepted) in matrix {
+            let peer = Handshake {
+                protocol_version: version,
+                mode,
+                grid_width: width,
+                grid_height: height,
+                seed: 7, // seeds never have to match, the host's wins
+            };
+            assert_eq!(ours.check_compatible(&peer).is_ok(), accepted, "{:?}", peer);
+        }
+
+        let error = ours
+            .check_compatible(&Handshake {
+                protocol_version: PROTOCOL_VERSION + 1,
+                ..ours
+            })
+            .unwrap_err();
+        assert!(error.to_string().contains("protocol version"));
+    }
+
+    #[test]
+    fn test_peer_reconnect_window() {
+        let mut peer = PeerStatus::Connected;
+        peer.disconnect();
+        assert!(peer.is_paused());
+        peer.advance(RECONNECT_WINDOW / 2.0);
+        assert!(peer.reconnect());
+        assert_eq!(peer, PeerStatus::Connected);
+
+        peer.disconnect();
+        peer.advance(RECONNECT_WINDOW);
+        assert_eq!(peer, PeerStatus::Dropped);
+        assert!(!peer.is_paused());
+        assert!(!peer.reconnect());
+    }
+
+    #[test]
+    fn test_resync_from_host() {
+        let mut host = GameState::new();
+        host.move_snake();
+        host.score = 40;
+        let mut client = GameState::new();
+        assert_ne!(client.state_hash(), host.state_hash());
+
+        // A damaged snapshot is refused
+        assert!(!client.resync_from(&host, host.state_hash() ^ 1));
+        assert_eq!(client.score, 0);
+
+        assert!(client.resync_from(&host, host.state_hash()));
+        assert_eq!(client.state_hash(), host.state_hash());
+        assert_eq!(client.snake, host.snake);
+    }
+
+    // One end of an in-memory connection, for playing sessions against each other
+    struct PipeLink {
+        outgoing: std::sync::mpsc::Sender<String>,
+        incoming: std::sync::mpsc::Receiver<String>,
+    }
+
+    impl NetLink for PipeLink {
+        fn send(&mut self, line: &str) -> std::io::Result<()> {
+            self.outgoing
+                .send(line.to_string())
+                .map_err(|_| std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn recv(&mut self) -> std::io::Result<Option<String>> {
+            use std::sync::mpsc::TryRecvError;
+            match self.incoming.try_recv() {
+                Ok(line) => Ok(Some(line)),
+                Err(TryRecvError::Empty) => Ok(None),
+                Err(TryRecvError::Disconnected) => Err(std::io::ErrorKind::BrokenPipe.into()),
+            }
+        }
+    }
+
+    fn pipe() -> (Box<dyn NetLink>, Box<dyn NetLink>) {
+        let (a_out, b_in) = std::sync::mpsc::channel();
+        let (b_out, a_in) = std::sync::mpsc::channel();
+        (
+            Box::new(PipeLink {
+                outgoing: a_out,
+                incoming: a_in,
+            }),
+            Box::new(PipeLink {
+                outgoing: b_out,
+                incoming: b_in,
+            }),
+        )
+    }
+
+    // Run sessions side by side for `seconds`, a host step at a time
+    fn run_sessions(sessions: &mut [&mut NetSession], seconds: f64) {
+        for _ in 0..(seconds / NET_STEP).round() as usize {
+            for session in sessions.iter_mut() {
+                session.update(NET_STEP);
+            }
+        }
+    }
+
+    fn board_hashes(session: &NetSession) -> Vec<u64> {
+        session
+            .boards
+            .iter()
+            .map(|board| board.state_hash())
+            .collect()
+    }
+
+    #[test]
+    fn test_online_match_through_session() {
+        let mut host = NetSession::host(NetMode::Versus, GameConfig::default(), "Ann", 42);
+        let (to_client, to_host) = pipe();
+        host.accept(to_client);
+        let mut client = NetSession::join(to_host, NetMode::Versus, GameConfig::default(), "Bob");
+        run_sessions(&mut [&mut host, &mut client], 0.3);
+
+        assert!(host.is_playing() && client.is_playing());
+        assert_eq!(client.names, host.names);
+        assert_eq!(client.names, ["Ann".to_string(), "Bob".to_string()]);
+        assert_eq!(client.handshake.seed, 42);
+        assert_eq!(board_hashes(&client), board_hashes(&host));
+
+        // The client's turns steer its own board on the host
+        client.steer(Direction::Down);
+        run_sessions(&mut [&mut host, &mut client], 0.3);
+        assert_eq!(host.boards[1].direction, Direction::Down);
+        assert_eq!(host.boards[0].direction, Direction::Right);
+        assert_eq!(board_hashes(&client), board_hashes(&host));
+
+        // The client drops: the host holds the match until it's back...
+        client.peers.clear();
+        run_sessions(&mut [&mut host], 0.1);
+        assert!(host.other.is_some_and(|status| status.is_paused()));
+        let paused = board_hashes(&host);
+        run_sessions(&mut [&mut host], 1.0);
+        assert_eq!(board_hashes(&host), paused);
+
+        // ...nobody else gets the seat meanwhile...
+        let (to_stranger, from_stranger) = pipe();
+        host.accept(to_stranger);
+        let mut stranger =
+            NetSession::join(from_stranger, NetMode::Versus, GameConfig::default(), "Eve");
+        run_sessions(&mut [&mut host, &mut stranger], 0.1);
+        assert!(stranger
+            .notice
+            .as_ref()
+            .is_some_and(|notice| notice.contains("two players")));
+
+        // ...and the client comes back to the host's boards and carries on
+        let (to_client, to_host) = pipe();
+        host.accept(to_client);
+        client.rejoin(to_host);
+        run_sessions(&mut [&mut host, &mut client], 0.2);
+        assert!(host.is_playing());
+        assert_eq!(board_hashes(&client), board_hashes(&host));
+        assert_ne!(board_hashes(&host), paused);
+
+        // Gone for longer than the window loses the match
+        client.peers.clear();
+        run_sessions(&mut [&mut host], RECONNECT_WINDOW + 0.5);
+        assert_eq!(host.other, Some(PeerStatus::Dropped));
+        assert!(host.boards[1].game_over);
+        assert!(host
+            .notice
+            .as_ref()
+            .is_some_and(|notice| notice.contains("Ann wins")));
+    }
+
+    #[test]
+    fn test_online_handshake_mismatch_refused() {
+        let mut host = NetSession::host(NetMode::Versus, GameConfig::default(), "Ann", 42);
+        let (to_client, to_host) = pipe();
+        host.accept(to_client);
+        let mut client = NetSession::join(to_host, NetMode::Coop, GameConfig::default(), "Bob");
+        run_sessions(&mut [&mut host, &mut client], 0.1);
+
+        assert_eq!(host.other, None);
+        assert!(host.peers.is_empty());
+        assert!(!client.is_playing());
+        assert!(client
+            .notice
+            .as_ref()
+            .is_some_and(|notice| notice.contains("wants versus")));
+
+        // both ends have to be on the same size of board
+        let bigger = GameConfig::new(30, 20, CELL_SIZE).unwrap();
+        let mut host = NetSession::host(NetMode::Versus, bigger, "Ann", 42);
+        let (to_client, to_host) = pipe();
+        host.accept(to_client);
+        let mut client = NetSession::join(to_host, NetMode::Versus, GameConfig::default(), "Bob");
+        run_sessions(&mut [&mut host, &mut client], 0.1);
+        assert!(!client.is_playing());
+        assert!(client
+            .notice
+            .as_ref()
+            .is_some_and(|notice| notice.contains("30x20")));
+
+        let (to_client, to_host) = pipe();
+        host.accept(to_client);
+        let mut client = NetSession::join(to_host, NetMode::Versus, bigger, "Bob");
+        run_sessions(&mut [&mut host, &mut client], 0.1);
+        assert!(client.is_playing());
+        assert!(client
+            .boards
+            .iter()
+            .all(|board| board.config.grid() == (30, 20)));
+        assert_eq!(board_hashes(&client), board_hashes(&host));
+    }
+
+    #[test]
+    fn test_online_chat_through_session() {
+        let mut host = NetSession::host(NetMode::Coop, GameConfig::default(), "Ann", 7);
+        let (to_client, to_host) = pipe();
+        host.accept(to_client);
+        let mut client = NetSession::join(to_host, NetMode::Coop, GameConfig::default(), "Bob");
+        run_sessions(&mut [&mut host, &mut client], 0.1);
+
+        client.say("good luck");
+        host.say("  you too  ");
+        run_sessions(&mut [&mut host, &mut client], 0.1);
+        let said = |session: &NetSession| -> Vec<(String, String)> {
+            session
+                .chat
+                .messages
+                .iter()
+                .map(|message| (message.sender.clone(), message.text.clone()))
+                .collect()
+        };
+        assert_eq!(
+            said(&host),
+            [
+                ("Ann".into(), "you too".into()),
+                ("Bob".into(), "good luck".into())
+            ]
+        );
+        assert_eq!(
+            said(&client),
+            [
+                ("Bob".into(), "good luck".into()),
+                ("Ann".into(), "you too".into())
+            ]
+        );
+
+        // Muting drops the other side's messages but not our own
+        client.chat.muted = true;
+        host.say("hello?");
+        client.say("brb");
+        run_sessions(&mut [&mut host, &mut client], 0.1);
+        assert_eq!(client.chat.messages.last().unwrap().text, "brb");
+        assert_eq!(host.chat.messages.last().unwrap().text, "brb");
+
+        // Everything's faded out a few seconds later
+        run_sessions(&mut [&mut host, &mut client], CHAT_MESSAGE_LIFETIME);
+        assert!(host.chat.messages.is_empty() && client.chat.messages.is_empty());
+    }
+
+    #[test]
+    fn test_online_latency_and_input_delay() {
+        let mut host = NetSession::host(NetMode::Versus, GameConfig::default(), "Ann", 7);
+        let (to_client, to_host) = pipe();
+        host.accept(to_client);
+        let mut client = NetSession::join(to_host, NetMode::Versus, GameConfig::default(), "Bob");
+        run_sessions(&mut [&mut host, &mut client], PING_INTERVAL * 2.0);
+
+        // Both ends measure the round trip, which here is a step each way at most
+        for session in [&host, &client] {
+            let round_trip = session.latency.round_trip.unwrap();
+            assert!(round_trip > 0.0 && round_trip <= NET_STEP * 2.0 + 1e-9);
+        }
+        // and the host's own turns wait out the one-way trip
+        assert_eq!(host.input_delay.delay_ticks, 1);
+
+        // A chosen delay holds the host's turn back that many steps
+        host.set_input_delay(Some(3));
+        host.steer(Direction::Down);
+        run_sessions(&mut [&mut host, &mut client], NET_STEP * 2.0);
+        assert_eq!(host.boards[0].next_direction, Direction::Right);
+        run_sessions(&mut [&mut host, &mut client], NET_STEP);
+        assert_eq!(host.boards[0].next_direction, Direction::Down);
+
+        // and stays put whatever the ping does
+        run_sessions(&mut [&mut host, &mut client], PING_INTERVAL * 2.0);
+        assert_eq!(host.input_delay.delay_ticks, 3);
+        host.set_input_delay(None);
+        assert_eq!(host.input_delay.delay_ticks, 1);
+    }
+
+    #[test]
+    fn test_online_host_migration() {
+        let mut host = NetSession::host(NetMode::Versus, GameConfig::default(), "Ann", 7);
+        let (to_client, to_host) = pipe();
+        host.accept(to_client);
+        let mut client = NetSession::join(to_host, NetMode::Versus, GameConfig::default(), "Bob");
+        run_sessions(&mut [&mut host, &mut client], 0.5);
+        let last_hashes = board_hashes(&host);
+        assert_eq!(board_hashes(&client), last_hashes);
+
+        // The host quits; the client tries to get back for a while, then takes over from
+        // the boards as they last matched the host's hash
+        drop(host);
+        run_sessions(&mut [&mut client], HOST_TAKEOVER_WAIT / 2.0);
+        assert_eq!(client.role, NetRole::Client);
+        assert!(client.lost_host());
+        run_sessions(&mut [&mut client], HOST_TAKEOVER_WAIT);
+        assert_eq!(client.role, NetRole::Host);
+        assert_eq!(board_hashes(&client), last_hashes);
+        assert!(client.wants_player());
+
+        // The old host's player comes back as a client, in their own seat
+        let (to_ann, to_bob) = pipe();
+        client.accept(to_ann);
+        let mut ann = NetSession::join(to_bob, NetMode::Versus, GameConfig::default(), "Ann");
+        run_sessions(&mut [&mut client, &mut ann], 0.3);
+        assert!(client.is_playing() && ann.is_playing());
+        assert_eq!(ann.player, 0);
+        assert_eq!(ann.names, client.names);
+        assert_ne!(board_hashes(&client), last_hashes);
+        assert_eq!(board_hashes(&ann), board_hashes(&client));
+
+        ann.steer(Direction::Down);
+        run_sessions(&mut [&mut client, &mut ann], 0.3);
+        assert_eq!(client.boards[0].direction, Direction::Down);
+        assert_eq!(client.boards[1].direction, Direction::Right);
+    }
+
+    #[test]
+    fn test_online_spectators() {
+        let mut host = NetSession::host(NetMode::Versus, GameConfig::default(), "Ann", 9);
+        let (to_client, to_host) = pipe();
+        host.accept(to_client);
+        let mut client = NetSession::join(to_host, NetMode::Versus, GameConfig::default(), "Bob");
+        let (to_viewer, to_host) = pipe();
+        host.accept(to_viewer);
+        let mut viewer =
+            NetSession::spectate(to_host, NetMode::Versus, GameConfig::default(), "Cat");
+        run_sessions(&mut [&mut host, &mut client, &mut viewer], 0.3);
+
+        // The spectator sees who's playing and mirrors both boards...
+        assert_eq!(host.spectators.names, ["Cat".to_string()]);
+        assert_eq!(viewer.names, host.names);
+        assert_eq!(viewer.handshake.seed, 9);
+        assert_eq!(board_hashes(&viewer), board_hashes(&host));
+
+        // ...but its turns steer nobody
+        viewer.steer(Direction::Down);
+        run_sessions(&mut [&mut host, &mut client, &mut viewer], 0.3);
+        assert_eq!(host.boards[0].direction, Direction::Right);
+        assert_eq!(host.boards[1].direction, Direction::Right);
+        assert_eq!(board_hashes(&viewer), board_hashes(&host));
+
+        // Only so many get to watch
+        let mut crowd = Vec::new();
+        for seat in 1..=MAX_SPECTATORS {
+            let (to_viewer, to_host) = pipe();
+            host.accept(to_viewer);
+            crowd.push(NetSession::spectate(
+                to_host,
+                NetMode::Versus,
+                GameConfig::default(),
+                &format!("V{}", seat),
+            ));
+        }
+        let mut everyone: Vec<&mut NetSession> = crowd.iter_mut().collect();
+        everyone.push(&mut host);
+        run_sessions(&mut everyone, 0.1);
+        assert_eq!(host.spectators.names.len(), MAX_SPECTATORS);
+        assert!(crowd[MAX_SPECTATORS - 1]
+            .notice
+            .as_ref()
+            .is_some_and(|notice| notice.contains("spectator slots")));
+
+        // A spectator that leaves frees its slot, and one that loses the host never takes over
+        viewer.peers.clear();
+        run_sessions(&mut [&mut host], 0.1);
+        assert!(!host.spectators.names.contains(&"Cat".to_string()));
+        host.peers.clear();
+        run_sessions(
+            &mut [&mut host, &mut client, &mut crowd[0]],
+            HOST_TAKEOVER_WAIT + 1.0,
+        );
+        assert_eq!(client.role, NetRole::Host);
+        assert_eq!(crowd[0].role, NetRole::Spectator);
+        assert!(crowd[0].lost_host());
+    }
+
+    #[test]
+    fn test_room_code() {
+        let code = room_code();
+        assert_eq!(code.len(), ROOM_CODE_LENGTH);
+        assert!(code
+            .chars()
+            .all(|c| c.is_ascii_uppercase() && c != 'I' && c != 'O'));
+    }
+
+    #[test]
+    fn test_chat_log() {
+        let mut chat = ChatLog::default();
+        chat.add("Ann", &"a".repeat(MAX_CHAT_LENGTH + 20));
+        chat.add("Ann", "   "); // nothing to say
+        assert_eq!(chat.messages.len(), 1);
+        assert_eq!(chat.messages[0].text.len(), MAX_CHAT_LENGTH);
+
+        chat.receive(&encode_chat_line("Bob", "gg\n"));
+        assert_eq!(chat.messages[1].sender, "Bob");
+        assert_eq!(chat.messages[1].text, "gg");
+
+        // Fully opaque until the last second, then fading out and gone
+        chat.advance(CHAT_MESSAGE_LIFETIME - CHAT_FADE / 2.0);
+        let alphas: Vec<f32> = chat.visible().map(|(_, alpha)| alpha).collect();
+        assert_eq!(alphas, vec![0.5, 0.5]);
+        chat.advance(CHAT_FADE);
+        assert!(chat.messages.is_empty());
+
+        chat.muted = true;
+        chat.receive("chat Bob hello?");
+        assert!(chat.messages.is_empty());
+    }
+
+    #[test]
+    fn test_latency_smoothing() {
+        let mut latency = Latency::default();
+        assert_eq!(latency.label(), "-- ms");
+        latency.record(0.1);
+        assert_eq!(latency.label(), "100 ms");
+        latency.record(0.6); // one bad ping only moves it a little
+        assert_eq!(latency.label(), "200 ms");
+
+        // 100ms each way at 0.15s ticks needs one tick of delay
+        assert_eq!(latency.suggested_delay(0.15), 1);
+        assert_eq!(Latency::default().suggested_delay(0.15), 0);
+    }
+
+    #[test]
+    fn test_input_delay() {
+        let mut delay = InputDelay::new(2);
+        assert_eq!(delay.push(10, Direction::Up), 12);
+        delay.push(11, Direction::Left);
+
+        assert!(delay.take_due(11).is_empty());
+        assert_eq!(delay.take_due(12), vec![Direction::Up]);
+        assert_eq!(delay.take_due(20), vec![Direction::Left]);
+
+        // No delay applies turns straight away
+        let mut no_delay = InputDelay::new(0);
+        no_delay.push(5, Direction::Down);
+        assert_eq!(no_delay.take_due(5), vec![Direction::Down]);
+    }
+
+    #[test]
+    fn test_host_migration() {
+        let mut game = GameState::new();
+        let mut migration = HostMigration::new(NetRole::Client);
+
+        // Nothing verified yet, so there's nothing safe to continue from
+        assert_eq!(migration.host_lost(&mut game), None);
+        assert_eq!(migration.role, NetRole::Client);
+
+        assert!(migration.verify(1, game.state_hash(), &game));
+        let verified_snake = game.snake.clone();
+        game.move_snake();
+        assert!(!migration.verify(2, 0, &game)); // disagreed with the host, not kept
+
+        assert_eq!(migration.host_lost(&mut game), Some(1));
+        assert_eq!(migration.role, NetRole::Host);
+        assert_eq!(game.snake, verified_snake);
+    }
+
+    #[test]
+    fn test_spectator_slots() {
+        assert!(NetRole::Client.can_steer());
+        assert!(!NetRole::Spectator.can_steer());
+
+        let mut slots = SpectatorSlots::default();
+        for index in 0..MAX_SPECTATORS {
+            assert!(slots.join(&format!("viewer{}", index)));
+        }
+        assert!(!slots.join("late"));
+        slots.leave("viewer0");
+        assert!(slots.join("late"));
+
+        // A spectator never takes over when the host drops
+        let mut game = GameState::new();
+        let mut migration = HostMigration::new(NetRole::Spectator);
+        migration.verify(1, game.state_hash(), &game);
+        assert_eq!(migration.host_lost(&mut game), None);
+    }
+
+    #[test]
+    fn test_ghost_run_recording() {
+        let mut game = GameState::new();
+        let mut run = GhostRun::start(&game);
+        let start = game.snake.clone();
+
+        game.food = Food::normal(game.snake[0].move_in_direction(game.direction));
+        game.move_snake();
+        game.elapsed = 1.5;
+        run.record_tick(&game, true);
+        game.move_snake();
+        run.record_tick(&game, false);
+
+        // Before eating the ghost is as long as a new snake, after eating it's one longer
+        assert_eq!(run.body_at(0), &start[..1]);
+        assert_eq!(run.body_at(2).len(), 3);
+        assert_eq!(run.body_at(2)[2], game.snake[0]);
+        assert_eq!(run.body_at(99), run.body_at(2)); // past the end it stays put
+
+        assert_eq!(run.split(0, 1.0), Some(-0.5));
+        assert_eq!(run.split(1, 1.0), None);
+
+        assert_eq!(GhostRun::parse(&run.to_text()), Some(run));
+        assert_eq!(GhostRun::parse("path 1,x"), None);
+        assert_eq!(GhostRun::parse(""), None);
+        assert!(GhostRun::default().body_at(5).is_empty());
+    }
+
+    #[test]
+    fn test_bench_backends_agree() {
+        let config = GameConfig::new(12, 10, CELL_SIZE).unwrap();
+        let workload = BenchWorkload::record(config, 7, 3);
+        assert_eq!(workload, BenchWorkload::record(config, 7, 3));
+        assert!(workload.move_count() > 30);
+        assert!(workload.blocked > 0);
+
+        let results = bench_bodies(&workload, 1);
+        let names: Vec<&str> = results.iter().map(|result| result.name).collect();
+        assert_eq!(names, ["Vec", "VecDeque+HashSet", "bitboard"]);
+        assert!(results.iter().all(|result| result.correct));
+
+        let table = bench_table(&workload, &results);
+        assert!(table.starts_with("3 games"));
+        assert!(table.contains("on a 12x10 board"));
+        assert_eq!(table.lines().count(), 5);
+        assert!(!table.contains("WRONG"));
+    }
+
+    #[test]
+    fn test_seeded_games_repeat() {
+        // the bot plays the same moves, so the same seed has to give the same food
+        let foods = |seed| {
+            let mut game = GameState::new_with_seed(seed);
+            game.high_score = u32::MAX;
+            game.weather = Weather::Rain;
+            let mut foods = vec![game.food];
+            play_headless(&mut game, |_, game| {
+                if foods.last() != Some(&game.food) {
+                    foods.push(game.food);
+                }
+            });
+            (foods, game.run.slippery_cells)
+        };
+        let (first, puddles) = foods(42);
+        assert!(first.len() > 3);
+        assert_eq!(foods(42), (first.clone(), puddles));
+        assert_ne!(foods(43).0, first);
+
+        // a restart carries on the same sequence rather than starting it over
+        let mut game = GameState::new_with_seed(42);
+        game.restart();
+        let mut replayed = GameState::new_with_seed(42);
+        replayed.restart();
+        assert_eq!(game.food, replayed.food);
+        assert_eq!(game.rng.gen::<u64>(), replayed.rng.gen::<u64>());
+    }
+
+    #[test]
+    fn test_tick_without_a_context() {
+        let mut game = GameState::new();
+        game.food = Food::normal(Position::new(0, 0));
+        let head = game.snake[0];
+        let seconds_per_move = game.speed.seconds_per_cell();
+
+        game.tick(seconds_per_move * 0.5);
+        assert_eq!(game.snake[0], head);
+        assert!(game.elapsed > 0.0);
+        game.tick(seconds_per_move * 0.6);
+        assert_eq!(game.snake[0], head.move_in_direction(Direction::Right));
+
+        // nonsense times don't move anything or panic
+        let elapsed = game.elapsed;
+        game.tick(-1.0);
+        game.tick(f64::NAN);
+        game.tick(f64::INFINITY);
+        assert_eq!(game.elapsed, elapsed);
+        // and a huge one counts as a short stall
+        game.tick(f64::MAX);
+        assert!(game.elapsed <= elapsed + MAX_TICK_SECONDS);
+
+        // a bot can play a whole game on ticks alone
+        game.high_score = u32::MAX;
+        while !game.game_over {
+            let direction = bot_direction(&game);
+            game.handle_input(direction);
+            game.tick(1.0 / 60.0);
+        }
+        assert!(game.score > 0);
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_tui_board() {
+        use ratatui::backend::TestBackend;
+        use ratatui::crossterm::event::KeyCode as TermKey;
+
+        let mut game = GameState::new_with_seed(3);
+        game.skip_transition();
+        let head = game.snake[0];
+        let lines = tui_board(&game);
+        assert_eq!(lines.len(), GRID_HEIGHT as usize);
+        assert!(lines
+            .iter()
+            .all(|line| line.width() == GRID_WIDTH as usize * 2));
+        // the head is drawn in the head's color
+        let span = &lines[head.y as usize].spans[head.x as usize];
+        assert_eq!(span.content, "██");
+        let (r, g, b) = game.segment_color(0).to_rgb();
+        assert_eq!(span.style.fg, Some(ratatui::style::Color::Rgb(r, g, b)));
+
+        // the whole screen fits in a small terminal
+        let mut terminal = ratatui::Terminal::new(TestBackend::new(
+            GRID_WIDTH as u16 * 2 + 2,
+            GRID_HEIGHT as u16 + 4,
+        ))
+        .unwrap();
+        terminal.draw(|frame| draw_tui(frame, &game)).unwrap();
+        let screen = format!("{:?}", terminal.backend().buffer());
+        assert!(screen.contains("Score 0"));
+        assert!(screen.contains("Snake"));
+
+        assert_eq!(
+            TuiAction::from_key(TermKey::Left),
+            Some(TuiAction::Steer(Direction::Left))
+        );
+        assert_eq!(
+            TuiAction::from_key(TermKey::Char('q')),
+            Some(TuiAction::Quit)
+        );
+        assert_eq!(TuiAction::from_key(TermKey::Enter), None);
+    }
+
+    #[test]
+    fn test_rng_kinds() {
+        // Std rolls just as StdRng always did, so old seeds keep their games
+        let mut std_rng = rand::rngs::StdRng::seed_from_u64(5);
+        let mut game_rng = GameRng::new(RngKind::Std, 5);
+        for _ in 0..10 {
+            assert_eq!(std_rng.gen::<u64>(), game_rng.gen::<u64>());
+            assert_eq!(std_rng.gen_range(0..20), game_rng.gen_range(0..20));
+        }
+
+        // every kind picks up exactly where it was, whatever sizes it was rolled in
+        for kind in RngKind::ALL.into_iter().filter(|kind| kind.is_available()) {
+            let mut rng = GameRng::new(kind, 42);
+            assert_eq!(rng.kind(), kind);
+            let mut bytes = [0; 13];
+            for _ in 0..7 {
+                rng.gen_range(0..20);
+                rng.gen::<u64>();
+                rng.gen_bool(0.5);
+                rng.fill(&mut bytes);
+            }
+            let mut resumed = GameRng::resume(kind, rng.seed(), rng.position()).unwrap();
+            for _ in 0..10 {
+                assert_eq!(rng.gen::<u64>(), resumed.gen::<u64>(), "{}", kind.name());
+                assert_eq!(rng.gen::<u32>(), resumed.gen::<u32>(), "{}", kind.name());
+            }
+        }
+        // a damaged position can't hang the launch
+        for kind in RngKind::ALL.into_iter().filter(|kind| kind.is_available()) {
+            let resumed = GameRng::resume(kind, 42, u128::MAX);
+            assert!(resumed.is_none() || kind == RngKind::Std, "{}", kind.name());
+        }
+        assert!(GameRng::resume(RngKind::Small, 42, MAX_RNG_STEPS + 1).is_none());
+        assert_eq!(RngKind::parse("small"), Ok(RngKind::Small));
+        assert!(RngKind::parse("mersenne").is_err());
+
+        // a suspended run carries its RNG with it
+        let mut game = GameState::new_with_seed(9);
+        game.set_rng(RngKind::Small);
+        game.rng.gen::<u64>();
+        let mut resumed = GameState::new();
+        assert!(resumed.resume_text(&game.suspend_text()));
+        assert_eq!(resumed.rng.kind(), RngKind::Small);
+        assert_eq!(resumed.seed, 9);
+        assert_eq!(game.rng.gen::<u64>(), resumed.rng.gen::<u64>());
+
+        // and a replay records which one it was
+        let replay = Replay::start(&game);
+        assert_eq!(replay.rng, RngKind::Small);
+        assert_eq!(
+            Replay::from_bytes(&replay.to_bytes(false)).unwrap().rng,
+            RngKind::Small
+        );
+
+        let mut options = LaunchOptions::default();
+        options
+            .apply_args(&["--rng".to_string(), "small".to_string()])
+            .unwrap();
+        assert_eq!(options.new_game().rng.kind(), RngKind::Small);
+    }
+
+    #[test]
+    fn test_food_kinds() {
+        // eat one piece of `kind` from a snake `length` long, running at `speed`
+        let eat = |kind: FoodKind, length: usize, speed: Speed| {
+            let mut game = GameState::new();
+            game.handicap.starting_length = Some(length);
+            game.apply_handicap();
+            game.speed = speed;
+            game.score = 100;
+            game.food = Food::new(game.snake[0].move_in_direction(game.direction), kind);
+            game.step();
+            assert!(!game.game_over);
+            game
+        };
+        let fast = Speed::cells_per_second(8);
+
+        let normal = eat(FoodKind::Normal, 5, fast);
+        assert_eq!((normal.score, normal.snake.len()), (110, 6));
+        assert!(normal.speed > fast);
+
+        let golden = eat(FoodKind::Golden, 5, fast);
+        assert_eq!((golden.score, golden.snake.len()), (150, 6));
+
+        let slow = eat(FoodKind::SlowDown, 5, fast);
+        assert_eq!((slow.score, slow.snake.len()), (110, 6));
+        assert!(slow.speed < fast && slow.speed >= Speed::START);
+        assert_eq!(eat(FoodKind::SlowDown, 5, Speed::START).speed, Speed::START);
+
+        let shrink = eat(FoodKind::Shrink, 8, fast);
+        assert_eq!((shrink.score, shrink.snake.len()), (110, 8 - SHRINK_CELLS));
+        assert_eq!(eat(FoodKind::Shrink, 4, fast).snake.len(), 3);
+
+        let poison = eat(FoodKind::Poison, 5, fast);
+        assert_eq!((poison.score, poison.snake.len()), (90, 5));
+
+        // golden food and poison rot away, and something else takes their place
+        let mut game = GameState::new();
+        game.food = Food::new(Position::new(0, 0), FoodKind::Golden);
+        assert_eq!(game.food.ticks_left, Some(FOOD_TIMEOUT_TICKS));
+        game.step();
+        assert_eq!(game.food.ticks_left, Some(FOOD_TIMEOUT_TICKS - 1));
+        game.food.ticks_left = Some(1);
+        game.step();
+        assert_ne!(game.food.position, Position::new(0, 0));
+        assert_eq!(game.food.kind, FoodKind::Normal);
+        assert_eq!(Food::normal(Position::new(1, 1)).ticks_left, None);
+
+        // classic games only have normal food, and the mixed one turns up every kind
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        assert!((0..100).all(|_| FoodMix::classic().roll(&mut rng) == FoodKind::Normal));
+        let rolled: Vec<FoodKind> = (0..500).map(|_| FoodMix::mixed().roll(&mut rng)).collect();
+        assert!(FoodKind::ALL.iter().all(|kind| rolled.contains(kind)));
+
+        let mut game = GameState::new();
+        game.high_score = 500;
+        game.set_food_mix(FoodMix::mixed());
+        assert_eq!(game.high_score_file(), "high_score_mixed.txt");
+        assert_eq!(game.high_score, 0);
+        game.restart();
+        assert_eq!(game.food_mix, FoodMix::mixed());
+    }
+
+    #[test]
+    fn test_levels() {
+        let pillars =
+            "name = \"Pillars\"\nsize = \"8x6\"\nspeed = 7\ntarget_score = 10\nwalls = \"\"\"\n\
+            ........\n.#....#.\n........\n........\n.#....#.\n........\n\"\"\"\n";
+        let level = Level::parse(pillars).unwrap();
+        assert_eq!(level.name, "Pillars");
+        assert_eq!((level.config.grid_width, level.config.grid_height), (8, 6));
+        assert_eq!(level.speed, Speed::cells_per_second(7));
+        assert_eq!(level.walls.len(), 4);
+        assert!(level.walls.contains(&Position::new(6, 4)));
+
+        // no size or walls is an open classic board
+        let open = Level::parse("name = \"Open\"\ntarget_score = 20").unwrap();
+        assert_eq!(open.config, GameConfig::default());
+        assert!(open.walls.is_empty());
+
+        // a name with quotes in it still reads back
+        let quoted = Level {
+            name: "Say \"hi\" \\ bye".to_string(),
+            ..level.clone()
+        };
+        assert_eq!(Level::parse(&quoted.to_text()), Ok(quoted));
+
+        for (broken, problem) in [
+            ("name = \"No target\"", "target_score"),
+            ("name = \"x\"\ntarget_score = 5\ncolour = 3", "unknown field `colour`"),
+            ("name = \"x\"\nname = \"y\"\ntarget_score = 5", "duplicate field `name`"),
+            ("name = \"x\"\ntarget_score = 5\nspeed = 4294968", "from 1 to 1000"),
+            ("name = \"x\"\ntarget_score = 5\nsize = \"8x6\"\nwalls = \"\"\"\n........\n\"\"\"", "rows"),
+            ("name = \"x\"\ntarget_score = 5\nwalls = \"\"\"\n", "unterminated string"),
+            (
+                "name = \"x\"\ntarget_score = 5\nsize = \"8x6\"\nwalls = \"\"\"\n........\n........\n........\n.....#..\n........\n........\n\"\"\"",
+                "in the snake's way",
+            ),
+        ] {
+            let e = Level::parse(broken).unwrap_err();
+            assert!(e.contains(problem), "{}: {}", problem, e);
+        }
+
+        // clearing the first level holds on the overlay, then moves on keeping the score
+        assert!(GameState::with_levels(Vec::new()).is_err());
+        let mut game = GameState::with_levels(vec![open.clone(), level.clone()]).unwrap();
+        game.high_score = u32::MAX;
+        assert_eq!(game.high_score_file(), "high_score_levels.txt");
+        game.score = 10;
+        game.food = Food::normal(game.snake[0].move_in_direction(Direction::Right));
+        game.step();
+        assert_eq!(game.score, 20);
+        assert!(game.level_complete());
+        let head = game.snake[0];
+        game.step();
+        assert_eq!(game.snake[0], head);
+        game.advance(std::time::Duration::from_secs_f64(
+            LEVEL_COMPLETE_DURATION + 0.1,
+        ));
+        assert!(!game.level_complete());
+        let run = game.levels.as_ref().unwrap();
+        assert_eq!((run.current, run.start_score), (1, 20));
+        assert!(run.board_changed);
+        assert_eq!(game.config, level.config);
+        assert_eq!(game.speed, level.speed);
+        assert!(!game.walls.contains(&game.food.position));
+
+        // walls are deadly
+        game.snake = vec![Position::new(2, 1), Position::new(3, 1)];
+        game.direction = Direction::Left;
+        game.next_direction = Direction::Left;
+        game.step();
+        assert!(game.game_over);
+        assert!(!game.levels.as_ref().unwrap().cleared);
+
+        // beating the last level ends the run as cleared, and a restart goes back to the first
+        game.restart();
+        assert_eq!(game.levels.as_ref().unwrap().current, 0);
+        assert_eq!(game.config, open.config);
+        assert!(game.walls.is_empty());
+        game.next_level();
+        game.next_level();
+        assert!(game.game_over);
+        assert!(game.levels.as_ref().unwrap().cleared);
+
+        // levels play in file name order, skipping broken files
+        let dir = std::env::temp_dir().join(format!("snake-levels-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("02_pillars.toml"), pillars).unwrap();
+        std::fs::write(
+            dir.join("01_open.toml"),
+            "name = \"Open\"\ntarget_score = 20",
+        )
+        .unwrap();
+        std::fs::write(dir.join("03_broken.toml"), "name = \"Broken\"").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a level").unwrap();
+        let (levels, problems) = discover_levels(dir.to_str().unwrap());
+        let names: Vec<&str> = levels.iter().map(|level| level.name.as_str()).collect();
+        assert_eq!(names, ["Open", "Pillars"]);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("03_broken.toml"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_level_editor() {
+        let config = GameConfig::new(10, 8, CELL_SIZE).unwrap();
+        let cell = Position::new;
+        let mut images = Symmetry::MirrorBoth.images(cell(1, 1), &config);
+        images.sort_by_key(|cell| (cell.y, cell.x));
+        assert_eq!(images, [cell(1, 1), cell(8, 1), cell(1, 6), cell(8, 6)]);
+        assert_eq!(
+            Symmetry::RotateHalf.images(cell(0, 0), &config),
+            [cell(0, 0), cell(9, 7)]
+        );
+        let square = GameConfig::new(9, 9, CELL_SIZE).unwrap();
+        assert_eq!(Symmetry::RotateQuarter.images(cell(1, 2), &square).len(), 4);
+        assert!(Symmetry::RotateQuarter
+            .images(cell(0, 0), &config)
+            .iter()
+            .all(|image| config.contains(*image)));
+        assert_eq!(line_cells(cell(0, 0), cell(3, 1)).len(), 4);
+        assert_eq!(rectangle_cells(cell(3, 3), cell(1, 1)).len(), 8);
+
+        let storage: Arc<dyn StorageBackend> = Arc::new(InMemoryStorage::default());
+        let path = std::env::temp_dir().join(format!("snake-editor-{}.toml", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut editor = LevelEditor::open(path, storage.clone()).unwrap();
+        editor.level.config = config;
+
+        // a mirrored rectangle, undone and redone
+        editor.set_tool(EditorTool::Rectangle);
+        editor.cycle_symmetry();
+        assert_eq!(editor.prefs.symmetry, Symmetry::MirrorLeftRight);
+        editor.press(cell(1, 1), true);
+        editor.drag(cell(2, 2));
+        assert!(editor.level.walls.is_empty(), "only a preview until let go");
+        assert_eq!(editor.stroke_cells().len(), 8);
+        editor.release();
+        assert_eq!(editor.level.walls.len(), 8);
+        editor.undo();
+        assert!(editor.level.walls.is_empty());
+        editor.key_down(synthetic_key(KeyCode::Y, KeyMods::CTRL));
+        assert_eq!(editor.level.walls.len(), 8);
+
+        // the brush fills in cells a quick drag jumps over, and a new edit drops the redos
+        editor.undo();
+        editor.set_tool(EditorTool::Brush);
+        editor.prefs.symmetry = Symmetry::Off;
+        editor.press(cell(0, 0), true);
+        editor.drag(cell(3, 0));
+        editor.release();
+        assert_eq!(editor.level.walls.len(), 4);
+        assert!(editor.redo.is_empty());
+        editor.press(cell(1, 0), false);
+        editor.release();
+        assert_eq!(editor.level.walls.len(), 3);
+
+        // the snake's start stays clear, and a stroke that changes nothing isn't kept
+        let start = SnakeStart::centered(&config);
+        editor.set_tool(EditorTool::Line);
+        editor.press(cell(0, start.head.y), true);
+        editor.drag(cell(9, start.head.y));
+        editor.release();
+        assert!(start
+            .cells()
+            .iter()
+            .all(|cell| !editor.level.walls.contains(cell)));
+        let kept = editor.undo.len();
+        editor.press(cell(start.head.x, start.head.y), true);
+        editor.release();
+        assert_eq!(editor.undo.len(), kept);
+        for _ in 0..EDITOR_UNDO_LIMIT + 5 {
+            editor.press(cell(5, 0), true);
+            editor.release();
+            editor.press(cell(5, 0), false);
+            editor.release();
+        }
+        assert_eq!(editor.undo.len(), EDITOR_UNDO_LIMIT);
+
+        // floor walled off from the start isn't saved, and Esc asks before losing edits
+        editor.level.walls = vec![cell(1, 0), cell(0, 1)];
+        assert!(editor.save().unwrap_err().contains("walled off"));
+        editor.key_down(synthetic_key(KeyCode::Escape, KeyMods::empty()));
+        assert!(editor.leaving && !editor.exiting);
+        editor.key_down(synthetic_key(KeyCode::Escape, KeyMods::empty()));
+        assert!(editor.exiting);
+
+        // a saved level loads back the same, and the tool and symmetry carry over
+        editor.level.walls = vec![cell(0, 0), cell(9, 7)];
+        editor.save().unwrap();
+        assert!(!editor.unsaved);
+        let reopened = LevelEditor::open(path, storage.clone()).unwrap();
+        assert_eq!(reopened.level.to_text(), editor.level.to_text());
+        assert_eq!(reopened.level.walls.len(), 2);
+        assert_eq!(reopened.prefs.tool, EditorTool::Line);
+        assert_eq!(
+            EditorPrefs::parse(&reopened.prefs.to_text()),
+            Ok(reopened.prefs)
+        );
+        assert!(EditorPrefs::parse("tool = spray").is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_random_levels() {
+        // a seed always makes the same level, and every floor cell can be reached
+        let config = GameConfig::default();
+        assert_eq!(Level::generate(7, config), Level::generate(7, config));
+        assert_ne!(
+            Level::generate(7, config).walls,
+            Level::generate(8, config).walls
+        );
+        for seed in 0..20 {
+            for config in [
+                config,
+                GameConfig::new(8, 6, CELL_SIZE).unwrap(),
+                GameConfig::new(60, 40, CELL_SIZE).unwrap(),
+            ] {
+                let level = Level::generate(seed, config);
+                assert!(level.unreachable().is_empty(), "seed {}", seed);
+                let start = SnakeStart::centered(&config).cells();
+                assert!(start.iter().all(|cell| !level.walls.contains(cell)));
+                let share = level.walls.len() as f64 / config.cell_count() as f64;
+                assert!(share > 0.0 && share < 0.5, "seed {}: {}", seed, share);
+                assert_eq!(level.seed, Some(seed));
+            }
+        }
+
+        // hand-made levels get the same check
+        let pocket = "name = \"Pocket\"\nsize = \"8x6\"\ntarget_score = 5\nwalls = \"\"\"\n\
+            .#......\n#.......\n........\n........\n........\n........\n\"\"\"";
+        let e = Level::parse(pocket).unwrap_err();
+        assert!(e.contains("walled off") && e.contains("0,0"), "{}", e);
+
+        // G swaps in a new one, and generated levels don't fill up the level progress
+        let mut recorder = ReplayRecorder::new(GameState::with_level(Level::generate(3, config)));
+        drive(&mut recorder, &[SyntheticInput::Press(KeyCode::G)]);
+        let level = recorder.game.levels.as_ref().unwrap().level().clone();
+        assert_ne!(level.seed, Some(3));
+        assert_eq!(recorder.game.walls, level.walls);
+        recorder.game.end_run();
+        assert!(recorder.game.storage.read(LEVEL_PROGRESS_FILE).is_none());
+    }
+
+    #[test]
+    fn test_level_difficulty() {
+        let open = Level::parse("name = \"Open\"\nsize = \"8x6\"\ntarget_score = 10").unwrap();
+        let difficulty = Difficulty::estimate(&open, 4);
+        assert_eq!(difficulty.open_share, 1.0);
+        assert_eq!(difficulty.corridor_width, 6.0);
+        assert_eq!((difficulty.bot_cleared, difficulty.stars), (4, 1));
+        assert_eq!(difficulty.stars_text(), "*····");
+
+        let bars = "name = \"Bars\"\nsize = \"8x6\"\ntarget_score = 10\nwalls = \"\"\"\n\
+            ........\n##.###.#\n........\n........\n#.###.##\n........\n\"\"\"";
+        let bars = Difficulty::estimate(&Level::parse(bars).unwrap(), 4);
+        assert!(bars.open_share < 1.0 && bars.corridor_width < 6.0);
+        assert!(bars.stars > difficulty.stars, "{}", bars);
+        assert!(bars.to_string().contains("the bot cleared"));
+        // and the same every time
+        assert_eq!(Difficulty::estimate(&open, 4), difficulty);
+    }
+
+    #[test]
+    fn test_level_select() {
+        let level =
+            |name: &str| Level::parse(&format!("name = \"{}\"\ntarget_score = 10", name)).unwrap();
+        let mut game =
+            GameState::with_levels(vec![level("First"), level("Second Try"), level("Third")])
+                .unwrap();
+        game.high_score = u32::MAX;
+        let mut recorder = ReplayRecorder::new(game);
+        let press = |recorder: &mut ReplayRecorder, key| {
+            drive(recorder, &[SyntheticInput::Press(key)]);
+        };
+
+        // only the first level is open to start with
+        press(&mut recorder, KeyCode::L);
+        let select = recorder.level_select.as_ref().unwrap();
+        assert_eq!((select.focus, select.thumbnails.len()), (0, 3));
+        // the levels are rated one a frame while the list is up
+        recorder.advance(std::time::Duration::from_millis(16));
+        let select = recorder.level_select.as_ref().unwrap();
+        assert!(select.difficulty[0].is_some() && select.difficulty[1].is_none());
+        press(&mut recorder, KeyCode::Down);
+        press(&mut recorder, KeyCode::Return);
+        assert!(recorder.level_select.is_some());
+        assert_eq!(recorder.game.levels.as_ref().unwrap().current, 0);
+        press(&mut recorder, KeyCode::Escape);
+        assert!(recorder.level_select.is_none());
+
+        // clearing it saves its record and opens the next
+        let game = &mut recorder.game;
+        game.food = Food::normal(game.snake[0].move_in_direction(game.direction));
+        game.step();
+        assert!(game.level_complete());
+        let progress = LevelProgress::load(game.storage.as_ref());
+        assert_eq!(
+            progress.get("First"),
+            LevelRecord {
+                cleared: true,
+                best_score: 10,
+                best_time: 0.0
+            }
+        );
+        let levels = &game.levels.as_ref().unwrap().levels;
+        assert!(progress.is_unlocked(levels, 1) && !progress.is_unlocked(levels, 2));
+
+        // dying partway through a level still keeps how long it lasted
+        game.advance(std::time::Duration::from_secs_f64(
+            LEVEL_COMPLETE_DURATION + 0.1,
+        ));
+        game.advance(std::time::Duration::from_secs_f64(0.05));
+        game.snake = vec![Position::new(0, 1), Position::new(1, 1)];
+        game.direction = Direction::Left;
+        game.next_direction = Direction::Left;
+        game.step();
+        assert!(game.game_over);
+        let progress = LevelProgress::load(game.storage.as_ref());
+        let second = progress.get("Second Try");
+        assert!(!second.cleared && second.best_time > 0.0);
+        assert_eq!(LevelProgress::parse(&progress.to_text()), progress);
+
+        // picking a level starts a fresh run there, and restarts go back to it
+        press(&mut recorder, KeyCode::L);
+        assert_eq!(recorder.level_select.as_ref().unwrap().focus, 1);
+        press(&mut recorder, KeyCode::Return);
+        assert!(recorder.level_select.is_none());
+        let run = recorder.game.levels.as_ref().unwrap();
+        assert_eq!((run.current, run.first), (1, 1));
+        assert!(!recorder.game.game_over);
+        recorder.game.restart();
+        assert_eq!(recorder.game.levels.as_ref().unwrap().current, 1);
+
+        // mid-run it asks first
+        recorder.game.change_screen(Screen::Playing);
+        recorder.game.skip_transition();
+        recorder.game.elapsed = 5.0;
+        press(&mut recorder, KeyCode::L);
+        press(&mut recorder, KeyCode::Up);
+        press(&mut recorder, KeyCode::Return);
+        assert!(recorder.confirm.is_some());
+    }
+
+    // Average distance of many spawned foods from a cell, for comparing policies
+    fn mean_food_distance(policy: FoodPolicy, snake: &[Position], from: Position) -> f64 {
+        let mut policy = policy;
+        let mut rng = GameRng::new(RngKind::Std, 1);
+        let total: i32 = (0..500)
+            .map(|_| {
+                let food = policy
+                    .spawn(snake, &GameConfig::default(), &mut rng)
+                    .unwrap();
+                assert!(GameConfig::default().contains(food) && !snake.contains(&food));
+                (food.x - from.x).abs() + (food.y - from.y).abs()
+            })
+            .sum();
+        total as f64 / 500.0
+    }
+
+    #[test]
+    fn test_food_policies() {
+        let mut game = GameState::new_with_seed(3);
+        let head = game.snake[0];
+        let center = game.config.center();
+
+        let uniform_from_head = mean_food_distance(FoodPolicy::Uniform, &game.snake, head);
+        let far_from_head = mean_food_distance(FoodPolicy::FarFromHead, &game.snake, head);
+        assert!(far_from_head > uniform_from_head + 2.0);
+
+        let uniform_from_center = mean_food_distance(FoodPolicy::Uniform, &game.snake, center);
+        let near_center = mean_food_distance(FoodPolicy::NearCenter, &game.snake, center);
+        assert!(near_center < uniform_from_center - 2.0);
+
+        // Uniform lands in every quarter of the board a fair share of the time
+        let run = &mut game.run;
+        let mut counts = [0; 4];
+        let mut uniform = FoodPolicy::Uniform;
+        for _ in 0..1000 {
+            counts[run.config.quadrant(
+                uniform
+                    .spawn(&run.snake, &run.config, &mut run.rng)
+                    .unwrap(),
+            )] += 1;
+        }
+        assert!(counts.iter().all(|count| *count > 150), "{:?}", counts);
+
+        let mut anti_streak = FoodPolicy::AntiStreak {
+            last_quadrant: None,
+        };
+        let mut last = None;
+        for _ in 0..500 {
+            let quarter = run.config.quadrant(
+                anti_streak
+                    .spawn(&run.snake, &run.config, &mut run.rng)
+                    .unwrap(),
+            );
+            assert_ne!(Some(quarter), last);
+            last = Some(quarter);
+        }
+    }
+
+    #[test]
+    fn test_food_policy_survives_restart() {
+        let mut game = GameState::new();
+        game.food_policy = FoodPolicy::NearCenter;
+        game.restart();
+        assert_eq!(game.food_policy, FoodPolicy::NearCenter);
+    }
+
+    #[test]
+    fn test_spawner_weights() {
+        let mut rng = rand::thread_rng();
+        let table = Spawner::new(1.0, 1).with('a', 3).with('b', 1).with('c', 0);
+        let picks: Vec<char> = (0..2000).filter_map(|_| table.pick(&mut rng)).collect();
+        assert_eq!(picks.len(), 2000);
+        assert!(!picks.contains(&'c')); // zero weight never spawns
+        let share_a = picks.iter().filter(|pick| **pick == 'a').count() as f64 / 2000.0;
+        assert!((share_a - 0.75).abs() < 0.05, "{}", share_a);
+
+        assert_eq!(Spawner::<char>::new(1.0, 1).pick(&mut rng), None);
+        assert_eq!(Spawner::new(0.0, 1).with('a', 1).tick(&mut rng), None);
+    }
+
+    #[test]
+    fn test_spawner_interval() {
+        let mut rng = rand::thread_rng();
+        let mut spawner = Spawner::new(1.0, 3).with(Hazard::Puddle, 1);
+        let rolls: Vec<bool> = (0..6).map(|_| spawner.tick(&mut rng).is_some()).collect();
+        assert_eq!(rolls, vec![false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn test_state_diff() {
+        let mut before = GameState::new();
+        before.food = Food::normal(Position::new(0, 0)); // out of the way of the first step
+        assert!(before.diff(&before).is_empty());
+        assert_eq!(before.diff(&before).to_string(), "no differences");
+
+        // One step: new head in, old tail out
+        let mut after = before.clone();
+        after.move_snake();
+        let diff = before.diff(&after);
+        assert_eq!(diff.segments_added, vec![after.snake[0]]);
+        assert_eq!(diff.segments_removed, vec![before.snake[2]]);
+        assert_eq!(diff.food_moved, None);
+
+        // Eating grows the snake, moves the food and scores
+        let mut eaten = before.clone();
+        eaten.food = Food::normal(eaten.snake[0].move_in_direction(eaten.direction));
+        let start = eaten.clone();
+        eaten.move_snake();
+        let diff = start.diff(&eaten);
+        assert!(diff.segments_removed.is_empty());
+        assert_eq!(
+            diff.food_moved,
+            Some((start.food.position, eaten.food.position))
+        );
+        assert_eq!(diff.score_delta, 10);
+        assert!(diff.to_string().contains("score: +10"));
+
+        let mut crashed = before.clone();
+        crashed.game_over = true;
+        crashed.direction = Direction::Up;
+        let diff = before.diff(&crashed);
+        assert_eq!(diff.game_over_changed, Some(true));
+        assert_eq!(
+            diff.direction_changed,
+            Some((Direction::Right, Direction::Up))
+        );
+    }
+
+    #[test]
+    fn test_state_sync_round_trip() {
+        let mut game = GameState::new();
+        let mut encoder = SyncEncoder::default();
+        let mut decoder = SyncDecoder::default();
+
+        let mut deltas = 0;
+        for tick in 0..KEYFRAME_INTERVAL + 5 {
+            if tick % 4 == 0 {
+                game.food = Food::normal(game.snake[0].move_in_direction(game.direction));
+            }
+            if tick % 7 == 0 {
+                let turn = match game.direction {
+                    Direction::Up | Direction::Down => Direction::Right,
+                    _ => Direction::Down,
+                };
+                game.handle_input(turn);
+            }
+            game.move_snake();
+            if game.game_over {
+                game.restart(); // a restart can't be a delta, so it goes out as a keyframe
+            }
+
+            let frame = encoder.encode(&game);
+            if matches!(frame, SyncFrame::Delta { .. }) {
+                deltas += 1;
+            }
+            assert_eq!(SyncFrame::parse(&frame.to_line()), Some(frame.clone()));
+            let synced = decoder.apply(&frame.to_line()).unwrap();
+            assert!(game.diff(synced).is_empty(), "{}", game.diff(synced));
+        }
+        assert!(deltas > KEYFRAME_INTERVAL / 2);
+    }
+
+    #[test]
+    fn test_state_sync_recovery() {
+        let mut game = GameState::new();
+        let mut encoder = SyncEncoder::default();
+        let mut decoder = SyncDecoder::default();
+
+        // A delta with nothing before it
+        encoder.encode(&game);
+        game.move_snake();
+        let delta = encoder.encode(&game).to_line();
+        assert_eq!(decoder.apply(&delta).err(), Some(SyncError::NeedKeyframe));
+        assert_eq!(decoder.apply("D 1 2").err(), Some(SyncError::Malformed));
+
+        // A receiver that drifted gets caught by the hash, then recovers from a keyframe
+        encoder.request_keyframe();
+        decoder.apply(&encoder.encode(&game).to_line()).unwrap();
+        decoder.state.as_mut().unwrap().snake.pop();
+        game.move_snake();
+        let delta = encoder.encode(&game).to_line();
+        assert_eq!(decoder.apply(&delta).err(), Some(SyncError::HashMismatch));
+        encoder.request_keyframe();
+        let synced = decoder.apply(&encoder.encode(&game).to_line()).unwrap();
+        assert!(game.diff(synced).is_empty());
+    }
+
+    #[test]
+    fn test_state_sync_decoder_survives_garbage() {
+        let mut rng = rand::thread_rng();
+        let mut game = GameState::new();
+        let mut encoder = SyncEncoder::default();
+        let mut decoder = SyncDecoder::default();
+        for _ in 0..500 {
+            game.move_snake();
+            if game.game_over {
+                game.restart();
+            }
+            let mut bytes = encoder.encode(&game).to_line().into_bytes();
+            // corrupt a random byte now and then - decoding may fail but must never panic
+            if rng.gen_bool(0.5) {
+                let index = rng.gen_range(0..bytes.len());
+                bytes[index] = rng.gen();
+            }
+            let _ = decoder.apply(&String::from_utf8_lossy(&bytes));
+        }
+    }
+
+    // A short recorded run that eats once straight away and turns a few corners
+    fn recorded_replay() -> (Replay, Vec<GameState>) {
+        let mut game = GameState::new();
+        game.food = Food::normal(game.snake[0].move_in_direction(game.direction));
+        let mut replay = Replay::start(&game);
+        let mut states = vec![game.clone()];
+        for tick in 0..30 {
+            if tick % 5 == 4 {
+                game.direction = match game.direction {
+                    Direction::Up | Direction::Down => Direction::Left,
+                    _ => Direction::Up,
+                };
+            }
+            game.move_snake();
+            replay.record_tick(&game);
+            if tick == 0 {
+                // keep the next food out of the snake's way so it only eats once
+                game.food = Food::normal(Position::new(0, GRID_HEIGHT - 1));
+                *replay.food.last_mut().unwrap() = game.food.position;
+            }
+            states.push(game.clone());
+        }
+        (replay, states)
+    }
+
+    #[test]
+    fn test_determinism_audit() {
+        // a whole bot game, recorded the way the game records runs
+        let mut game = GameState::new();
+        let mut replay = Replay::start(&game);
+        play_headless(&mut game, |_, game| replay.record_tick(game));
+        let report = replay.audit_determinism();
+        assert_eq!(report.divergence, None, "{}", report);
+        assert_eq!(report.ticks, replay.tick_count());
+
+        let report = AuditReport::compare(&[1, 2, 3], &[1, 2, 4]);
+        assert_eq!(report.ticks, 2);
+        assert_eq!(report.divergence, Some((3, 3, 4)));
+        assert_eq!(
+            AuditReport::compare(&[1, 2], &[1]).divergence,
+            Some((2, 2, 0))
+        );
+    }
+
+    #[test]
+    fn test_frame_profiler() {
+        use std::time::{Duration, Instant};
+        let mut profiler = FrameProfiler::default();
+        let start = Instant::now();
+        // 99 smooth frames and one stutter, when the tick took 30ms
+        for frame in 0..=100u64 {
+            profiler.record(FramePhase::Render, Duration::from_millis(3));
+            if frame == 50 {
+                profiler.record(FramePhase::Tick, Duration::from_millis(30));
+            }
+            profiler.end_frame(start + Duration::from_millis(16 * frame));
+        }
+        assert_eq!(profiler.frames.len(), 100);
+        assert_eq!(profiler.phase(FramePhase::Render).mean_ms(), 3.0);
+        let tick = profiler.phase(FramePhase::Tick);
+        assert_eq!(tick.percentile_ms(0.5), 0.0);
+        assert_eq!(tick.max_ms(), 30.0);
+        assert_eq!(tick.histogram(), [100, 0, 0, 0, 0, 1, 0]);
+
+        let json = profiler.to_json();
+        assert!(
+            json.starts_with("{\"schema\":1,\"frames\":100,"),
+            "{}",
+            json
+        );
+        assert!(json.contains("\"tick\":{\"mean_ms\":0.297,"), "{}", json);
+        assert_eq!(profiler.summary_lines().len(), 1 + FramePhase::ALL.len());
+
+        // only the last PROFILE_WINDOW frames are kept
+        for _ in 0..PROFILE_WINDOW {
+            profiler.end_frame(Instant::now());
+        }
+        assert_eq!(profiler.phase(FramePhase::Tick).max_ms(), 0.0);
+        assert_eq!(profiler.frames.len(), PROFILE_WINDOW);
+
+        // F3 shows the overlay, and input handling gets timed
+        let mut recorder = ReplayRecorder::new(GameState::new());
+        drive(&mut recorder, &[SyntheticInput::Press(KeyCode::F3)]);
+        assert!(recorder.show_debug);
+        recorder.profiler.end_frame(Instant::now());
+        assert_eq!(recorder.profiler.phase(FramePhase::Input).len(), 1);
+    }
+
+    #[test]
+    fn test_memory_caps() {
+        let caps = MemoryCaps::parse("replay_ticks = 5000 # about ten minutes\nbrowser_mib = 4\n");
+        assert_eq!(
+            caps,
+            Ok(MemoryCaps {
+                replay_ticks: 5000,
+                browser_bytes: 4 * 1024 * 1024,
+            })
+        );
+        assert!(MemoryCaps::parse("replay_ticks = 10").is_err());
+        assert!(MemoryCaps::parse("particles = 10").is_err());
+        assert_eq!(format_bytes(2048), "2.0 KiB");
+
+        // a bot game, trimmed to its last few hundred ticks, plays back the same from there
+        let replay = loop {
+            let mut game = GameState::new();
+            let mut replay = Replay::start(&game);
+            play_headless(&mut game, |_, game| replay.record_tick(game));
+            // long enough to have a few keyframes to cut at
+            if replay.tick_count() > 300 {
+                break replay;
+            }
+        };
+        let full = replay.states();
+        let mut trimmed = replay.clone();
+        let dropped = trimmed.trim_to(150);
+        assert!(dropped > 0 && trimmed.tick_count() <= 150);
+        assert!(trimmed.heap_bytes() < replay.heap_bytes());
+        let played = trimmed.states();
+        assert_eq!(played.len(), full.len() - dropped);
+        for (played, recorded) in played.iter().zip(&full[dropped..]) {
+            assert_eq!(played.snake, recorded.snake);
+            assert_eq!(played.score, recorded.score);
+            assert_eq!(played.food, recorded.food);
+        }
+        assert_eq!(
+            Replay::from_bytes(&trimmed.to_bytes(false)),
+            Ok(trimmed.clone())
+        );
+        assert_eq!(trimmed.clone().trim_to(150), 0);
+
+        // the browser keeps the newest replays that fit
+        let mut browser = ReplayBrowser::new(
+            vec![trimmed.clone(), replay.clone(), replay],
+            GameConfig::default(),
+        );
+        browser.selected = 2;
+        browser.evict_to(trimmed.heap_bytes() + 1);
+        assert_eq!(browser.replays.len(), 1);
+        assert_eq!(browser.left_on_disk, 2);
+        assert_eq!(browser.selected, 0);
+    }
+
+    #[test]
+    fn test_replay_round_trip() {
+        let (replay, _) = recorded_replay();
+        let bytes = replay.to_bytes(false);
+        assert_eq!(&bytes[..4], b"SNKR");
+        assert_eq!(Replay::from_bytes(&bytes), Ok(replay.clone()));
+
+        // 30 ticks of directions fit in 8 bytes
+        let directions_start = bytes.len() - 8 - 4 - 8; // before the keyframe count and checksum
+        assert_eq!(
+            bytes[directions_start - 4..directions_start],
+            30u32.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn test_replay_keeps_its_board() {
+        // a small board with wrapping walls, run off the right edge and back in
+        let mut config = GameConfig::new(12, 10, CELL_SIZE).unwrap();
+        config.walls = WallMode::Wrap;
+        let mut game = GameState::new_with_config(config);
+        game.food = Food::normal(Position::new(0, 0));
+        let mut replay = Replay::start(&game);
+        for _ in 0..10 {
+            game.move_snake();
+            replay.record_tick(&game);
+        }
+        assert!(!game.game_over);
+
+        let loaded = Replay::from_bytes(&replay.to_bytes(false)).unwrap();
+        assert_eq!(loaded.config.grid(), (12, 10));
+        assert_eq!(loaded.config.walls, WallMode::Wrap);
+        let end = loaded.states().pop().unwrap();
+        assert_eq!(end.config.grid(), (12, 10));
+        assert_eq!(end.snake, game.snake);
+        assert!(!end.game_over);
+    }
+
+    #[test]
+    fn test_replay_playback() {
+        let (replay, states) = recorded_replay();
+        let played = replay.states();
+        assert_eq!(played.len(), states.len());
+        for (played, recorded) in played.iter().zip(&states) {
+            assert_eq!(played.snake, recorded.snake);
+            assert_eq!(played.score, recorded.score);
+        }
+    }
+
+    #[test]
+    fn test_replay_corruption() {
+        let (replay, _) = recorded_replay();
+        let bytes = replay.to_bytes(false);
+
+        assert_eq!(Replay::from_bytes(b"nope"), Err(ReplayError::NotAReplay));
+        let mut future = bytes.clone();
+        future[4] = REPLAY_VERSION + 1;
+        assert_eq!(
+            Replay::from_bytes(&future),
+            Err(ReplayError::UnsupportedVersion(REPLAY_VERSION + 1))
+        );
+
+        // Any flipped bit or missing byte is caught, never a panic
+        for index in 6..bytes.len() {
+            let mut flipped = bytes.clone();
+            flipped[index] ^= 0x10;
+            assert!(Replay::from_bytes(&flipped).is_err(), "flip at {}", index);
+            assert!(Replay::from_bytes(&bytes[..index]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_replay_player() {
+        let (mut replay, states) = recorded_replay();
+        replay.mode = "coop".to_string();
+        assert_eq!(
+            Replay::from_bytes(&replay.to_bytes(false)).unwrap().mode,
+            "coop"
+        );
+
+        let mut player = ReplayPlayer::new(&replay);
+        assert_eq!(player.food_ticks, vec![1]); // the food right in front of the snake
+
+        // One tick takes the run's own game speed, half as long at 2x
+        player.advance(states[0].speed.seconds_per_cell() / 2.0);
+        assert_eq!(player.tick(), 0);
+        player.cycle_speed();
+        player.advance(states[0].speed.seconds_per_cell() / 4.0);
+        assert_eq!(player.tick(), 1);
+
+        player.paused = true;
+        player.advance(100.0);
+        assert_eq!(player.tick(), 1);
+        player.paused = false;
+        player.advance(100.0);
+        assert!(player.is_finished());
+        assert_eq!(player.current().snake, states[states.len() - 1].snake);
+
+        player.seek_previous_food();
+        assert_eq!(player.tick(), 1);
+        player.seek_previous_food();
+        assert_eq!(player.tick(), 0);
+        player.seek_next_food();
+        assert_eq!(player.tick(), 1);
+        player.scrub(1.0);
+        assert!(player.is_finished());
+
+        player.cycle_speed();
+        player.cycle_speed();
+        assert_eq!(player.speed, 1.0);
+    }
+
+    #[test]
+    fn test_replay_keyframe_seeking() {
+        // A long run going round in a loop, long enough for a few keyframes
+        let mut game = GameState::new();
+        let mut replay = Replay::start(&game);
+        let mut states = vec![game.clone()];
+        let turns = [
+            Direction::Down,
+            Direction::Left,
+            Direction::Up,
+            Direction::Right,
+        ];
+        for tick in 0..350 {
+            if tick % 3 == 2 {
+                game.direction = turns[(tick / 3) % 4];
+            }
+            game.move_snake();
+            replay.record_tick(&game);
+            states.push(game.clone());
+        }
+        assert!(!game.game_over);
+        assert_eq!(replay.keyframes.len(), 350 / REPLAY_KEYFRAME_INTERVAL);
+
+        let replay = Replay::from_bytes(&replay.to_bytes(false)).unwrap();
+        for tick in [0, 1, 99, 100, 101, 250, 350, 9999] {
+            let cursor = replay.seek(tick);
+            let expected = &states[tick.min(350)];
+            assert!(expected.diff(&cursor.game).is_empty(), "tick {}", tick);
+        }
+
+        // The keyframe is used rather than replaying from the start
+        assert_eq!(replay.keyframes[2].tick, 300);
+        let mut bent = replay.clone();
+        bent.keyframes[2].score = 77;
+        assert_eq!(bent.seek(320).game.score, 77);
+    }
+
+    #[test]
+    fn test_reads_version_1_replays() {
+        // Version 1 was the same up to the directions, without keyframes
+        let (mut replay, _) = recorded_replay();
+        replay.keyframes.clear();
+        let mut bytes = replay.to_bytes(false);
+        bytes.truncate(bytes.len() - 8 - 4); // checksum and keyframe count
+        bytes.remove(6 + 8); // the RNG after the seed, from version 4
+                             // the walls after the board size, from version 5
+        bytes.remove(6 + 8 + 8 + 1 + replay.mode.len() + 4 + 8 + 2);
+        let checksum = fnv1a(&bytes[6..]);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes[4] = 1;
+        assert_eq!(Replay::from_bytes(&bytes), Ok(replay));
+    }
+
+    #[test]
+    fn test_ascii_board() {
+        let mut game = GameState::new();
+        game.food = Food::normal(Position::new(0, 0));
+        let board = ascii_board(&game);
+        let lines: Vec<&str> = board.lines().collect();
+        assert_eq!(lines.len(), GRID_HEIGHT as usize + 2);
+        assert_eq!(lines[0], "#".repeat(GRID_WIDTH as usize + 2));
+        assert_eq!(
+            lines[1],
+            format!("#*{}#", " ".repeat(GRID_WIDTH as usize - 1))
+        );
+        assert_eq!(&lines[8][9..13], "oo@ "); // row 7, the snake heading right
+    }
+
+    #[test]
+    fn test_replay_storyboard() {
+        let (replay, _) = recorded_replay();
+        let storyboard = replay.storyboard(10);
+        let headings: Vec<&str> = storyboard
+            .lines()
+            .filter(|line| line.starts_with("tick"))
+            .collect();
+        assert_eq!(headings[0], "tick 0 - start");
+        assert_eq!(headings[1], "tick 1 - ate food, score 10");
+        assert_eq!(headings[2], "tick 10");
+        assert!(headings.last().unwrap().ends_with("crashed with 10 points"));
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01 00:00");
+        assert_eq!(format_date(951_827_696), "2000-02-29 12:34");
+        assert_eq!(format_date(1_792_142_550), "2026-10-16 09:22");
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_replay_compression() {
+        let (mut replay, _) = recorded_replay();
+        replay.directions = vec![Direction::Right; 4000];
+        let compressed = replay.to_bytes(true);
+        assert!(compressed.len() < replay.to_bytes(false).len() / 4);
+        assert_eq!(Replay::from_bytes(&compressed), Ok(replay));
+    }
+
+    #[test]
+    fn test_text_input() {
+        let mut name = TextInput::new(CharFilter::Name, 5);
+        assert!(name.insert('A'));
+        assert!(!name.insert('!')); // filtered out
+        for c in "bcdef".chars() {
+            name.insert(c);
+        }
+        assert_eq!(name.value(), "Abcde"); // capped at five
+        assert!(name.move_cursor(-2));
+        assert!(name.backspace());
+        assert_eq!(name.display(), "Ab|de");
+        assert!(name.move_cursor(-9));
+        assert_eq!(name.cursor, 0);
+        assert!(!name.backspace());
+        assert!(!name.move_cursor(-1));
+
+        // picking cycles the character after the cursor, or adds one at the end
+        assert!(name.pick(true));
+        assert_eq!(name.value(), "Bbde");
+        assert!(name.pick(false) && name.pick(false));
+        assert_eq!(name.value(), ".bde"); // wrapped round to the end of the alphabet
+        name.move_cursor(9);
+        assert!(name.pick(true));
+        assert_eq!(name.value(), ".bdeA"); // the cursor stays on the new character
+        assert!(name.pick(true));
+        assert_eq!(name.value(), ".bdeB");
+        name.move_cursor(1);
+        assert!(!name.pick(true)); // full
+
+        let mut code = TextInput::new(CharFilter::RoomCode, 4).with_text("abio");
+        assert_eq!(code.value(), "AB"); // upper-cased, and I and O aren't in room codes
+        let mut seed = TextInput::new(CharFilter::Digits, 20).with_text("12a3");
+        assert_eq!(seed.value(), "123");
+        assert!(!code.insert(' ') && !seed.insert('-'));
+    }
+
+    #[test]
+    fn test_hotseat_name_entry() {
+        let mut hotseat = Hotseat::with_name_entry();
+        assert!(hotseat.naming.as_ref().unwrap().is_typing());
+        // rename P1 to Ann, clear P2 and fill in the third name on the pad
+        for _ in 0..2 {
+            hotseat.naming_input(UiInput::Erase);
+        }
+        for c in "Ann".chars() {
+            hotseat.naming_input(UiInput::Type(c));
+        }
+        hotseat.naming_input(UiInput::Down);
+        hotseat.naming_input(UiInput::Erase);
+        hotseat.naming_input(UiInput::Erase);
+        hotseat.naming_input(UiInput::Down);
+        hotseat.naming_input(UiInput::PickNext);
+        hotseat.naming_input(UiInput::Right);
+        hotseat.naming_input(UiInput::PickNext);
+        hotseat.naming_input(UiInput::PickNext);
+        assert!(hotseat.naming.is_some());
+
+        hotseat.naming_input(UiInput::Accept);
+        assert!(hotseat.naming.is_none());
+        assert_eq!(hotseat.players, vec!["Ann".to_string(), "AB".to_string()]);
+        assert_eq!(hotseat.current_player(), Some("Ann"));
+
+        // a name the filter turns down keeps the players on the name entry
+        let mut hotseat = Hotseat::with_name_entry();
+        hotseat.name_filter.add_words("ann");
+        for _ in 0..2 {
+            hotseat.naming_input(UiInput::Erase);
+        }
+        for c in "Ann".chars() {
+            hotseat.naming_input(UiInput::Type(c));
+        }
+        hotseat.naming_input(UiInput::Accept);
+        let naming = hotseat.naming.as_ref().expect("still naming");
+        assert!(matches!(
+            naming.widgets.last(),
+            Some(Widget::Label { text, .. }) if text == "Player 1: that name isn't allowed"
+        ));
+    }
+
+    #[test]
+    fn test_name_filter() {
+        let mut filter = NameFilter::default();
+        assert_eq!(filter.check("  Ann   Lee ").as_deref(), Ok("Ann Lee"));
+        assert_eq!(filter.check("   ").unwrap_err(), "a name can't be blank");
+        assert_eq!(
+            filter.check("Bartholomew Smith").unwrap_err(),
+            "names can be at most 12 characters"
+        );
+        assert_eq!(
+            filter.check("Zoë").unwrap_err(),
+            "`ë` can't be used in a name"
+        );
+        for rude in ["shit", "SH1T", "s.h.i.t", "bullsh1t-99"] {
+            assert!(filter.check(rude).is_err(), "{}", rude);
+        }
+        assert!(filter.check("Dickens").is_err()); // blunt, but it's only a basic filter
+
+        filter.add_words("# my list\nSn4ke\n\n");
+        assert!(filter.blocked.contains(&"snake".to_string()));
+        assert!(filter.check("Snake Lord").is_err());
+        let input = TextInput::new(CharFilter::Name, 20).with_text("Ann");
+        assert_eq!(input.name(&filter).as_deref(), Ok("Ann"));
+    }
+
+    #[test]
+    fn test_hotseat_turns() {
+        let mut hotseat = Hotseat::new(vec!["Ann".to_string(), "Bob".to_string()]);
+        assert_eq!(hotseat.current_player(), Some("Ann"));
+
+        // Can't hand over mid-run
+        hotseat.advance();
+        assert_eq!(hotseat.current_player(), Some("Ann"));
+
+        hotseat.game.score = 30;
+        hotseat.game.game_over = true;
+        hotseat.record_turn();
+        hotseat.record_turn(); // only counted once
+        assert_eq!(hotseat.results.len(), 1);
+        assert_eq!(hotseat.current_player(), Some("Bob"));
+
+        hotseat.advance();
+        assert!(!hotseat.game.game_over);
+        assert_eq!(hotseat.game.score, 0);
+
+        hotseat.game.score = 50;
+        hotseat.game.game_over = true;
+        hotseat.record_turn();
+        assert!(hotseat.is_finished());
+
+        // Best score first
+        let standings = hotseat.standings();
+        assert_eq!(standings[0].player, "Bob");
+        assert_eq!(standings[1].player, "Ann");
+
+        // Enter after the results starts a new session
+        hotseat.advance();
+        assert!(hotseat.results.is_empty());
+        assert_eq!(hotseat.current_player(), Some("Ann"));
+    }
+
+    #[test]
+    fn test_dual_game_board_layout() {
+        let config = GameConfig::default();
+        let board_width = GRID_WIDTH as f32 * CELL_SIZE;
+        assert_eq!(DualGame::board_offset(&config, 0), 0.0);
+        assert_eq!(DualGame::board_offset(&config, 1), board_width + CELL_SIZE);
+        assert_eq!(
+            DualGame::window_width(&config),
+            board_width * 2.0 + CELL_SIZE
+        );
+
+        // Bigger boards spread the window out to fit
+        let bigger = GameConfig::new(30, 20, 20.0).unwrap();
+        let dual = DualGame::new_match(MatchSettings {
+            config: bigger,
+            ..MatchSettings::new(100)
+        });
+        assert_eq!(dual.boards[1].config, bigger);
+        assert_eq!(DualGame::board_offset(dual.config(), 1), 620.0);
+        assert_eq!(DualGame::window_width(dual.config()), 1220.0);
+    }
+
+    #[test]
+    fn test_idle_pulse_stays_in_range() {
+        for step in 0..100 {
+            let anim_time = step as f64 * 0.1;
+            for index in 0..10 {
+                let pulse = idle_pulse(anim_time, index);
+                assert!((0.0..=1.0).contains(&pulse));
+            }
+        }
+
+        // Neighbouring segments shouldn't pulse in lockstep
+        assert_ne!(idle_pulse(1.0, 0), idle_pulse(1.0, 1));
+    }
+
+    #[test]
+    fn test_is_blinking() {
+        assert!(!is_blinking(0.0));
+        assert!(!is_blinking(1.5));
+        assert!(is_blinking(2.9));
+        assert!(!is_blinking(3.1));
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0.0), "0:00");
+        assert_eq!(format_time(9.7), "0:09");
+        assert_eq!(format_time(75.0), "1:15");
+        assert_eq!(format_time(600.0), "10:00");
+    }
+
     #[test]
     fn test_high_score_starts_at_zero() {
         // This test verifies that high score starts at 0 when no file exists
@@ -1,6 +1,338 @@
-use create_rust_snake_game::run_game;
+use create_rust_snake_game::{
+    bench_bodies, bench_table, default_storage, discover_levels, discover_packs, play_headless,
+    reset_save_data, run_coop_game, run_dual_game, run_game, run_ghost_race, run_hotseat_game,
+    run_kiosk, run_level_editor, run_level_game, run_online_game, run_pack_browser, run_pack_game,
+    run_replay_browser, run_screensaver, run_versus_game, set_data_dir, set_server, BenchWorkload,
+    Difficulty, GameState, LaunchOptions, Level, NameFilter, NetMode, OnlineGame, PackArchive,
+    PeerRoute, Replay, SaveArchive, Thumbnail, DIFFICULTY_GAMES, LEVELS_DIR, MODS_DIR,
+    NAME_BLOCKLIST_FILE, NET_PORT, PACK_ARCHIVE_EXTENSION, SAVE_ARCHIVE_EXTENSION,
+};
+
+// Score to race to in local versus
+const VERSUS_TARGET_SCORE: u32 = 200;
+// Bot games played for `bench`, and how many times each backend goes through them
+const BENCH_GAMES: usize = 20;
+const BENCH_ROUNDS: usize = 5;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    run_game()?;
+    // --dual plays the two-board challenge, --versus is two players on split screen,
+    // --coop is two players sharing one snake, --hotseat is players taking turns,
+    // --host and --join play online (versus, or co-op with --coop too), --spectate watches,
+    // --name says who you are
+    // and --relay with a room code gets through when the host can't be reached directly,
+    // --ghost races a recorded run, --replays watches saved ones and --storyboard prints one,
+    // --audit plays a replay twice under different conditions to check it comes out the same,
+    // --packs switches content packs on and off, --pack plays with just one of them,
+    // --export-pack and --import-pack share them as single files,
+    // --export-save, --import-save and --reset-save move or wipe the player's data,
+    // --screensaver has a bot play fullscreen until there's any input,
+    // --kiosk runs as an arcade cabinet with coins and credits,
+    // --levels plays through the levels in levels/ one after another,
+    // --tui plays in the terminal instead of a window (a build with --features tui),
+    // --thumbnail saves a picture of a level, or of how a replay ended, as a PNG,
+    // --random-level plays a generated level, from a seed if one's given,
+    // --rate says how hard a level looks and why, --edit opens a level in the editor
+    // `bench` times ways of keeping the snake's body on the bot's games, for --grid and --seed.
+    // --headless has the bot play one game with no window and prints how it went, as
+    // text or --output json|csv, with a line per tick too given --per-tick.
+    // SNAKE_* environment variables set the same things as their flags - see LaunchOptions
+    let args: Vec<String> = std::env::args().collect();
+    let options = LaunchOptions::load(&args)?;
+    if let Some(dir) = &options.data_dir {
+        std::fs::create_dir_all(dir)?;
+        set_data_dir(dir.clone());
+    }
+    if let Some(server) = &options.server {
+        set_server(server.clone());
+    }
+
+    if args.get(1).map(String::as_str) == Some("bench") {
+        // the same bot games every time, so results from two machines can be compared
+        let workload = BenchWorkload::record(
+            options.game_config(),
+            options.seed.unwrap_or(1),
+            BENCH_GAMES,
+        );
+        print!(
+            "{}",
+            bench_table(&workload, &bench_bodies(&workload, BENCH_ROUNDS))
+        );
+    } else if options.headless {
+        let mut game = options.new_game();
+        // out of reach, so the bot's run never replaces the player's records
+        game.high_score = u32::MAX;
+        game.best_time = f64::MAX;
+        let output = options.output;
+        if let Some(header) = output.header() {
+            println!("{}", header);
+        }
+        let ticks = play_headless(&mut game, |tick, game| {
+            if options.per_tick {
+                println!("{}", output.tick_line(tick, game));
+            }
+        });
+        println!("{}", output.summary_line(ticks, &game));
+    } else if args.iter().any(|arg| arg == "--screensaver") {
+        run_screensaver()?;
+    } else if args.iter().any(|arg| arg == "--kiosk") {
+        run_kiosk()?;
+    } else if args.iter().any(|arg| arg == "--dual") {
+        run_dual_game(options.game_config())?;
+    } else if args.iter().any(|arg| arg == "--versus") {
+        // optional player names after --versus, for the ratings
+        let mut names = checked_names(names_after(&args, "--versus"))?.into_iter();
+        let player_names = [
+            names.next().unwrap_or_else(|| "P1".to_string()),
+            names.next().unwrap_or_else(|| "P2".to_string()),
+        ];
+        run_versus_game(options.game_config(), VERSUS_TARGET_SCORE, player_names)?;
+    } else if args.iter().any(|arg| arg == "--host") {
+        // --host [port], and --relay <address> to take players through a relay too
+        let port = match names_after(&args, "--host").first() {
+            Some(port) => port.parse()?,
+            None => NET_PORT,
+        };
+        let relay = names_after(&args, "--relay").into_iter().next();
+        let name = net_player_name(&args, "Host")?;
+        let mut online = OnlineGame::host(
+            net_mode(&args),
+            options.game_config(),
+            &name,
+            port,
+            relay.as_deref(),
+        )?;
+        // --input-delay <steps> fixes how long our turns wait, instead of following the ping
+        if let Some(delay) = names_after(&args, "--input-delay").first() {
+            online.session.set_input_delay(Some(delay.parse()?));
+        }
+        if let Some(room) = online.room_code() {
+            println!("Room code: {}", room);
+        }
+        run_online_game(online)?;
+    } else if let Some(flag) = ["--join", "--spectate"]
+        .into_iter()
+        .find(|flag| args.iter().any(|arg| arg == flag))
+    {
+        // --join [host address], and/or --relay <address> --room <code> - the same for --spectate
+        let route = PeerRoute {
+            direct: names_after(&args, flag).into_iter().next(),
+            relay: names_after(&args, "--relay")
+                .into_iter()
+                .next()
+                .zip(names_after(&args, "--room").into_iter().next()),
+        };
+        if route == PeerRoute::default() {
+            return Err(format!("{} needs the host's address, or --relay and --room", flag).into());
+        }
+        let (mode, config) = (net_mode(&args), options.game_config());
+        let online = if flag == "--spectate" {
+            OnlineGame::spectate(mode, config, &net_player_name(&args, "Viewer")?, route)?
+        } else {
+            OnlineGame::join(mode, config, &net_player_name(&args, "Guest")?, route)?
+        };
+        run_online_game(online)?;
+    } else if args.iter().any(|arg| arg == "--hotseat") {
+        // with no names given, the players type them in first
+        run_hotseat_game(
+            options.game_config(),
+            checked_names(names_after(&args, "--hotseat"))?,
+        )?;
+    } else if args.iter().any(|arg| arg == "--ghost") {
+        // a friend's ghost file, or your own last run
+        let ghost = names_after(&args, "--ghost");
+        run_ghost_race(options.game_config(), ghost.first().map(String::as_str))?;
+    } else if args.iter().any(|arg| arg == "--storyboard") {
+        // --storyboard <replay file> [ticks between frames]
+        let options = names_after(&args, "--storyboard");
+        let path = options.first().ok_or("--storyboard needs a replay file")?;
+        let every = options.get(1).map_or(Ok(10), |every| every.parse())?;
+        print!("{}", Replay::load(path)?.storyboard(every));
+    } else if args.iter().any(|arg| arg == "--audit") {
+        // --audit [replay file] - without one, a fresh bot game gets audited
+        let options = names_after(&args, "--audit");
+        let replay = match options.first() {
+            Some(path) => Replay::load(path)?,
+            None => {
+                let mut game = GameState::new();
+                game.high_score = u32::MAX;
+                game.best_time = f64::MAX;
+                let mut replay = Replay::start(&game);
+                play_headless(&mut game, |_, game| replay.record_tick(game));
+                replay
+            }
+        };
+        let report = replay.audit_determinism();
+        if report.divergence.is_some() {
+            return Err(report.to_string().into());
+        }
+        println!("{}", report);
+    } else if args.iter().any(|arg| arg == "--thumbnail") {
+        // --thumbnail <level .toml or replay file> [png file] [pixels to a cell]
+        let options = names_after(&args, "--thumbnail");
+        let path = options
+            .first()
+            .ok_or("--thumbnail needs a level or replay file")?;
+        let cell_size = options.get(2).map_or(Ok(8), |size| size.parse())?;
+        let thumbnail = if path.ends_with(".toml") {
+            Level::load(std::path::Path::new(path))?.thumbnail(cell_size)
+        } else {
+            let end = Replay::load(path)?
+                .states()
+                .pop()
+                .ok_or("the replay is empty")?;
+            Thumbnail::of(&end, cell_size)
+        };
+        let file = options
+            .get(1)
+            .cloned()
+            .unwrap_or_else(|| format!("{}.png", path));
+        thumbnail.save_png(&file)?;
+        println!("Saved {}", file);
+    } else if args.iter().any(|arg| arg == "--replays") {
+        run_replay_browser(options.game_config())?;
+    } else if args.iter().any(|arg| arg == "--export-pack") {
+        // --export-pack <pack name> [file]
+        let options = names_after(&args, "--export-pack");
+        let name = options.first().ok_or("--export-pack needs a pack name")?;
+        let (packs, _) = discover_packs(MODS_DIR);
+        let pack = packs
+            .iter()
+            .find(|pack| pack.manifest.name == *name)
+            .ok_or_else(|| format!("no pack called `{}` in {}/", name, MODS_DIR))?;
+        let file = options
+            .get(1)
+            .cloned()
+            .unwrap_or_else(|| format!("{}.{}", name, PACK_ARCHIVE_EXTENSION));
+        PackArchive::from_dir(&pack.dir)?.save(&file)?;
+        println!("Exported {} to {}", name, file);
+    } else if args.iter().any(|arg| arg == "--import-pack") {
+        let options = names_after(&args, "--import-pack");
+        let file = options.first().ok_or("--import-pack needs a pack file")?;
+        let dir = PackArchive::load(file)?.install(MODS_DIR)?;
+        println!("Installed {} to {}", file, dir.display());
+    } else if args.iter().any(|arg| arg == "--export-save") {
+        // --export-save [file]
+        let options = names_after(&args, "--export-save");
+        let file = options
+            .first()
+            .cloned()
+            .unwrap_or_else(|| format!("snake.{}", SAVE_ARCHIVE_EXTENSION));
+        let archive = SaveArchive::collect(default_storage().as_ref());
+        archive.save(&file)?;
+        println!("Exported {} files to {}", archive.files.len(), file);
+    } else if args.iter().any(|arg| arg == "--import-save") {
+        let options = names_after(&args, "--import-save");
+        let file = options.first().ok_or("--import-save needs a save file")?;
+        let restored = SaveArchive::load(file)?.restore(default_storage().as_ref())?;
+        println!("Imported {} files from {}", restored, file);
+    } else if args.iter().any(|arg| arg == "--reset-save") {
+        // there's no undo, so make sure
+        println!("Erase every score, setting and replay? This can't be undone. Type `yes`:");
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if answer.trim() != "yes" {
+            println!("Nothing was erased");
+            return Ok(());
+        }
+        let removed = reset_save_data(default_storage().as_ref())?;
+        println!("Erased {} files", removed);
+    } else if args.iter().any(|arg| arg == "--pack") {
+        let (packs, problems) = discover_packs(MODS_DIR);
+        for problem in &problems {
+            eprintln!("{}", problem);
+        }
+        let name = names_after(&args, "--pack")
+            .into_iter()
+            .next()
+            .ok_or("--pack needs a pack name")?;
+        let pack = packs
+            .iter()
+            .find(|pack| pack.manifest.name == name)
+            .ok_or_else(|| format!("no pack called `{}` in {}/", name, MODS_DIR))?;
+        run_pack_game(options.game_config(), pack)?;
+    } else if args.iter().any(|arg| arg == "--packs") {
+        run_pack_browser(options.game_config())?;
+    } else if args.iter().any(|arg| arg == "--levels") {
+        let (levels, problems) = discover_levels(LEVELS_DIR);
+        for problem in problems {
+            eprintln!("{}", problem);
+        }
+        if levels.is_empty() {
+            return Err(format!("no levels to play in {}/", LEVELS_DIR).into());
+        }
+        run_level_game(levels)?;
+    } else if args.iter().any(|arg| arg == "--rate") {
+        let options = names_after(&args, "--rate");
+        let path = options.first().ok_or("--rate needs a level file")?;
+        let level = Level::load(std::path::Path::new(path))?;
+        println!("{}", Difficulty::estimate(&level, DIFFICULTY_GAMES));
+    } else if args.iter().any(|arg| arg == "--edit") {
+        let options = names_after(&args, "--edit");
+        let path = options.first().ok_or("--edit needs a level file")?;
+        run_level_editor(path)?;
+    } else if args.iter().any(|arg| arg == "--random-level") {
+        let seed = match names_after(&args, "--random-level").first() {
+            Some(seed) => seed.parse()?,
+            None => options.seed.unwrap_or_else(|| {
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |since| since.as_nanos() as u64)
+            }),
+        };
+        run_level_game(vec![Level::generate(seed, options.game_config())])?;
+    } else if args.iter().any(|arg| arg == "--coop") {
+        run_coop_game(options.game_config())?;
+    } else if args.iter().any(|arg| arg == "--tui") {
+        run_tui(&options)?;
+    } else {
+        run_game(&options)?;
+    }
     Ok(())
 }
+
+#[cfg(feature = "tui")]
+fn run_tui(options: &LaunchOptions) -> Result<(), Box<dyn std::error::Error>> {
+    Ok(create_rust_snake_game::run_game_tui(options)?)
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_tui(_: &LaunchOptions) -> Result<(), Box<dyn std::error::Error>> {
+    Err("--tui needs a build with --features tui".into())
+}
+
+// Online matches are versus unless --coop is given too
+fn net_mode(args: &[String]) -> NetMode {
+    if args.iter().any(|arg| arg == "--coop") {
+        NetMode::Coop
+    } else {
+        NetMode::Versus
+    }
+}
+
+// The name the other player sees, from --name
+fn net_player_name(args: &[String], default: &str) -> Result<String, String> {
+    let names = checked_names(names_after(args, "--name"))?;
+    Ok(names
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| default.to_string()))
+}
+
+// Names from the command line go through the same filter as typed ones
+fn checked_names(names: Vec<String>) -> Result<Vec<String>, String> {
+    let filter = NameFilter::load(NAME_BLOCKLIST_FILE);
+    names
+        .iter()
+        .map(|name| filter.check(name).map_err(|e| format!("`{}`: {}", name, e)))
+        .collect()
+}
+
+// Arguments given straight after a flag, e.g. the names in `--hotseat Ann Bob`
+fn names_after(args: &[String], flag: &str) -> Vec<String> {
+    args.iter()
+        .skip_while(|arg| *arg != flag)
+        .skip(1)
+        .take_while(|arg| !arg.starts_with("--"))
+        .cloned()
+        .collect()
+}
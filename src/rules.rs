@@ -0,0 +1,492 @@
+//! The bare rules of snake, with no std, graphics or files - just `alloc` - so they can
+//! run on a microcontroller driving a small screen: the board, moving and growing the
+//! snake, placing food and timing the ticks. The full game in `game` plays by these same
+//! rules. The caller brings the randomness (a [`RulesRng`]) and the clock (feeding
+//! [`TickClock::advance`]).
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+
+// Game constants
+pub const GRID_WIDTH: i32 = 20;
+pub const GRID_HEIGHT: i32 = 15;
+
+// Direction enum for snake movement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+// useful so the snake can't reverse into itself
+impl Direction {
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+// Position struct for grid coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Position {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+
+    // Move position in a given direction
+    pub fn move_in_direction(&self, direction: Direction) -> Self {
+        match direction {
+            // note coordinate system starts at top left and increases down/right in the grid
+            Direction::Up => Position::new(self.x, self.y - 1),
+            Direction::Down => Position::new(self.x, self.y + 1),
+            Direction::Left => Position::new(self.x - 1, self.y),
+            Direction::Right => Position::new(self.x + 1, self.y),
+        }
+    }
+
+    // Check if position is within bounds of a board this size
+    pub fn is_within(&self, width: i32, height: i32) -> bool {
+        self.x >= 0 && self.x < width && self.y >= 0 && self.y < height
+    }
+
+    // Positions are written "x,y" in saved files and messages
+    pub fn parse(text: &str) -> Option<Self> {
+        let (x, y) = text.split_once(',')?;
+        Some(Self::new(x.parse().ok()?, y.parse().ok()?))
+    }
+
+    pub fn to_text(&self) -> String {
+        format!("{},{}", self.x, self.y)
+    }
+}
+
+// Pixels a side for a cell on the default board
+pub const CELL_SIZE: f32 = 30.0;
+
+// The smallest and biggest boards a game can be played on, in cells a side
+pub const MIN_GRID_SIZE: (i32, i32) = (8, 6);
+pub const MAX_GRID_SIDE: i32 = 200;
+
+// What the edge of the board does to a snake that runs into it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WallMode {
+    Solid, // the run ends
+    Wrap,  // the snake comes back in on the other side
+}
+
+impl WallMode {
+    pub const ALL: [WallMode; 2] = [WallMode::Solid, WallMode::Wrap];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WallMode::Solid => "solid",
+            WallMode::Wrap => "wrap",
+        }
+    }
+}
+
+// How fast a run starts out. It speeds up the same way from there
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeedPreset {
+    Slow,
+    Normal,
+    Fast,
+}
+
+impl SpeedPreset {
+    pub const ALL: [SpeedPreset; 3] = [SpeedPreset::Slow, SpeedPreset::Normal, SpeedPreset::Fast];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SpeedPreset::Slow => "slow",
+            SpeedPreset::Normal => "normal",
+            SpeedPreset::Fast => "fast",
+        }
+    }
+
+    pub fn start(self) -> Speed {
+        match self {
+            SpeedPreset::Slow => Speed::cells_per_second(3),
+            SpeedPreset::Normal => Speed::START,
+            SpeedPreset::Fast => Speed::cells_per_second(7),
+        }
+    }
+}
+
+// The board's size in cells and how big each cell is drawn, chosen at launch, and the
+// rules the board plays by. The default is the classic 20x15 board of 30 pixel cells with
+// solid walls
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameConfig {
+    pub grid_width: i32,
+    pub grid_height: i32,
+    pub cell_size: f32,
+    pub walls: WallMode,
+    pub speed: SpeedPreset,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            grid_width: GRID_WIDTH,
+            grid_height: GRID_HEIGHT,
+            cell_size: CELL_SIZE,
+            walls: WallMode::Solid,
+            speed: SpeedPreset::Normal,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn new(grid_width: i32, grid_height: i32, cell_size: f32) -> Result<Self, String> {
+        let (min_width, min_height) = MIN_GRID_SIZE;
+        if !(min_width..=MAX_GRID_SIDE).contains(&grid_width)
+            || !(min_height..=MAX_GRID_SIDE).contains(&grid_height)
+        {
+            return Err(format!(
+                "boards go from {}x{} up to {}x{}, not {}x{}",
+                min_width, min_height, MAX_GRID_SIDE, MAX_GRID_SIDE, grid_width, grid_height
+            ));
+        }
+        if !(4.0..=100.0).contains(&cell_size) {
+            return Err(format!(
+                "cells should be 4 to 100 pixels, not {}",
+                cell_size
+            ));
+        }
+        Ok(Self {
+            grid_width,
+            grid_height,
+            cell_size,
+            ..Self::default()
+        })
+    }
+
+    pub fn contains(&self, cell: Position) -> bool {
+        cell.is_within(self.grid_width, self.grid_height)
+    }
+
+    // The cell one move from `cell`, coming back in on the far side when the walls wrap
+    pub fn step(&self, cell: Position, direction: Direction) -> Position {
+        let next = cell.move_in_direction(direction);
+        match self.walls {
+            WallMode::Solid => next,
+            WallMode::Wrap => Position::new(
+                next.x.rem_euclid(self.grid_width),
+                next.y.rem_euclid(self.grid_height),
+            ),
+        }
+    }
+
+    pub fn center(&self) -> Position {
+        Position::new(self.grid_width / 2, self.grid_height / 2)
+    }
+
+    pub fn cell_count(&self) -> usize {
+        (self.grid_width * self.grid_height) as usize
+    }
+
+    // Columns and rows, for indexing pixel buffers
+    pub fn grid(&self) -> (usize, usize) {
+        (self.grid_width as usize, self.grid_height as usize)
+    }
+
+    // Every cell on the board, a row at a time
+    pub fn cells(&self) -> impl Iterator<Item = Position> + '_ {
+        (0..self.grid_height)
+            .flat_map(move |y| (0..self.grid_width).map(move |x| Position::new(x, y)))
+    }
+
+    // Which quarter of the board a cell is in, 0 to 3
+    pub fn quadrant(&self, cell: Position) -> usize {
+        usize::from(cell.x >= self.grid_width / 2) + 2 * usize::from(cell.y >= self.grid_height / 2)
+    }
+
+    // The board's size in pixels
+    pub fn screen_width(&self) -> f32 {
+        self.grid_width as f32 * self.cell_size
+    }
+
+    pub fn screen_height(&self) -> f32 {
+        self.grid_height as f32 * self.cell_size
+    }
+}
+
+// A whole cell, in the units TickClock counts in: microseconds times millicells per second
+const TICK_CLOCK_CELL: u64 = 1_000_000_000;
+
+// The fastest a speed can be set to, far past what anyone can play
+pub const MAX_CELLS_PER_SECOND: u32 = 1000;
+
+// How fast the snake moves, in thousandths of a cell per second. Whole numbers, so ticks
+// can be counted exactly however long a run goes on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Speed {
+    pub millicells_per_second: u32,
+}
+
+impl Speed {
+    // Five moves a second at the start of a run, and never more than ten
+    pub const START: Speed = Speed::cells_per_second(5);
+    pub const MAX: Speed = Speed::cells_per_second(10);
+
+    pub const fn cells_per_second(cells: u32) -> Self {
+        Self {
+            millicells_per_second: cells * 1000,
+        }
+    }
+
+    pub fn as_cells_per_second(&self) -> f64 {
+        self.millicells_per_second as f64 / 1000.0
+    }
+
+    // The time a tick takes, for frontends that want an interval
+    pub fn seconds_per_cell(&self) -> f64 {
+        1.0 / self.as_cells_per_second().max(0.001)
+    }
+
+    // Speeds used to be stored as seconds between moves
+    pub fn from_seconds_per_cell(seconds: f64) -> Self {
+        // adding a half and truncating rounds it - f64::round needs std
+        let millicells = 1000.0 / seconds.max(0.001) + 0.5;
+        Self {
+            millicells_per_second: millicells.clamp(1.0, u32::MAX as f64) as u32,
+        }
+    }
+
+    // A bit faster after each food: 1/0.95 times the speed, up to the maximum
+    pub fn faster(self) -> Self {
+        let millicells = self.millicells_per_second as u64 * 20 / 19;
+        Self {
+            millicells_per_second: millicells.min(Speed::MAX.millicells_per_second as u64) as u32,
+        }
+        .max(self)
+    }
+
+    // A step back the other way, but never slower than a run starts
+    pub fn slower(self) -> Self {
+        let millicells = self.millicells_per_second as u64 * 19 / 20;
+        Self {
+            millicells_per_second: millicells as u32,
+        }
+        .max(Speed::START)
+        .min(self)
+    }
+
+    // "7.5 cells/s". A bare number is the old seconds between moves, and gets converted
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if let Some(cells) = text.strip_suffix("cells/s") {
+            let cells: f64 = cells
+                .trim()
+                .parse()
+                .map_err(|_| format!("expected a speed like `5 cells/s`, got `{}`", text))?;
+            if !(0.001..=MAX_CELLS_PER_SECOND as f64).contains(&cells) {
+                return Err(format!("`{}` is too slow or too fast", text));
+            }
+            return Ok(Self {
+                millicells_per_second: (cells * 1000.0 + 0.5) as u32,
+            });
+        }
+        match text.parse::<f64>() {
+            Ok(seconds) if seconds > 0.0 => Ok(Self::from_seconds_per_cell(seconds)),
+            _ => Err(format!("expected a speed like `5 cells/s`, got `{}`", text)),
+        }
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed::START
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let whole = self.millicells_per_second / 1000;
+        let fraction = self.millicells_per_second % 1000;
+        if fraction == 0 {
+            write!(f, "{} cells/s", whole)
+        } else {
+            let fraction = format!("{:03}", fraction);
+            write!(f, "{}.{} cells/s", whole, fraction.trim_end_matches('0'))
+        }
+    }
+}
+
+// Counts time towards the next tick in whole units, so nothing is lost to rounding
+// however the frames fall
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TickClock {
+    progress: u64,
+}
+
+impl TickClock {
+    // Add a frame's time, true when a tick is due. Time left over counts towards the
+    // next one, but never more than a tick's worth - a stall shouldn't bank up a burst
+    pub fn advance(&mut self, delta: Duration, speed: Speed) -> bool {
+        let micros = delta.as_micros().min(u64::MAX as u128) as u64;
+        self.progress = self
+            .progress
+            .saturating_add(micros.saturating_mul(speed.millicells_per_second as u64));
+        if self.progress < TICK_CLOCK_CELL {
+            return false;
+        }
+        self.progress = (self.progress - TICK_CLOCK_CELL).min(TICK_CLOCK_CELL - 1);
+        true
+    }
+}
+
+// Where the rules get their randomness from - a hardware RNG, a seeded PRNG, anything
+pub trait RulesRng {
+    // A number from 0 up to (not including) `bound`
+    fn below(&mut self, bound: usize) -> usize;
+}
+
+// A tiny xorshift generator for boards without an RNG of their own. Never seed it with 0
+pub struct XorShift32(pub u32);
+
+impl RulesRng for XorShift32 {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as usize % bound.max(1)
+    }
+}
+
+// What one move did to the snake
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Move {
+    Moved,
+    Ate, // the head landed on the food, and the snake grew
+    Crashed,
+}
+
+// Whether a head moving onto `cell` crashes: off a board with solid walls, into one of
+// the `blocked` cells, or into the body - bar the tail, which moves out of the way
+pub fn collides(
+    snake: &[Position],
+    blocked: &[Position],
+    config: &GameConfig,
+    cell: Position,
+) -> bool {
+    !config.contains(cell) || blocked.contains(&cell) || snake[..snake.len() - 1].contains(&cell)
+}
+
+// Move the snake a cell in `direction`, keeping the tail when the head lands on `food`.
+// A crash leaves the snake where it was
+pub fn move_snake(
+    snake: &mut Vec<Position>,
+    direction: Direction,
+    config: &GameConfig,
+    blocked: &[Position],
+    food: Position,
+) -> Move {
+    let new_head = config.step(snake[0], direction);
+    if collides(snake, blocked, config, new_head) {
+        return Move::Crashed;
+    }
+    snake.insert(0, new_head);
+    if new_head == food {
+        return Move::Ate;
+    }
+    snake.pop();
+    Move::Moved
+}
+
+// A cell clear of everything in `taken`, every one equally likely, picked in one pass over
+// the board - rerolling until one missed the snake never finished on a full board. None
+// when there's nowhere left
+pub fn free_cell(
+    taken: &[Position],
+    config: &GameConfig,
+    rng: &mut dyn RulesRng,
+) -> Option<Position> {
+    let free: Vec<Position> = config
+        .cells()
+        .filter(|cell| !taken.contains(cell))
+        .collect();
+    if free.is_empty() {
+        return None;
+    }
+    Some(free[rng.below(free.len())])
+}
+
+// Something that decides where the next piece of food goes, rolling the caller's RNG so a
+// seeded game places the same food every time. None once the snake has filled the board
+pub trait FoodSpawner {
+    fn spawn(
+        &mut self,
+        snake: &[Position],
+        config: &GameConfig,
+        rng: &mut dyn RulesRng,
+    ) -> Option<Position>;
+}
+
+// How many random spots the picky policies choose between
+const FOOD_CANDIDATES: usize = 4;
+// How many times anti-streak rerolls before it gives up (e.g. when the snake fills the board)
+const ANTI_STREAK_REROLLS: usize = 20;
+
+// Built-in food placement policies - each mode picks one
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FoodPolicy {
+    #[default]
+    Uniform, // anywhere free, all equally likely
+    FarFromHead, // the farthest from the head of a few random spots
+    NearCenter,  // the nearest to the middle of a few random spots
+    AntiStreak {
+        last_quadrant: Option<usize>,
+    }, // never the same quarter of the board twice running
+}
+
+impl FoodSpawner for FoodPolicy {
+    fn spawn(
+        &mut self,
+        snake: &[Position],
+        config: &GameConfig,
+        rng: &mut dyn RulesRng,
+    ) -> Option<Position> {
+        let mut random = || free_cell(snake, config, rng);
+        let distance = |a: Position, b: Position| (a.x - b.x).abs() + (a.y - b.y).abs();
+
+        match self {
+            FoodPolicy::Uniform => random(),
+            FoodPolicy::FarFromHead => (0..FOOD_CANDIDATES)
+                .map_while(|_| random())
+                .max_by_key(|food| distance(*food, snake[0])),
+            FoodPolicy::NearCenter => {
+                let center = config.center();
+                (0..FOOD_CANDIDATES)
+                    .map_while(|_| random())
+                    .min_by_key(|food| distance(*food, center))
+            }
+            FoodPolicy::AntiStreak { last_quadrant } => {
+                let mut food = random()?;
+                for _ in 0..ANTI_STREAK_REROLLS {
+                    if Some(config.quadrant(food)) != *last_quadrant {
+                        break;
+                    }
+                    food = random()?;
+                }
+                *last_quadrant = Some(config.quadrant(food));
+                Some(food)
+            }
+        }
+    }
+}
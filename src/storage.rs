@@ -0,0 +1,187 @@
+//! Where the game keeps small saved values, by name: the [`StorageBackend`] trait and
+//! the backends that don't need the rest of the game - files, memory, and the browser's
+//! localStorage on wasm32. Built with std (or the `storage` feature on its own), so the
+//! web backend can be checked without ggez, which doesn't build for the web.
+
+extern crate std;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+// Where small saved values live (high scores, best times, a suspended run), by name.
+// Normally files next to the game; tests and sandboxed platforms bring their own
+pub trait StorageBackend: Send + Sync {
+    fn read(&self, key: &str) -> Option<String>;
+    fn write(&self, key: &str, value: &str) -> std::io::Result<()>;
+    fn remove(&self, key: &str) -> std::io::Result<()>;
+    // Every key with a value, in no particular order
+    fn keys(&self) -> Vec<String>;
+
+    // Binary values, like replays. Backends that only hold text keep them as hex
+    fn read_bytes(&self, key: &str) -> Option<Vec<u8>> {
+        from_hex(&self.read(key)?)
+    }
+
+    fn write_bytes(&self, key: &str, value: &[u8]) -> std::io::Result<()> {
+        self.write(key, &to_hex(value))
+    }
+}
+
+// On the end of a file that's still being written - or never finished, if the game died
+const TEMPORARY_SUFFIX: &str = ".tmp";
+
+// Each key is a file in `dir`
+pub struct FileStorage {
+    pub dir: std::path::PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(dir: impl Into<std::path::PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl StorageBackend for FileStorage {
+    fn read(&self, key: &str) -> Option<String> {
+        std::fs::read_to_string(self.dir.join(key)).ok()
+    }
+
+    fn write(&self, key: &str, value: &str) -> std::io::Result<()> {
+        self.write_bytes(key, value.as_bytes())
+    }
+
+    fn remove(&self, key: &str) -> std::io::Result<()> {
+        std::fs::remove_file(self.dir.join(key))
+    }
+
+    fn keys(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| !name.ends_with(TEMPORARY_SUFFIX))
+            .collect()
+    }
+
+    fn read_bytes(&self, key: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.dir.join(key)).ok()
+    }
+
+    // Through a temporary file renamed over the old one, so a crash partway through a
+    // write leaves the last save whole
+    fn write_bytes(&self, key: &str, value: &[u8]) -> std::io::Result<()> {
+        let temporary = self.dir.join(format!("{}{}", key, TEMPORARY_SUFFIX));
+        std::fs::write(&temporary, value)?;
+        std::fs::rename(temporary, self.dir.join(key))
+    }
+}
+
+// Values kept only as long as the storage is, for tests
+#[derive(Default)]
+pub struct InMemoryStorage {
+    values: std::sync::Mutex<std::collections::HashMap<String, String>>,
+}
+
+impl StorageBackend for InMemoryStorage {
+    fn read(&self, key: &str) -> Option<String> {
+        self.values.lock().ok()?.get(key).cloned()
+    }
+
+    fn write(&self, key: &str, value: &str) -> std::io::Result<()> {
+        if let Ok(mut values) = self.values.lock() {
+            values.insert(key.to_string(), value.to_string());
+        }
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> std::io::Result<()> {
+        if let Ok(mut values) = self.values.lock() {
+            values.remove(key);
+        }
+        Ok(())
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.values
+            .lock()
+            .map(|values| values.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+// The browser's localStorage, for a web build: values survive reloads and are kept per
+// site. Keys get a prefix so they can't collide with anything else on the same page
+#[cfg(target_arch = "wasm32")]
+pub struct LocalStorage {
+    pub prefix: String,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Default for LocalStorage {
+    fn default() -> Self {
+        Self {
+            prefix: "snake_game/".to_string(),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl LocalStorage {
+    // Looked up on every call - the handle can't be shared between threads, and
+    // private browsing or a blocked site can take it away
+    fn storage() -> std::io::Result<web_sys::Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| std::io::Error::other("localStorage isn't available"))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl StorageBackend for LocalStorage {
+    fn read(&self, key: &str) -> Option<String> {
+        let key = format!("{}{}", self.prefix, key);
+        Self::storage().ok()?.get_item(&key).ok().flatten()
+    }
+
+    fn write(&self, key: &str, value: &str) -> std::io::Result<()> {
+        let key = format!("{}{}", self.prefix, key);
+        Self::storage()?
+            .set_item(&key, value)
+            .map_err(|_| std::io::Error::other("localStorage is full or blocked"))
+    }
+
+    fn remove(&self, key: &str) -> std::io::Result<()> {
+        let key = format!("{}{}", self.prefix, key);
+        Self::storage()?
+            .remove_item(&key)
+            .map_err(|_| std::io::Error::other("localStorage is blocked"))
+    }
+
+    // Only ours - the page's other keys don't have the prefix
+    fn keys(&self) -> Vec<String> {
+        let Ok(storage) = Self::storage() else {
+            return Vec::new();
+        };
+        let count = storage.length().unwrap_or(0);
+        (0..count)
+            .filter_map(|index| storage.key(index).ok().flatten())
+            .filter_map(|key| key.strip_prefix(&self.prefix).map(str::to_string))
+            .collect()
+    }
+}
+
+// Bytes as lowercase hex, two digits each, for binary kept in the text saves
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,114 @@
+//! A tiny synthesizer for retro beeps: square waves and noise shaped by an envelope,
+//! rendered to 16-bit samples and wrapped up as a WAV file, so the game has sounds even
+//! with no audio files. Like [`rules`](crate::rules) it only needs `alloc`.
+
+use crate::rules::{RulesRng, XorShift32};
+use alloc::vec::Vec;
+
+pub const SAMPLE_RATE: u32 = 22_050;
+// Every beep fades in over this long, so it starts without a click
+pub const ATTACK_SECONDS: f32 = 0.005;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wave {
+    Square,
+    Noise, // a new random level every cycle, so the pitch sets how hissy or crunchy it is
+}
+
+// One note: a wave sliding from one pitch to another, fading in quickly and then out
+// over the rest of its length
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Beep {
+    pub wave: Wave,
+    pub from_hz: f32,
+    pub to_hz: f32,
+    pub seconds: f32,
+    pub volume: f32, // 0.0 to 1.0
+}
+
+impl Beep {
+    pub fn square(from_hz: f32, to_hz: f32, seconds: f32) -> Self {
+        Self {
+            wave: Wave::Square,
+            from_hz,
+            to_hz,
+            seconds,
+            volume: 0.4,
+        }
+    }
+
+    pub fn noise(hz: f32, seconds: f32) -> Self {
+        Self {
+            wave: Wave::Noise,
+            from_hz: hz,
+            to_hz: hz,
+            seconds,
+            volume: 0.4,
+        }
+    }
+
+    pub fn volume(self, volume: f32) -> Self {
+        Self { volume, ..self }
+    }
+}
+
+// The beeps one after another, as mono samples
+pub fn render(beeps: &[Beep], sample_rate: u32) -> Vec<i16> {
+    let rate = sample_rate as f32;
+    let mut noise = XorShift32(0x2545_f491);
+    let mut samples = Vec::new();
+    for beep in beeps {
+        let count = (beep.seconds * rate) as usize;
+        let attack = ((ATTACK_SECONDS * rate) as usize).clamp(1, count.max(1));
+        let (mut phase, mut level) = (0.0f32, 0.0f32);
+        for index in 0..count {
+            let progress = index as f32 / count as f32;
+            let hz = beep.from_hz + (beep.to_hz - beep.from_hz) * progress;
+            phase += hz / rate;
+            let wrapped = phase >= 1.0;
+            if wrapped {
+                phase -= 1.0;
+            }
+            let wave = match beep.wave {
+                Wave::Square if phase < 0.5 => 1.0,
+                Wave::Square => -1.0,
+                Wave::Noise => {
+                    if wrapped || index == 0 {
+                        level = noise.below(2001) as f32 / 1000.0 - 1.0;
+                    }
+                    level
+                }
+            };
+            let envelope = if index < attack {
+                index as f32 / attack as f32
+            } else {
+                1.0 - (index - attack) as f32 / (count - attack).max(1) as f32
+            };
+            let sample = wave * envelope * beep.volume.clamp(0.0, 1.0) * i16::MAX as f32;
+            samples.push(sample as i16);
+        }
+    }
+    samples
+}
+
+// Samples as a 16-bit mono PCM WAV file
+pub fn wav(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let data_size = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_size as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes()); // size of the format chunk
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes()); // bytes per second
+    bytes.extend_from_slice(&2u16.to_le_bytes()); // bytes per sample
+    bytes.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
@@ -4,15 +4,23 @@
 //! They test the complete game flow and interactions between components.
 
 use create_rust_snake_game::*;
+use ggez::input::gamepad::gilrs::Button;
+use ggez::input::keyboard::KeyCode;
+use std::time::Duration;
 
 #[cfg(test)]
 mod integration_tests {
     use super::*;
 
+    /// A game that saves to memory, so tests never touch the real high score files
+    fn new_game() -> GameState {
+        GameState::with_storage(std::sync::Arc::new(InMemoryStorage::default()))
+    }
+
     /// Test a complete game session from start to collision
     #[test]
     fn test_complete_game_session() {
-        let mut game = GameState::new();
+        let mut game = new_game();
         let mut moves = 0;
         const MAX_MOVES: usize = 100; // Prevent infinite loops in tests
 
@@ -39,7 +47,7 @@ mod integration_tests {
             // Verify all snake segments are valid positions
             for segment in &game.snake {
                 assert!(
-                    segment.is_valid(),
+                    game.config.contains(*segment),
                     "All snake segments should be valid positions"
                 );
             }
@@ -61,8 +69,8 @@ mod integration_tests {
     /// Test game state consistency across multiple food consumptions
     #[test]
     fn test_multiple_food_consumption() {
-        let mut game = GameState::new();
-        let initial_speed = game.game_speed;
+        let mut game = new_game();
+        let initial_speed = game.speed;
         let mut food_eaten = 0;
 
         // Force snake to eat multiple pieces of food
@@ -70,7 +78,7 @@ mod integration_tests {
             // Place food directly in front of snake
             let head = game.snake[0];
             let food_pos = head.move_in_direction(game.direction);
-            game.food = food_pos;
+            game.food = Food::normal(food_pos);
 
             let initial_length = game.snake.len();
             let initial_score = game.score;
@@ -83,10 +91,10 @@ mod integration_tests {
             food_eaten += 1;
 
             // Verify game speed increases with each food eaten
-            assert!(game.game_speed < initial_speed);
+            assert!(game.speed > initial_speed);
 
             // Verify new food is not on snake
-            assert!(!game.snake.contains(&game.food));
+            assert!(!game.snake.contains(&game.food.position));
         }
 
         assert_eq!(food_eaten, 5);
@@ -95,7 +103,7 @@ mod integration_tests {
     /// Test edge case: snake movement at grid boundaries
     #[test]
     fn test_boundary_movement() {
-        let _game = GameState::new();
+        let _game = new_game();
 
         // Move snake to different edges and test movement
         let test_cases = [
@@ -119,15 +127,59 @@ mod integration_tests {
                 Position::new(pos.x, pos.y + 2),
             ];
             let mut test_game = GameState {
-                snake,
-                direction,
-                next_direction: direction,
-                food: Position::new(5, 5), // Place food away from edge
-                score: 0,
+                run: Run {
+                    snake,
+                    direction,
+                    next_direction: direction,
+                    food: Food::normal(Position::new(5, 5)), // Place food away from edge
+                    score: 0,
+                    game_over: false,
+                    speed: Speed::START,
+                    clock: TickClock::default(),
+                    elapsed: 0.0,
+                    weather: Weather::Clear,
+                    slippery_cells: Vec::new(),
+                    ice: false,
+                    grip_cells: Vec::new(),
+                    walls: Vec::new(),
+                    coop: false,
+                    turn_queue: Vec::new(),
+                    handicap: Handicap::default(),
+                    food_policy: FoodPolicy::default(),
+                    food_mix: FoodMix::default(),
+                    hazards: Spawner::rain(),
+                    spawns: Spawns::default(),
+                    grace_left: GracePeriod::Off,
+                    turn_cooldown: 0.0,
+                    turned_at: None,
+                    rng: rand::SeedableRng::seed_from_u64(0),
+                    seed: 0,
+                    config: GameConfig::default(),
+                },
                 high_score: 0,
-                game_over: false,
-                game_speed: 0.2,
-                last_update: 0.0,
+                high_score_checked: true,
+                best_time: 0.0,
+                anim_time: 0.0,
+                screen: Screen::Playing,
+                screen_time: 0.0,
+                menu: MainMenu::default(),
+                settings: Settings::default(),
+                transition: None,
+                reduced_motion: false,
+                theme: Theme::default(),
+                photosensitive: false,
+                food_pulse: 0.0,
+                levels: None,
+                toast: None,
+                packs: Vec::new(),
+                haptics: true,
+                haptic_intensity: DEFAULT_HAPTIC_INTENSITY,
+                notify_best: false,
+                show_hud: true,
+                key_repeat: KeyRepeat::default(),
+                mixer: Mixer::default(),
+                text_cache: TextCache::default(),
+                storage: std::sync::Arc::new(InMemoryStorage::default()),
             };
 
             // Moving in the direction that would go out of bounds should end the game
@@ -142,7 +194,7 @@ mod integration_tests {
     /// Test input handling during game play
     #[test]
     fn test_input_handling_during_gameplay() {
-        let mut game = GameState::new();
+        let mut game = new_game();
 
         // Test that valid direction changes work
         game.handle_input(Direction::Up);
@@ -168,7 +220,7 @@ mod integration_tests {
     /// Test game state after restart
     #[test]
     fn test_game_restart() {
-        let mut game = GameState::new();
+        let mut game = new_game();
 
         // Play for a bit
         for _ in 0..5 {
@@ -179,7 +231,7 @@ mod integration_tests {
         game.game_over = true;
 
         // Restart game
-        let restarted_game = GameState::new();
+        let restarted_game = new_game();
 
         // Verify game is in initial state
         assert_eq!(restarted_game.snake.len(), 3);
@@ -207,35 +259,39 @@ mod integration_tests {
         snake.pop();
 
         // This should not panic and should find a valid food position
-        let food = GameState::generate_food_position(&snake);
-        assert!(food.is_valid());
+        let food = GameState::generate_food_position(&snake).unwrap();
+        assert!(GameConfig::default().contains(food));
         assert!(!snake.contains(&food));
+
+        // and with the last cell gone there's nowhere left
+        snake.push(food);
+        assert_eq!(GameState::generate_food_position(&snake), None);
     }
 
     /// Test game speed progression
     #[test]
     fn test_game_speed_progression() {
-        let mut game = GameState::new();
-        let initial_speed = game.game_speed;
+        let mut game = new_game();
+        let initial_speed = game.speed;
         let mut previous_speed = initial_speed;
 
         // Eat several pieces of food and verify speed increases
         for _ in 0..5 {
             let head = game.snake[0];
             let food_pos = head.move_in_direction(game.direction);
-            game.food = food_pos;
+            game.food = Food::normal(food_pos);
 
-            let speed_before = game.game_speed;
+            let speed_before = game.speed;
             game.move_snake();
 
-            // Speed should decrease (making game faster) or stay at minimum
-            assert!(game.game_speed <= speed_before);
-            assert!(game.game_speed <= previous_speed);
+            // Speed should increase or stay at maximum
+            assert!(game.speed >= speed_before);
+            assert!(game.speed >= previous_speed);
 
-            previous_speed = game.game_speed;
+            previous_speed = game.speed;
 
-            // Speed should never go below minimum
-            assert!(game.game_speed >= 0.1);
+            // Speed should never go above maximum
+            assert!(game.speed <= Speed::MAX);
         }
     }
 
@@ -253,21 +309,302 @@ mod integration_tests {
         ];
 
         let mut game = GameState {
-            snake,
-            direction: Direction::Down, // This will make head collide with body at (5, 6)
-            next_direction: Direction::Down,
-            food: Position::new(0, 0),
-            score: 0,
+            run: Run {
+                snake,
+                direction: Direction::Down, // This will make head collide with body at (5, 6)
+                next_direction: Direction::Down,
+                food: Food::normal(Position::new(0, 0)),
+                score: 0,
+                game_over: false,
+                speed: Speed::START,
+                clock: TickClock::default(),
+                elapsed: 0.0,
+                weather: Weather::Clear,
+                slippery_cells: Vec::new(),
+                ice: false,
+                grip_cells: Vec::new(),
+                walls: Vec::new(),
+                coop: false,
+                turn_queue: Vec::new(),
+                handicap: Handicap::default(),
+                food_policy: FoodPolicy::default(),
+                food_mix: FoodMix::default(),
+                hazards: Spawner::rain(),
+                spawns: Spawns::default(),
+                grace_left: GracePeriod::Off,
+                turn_cooldown: 0.0,
+                turned_at: None,
+                rng: rand::SeedableRng::seed_from_u64(0),
+                seed: 0,
+                config: GameConfig::default(),
+            },
             high_score: 0,
-            game_over: false,
-            game_speed: 0.2,
-            last_update: 0.0,
+            high_score_checked: true,
+            best_time: 0.0,
+            anim_time: 0.0,
+            screen: Screen::Playing,
+            screen_time: 0.0,
+            menu: MainMenu::default(),
+            settings: Settings::default(),
+            transition: None,
+            reduced_motion: false,
+            theme: Theme::default(),
+            photosensitive: false,
+            food_pulse: 0.0,
+            levels: None,
+            toast: None,
+            packs: Vec::new(),
+            haptics: true,
+            haptic_intensity: DEFAULT_HAPTIC_INTENSITY,
+            notify_best: false,
+            show_hud: true,
+            key_repeat: KeyRepeat::default(),
+            mixer: Mixer::default(),
+            text_cache: TextCache::default(),
+            storage: std::sync::Arc::new(InMemoryStorage::default()),
         };
 
         // This move should cause self-collision
         game.move_snake();
         assert!(game.game_over);
     }
+
+    /// Test two players finding each other through the relay by room code
+    #[test]
+    fn test_relay_pairs_players_by_room() {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let relay = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || run_relay(listener));
+
+        // Nobody listening at the direct address, so both fall back to the relay
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let direct = closed.local_addr().unwrap().to_string();
+        drop(closed);
+
+        let route = |direct: Option<&str>, room: &str| PeerRoute {
+            direct: direct.map(str::to_string),
+            relay: Some((relay.clone(), room.to_string())),
+        };
+        let mut host = route(Some(&direct), "ABCD")
+            .connect(DIRECT_CONNECT_TIMEOUT)
+            .unwrap();
+        let mut stranger = route(None, "WXYZ").connect(DIRECT_CONNECT_TIMEOUT).unwrap();
+        let client = route(None, "abcd").connect(DIRECT_CONNECT_TIMEOUT).unwrap();
+
+        writeln!(host, "hello").unwrap();
+        let mut line = String::new();
+        BufReader::new(&client).read_line(&mut line).unwrap();
+        assert_eq!(line, "hello\n");
+
+        // The player in the other room is still waiting on their own
+        stranger
+            .set_read_timeout(Some(std::time::Duration::from_millis(100)))
+            .unwrap();
+        assert!(BufReader::new(&mut stranger).read_line(&mut line).is_err());
+    }
+
+    /// Poll both ends of an online match until `done` says so, or give up after a few seconds
+    fn poll_until(
+        host: &mut OnlineGame,
+        client: &mut OnlineGame,
+        done: impl Fn(&OnlineGame, &OnlineGame) -> bool,
+    ) -> bool {
+        for _ in 0..1000 {
+            host.poll(NET_STEP);
+            client.poll(NET_STEP);
+            if done(host, client) {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        false
+    }
+
+    /// A hosted match found through the relay by its room code plays like a direct one,
+    /// and a player who drops out gets back in through the same room
+    #[test]
+    fn test_online_match_through_relay() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let relay = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || run_relay(listener));
+
+        let mut host = OnlineGame::host(
+            NetMode::Versus,
+            GameConfig::default(),
+            "Ann",
+            0,
+            Some(&relay),
+        )
+        .unwrap();
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let route = PeerRoute {
+            direct: Some(closed.local_addr().unwrap().to_string()),
+            relay: Some((relay, host.room_code().unwrap().to_lowercase())),
+        };
+        drop(closed);
+
+        let both_playing = |host: &OnlineGame, client: &OnlineGame| {
+            host.session.is_playing() && client.session.is_playing()
+        };
+        let mut client =
+            OnlineGame::join(NetMode::Versus, GameConfig::default(), "Bob", route.clone()).unwrap();
+        assert!(poll_until(&mut host, &mut client, both_playing));
+        assert_eq!(client.session.names, host.session.names);
+
+        // The client quits; the host waits at the room again and lets it back in
+        drop(client);
+        let mut client =
+            OnlineGame::join(NetMode::Versus, GameConfig::default(), "Bob", route).unwrap();
+        assert!(poll_until(&mut host, &mut client, |host, client| {
+            both_playing(host, client)
+                && client.session.boards[0].state_hash() == host.session.boards[0].state_hash()
+        }));
+    }
+
+    /// The replay browser's list can be worked from a keyboard or a pad alone, wrapping at
+    /// the ends, and both back out the same way
+    #[test]
+    fn test_menus_navigate_by_keyboard_and_pad() {
+        let replays: Vec<Replay> = (0..3)
+            .map(|_| {
+                let mut game = new_game();
+                let mut replay = Replay::start(&game);
+                for _ in 0..5 {
+                    game.step();
+                    replay.record_tick(&game);
+                }
+                replay
+            })
+            .collect();
+        let mut browser = ReplayBrowser::new(replays, GameConfig::default());
+
+        drive(
+            &mut browser,
+            &[
+                SyntheticInput::Press(KeyCode::Down),
+                SyntheticInput::Pad(Button::DPadDown),
+            ],
+        );
+        assert_eq!(browser.selected, 2);
+        drive(&mut browser, &[SyntheticInput::Press(KeyCode::Down)]);
+        assert_eq!(
+            browser.selected, 0,
+            "Down from the last row wraps to the first"
+        );
+        drive(&mut browser, &[SyntheticInput::Pad(Button::DPadUp)]);
+        assert_eq!(browser.selected, 2, "and Up from the first to the last");
+
+        // A accepts, plays for a while, B backs out to the list again
+        drive(
+            &mut browser,
+            &[
+                SyntheticInput::Pad(Button::South),
+                SyntheticInput::Wait(Duration::from_millis(500)),
+            ],
+        );
+        assert!(browser.player.as_ref().unwrap().tick() > 0);
+        drive(&mut browser, &[SyntheticInput::Pad(Button::East)]);
+        assert!(browser.player.is_none());
+        assert!(!browser.exiting);
+
+        // Enter works like A, and Esc like B
+        drive(&mut browser, &[SyntheticInput::Press(KeyCode::Return)]);
+        assert!(browser.player.is_some());
+        drive(
+            &mut browser,
+            &[
+                SyntheticInput::Press(KeyCode::Escape),
+                SyntheticInput::Press(KeyCode::Escape),
+            ],
+        );
+        assert!(browser.player.is_none());
+        assert!(browser.exiting, "Esc on the list leaves the browser");
+    }
+
+    /// Drive the recorder the way ggez would, through keys, a gamepad and frames of time
+    #[test]
+    fn test_synthetic_input_drives_the_whole_path() {
+        let mut app = ReplayRecorder::new(new_game());
+        app.saved = true; // don't write a replay file if the run ends
+
+        // any key skips the splash screen onto the main menu, Play is picked from there,
+        // and its fade plays out over the next frames
+        app.game.change_screen(Screen::Splash);
+        drive(
+            &mut app,
+            &[
+                SyntheticInput::Press(KeyCode::Space),
+                SyntheticInput::Press(KeyCode::Return),
+                SyntheticInput::Wait(Duration::from_secs(1)),
+            ],
+        );
+        assert_eq!(app.game.screen, Screen::Playing);
+        let moved = app.replay.tick_count();
+        assert!(moved > 0, "the snake should be moving");
+
+        // steering turns on the next tick
+        drive(
+            &mut app,
+            &[
+                SyntheticInput::Press(KeyCode::Up),
+                SyntheticInput::Wait(Duration::from_millis(250)),
+            ],
+        );
+        assert_eq!(app.game.direction, Direction::Up);
+
+        // while paused time goes by without a tick, and the key that unpauses doesn't steer
+        app.paused = true;
+        let head = app.game.snake[0];
+        drive(&mut app, &[SyntheticInput::Wait(Duration::from_secs(2))]);
+        assert_eq!(app.game.snake[0], head);
+        drive(&mut app, &[SyntheticInput::Press(KeyCode::Left)]);
+        assert!(!app.paused);
+        assert_eq!(app.game.next_direction, Direction::Up);
+
+        // the d-pad steers like the arrows
+        drive(
+            &mut app,
+            &[
+                SyntheticInput::Pad(Button::DPadLeft),
+                SyntheticInput::Wait(Duration::from_millis(250)),
+            ],
+        );
+        assert_eq!(app.game.direction, Direction::Left);
+
+        // holding Left on the timeline keeps stepping back, one tick per repeat
+        drive(&mut app, &[SyntheticInput::Press(KeyCode::F9)]);
+        let end = app.replay.tick_count();
+        assert_eq!(
+            app.time_travel.as_ref().map(|travel| travel.tick),
+            Some(end)
+        );
+        drive(
+            &mut app,
+            &[SyntheticInput::Hold(
+                KeyCode::Left,
+                Duration::from_millis(600),
+            )],
+        );
+        let tick = app.time_travel.as_ref().unwrap().tick;
+        assert!(tick + 1 < end, "repeats should step back more than once");
+        drive(&mut app, &[SyntheticInput::Press(KeyCode::F9)]);
+        assert!(app.time_travel.is_none());
+
+        // Start restarts once that's confirmed, and a fresh recording begins with it
+        drive(&mut app, &[SyntheticInput::Pad(Button::Start)]);
+        assert!(app.confirm.is_some(), "a restart mid-run is asked about");
+        drive(
+            &mut app,
+            &[
+                SyntheticInput::Pad(Button::DPadUp),
+                SyntheticInput::Pad(Button::South),
+            ],
+        );
+        assert_eq!(app.game.score, 0);
+        assert_eq!(app.replay.tick_count(), 0);
+    }
 }
 
 /// Helper functions for integration tests
@@ -282,15 +619,59 @@ mod test_helpers {
         direction: Direction,
     ) -> GameState {
         GameState {
-            snake: positions.clone(),
-            direction,
-            next_direction: direction,
-            food: GameState::generate_food_position(&positions),
-            score: 0,
+            run: Run {
+                snake: positions.clone(),
+                direction,
+                next_direction: direction,
+                food: Food::normal(GameState::generate_food_position(&positions).unwrap()),
+                score: 0,
+                game_over: false,
+                speed: Speed::START,
+                clock: TickClock::default(),
+                elapsed: 0.0,
+                weather: Weather::Clear,
+                slippery_cells: Vec::new(),
+                ice: false,
+                grip_cells: Vec::new(),
+                walls: Vec::new(),
+                coop: false,
+                turn_queue: Vec::new(),
+                handicap: Handicap::default(),
+                food_policy: FoodPolicy::default(),
+                food_mix: FoodMix::default(),
+                hazards: Spawner::rain(),
+                spawns: Spawns::default(),
+                grace_left: GracePeriod::Off,
+                turn_cooldown: 0.0,
+                turned_at: None,
+                rng: rand::SeedableRng::seed_from_u64(0),
+                seed: 0,
+                config: GameConfig::default(),
+            },
             high_score: 0,
-            game_over: false,
-            game_speed: 0.2,
-            last_update: 0.0,
+            high_score_checked: true,
+            best_time: 0.0,
+            anim_time: 0.0,
+            screen: Screen::Playing,
+            screen_time: 0.0,
+            menu: MainMenu::default(),
+            settings: Settings::default(),
+            transition: None,
+            reduced_motion: false,
+            theme: Theme::default(),
+            photosensitive: false,
+            food_pulse: 0.0,
+            levels: None,
+            toast: None,
+            packs: Vec::new(),
+            haptics: true,
+            haptic_intensity: DEFAULT_HAPTIC_INTENSITY,
+            notify_best: false,
+            show_hud: true,
+            key_repeat: KeyRepeat::default(),
+            mixer: Mixer::default(),
+            text_cache: TextCache::default(),
+            storage: std::sync::Arc::new(InMemoryStorage::default()),
         }
     }
 
@@ -303,7 +684,7 @@ mod test_helpers {
         // All snake segments should be valid
         for segment in &game.snake {
             assert!(
-                segment.is_valid(),
+                game.config.contains(*segment),
                 "All snake segments should be valid positions"
             );
         }
@@ -321,13 +702,22 @@ mod test_helpers {
         // Score is u32, so it's always >= 0
 
         // Game speed should be within reasonable bounds
-        assert!(game.game_speed > 0.0, "Game speed should be positive");
-        assert!(game.game_speed <= 1.0, "Game speed should not be too slow");
+        assert!(
+            game.speed >= Speed::cells_per_second(1),
+            "Game speed should not be too slow"
+        );
+        assert!(
+            game.speed <= Speed::MAX,
+            "Game speed should not be too fast"
+        );
 
         // Food should be valid and not on snake
-        assert!(game.food.is_valid(), "Food should be in valid position");
         assert!(
-            !game.snake.contains(&game.food),
+            game.config.contains(game.food.position),
+            "Food should be in valid position"
+        );
+        assert!(
+            !game.snake.contains(&game.food.position),
             "Food should not be on snake"
         );
     }
@@ -0,0 +1,162 @@
+//! Soak test for long sessions
+//!
+//! Bots play back to back through the real input and frame handling for hours, checking
+//! that nothing the game keeps grows without bound, the clocks don't drift and nothing
+//! panics. It's ignored by default - run it on purpose, in release:
+//!
+//! ```bash
+//! SNAKE_SOAK_HOURS=3 cargo test --release --test soak -- --ignored --nocapture
+//! ```
+//!
+//! The hours are wall clock time. Frames are fed in at 60 a second of game time, as fast
+//! as the game takes them, so an hour of soak is many hours of play.
+
+use create_rust_snake_game::*;
+use ggez::input::keyboard::{KeyCode, KeyMods};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A player gives up and restarts after this much game time, like people do
+const RUN_LIMIT: Duration = Duration::from_secs(10 * 60);
+
+/// How much the process may grow past where it settled after the first runs
+const RESIDENT_GROWTH_LIMIT_KIB: u64 = 64 * 1024;
+
+/// Runs played before taking the memory baseline, once allocators have warmed up
+const WARMUP_RUNS: u32 = 20;
+
+/// Resident memory from /proc, where there is one
+fn resident_kib() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4)
+}
+
+fn key_for(direction: Direction) -> KeyCode {
+    match direction {
+        Direction::Up => KeyCode::Up,
+        Direction::Down => KeyCode::Down,
+        Direction::Left => KeyCode::Left,
+        Direction::Right => KeyCode::Right,
+    }
+}
+
+/// Whether a frame fed in now counts towards the run's time
+fn is_live(app: &ReplayRecorder) -> bool {
+    let game = &app.game;
+    game.screen == Screen::Playing
+        && game.transition.is_none()
+        && !game.game_over
+        && !game.in_grace()
+}
+
+/// Everything the game holds on to should be bounded by the board, not the session
+fn check_bounds(app: &ReplayRecorder) {
+    let cells = (GRID_WIDTH * GRID_HEIGHT) as usize;
+    let game = &app.game;
+    assert!(game.snake.len() <= cells, "snake outgrew the board");
+    assert!(game.turn_queue.len() <= MAX_QUEUED_TURNS);
+    assert!(game.slippery_cells.len() <= cells);
+    assert!(game.grip_cells.len() <= cells);
+    assert!(app.time_travel.is_none());
+    assert!(app.replay.tick_count() <= app.caps.replay_ticks);
+}
+
+#[test]
+#[ignore]
+fn soak_bots_for_hours() {
+    let hours: f64 = std::env::var("SNAKE_SOAK_HOURS")
+        .ok()
+        .and_then(|hours| hours.parse().ok())
+        .unwrap_or(2.0);
+    let deadline = Instant::now() + Duration::from_secs_f64(hours * 3600.0);
+
+    let mut app = ReplayRecorder::new(GameState::with_storage(
+        Arc::new(InMemoryStorage::default()),
+    ));
+    app.game.screen = Screen::Playing;
+    app.game.skip_transition();
+
+    let (mut runs, mut total_frames, mut live_frames) = (0u32, 0u64, 0u64);
+    let mut baseline = None;
+    while Instant::now() < deadline {
+        app.saved = true; // no replay files for every run
+
+        // steer with key presses, like a player
+        let direction = bot_direction(&app.game);
+        if !app.game.game_over && direction != app.game.next_direction {
+            drive(&mut app, &[SyntheticInput::Press(key_for(direction))]);
+        }
+        if is_live(&app) {
+            live_frames += 1;
+        }
+        drive(&mut app, &[SyntheticInput::Wait(SYNTHETIC_FRAME)]);
+        total_frames += 1;
+
+        let gave_up = live_frames as u128 * SYNTHETIC_FRAME.as_nanos() >= RUN_LIMIT.as_nanos();
+        if !app.game.game_over && !gave_up {
+            continue;
+        }
+
+        // the run's clock is the sum of its frames, to well under a millisecond
+        let live = live_frames as f64 * SYNTHETIC_FRAME.as_secs_f64();
+        assert!(
+            (app.game.elapsed - live).abs() < 1e-3,
+            "run time drifted: {} against {} fed in",
+            app.game.elapsed,
+            live
+        );
+        // and the tick clock moved the snake as often as its speed says, no more, no less
+        let ticks = app.replay.tick_count() as f64;
+        let slowest = live * Speed::START.as_cells_per_second();
+        let fastest = live * Speed::MAX.as_cells_per_second();
+        assert!(
+            ticks >= slowest.floor() - 1.0 && ticks <= fastest.ceil() + 1.0,
+            "{} ticks in {}s of play",
+            ticks,
+            live
+        );
+        check_bounds(&app);
+
+        runs += 1;
+        live_frames = 0;
+        drive(
+            &mut app,
+            &[SyntheticInput::PressWith(KeyCode::R, KeyMods::CTRL)],
+        );
+        if app.confirm.is_some() {
+            // giving up mid-run is asked about - Up to Restart, then Enter
+            drive(
+                &mut app,
+                &[
+                    SyntheticInput::Press(KeyCode::Up),
+                    SyntheticInput::Press(KeyCode::Return),
+                ],
+            );
+        }
+        assert_eq!(app.replay.tick_count(), 0, "the last run's replay was kept");
+        assert_eq!(app.game.elapsed, 0.0);
+
+        if runs == WARMUP_RUNS {
+            baseline = resident_kib();
+        }
+        if let (Some(baseline), Some(now)) = (baseline, resident_kib()) {
+            assert!(
+                now <= baseline + RESIDENT_GROWTH_LIMIT_KIB,
+                "grew from {} KiB to {} KiB over {} runs",
+                baseline,
+                now,
+                runs
+            );
+        }
+    }
+
+    println!(
+        "{} runs, {} frames ({:.1} hours of play), resident {:?} KiB (settled at {:?})",
+        runs,
+        total_frames,
+        total_frames as f64 * SYNTHETIC_FRAME.as_secs_f64() / 3600.0,
+        resident_kib(),
+        baseline
+    );
+}